@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use blast::prelude::{aiff, wav};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+// decode
+//
+// how fast a WAV and an AIFF are parsed: ten seconds of
+// 16-bit stereo at 44.1 kHz each, written to the temp dir
+// once, then read back over and over
+//
+// cargo bench -p blast --bench decode (cargo test runs each
+// once, checking that both files read back as written)
+//
+
+const RATE: u32 = 44_100;
+const CHANNELS: u32 = 2;
+const FRAMES: usize = RATE as usize * 10;
+
+fn samples() -> Vec<i16> {
+    (0..FRAMES * CHANNELS as usize).map(|i| ((i * 7919) % 65_536) as i16).collect()
+}
+
+fn temp(ext: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("blast_bench_{}.{ext}", std::process::id()))
+}
+
+// COMM and SSND, the least a parser takes
+fn aiff_bytes(samples: &[i16]) -> Vec<u8> {
+    // the rate as an 80-bit extended float
+    let shift = (RATE as u64).leading_zeros();
+    let exponent = (16_383 + 63 - shift) as u16;
+
+    let mut comm = Vec::new();
+    comm.extend_from_slice(&(CHANNELS as u16).to_be_bytes());
+    comm.extend_from_slice(&(FRAMES as u32).to_be_bytes());
+    comm.extend_from_slice(&16u16.to_be_bytes());
+    comm.extend_from_slice(&exponent.to_be_bytes());
+    comm.extend_from_slice(&((RATE as u64) << shift).to_be_bytes());
+    let mut ssnd = vec![0u8; 8];
+    for s in samples {
+        ssnd.extend_from_slice(&s.to_be_bytes());
+    }
+
+    let mut body = b"AIFF".to_vec();
+    for (id, chunk) in [(b"COMM", &comm), (b"SSND", &ssnd)] {
+        body.extend_from_slice(id);
+        body.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        body.extend_from_slice(chunk);
+    }
+    let mut bytes = b"FORM".to_vec();
+    bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&body);
+    bytes
+}
+
+fn decode(c: &mut Criterion) {
+    let samples = samples();
+    let wav_path = temp("wav");
+    let aiff_path = temp("aiff");
+    wav::write(wav_path.to_str().unwrap(), RATE, CHANNELS, &samples).unwrap();
+    std::fs::write(&aiff_path, aiff_bytes(&samples)).unwrap();
+    for af in [wav::parse(wav_path.to_str().unwrap()), aiff::parse(aiff_path.to_str().unwrap())] {
+        let af = af.unwrap();
+        assert_eq!((af.sample_rate, af.num_channels, af.frames()), (RATE, CHANNELS, FRAMES));
+        assert!(af.channel(1).eq(samples.iter().skip(1).step_by(2).copied()), "{}", af.format);
+    }
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Bytes((samples.len() * 2) as u64));
+    group.bench_function("wav", |b| b.iter(|| wav::parse(wav_path.to_str().unwrap()).unwrap()));
+    group.bench_function("aiff", |b| b.iter(|| aiff::parse(aiff_path.to_str().unwrap()).unwrap()));
+    group.finish();
+
+    let _ = std::fs::remove_file(wav_path);
+    let _ = std::fs::remove_file(aiff_path);
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use blast::prelude::{AudioFile, CmdProcessor, Conductor, EngineState, sample_rate};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+// render
+//
+// what the engine costs per period: some number of looping
+// stereo Voices rendered a period at a time, at the periods
+// a backend is likely to ask for. per-frame time over the
+// Voice count is roughly the cost of a Voice
+//
+// cargo bench -p blast --bench render (cargo test runs each
+// once, checking the Voices are heard, so it isn't silence
+// being timed)
+//
+
+const RATE: u32 = 44_100;
+const CHANNELS: usize = 2;
+const VOICES: [usize; 3] = [1, 8, 32];
+const PERIODS: [usize; 3] = [64, 256, 1024];
+
+// a Track per Voice (a Voice is named for its Track),
+// a second of noise each
+fn tracks(voices: usize) -> Vec<AudioFile> {
+    (0..voices)
+        .map(|v| {
+            let samples = (0..RATE as usize * CHANNELS).map(|i| ((i * 7919 + v * 104_729) % 65_536) as i16 / 8).collect();
+            let name = format!("t{v}");
+            AudioFile::new(&name, &name, "wav", RATE, CHANNELS as u32, 16, samples)
+        })
+        .collect()
+}
+
+// every Voice loaded, looping and playing
+fn engine(voices: usize) -> Conductor {
+    let tracks = tracks(voices);
+    let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone(), CHANNELS));
+    let mut engine = Conductor::prepare(CHANNELS, tracks.into_iter().map(|af| (af.file_name.clone(), af)).collect::<HashMap<_, _>>());
+    engine.reserve(voices);
+    for v in 0..voices {
+        for line in [format!("load t{v}"), format!("loop t{v}"), format!("start -v t{v}")] {
+            if let Some(cmd) = cmd_processor.parse(line).unwrap() {
+                engine.apply(cmd);
+            }
+        }
+    }
+    engine
+}
+
+fn render(c: &mut Criterion) {
+    sample_rate::set(RATE);
+    let mut group = c.benchmark_group("render");
+    for voices in VOICES {
+        let mut engine = engine(voices);
+        let mut first = vec![0i16; PERIODS[0] * CHANNELS];
+        engine.render(&mut first);
+        assert!(first.iter().any(|s| *s != 0), "{voices} voices rendered silence");
+        for period in PERIODS {
+            let mut out = vec![0i16; period * CHANNELS];
+            group.throughput(Throughput::Elements(period as u64));
+            group.bench_with_input(BenchmarkId::new(format!("{voices} voices"), period), &period, |b, _| {
+                b.iter(|| engine.render(&mut out));
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
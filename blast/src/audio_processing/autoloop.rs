@@ -0,0 +1,100 @@
+use crate::file_parsing::decode_helpers::AudioFile;
+
+// autoloop
+//
+// finds where a sampled phrase really starts and ends (trimming
+// silence), and a loop start whose lead-in sounds most like the
+// end of the phrase, so jumping back from the end is seamless
+//
+// the lead-ins are compared by normalized cross-correlation, first
+// on a decimated signal across the whole phrase, then at full
+// resolution around the best match; cheap enough to run on every
+// Track when the session starts
+//
+
+// ~ -60 dBFS
+const SILENCE: i32 = 32;
+// below this, nothing sounds enough like the tail
+// and the whole phrase loops
+const MIN_CORRELATION: f32 = 0.5;
+// points compared per window on the coarse pass
+const COARSE_POINTS: usize = 64;
+
+// all in frames of the Track; ends are exclusive
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AutoLoop {
+    pub start: usize,
+    pub end: usize,
+    pub loop_start: usize,
+    pub loop_end: usize,
+}
+
+// None for a Track that's silent throughout
+pub fn detect(af: &AudioFile) -> Option<AutoLoop> {
+    let channels = af.num_channels.max(1) as usize;
+    let mono: Vec<f32> = af.samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().map(|s| *s as f32).sum::<f32>() / channels as f32)
+        .collect();
+
+    let loud = |s: &f32| s.abs() as i32 > SILENCE;
+    let start = mono.iter().position(loud)?;
+    let end = mono.iter().rposition(loud)? + 1;
+
+    // the tail compared is 50ms, or a quarter of the phrase if that's shorter
+    let window = ((af.sample_rate / 20) as usize).min((end - start) / 4);
+    let whole = AutoLoop { start, end, loop_start: start, loop_end: end };
+    if window < 16 {
+        return Some(whole);
+    }
+
+    // a lead-in ends at the candidate loop start, which leaves
+    // at least two windows' worth of loop
+    let tail = &mono[end - window..end];
+    let first = start + window;
+    let last = end - 2 * window;
+    if last <= first {
+        return Some(whole);
+    }
+
+    let stride = (window / COARSE_POINTS).max(1);
+    let score = |at: usize, stride: usize| correlate(&mono[at - window..at], tail, stride);
+
+    let mut best = (first, f32::MIN);
+    for at in (first..=last).step_by(stride) {
+        let s = score(at, stride);
+        if s > best.1 {
+            best = (at, s);
+        }
+    }
+
+    let around = best.0.saturating_sub(stride).max(first)..=(best.0 + stride).min(last);
+    best = (best.0, f32::MIN);
+    for at in around {
+        let s = score(at, 1);
+        if s > best.1 {
+            best = (at, s);
+        }
+    }
+
+    if best.1 < MIN_CORRELATION {
+        return Some(whole);
+    }
+
+    Some(AutoLoop { loop_start: best.0, ..whole })
+}
+
+// normalized cross-correlation of every stride-th point
+fn correlate(a: &[f32], b: &[f32], stride: usize) -> f32 {
+    let (mut ab, mut aa, mut bb) = (0f32, 0f32, 0f32);
+    for (x, y) in a.iter().step_by(stride).zip(b.iter().step_by(stride)) {
+        ab += x * y;
+        aa += x * x;
+        bb += y * y;
+    }
+
+    match aa * bb {
+        0.0 => 0.0,
+        energy => ab / energy.sqrt(),
+    }
+}
@@ -0,0 +1,279 @@
+use std::{ffi::CString, ptr};
+
+use alsa_sys::*;
+use libc::{c_int, EAGAIN, ENODEV, EPIPE};
+
+use crate::audio_processing::{
+    backend::{AudioBackend, Cycle, Latency},
+    devices::DEFAULT_DEVICE,
+};
+
+// alsa
+//
+// writes straight into the device's DMA buffer: the PCM is
+// opened for interleaved mmap access, so the area ALSA hands
+// over for a chunk is a plain run of frames the engine renders
+// into, with nothing copied in between
+//
+
+pub struct Alsa {
+    handle: *mut snd_pcm_t,
+    dev: CString,
+    channels: usize,
+    sample_rate: u32,
+    latency: Latency,
+}
+
+impl Alsa {
+    pub fn open(device: Option<&str>, channels: usize, sample_rate: u32, latency: Latency) -> Result<Self, String> {
+        let device = device.unwrap_or(DEFAULT_DEVICE);
+        let dev = CString::new(device).map_err(|_| format!("Invalid device name '{device}'"))?;
+        let (handle, latency) = unsafe { open_pcm(&dev, channels as u32, sample_rate, latency)? };
+        Ok(Self { handle, dev, channels, sample_rate, latency })
+    }
+
+    // the chunk of the DMA buffer mmap_begin gave, as frames
+    unsafe fn chunk<'a>(&self, areas: *const snd_pcm_channel_area_t, offset: snd_pcm_uframes_t, frames: snd_pcm_uframes_t) -> &'a mut [i16] {
+        unsafe {
+            // interleaved: every channel's area starts at the same
+            // address, one sample further in, stepping a frame
+            let first = &*areas;
+            debug_assert_eq!(first.step as usize, self.channels * 16);
+            let base = (first.addr as *mut i16).add(first.first as usize / 16);
+            std::slice::from_raw_parts_mut(base.add(offset as usize * self.channels), frames as usize * self.channels)
+        }
+    }
+}
+
+impl AudioBackend for Alsa {
+    fn name(&self) -> &'static str {
+        "alsa"
+    }
+
+    fn latency(&self) -> Latency {
+        self.latency
+    }
+
+    fn device(&self) -> String {
+        self.dev.to_string_lossy().into_owned()
+    }
+
+    fn cycle(&mut self, render: &mut dyn FnMut(&mut [i16])) -> Cycle {
+        unsafe {
+            let handle = self.handle;
+            // a reopen that failed
+            if handle.is_null() {
+                return Cycle::Lost;
+            }
+            let avail = snd_pcm_avail_update(handle) as i32;
+            if avail == -EPIPE {
+                return if recovered(handle, avail) { Cycle::Underrun } else { Cycle::Lost };
+            }
+            if avail < 0 {
+                return if recovered(handle, avail) { Cycle::Waited } else { Cycle::Lost };
+            }
+            if avail < self.latency.period as i32 {
+                let r = snd_pcm_wait(handle, -1);
+                if r < 0 && !recovered(handle, r) {
+                    return Cycle::Lost;
+                }
+                return Cycle::Waited;
+            }
+
+            // get remaining frames to write
+            let mut remaining = avail as snd_pcm_uframes_t;
+            let mut wrote = 0;
+
+            while remaining > 0 {
+                let mut areas_ptr: *const snd_pcm_channel_area_t = ptr::null();
+                let mut offset: snd_pcm_uframes_t = 0;
+                let mut frames: snd_pcm_uframes_t = remaining;
+
+                // mmap begin
+                let r = snd_pcm_mmap_begin(handle, &mut areas_ptr, &mut offset, &mut frames);
+                if r == -EAGAIN {
+                    break; // hardware not ready
+                }
+                if r < 0 {
+                    if !recovered(handle, r) {
+                        return Cycle::Lost;
+                    }
+                    break;
+                }
+
+                // write to DMA buffer
+                render(self.chunk(areas_ptr, offset, frames));
+
+                let committed = snd_pcm_mmap_commit(handle, offset, frames) as i32;
+                if committed < 0 {
+                    if !recovered(handle, committed) {
+                        return Cycle::Lost;
+                    }
+                    break;
+                }
+
+                remaining -= committed as snd_pcm_uframes_t;
+                wrote += committed as usize;
+            }
+            if snd_pcm_state(handle) != SND_PCM_STATE_RUNNING {
+                snd_pcm_start(handle);
+            }
+            Cycle::Wrote(wrote)
+        }
+    }
+
+    fn reopen(&mut self, latency: Latency) -> Result<(), String> {
+        unsafe {
+            if !self.handle.is_null() {
+                snd_pcm_drop(self.handle);
+                snd_pcm_close(self.handle);
+                self.handle = ptr::null_mut();
+            }
+            let (handle, latency) = open_pcm(&self.dev, self.channels as u32, self.sample_rate, latency)?;
+            self.handle = handle;
+            self.latency = latency;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Alsa {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe {
+                snd_pcm_drop(self.handle);
+                snd_pcm_close(self.handle);
+            }
+        }
+    }
+}
+
+// opens and configures the playback device, asking for
+// latency; returns it with the latency it settled on
+//
+// snd_pcm_recover handles xruns and a suspend the device can
+// resume from; a device that's been unplugged (or won't resume)
+// needs opening again
+unsafe fn recovered(handle: *mut snd_pcm_t, err: c_int) -> bool {
+    err != -ENODEV && unsafe { snd_pcm_recover(handle, err, 1) } >= 0
+}
+
+unsafe fn open_pcm(dev: &CString, num_channels: u32, sample_rate: u32, latency: Latency) -> Result<(*mut snd_pcm_t, Latency), String> {
+    unsafe {
+        let mut handle: *mut snd_pcm_t = ptr::null_mut();
+        alsa_result(
+            snd_pcm_open(
+                &mut handle,
+                dev.as_ptr(),
+                SND_PCM_STREAM_PLAYBACK,
+                0,
+            ),
+            "snd_pcm_open",
+        )?;
+
+        match configure_pcm(handle, num_channels, sample_rate, latency) {
+            Ok(latency) => Ok((handle, latency)),
+            Err(err) => {
+                snd_pcm_close(handle);
+                Err(err)
+            }
+        }
+    }
+}
+
+unsafe fn configure_pcm(handle: *mut snd_pcm_t, num_channels: u32, sample_rate: u32, latency: Latency) -> Result<Latency, String> {
+    unsafe {
+        // config hardware
+        let mut hw: *mut snd_pcm_hw_params_t = ptr::null_mut();
+        snd_pcm_hw_params_malloc(&mut hw);
+        snd_pcm_hw_params_any(handle, hw);
+        let settled = set_hw_params(handle, hw, num_channels, sample_rate, latency);
+        snd_pcm_hw_params_free(hw);
+        let settled = settled?;
+
+        // config software params
+        let mut sw: *mut snd_pcm_sw_params_t = ptr::null_mut();
+        snd_pcm_sw_params_malloc(&mut sw);
+        snd_pcm_sw_params_current(handle, sw);
+        let sw_set = set_sw_params(handle, sw, settled.period as snd_pcm_uframes_t);
+        snd_pcm_sw_params_free(sw);
+        sw_set?;
+
+        // prepare device
+        alsa_result(snd_pcm_prepare(handle), "snd_pcm_prepare")?;
+
+        Ok(settled)
+    }
+}
+
+unsafe fn set_hw_params(handle: *mut snd_pcm_t, hw: *mut snd_pcm_hw_params_t, num_channels: u32, sample_rate: u32, latency: Latency) -> Result<Latency, String> {
+    unsafe {
+        alsa_result(
+            snd_pcm_hw_params_set_access(handle, hw, SND_PCM_ACCESS_MMAP_INTERLEAVED),
+            "set_access",
+        )?;
+        alsa_result(
+            snd_pcm_hw_params_set_format(handle, hw, SND_PCM_FORMAT_S16_LE),
+            "set_format",
+        )?;
+        alsa_result(snd_pcm_hw_params_set_channels(handle, hw, num_channels), "set_ channels")?;
+        alsa_result(snd_pcm_hw_params_set_rate(handle, hw, sample_rate, 0), "set_rate")?;
+
+        // what the device can do at this format and rate
+        let (mut min, mut max): (snd_pcm_uframes_t, snd_pcm_uframes_t) = (0, 0);
+        snd_pcm_hw_params_get_period_size_min(hw, &mut min, ptr::null_mut());
+        snd_pcm_hw_params_get_period_size_max(hw, &mut max, ptr::null_mut());
+        if !(min..=max).contains(&(latency.period as snd_pcm_uframes_t)) {
+            return Err(format!("The device takes periods of {min}-{max} frames, not {}", latency.period));
+        }
+        snd_pcm_hw_params_get_buffer_size_min(hw, &mut min);
+        snd_pcm_hw_params_get_buffer_size_max(hw, &mut max);
+        if !(min..=max).contains(&(latency.buffer as snd_pcm_uframes_t)) {
+            return Err(format!("The device takes buffers of {min}-{max} frames, not {}", latency.buffer));
+        }
+
+        let mut period_size = latency.period as snd_pcm_uframes_t;
+        alsa_result(
+            snd_pcm_hw_params_set_period_size_near(handle, hw, &mut period_size, ptr::null_mut()),
+            "set_period_size",
+        )?;
+
+        // as many periods as asked for, of the period it got
+        let mut buffer_size = period_size * (latency.buffer / latency.period).max(2) as snd_pcm_uframes_t;
+        alsa_result(
+            snd_pcm_hw_params_set_buffer_size_near(handle, hw, &mut buffer_size),
+            "set_buffer_size",
+        )?;
+
+        alsa_result(snd_pcm_hw_params(handle, hw), "snd_pcm_hw_params")?;
+        Ok(Latency { period: period_size as usize, buffer: buffer_size as usize })
+    }
+}
+
+unsafe fn set_sw_params(handle: *mut snd_pcm_t, sw: *mut snd_pcm_sw_params_t, period_size: snd_pcm_uframes_t) -> Result<(), String> {
+    unsafe {
+        let mut boundary: snd_pcm_uframes_t = 0;
+        snd_pcm_sw_params_get_boundary(sw, &mut boundary);
+        snd_pcm_sw_params_set_stop_threshold(handle, sw, boundary);
+        // start immediately upon write
+        alsa_result(snd_pcm_sw_params_set_start_threshold(handle, sw, period_size), "set_start_threshold")?;
+
+        // wake when period is available
+        alsa_result(
+            snd_pcm_sw_params_set_avail_min(handle, sw, period_size),
+            "set_avail_min",
+        )?;
+
+        alsa_result(snd_pcm_sw_params(handle, sw), "snd_pcm_sw_params")
+    }
+}
+
+// check error codes for alsa
+//
+unsafe fn alsa_result(code: c_int, ctx: &str) -> Result<(), String> {
+    if code < 0 {
+        let msg = unsafe { std::ffi::CStr::from_ptr(snd_strerror(code)) };
+        return Err(format!("{ctx}: {}", msg.to_string_lossy()));
+    }
+    Ok(())
+}
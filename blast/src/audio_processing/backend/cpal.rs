@@ -0,0 +1,177 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use cpal::{
+    BufferSize, FromSample, Sample, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig, StreamError,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+
+use crate::audio_processing::{
+    backend::{AudioBackend, Cycle, Latency},
+    ring::Ring,
+    tui,
+};
+
+// cpal
+//
+// cpal calls back from a thread of its own for every buffer, so
+// the audio thread renders ahead into a ring (the buffer long)
+// that the callback copies out of, converting to whatever sample
+// format the device wants (f32 on CoreAudio and WASAPI)
+//
+// the callback never waits: if the ring runs dry it pads with
+// silence and counts an underrun, which the next cycle reports
+//
+
+// what's rendered ahead, how often the callback found too
+// little of it, and whether the device is gone
+struct Ahead {
+    ring: Ring,
+    underruns: AtomicU64,
+    lost: AtomicBool,
+}
+
+impl Ahead {
+    fn new(len: usize) -> Self {
+        Self { ring: Ring::new(len), underruns: AtomicU64::new(0), lost: AtomicBool::new(false) }
+    }
+
+    fn pop_into<T: Sample + FromSample<i16>>(&self, out: &mut [T]) {
+        let n = self.ring.pop_with(out, T::from_sample);
+        out[n..].fill(T::EQUILIBRIUM);
+
+        // before anything's been written it's just starting
+        if n < out.len() && self.ring.started() {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub struct Cpal {
+    stream: Stream,
+    ahead: Arc<Ahead>,
+    device: Option<String>,
+    channels: usize,
+    sample_rate: u32,
+    latency: Latency,
+    scratch: Vec<i16>, // a period, rendered before it goes in the ring
+    underruns: u64,   // as of the last cycle
+}
+
+impl Cpal {
+    pub fn open(device: Option<&str>, channels: usize, sample_rate: u32, latency: Latency) -> Result<Self, String> {
+        let ahead = Arc::new(Ahead::new(latency.buffer * channels));
+        let stream = build(device, channels, sample_rate, latency.period, Arc::clone(&ahead))?;
+        Ok(Self {
+            stream,
+            ahead,
+            device: device.map(str::to_string),
+            channels,
+            sample_rate,
+            latency,
+            scratch: vec![0; latency.period * channels],
+            underruns: 0,
+        })
+    }
+}
+
+impl AudioBackend for Cpal {
+    fn name(&self) -> &'static str {
+        "cpal"
+    }
+
+    fn latency(&self) -> Latency {
+        self.latency
+    }
+
+    fn device(&self) -> String {
+        self.device.as_ref().map_or("default".to_string(), |d| d.clone())
+    }
+
+    fn cycle(&mut self, render: &mut dyn FnMut(&mut [i16])) -> Cycle {
+        if self.ahead.lost.load(Ordering::Relaxed) {
+            return Cycle::Lost;
+        }
+        let underruns = self.ahead.underruns.load(Ordering::Relaxed);
+        if underruns != self.underruns {
+            self.underruns = underruns;
+            return Cycle::Underrun;
+        }
+        if self.ahead.ring.free() < self.scratch.len() {
+            // a quarter of a period
+            thread::sleep(Duration::from_secs_f32(self.latency.period as f32 / self.sample_rate.max(1) as f32 / 4.0));
+            return Cycle::Waited;
+        }
+        render(&mut self.scratch);
+        self.ahead.ring.push(&self.scratch);
+        Cycle::Wrote(self.latency.period)
+    }
+
+    fn reopen(&mut self, latency: Latency) -> Result<(), String> {
+        let ahead = Arc::new(Ahead::new(latency.buffer * self.channels));
+        self.stream = build(self.device.as_deref(), self.channels, self.sample_rate, latency.period, Arc::clone(&ahead))?;
+        self.ahead = ahead;
+        self.latency = latency;
+        self.scratch = vec![0; latency.period * self.channels];
+        self.underruns = 0;
+        Ok(())
+    }
+}
+
+// the device by name (or the host's default), playing what's ahead
+fn build(device: Option<&str>, channels: usize, sample_rate: u32, period: usize, ahead: Arc<Ahead>) -> Result<Stream, String> {
+    let host = cpal::default_host();
+    let dev = match device {
+        None | Some("default") => host.default_output_device().ok_or("No default output device")?,
+        Some(name) => host
+            .output_devices()
+            .map_err(|e| e.to_string())?
+            .find(|d| d.name().is_ok_and(|n| n == name))
+            .ok_or(format!("No output device '{name}'"))?,
+    };
+    let format = dev.default_output_config().map_err(|e| e.to_string())?.sample_format();
+
+    // a fixed buffer of a period if the device allows it
+    let mut config = StreamConfig {
+        channels: channels as u16,
+        sample_rate: SampleRate(sample_rate),
+        buffer_size: BufferSize::Fixed(period as u32),
+    };
+    let stream = match stream(&dev, &config, format, Arc::clone(&ahead)) {
+        Ok(stream) => stream,
+        Err(_) => {
+            config.buffer_size = BufferSize::Default;
+            stream(&dev, &config, format, ahead)?
+        }
+    };
+    stream.play().map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+fn stream(dev: &cpal::Device, config: &StreamConfig, format: SampleFormat, ahead: Arc<Ahead>) -> Result<Stream, String> {
+    match format {
+        SampleFormat::I16 => typed::<i16>(dev, config, ahead),
+        SampleFormat::F32 => typed::<f32>(dev, config, ahead),
+        other => Err(format!("Unsupported sample format {other}")),
+    }
+}
+
+fn typed<T: SizedSample + FromSample<i16>>(dev: &cpal::Device, config: &StreamConfig, ahead: Arc<Ahead>) -> Result<Stream, String> {
+    let errors = Arc::clone(&ahead);
+    dev.build_output_stream(
+        config,
+        move |out: &mut [T], _| ahead.pop_into(out),
+        move |err| match err {
+            StreamError::DeviceNotAvailable => errors.lost.store(true, Ordering::Relaxed),
+            err => tui::err(format!("cpal: {err}")),
+        },
+        None,
+    )
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,357 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_int, c_ulong, c_void},
+    ptr,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::audio_processing::{
+    backend::{AudioBackend, Cycle, Latency, library, symbol},
+    ring::Ring,
+    simd,
+    tui,
+};
+
+// jack
+//
+// a JACK client, "blast", with an output port per channel
+// (out_1, out_2, ...) that other clients can be patched to
+//
+// JACK asks for every period from a thread of its own, but the
+// engine stays on the audio thread, so the audio thread renders
+// a period ahead into a ring that the process callback copies
+// out to the ports, a chunk at a time; that period is the only
+// latency beyond JACK's own
+//
+// the callback never locks or waits: if the ring runs dry it
+// plays silence for the rest and counts an underrun, which the
+// next cycle reports
+//
+// the ports are connected when the client starts: to the
+// physical playback ports by default, or to the ports a
+// --device pattern matches (e.g. "system:playback"), or to
+// nothing with --device none
+//
+// JACK's server sets the sample rate and period; a server at
+// another rate than the files is an error, and the latency asked
+// for is ignored (it's the whole session's); the ring is two of
+// JACK's periods either way
+//
+
+const CLIENT: &CStr = c"blast";
+const AUDIO_TYPE: &CStr = c"32 bit float mono audio";
+
+const JACK_NO_START_SERVER: c_int = 0x01;
+const JACK_PORT_IS_INPUT: c_ulong = 0x1;
+const JACK_PORT_IS_OUTPUT: c_ulong = 0x2;
+const JACK_PORT_IS_PHYSICAL: c_ulong = 0x4;
+
+type Process = extern "C" fn(u32, *mut c_void) -> c_int;
+type Shutdown = extern "C" fn(*mut c_void);
+
+type ClientOpen = unsafe extern "C" fn(*const c_char, c_int, *mut c_int, ...) -> *mut c_void;
+type ClientClose = unsafe extern "C" fn(*mut c_void) -> c_int;
+type GetU32 = unsafe extern "C" fn(*mut c_void) -> u32;
+type PortRegister = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, c_ulong, c_ulong) -> *mut c_void;
+type PortGetBuffer = unsafe extern "C" fn(*mut c_void, u32) -> *mut f32;
+type PortName = unsafe extern "C" fn(*mut c_void) -> *const c_char;
+type SetProcess = unsafe extern "C" fn(*mut c_void, Process, *mut c_void) -> c_int;
+type OnShutdown = unsafe extern "C" fn(*mut c_void, Shutdown, *mut c_void);
+type Activate = unsafe extern "C" fn(*mut c_void) -> c_int;
+type GetPorts = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, c_ulong) -> *mut *const c_char;
+type Connect = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char) -> c_int;
+type Free = unsafe extern "C" fn(*mut c_void);
+
+// the functions used, found once
+struct Lib {
+    client_open: ClientOpen,
+    client_close: ClientClose,
+    sample_rate: GetU32,
+    buffer_size: GetU32,
+    port_register: PortRegister,
+    port_get_buffer: PortGetBuffer,
+    port_name: PortName,
+    set_process: SetProcess,
+    on_shutdown: OnShutdown,
+    activate: Activate,
+    get_ports: GetPorts,
+    connect: Connect,
+    free: Free,
+}
+
+static LIB: OnceLock<Result<Lib, String>> = OnceLock::new();
+
+fn lib() -> Result<&'static Lib, String> {
+    LIB.get_or_init(|| unsafe { load() }).as_ref().map_err(Clone::clone)
+}
+
+unsafe fn load() -> Result<Lib, String> {
+    unsafe {
+        let handle = library(c"libjack.so.0")
+            .map_err(|_| "Couldn't load libjack (is JACK, or pipewire-jack, installed?)".to_string())?;
+        Ok(Lib {
+            client_open: symbol(handle, c"jack_client_open")?,
+            client_close: symbol(handle, c"jack_client_close")?,
+            sample_rate: symbol(handle, c"jack_get_sample_rate")?,
+            buffer_size: symbol(handle, c"jack_get_buffer_size")?,
+            port_register: symbol(handle, c"jack_port_register")?,
+            port_get_buffer: symbol(handle, c"jack_port_get_buffer")?,
+            port_name: symbol(handle, c"jack_port_name")?,
+            set_process: symbol(handle, c"jack_set_process_callback")?,
+            on_shutdown: symbol(handle, c"jack_on_shutdown")?,
+            activate: symbol(handle, c"jack_activate")?,
+            get_ports: symbol(handle, c"jack_get_ports")?,
+            connect: symbol(handle, c"jack_connect")?,
+            free: symbol(handle, c"jack_free")?,
+        })
+    }
+}
+
+// interleaved samples the callback copies out at once, on its stack
+const CHUNK: usize = 1024;
+
+// what's rendered ahead, and how often the callback found too
+// little of it
+pub struct Ahead {
+    pub ring: Ring,
+    pub underruns: AtomicU64,
+}
+
+impl Ahead {
+    pub fn new(len: usize) -> Self {
+        Self { ring: Ring::new(len), underruns: AtomicU64::new(0) }
+    }
+
+    // frames of channels, a chunk of interleaved samples at a time,
+    // to out (with the frame it starts at); silence once the ring's
+    // dry
+    pub fn pop_into(&self, frames: usize, channels: usize, mut out: impl FnMut(usize, &[i16])) {
+        let channels = channels.clamp(1, CHUNK);
+        let mut chunk = [0i16; CHUNK];
+        let mut dry = false;
+        let mut at = 0;
+        while at < frames {
+            let len = (frames - at).min(CHUNK / channels) * channels;
+            let n = match dry {
+                true => 0,
+                false => self.ring.pop_with(&mut chunk[..len], |s| s),
+            };
+            chunk[n..len].fill(0);
+            dry |= n < len;
+            out(at, &chunk[..len]);
+            at += len / channels;
+        }
+
+        // before anything's been written it's just starting
+        if dry && self.ring.started() {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// everything the callback touches
+struct Shared {
+    lib: &'static Lib,
+    ports: Vec<*mut c_void>,
+    channels: usize,
+    ahead: Ahead,
+    gone: AtomicBool, // the server shut the client down
+}
+
+// the ports are only ever used by the callback
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+pub struct Jack {
+    lib: &'static Lib,
+    client: *mut c_void,
+    shared: Arc<Shared>,
+    device: Option<String>, // the ports connected to
+    channels: usize,
+    sample_rate: u32,
+    period: usize,
+    scratch: Vec<i16>, // a period, rendered before it goes in the ring
+    underruns: u64,    // as of the last cycle
+}
+
+impl Jack {
+    pub fn open(device: Option<&str>, channels: usize, sample_rate: u32, _latency: Latency) -> Result<Self, String> {
+        let lib = lib()?;
+        let (client, shared, period) = unsafe { start(lib, device, channels, sample_rate)? };
+        Ok(Self {
+            lib,
+            client,
+            shared,
+            device: device.map(str::to_string),
+            channels,
+            sample_rate,
+            period,
+            scratch: vec![0; period * channels],
+            underruns: 0,
+        })
+    }
+}
+
+// opens the client, registers its ports and activates it,
+// connecting the ports as device says
+unsafe fn start(lib: &'static Lib, device: Option<&str>, channels: usize, sample_rate: u32) -> Result<(*mut c_void, Arc<Shared>, usize), String> {
+    unsafe {
+        let mut status = 0;
+        let client = (lib.client_open)(CLIENT.as_ptr(), JACK_NO_START_SERVER, &mut status);
+        if client.is_null() {
+            return Err(format!("Couldn't connect to the JACK server (status {status:#x})"));
+        }
+        let close = |err: String| {
+            (lib.client_close)(client);
+            Err(err)
+        };
+
+        let rate = (lib.sample_rate)(client);
+        if rate != sample_rate {
+            return close(format!("The JACK server runs at {rate} Hz, but the files are at {sample_rate} Hz"));
+        }
+
+        let mut ports = Vec::with_capacity(channels);
+        for ch in 1..=channels {
+            let name = CString::new(format!("out_{ch}")).unwrap();
+            let port = (lib.port_register)(client, name.as_ptr(), AUDIO_TYPE.as_ptr(), JACK_PORT_IS_OUTPUT, 0);
+            if port.is_null() {
+                return close(format!("Couldn't register JACK port out_{ch}"));
+            }
+            ports.push(port);
+        }
+
+        let period = (lib.buffer_size)(client) as usize;
+        let shared = Arc::new(Shared {
+            lib,
+            ports,
+            channels,
+            ahead: Ahead::new(period * 2 * channels),
+            gone: AtomicBool::new(false),
+        });
+        let arg = Arc::as_ptr(&shared) as *mut c_void;
+        (lib.set_process)(client, process, arg);
+        (lib.on_shutdown)(client, shutdown, arg);
+        if (lib.activate)(client) != 0 {
+            return close("Couldn't activate the JACK client".to_string());
+        }
+
+        if device != Some("none") {
+            connect(lib, client, &shared.ports, device);
+        }
+        Ok((client, shared, period))
+    }
+}
+
+// out_1 to the first port device matches, and so on; any that
+// can't be are left for patching by hand, saying so
+unsafe fn connect(lib: &Lib, client: *mut c_void, ports: &[*mut c_void], device: Option<&str>) {
+    unsafe {
+        let (pattern, flags) = match device {
+            None => (None, JACK_PORT_IS_INPUT | JACK_PORT_IS_PHYSICAL),
+            Some(pattern) => (CString::new(pattern).ok(), JACK_PORT_IS_INPUT),
+        };
+        let found = (lib.get_ports)(client, pattern.as_deref().map_or(ptr::null(), CStr::as_ptr), AUDIO_TYPE.as_ptr(), flags);
+        let mut dests = Vec::new();
+        if !found.is_null() {
+            let mut at = found;
+            while !(*at).is_null() {
+                dests.push(*at);
+                at = at.add(1);
+            }
+        }
+
+        for (i, port) in ports.iter().enumerate() {
+            let src = (lib.port_name)(*port);
+            let ok = dests.get(i).is_some_and(|dst| (lib.connect)(client, src, *dst) == 0);
+            if !ok {
+                tui::warn(format!("jack: {} isn't connected", CStr::from_ptr(src).to_string_lossy()));
+            }
+        }
+        if !found.is_null() {
+            (lib.free)(found.cast());
+        }
+    }
+}
+
+// JACK's thread: copies nframes of what's ahead to the ports
+extern "C" fn process(nframes: u32, arg: *mut c_void) -> c_int {
+    let shared = unsafe { &*(arg as *const Shared) };
+    let lib = shared.lib;
+    let frames = nframes as usize;
+
+    shared.ahead.pop_into(frames, shared.channels, |at, chunk| {
+        for (ch, port) in shared.ports.iter().enumerate() {
+            let out = unsafe { std::slice::from_raw_parts_mut((lib.port_get_buffer)(*port, nframes), frames) };
+            simd::deinterleave(&mut out[at..], chunk, shared.channels, ch);
+        }
+    });
+    0
+}
+
+extern "C" fn shutdown(arg: *mut c_void) {
+    let shared = unsafe { &*(arg as *const Shared) };
+    shared.gone.store(true, Ordering::Relaxed);
+}
+
+impl AudioBackend for Jack {
+    fn name(&self) -> &'static str {
+        "jack"
+    }
+
+    fn latency(&self) -> Latency {
+        Latency { period: self.period, buffer: self.period * 2 }
+    }
+
+    fn device(&self) -> String {
+        self.device.clone().unwrap_or("system:playback".to_string())
+    }
+
+    fn cycle(&mut self, render: &mut dyn FnMut(&mut [i16])) -> Cycle {
+        if self.client.is_null() || self.shared.gone.load(Ordering::Relaxed) {
+            return Cycle::Lost;
+        }
+        let underruns = self.shared.ahead.underruns.load(Ordering::Relaxed);
+        if underruns != self.underruns {
+            self.underruns = underruns;
+            return Cycle::Underrun;
+        }
+        if self.shared.ahead.ring.free() < self.scratch.len() {
+            // a quarter of a period
+            thread::sleep(Duration::from_secs_f32(self.period as f32 / self.sample_rate.max(1) as f32 / 4.0));
+            return Cycle::Waited;
+        }
+        render(&mut self.scratch);
+        self.shared.ahead.ring.push(&self.scratch);
+        Cycle::Wrote(self.period)
+    }
+
+    fn reopen(&mut self, _latency: Latency) -> Result<(), String> {
+        unsafe {
+            if !self.client.is_null() {
+                (self.lib.client_close)(self.client);
+                self.client = ptr::null_mut();
+            }
+            let (client, shared, period) = start(self.lib, self.device.as_deref(), self.channels, self.sample_rate)?;
+            self.client = client;
+            self.shared = shared;
+            self.period = period;
+        }
+        self.scratch = vec![0; self.period * self.channels];
+        self.underruns = 0;
+        Ok(())
+    }
+}
+
+impl Drop for Jack {
+    fn drop(&mut self) {
+        // closing stops the callback before shared can go
+        if !self.client.is_null() {
+            unsafe { (self.lib.client_close)(self.client) };
+        }
+    }
+}
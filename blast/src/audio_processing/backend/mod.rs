@@ -0,0 +1,208 @@
+// backends
+//
+// what the audio thread plays through: the engine renders
+// interleaved 16-bit frames, and a backend gets them to a device
+//
+// the audio thread drives the loop either way; each cycle it
+// waits on the backend for room, then renders straight into
+// whatever buffer the backend hands it (ALSA's DMA buffer
+// itself; for callback APIs like cpal, a ring the callback
+// reads from)
+//
+// ALSA is the default on Linux, falling back to the sound
+// server (pulse) when nothing's named and the default device is
+// taken; jack joins a JACK session as a client of its own;
+// cpal (built with --features cpal) covers macOS'
+// CoreAudio and Windows' WASAPI, and can be picked with
+// --backend cpal anywhere
+//
+
+use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::ffi::{CStr, c_void};
+
+use crate::audio_processing::tui;
+
+#[cfg(target_os = "linux")]
+pub mod alsa;
+#[cfg(feature = "cpal")]
+pub mod cpal;
+#[cfg(target_os = "linux")]
+pub mod jack;
+pub mod null;
+#[cfg(target_os = "linux")]
+pub mod pulse;
+
+// how a cycle went
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cycle {
+    Wrote(usize), // frames
+    Waited,       // nothing had room yet
+    Underrun,     // the device ran dry (and was recovered)
+    Lost,         // the device went away; it's reopened until it's back
+}
+
+// how much the device buffers, in frames: the period is what
+// a cycle renders, and the buffer (a few periods) is how far
+// ahead of the speakers that is, i.e. the latency
+//
+// what's asked for is checked here and then against the device,
+// which settles on what it can do nearest to it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Latency {
+    pub period: usize,
+    pub buffer: usize,
+}
+
+impl Latency {
+    pub const MIN_PERIOD: usize = 16;
+    pub const MAX_PERIOD: usize = 4096;
+    pub const PERIODS: usize = 4; // the buffer, when it isn't given
+
+    pub const DEFAULT: Latency = Latency { period: 128, buffer: 128 * Self::PERIODS };
+
+    pub fn new(period: usize, buffer: Option<usize>) -> Result<Self, String> {
+        if !(Self::MIN_PERIOD..=Self::MAX_PERIOD).contains(&period) {
+            return Err(format!("A period is {}-{} frames, not {period}", Self::MIN_PERIOD, Self::MAX_PERIOD));
+        }
+        let buffer = buffer.unwrap_or(period * Self::PERIODS);
+        if buffer < period * 2 {
+            return Err(format!("A buffer of {buffer} frames can't hold two periods of {period}"));
+        }
+        Ok(Self { period, buffer })
+    }
+
+    // twice as long, buffer and all (up to MAX_PERIOD)
+    pub fn doubled(&self) -> Self {
+        let period = (self.period * 2).min(Self::MAX_PERIOD);
+        Self { period, buffer: self.buffer * period / self.period }
+    }
+
+    // e.g. "period 128, buffer 512 frames (10.7ms at 48000 Hz)"
+    pub fn describe(&self, sample_rate: u32) -> String {
+        let ms = self.buffer as f32 * 1000.0 / sample_rate.max(1) as f32;
+        format!("period {}, buffer {} frames ({ms:.1}ms at {sample_rate} Hz)", self.period, self.buffer)
+    }
+}
+
+// what the device playing settled on, for latency to show
+static SETTLED: Mutex<Option<Latency>> = Mutex::new(None);
+
+pub fn settle(latency: Latency) {
+    *SETTLED.lock().unwrap() = Some(latency);
+}
+
+pub fn settled() -> Option<Latency> {
+    *SETTLED.lock().unwrap()
+}
+
+pub trait AudioBackend {
+    fn name(&self) -> &'static str;
+
+    // as the device settled on them
+    fn latency(&self) -> Latency;
+
+    // frames per period
+    fn period(&self) -> usize {
+        self.latency().period
+    }
+
+    // what's playing, as devices lists it
+    fn device(&self) -> String;
+
+    // waits for a period's room, then has render fill as many
+    // interleaved frames as the device takes now
+    fn cycle(&mut self, render: &mut dyn FnMut(&mut [i16])) -> Cycle;
+
+    // closes the device and opens it again, asking for latency,
+    // e.g. after a hang or to lengthen the period
+    fn reopen(&mut self, latency: Latency) -> Result<(), String>;
+}
+
+const NONE_BUILT_IN: &str = "No audio backend built in (build with --features cpal, or play to --backend null)";
+
+// the ones with devices; null is always there too, but never
+// picked unless it's named
+pub const BACKENDS: &[&str] = &[
+    #[cfg(target_os = "linux")]
+    "alsa",
+    #[cfg(target_os = "linux")]
+    "pulse",
+    #[cfg(target_os = "linux")]
+    "jack",
+    #[cfg(feature = "cpal")]
+    "cpal",
+];
+
+// the first one built in: ALSA on Linux, otherwise cpal
+pub fn default_backend() -> Option<&'static str> {
+    BACKENDS.first().copied()
+}
+
+// opens device (or the backend's default) on the named backend
+pub fn open(
+    backend: &str,
+    device: Option<&str>,
+    channels: usize,
+    sample_rate: u32,
+    latency: Latency,
+) -> Result<Box<dyn AudioBackend>, String> {
+    // unused without any device backend built in
+    let _ = device;
+    match backend {
+        #[cfg(target_os = "linux")]
+        "alsa" => Ok(Box::new(alsa::Alsa::open(device, channels, sample_rate, latency)?)),
+        #[cfg(target_os = "linux")]
+        "jack" => Ok(Box::new(jack::Jack::open(device, channels, sample_rate, latency)?)),
+        #[cfg(target_os = "linux")]
+        "pulse" => Ok(Box::new(pulse::Pulse::open(device, channels, sample_rate, latency)?)),
+        #[cfg(feature = "cpal")]
+        "cpal" => Ok(Box::new(cpal::Cpal::open(device, channels, sample_rate, latency)?)),
+        "null" => Ok(Box::new(null::Null::open(channels, sample_rate, latency))),
+        _ => Err(format!("Unknown backend '{backend}' (built in: {})", [BACKENDS, &["null"]].concat().join(", "))),
+    }
+}
+
+// with neither a backend nor a device named: the first backend
+// that opens its default device, saying why the ones before it
+// didn't
+pub fn open_default(channels: usize, sample_rate: u32, latency: Latency) -> Result<Box<dyn AudioBackend>, String> {
+    let mut failed: Vec<String> = Vec::new();
+    for backend in BACKENDS {
+        match open(backend, None, channels, sample_rate, latency) {
+            Ok(opened) => {
+                if let Some(last) = failed.last() {
+                    tui::warn(format!("{last}; playing through {backend} instead"));
+                }
+                return Ok(opened);
+            }
+            Err(err) => failed.push(format!("{backend}: {err}")),
+        }
+    }
+    Err(match failed[..] {
+        [] => NONE_BUILT_IN.to_string(),
+        _ => failed.join("; "),
+    })
+}
+
+// the sound servers' libraries are loaded when their backend is
+// first opened rather than linked, so nothing more is needed to
+// build, and machines without one just can't pick it
+#[cfg(target_os = "linux")]
+unsafe fn library(name: &CStr) -> Result<*mut c_void, String> {
+    let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW) };
+    if handle.is_null() {
+        return Err(format!("Couldn't load {}", name.to_string_lossy()));
+    }
+    Ok(handle)
+}
+
+// F has to be the function's own (extern "C") type
+#[cfg(target_os = "linux")]
+unsafe fn symbol<F: Copy>(handle: *mut c_void, name: &CStr) -> Result<F, String> {
+    let f = unsafe { libc::dlsym(handle, name.as_ptr()) };
+    if f.is_null() {
+        return Err(format!("No {} in its library", name.to_string_lossy()));
+    }
+    Ok(unsafe { std::mem::transmute_copy::<*mut c_void, F>(&f) })
+}
@@ -0,0 +1,133 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::audio_processing::backend::{AudioBackend, Cycle, Latency};
+
+// null
+//
+// a device that isn't there: it takes a period whenever a real
+// one would have room for it, at speed times the sample rate
+// (or as fast as it's asked, at speed 0), and throws it away;
+// falling behind it underruns like a real one
+//
+// for machines without audio hardware (CI, a server), where
+// --backend null still runs the whole REPL and engine, and for
+// tests, which can keep what was rendered with capture (and
+// pull the plug on it with plug)
+//
+
+pub struct Null {
+    channels: usize,
+    sample_rate: u32,
+    latency: Latency, // the buffer's what it pretends to have
+    speed: f32,
+    started: Instant,
+    written: u64, // frames, since started
+    scratch: Vec<i16>,
+    captured: Option<Arc<Mutex<Vec<i16>>>>,
+    plugged: Arc<AtomicBool>,
+}
+
+impl Null {
+    pub fn open(channels: usize, sample_rate: u32, latency: Latency) -> Self {
+        Self {
+            channels,
+            sample_rate,
+            latency,
+            speed: 1.0,
+            started: Instant::now(),
+            written: 0,
+            scratch: vec![0; latency.period * channels],
+            captured: None,
+            plugged: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    // 1 is real time, 0 never waits
+    #[cfg(test)]
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+
+    // everything rendered from here on, interleaved
+    #[cfg(test)]
+    pub fn capture(&mut self) -> Arc<Mutex<Vec<i16>>> {
+        Arc::clone(self.captured.get_or_insert_default())
+    }
+
+    // set false, the device is gone (as if unplugged) until
+    // it's set again
+    #[cfg(test)]
+    pub fn plug(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.plugged)
+    }
+
+    // frames a device would have played by now
+    fn played(&self) -> u64 {
+        (self.started.elapsed().as_secs_f64() * self.sample_rate as f64 * self.speed as f64) as u64
+    }
+}
+
+impl AudioBackend for Null {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn latency(&self) -> Latency {
+        self.latency
+    }
+
+    fn device(&self) -> String {
+        "null".to_string()
+    }
+
+    fn cycle(&mut self, render: &mut dyn FnMut(&mut [i16])) -> Cycle {
+        if !self.plugged.load(Ordering::Relaxed) {
+            return Cycle::Lost;
+        }
+        // a device starts playing once it's first written to
+        if self.written == 0 {
+            self.started = Instant::now();
+        }
+        let played = if self.speed > 0.0 { self.played() } else { 0 };
+
+        // it'd have run dry; starts over, as a recovered device would
+        if self.written > 0 && played > self.written {
+            self.started = Instant::now();
+            self.written = 0;
+            return Cycle::Underrun;
+        }
+        let period = self.latency.period;
+        if self.speed > 0.0 && self.written - played + period as u64 > self.latency.buffer as u64 {
+            // until a period's played
+            let secs = period as f64 / (self.sample_rate.max(1) as f64 * self.speed as f64);
+            thread::sleep(Duration::from_secs_f64(secs));
+            return Cycle::Waited;
+        }
+
+        render(&mut self.scratch);
+        if let Some(captured) = &self.captured {
+            captured.lock().unwrap().extend_from_slice(&self.scratch);
+        }
+        self.written += period as u64;
+        Cycle::Wrote(period)
+    }
+
+    fn reopen(&mut self, latency: Latency) -> Result<(), String> {
+        if !self.plugged.load(Ordering::Relaxed) {
+            return Err("null: unplugged".to_string());
+        }
+        self.latency = latency;
+        self.scratch = vec![0; latency.period * self.channels];
+        self.started = Instant::now();
+        self.written = 0;
+        Ok(())
+    }
+}
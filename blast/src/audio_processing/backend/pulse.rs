@@ -0,0 +1,214 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_int, c_void},
+    ptr,
+    sync::OnceLock,
+};
+
+use crate::audio_processing::{
+    backend::{AudioBackend, Cycle, Latency, library, symbol},
+    tui,
+};
+
+// pulse
+//
+// plays through the sound server (PulseAudio, or PipeWire's pulse
+// server) with the simple API, for desktops where the server has
+// hw:0,0 and opening it directly fails
+//
+// libpulse-simple is loaded, not linked (see library)
+//
+// writes block until the server has room, which is the wait;
+// the buffer asked for is the server's target length, to keep
+// the latency near ALSA's
+//
+
+const PA_STREAM_PLAYBACK: c_int = 1;
+#[cfg(target_endian = "little")]
+const PA_SAMPLE_S16NE: c_int = 3;
+#[cfg(target_endian = "big")]
+const PA_SAMPLE_S16NE: c_int = 4;
+
+#[repr(C)]
+struct SampleSpec {
+    format: c_int,
+    rate: u32,
+    channels: u8,
+}
+
+#[repr(C)]
+struct BufferAttr {
+    maxlength: u32,
+    tlength: u32,
+    prebuf: u32,
+    minreq: u32,
+    fragsize: u32,
+}
+
+type New = unsafe extern "C" fn(
+    *const c_char,
+    *const c_char,
+    c_int,
+    *const c_char,
+    *const c_char,
+    *const SampleSpec,
+    *const c_void,
+    *const BufferAttr,
+    *mut c_int,
+) -> *mut c_void;
+type Write = unsafe extern "C" fn(*mut c_void, *const c_void, usize, *mut c_int) -> c_int;
+type Free = unsafe extern "C" fn(*mut c_void);
+type StrError = unsafe extern "C" fn(c_int) -> *const c_char;
+
+// the functions used, found once
+struct Lib {
+    new: New,
+    write: Write,
+    free: Free,
+    strerror: StrError,
+}
+
+static LIB: OnceLock<Result<Lib, String>> = OnceLock::new();
+
+fn lib() -> Result<&'static Lib, String> {
+    LIB.get_or_init(|| unsafe { load() }).as_ref().map_err(Clone::clone)
+}
+
+unsafe fn load() -> Result<Lib, String> {
+    unsafe {
+        let handle = library(c"libpulse-simple.so.0")
+            .map_err(|_| "Couldn't load libpulse-simple (is PulseAudio or pipewire-pulse installed?)".to_string())?;
+        // pa_strerror is libpulse's, which libpulse-simple pulls in
+        Ok(Lib {
+            new: symbol(handle, c"pa_simple_new")?,
+            write: symbol(handle, c"pa_simple_write")?,
+            free: symbol(handle, c"pa_simple_free")?,
+            strerror: symbol(handle, c"pa_strerror")?,
+        })
+    }
+}
+
+impl Lib {
+    fn error(&self, err: c_int) -> String {
+        unsafe { CStr::from_ptr((self.strerror)(err)).to_string_lossy().into_owned() }
+    }
+}
+
+pub struct Pulse {
+    lib: &'static Lib,
+    simple: *mut c_void,
+    device: Option<CString>, // a sink; None for the server's default
+    channels: usize,
+    sample_rate: u32,
+    latency: Latency,
+    scratch: Vec<i16>, // a period, rendered before it's written
+}
+
+impl Pulse {
+    pub fn open(device: Option<&str>, channels: usize, sample_rate: u32, latency: Latency) -> Result<Self, String> {
+        let device = match device {
+            None | Some("default") => None,
+            Some(name) => Some(CString::new(name).map_err(|_| format!("Invalid sink name '{name}'"))?),
+        };
+        let lib = lib()?;
+        let simple = unsafe { connect(lib, device.as_deref(), channels, sample_rate, latency)? };
+        Ok(Self {
+            lib,
+            simple,
+            device,
+            channels,
+            sample_rate,
+            latency,
+            scratch: vec![0; latency.period * channels],
+        })
+    }
+}
+
+unsafe fn connect(lib: &Lib, device: Option<&CStr>, channels: usize, sample_rate: u32, latency: Latency) -> Result<*mut c_void, String> {
+    let spec = SampleSpec {
+        format: PA_SAMPLE_S16NE,
+        rate: sample_rate,
+        channels: channels as u8,
+    };
+    let bytes = |frames: usize| (frames * channels * size_of::<i16>()) as u32;
+    // u32::MAX leaves it to the server
+    let attr = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: bytes(latency.buffer),
+        prebuf: u32::MAX,
+        minreq: bytes(latency.period),
+        fragsize: u32::MAX,
+    };
+    let mut err = 0;
+    let simple = unsafe {
+        (lib.new)(
+            ptr::null(),
+            c"blast".as_ptr(),
+            PA_STREAM_PLAYBACK,
+            device.map_or(ptr::null(), CStr::as_ptr),
+            c"playback".as_ptr(),
+            &spec,
+            ptr::null(),
+            &attr,
+            &mut err,
+        )
+    };
+    if simple.is_null() {
+        return Err(format!("Couldn't connect to the sound server: {}", lib.error(err)));
+    }
+    Ok(simple)
+}
+
+impl AudioBackend for Pulse {
+    fn name(&self) -> &'static str {
+        "pulse"
+    }
+
+    fn latency(&self) -> Latency {
+        self.latency
+    }
+
+    fn device(&self) -> String {
+        self.device.as_ref().map_or("default".to_string(), |d| d.to_string_lossy().into_owned())
+    }
+
+    fn cycle(&mut self, render: &mut dyn FnMut(&mut [i16])) -> Cycle {
+        // the server plays silence if it runs dry, so the only
+        // failure is losing it, e.g. when it restarts
+        if self.simple.is_null() {
+            return Cycle::Lost;
+        }
+
+        render(&mut self.scratch);
+        let mut err = 0;
+        let r = unsafe {
+            (self.lib.write)(self.simple, self.scratch.as_ptr().cast(), size_of_val(&self.scratch[..]), &mut err)
+        };
+        if r < 0 {
+            tui::err(format!("pulse: {}", self.lib.error(err)));
+            return Cycle::Lost;
+        }
+        Cycle::Wrote(self.latency.period)
+    }
+
+    fn reopen(&mut self, latency: Latency) -> Result<(), String> {
+        unsafe {
+            // null if the last try failed
+            if !self.simple.is_null() {
+                (self.lib.free)(self.simple);
+                self.simple = ptr::null_mut();
+            }
+            self.simple = connect(self.lib, self.device.as_deref(), self.channels, self.sample_rate, latency)?;
+        }
+        self.latency = latency;
+        self.scratch = vec![0; latency.period * self.channels];
+        Ok(())
+    }
+}
+
+impl Drop for Pulse {
+    fn drop(&mut self) {
+        if !self.simple.is_null() {
+            unsafe { (self.lib.free)(self.simple) };
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::audio_processing::commands::EngineState;
+
+// bindings
+//
+// single keys that act without typing a line: Ctrl-K switches
+// the REPL between typing and keys, and while in keys every
+// bound key runs its action at once, on the selected Voice
+//
+// an action is one of these, or else a REPL line in which $v
+// is the selected Voice:
+//   startstop          starts the Voice, or stops it if it's playing
+//   mute, solo         switches it on, or off if it's on
+//   select prev|next   selects the Voice before or after it
+//
+// a key is a character, or space, up, down, left or right; the
+// defaults below can be changed, and others bound, under [keys]
+// in blast.conf:
+//   t = trig $v -b
+//
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Char(char),
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Key {
+    pub fn parse(name: &str) -> Option<Self> {
+        let key = match name {
+            "space" => Key::Space,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_graphic() => Key::Char(c),
+                    _ => return None,
+                }
+            }
+        };
+        Some(key)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    StartStop,
+    Mute,
+    Solo,
+    Select(isize), // by how many Voices
+    Line(String),
+}
+
+impl Action {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let action = match text.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => return Err("empty action".to_string()),
+            ["startstop"] => Action::StartStop,
+            ["mute"] => Action::Mute,
+            ["solo"] => Action::Solo,
+            ["select", "prev"] => Action::Select(-1),
+            ["select", "next"] => Action::Select(1),
+            ["select", ..] => return Err(format!("'{text}': select takes prev or next")),
+            _ => Action::Line(text.trim().to_string()),
+        };
+        Ok(action)
+    }
+
+    // the line this comes to for voice, as things are now;
+    // None for a selection, which isn't a line
+    pub fn line(&self, voice: Option<&str>, state: &EngineState) -> Result<Option<String>, String> {
+        if let Action::Select(_) = self {
+            return Ok(None);
+        }
+        if let Action::Line(line) = self
+            && !line.contains("$v") {
+            return Ok(Some(line.clone()));
+        }
+
+        let name = voice.ok_or("No Voice selected (up/down or a line naming one selects it)")?;
+        let repr = state.voice(name).ok_or(format!("Couldn't find Voice '{name}'"))?;
+        let switch = |on: bool| if on { "off" } else { "on" };
+        let line = match self {
+            Action::StartStop if repr.is_playing() => format!("stop -v {name}"),
+            Action::StartStop => format!("start -v {name}"),
+            Action::Mute => format!("mute -v {name} {}", switch(repr.is_muted())),
+            Action::Solo => format!("solo -v {name} {}", switch(repr.is_soloed())),
+            Action::Line(line) => line.replace("$v", name),
+            Action::Select(_) => unreachable!(),
+        };
+        Ok(Some(line))
+    }
+}
+
+pub struct Bindings {
+    keys: Vec<(Key, Action)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            keys: vec![
+                (Key::Space, Action::StartStop),
+                (Key::Char('m'), Action::Mute),
+                (Key::Char('s'), Action::Solo),
+                (Key::Up, Action::Select(-1)),
+                (Key::Down, Action::Select(1)),
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    // replaces whatever key was bound to
+    pub fn bind(&mut self, key: &str, action: &str) -> Result<(), String> {
+        let key = Key::parse(key).ok_or(format!("'{key}' isn't a key (a character, space or an arrow)"))?;
+        let action = Action::parse(action)?;
+        self.keys.retain(|(k, _)| *k != key);
+        self.keys.push((key, action));
+        Ok(())
+    }
+
+    pub fn get(&self, key: Key) -> Option<&Action> {
+        self.keys.iter().find(|(k, _)| *k == key).map(|(_, action)| action)
+    }
+}
+
+// the Voice by of names away from selected, wrapping around;
+// the first (or last) when none is selected
+pub fn step<'a>(names: &[&'a str], selected: Option<&str>, by: isize) -> Option<&'a str> {
+    if names.is_empty() {
+        return None;
+    }
+    let len = names.len() as isize;
+    let at = match selected.and_then(|s| names.iter().position(|n| *n == s)) {
+        Some(at) => at as isize + by,
+        None if by < 0 => len - 1,
+        None => 0,
+    };
+    Some(names[at.rem_euclid(len) as usize])
+}
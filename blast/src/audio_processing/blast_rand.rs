@@ -25,7 +25,7 @@ impl X128P {
 
     #[inline]
     fn rotl(x: u64, k: u32) -> u64 {
-        (x << k) | (x >> (64 - k))
+        x.rotate_left(k)
     }
 
     pub fn next_u64(&mut self) -> u64 {
@@ -49,7 +49,7 @@ impl X128P {
 
     pub fn next_i64_range(&mut self, lower: i64, upper: i64) -> i64 {
         let r = self.next_u64();
-        let mut range: u64 = match upper > lower {
+        let range: u64 = match upper > lower {
             true => (upper - lower).try_into().unwrap(),
             false => (lower - upper).try_into().unwrap(),
         };
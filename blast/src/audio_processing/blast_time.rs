@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicU32, AtomicU64, AtomicBool, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 // sample_rate
 // (mainly used by TempoState and TempoGroup)
@@ -17,6 +17,7 @@ pub mod sample_rate {
     }
 }
 
+#[allow(clippy::module_inception)]
 pub mod blast_time {
     use super::*;
 
@@ -33,6 +34,72 @@ pub mod blast_time {
         pub fn current() -> u64 {
             SAMPLE_COUNTER.load(Ordering::Relaxed)
         }
+
+        // the meter the clock is read in: a beat length in samples
+        // (stored as f32 bits; 0 until set, read as 120 BPM) and
+        // how many beats make a bar
+        static BEAT_LEN: AtomicU32 = AtomicU32::new(0);
+        static BEATS_PER_BAR: AtomicU32 = AtomicU32::new(4);
+
+        pub fn set_meter(beat_len: f32, beats_per_bar: u32) {
+            BEAT_LEN.store(beat_len.to_bits(), Ordering::Relaxed);
+            BEATS_PER_BAR.store(beats_per_bar.max(1), Ordering::Relaxed);
+        }
+
+        // (beat length in samples, beats per bar)
+        pub fn meter() -> (f32, u32) {
+            let beat_len = match f32::from_bits(BEAT_LEN.load(Ordering::Relaxed)) {
+                len if len > 0.0 => len,
+                _ => convert_interval(&TempoUnit::Bpm, 120.0),
+            };
+            (beat_len, BEATS_PER_BAR.load(Ordering::Relaxed))
+        }
+
+        // where the clock is, in bars
+        pub fn position() -> Position {
+            position_at(current())
+        }
+
+        pub fn position_at(sample: u64) -> Position {
+            let (beat_len, beats_per_bar) = meter();
+            Position::at(sample as f64 / beat_len as f64, beats_per_bar)
+        }
+    }
+
+    // a point in bars: the bar and the beat in it count from 0,
+    // phase is how far into the beat (0..1)
+    //
+    // shown the way a sequencer counts, from 1: bar 3, beat 2 is 3.2
+    //
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Position {
+        pub bar: u64,
+        pub beat: u32,
+        pub phase: f32,
+    }
+
+    impl Position {
+        pub fn at(beats: f64, beats_per_bar: u32) -> Self {
+            let beats = beats.max(0.0);
+            let per_bar = beats_per_bar.max(1) as u64;
+            let whole = beats as u64;
+            Self {
+                bar: whole / per_bar,
+                beat: (whole % per_bar) as u32,
+                phase: (beats - whole as f64) as f32,
+            }
+        }
+
+        // the first beat of a bar
+        pub fn is_downbeat(&self) -> bool {
+            self.beat == 0
+        }
+    }
+
+    impl std::fmt::Display for Position {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}.{}", self.bar + 1, self.beat + 1)
+        }
     }
     // tempo control
     // 
@@ -40,7 +107,7 @@ pub mod blast_time {
     // a TempoSolo to function at its own pace,
     // a TempoContext to synchronize with other separate Processes,
     // or a TempoGroup to default to the TempoState of the Group to which it
-    //// would be assigned
+    //   would be assigned
     //
     // a TempoContext is created by a special command (tempocon/tc);
     // a TempoSolo is created along with the Process that instantiates it;
@@ -61,9 +128,22 @@ pub mod blast_time {
         pub interval: f32,
         pub active: bool,
         pub current: u32,
+        pub frac: f64, // what current dropped, while a ramp rescales it
+        pub beats_per_bar: u32,
+        pub ramp: Option<TempoRamp>,
+    }
+
+    // a glide of the interval, linear in samples per beat
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct TempoRamp {
+        pub from: f32,
+        pub to: f32,
+        pub len: f32, // in samples
+        pub elapsed: f32,
     }
 
     #[derive(Clone, Copy, Debug, PartialEq)]
+    #[allow(clippy::upper_case_acronyms)]
     pub enum TempoMode {
         Process,
         Voice,
@@ -93,6 +173,9 @@ pub mod blast_time {
                 interval: sample_rate::get() as f32,
                 active: false,
                 current: 0,
+                frac: 0.0,
+                beats_per_bar: 4,
+                ramp: None,
             }
         }
 
@@ -103,21 +186,80 @@ pub mod blast_time {
             self.interval = interval_in_samps;
         }
 
-        pub fn clone(&self) -> TempoState {
-            let mut clone = TempoState::new(None);
-            clone.init(self.mode.clone(), self.unit.clone(), self.interval);
+        // copies mode, unit and interval (already in samples)
+        // into a new, inactive TempoState
+        pub fn clone_params(&self) -> TempoState {
+            let mut clone = TempoState::new(Some(self.mode));
+            clone.unit = self.unit;
+            clone.interval = self.interval;
+            clone.beats_per_bar = self.beats_per_bar;
             clone
         }
 
+        // picks up where from (the one this was rebuilt
+        // from) is: running or not, and how far along
+        pub fn carry_on(&mut self, from: &TempoState) {
+            self.active = from.active;
+            self.current = from.current;
+            self.frac = from.frac;
+        }
+
         // store current as AtomicU32
         pub fn update(&mut self, delta_in_samples: f64) {
             self.current += delta_in_samples as u32;
+
+            if let Some(ramp) = &mut self.ramp {
+                ramp.elapsed += delta_in_samples as f32;
+                let t = (ramp.elapsed / ramp.len).min(1.0);
+                let interval = ramp.from + (ramp.to - ramp.from) * t;
+                if t >= 1.0 {
+                    self.ramp = None;
+                }
+                self.rescale(interval);
+            }
+        }
+
+        // glides the interval (in samples) to to over len samples
+        pub fn ramp_to(&mut self, to: f32, len: f32) {
+            match len > 0.0 {
+                true => self.ramp = Some(TempoRamp { from: self.interval, to, len, elapsed: 0.0 }),
+                false => {
+                    self.ramp = None;
+                    self.rescale(to);
+                }
+            }
+        }
+
+        // changes the interval without moving the beat:
+        // current is scaled along, so current() stays put
+        fn rescale(&mut self, interval: f32) {
+            let exact = (self.current as f64 + self.frac) * (interval / self.interval) as f64;
+            self.current = exact as u32;
+            self.frac = exact - self.current as f64;
+            self.interval = interval;
         }
 
         // return current as f32
         pub fn current(&self) -> f32 {
-            let step_f = self.current as f32 / self.interval;
-            step_f
+            self.current as f32 / self.interval
+        }
+
+        // where the count is, in bars of beats_per_bar
+        pub fn position(&self) -> Position {
+            let beats = (self.current as f64 + self.frac) / self.interval as f64;
+            Position::at(beats, self.beats_per_bar)
+        }
+
+        pub fn bar(&self) -> u64 {
+            self.position().bar
+        }
+
+        pub fn beat(&self) -> u32 {
+            self.position().beat
+        }
+
+        pub fn phase(&self) -> f32 {
+            self.position().phase
         }
 
         pub fn start(&mut self) {
@@ -140,6 +282,7 @@ pub mod blast_time {
 
         pub fn reset(&mut self) {
             self.current = 0;
+            self.frac = 0.0;
         }
 
         pub fn set_interval(&mut self, new_interval: f32) {
@@ -148,15 +291,13 @@ pub mod blast_time {
         }
     }
 
-    fn convert_interval(unit: &TempoUnit, interval: f32) -> f32 {
+    pub fn convert_interval(unit: &TempoUnit, interval: f32) -> f32 {
         let frac = match unit {
             TempoUnit::Samples => return interval,
             TempoUnit::Millis => interval / 1000.0,
             TempoUnit::Bpm => 60.0 / interval,
         };
-        
-        let interval_in_samples = sample_rate::get() as f32 * frac;
-       
-        interval_in_samples
+
+        sample_rate::get() as f32 * frac
     }
 }
@@ -0,0 +1,71 @@
+// capture
+//
+// records when a Voice is started over a window of bars and
+// turns the presses into a Seq, quantized to a grid
+//
+// the Seq's tempo is the grid itself (a whole number of samples
+// per step), since Seq only fires on exact step values
+//
+pub struct Capture {
+    pub voice: String,
+    start: u64, // global clock, in samples
+    len: u64,
+    grid: u64, // samples per step
+    period: usize, // steps in the window
+    hits: Vec<u64>,
+}
+
+impl Capture {
+    // beat is in samples; division is steps per beat
+    pub fn new(voice: String, start: u64, bars: usize, beats_per_bar: usize, beat: f32, division: usize) -> Self {
+        let grid = (beat / division as f32).round().max(1.0) as u64;
+        let period = bars * beats_per_bar * division;
+
+        Self {
+            voice,
+            start,
+            len: grid * period as u64,
+            grid,
+            period,
+            hits: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, now: u64) {
+        if now >= self.start && !self.done(now) {
+            self.hits.push(now - self.start);
+        }
+    }
+
+    pub fn done(&self, now: u64) -> bool {
+        now >= self.start + self.len
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.len()
+    }
+
+    // hits snap to the nearest step; one that rounds up
+    // past the end wraps around to the first
+    pub fn steps(&self) -> Vec<usize> {
+        let mut steps: Vec<usize> = self.hits
+            .iter()
+            .map(|t| ((*t as f64 / self.grid as f64).round() as usize) % self.period)
+            .collect();
+        steps.sort_unstable();
+        steps.dedup();
+        steps
+    }
+
+    // the seq command that plays the capture back
+    pub fn seq_line(&self) -> String {
+        let steps: Vec<String> = self.steps().iter().map(usize::to_string).collect();
+        format!(
+            "seq {} -t s:{} -p {} -s {}",
+            self.voice,
+            self.grid,
+            self.period,
+            steps.join(","),
+        )
+    }
+}
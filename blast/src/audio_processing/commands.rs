@@ -1,44 +1,158 @@
-use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::collections::{HashMap, hash_map::Entry};
+use std::cell::{RefCell, UnsafeCell};
+use std::rc::Rc;
+use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}};
+use std::collections::{BTreeMap, HashMap, VecDeque, hash_map::Entry};
+use std::iter::Peekable;
 
-use crate::file_parsing::decode_helpers::AudioFile;
+use crate::file_parsing::{bundle, smf, stems, decode_helpers::{self, AudioFile}};
 use crate::audio_processing::{
-    blast_time::blast_time::{TempoUnit, TempoMode},
+    blast_time::{
+        sample_rate,
+        blast_time::{TempoState, TempoUnit, TempoMode, clock, convert_interval},
+    },
     blast_rand::{X128P, fast_seed},
+    capture::Capture,
+    autoloop::{self, AutoLoop},
+    loudness::{self, Loudness},
+    routing::Routing,
+    engine::{Conductor, Looping, Slots, Steal, Tail, Voice, BLOCK, MAX_BUSES, MAX_EFFECTS, MAX_PROCESSES, MAX_TEMPO_CONTEXTS, MAX_TRACKS, VOICE_POOL},
+    processes::{NO_STEP, Wave, LfoTarget, Swing},
+    notes::{self, KeyMap, KeyMode},
+    midi::{self, ClockSync, NoteInput},
+    scripting::Scripts,
+    meters::{Level, VoiceLevels},
+    wave::Envelope,
+    record::{Recording, Tap},
+    backend::{self, Latency},
+    offline,
+    stats::Stats,
+    devices,
+    schema::{self, ArgType},
+    tui,
+    effects::{DelayTime, Effect, MAX_DELAY_SECS, delay_buffer, Verb},
 };
 
+// the command thread's Commands, on their way to the audio thread:
+// a ring of slots with one producer and one consumer. a slot is
+// the producer's from when the consumer's tail moves past it
+// (stored Release, loaded Acquire) until the producer publishes
+// it by moving head past it (the same), so a Command is whole by
+// the time it's taken, and its slot empty by the time it's reused
+//
+// each side works under a claim, so a second thread pushing (or
+// popping) at the same time is refused rather than racing; one
+// after another is fine, the claim handing over what the last
+// one left
 pub struct CmdQueue {
-    buf: Vec<UnsafeCell<Option<Command>>>,
-    cap: usize,
-    head: AtomicUsize,
-    tail: AtomicUsize,
+    buf: Box<[UnsafeCell<Option<Command>>]>,
+    head: Padded<AtomicUsize>, // the next slot to fill; only the producer moves it
+    tail: Padded<AtomicUsize>, // the next slot to take; only the consumer moves it
+    pushing: Padded<AtomicBool>,
+    popping: Padded<AtomicBool>,
 }
 
-unsafe impl Send for CmdQueue {}
+// a slot is only ever touched by the side that holds it (see
+// above), and Commands may move between threads
 unsafe impl Sync for CmdQueue {}
+const _: fn() = || {
+    fn send<T: Send>() {}
+    send::<Command>();
+};
 
-impl CmdQueue {
-    pub fn new(cap: usize) -> Self {
-        let mut buf = Vec::<UnsafeCell<Option<Command>>>::with_capacity(cap);
+// what a Command carries for the engine that isn't Send by itself:
+// a TempoState in a new Rc, an empty Vec of Voices, Processes or
+// Effects to fill, or a whole session, made here so the audio
+// thread doesn't allocate them. nothing on this side ever holds a
+// second Rc to what's in one (they're only made by the constructors
+// below), so moving it whole is moving the only handle there is
+pub struct Unshared<T>(T);
 
-        for _ in {0..cap} {
-            buf.push(UnsafeCell::new(None));
-        }
+unsafe impl<T> Send for Unshared<T> {}
+
+impl<T> Unshared<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl Unshared<Rc<RefCell<TempoState>>> {
+    pub fn tempo(state: TempoState) -> Self {
+        Self(Rc::new(RefCell::new(state)))
+    }
+}
+
+impl<T> Unshared<Vec<T>> {
+    pub fn with_capacity(cap: usize) -> Self {
+        Self(Vec::with_capacity(cap))
+    }
+}
+
+impl Unshared<Box<Conductor>> {
+    pub fn session(conductor: Box<Conductor>) -> Self {
+        Self(conductor)
+    }
+}
+
+impl Unshared<Slots> {
+    pub fn slots() -> Self {
+        Self(Slots {
+            processes: Vec::with_capacity(MAX_PROCESSES),
+            effects: Vec::with_capacity(MAX_EFFECTS),
+            tempi: Vec::with_capacity(MAX_PROCESSES),
+        })
+    }
+}
+
+// the TempoState a TempoRepr stands for, made up front; None
+// when the engine shares one it has (see TempoRepr::made)
+pub type MadeTempo = Option<Unshared<Rc<RefCell<TempoState>>>>;
+
+// a cache line of its own, so the two sides don't keep taking it
+// from each other
+#[repr(align(64))]
+struct Padded<T>(T);
+
+impl<T> std::ops::Deref for Padded<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+// held while pushing or popping; let go on drop
+struct Claim<'a>(&'a AtomicBool);
+
+impl<'a> Claim<'a> {
+    fn take(flag: &'a AtomicBool) -> Option<Self> {
+        (!flag.swap(true, Ordering::Acquire)).then_some(Self(flag))
+    }
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
 
+impl CmdQueue {
+    // holds cap - 1 Commands
+    pub fn new(cap: usize) -> Self {
         Self {
-            buf,
-            cap,
-            head: AtomicUsize::new(0),
-            tail: AtomicUsize::new(0),
+            buf: (0..cap.max(2)).map(|_| UnsafeCell::new(None)).collect(),
+            head: Padded(AtomicUsize::new(0)),
+            tail: Padded(AtomicUsize::new(0)),
+            pushing: Padded(AtomicBool::new(false)),
+            popping: Padded(AtomicBool::new(false)),
         }
     }
 
     pub fn try_push(&self, cmd: Command) -> Result<(), String> {
+        let _claim = Claim::take(&self.pushing).ok_or("Command queue pushed from two threads at once")?;
         let head = self.head.load(Ordering::Relaxed);
-        let tail = self.tail.load(Ordering::Acquire);
+        let next = (head + 1) % self.buf.len();
 
-        if (head + 1) % self.cap == tail {
+        // the consumer's done with the slot once tail's past it
+        if next == self.tail.load(Ordering::Acquire) {
             return Err(String::from("Command queue full"));
         }
 
@@ -46,15 +160,25 @@ impl CmdQueue {
             *self.buf[head].get() = Some(cmd);
         }
 
-        self.head.store((head + 1) % self.cap, Ordering::Release);
+        // hands the slot over, filled
+        self.head.store(next, Ordering::Release);
         Ok(())
     }
 
+    // only the one pushing can fill it, so a false here holds
+    // until its next push
+    pub fn is_full(&self) -> bool {
+        let head = self.head.load(Ordering::Acquire);
+        (head + 1) % self.buf.len() == self.tail.load(Ordering::Acquire)
+    }
+
+    // None when it's empty, or something else is popping
     pub fn try_pop(&self) -> Option<Command> {
+        let _claim = Claim::take(&self.popping)?;
         let tail = self.tail.load(Ordering::Relaxed);
-        let head = self.head.load(Ordering::Acquire);
 
-        if head == tail {
+        // the producer's filled the slot once head's past it
+        if tail == self.head.load(Ordering::Acquire) {
             return None;
         }
 
@@ -62,136 +186,736 @@ impl CmdQueue {
             (*self.buf[tail].get()).take()
         };
 
-        self.tail.store((tail + 1) % self.cap, Ordering::Release);
-        
+        // hands the slot back, empty
+        self.tail.store((tail + 1) % self.buf.len(), Ordering::Release);
         cmd
     }
 }
 
-use blast_macros::var_args;
+use blast_macros::{var_args, CommandArgs};
 
 macro_rules! commands {
     ( $( $var:ident ),* $(,)? ) => {
+        // outside the crate, Commands come from a CmdProcessor (or
+        // the few constructors below), and there are more to come
+        #[non_exhaustive]
         pub enum Command {
             $(
                 $var(var_args!($var)), // formats as {CmdType}Args
             )*
         }
 
-        unsafe impl Send for Command {}
-        unsafe impl Sync for Command {}
     }
 }
 
 commands! {
     // Voices
     Load,
+    Clone,
     Start,
+    Trig,
     Pause,
     Resume,
     Stop,
     Unload,
     Velocity,
+    Stretch,
+    Pitch,
+    Keymap,
+    Note,
+    Gain,
+    Pan,
+    Offset,
+    Trim,
+    Seek,
+    Route,
+    Out,
+    Loop,
+    Poly,
+    Mute,
+    Solo,
+    Fade,
     // Groups
     Group,
+    ImportStems,
+    Bounce,
     Tc,
+    Tempo,
+    Meter,
     // Processes
     Seq,
+    Follow,
+    Lfo,
+    Proc,
+    // Effects
+    Delay,
+    Verb,
+    Bus,
+    Send,
     // Program
+    Rename,
+    Session,
+    Audio,
+    Midi,
     Quit,
 }
 
+// the Commands that need nothing of the engine's to build
+impl Command {
+    pub fn fade(ms: f32) -> Self {
+        Command::Fade(FadeArgs { ms })
+    }
+
+    pub fn quit() -> Self {
+        Command::Quit(QuitArgs {})
+    }
+}
+
 // specialized args for commands
 // (need definition because they're declared in the commands! macro)
 
 pub struct LoadArgs {
-    pub track_idx: usize,
-    pub tempo_repr: TempoRepr,
+    pub(crate) track_idx: usize,
+    pub(crate) tempo_repr: TempoRepr,
+    pub(crate) autoloop: Option<AutoLoop>, // trim and loop points to use
+    pub(crate) gain: f32, // what the Voice starts at (load --normalize)
+    pub(crate) meter: Arc<Level>, // where the engine publishes its levels
+    // allocated here so the audio thread doesn't have to: a
+    // BLOCK of output frames for the Voice's bus, and its
+    // default routing
+    pub(crate) bus: Vec<f32>,
+    pub(crate) routing: Routing,
+    // and its TempoState, and room for its Processes and Effects
+    pub(crate) tempo_state: MadeTempo,
+    pub(crate) slots: Unshared<Slots>,
+}
+
+// a new Voice on src's Track, made on the audio thread from src's
+// settings; what it needs is allocated here, as for LoadArgs
+pub struct CloneArgs {
+    pub(crate) src: Idx,
+    pub(crate) tempo_repr: TempoRepr,
+    pub(crate) meter: Arc<Level>,
+    pub(crate) bus: Vec<f32>,
+    pub(crate) routing: Routing,
+    pub(crate) tempo_state: MadeTempo,
+    pub(crate) slots: Unshared<Slots>,
 }
 
+// transport commands act on every target at once;
+// "all" is every TempoContext, Group and ungrouped Voice
 pub struct StartArgs {
-    pub idx: Idx,
+    pub(crate) idxs: Vec<Idx>,
+}
+
+// on_beat waits for the next beat of the Voice's tempo
+pub struct TrigArgs {
+    pub(crate) idx: Idx,
+    pub(crate) on_beat: bool,
 }
 
 pub struct PauseArgs {
-    pub idx: Idx,
+    pub(crate) idxs: Vec<Idx>,
 }
 
 pub struct ResumeArgs {
-    pub idx: Idx,
+    pub(crate) idxs: Vec<Idx>,
 }
 
 pub struct StopArgs {
-    pub idx: Idx,
+    pub(crate) idxs: Vec<Idx>,
 }
 
 pub struct UnloadArgs {
-    pub idx: usize,
+    pub(crate) idx: usize,
 }
 
 pub struct VelocityArgs {
-    pub idx: usize,
-    pub val: f32,
+    pub(crate) idx: usize,
+    pub(crate) val: f32,
+}
+
+// 1 is the Track's own length
+pub struct StretchArgs {
+    pub(crate) idx: Idx,
+    pub(crate) factor: f32,
+}
+
+// 0 is the Track's own pitch
+pub struct PitchArgs {
+    pub(crate) idx: Idx,
+    pub(crate) semitones: f32,
+}
+
+// keymap is None to play the sample as it is again
+pub struct KeymapArgs {
+    pub(crate) idx: Idx,
+    pub(crate) keymap: Option<KeyMap>,
+}
+
+// a MIDI note number, within the Voice's keymap
+pub struct NoteArgs {
+    pub(crate) idx: Idx,
+    pub(crate) note: u8,
+}
+
+// idx is None for the master bus
+pub struct GainArgs {
+    pub(crate) idx: Option<Idx>,
+    pub(crate) val: f32,
+}
+
+// -1..1
+pub struct PanArgs {
+    pub(crate) idx: Idx,
+    pub(crate) val: f32,
+}
+
+// in samples; negative is early
+pub struct OffsetArgs {
+    pub(crate) idx: Idx,
+    pub(crate) samples: f32,
+}
+
+// frames of the Track, start..end; None plays all of it
+pub struct TrimArgs {
+    pub(crate) idx: Idx,
+    pub(crate) region: Option<(usize, usize)>,
+}
+
+// a frame of the Track
+pub struct SeekArgs {
+    pub(crate) idx: Idx,
+    pub(crate) position: usize,
+}
+
+pub struct RouteArgs {
+    pub(crate) idx: Idx,
+    pub(crate) routing: Routing,
+}
+
+// direct skips the master's Effects and gain, straight to the device
+pub struct OutArgs {
+    pub(crate) idx: Idx,
+    pub(crate) routing: Routing,
+    pub(crate) direct: bool,
+}
+
+pub struct LoopArgs {
+    pub(crate) idx: Idx,
+    pub(crate) looping: Looping,
+}
+
+// max is 1 to turn polyphony off
+pub struct PolyArgs {
+    pub(crate) idx: Idx,
+    pub(crate) max: usize,
+    pub(crate) steal: Steal,
+    pub(crate) pool: Vec<Tail>,
+}
+
+pub struct MuteArgs {
+    pub(crate) idx: Idx,
+    pub(crate) on: bool,
+}
+
+pub struct SoloArgs {
+    pub(crate) idx: Idx,
+    pub(crate) on: bool,
+}
+
+// applies to every fade from then on
+pub struct FadeArgs {
+    pub(crate) ms: f32,
 }
 
 pub struct GroupArgs {
-    pub tempo: TempoRepr,
-    pub vs_fs_ps: Vec<(usize, bool, Vec<usize>)>, 
+    pub(crate) tempo: TempoRepr,
+    pub(crate) vs_fs_ps: Vec<(usize, bool, Vec<usize>)>, 
+    pub(crate) bus: Vec<f32>, // a BLOCK of output frames, for the Group's mix
+    pub(crate) levels: Vec<f32>, // and its level at each of them
+    pub(crate) tempo_state: MadeTempo,
+    pub(crate) voices: Unshared<Vec<Voice>>, // room for the Voices moved into it
+    pub(crate) slots: Unshared<Slots>,
     // store the ids Voice
     // with whether or not its TempoState refers to the Group's
     // and with the ids of all of the Processes 
     // whose TempoStates refer to the Group's
 }
 
+// the Tracks the engine hasn't got yet, then the loads
+// and the group that make Voices of them
+pub struct ImportStemsArgs {
+    pub(crate) tracks: Vec<AudioFile>,
+    pub(crate) commands: Vec<Command>,
+}
+
+// the Track a bounce rendered; None on a replay of the
+// history, when the engine already has it
+pub struct BounceArgs {
+    pub(crate) track: Option<AudioFile>,
+}
+
 pub struct TcArgs {
-    pub tempo: TempoRepr,
+    pub(crate) tempo: TempoRepr,
+    pub(crate) tempo_state: MadeTempo,
+}
+
+// idx is a TempoContext's (Idx::Tempo) or a Group's
+pub struct TempoArgs {
+    pub(crate) idx: Idx,
+    pub(crate) action: TempoAction,
+}
+
+pub enum TempoAction {
+    Ramp { to: f32, len: RampLen }, // to is an interval in samples
+    Set { unit: TempoUnit, interval: f32 },
+}
+
+// idx is None for the global clock, which also takes a beat length
+pub struct MeterArgs {
+    pub(crate) idx: Option<Idx>,
+    pub(crate) beats_per_bar: u32,
+    pub(crate) beat_len: Option<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RampLen {
+    Beats(f32), // of the tempo as it glides
+    Samples(f32),
 }
 
 pub struct SeqArgs {
-    pub idx: Idx,
-    pub tempo: TempoRepr,
-    pub period: usize,
-    pub steps: Vec<f32>,
-    pub chance: Vec<f32>,
-    pub jit: Vec<f32>,
-    pub rng: X128P,
+    pub(crate) idx: Idx,
+    pub(crate) tempo: TempoRepr,
+    pub(crate) period: usize,
+    pub(crate) steps: Vec<f32>,
+    pub(crate) chance: Vec<f32>,
+    pub(crate) jit: Vec<f32>,
+    pub(crate) levels: Vec<f32>, // how hard each step hits
+    pub(crate) swing: Swing,
+    pub(crate) rng: X128P,
+    pub(crate) seed: Option<u64>, // given with --seed
+    pub(crate) phase: Arc<AtomicUsize>,
+    pub(crate) tempo_state: MadeTempo,
+}
+
+// tempo is None for the Voice's own
+pub struct LfoArgs {
+    pub(crate) idx: Idx,
+    pub(crate) tempo: Option<TempoRepr>,
+    pub(crate) wave: Wave,
+    pub(crate) target: LfoTarget,
+    pub(crate) depth: f32,
+    pub(crate) rng: X128P,
+    pub(crate) tempo_state: MadeTempo,
+}
+
+pub struct FollowArgs {
+    pub(crate) idx: Idx, // the Voice listened to
+    pub(crate) tempo: usize, // the TempoContext steered
+    pub(crate) min_bpm: f32,
+    pub(crate) max_bpm: f32,
+}
+
+// proc is the Process's place in its Voice's Vec<Process>
+pub struct ProcArgs {
+    pub(crate) idx: Idx,
+    pub(crate) proc: usize,
+    pub(crate) action: ProcAction,
+}
+
+pub enum ProcAction {
+    Remove,
+    Set(ProcParam),
+}
+
+pub enum ProcParam {
+    // Seq
+    Period(usize),
+    // chance, jitter and levels follow along, resized here
+    Steps { steps: Vec<f32>, chance: Vec<f32>, jit: Vec<f32>, levels: Vec<f32> },
+    Levels(Vec<f32>),
+    Swing(Swing),
+    Seed(u64),
+    // Lfo
+    Depth(f32),
+    Wave(Wave),
+}
+
+// names only live in the EngineState; the engine goes by index
+pub struct RenameArgs {}
+
+pub struct SessionArgs {
+    pub(crate) action: SessionAction,
+}
+
+// a session comes built (see EngineState::session)
+pub enum SessionAction {
+    Load(Unshared<Box<Conductor>>), // the next session
+    Crossfade(usize), // over this many frames
+    Drop,
+    Replace(Unshared<Box<Conductor>>), // fades over to it
+    // takes over from this one where it is: each (old, new) of
+    // its Voices, Groups and TempoContexts picks up where the
+    // old one was (see EngineState::carry)
+    Restore(Unshared<Box<Conductor>>, Vec<(Idx, Idx)>),
+    // the lines an undo takes back, as the Commands of their
+    // inverses (see HistoryEntry), applied in order
+    Undo(Vec<Command>),
+}
+
+// time is None to take the delay off;
+// buf is sized for MAX_DELAY_SECS
+pub struct DelayArgs {
+    pub(crate) idx: Idx,
+    pub(crate) time: Option<DelayTime>,
+    pub(crate) feedback: f32,
+    pub(crate) mix: f32,
+    pub(crate) buf: Vec<f32>,
+}
+
+// idx is None for the master bus; verb is None to take it off
+pub struct VerbArgs {
+    pub(crate) idx: Option<Idx>,
+    pub(crate) verb: Option<Verb>,
+}
+
+// a new aux bus, with a BLOCK of output frames to mix on, room
+// for its Effects, and a TempoState its delays never read (a bus
+// has no tempo, so they're timed in samples)
+pub struct BusArgs {
+    pub(crate) buf: Vec<f32>,
+    pub(crate) effects: Unshared<Vec<Effect>>,
+    pub(crate) tempo_state: Unshared<Rc<RefCell<TempoState>>>,
+}
+
+// how much of a Voice goes to an aux bus; 0 sends none
+pub struct SendArgs {
+    pub(crate) idx: Idx,
+    pub(crate) bus: usize,
+    pub(crate) amount: f32,
+}
+
+// the flags Commands take, parsed by their derived parse_flags;
+// each field's parser is a CmdProcessor method (see flag_unit & co.),
+// and what's described here is what help and the schema show
+
+#[derive(CommandArgs)]
+pub(crate) struct LoadFlags {
+    // resolved once the Track is known
+    #[arg(short = "t", parser = flag_text, kind = ArgType::Tempo,
+          help = "s|m|b:<interval>, or c:<tempocontext>/g:<group> to share one")]
+    tempo: Option<String>,
+    #[arg(long = "autoloop", switch, help = "trim silence and loop where the tail fits best")]
+    autoloop: bool,
+    #[arg(long = "normalize", value = "lufs", parser = flag_float, optional = loudness::TARGET_LUFS,
+          kind = ArgType::Float, help = "start at the gain that brings it to <lufs> (-18), peaks under -1 dBFS")]
+    normalize: Option<f32>,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct GroupFlags {
+    #[arg(short = "v", long = "voices", value = "voice,...", parser = flag_names, default = Vec::new(),
+          kind = ArgType::List(&ArgType::Voice), help = "the Voices to move")]
+    voices: Vec<String>,
+    #[arg(short = "t", parser = flag_text, kind = ArgType::Tempo,
+          help = "s|m|b:<interval>, or c:<tempocontext> (default b:240)")]
+    tempo: Option<String>,
+}
+
+// TODO: -j/--jitter, per step as -c is: _ for none, e|l for up
+// to e before and l after the beat, e1-e2|l1-l2 for ranges,
+// and n:, a: and n1-n2: as for chance
+#[derive(CommandArgs)]
+pub(crate) struct SeqFlags {
+    #[arg(short = "n", parser = flag_text, help = "what to call it (default seq, seq2, ...)")]
+    name: Option<String>,
+    // resolved against the Voice once it's known
+    #[arg(short = "t", parser = flag_text, kind = ArgType::Tempo,
+          help = "s|m|b:<interval>, v for the Voice's, or c:/g:<name>")]
+    tempo: Option<String>,
+    #[arg(short = "p", parser = flag_count, default = 4, kind = ArgType::Int, help = "steps per cycle (default 4)")]
+    period: usize,
+    #[arg(short = "s", parser = flag_steps, default = Vec::new(), kind = ArgType::List(&ArgType::Float),
+          help = "the steps that trigger")]
+    steps: Vec<f32>,
+    // both made sense of against the steps
+    #[arg(short = "c", value = "chances", parser = flag_text, kind = ArgType::List(&ArgType::Text),
+          help = "percent per step; _, n:val, a:val or n1-n2:val")]
+    chance: Option<String>,
+    #[arg(short = "v", parser = flag_text, kind = ArgType::List(&ArgType::Gain),
+          help = "how hard each step hits, linear or dB; _ for full")]
+    levels: Option<String>,
+    #[arg(short = "w", parser = flag_swing, default = Swing::STRAIGHT,
+          help = "percent of a half unit every other one is late by; percent:n for 1/n units")]
+    swing: Swing,
+    #[arg(long = "seed", value = "n", parser = flag_seed, kind = ArgType::Int,
+          help = "makes chance decisions the same every run (and every start)")]
+    seed: Option<u64>,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct SeqExportFlags {
+    #[arg(short = "n", value = "seq", parser = flag_text, help = "the Seq to write, if the Voice has more than one")]
+    name: Option<String>,
+    #[arg(short = "r", value = "cycles", parser = flag_count, default = 1, kind = ArgType::Int,
+          help = "cycles to write, chance rolled anew each (default 1)")]
+    repeat: usize,
+    #[arg(short = "k", value = "note", parser = flag_note, default = 36,
+          help = "the note steps are written on (default C2)")]
+    key: u8,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct CaptureFlags {
+    #[arg(short = "t", parser = beat_len, default = clock::meter().0, kind = ArgType::Tempo,
+          help = "the beat, as s|m|b:<interval> or c:<tempocontext> (default: the clock's)")]
+    tempo: f32,
+    #[arg(short = "b", parser = flag_count, default = clock::meter().1 as usize, kind = ArgType::Int,
+          help = "beats per bar (default: the clock's)")]
+    beats: usize,
+    #[arg(short = "q", long = "quantize", value = "steps", parser = flag_count, default = 4, kind = ArgType::Int,
+          help = "steps per beat (default 4)")]
+    division: usize,
 }
 
-// doesn't need any members, just triggers raise(SIGTERM)
+#[derive(CommandArgs)]
+pub(crate) struct SeekFlags {
+    #[arg(short = "q", long = "quantize", switch, help = "lands on the nearest beat of the Voice's tempo")]
+    quantize: bool,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct TrigFlags {
+    #[arg(short = "b", switch, help = "waits for the next beat of the Voice's tempo")]
+    beat: bool,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct StemFlags {
+    #[arg(short = "t", parser = flag_text, kind = ArgType::Tempo,
+          help = "the Group's tempo, s|m|b:<interval> or c:<tempocontext>")]
+    tempo: Option<String>,
+    #[arg(long = "resample", switch, help = "converts stems to the rate most of them are at")]
+    resample: bool,
+    #[arg(long = "pad", switch, help = "fills shorter stems out with silence")]
+    pad: bool,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct LfoFlags {
+    #[arg(short = "w", parser = flag_wave, default = Wave::Sine,
+          kind = ArgType::Choice(&["sine", "tri", "square", "random"]), help = "the shape (default sine)")]
+    wave: Wave,
+    // resolved against the Voice once it's known
+    #[arg(short = "t", parser = flag_text, kind = ArgType::Tempo,
+          help = "s|m|b:<interval>, c:/g:<name>, or v for the Voice's (default)")]
+    tempo: Option<String>,
+    #[arg(short = "d", parser = flag_depth, default = 0.5, kind = ArgType::Float, help = "how far it swings (default 0.5)")]
+    depth: f32,
+    #[arg(short = "n", parser = flag_text, help = "what to call it (default lfo, lfo2, ...)")]
+    name: Option<String>,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct FollowFlags {
+    #[arg(short = "n", parser = flag_text, help = "what to call it (default follow, follow2, ...)")]
+    name: Option<String>,
+    #[arg(long = "bpm", value = "min-max", parser = flag_bpm_range, default = (60.0, 180.0),
+          help = "range beats are folded into (default 60-180)")]
+    bpm: (f32, f32),
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct DelayFlags {
+    #[arg(short = "f", parser = flag_unit, default = 0.35, kind = ArgType::Float,
+          help = "0..1, how much of each repeat comes back (default 0.35)")]
+    feedback: f32,
+    #[arg(short = "m", parser = flag_unit, default = 0.3, kind = ArgType::Float, help = "0..1, dry to wet (default 0.3)")]
+    mix: f32,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct VerbFlags {
+    #[arg(short = "r", parser = flag_unit, default = 0.5, kind = ArgType::Float,
+          help = "0..1, how long it rings (default 0.5)")]
+    room: f32,
+    #[arg(short = "d", parser = flag_unit, default = 0.5, kind = ArgType::Float,
+          help = "0..1, how quickly the highs die (default 0.5)")]
+    damping: f32,
+    #[arg(short = "m", parser = flag_unit, default = 0.3, kind = ArgType::Float, help = "0..1, dry to wet (default 0.3)")]
+    mix: f32,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct CrossfadeFlags {
+    #[arg(short = "t", parser = beat_len, default = convert_interval(&TempoUnit::Bpm, 120.0), kind = ArgType::Tempo,
+          help = "the beat <n>b counts, as s|m|b:<interval> or c:<tempocontext> (default b:120)")]
+    tempo: f32,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct BundleFlags {
+    #[arg(long = "hashes", switch, help = "only list names and hashes, without the audio")]
+    hashes: bool,
+    #[arg(short = "f", long = "file", parser = flag_text, repeats, kind = ArgType::Path, help = "another file to pack")]
+    files: Vec<String>,
+}
+
+#[derive(CommandArgs)]
+pub(crate) struct WaveFlags {
+    #[arg(short = "r", parser = flag_rows, default = 4, kind = ArgType::Int, help = "how tall (1-16, default 4)")]
+    rows: usize,
+}
+
+// handled by the runtime around the Conductor, not by it
+pub struct AudioArgs {
+    pub(crate) action: AudioAction,
+}
+
+pub enum AudioAction {
+    Restart, // close and reopen the device
+    Latency(Latency), // the same, asking for this
+    // tee the master into this, or stop; the file's already
+    // open by the time the runtime has this
+    Record(Option<Arc<Tap>>),
+}
+
+pub struct MidiArgs {
+    pub(crate) action: MidiAction,
+}
+
+pub enum MidiAction {
+    // the listener is already running by the time the engine has this
+    Clock { device: String, tc: usize, sync: Arc<ClockSync> },
+    ClockOff,
+}
+
+// doesn't need any members; play() stops when it gets one
 pub struct QuitArgs {}
 
 // structs to represent engine/object state
 
 // use for terse, ambiguous Commands like Start;
 // prefer Reprs when more info is required
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Idx {
     Tempo(usize),
     Voice(usize),
     Process(usize),
     Group(usize),
+    GroupVoice(usize, usize), // (Group, Voice within the Group)
+    Bus(usize), // an aux bus
     // don't need one for Track because TrackRepr is already
     // just an index, and there are few Commands that operate on
     // Tracks, so it'll never be ambiguous
 }
 
+// what a Voice, Group or TempoContext was last told to do
+// (a one-shot that has played out still reads as playing)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transport {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+impl Transport {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transport::Stopped => "stopped",
+            Transport::Playing => "playing",
+            Transport::Paused => "paused",
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct TrackRepr {
     idx: usize,
+    path: String, // where the Track was loaded from
+    channels: usize,
+    frames: usize,
+    rate: u32,
+    channel_mask: Option<u32>, // its speakers, for its default routing
+    autoloop: Option<AutoLoop>, // proposed for load --autoloop
+    loudness: Loudness, // for load --normalize
+    envelope: Arc<Envelope>, // its peaks, for wave
+    root_note: Option<u8>, // the file's own, for keymap
+    info: Arc<str>, // what info shows
+    audio: AudioFile, // its samples shared, for bounce to render with
 }
 
 impl TrackRepr {
-    fn new(idx: usize) -> Self {
-        Self { idx }
+    fn new(idx: usize, af: &AudioFile) -> Self {
+        let loudness = loudness::measure(af);
+        Self {
+            idx,
+            path: af.path.clone(),
+            channels: af.num_channels as usize,
+            frames: af.frames(),
+            rate: af.sample_rate,
+            channel_mask: af.channel_mask,
+            // a mapped Track is a recording too long to be a loop
+            autoloop: match af.samples.is_mapped() {
+                true => None,
+                false => autoloop::detect(af),
+            },
+            loudness,
+            envelope: Arc::new(Envelope::of(af)),
+            root_note: af.root_note,
+            info: describe(af, &loudness).into(),
+            audio: af.clone(),
+        }
+    }
+}
+
+// what the file is and what it decoded to, side by side, so a
+// bad decode shows (a length that's off, bytes that don't add up)
+fn describe(af: &AudioFile, loudness: &Loudness) -> String {
+    let encoding = match af.meta.encoding.is_empty() {
+        true => String::new(),
+        false => format!(" {}", af.meta.encoding),
+    };
+    let mut lines = vec![
+        format!(
+            "{}: {}{encoding}, {}-bit, {}ch at {} Hz",
+            af.file_name, af.format, af.bits_per_sample, af.num_channels, af.sample_rate,
+        ),
+        format!(
+            "{} frames ({:.3}s): {} samples decoded from {} bytes",
+            af.frames(), af.duration().as_secs_f64(), af.samples.len(), af.meta.data_bytes,
+        ),
+    ];
+    lines.push(format!("loudness: {:.1} LUFS, peak {:.1} dBFS", loudness.lufs, loudness.peak));
+    if let Some((start, end)) = af.loop_points {
+        lines.push(format!("loop: frames {start}-{end}"));
+    }
+    if let Some(note) = af.root_note {
+        lines.push(format!("root: {}", notes::name(note)));
+    }
+    if let Some(mask) = af.channel_mask {
+        lines.push(format!("speakers: {}", decode_helpers::speakers(mask).join(" ")));
     }
+    for (name, text) in &af.meta.tags {
+        lines.push(format!("{name}: {text}"));
+    }
+    lines.push(format!("from {}", af.path));
+    lines.join("\n")
 }
 
 // owned bool determines whether a TempoState is initialized
 // or cloned inside of the engine
+#[derive(Clone)]
 pub struct TempoRepr {
     pub idx: usize,
     pub owned: bool,
     pub mode: TempoMode,
     pub unit: TempoUnit,
     pub interval: f32,
+    pub beats_per_bar: u32,
+    pub transport: Transport, // only kept for TempoContexts
 }
 
 impl TempoRepr {
@@ -202,16 +926,8 @@ impl TempoRepr {
             mode: TempoMode::TBD,
             unit: TempoUnit::Samples,
             interval: 0f32,
-        }
-    }
-
-    pub fn clone(other: &TempoRepr) -> Self {
-        Self {
-            idx: other.idx,
-            owned: other.owned,
-            mode: other.mode,
-            unit: other.unit,
-            interval: other.interval,
+            beats_per_bar: 4,
+            transport: Transport::Stopped,
         }
     }
 
@@ -223,6 +939,8 @@ impl TempoRepr {
             mode: other.mode,
             unit: other.unit,
             interval: other.interval,
+            beats_per_bar: other.beats_per_bar,
+            transport: Transport::Stopped,
         }
     }
 
@@ -231,26 +949,161 @@ impl TempoRepr {
         self.unit = unit;
         self.interval = interval;
     }
+
+    // the TempoState the engine would otherwise make for an owned
+    // one, made here instead; one that isn't owned is shared, unless
+    // there's nothing to share it from (a Process's, or one not yet set)
+    fn made(&self) -> MadeTempo {
+        let mut state = TempoState::new(None);
+        match (self.owned, self.mode) {
+            (true, _) => {
+                state.init(self.mode, self.unit, self.interval);
+                state.beats_per_bar = self.beats_per_bar;
+            }
+            (false, TempoMode::Process | TempoMode::TBD) => (),
+            (false, _) => return None,
+        }
+        Some(Unshared::tempo(state))
+    }
 }
 
+#[derive(Clone)]
 pub struct VoiceRepr {
     idx: usize,
+    track: usize, // the Track's idx
+    channels: usize, // of its Track
+    frames: usize, // it can play, after any autoloop
+    rate: u32, // of its Track
+    trim: Option<(usize, usize)>,
     tempo: TempoRepr,
     processes: HashMap<String, ProcRepr>,
     proc_tempi: HashMap<usize, TempoRepr>,
+    keymap: Option<KeyMap>,
+    transport: Transport,
+    muted: bool,
+    soloed: bool,
+    meter: Arc<Level>, // shared with the Voice in the engine
 }
 
 impl VoiceRepr {
-    fn new(idx: usize, tempo: TempoRepr) -> Self {
+    pub fn is_playing(&self) -> bool {
+        matches!(self.transport, Transport::Playing)
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn is_soloed(&self) -> bool {
+        self.soloed
+    }
+
+    // a beat of its tempo, in its Track's frames
+    fn beat_frames(&self) -> Option<f32> {
+        match self.tempo.interval {
+            0.0 => None,
+            interval => Some(convert_interval(&self.tempo.unit, interval) * self.rate as f32 / sample_rate::get().max(1) as f32),
+        }
+    }
+
+    // a point in its Track, in frames: raw is <n> frames, <n>ms,
+    // or <n>b (beats of its tempo)
+    fn frame_at(&self, name: &str, raw: &str, cmd: &str) -> CmdResult<f32> {
+        let (val, per) = if let Some(ms) = raw.strip_suffix("ms") {
+            (ms, self.rate as f32 / 1000.0)
+        } else if let Some(beats) = raw.strip_suffix('b') {
+            (beats, self.beat_frames().ok_or(CmdErr::Formatting {
+                err: format!("{name} has no tempo to count beats in; give frames or ms"),
+            })?)
+        } else {
+            (raw, 1.0)
+        };
+        let val = val
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })?;
+        Ok(val * per)
+    }
+
+    // files a new Process under name, or its kind (seq, seq2, ...)
+    fn add_process(&mut self, name: Option<String>, repr: ProcRepr) -> CmdResult<String> {
+        if self.processes.len() >= MAX_PROCESSES {
+            return Err(CmdErr::Formatting {
+                err: format!("A Voice can have at most {MAX_PROCESSES} Processes"),
+            });
+        }
+        let name = match name {
+            Some(name) if name.contains('.') => return Err(CmdErr::InvalidArg {
+                arg: name,
+                cmd: repr.kind.name().to_string(),
+            }),
+            Some(name) if self.processes.contains_key(&name) => return Err(CmdErr::AlreadyIs {
+                ty: "Process".to_string(),
+                name,
+            }),
+            Some(name) => name,
+            None => {
+                let kind = repr.kind.name();
+                (1..)
+                    .map(|n| match n {
+                        1 => kind.to_string(),
+                        _ => format!("{kind}{n}"),
+                    })
+                    .find(|name| !self.processes.contains_key(name))
+                    .unwrap()
+            }
+        };
+        self.processes.insert(name.clone(), repr);
+        Ok(name)
+    }
+
+    fn new(idx: usize, track: &TrackRepr, frames: usize, tempo: TempoRepr) -> Self {
         Self {
             idx,
+            track: track.idx,
+            channels: track.channels,
+            frames,
+            rate: track.rate,
+            trim: None,
             tempo,
             processes: HashMap::<String, ProcRepr>::new(),
             proc_tempi: HashMap::<usize, TempoRepr>::new(),
+            keymap: None,
+            transport: Transport::Stopped,
+            muted: false,
+            soloed: false,
+            meter: Arc::new(Level::default()),
+        }
+    }
+
+    // the same Track, trim, keymap and tempo (a copy of its own,
+    // or the one it borrows), but otherwise new: stopped, unmuted,
+    // and with no Processes
+    fn cloned(&self, idx: usize) -> Self {
+        Self {
+            idx,
+            track: self.track,
+            channels: self.channels,
+            frames: self.frames,
+            rate: self.rate,
+            trim: self.trim,
+            tempo: TempoRepr::clone(&self.tempo),
+            processes: HashMap::<String, ProcRepr>::new(),
+            proc_tempi: HashMap::<usize, TempoRepr>::new(),
+            keymap: self.keymap,
+            transport: Transport::Stopped,
+            muted: false,
+            soloed: false,
+            meter: Arc::new(Level::default()),
         }
     }
 }
 
+#[derive(Clone)]
 pub struct ProcRepr {
     // Processes are difficult to represent because they all
     // differ, so can only represent info that applies
@@ -258,87 +1111,871 @@ pub struct ProcRepr {
     //
     idx: usize, // index of the Process in its owner's
                 // Vec<Process>
+    kind: ProcKind,
 
+    #[allow(dead_code)]
     owner_idx: Idx, // index of the Process's $owner
                       // in the engine's Vec<$owner>
     tempo: Option<TempoRepr>,
+    pattern: Option<PatternRepr>, // for Seqs
     // maybe create ProcArgs enum, one for each Process
 }
 
 impl ProcRepr {
-    fn new(idx: usize, owner_idx: Idx, tempo: Option<TempoRepr>) -> Self {
-        Self { idx, owner_idx, tempo }
+    fn new(idx: usize, kind: ProcKind, owner_idx: Idx, tempo: Option<TempoRepr>, pattern: Option<PatternRepr>) -> Self {
+        Self { idx, kind, owner_idx, tempo, pattern }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProcKind {
+    Seq,
+    Lfo,
+    Follow,
+}
+
+impl ProcKind {
+    // also what a Process is called unless it's named
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcKind::Seq => "seq",
+            ProcKind::Lfo => "lfo",
+            ProcKind::Follow => "follow",
+        }
+    }
+}
+
+// what `status` shows of a Seq; the engine
+// keeps phase up to date as steps play
+#[derive(Clone)]
+pub struct PatternRepr {
+    period: usize,
+    steps: Vec<f32>,
+    chance: Vec<f32>,
+    jit: Vec<f32>,
+    levels: Vec<f32>,
+    swing: Swing,
+    seed: Option<u64>,
+    phase: Arc<AtomicUsize>,
+}
+
+impl PatternRepr {
+    // one cell per unit of the period, x where a step lands
+    // and . where none does; the cell of the last step
+    // played is shown in reverse video
+    pub fn diagram(&self) -> String {
+        let current = self.phase.load(Ordering::Relaxed);
+        let current_cell = self.steps.get(current).map(|step| *step as usize);
+
+        let mut line = String::new();
+        for cell in 0..self.period {
+            let hit = self.steps.iter().any(|step| *step as usize == cell);
+            let c = if hit { 'x' } else { '.' };
+            if Some(cell) == current_cell {
+                line.push_str(&format!("\x1b[7m{c}\x1b[0m"));
+            } else {
+                line.push(c);
+            }
+        }
+        line
+    }
+
+    // cycles of the pattern as notes on key, a unit of its tempo
+    // to a quarter note: chance rolled (as the Seq's own seed would
+    // roll it the first time through) and swing applied, each
+    // step as loud as its level
+    pub fn flatten(&self, cycles: usize, key: u8) -> smf::Clip {
+        let ppq = smf::PPQ as f32;
+        let mut rng = X128P::new(self.seed.unwrap_or_else(fast_seed));
+        let mut notes = Vec::<smf::Note>::new();
+        for cycle in 0..cycles {
+            for (i, step) in self.steps.iter().enumerate() {
+                if rng.next_i64_range(0, 100) >= self.chance[i] as i64 {
+                    continue;
+                }
+                let at = (cycle * self.period) as f32 + self.swing.apply(*step);
+                notes.push(smf::Note {
+                    tick: (at * ppq).round() as u32,
+                    key,
+                    velocity: (self.levels[i].min(1.0) * 127.0).round().max(1.0) as u8,
+                });
+            }
+        }
+        smf::Clip { ppq: smf::PPQ, notes, len: (cycles * self.period) as u32 * smf::PPQ as u32 }
+    }
+}
+
+// the seq flags that play clip back on voice: notes on key (or
+// every note, without one) as steps, a quarter note to a unit of
+// tempo, velocities as levels, the period the clip's whole length
+fn clip_seq_line(voice: &str, clip: &smf::Clip, key: Option<u8>) -> CmdResult<String> {
+    let ppq = clip.ppq as f32;
+    // notes together are one step, as loud as the loudest
+    let mut hits = Vec::<(u32, u8)>::new();
+    for note in clip.notes.iter().filter(|note| key.is_none_or(|key| note.key == key)) {
+        match hits.last_mut() {
+            Some((tick, velocity)) if *tick == note.tick => *velocity = (*velocity).max(note.velocity),
+            _ => hits.push((note.tick, note.velocity)),
+        }
     }
+    let Some(&(last, _)) = hits.last() else {
+        return Err(CmdErr::Formatting { err: "No notes to make steps of".to_string() });
+    };
+
+    let period = (clip.len.max(last + 1) as f32 / ppq).ceil().max(1.0) as usize;
+    let round = |v: f32, places: f32| (v * places).round() / places;
+    let steps: Vec<String> = hits.iter().map(|(tick, _)| round(*tick as f32 / ppq, 10000.0).to_string()).collect();
+    let mut line = format!("seq {voice} -p {period} -s {}", steps.join(","));
+    if hits.iter().any(|(_, velocity)| *velocity < 127) {
+        let levels: Vec<String> = hits.iter().map(|(_, velocity)| round(*velocity as f32 / 127.0, 100.0).to_string()).collect();
+        line.push_str(&format!(" -v {}", levels.join(",")));
+    }
+    Ok(line)
 }
 
+#[derive(Clone)]
 pub struct GroupRepr {
     idx: usize,
     tempo: TempoRepr,
     voices: HashMap<String, VoiceRepr>,
+    transport: Transport,
 }
 
 impl GroupRepr {
     fn new(idx: usize, tempo: TempoRepr, voices: HashMap<String, VoiceRepr>) -> Self {
-        Self { idx, tempo, voices }
+        Self { idx, tempo, voices, transport: Transport::Stopped }
+    }
+
+    fn is_playing(&self) -> bool {
+        matches!(self.transport, Transport::Playing)
     }
 }
 
 // keeps track of all entities' states
+#[derive(Clone)]
 pub struct EngineState {
     tracks: HashMap<String, TrackRepr>,
     voices: HashMap<String, VoiceRepr>,
     groups: HashMap<String, GroupRepr>,
     tempo_cons: HashMap<String, TempoRepr>,
+    buses: HashMap<String, usize>, // aux buses, by name, to their idx
     out_channels: usize,
+    pool: usize, // Voices that can be loaded at once (see Conductor::reserve)
+    fade_ms: f32, // the engine's (see try_fade)
+    next: Option<Box<EngineState>>, // a session loaded in the background
+    history: Vec<HistoryEntry>, // what got it here, oldest first
+    rev: u64, // the last revision handed out
+    undone: Vec<HistoryEntry>, // taken back by undo, the next to redo last
 }
 
-impl EngineState {
-    pub fn new(files: Vec<AudioFile>, out_channels: usize) -> Self {
-        let mut tracks: HashMap<String, TrackRepr> = HashMap::new();
-        for (idx, af) in files.iter().enumerate() {
-            tracks.insert(af.file_name.clone(), TrackRepr::new(idx));
-        }
+// lines that move a playhead or the transport rather than change
+// what the session is: a replay sends none of them (where things
+// are is carried over instead; see EngineState::carry), and a
+// saved set leaves them out, so it opens with nothing playing
+fn moves_playhead(line: &str) -> bool {
+    let cmd = line.split_whitespace().next().unwrap_or("");
+    matches!(cmd, "start" | "pause" | "resume" | "stop" | "trig" | "note" | "seek")
+}
 
-        Self {
-            tracks,
-            out_channels,
-            voices: HashMap::<String, VoiceRepr>::new(),
-            groups: HashMap::<String, GroupRepr>::new(),
-            tempo_cons: HashMap::<String, TempoRepr>::new(),
+// the line that takes an accepted one back, from the states
+// either side of it; lines that change what the state doesn't
+// keep (a gain, say) have none, and are undone by a rebuild
+fn inverse(line: &str, before: &EngineState, after: &EngineState) -> Option<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words[..] {
+        ["load" | "clone", ..] => {
+            let mut added = after.voices.keys().filter(|name| !before.voices.contains_key(*name));
+            match (added.next(), added.next()) {
+                (Some(name), None) => Some(format!("unload {name}")),
+                _ => None,
+            }
         }
+        [cmd @ ("mute" | "solo"), ty @ ("-v" | "--voice"), name, ..] => {
+            let voice = before.voice(name)?;
+            let was = if cmd == "mute" { voice.muted } else { voice.soloed };
+            Some(format!("{cmd} {ty} {name} {}", if was { "on" } else { "off" }))
+        }
+        ["rename", ty, old, new] => {
+            // a Voice in a Group keeps its group. prefix
+            let new = match old.split_once('.') {
+                Some((g_name, _)) => format!("{g_name}.{new}"),
+                None => new.to_string(),
+            };
+            let old = old.rsplit('.').next().unwrap_or(old);
+            Some(format!("rename {ty} {new} {old}"))
+        }
+        ["seq" | "follow" | "lfo", name, ..] => {
+            let had = &before.voice(name)?.processes;
+            let added = after.voice(name)?.processes.keys().find(|p| !had.contains_key(*p))?;
+            Some(format!("proc rm {name}.{added}"))
+        }
+        _ => None,
+    }
+}
+
+// an accepted line, and its place in the history;
+// revisions only ever go up, even across reverts
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct HistoryEntry {
+    pub rev: u64,
+    pub line: String,
+    pub undo: Option<String>, // the line that takes it back, if there is one
+}
+
+impl EngineState {
+    // a Voice by name (group.voice inside a Group)
+    pub fn voice(&self, name: &str) -> Option<&VoiceRepr> {
+        match name.split_once('.') {
+            Some((g_name, v_name)) => self.groups.get(g_name)?.voices.get(v_name),
+            None => self.voices.get(name),
+        }
+    }
+
+    // the Voices and Groups among idxs whose tempo is borrowed
+    // from a TempoContext or Group that isn't playing
+    fn stalled(&self, idxs: &[Idx]) -> Vec<String> {
+        let running = |tempo: &TempoRepr| tempo.owned || match tempo.mode {
+            TempoMode::Context => self.tempo_cons.values().any(|t| t.idx == tempo.idx && t.transport == Transport::Playing),
+            TempoMode::Group => self.groups.values().any(|g| g.idx == tempo.idx && g.is_playing()),
+            _ => true,
+        };
+
+        let mut stalled = Vec::new();
+        for idx in idxs {
+            match *idx {
+                Idx::Voice(i) => stalled.extend(self.voices
+                    .iter()
+                    .filter(|(_, v)| v.idx == i && !running(&v.tempo))
+                    .map(|(name, _)| format!("Voice {name}"))),
+                Idx::Group(i) => stalled.extend(self.groups
+                    .iter()
+                    .filter(|(_, g)| g.idx == i && !running(&g.tempo))
+                    .map(|(name, _)| format!("Group {name}"))),
+                _ => (),
+            }
+        }
+        stalled
+    }
+
+    // the name of the Voice at idx in the engine
+    pub fn voice_name(&self, idx: Idx) -> Option<String> {
+        match idx {
+            Idx::Voice(v) => self.voices.iter().find(|(_, voice)| voice.idx == v).map(|(name, _)| name.clone()),
+            Idx::GroupVoice(g, v) => {
+                let (g_name, group) = self.groups.iter().find(|(_, group)| group.idx == g)?;
+                let (name, _) = group.voices.iter().find(|(_, voice)| voice.idx == v)?;
+                Some(format!("{g_name}.{name}"))
+            }
+            _ => None,
+        }
+    }
+
+    // every Voice's meter, by name (group.voice inside Groups)
+    pub fn meters(&self) -> VoiceLevels {
+        let mut meters: VoiceLevels = self.voices
+            .iter()
+            .map(|(name, v)| (name.clone(), Arc::clone(&v.meter)))
+            .chain(self.groups.iter().flat_map(|(g_name, g)| {
+                g.voices.iter().map(move |(name, v)| (format!("{g_name}.{name}"), Arc::clone(&v.meter)))
+            }))
+            .collect();
+        meters.sort_by(|a, b| a.0.cmp(&b.0));
+        meters
+    }
+
+    // one line for the TUI's status bar
+    pub fn summary(&self) -> String {
+        let voices = self.voices.len() + self.groups.values().map(|g| g.voices.len()).sum::<usize>();
+        let playing = self.voices
+            .values()
+            .chain(self.groups.values().flat_map(|g| g.voices.values()))
+            .filter(|v| matches!(v.transport, Transport::Playing))
+            .count();
+        let mut line = format!(
+            "rev {}  {voices} voices ({playing} playing)  {} groups  {} tempocontexts",
+            self.rev,
+            self.groups.len(),
+            self.tempo_cons.len(),
+        );
+        if self.next.is_some() {
+            line.push_str("  next session ready");
+        }
+        line
+    }
+
+    // how a tempo was given: s|m|b:<interval>, or the
+    // TempoContext or Group it's shared from
+    fn tempo_name(&self, tempo: &TempoRepr) -> String {
+        let owner = match (tempo.owned, tempo.mode) {
+            (false, TempoMode::Context) => self.tempo_cons
+                .iter()
+                .find(|(_, t)| t.idx == tempo.idx)
+                .map(|(name, _)| format!("c:{name}")),
+            (false, TempoMode::Group) => self.groups
+                .iter()
+                .find(|(_, g)| g.idx == tempo.idx)
+                .map(|(name, _)| format!("g:{name}")),
+            _ => None,
+        };
+        if let Some(owner) = owner {
+            return owner;
+        }
+
+        let unit = match tempo.unit {
+            TempoUnit::Samples => "s",
+            TempoUnit::Millis => "m",
+            TempoUnit::Bpm => "b",
+        };
+        match tempo.interval {
+            0.0 => "none".to_string(),
+            interval => format!("{unit}:{interval}"),
+        }
+    }
+
+    pub fn new(files: Vec<AudioFile>, out_channels: usize) -> Self {
+        let mut tracks: HashMap<String, TrackRepr> = HashMap::new();
+        for (idx, af) in files.iter().enumerate() {
+            let repr = TrackRepr::new(idx, af);
+            tracks.insert(af.file_name.clone(), repr);
+        }
+
+        Self {
+            tracks,
+            out_channels,
+            pool: VOICE_POOL,
+            fade_ms: 5.0,
+            voices: HashMap::<String, VoiceRepr>::new(),
+            groups: HashMap::<String, GroupRepr>::new(),
+            tempo_cons: HashMap::<String, TempoRepr>::new(),
+            buses: HashMap::<String, usize>::new(),
+            next: None,
+            history: Vec::new(),
+            rev: 0,
+            undone: Vec::new(),
+        }
+    }
+
+    // as many as the engine has slots for; set
+    // along with Conductor::reserve, before anything's loaded
+    pub fn set_pool(&mut self, voices: usize) {
+        self.pool = voices.max(1);
+    }
+
+    // the Tracks' audio in the order the engine has them (their
+    // idxs); the samples are shared, not copied
+    fn audio(&self) -> Vec<AudioFile> {
+        let mut tracks: Vec<&TrackRepr> = self.tracks.values().collect();
+        tracks.sort_by_key(|t| t.idx);
+        tracks.into_iter().map(|t| t.audio.clone()).collect()
+    }
+
+    // a session the engine can take on whole, built here from the
+    // Commands that lead to this state from nothing loaded (see
+    // run_script and replay), so the audio thread doesn't have to
+    pub fn session(&self, commands: Vec<Command>) -> Unshared<Box<Conductor>> {
+        let mut conductor = Conductor::with_tracks(self.out_channels, self.audio());
+        conductor.reserve(self.pool);
+        for cmd in commands {
+            conductor.apply(cmd);
+        }
+        conductor.apply(Command::Fade(FadeArgs { ms: self.fade_ms }));
+        Unshared::session(Box::new(conductor))
+    }
+
+    // what a state rebuilt from the history (see replay) keeps
+    // of this one: the transport of every Voice, Group and
+    // TempoContext it still has (by name, and a Voice only on
+    // the same Track), and for the engine, which of this one's
+    // each of them is, (old, new)
+    fn carry(&self, state: &mut EngineState) -> Vec<(Idx, Idx)> {
+        let mut carry = Vec::new();
+        for (name, tc) in &self.tempo_cons {
+            if let Some(new) = state.tempo_cons.get_mut(name) {
+                new.transport = tc.transport;
+                carry.push((Idx::Tempo(tc.idx), Idx::Tempo(new.idx)));
+            }
+        }
+        for (name, voice) in &self.voices {
+            if let Some(new) = state.voices.get_mut(name) && new.track == voice.track {
+                new.transport = voice.transport;
+                carry.push((Idx::Voice(voice.idx), Idx::Voice(new.idx)));
+            }
+        }
+        for (name, group) in &self.groups {
+            let Some(new) = state.groups.get_mut(name) else { continue };
+            new.transport = group.transport;
+            carry.push((Idx::Group(group.idx), Idx::Group(new.idx)));
+            for (v_name, voice) in &group.voices {
+                if let Some(new_voice) = new.voices.get_mut(v_name) && new_voice.track == voice.track {
+                    new_voice.transport = voice.transport;
+                    carry.push((Idx::GroupVoice(group.idx, voice.idx), Idx::GroupVoice(new.idx, new_voice.idx)));
+                }
+            }
+        }
+        carry
+    }
+
+    // a Start for whatever this state has playing but carried
+    // doesn't take over (see carry), from the top: the session a
+    // replay builds has nothing playing by itself
+    fn restart(&self, carried: &[(Idx, Idx)]) -> Command {
+        let playing = |transport: Transport, idx: Idx| {
+            transport == Transport::Playing && !carried.iter().any(|&(_, new)| new == idx)
+        };
+        let tcs = self.tempo_cons.values().map(|t| (t.transport, Idx::Tempo(t.idx)));
+        let groups = self.groups.values().map(|g| (g.transport, Idx::Group(g.idx)));
+        let voices = self.voices.values().map(|v| (v.transport, Idx::Voice(v.idx)));
+        let idxs = tcs.chain(groups).chain(voices).filter(|&(t, idx)| playing(t, idx)).map(|(_, idx)| idx).collect();
+        Command::Start(StartArgs { idxs })
+    }
+
+    // Voices loaded, in Groups or not
+    fn voice_count(&self) -> usize {
+        self.voices.len() + self.groups.values().map(|g| g.voices.len()).sum::<usize>()
+    }
+
+    // nothing loaded yet, but the same Tracks and outputs
+    // (and revisions carry on from here)
+    fn fresh(&self) -> Self {
+        Self {
+            tracks: self.tracks.clone(),
+            out_channels: self.out_channels,
+            pool: self.pool,
+            fade_ms: self.fade_ms,
+            voices: HashMap::<String, VoiceRepr>::new(),
+            groups: HashMap::<String, GroupRepr>::new(),
+            tempo_cons: HashMap::<String, TempoRepr>::new(),
+            buses: HashMap::<String, usize>::new(),
+            next: None,
+            history: Vec::new(),
+            rev: self.rev,
+            undone: Vec::new(),
+        }
+    }
+
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    pub fn rev(&self) -> u64 {
+        self.rev
+    }
+
+    fn record(&mut self, line: &str, undo: Option<String>) {
+        self.rev += 1;
+        self.history.push(HistoryEntry { rev: self.rev, line: line.to_string(), undo });
     }
 }
 
+// a validated Command, and the EngineState it will leave behind
+pub struct Plan {
+    pub(crate) command: Command,
+    state: EngineState,
+    line: String,
+    undo: Option<String>, // see inverse
+}
+
+// lines run can have queued at once
+const MAX_SCRIPTED: usize = 10_000;
+
 // validates and formats Commands for the engine
 // (handles string allocations, integer/float parsing, etc)
 pub struct CmdProcessor {
-    pub engine_state: EngineState,
+    pub(crate) engine_state: EngineState,
+    capture: Option<Capture>,
+    midi_clock: Option<Arc<ClockSync>>, // the listener running, if any
+    pub(crate) notes: NoteInput, // what MIDI notes play, and where from
+    pub(crate) scripts: Scripts, // the Rhai scripts running
+    recording: Option<Recording>, // the file rec is writing, if any
+    script: VecDeque<(String, String)>, // lines queued by run, each with where it's from
+    aliases: BTreeMap<String, String>, // name -> lines, separated by ;
+    pub(crate) stats: Arc<Stats>, // kept by the audio loop
 }
 
 impl CmdProcessor {
     pub fn new(engine_state: EngineState) -> Self {
-        Self { engine_state }
+        Self {
+            engine_state,
+            capture: None,
+            midi_clock: None,
+            notes: NoteInput::default(),
+            scripts: Scripts::default(),
+            recording: None,
+            script: VecDeque::new(),
+            aliases: BTreeMap::new(),
+            stats: Arc::new(Stats::new()),
+        }
     }
     
-    pub fn parse(&mut self, cmd: String) -> CmdResult<Command> {
-        let mut parts = cmd.splitn(2, ' ');
+    // what the lines so far have built, and their history
+    pub fn state(&self) -> &EngineState {
+        &self.engine_state
+    }
+
+    // plans and commits in one go, for callers that apply
+    // Commands directly (nothing can refuse them);
+    // returns None for commands that are handled entirely
+    // on the command thread (nothing to send to the engine)
+    pub fn parse(&mut self, line: String) -> CmdResult<Option<Command>> {
+        let Some(plan) = self.prepare(line)? else {
+            return Ok(None);
+        };
+
+        let mut command = None;
+        self.commit(plan, |cmd| {
+            command = Some(cmd);
+            Ok::<(), CmdErr>(())
+        })?;
+        Ok(command)
+    }
+
+    // runs control-only commands (cue, bundle) right away,
+    // and plans the rest
+    pub fn prepare(&mut self, line: String) -> CmdResult<Option<Plan>> {
+        let mut parts = line.splitn(2, ' ');
         let cmd = parts.next().unwrap();
-        let args = parts.next().unwrap_or_else(|| "").to_string();
-        
+        let args = parts.next().unwrap_or("").to_string();
+
+        if let Some(body) = self.aliases.get(cmd) {
+            self.expand_alias(cmd.to_string(), body.clone(), args)?;
+            return Ok(None);
+        }
+
         match cmd {
-            "load" => self.try_load(args),
-            "start" => self.try_start(args),
-            "pause" => self.try_pause(args),
-            "resume" => self.try_resume(args),
-            "stop" => self.try_stop(args),
-            "unload" => self.try_unload(args),
-            "velocity" => self.try_velocity(args),
-            "group" => self.try_group(args),
-            "tc" | "tempocon" => self.try_tc(args),
-            "seq" => self.try_seq(args),
-            "q" | "quit" => Ok(Command::Quit(QuitArgs{})),
-            _ => return Err(CmdErr::NoCmd { cmd: cmd.to_owned() }),
+            "cue" => {
+                self.try_cue(args)?;
+                return Ok(None);
+            }
+            "help" => {
+                let name = args.split_whitespace().next();
+                let text = schema::help(name).ok_or_else(|| CmdErr::NoCmd {
+                    cmd: name.unwrap_or_default().to_string(),
+                    suggestion: schema::suggest(name.unwrap_or_default(), self.aliases.keys().map(String::as_str)),
+                })?;
+                tui::log(text);
+                return Ok(None);
+            }
+            "alias" => {
+                if let Some(listing) = self.try_alias(args)? {
+                    tui::log(listing);
+                }
+                return Ok(None);
+            }
+            "bundle" => {
+                self.try_bundle(args)?;
+                return Ok(None);
+            }
+            "save" => {
+                self.try_save(args)?;
+                return Ok(None);
+            }
+            "run" => {
+                self.try_run(args)?;
+                return Ok(None);
+            }
+            "script" => {
+                self.try_script(args)?;
+                return Ok(None);
+            }
+            "status" => {
+                tui::log(self.status(args)?);
+                return Ok(None);
+            }
+            "ls" => {
+                tui::log(self.list(args)?);
+                return Ok(None);
+            }
+            "proc" if args.split_whitespace().next() == Some("ls") => {
+                tui::log(self.list_procs(args)?);
+                return Ok(None);
+            }
+            "history" => {
+                tui::log(self.show_history(args)?);
+                return Ok(None);
+            }
+            "wave" => {
+                tui::log(self.wave(args)?);
+                return Ok(None);
+            }
+            "info" => {
+                tui::log(self.info(args)?);
+                return Ok(None);
+            }
+            "latency" if args.trim().is_empty() => {
+                match backend::settled() {
+                    Some(latency) => tui::log(format!("Latency: {}", latency.describe(sample_rate::get()))),
+                    None => tui::warn("No audio device open"),
+                }
+                return Ok(None);
+            }
+            "stats" => {
+                match args.trim() {
+                    "" => tui::log(self.stats.snapshot().describe()),
+                    "reset" => {
+                        self.stats.reset();
+                        tui::ok("Stats reset");
+                    }
+                    raw => return Err(CmdErr::InvalidArg { arg: raw.to_string(), cmd: "stats".to_string() }),
+                }
+                return Ok(None);
+            }
+            "devices" => {
+                let found = devices::playback().map_err(|err| CmdErr::Formatting { err })?;
+                tui::log(devices::listing(&found, &devices::playing()));
+                return Ok(None);
+            }
+            "capture" => return self.try_capture(args),
+            "seq" if args.starts_with("export ") => {
+                self.try_seq_export(args)?;
+                return Ok(None);
+            }
+            "seq" if args.starts_with("import ") => return self.try_seq_import(args).map(Some),
+            "midi" if matches!(args.split_whitespace().next(), Some("notes" | "map" | "keys")) => {
+                if let Some(listing) = self.try_midi_notes(args)? {
+                    tui::log(listing);
+                }
+                return Ok(None);
+            }
+            "midi" => return self.try_midi(line).map(Some),
+            "rec" => return self.try_rec(line).map(Some),
+            "q" | "quit" => {
+                // don't lose the end of a recording (or its markers)
+                if let Some(recording) = self.recording.take() {
+                    tui::ok(recording.stop().map_err(|err| CmdErr::Formatting { err })?);
+                }
+            }
+            _ => (),
+        }
+
+        self.plan(line).map(Some)
+    }
+
+    // validates a line against a copy of the EngineState,
+    // so nothing changes until the Plan is committed
+    // (and a Command that fails halfway leaves nothing behind)
+    pub fn plan(&self, line: String) -> CmdResult<Plan> {
+        let mut parts = line.splitn(2, ' ');
+        let cmd = parts.next().unwrap();
+        let args = parts.next().unwrap_or("").to_string();
+
+        if cmd == "redo" {
+            return self.plan_redo();
+        }
+
+        let mut scratch = Self {
+            engine_state: self.engine_state.clone(),
+            capture: None,
+            midi_clock: None,
+            notes: NoteInput::default(),
+            scripts: Scripts::default(),
+            recording: None,
+            script: VecDeque::new(),
+            aliases: BTreeMap::new(),
+            stats: Arc::clone(&self.stats),
+        };
+
+        let command = match cmd {
+            "load" => scratch.try_load(args)?,
+            "clone" => scratch.try_clone(args)?,
+            "start" => scratch.try_start(args)?,
+            "trig" => scratch.try_trig(args)?,
+            "pause" => scratch.try_pause(args)?,
+            "resume" => scratch.try_resume(args)?,
+            "stop" => scratch.try_stop(args)?,
+            "unload" => scratch.try_unload(args)?,
+            "velocity" => scratch.try_velocity(args)?,
+            "stretch" => {
+                let (idx, factor) = scratch.parse_voice_val(args, "stretch", |f| f > 0.0)?;
+                Command::Stretch(StretchArgs { idx, factor })
+            }
+            "pitch" => {
+                let (idx, semitones) = scratch.parse_voice_val(args, "pitch", |st| st.abs() <= 48.0)?;
+                Command::Pitch(PitchArgs { idx, semitones })
+            }
+            "keymap" => scratch.try_keymap(args)?,
+            "note" => scratch.try_note(args)?,
+            "gain" => scratch.try_gain(args)?,
+            "pan" => scratch.try_pan(args)?,
+            "offset" => scratch.try_offset(args)?,
+            "trim" => scratch.try_trim(args)?,
+            "seek" => scratch.try_seek(args)?,
+            "route" => scratch.try_route(args)?,
+            "out" => scratch.try_out(args)?,
+            "loop" => scratch.try_loop(args)?,
+            "poly" => scratch.try_poly(args)?,
+            "mute" => {
+                let (idx, on) = scratch.parse_switch(args.clone(), "mute")?;
+                if let Some(voice) = scratch.switched_voice(&args) {
+                    voice.muted = on;
+                }
+                Command::Mute(MuteArgs { idx, on })
+            }
+            "solo" => {
+                let (idx, on) = scratch.parse_switch(args.clone(), "solo")?;
+                if let Some(voice) = scratch.switched_voice(&args) {
+                    voice.soloed = on;
+                }
+                Command::Solo(SoloArgs { idx, on })
+            }
+            "fade" => scratch.try_fade(args)?,
+            "group" => scratch.try_group(args)?,
+            "import-stems" => scratch.try_import_stems(args)?,
+            "bounce" => scratch.try_bounce(args)?,
+            "tc" | "tempocon" => scratch.try_tc(args)?,
+            "tempo" => scratch.try_tempo(args)?,
+            "meter" => scratch.try_meter(args)?,
+            "seq" => scratch.try_seq(args)?,
+            "follow" => scratch.try_follow(args)?,
+            "lfo" => scratch.try_lfo(args)?,
+            "proc" => scratch.try_proc(args)?,
+            "delay" => scratch.try_delay(args)?,
+            "verb" | "reverb" => scratch.try_verb(args)?,
+            "bus" => scratch.try_bus(args)?,
+            "send" => scratch.try_send(args)?,
+            "rename" => scratch.try_rename(args)?,
+            "session" => scratch.try_session(args)?,
+            "revert" => scratch.try_revert(args)?,
+            "undo" => scratch.try_undo(args)?,
+            "open" => scratch.try_open(args)?,
+            "audio" => scratch.try_audio(args)?,
+            "latency" => scratch.try_latency(args)?,
+            "midi" => scratch.plan_midi(args)?,
+            "rec" => scratch.plan_rec(args)?,
+            "q" | "quit" => Command::Quit(QuitArgs{}),
+            _ => return Err(CmdErr::NoCmd {
+                cmd: cmd.to_owned(),
+                suggestion: schema::suggest(cmd, self.aliases.keys().map(String::as_str)),
+            }),
+        };
+
+        // anything new in the history means there's nothing to redo
+        if !matches!(command, Command::Session(_) | Command::Audio(_) | Command::Midi(_) | Command::Quit(_)) {
+            scratch.engine_state.undone.clear();
+        }
+
+        let undo = inverse(&line, &self.engine_state, &scratch.engine_state);
+        Ok(Plan { command, state: scratch.engine_state, line, undo })
+    }
+
+    // redo
+    //
+    // the last line undone, planned again as itself (and given
+    // a new revision)
+    fn plan_redo(&self) -> CmdResult<Plan> {
+        let mut undone = self.engine_state.undone.clone();
+        let event = undone.pop().ok_or(CmdErr::Formatting {
+            err: "Nothing to redo".to_string(),
+        })?;
+
+        let mut plan = self.plan(event.line)?;
+        plan.state.undone = undone;
+        Ok(plan)
+    }
+
+    // hands the Plan's Command to send (e.g. pushing it onto the
+    // CmdQueue), and only takes on its EngineState if that succeeds
+    pub fn commit<E>(
+        &mut self,
+        plan: Plan,
+        send: impl FnOnce(Command) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let Plan { command, state, line, undo } = plan;
+        // sessions and reverts rebuild the state rather than add
+        // to it, and the rest doesn't touch it
+        let recorded = !matches!(
+            command,
+            Command::Session(_) | Command::Audio(_) | Command::Midi(_) | Command::Quit(_),
+        );
+        let started = match &command {
+            Command::Start(StartArgs { idxs }) | Command::Resume(ResumeArgs { idxs }) => idxs.clone(),
+            _ => Vec::new(),
+        };
+        send(command)?;
+        self.engine_state = state;
+        if recorded {
+            self.engine_state.record(&line, undo);
+        }
+
+        // undo, revert and open don't add a revision to acknowledge,
+        // so they say which one they left the state at instead
+        let at = self.engine_state.history.last().map_or(0, |e| e.rev);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("undo") => tui::ok(format!("undone to rev {at}")),
+            Some("revert") => tui::ok(format!("reverted to rev {at}")),
+            Some("open") => tui::ok(format!("opened {} (rev {at})", words.next().unwrap_or_default())),
+            _ => (),
+        }
+
+        // the engine doesn't print, so starting on a tempo that
+        // isn't running is pointed out from here (a MIDI clock
+        // may be running it without a start)
+        if self.midi_clock.is_none() {
+            for name in self.engine_state.stalled(&started) {
+                tui::warn(format!("Tempo not active for {name}"));
+            }
+        }
+
+        // transport, locates, pattern changes (seq, or proc set on
+        // a Seq) and crossfades to the next session are marked on
+        // the recording's cue sheet
+        words = line.split_whitespace();
+        let marked = match words.next() {
+            Some("start" | "pause" | "resume" | "stop" | "seek" | "seq") => true,
+            Some("session") => matches!(words.next(), Some("crossfade" | "xfade")),
+            Some("proc") => words.next() == Some("set") && words
+                .next()
+                .and_then(|target| target.rsplit_once('.'))
+                .is_some_and(|(v_name, p_name)| {
+                    self.find_voice(v_name.to_string()).is_ok_and(|voice| {
+                        voice.processes.get(p_name).is_some_and(|p| p.kind == ProcKind::Seq)
+                    })
+                }),
+            _ => false,
+        };
+        if marked && let Some(recording) = &mut self.recording {
+            recording.cues.mark(clock::current(), &line);
+        }
+
+        // and starts of the Voice being captured are recorded
+        if let Some(capture) = &mut self.capture {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let started = words.first() == Some(&"start")
+                && (words[1..] == ["all"] || words[1..].chunks(2).any(|pair| {
+                    matches!(pair[0], "-v" | "--voice") && pair.get(1) == Some(&capture.voice.as_str())
+                }));
+            if started {
+                capture.record(clock::current());
+            }
+        }
+
+        Ok(())
+    }
+
+    // once a capture's bars have gone by, the Seq it made;
+    // checked before every line, so a capture is converted
+    // by whatever is entered next
+    pub fn poll_capture(&mut self) -> Option<CmdResult<Plan>> {
+        match &self.capture {
+            Some(capture) if capture.done(clock::current()) => Some(self.end_capture()),
+            _ => None,
+        }
+    }
+
+    fn end_capture(&mut self) -> CmdResult<Plan> {
+        let capture = self.capture.take().ok_or(CmdErr::Formatting {
+            err: "No capture running; use 'capture <voice> <bars>'".to_string(),
+        })?;
+
+        if capture.hits() == 0 {
+            return Err(CmdErr::Formatting {
+                err: format!("Captured nothing for {}", capture.voice),
+            });
         }
+
+        tui::ok(format!("Captured {} hits on {}", capture.hits(), capture.voice));
+        self.plan(capture.seq_line())
     }
 
     // CmdResults (returned directly to command thread)
@@ -367,147 +2004,132 @@ impl CmdProcessor {
             })?;
         let name = name.to_string();
 
+        // every slot in the engine's pool is taken
+        let pool = self.engine_state.pool;
+        if self.engine_state.voice_count() >= pool {
+            return Err(CmdErr::Formatting {
+                err: format!("All {pool} Voice slots are in use; unload one first (or raise voices in blast.conf)"),
+            });
+        }
+
         let track = self.find_track(name.clone())?;
         let track_idx = track.idx;
-        
-        // initialize tempo_repr with an idx of 0 because
-        // a Voice will only ever have one personal TempoState
-        let mut tempo_repr = TempoRepr::new(0usize);
+        let track = track.clone();
+        let channels = track.channels;
+        let channel_mask = track.channel_mask;
 
         // if a Voice by this name (currently the track name)
         // already exists, then return error
-        match self.find_voice(name.clone()) {
-            Ok(voice) => return Err(CmdErr::AlreadyIs { 
+        if self.find_voice(name.clone()).is_ok() {
+            return Err(CmdErr::AlreadyIs { 
                 ty: "Voice".to_string(), 
-                name: name 
-            }),
-            Err(_) => (),
+                name 
+            });
         }
-        
-        while let Some(arg) = args.next() {
-            match arg {
-                "-t" | "--tempo" => {
-                    let t_arg = args
-                        .next()
-                        .ok_or(CmdErr::MissingArg { 
-                            arg: "unit".to_string(), 
-                            cmd: "load -t/--tempo".to_string() 
-                        })?;
 
-                    let mut t_args = t_arg.split(':');
+        let flags = LoadFlags::parse_flags(self, &mut args, "load")?;
+        // a Voice will only ever have one personal TempoState,
+        // so its TempoRepr has an idx of 0
+        let tempo_repr = match flags.tempo {
+            Some(raw) => self.flag_tempo_repr(&raw, TempoMode::Voice, 0, true, "load")?,
+            None => TempoRepr::new(0usize),
+        };
+        // trim silence and take the proposed loop points
+        let autoloop = track.autoloop.filter(|_| flags.autoloop);
+        // start at a gain that brings the Track to a
+        // loudness (TARGET_LUFS, unless one's given)
+        let gain = flags.normalize.map_or(1.0, |target| track.loudness.gain_to(target));
 
-                    let u = t_args.next().unwrap();
-                    if u == "c" {
-                        // find TempoContext
-                        let tc_name = t_args
-                            .next()
-                            .ok_or(CmdErr::MissingArg { 
-                                arg: "name".to_string(), 
-                                cmd: "load -t c:???".to_string() 
-                            })?;
-                        
-                        tempo_repr = match self.find_tc(tc_name.to_string()) {
-                            Ok(tc) => TempoRepr::clone_owner(&tc),
-                            Err(error) => return Err(error.into()),
-                        };
-                        continue;
-                    }
-                    if u == "g" {
-                        // find Group
-                        let g_name = t_args
-                            .next()
-                            .ok_or(CmdErr::MissingArg { 
-                                arg: "name".to_string(), 
-                                cmd: "load -t g:???".to_string() 
-                            })?;
+        // if this is the first Voice,
+        // it will be indexed at 0
+        let idx = self.engine_state.voices.len();
+        let frames = autoloop.map_or(track.frames, |al| al.end - al.start);
+        let repr = VoiceRepr::new(idx, &track, frames, TempoRepr::clone(&tempo_repr));
+        let meter = Arc::clone(&repr.meter);
+        self.engine_state.voices.insert(name, repr);
 
-                        tempo_repr = match self.find_group(g_name.to_string()) {
-                            Ok(g) => TempoRepr::clone_owner(&g.tempo),
-                            Err(error) => return Err(error.into()),
-                        };
-                        continue;
-                    }
+        let out_channels = self.engine_state.out_channels;
+        let bus = vec![0.0; BLOCK * out_channels];
+        let routing = Routing::for_layout(channel_mask, channels, out_channels);
 
-                    // make new TempoState from matched arguments
-                                    
-                    let unit = match u {
-                        "s" => TempoUnit::Samples,
-                        "m" => TempoUnit::Millis,
-                        "b" => TempoUnit::Bpm,
-                        _ => return Err(CmdErr::InvalidArg { 
-                            arg: u.to_owned(), 
-                            cmd: "load -t".to_string() 
-                        }),
-                    };
+        let tempo_state = tempo_repr.made();
+        Ok(Command::Load(LoadArgs{track_idx, tempo_repr, autoloop, gain, meter, bus, routing, tempo_state, slots: Unshared::slots()}))
+    }
 
-                    let interval = t_args
-                        .next()
-                        .ok_or(CmdErr::MissingArg { 
-                            arg: "interval".to_string(), 
-                            cmd: "load -t".to_string() 
-                        })
-                        .and_then(|raw| {
-                            raw.parse::<f32>()
-                               .map_err(|_| CmdErr::InvalidArg { 
-                                    arg: raw.to_owned(), 
-                                    cmd: "load -t".to_string() 
-                               })
-                        })?;
+    // clone <voice> <newname>
+    //
+    // a Voice on the same Track that plays on its own, starting
+    // from voice's gain, velocity, pan, offset, loop, trim,
+    // routing (and out), sends, stretch, pitch and keymap; Processes, Effects and
+    // polyphony aren't copied. A Voice in a Group clones to one
+    // that isn't in any
+    fn try_clone(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let (name, new) = match (args.next(), args.next()) {
+            (Some(name), Some(new)) => (name.to_string(), new.to_string()),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "voice and new name".to_string(),
+                cmd: "clone".to_string(),
+            }),
+        };
+        if new.contains('.') {
+            return Err(CmdErr::InvalidArg { arg: new, cmd: "clone".to_string() });
+        }
 
-                    tempo_repr.init(TempoMode::Voice, unit, interval);
-                }
-                // no argument matched
-                _ => return Err(CmdErr::InvalidArg { 
-                    arg: arg.to_owned(), 
-                    cmd: "load".to_string() 
-                }),
-            }
+        let pool = self.engine_state.pool;
+        if self.engine_state.voice_count() >= pool {
+            return Err(CmdErr::Formatting {
+                err: format!("All {pool} Voice slots are in use; unload one first (or raise voices in blast.conf)"),
+            });
         }
-        // if this is the first Voice,
-        // it will be indexed at 0
+
+        if self.engine_state.voices.contains_key(&new) {
+            return Err(CmdErr::AlreadyIs { ty: "Voice".to_string(), name: new });
+        }
+
+        let src = self.voice_idx(name.clone())?;
         let idx = self.engine_state.voices.len();
-        self.engine_state.voices.insert(
-            name,
-            VoiceRepr::new(idx, TempoRepr::clone(&tempo_repr))
-        );
-        
-        Ok(Command::Load(LoadArgs{track_idx, tempo_repr}))
+        let repr = self.find_voice(name)?.cloned(idx);
+        let channels = repr.channels;
+        let tempo_repr = TempoRepr::clone(&repr.tempo);
+        let meter = Arc::clone(&repr.meter);
+        self.engine_state.voices.insert(new, repr);
+
+        // the engine copies src's routing into this, which is
+        // the same shape, so it doesn't allocate
+        let out_channels = self.engine_state.out_channels;
+        let bus = vec![0.0; BLOCK * out_channels];
+        let routing = Routing::empty(channels, out_channels);
+
+        let tempo_state = tempo_repr.made();
+        Ok(Command::Clone(CloneArgs { src, tempo_repr, meter, bus, routing, tempo_state, slots: Unshared::slots() }))
     }
 
-    // the following could start multiple things at the same time
-    // (e.g. *Args could hold a Vec<Idx>);
-    // maybe implement "all" as a reserved word
+    // start|pause|resume|stop -v|-g|-t <name> [-v|-g|-t <name>]...
+    // start|pause|resume|stop all
     //
     fn try_start(&mut self, args: String) -> CmdResult<Command> {
-        let (ty, name) = self.parse_type_and_name(
-            args, "start".to_string()
-        )?;
-        let idx = self.get_idx(ty, name)?;
-        Ok(Command::Start(StartArgs{ idx }))
+        let idxs = self.get_targets(args, "start".to_string())?;
+        self.set_transport(&idxs, Transport::Playing, true);
+        Ok(Command::Start(StartArgs{ idxs }))
     }
 
     fn try_pause(&mut self, args: String) -> CmdResult<Command> {
-        let (ty, name) = self.parse_type_and_name(
-            args, "pause".to_string()
-        )?;
-        let idx = self.get_idx(ty, name)?;
-        Ok(Command::Pause(PauseArgs{ idx }))
+        let idxs = self.get_targets(args, "pause".to_string())?;
+        self.set_transport(&idxs, Transport::Paused, false);
+        Ok(Command::Pause(PauseArgs{ idxs }))
     } 
 
     fn try_resume(&mut self, args: String) -> CmdResult<Command> {
-        let (ty, name) = self.parse_type_and_name(
-            args, "resume".to_string()
-        )?;
-        let idx = self.get_idx(ty, name)?;
-        Ok(Command::Resume(ResumeArgs{ idx }))
+        let idxs = self.get_targets(args, "resume".to_string())?;
+        self.set_transport(&idxs, Transport::Playing, false);
+        Ok(Command::Resume(ResumeArgs{ idxs }))
     }  
 
     fn try_stop(&mut self, args: String) -> CmdResult<Command> {
-        let (ty, name) = self.parse_type_and_name(
-            args, "stop".to_string()
-        )?;
-        let idx = self.get_idx(ty, name)?;
-        Ok(Command::Stop(StopArgs{ idx }))
+        let idxs = self.get_targets(args, "stop".to_string())?;
+        self.set_transport(&idxs, Transport::Stopped, true);
+        Ok(Command::Stop(StopArgs{ idxs }))
     } 
 
     fn try_unload(&mut self, name: String) -> CmdResult<Command> {
@@ -529,7 +2151,7 @@ impl CmdProcessor {
         // since all Voices after the removed Voice will be 
         // shifted to the left, decrease all VoiceReprs with
         // an idx greater than the removed Voice's
-        for (_, mut voice) in &mut self.engine_state.voices {
+        for voice in self.engine_state.voices.values_mut() {
             if voice.idx > idx {
                 voice.idx -= 1;
             }
@@ -538,6 +2160,113 @@ impl CmdProcessor {
         Ok(Command::Unload(UnloadArgs{ idx }))
     }
 
+    // keymap <voice> root <note> [span <semitones>] [-m varispeed|pitch]
+    // keymap <voice> off
+    //
+    // notes (C4 or 60) within span (default 24) either side of
+    // the root repitch the Voice and play it from the top
+    fn try_keymap(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "keymap".to_string(),
+            })?;
+        let idx = self.voice_idx(name.to_string())?;
+
+        let invalid = |arg: &str, cmd: &str| CmdErr::InvalidArg {
+            arg: arg.to_owned(),
+            cmd: cmd.to_string(),
+        };
+
+        let keymap = match args.next() {
+            Some("off") => None,
+            Some("root") => {
+                // left off, it's the note the file says it plays at
+                let root = match args.clone().next() {
+                    Some(raw) if !matches!(raw, "span" | "-m" | "--mode") => {
+                        args.next();
+                        notes::parse(raw).ok_or(invalid(raw, "keymap root"))?
+                    }
+                    _ => self.file_root(name)?,
+                };
+
+                let mut keymap = KeyMap { root, span: 24, mode: KeyMode::Varispeed };
+                while let Some(arg) = args.next() {
+                    let cmd = match arg {
+                        "span" => "keymap span",
+                        "-m" | "--mode" => "keymap -m",
+                        _ => return Err(invalid(arg, "keymap")),
+                    };
+                    let raw = args.next().ok_or(CmdErr::MissingArg {
+                        arg: arg.trim_start_matches('-').to_string(),
+                        cmd: cmd.to_string(),
+                    })?;
+                    match (arg, raw) {
+                        ("span", _) => {
+                            // the pitch grains go four octaves either way
+                            keymap.span = raw
+                                .parse::<u8>()
+                                .ok()
+                                .filter(|s| *s <= 48)
+                                .ok_or(invalid(raw, cmd))?;
+                        }
+                        (_, "varispeed") => keymap.mode = KeyMode::Varispeed,
+                        (_, "pitch") => keymap.mode = KeyMode::Pitch,
+                        _ => return Err(invalid(raw, cmd)),
+                    }
+                }
+                Some(keymap)
+            }
+            Some(other) => return Err(invalid(other, "keymap")),
+            None => return Err(CmdErr::MissingArg {
+                arg: "root/off".to_string(),
+                cmd: "keymap".to_string(),
+            }),
+        };
+
+        self.find_voice(name.to_string())?.keymap = keymap;
+        Ok(Command::Keymap(KeymapArgs { idx, keymap }))
+    }
+
+    // the base note the file under voice was saved with
+    fn file_root(&mut self, voice: &str) -> CmdResult<u8> {
+        let track = self.find_voice(voice.to_string())?.track;
+        self.engine_state.tracks
+            .values()
+            .find(|t| t.idx == track)
+            .and_then(|t| t.root_note)
+            .ok_or(CmdErr::Formatting {
+                err: format!("{voice}'s file has no base note; give one, e.g. 'keymap {voice} root C4'"),
+            })
+    }
+
+    // note <voice> <note>
+    fn try_note(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let (name, raw) = match (args.next(), args.next()) {
+            (Some(name), Some(raw)) => (name, raw),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "voice and note".to_string(),
+                cmd: "note".to_string(),
+            }),
+        };
+        let idx = self.voice_idx(name.to_string())?;
+
+        let keymap = self.find_voice(name.to_string())?.keymap.ok_or(CmdErr::Formatting {
+            err: format!("{name} has no keymap; use 'keymap {name} root <note>'"),
+        })?;
+        let note = notes::parse(raw)
+            .filter(|n| keymap.ratio(*n).is_some())
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: "note".to_string(),
+            })?;
+
+        Ok(Command::Note(NoteArgs { idx, note }))
+    }
+
     fn try_velocity(&mut self, args: String) -> CmdResult<Command> {
         let mut args = args.splitn(2, ' ');
         
@@ -571,132 +2300,408 @@ impl CmdProcessor {
         Ok(Command::Velocity(VelocityArgs{ idx, val }))
     }
 
-    fn try_group(&mut self, args: String) -> CmdResult<Command> {
+    // <voice> <value>, for Commands that take one number
+    fn parse_voice_val(&mut self, args: String, cmd: &str, valid: impl Fn(f32) -> bool) -> CmdResult<(Idx, f32)> {
         let mut args = args.split_whitespace();
         let name = args
             .next()
-            .ok_or(CmdErr::MissingArg { 
-                arg: "name".to_string(), 
-                cmd: "group".to_string() 
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: cmd.to_string(),
             })?;
 
-        // -t tempo -v voices
+        let idx = self.voice_idx(name.to_string())?;
 
-        let mut tempo = TempoRepr::new(0);
-        tempo.init(TempoMode::Group, TempoUnit::Bpm, 240.0);
-        let mut voices = HashMap::<String, VoiceRepr>::new();
-        // save Voice indices as Voices are collected,
-        // since these indices will change when added to voices
-        let mut v_ids = Vec::<usize>::new();
+        let raw = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "value".to_string(),
+                cmd: cmd.to_string(),
+            })?;
 
-        while let Some(arg) = args.next() {
-            match arg {
-                "-t" | "--tempo" => {
-                    match args.next() {
-                        Some(t) => {
-                            let mut t_args = t.split(':');
-                            let u_str = t_args
-                                .next()
-                                .ok_or(CmdErr::MissingArg { 
-                                    arg: "unit".to_string(), 
-                                    cmd: "group -t".to_string() 
-                                })?;
-
-                            if u_str == "c" {
-                                let tc_name = t_args
-                                    .next()
-                                    .ok_or(CmdErr::MissingArg { 
-                                        arg: "TempoContext name".to_string(), 
-                                        cmd: "group -t".to_string() 
-                                    })?;
+        let val = raw
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite() && valid(*v))
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })?;
 
-                                match self.find_tc(name.to_string()) {
-                                    Ok(tc) => tempo = TempoRepr::clone(&tc),
-                                    Err(error) => return Err(error.into()),
-                                }
-                            } else {
-                                let unit = match u_str {
-                                    "s" => TempoUnit::Samples,
-                                    "m" => TempoUnit::Millis,
-                                    "b" => TempoUnit::Bpm,
-                                    _ => return Err(CmdErr::InvalidArg { 
-                                            arg: u_str.to_string(), 
-                                            cmd: "group -t".to_string() 
-                                        }),
-                                };
-                                let interval = t_args
-                                    .next()
-                                    .ok_or(CmdErr::MissingArg { 
-                                        arg: "interval".to_string(), 
-                                        cmd: "group -t".to_string() 
-                                    })
-                                    .and_then(|raw| {
-                                        raw.parse::<f32>()
-                                           .map_err(|_| CmdErr::InvalidArg { 
-                                                arg: raw.to_string(), 
-                                                cmd: "group -t".to_string() 
-                                            })
-                                    })?;
-    
-                                let mut new_tempo = TempoRepr::new(0);
-                                new_tempo.init(TempoMode::Group, unit, interval);
-                                tempo = new_tempo;
-                            }
-                        }
-                        None => return Err(CmdErr::MissingArg { 
-                            arg: "arguments".to_string(), 
-                            cmd: "group -t".to_string() 
-                        }),
-                    }
+        Ok((idx, val))
+    }
+
+    fn try_gain(&mut self, args: String) -> CmdResult<Command> {
+        // gain master <val>
+        // gain -v/-g/-b <name> <val>
+        //
+        // val is linear, or in decibels with a dB suffix (e.g. -6dB)
+        let mut args = args.split_whitespace();
+        let target = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "master/-v/-g/-b".to_string(),
+                cmd: "gain".to_string(),
+            })?;
+
+        let idx = match target {
+            "master" => None,
+            "-v" | "--voice" | "-g" | "--group" | "-b" | "--bus" => {
+                let name = args
+                    .next()
+                    .ok_or(CmdErr::MissingArg {
+                        arg: "name".to_string(),
+                        cmd: format!("gain {}", target),
+                    })?;
+
+                match target {
+                    "-v" | "--voice" => Some(self.voice_idx(name.to_string())?),
+                    "-b" | "--bus" => Some(Idx::Bus(self.find_bus(name.to_string())?)),
+                    _ => Some(self.get_idx(target.to_string(), name.to_string())?),
                 }
-                "-v" | "--voices" => {
-                    match args.next() {
-                        Some(v) => {
-                            let names: Vec<_> = v.split(',').collect();
-
-                            // need to collect all indices of the Voices that
-                            // are being removed; then sort high to low
-                            // and decrement all other indices -ge
-                            for name in names {
-                                let name = name.to_string();
-                                match self.engine_state.voices.remove(&name) {
-                                    Some(mut voice) => {
-                                        v_ids.push(voice.idx);
-                                        voice.idx = voices.len(); // assign new index
-                                                                  // in Group's Vec
-                                        voices.insert(name, voice);
-                                    }
-                                    None => return Err(CmdErr::NoVoice { 
-                                        name: name, 
-                                        group: None 
-                                    }),
-                                }
-                            }
+            }
+            _ => return Err(CmdErr::InvalidArg {
+                arg: target.to_owned(),
+                cmd: "gain".to_string(),
+            }),
+        };
 
-                            // sort removed voices in reverse
-                            // so that the remaining voice.idx
-                            // are decremented correctly
-                            let mut sorted = v_ids.clone();
-                            sorted.sort_by(|a, b| b.cmp(a));
-
-                            for removed_id in sorted {
-                                for (_, v) in &mut self.engine_state.voices {
-                                    if v.idx > removed_id {
-                                        v.idx -= 1;
-                                    }
-                                }
+        let raw = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "value".to_string(),
+                cmd: "gain".to_string(),
+            })?;
+
+        let val = gain_val(raw).ok_or(CmdErr::InvalidArg {
+            arg: raw.to_owned(),
+            cmd: "gain".to_string(),
+        })?;
+
+        Ok(Command::Gain(GainArgs { idx, val }))
+    }
+
+    fn try_route(&mut self, args: String) -> CmdResult<Command> {
+        // route -v <name> <src>:<out>[:<gain>] [...]
+        // route -v <name> default
+        //
+        // connections replace the Voice's whole routing;
+        // channels are counted from 0
+        let mut args = args.split_whitespace();
+        let (ty, name) = match (args.next(), args.next()) {
+            (Some(ty), Some(name)) => (ty, name),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "-v and name".to_string(),
+                cmd: "route".to_string(),
+            }),
+        };
+
+        if !matches!(ty, "-v" | "--voice") {
+            return Err(CmdErr::InvalidArg {
+                arg: ty.to_owned(),
+                cmd: "route".to_string(),
+            });
+        }
+
+        let idx = self.voice_idx(name.to_string())?;
+        let voice = self.find_voice(name.to_string())?;
+        let (sources, track) = (voice.channels, voice.track);
+        let mask = self.engine_state.tracks.values().find(|t| t.idx == track).and_then(|t| t.channel_mask);
+        let outputs = self.engine_state.out_channels;
+
+        let mut conns = args.peekable();
+        let routing = match conns.peek() {
+            None => return Err(CmdErr::MissingArg {
+                arg: "src:out[:gain] or default".to_string(),
+                cmd: "route".to_string(),
+            }),
+            Some(&"default") => Routing::for_layout(mask, sources, outputs),
+            Some(_) => {
+                let mut routing = Routing::empty(sources, outputs);
+                for conn in conns {
+                    let invalid = || CmdErr::InvalidArg {
+                        arg: conn.to_owned(),
+                        cmd: format!("route ({} source, {} output channels)", sources, outputs),
+                    };
+
+                    let mut parts = conn.split(':');
+                    let src = parts.next().and_then(|p| p.parse::<usize>().ok());
+                    let out = parts.next().and_then(|p| p.parse::<usize>().ok());
+                    let gain = match parts.next() {
+                        Some(g) => g.parse::<f32>().ok(),
+                        None => Some(1.0),
+                    };
+
+                    match (src, out, gain, parts.next()) {
+                        (Some(src), Some(out), Some(gain), None) => {
+                            if !routing.connect(src, out, gain) {
+                                return Err(invalid());
                             }
                         }
-                        None => return Err(CmdErr::MissingArg { 
-                            arg: "arguments".to_string(), 
-                            cmd: "group -v".to_string() 
-                        }),
+                        _ => return Err(invalid()),
                     }
                 }
-                _ => return Err(CmdErr::InvalidArg { 
-                        arg: arg.to_owned(), 
-                        cmd: "group".to_string() 
-                    }),
+                routing
+            }
+        };
+
+        Ok(Command::Route(RouteArgs { idx, routing }))
+    }
+
+    fn try_out(&mut self, args: String) -> CmdResult<Command> {
+        // out <voice> <ch>[,<ch>...]
+        // out <voice> master
+        //
+        // plays a Voice on just those outputs (counted from 0, as
+        // route), past the master's Effects and gain, e.g. a click
+        // to a drummer's headphones on 2,3; master puts it back on
+        // its default routing, through the master
+        let mut args = args.split_whitespace();
+        let (name, raw) = match (args.next(), args.next()) {
+            (Some(name), Some(raw)) => (name, raw),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "voice and channels".to_string(),
+                cmd: "out".to_string(),
+            }),
+        };
+
+        let idx = self.voice_idx(name.to_string())?;
+        if let Idx::GroupVoice(..) = idx {
+            return Err(CmdErr::Formatting {
+                err: format!("{name} plays through its Group; out is for Voices on their own"),
+            });
+        }
+        let voice = self.find_voice(name.to_string())?;
+        let (sources, track) = (voice.channels, voice.track);
+        let mask = self.engine_state.tracks.values().find(|t| t.idx == track).and_then(|t| t.channel_mask);
+        let outputs = self.engine_state.out_channels;
+
+        if raw == "master" {
+            let routing = Routing::for_layout(mask, sources, outputs);
+            return Ok(Command::Out(OutArgs { idx, routing, direct: false }));
+        }
+
+        let chans = raw
+            .split(',')
+            .map(|ch| ch.parse::<usize>().ok().filter(|ch| *ch < outputs))
+            .collect::<Option<Vec<usize>>>()
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: format!("out ({outputs} output channels)"),
+            })?;
+        let routing = Routing::to_outputs(sources, outputs, &chans);
+
+        Ok(Command::Out(OutArgs { idx, routing, direct: true }))
+    }
+
+    fn try_loop(&mut self, args: String) -> CmdResult<Command> {
+        // loop <voice> [on|off|count]
+        //
+        // count is how many times the Voice wraps back
+        // before it plays out to the end
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "loop".to_string(),
+            })?;
+
+        let idx = self.voice_idx(name.to_string())?;
+
+        let looping = match args.next() {
+            None | Some("on") => Looping::On,
+            Some("off") => Looping::Off,
+            Some(raw) => raw
+                .parse::<u32>()
+                .map(Looping::Times)
+                .map_err(|_| CmdErr::InvalidArg {
+                    arg: raw.to_owned(),
+                    cmd: "loop".to_string(),
+                })?,
+        };
+
+        Ok(Command::Loop(LoopArgs { idx, looping }))
+    }
+
+    fn try_poly(&mut self, args: String) -> CmdResult<Command> {
+        // poly <voice> <max|off> [oldest|never]
+        //
+        // max is how many instances can sound at once;
+        // when they all are, a restart either steals the
+        // oldest (default) or is ignored
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "poly".to_string(),
+            })?;
+
+        let idx = self.voice_idx(name.to_string())?;
+
+        let raw = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "max".to_string(),
+                cmd: "poly".to_string(),
+            })?;
+
+        let max = match raw {
+            "off" => 1,
+            _ => raw
+                .parse::<usize>()
+                .ok()
+                .filter(|&max| max >= 1)
+                .ok_or(CmdErr::InvalidArg {
+                    arg: raw.to_owned(),
+                    cmd: "poly".to_string(),
+                })?,
+        };
+
+        let steal = match args.next() {
+            None | Some("oldest") => Steal::Oldest,
+            Some("never") => Steal::Never,
+            Some(other) => {
+                return Err(CmdErr::InvalidArg {
+                    arg: other.to_owned(),
+                    cmd: "poly".to_string(),
+                });
+            }
+        };
+
+        // allocated here, not on the audio thread; tails being
+        // stolen still hold a slot while they fade
+        let pool = match max {
+            1 => Vec::new(),
+            _ => Vec::with_capacity(2 * (max - 1)),
+        };
+
+        Ok(Command::Poly(PolyArgs { idx, max, steal, pool }))
+    }
+
+    // mute/solo -v <voice>|-g <group> [on|off]
+    fn parse_switch(&mut self, args: String, cmd: &str) -> CmdResult<(Idx, bool)> {
+        let mut args = args.split_whitespace();
+        let idx = self.parse_target(&mut args, cmd)?;
+
+        let on = match args.next() {
+            None | Some("on") => true,
+            Some("off") => false,
+            Some(other) => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: cmd.to_string(),
+            }),
+        };
+
+        Ok((idx, on))
+    }
+
+    // the Voice a switch was for, if it was for one
+    fn switched_voice(&mut self, args: &str) -> Option<&mut VoiceRepr> {
+        match args.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-v" | "--voice", name, ..] => self.find_voice(name.to_string()).ok(),
+            _ => None,
+        }
+    }
+
+    // -v <voice> (in a Group or not) or -g <group>
+    fn parse_target<'a>(&mut self, args: &mut impl Iterator<Item = &'a str>, cmd: &str) -> CmdResult<Idx> {
+        let (ty, name) = match (args.next(), args.next()) {
+            (Some(ty), Some(name)) => (ty, name),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "-v/-g and name".to_string(),
+                cmd: cmd.to_string(),
+            }),
+        };
+
+        let idx = match ty {
+            "-v" | "--voice" => self.voice_idx(name.to_string())?,
+            "-g" | "--group" => self.get_idx(ty.to_string(), name.to_string())?,
+            _ => return Err(CmdErr::InvalidArg {
+                arg: ty.to_owned(),
+                cmd: cmd.to_string(),
+            }),
+        };
+        Ok(idx)
+    }
+
+    fn try_fade(&mut self, args: String) -> CmdResult<Command> {
+        // fade <ms>
+        // (length of the ramps on start/stop/pause/resume/unload)
+        let raw = args
+            .split_whitespace()
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "ms".to_string(),
+                cmd: "fade".to_string(),
+            })?;
+
+        let ms = raw
+            .parse::<f32>()
+            .ok()
+            .filter(|ms| *ms >= 0.0)
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: "fade".to_string(),
+            })?;
+
+        self.engine_state.fade_ms = ms;
+        Ok(Command::Fade(FadeArgs { ms }))
+    }
+
+    fn try_group(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg { 
+                arg: "name".to_string(), 
+                cmd: "group".to_string() 
+            })?;
+
+        let flags = GroupFlags::parse_flags(self, &mut args, "group")?;
+        let tempo = match flags.tempo {
+            Some(raw) => self.flag_tempo_repr(&raw, TempoMode::Group, 0, false, "group")?,
+            None => {
+                let mut tempo = TempoRepr::new(0);
+                tempo.init(TempoMode::Group, TempoUnit::Bpm, 240.0);
+                tempo
+            }
+        };
+        let mut voices = HashMap::<String, VoiceRepr>::new();
+        // save Voice indices as Voices are collected,
+        // since these indices will change when added to voices
+        let mut v_ids = Vec::<usize>::new();
+        let mut v_names = Vec::<String>::new();
+
+        // need to collect all indices of the Voices that
+        // are being removed; then sort high to low
+        // and decrement all other indices -ge
+        for name in flags.voices {
+            match self.engine_state.voices.remove(&name) {
+                Some(voice) => {
+                    v_ids.push(voice.idx);
+                    v_names.push(name.clone());
+                    voices.insert(name, voice);
+                }
+                None => return Err(CmdErr::NoVoice { 
+                    name, 
+                    group: None 
+                }),
+            }
+        }
+
+        // sort removed voices in reverse
+        // so that the remaining voice.idx
+        // are decremented correctly
+        let mut sorted = v_ids.clone();
+        sorted.sort_by(|a, b| b.cmp(a));
+
+        for removed_id in sorted {
+            for v in self.engine_state.voices.values_mut() {
+                if v.idx > removed_id {
+                    v.idx -= 1;
+                }
             }
         }
        
@@ -708,7 +2713,8 @@ impl CmdProcessor {
         // assigned to the Group's TempoState
         let mut p_ids: Vec<Vec<usize>> = Vec::new();
 
-        for (_, voice) in &mut voices {
+        for name in &v_names {
+            let voice = voices.get_mut(name).unwrap();
             // if the Voice wasn't assigned a TempoState at birth,
             // it takes on the TempoState of the Group
             // (this is how a Voice's Process is synced with a Group's TempoState
@@ -718,19 +2724,16 @@ impl CmdProcessor {
             if voice.tempo.mode == TempoMode::TBD {
                 voice.tempo = TempoRepr::clone_owner(&tempo);
                 v_flags.push(true);
-                for (_, mut process) in &mut voice.processes {
+                for process in voice.processes.values_mut() {
                     // checks if any Process tempo has TempoMode::TBD
                     // (i.e. it was assigned to its Voice's
                     // uninitialized tempo, in anticipation of the
                     // Voice being added to a Group later)
-                    match &process.tempo {
-                        Some(t) => {
-                            if t.mode == TempoMode::TBD {
-                                process.tempo = Some(TempoRepr::clone_owner(&tempo));
-                            }
-                            p_i.push(process.idx);
+                    if let Some(t) = &process.tempo {
+                        if t.mode == TempoMode::TBD {
+                            process.tempo = Some(TempoRepr::clone_owner(&tempo));
                         }
-                        None => (),
+                        p_i.push(process.idx);
                     }
                 }
             } else {
@@ -740,6 +2743,13 @@ impl CmdProcessor {
             p_ids.push(p_i);
         }
 
+        // the engine fills the Group's Vec<Voice> in reverse order
+        // of the Voices' old indices (see below), so each Voice's
+        // new index is the number of Voices that came after it
+        for (name, old) in v_names.iter().zip(&v_ids) {
+            voices.get_mut(name).unwrap().idx = v_ids.iter().filter(|&id| id > old).count();
+        }
+
         let group = GroupRepr::new(self.engine_state.groups.len(), TempoRepr::clone(&tempo), voices);
 
         self.engine_state.groups.insert(name.to_string(), group);
@@ -758,7 +2768,165 @@ impl CmdProcessor {
             vb.cmp(va)
         });
 
-        Ok(Command::Group(GroupArgs { tempo, vs_fs_ps }))
+        let bus = vec![0.0; BLOCK * self.engine_state.out_channels];
+        let levels = vec![0.0; BLOCK * self.engine_state.out_channels];
+        let tempo_state = tempo.made();
+        let voices = Unshared::with_capacity(vs_fs_ps.len());
+        Ok(Command::Group(GroupArgs { tempo, vs_fs_ps, bus, levels, tempo_state, voices, slots: Unshared::slots() }))
+    }
+
+    // import-stems <dir> as <group> [-t <tempo>] [--resample] [--pad]
+    //
+    // a Voice per file in dir, all in one new Group, so their
+    // transports are one and the stems play in lockstep
+    fn try_import_stems(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let (dir, group) = match (args.next(), args.next(), args.next()) {
+            (Some(dir), Some("as"), Some(group)) => (dir, group),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "<dir> as <group>".to_string(),
+                cmd: "import-stems".to_string(),
+            }),
+        };
+        let flags = StemFlags::parse_flags(self, &mut args, "import-stems")?;
+
+        if self.engine_state.groups.contains_key(group) {
+            return Err(CmdErr::AlreadyIs {
+                ty: "Group".to_string(),
+                name: group.to_string(),
+            });
+        }
+
+        let fixes = stems::Fixes { resample: flags.resample, pad: flags.pad };
+        let files = stems::read(dir, fixes).map_err(|e| CmdErr::FileIo {
+            path: dir.to_string(),
+            err: format!("{e}"),
+        })?;
+
+        // a stem imported before (e.g. on a replay of the history)
+        // is the same Track, and the engine already has it
+        let mut tracks = Vec::<AudioFile>::new();
+        let mut names = Vec::<String>::new();
+        for af in files {
+            let name = af.file_name.clone();
+            if name.contains([',', '.', ' ']) {
+                return Err(CmdErr::InvalidArg {
+                    arg: name,
+                    cmd: "import-stems".to_string(),
+                });
+            }
+
+            match self.engine_state.tracks.get(&name) {
+                Some(track) if track.path == af.path => (),
+                Some(_) => return Err(CmdErr::AlreadyIs {
+                    ty: "Track".to_string(),
+                    name,
+                }),
+                None => {
+                    let idx = self.engine_state.tracks.len();
+                    let repr = TrackRepr::new(idx, &af);
+                    self.engine_state.tracks.insert(name.clone(), repr);
+                    tracks.push(af);
+                }
+            }
+            names.push(name);
+        }
+        // the engine's room for them is made with it
+        if self.engine_state.tracks.len() > MAX_TRACKS {
+            return Err(CmdErr::Formatting {
+                err: format!("A session can have at most {MAX_TRACKS} Tracks"),
+            });
+        }
+
+        let mut commands = Vec::<Command>::new();
+        for name in &names {
+            commands.push(self.try_load(name.clone())?);
+        }
+        let tempo = flags.tempo.map(|t| format!(" -t {t}")).unwrap_or_default();
+        commands.push(self.try_group(format!("{group} -v {}{tempo}", names.join(",")))?);
+
+        Ok(Command::ImportStems(ImportStemsArgs { tracks, commands }))
+    }
+
+    // bounce <group> <name> [<length>]
+    //
+    // renders the Group, through its Processes and Effects (but
+    // not the aux buses or the master), into a new Track that
+    // loads like any other. length is frames, <n>ms or <n>b (beats
+    // of the Group's tempo); without one it runs until the Group's
+    // played out, up to MAX_BOUNCE_SECS
+    //
+    // the session is rebuilt from the history off to the side, as
+    // revert does, and the Group started from the top there along
+    // with every TempoContext; nothing that's playing is touched
+    fn try_bounce(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let (group, name) = match (args.next(), args.next()) {
+            (Some(group), Some(name)) => (group.to_string(), name.to_string()),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "group and name".to_string(),
+                cmd: "bounce".to_string(),
+            }),
+        };
+        if name.contains([',', '.']) {
+            return Err(CmdErr::InvalidArg { arg: name, cmd: "bounce".to_string() });
+        }
+
+        let g = self.find_group(group.clone())?;
+        let (g_idx, tempo) = (g.idx, TempoRepr::clone(&g.tempo));
+        let rate = sample_rate::get().max(1);
+        let frames = match args.next() {
+            None => None,
+            Some(raw) => {
+                let invalid = || CmdErr::InvalidArg { arg: raw.to_owned(), cmd: "bounce".to_string() };
+                let (val, per) = if let Some(ms) = raw.strip_suffix("ms") {
+                    (ms, rate as f32 / 1000.0)
+                } else if let Some(beats) = raw.strip_suffix('b') {
+                    match tempo.interval {
+                        0.0 => return Err(CmdErr::Formatting {
+                            err: format!("{group} has no tempo to count beats in; give frames or ms"),
+                        }),
+                        interval => (beats, convert_interval(&tempo.unit, interval)),
+                    }
+                } else {
+                    (raw, 1.0)
+                };
+                let frames = val.parse::<f32>().ok().filter(|v| v.is_finite() && *v > 0.0).ok_or_else(invalid)? * per;
+                Some((frames.round() as usize).max(1))
+            }
+        };
+
+        // bounced before (e.g. on a replay of the history),
+        // so the engine already has it
+        let path = format!("bounce:{group}");
+        match self.engine_state.tracks.get(&name) {
+            Some(track) if track.path == path => return Ok(Command::Bounce(BounceArgs { track: None })),
+            Some(_) => return Err(CmdErr::AlreadyIs { ty: "Track".to_string(), name }),
+            None => (),
+        }
+        // the engine's room for them is made with it
+        if self.engine_state.tracks.len() >= MAX_TRACKS {
+            return Err(CmdErr::Formatting {
+                err: format!("A session can have at most {MAX_TRACKS} Tracks"),
+            });
+        }
+
+        let (state, mut commands) = self.replay(&self.engine_state.history)?;
+        let idxs = (0..state.tempo_cons.len()).map(Idx::Tempo).chain([Idx::Group(g_idx)]).collect();
+        commands.push(Command::Fade(FadeArgs { ms: 0.0 }));
+        commands.push(Command::Start(StartArgs { idxs }));
+
+        let channels = self.engine_state.out_channels;
+        let samples = offline::bounce(self.engine_state.audio(), channels, commands, g_idx, frames);
+        if samples.is_empty() {
+            return Err(CmdErr::Formatting { err: format!("{group} played nothing to bounce") });
+        }
+
+        let af = AudioFile::new(&path, &name, "bounce", rate, channels as u32, 16, samples);
+        let idx = self.engine_state.tracks.len();
+        self.engine_state.tracks.insert(name, TrackRepr::new(idx, &af));
+
+        Ok(Command::Bounce(BounceArgs { track: Some(af) }))
     }
 
     fn try_tc(&mut self, args: String) -> CmdResult<Command> {
@@ -770,421 +2938,2441 @@ impl CmdProcessor {
                 cmd: "tempocon".to_string() 
             })?;        
 
-        let tempo = args
-            .next()
-            .ok_or(CmdErr::MissingArg {
-                arg: "tempo".to_string(),
-                cmd: "tempocon".to_string()
-            })?;
+        // the engine's room for them is made with it
+        if self.engine_state.tempo_cons.len() >= MAX_TEMPO_CONTEXTS {
+            return Err(CmdErr::Formatting {
+                err: format!("A session can have at most {MAX_TEMPO_CONTEXTS} TempoContexts"),
+            });
+        }
+
+        let tempo = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "tempo".to_string(),
+                cmd: "tempocon".to_string()
+            })?;
+
+        let tempo: Vec<_> = tempo.split(':').collect();
+
+        if tempo.len() != 2 {
+            return Err(CmdErr::TempoFormatting{});
+        }
+
+        let u = tempo.first().unwrap();
+        let unit = match *u {
+            "b" => TempoUnit::Bpm,
+            "m" => TempoUnit::Millis,
+            "s" => TempoUnit::Samples,
+            _ => return Err(CmdErr::InvalidArg {
+                               arg: u.to_string(),
+                               cmd: "-t/--tempo".to_string(),
+                            }),
+        };
+
+        let int_str = tempo.get(1).unwrap();
+        let interval = int_str
+                       .parse::<f32>()
+                       .map_err(|_| CmdErr::InvalidArg {
+                                    arg: tempo[1].to_owned(),
+                                    cmd: "-t/--tempo".to_string(),
+                       })?;
+
+        let mut tempo_state = TempoRepr::new(self.engine_state.tempo_cons.len());
+        tempo_state.init(TempoMode::Context, unit, interval);
+        let ts_clone = TempoRepr::clone(&tempo_state);
+        self.engine_state.tempo_cons.insert(name.to_string(), tempo_state);
+
+        Ok(Command::Tc(TcArgs { tempo_state: ts_clone.made(), tempo: ts_clone }))
+    }
+
+    // tempo ramp -t <tempocontext>|-g <group> <tempo> <n>b|<n>s|<n>ms
+    // tempo -v|-g|-t <name> s|m|b:<interval>
+    //
+    // glides the interval from where it is to tempo
+    // (s|m|b:<interval> or c:<tempocontext>)
+    fn try_tempo(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        match args.next() {
+            Some("ramp") => (),
+            Some(ty @ ("-v" | "--voice" | "-g" | "--group" | "-t" | "--tempocontext")) => {
+                return self.tempo_set(ty, args);
+            }
+            Some(other) => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: "tempo".to_string(),
+            }),
+            None => return Err(CmdErr::MissingArg {
+                arg: "ramp or -v|-g|-t <name>".to_string(),
+                cmd: "tempo".to_string(),
+            }),
+        }
+
+        let (ty, name) = match (args.next(), args.next()) {
+            (Some(ty), Some(name)) => (ty, name.to_string()),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "-t <tempocontext> or -g <group>".to_string(),
+                cmd: "tempo ramp".to_string(),
+            }),
+        };
+        let (idx, owned) = match ty {
+            "-t" | "--tempocontext" => (Idx::Tempo(self.find_tc(name.clone())?.idx), true),
+            "-g" | "--group" => {
+                let group = self.find_group(name.clone())?;
+                (Idx::Group(group.idx), group.tempo.owned)
+            }
+            _ => return Err(CmdErr::InvalidArg {
+                arg: ty.to_owned(),
+                cmd: "tempo ramp".to_string(),
+            }),
+        };
+        // a Group on a TempoContext would steer everything else on it
+        if !owned {
+            return Err(CmdErr::Formatting {
+                err: "That Group follows a TempoContext; ramp the TempoContext".to_string(),
+            });
+        }
+
+        let (raw_to, raw_len) = match (args.next(), args.next()) {
+            (Some(to), Some(len)) => (to, len),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "tempo and duration".to_string(),
+                cmd: "tempo ramp".to_string(),
+            }),
+        };
+        let to = self.beat_len(raw_to, "tempo ramp")?;
+
+        let invalid = || CmdErr::InvalidArg {
+            arg: raw_len.to_owned(),
+            cmd: "tempo ramp".to_string(),
+        };
+        let (count, len): (&str, fn(f32) -> RampLen) = if let Some(ms) = raw_len.strip_suffix("ms") {
+            (ms, |n| RampLen::Samples(convert_interval(&TempoUnit::Millis, n)))
+        } else if let Some(secs) = raw_len.strip_suffix('s') {
+            (secs, |n| RampLen::Samples(n * sample_rate::get() as f32))
+        } else if let Some(beats) = raw_len.strip_suffix('b') {
+            (beats, RampLen::Beats)
+        } else {
+            return Err(invalid());
+        };
+        let count = count
+            .parse::<f32>()
+            .ok()
+            .filter(|n| *n >= 0.0)
+            .ok_or_else(invalid)?;
+
+        // where it ends up is what the state keeps
+        let repr = match idx {
+            Idx::Tempo(_) => self.find_tc(name)?,
+            _ => &mut self.find_group(name)?.tempo,
+        };
+        repr.unit = TempoUnit::Samples;
+        repr.interval = to;
+
+        Ok(Command::Tempo(TempoArgs { idx, action: TempoAction::Ramp { to, len: len(count) } }))
+    }
+
+    // tempo -v|-g|-t <name> s|m|b:<interval>
+    //
+    // sets the tempo of something that keeps its own, straight
+    // away; the beat carries on from where it was
+    //
+    fn tempo_set<'a>(&mut self, ty: &str, mut args: impl Iterator<Item = &'a str>) -> CmdResult<Command> {
+        let (name, raw) = match (args.next(), args.next()) {
+            (Some(name), Some(raw)) => (name.to_string(), raw),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "name and s|m|b:<interval>".to_string(),
+                cmd: "tempo".to_string(),
+            }),
+        };
+        let invalid = |arg: &str| CmdErr::InvalidArg {
+            arg: arg.to_owned(),
+            cmd: "tempo".to_string(),
+        };
+        let (u, val) = raw.split_once(':').ok_or(CmdErr::TempoFormatting {})?;
+        let unit = match u {
+            "s" => TempoUnit::Samples,
+            "m" => TempoUnit::Millis,
+            "b" => TempoUnit::Bpm,
+            _ => return Err(invalid(u)),
+        };
+        let interval = val
+            .parse::<f32>()
+            .ok()
+            .filter(|i| *i > 0.0)
+            .ok_or_else(|| invalid(val))?;
+
+        let (idx, repr, follows) = match ty {
+            "-v" | "--voice" => {
+                let idx = self.voice_idx(name.clone())?;
+                (idx, &mut self.find_voice(name)?.tempo, "That Voice follows a Group or TempoContext")
+            }
+            "-g" | "--group" => {
+                let group = self.find_group(name)?;
+                (Idx::Group(group.idx), &mut group.tempo, "That Group follows a TempoContext")
+            }
+            _ => {
+                let tc = self.find_tc(name)?;
+                (Idx::Tempo(tc.idx), tc, "")
+            }
+        };
+        // setting a shared tempo here would move everything else on it
+        if !repr.owned {
+            return Err(CmdErr::Formatting { err: format!("{follows}; set its tempo there") });
+        }
+        // a Voice given a tempo of its own no longer waits for a Group's
+        if repr.mode == TempoMode::TBD {
+            repr.mode = TempoMode::Voice;
+        }
+        repr.unit = unit;
+        repr.interval = interval;
+
+        Ok(Command::Tempo(TempoArgs { idx, action: TempoAction::Set { unit, interval } }))
+    }
+
+    // meter <beats>[/<note>] [<tempo>]
+    // meter <beats>[/<note>] -t <tempocontext>|-g <group>
+    //
+    // how many beats make a bar; the tempo says what a beat is,
+    // so the note under the line is only checked, not used
+    //
+    // without a target it's the global clock's, which also takes
+    // the beat (s|m|b:<interval> or c:<tempocontext>, default b:120)
+    fn try_meter(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let raw = args.next().ok_or(CmdErr::MissingArg {
+            arg: "beats per bar".to_string(),
+            cmd: "meter".to_string(),
+        })?;
+        let invalid = || CmdErr::InvalidArg {
+            arg: raw.to_owned(),
+            cmd: "meter".to_string(),
+        };
+        let (beats, note) = raw.split_once('/').unwrap_or((raw, "4"));
+        let beats_per_bar = beats
+            .parse::<u32>()
+            .ok()
+            .filter(|n| (1..=64).contains(n))
+            .ok_or_else(invalid)?;
+        match note.parse::<u32>() {
+            Ok(n) if n.is_power_of_two() && n <= 64 => (),
+            _ => return Err(invalid()),
+        }
+
+        let (idx, beat_len) = match args.next() {
+            Some(ty @ ("-t" | "--tempocontext" | "-g" | "--group")) => {
+                let name = args.next().ok_or(CmdErr::MissingArg {
+                    arg: "name".to_string(),
+                    cmd: format!("meter {ty}"),
+                })?.to_string();
+                let (idx, repr) = match ty {
+                    "-t" | "--tempocontext" => {
+                        let tc = self.find_tc(name)?;
+                        (Idx::Tempo(tc.idx), tc)
+                    }
+                    _ => {
+                        let group = self.find_group(name)?;
+                        (Idx::Group(group.idx), &mut group.tempo)
+                    }
+                };
+                if matches!(idx, Idx::Group(_)) && !repr.owned {
+                    return Err(CmdErr::Formatting {
+                        err: "That Group follows a TempoContext; set the TempoContext's meter".to_string(),
+                    });
+                }
+                repr.beats_per_bar = beats_per_bar;
+                (Some(idx), None)
+            }
+            Some(tempo) => (None, Some(self.beat_len(tempo, "meter")?)),
+            None => (None, Some(convert_interval(&TempoUnit::Bpm, 120.0))),
+        };
+
+        if let Some(extra) = args.next() {
+            return Err(CmdErr::InvalidArg {
+                arg: extra.to_owned(),
+                cmd: "meter".to_string(),
+            });
+        }
+
+        Ok(Command::Meter(MeterArgs { idx, beats_per_bar, beat_len }))
+    }
+
+    // TODO: make able to apply to Group
+    fn try_seq(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg { 
+                arg: "name".to_string(), 
+                cmd: "seq".to_string() 
+            })?;
+        let name = name.to_string();
+
+        // chance decisions are random unless seeded
+        let SeqFlags { name: proc_name, tempo, period, steps, chance, levels, swing, seed } =
+            SeqFlags::parse_flags(self, &mut args, "seq")?;
+
+        // default assign to Process
+        let tempo = match tempo.as_deref() {
+            None => TempoRepr::new(self.find_voice(name.clone())?.proc_tempi.len()),
+            // refer to Voice's TempoState
+            Some("v") => TempoRepr::clone_owner(&self.find_voice(name.clone())?.tempo),
+            Some(raw) => {
+                let idx = self.find_voice(name.clone())?.proc_tempi.len();
+                self.flag_tempo_repr(raw, TempoMode::Process, idx, true, "seq")?
+            }
+        };
+
+        let needs_steps = |flag: &str| CmdErr::Formatting {
+            err: format!("{flag} needs -s/--steps"),
+        };
+        let chance = match chance {
+            Some(_) if steps.is_empty() => return Err(needs_steps("-c/--chance")),
+            Some(raw) => seq_chance(&raw, &steps)?,
+            None => vec![100f32; steps.len()],
+        };
+        let levels = match levels {
+            Some(_) if steps.is_empty() => return Err(needs_steps("-v/--levels")),
+            Some(raw) => seq_levels(&raw, steps.len(), "seq -v")?,
+            None => vec![1f32; steps.len()],
+        };
+        let jit = vec![100f32; steps.len()];
+
+        // TODO: allow for Idx::Group
+        let phase = Arc::new(AtomicUsize::new(NO_STEP));
+        let pattern = PatternRepr {
+            period,
+            steps: steps.clone(),
+            chance: chance.clone(),
+            jit: jit.clone(),
+            levels: levels.clone(),
+            swing,
+            seed,
+            phase: Arc::clone(&phase),
+        };
+
+        let voice = self.find_voice(name.clone())?;
+        let repr = ProcRepr::new(
+            voice.processes.len(),
+            ProcKind::Seq,
+            Idx::Voice(voice.idx), 
+            Some(TempoRepr::clone(&tempo)),
+            Some(pattern),
+        );
+        voice.add_process(proc_name, repr)?;
+        // push tempo to proc_tempi only if owned by the Process
+        if tempo.mode == TempoMode::Process {
+            voice.proc_tempi.insert(
+                voice.proc_tempi.len(), 
+                TempoRepr::clone(&tempo)
+            );
+        }
+
+        let args = SeqArgs {
+            idx: Idx::Voice(voice.idx),
+            tempo_state: tempo.made(),
+            tempo,
+            period,
+            steps,
+            chance,
+            jit,
+            levels,
+            swing,
+            rng: X128P::new(seed.unwrap_or_else(fast_seed)),
+            seed,
+            phase,
+        };
+
+        Ok(Command::Seq(args))
+    }
+
+    // seq export <voice> <file.mid> [-n <seq>] [-r <cycles>] [-k <note>]
+    //
+    // writes a Seq (the Voice's only one, unless named) as a MIDI
+    // file: chance is rolled once per step and swing applied, so
+    // what's written is one way it could play (the way a seeded
+    // one does), on note C2 unless given another
+    fn try_seq_export(&mut self, args: String) -> CmdResult<()> {
+        let mut args = args.split_whitespace().skip(1);
+        let (v_name, path) = match (args.next(), args.next()) {
+            (Some(v_name), Some(path)) => (v_name.to_string(), path.to_string()),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "voice and file".to_string(),
+                cmd: "seq export".to_string(),
+            }),
+        };
+        let SeqExportFlags { name: p_name, repeat: cycles, key } = SeqExportFlags::parse_flags(self, &mut args, "seq export")?;
+
+        let voice = self.find_voice(v_name.clone())?;
+        let mut seqs = voice.processes.iter().filter(|(name, p)| {
+            p.pattern.is_some() && p_name.as_ref().is_none_or(|p_name| p_name == *name)
+        });
+        let pattern = match (seqs.next(), seqs.next()) {
+            (Some((_, p)), None) => p.pattern.as_ref().unwrap(),
+            (None, _) => return Err(CmdErr::NoItem {
+                ty: "Seq".to_string(),
+                name: match &p_name {
+                    Some(p_name) => format!("{v_name}.{p_name}"),
+                    None => v_name,
+                },
+            }),
+            (Some(_), Some(_)) => return Err(CmdErr::Formatting {
+                err: format!("{v_name} has more than one Seq; name one with -n"),
+            }),
+        };
+
+        let clip = pattern.flatten(cycles, key);
+        smf::write(&path, &clip).map_err(|e| CmdErr::FileIo {
+            path: path.clone(),
+            err: format!("{e}"),
+        })?;
+        tui::ok(format!("Wrote {} notes to {path}", clip.notes.len()));
+        Ok(())
+    }
+
+    // seq import <voice> <file.mid> [-k <note>] [<seq flags>]
+    //
+    // adds a Seq playing a MIDI clip's notes (only those on note,
+    // if given), a quarter note to a unit of its tempo; the rest
+    // are seq's own flags (-t, -n, -c, ...), and what's kept in
+    // the history is the seq line it makes, not the file
+    fn try_seq_import(&mut self, args: String) -> CmdResult<Plan> {
+        let mut args = args.split_whitespace().skip(1);
+        let (v_name, path) = match (args.next(), args.next()) {
+            (Some(v_name), Some(path)) => (v_name, path),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "voice and file".to_string(),
+                cmd: "seq import".to_string(),
+            }),
+        };
+        let mut key: Option<u8> = None;
+        let mut rest = Vec::<&str>::new();
+        while let Some(arg) = args.next() {
+            match arg {
+                "-k" | "--key" => {
+                    let raw = args.next().ok_or(CmdErr::MissingArg { arg: "note".to_string(), cmd: "seq import -k".to_string() })?;
+                    key = Some(self.flag_note(raw, "seq import -k")?);
+                }
+                _ => rest.push(arg),
+            }
+        }
+
+        let clip = smf::read(path).map_err(|e| CmdErr::FileIo {
+            path: path.to_string(),
+            err: format!("{e}"),
+        })?;
+        let mut line = clip_seq_line(v_name, &clip, key)?;
+        for arg in rest {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        self.plan(line)
+    }
+
+    // capture <voice> <bars> [-t <tempo>] [-b <beats per bar>] [-q <steps per beat>]
+    // capture end
+    // capture cancel
+    //
+    // records `start -v <voice>` for that many bars (from now),
+    // then adds a Seq playing them back; tempo is a beat as
+    // s|m|b:<interval> or c:<tempocontext> (default b:120),
+    // with 4 beats per bar and 4 steps per beat
+    fn try_capture(&mut self, args: String) -> CmdResult<Option<Plan>> {
+        let mut args = args.split_whitespace();
+        let first = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "voice/end/cancel".to_string(),
+                cmd: "capture".to_string(),
+            })?;
+
+        match first {
+            "end" => return self.end_capture().map(Some),
+            "cancel" => {
+                self.capture = None;
+                return Ok(None);
+            }
+            _ => (),
+        }
+
+        if let Some(capture) = &self.capture {
+            return Err(CmdErr::AlreadyIs {
+                ty: "capture running".to_string(),
+                name: capture.voice.clone(),
+            });
+        }
+
+        let voice = first.to_string();
+        self.find_voice(voice.clone())?;
+
+        let raw = args.next().ok_or(CmdErr::MissingArg {
+            arg: "bars".to_string(),
+            cmd: "capture".to_string(),
+        })?;
+        let bars = self.flag_count(raw, "capture")?;
+        let flags = CaptureFlags::parse_flags(self, &mut args, "capture")?;
+
+        self.capture = Some(Capture::new(
+            voice,
+            clock::current(),
+            bars,
+            flags.beats,
+            flags.tempo,
+            flags.division,
+        ));
+
+        Ok(None)
+    }
+
+    // the length of a beat in samples, from a tempo
+    // given as b:<bpm>, m:<ms>, s:<samples> or c:<tempocontext>
+    fn beat_len(&mut self, raw: &str, cmd: &str) -> CmdResult<f32> {
+        let (u, val) = raw.split_once(':').ok_or(CmdErr::TempoFormatting {})?;
+
+        let beat = match u {
+            "c" => {
+                let tc = self.find_tc(val.to_string())?;
+                convert_interval(&tc.unit, tc.interval)
+            }
+            _ => {
+                let unit = match u {
+                    "s" => TempoUnit::Samples,
+                    "m" => TempoUnit::Millis,
+                    "b" => TempoUnit::Bpm,
+                    _ => return Err(CmdErr::InvalidArg {
+                        arg: u.to_owned(),
+                        cmd: cmd.to_string(),
+                    }),
+                };
+                let interval = val
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|i| *i > 0.0)
+                    .ok_or(CmdErr::InvalidArg {
+                        arg: val.to_owned(),
+                        cmd: cmd.to_string(),
+                    })?;
+                convert_interval(&unit, interval)
+            }
+        };
+        Ok(beat)
+    }
+
+    // flag parsers for #[derive(CommandArgs)]
+
+    // 0..1
+    fn flag_unit(&mut self, raw: &str, cmd: &str) -> CmdResult<f32> {
+        raw.parse::<f32>()
+            .ok()
+            .filter(|v| (0.0..=1.0).contains(v))
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })
+    }
+
+    // 1 or more
+    fn flag_count(&mut self, raw: &str, cmd: &str) -> CmdResult<usize> {
+        raw.parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })
+    }
+
+    // 0 or more
+    fn flag_depth(&mut self, raw: &str, cmd: &str) -> CmdResult<f32> {
+        raw.parse::<f32>()
+            .ok()
+            .filter(|d| *d >= 0.0)
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })
+    }
+
+    fn flag_wave(&mut self, raw: &str, cmd: &str) -> CmdResult<Wave> {
+        match raw {
+            "sine" => Ok(Wave::Sine),
+            "tri" | "triangle" => Ok(Wave::Triangle),
+            "square" => Ok(Wave::Square),
+            "random" => Ok(Wave::Random),
+            _ => Err(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            }),
+        }
+    }
+
+    // percent of a subdivision that every other one is late by;
+    // subdivisions are half a unit of tempo, or 1/n with percent:n
+    // (e.g. 30:4)
+    fn flag_swing(&mut self, raw: &str, cmd: &str) -> CmdResult<Swing> {
+        let invalid = || CmdErr::InvalidArg {
+            arg: raw.to_owned(),
+            cmd: cmd.to_string(),
+        };
+
+        let (percent, division) = match raw.split_once(':') {
+            Some((p, n)) => (p, n.parse::<f32>().map_err(|_| invalid())?),
+            None => (raw, 2.0),
+        };
+        let percent = percent
+            .parse::<f32>()
+            .ok()
+            .filter(|p| (0.0..100.0).contains(p))
+            .ok_or_else(invalid)?;
+        if division <= 0.0 || !division.is_finite() {
+            return Err(invalid());
+        }
+
+        Ok(Swing { amount: percent / 100.0, division })
+    }
+
+    // left for the Command to make sense of
+    fn flag_text(&mut self, raw: &str, _cmd: &str) -> CmdResult<String> {
+        Ok(raw.to_string())
+    }
+
+    fn flag_float(&mut self, raw: &str, cmd: &str) -> CmdResult<f32> {
+        raw.parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })
+    }
+
+    fn flag_seed(&mut self, raw: &str, cmd: &str) -> CmdResult<u64> {
+        raw.parse::<u64>().map_err(|_| CmdErr::InvalidArg {
+            arg: raw.to_owned(),
+            cmd: cmd.to_string(),
+        })
+    }
+
+    // a name (C2) or MIDI number
+    fn flag_note(&mut self, raw: &str, cmd: &str) -> CmdResult<u8> {
+        notes::parse(raw).ok_or(CmdErr::InvalidArg {
+            arg: raw.to_owned(),
+            cmd: cmd.to_string(),
+        })
+    }
+
+    // comma-separated
+    fn flag_names(&mut self, raw: &str, _cmd: &str) -> CmdResult<Vec<String>> {
+        Ok(raw.split(',').map(str::to_string).collect())
+    }
+
+    // comma-separated steps of a Seq
+    fn flag_steps(&mut self, raw: &str, cmd: &str) -> CmdResult<Vec<f32>> {
+        raw.split(',')
+            .map(|step| step.parse::<f32>().map_err(|_| CmdErr::InvalidArg {
+                arg: step.to_owned(),
+                cmd: cmd.to_string(),
+            }))
+            .collect()
+    }
+
+    // <min>-<max>, in bpm
+    fn flag_bpm_range(&mut self, raw: &str, cmd: &str) -> CmdResult<(f32, f32)> {
+        raw.split_once('-')
+            .and_then(|(lo, hi)| Some((lo.parse::<f32>().ok()?, hi.parse::<f32>().ok()?)))
+            .filter(|(lo, hi)| *lo > 0.0 && hi >= lo)
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })
+    }
+
+    // 1 to 16
+    fn flag_rows(&mut self, raw: &str, cmd: &str) -> CmdResult<usize> {
+        raw.parse::<usize>()
+            .ok()
+            .filter(|rows| (1..=16).contains(rows))
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })
+    }
+
+    // a tempo flag's s|m|b:<interval> (a new TempoState, in mode, at
+    // idx), c:<tempocontext>, or g:<group> where shares_group allows
+    // it; anything else (v, say) is for the caller to have handled
+    fn flag_tempo_repr(
+        &mut self,
+        raw: &str,
+        mode: TempoMode,
+        idx: usize,
+        shares_group: bool,
+        cmd: &str,
+    ) -> CmdResult<TempoRepr> {
+        let invalid = |arg: &str| CmdErr::InvalidArg { arg: arg.to_owned(), cmd: format!("{cmd} -t") };
+        let (u, val) = raw.split_once(':').ok_or(CmdErr::TempoFormatting {})?;
+        let unit = match u {
+            "c" => return Ok(TempoRepr::clone_owner(self.find_tc(val.to_string())?)),
+            "g" if shares_group => return Ok(TempoRepr::clone_owner(&self.find_group(val.to_string())?.tempo)),
+            "s" => TempoUnit::Samples,
+            "m" => TempoUnit::Millis,
+            "b" => TempoUnit::Bpm,
+            _ => return Err(invalid(u)),
+        };
+        let interval = val
+            .parse::<f32>()
+            .ok()
+            .filter(|i| *i > 0.0 && i.is_finite())
+            .ok_or_else(|| invalid(val))?;
+
+        let mut repr = TempoRepr::new(idx);
+        repr.init(mode, unit, interval);
+        Ok(repr)
+    }
+
+    // delay -v|-g <name> <time>|off [-f <feedback>] [-m <mix>]
+    //
+    // time is s:<samples>, m:<ms>, or a note division like 1/8
+    // (a quarter note being one unit of the owner's TempoState)
+    fn try_delay(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace().peekable();
+        let idx = self.parse_effect_target(&mut args, "delay")?;
+
+        let raw = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "time".to_string(),
+                cmd: "delay".to_string(),
+            })?;
+        let invalid = |arg: &str, cmd: &str| CmdErr::InvalidArg {
+            arg: arg.to_owned(),
+            cmd: cmd.to_string(),
+        };
+        let positive = |raw: &str| raw.parse::<f32>().ok().filter(|n| *n > 0.0);
+
+        let time = match raw {
+            "off" => None,
+            _ => {
+                let time = if let Some((n, d)) = raw.split_once('/') {
+                    if let Idx::Bus(_) = idx {
+                        return Err(CmdErr::Formatting {
+                            err: "A bus has no tempo to divide; give its delay in s: or m:".to_string(),
+                        });
+                    }
+                    match (positive(n), positive(d)) {
+                        (Some(n), Some(d)) => DelayTime::Beats(4.0 * n / d),
+                        _ => return Err(invalid(raw, "delay")),
+                    }
+                } else {
+                    let (u, val) = raw.split_once(':').ok_or(invalid(raw, "delay"))?;
+                    let unit = match u {
+                        "s" => TempoUnit::Samples,
+                        "m" => TempoUnit::Millis,
+                        _ => return Err(invalid(u, "delay")),
+                    };
+                    let val = positive(val).ok_or(invalid(val, "delay"))?;
+                    let samples = convert_interval(&unit, val);
+                    if samples > MAX_DELAY_SECS * sample_rate::get() as f32 {
+                        return Err(CmdErr::Formatting {
+                            err: format!("Delays can be at most {MAX_DELAY_SECS}s"),
+                        });
+                    }
+                    DelayTime::Samples(samples)
+                };
+                Some(time)
+            }
+        };
+
+        let DelayFlags { feedback, mix } = DelayFlags::parse_flags(self, &mut args, "delay")?;
+        // a repeat that never gets quieter would build up forever
+        let feedback = feedback.min(0.99);
+
+        let buf = match time {
+            Some(_) => delay_buffer(self.engine_state.out_channels),
+            None => Vec::new(),
+        };
+
+        Ok(Command::Delay(DelayArgs { idx, time, feedback, mix, buf }))
+    }
+
+    // verb master|-g <group>|-v <voice>|-b <bus> [off] [-r <room>] [-d <damping>] [-m <mix>]
+    fn try_verb(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace().peekable();
+        let idx = match args.peek() {
+            Some(&"master") => {
+                args.next();
+                None
+            }
+            _ => Some(self.parse_effect_target(&mut args, "verb")?),
+        };
+
+        if args.peek() == Some(&"off") {
+            return Ok(Command::Verb(VerbArgs { idx, verb: None }));
+        }
+
+        let VerbFlags { room, damping, mix } = VerbFlags::parse_flags(self, &mut args, "verb")?;
+
+        let verb = Verb::new(self.engine_state.out_channels, room, damping, mix);
+        Ok(Command::Verb(VerbArgs { idx, verb: Some(verb) }))
+    }
+
+    // an Effect's owner: -v <voice>, -g <group> or -b <bus>
+    fn parse_effect_target<'a>(&mut self, args: &mut Peekable<impl Iterator<Item = &'a str>>, cmd: &str) -> CmdResult<Idx> {
+        if let Some(&("-b" | "--bus")) = args.peek() {
+            args.next();
+            let name = args.next().ok_or(CmdErr::MissingArg {
+                arg: "bus".to_string(),
+                cmd: cmd.to_string(),
+            })?;
+            return Ok(Idx::Bus(self.find_bus(name.to_string())?));
+        }
+        self.parse_target(args, cmd)
+    }
+
+    // bus <name>
+    //
+    // a new aux bus, returned to the master at gain 1 (gain -b);
+    // Effects go on it with -b, and Voices reach it with send
+    fn try_bus(&mut self, args: String) -> CmdResult<Command> {
+        let name = args
+            .split_whitespace()
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "bus".to_string(),
+            })?
+            .to_string();
+        if name.contains('.') || name == "master" {
+            return Err(CmdErr::InvalidArg { arg: name, cmd: "bus".to_string() });
+        }
+
+        let buses = &mut self.engine_state.buses;
+        if buses.contains_key(&name) {
+            return Err(CmdErr::AlreadyIs { ty: "bus".to_string(), name });
+        }
+        if buses.len() >= MAX_BUSES {
+            return Err(CmdErr::Formatting {
+                err: format!("A session can have at most {MAX_BUSES} buses"),
+            });
+        }
+        let idx = buses.len();
+        buses.insert(name, idx);
+
+        let buf = vec![0.0; BLOCK * self.engine_state.out_channels];
+        Ok(Command::Bus(BusArgs {
+            buf,
+            effects: Unshared::with_capacity(MAX_EFFECTS),
+            tempo_state: Unshared::tempo(TempoState::new(None)),
+        }))
+    }
+
+    // send <voice> <bus> <amount>|off
+    //
+    // amount is linear, or in decibels with a dB suffix, as gain;
+    // what's sent is after the Voice's Effects and gain
+    fn try_send(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let (name, bus, raw) = match (args.next(), args.next(), args.next()) {
+            (Some(name), Some(bus), Some(raw)) => (name, bus, raw),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "voice, bus and amount".to_string(),
+                cmd: "send".to_string(),
+            }),
+        };
+
+        let idx = self.voice_idx(name.to_string())?;
+        let bus = self.find_bus(bus.to_string())?;
+        let amount = match raw {
+            "off" => 0.0,
+            _ => gain_val(raw).ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: "send".to_string(),
+            })?,
+        };
+
+        Ok(Command::Send(SendArgs { idx, bus, amount }))
+    }
+
+    fn try_pan(&mut self, args: String) -> CmdResult<Command> {
+        // pan <voice> <-1..1>
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "pan".to_string(),
+            })?;
+
+        let idx = self.voice_idx(name.to_string())?;
+
+        let raw = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "value".to_string(),
+                cmd: "pan".to_string(),
+            })?;
+
+        let val = raw
+            .parse::<f32>()
+            .ok()
+            .filter(|v| (-1.0..=1.0).contains(v))
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: "pan".to_string(),
+            })?;
+
+        Ok(Command::Pan(PanArgs { idx, val }))
+    }
+
+    fn try_trig(&mut self, args: String) -> CmdResult<Command> {
+        // trig <voice> [-b]
+        // (restarts it from the top without a stop and start,
+        // so a one-shot can be fired over and over)
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "trig".to_string(),
+            })?;
+
+        let idx = self.voice_idx(name.to_string())?;
+        let flags = TrigFlags::parse_flags(self, &mut args, "trig")?;
+
+        Ok(Command::Trig(TrigArgs { idx, on_beat: flags.beat }))
+    }
+
+    fn try_offset(&mut self, args: String) -> CmdResult<Command> {
+        // offset <voice> <samples>|<ms>ms
+        // (moves its Seq steps against its tempo, e.g. -5ms
+        // so a slow attack lands with everything else)
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "offset".to_string(),
+            })?;
+
+        let idx = self.voice_idx(name.to_string())?;
+
+        let raw = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "offset".to_string(),
+                cmd: "offset".to_string(),
+            })?;
+
+        let (val, unit) = match raw.strip_suffix("ms") {
+            Some(ms) => (ms, TempoUnit::Millis),
+            None => (raw, TempoUnit::Samples),
+        };
+        let val = val
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: "offset".to_string(),
+            })?;
+
+        // Seqs hit on whole samples
+        let samples = convert_interval(&unit, val.abs()).round().copysign(val);
+
+        Ok(Command::Offset(OffsetArgs { idx, samples }))
+    }
+
+    fn try_trim(&mut self, args: String) -> CmdResult<Command> {
+        // trim <voice> <start> <end>
+        // trim <voice> off
+        //
+        // plays only the Track between start and end, each in
+        // frames, <n>ms or <n>b (beats of the Voice's tempo)
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "trim".to_string(),
+            })?;
+
+        let idx = self.voice_idx(name.to_string())?;
+        let voice = self.find_voice(name.to_string())?;
+
+        let raw_start = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "start".to_string(),
+                cmd: "trim".to_string(),
+            })?;
+        if raw_start == "off" {
+            voice.trim = None;
+            return Ok(Command::Trim(TrimArgs { idx, region: None }));
+        }
+        let raw_end = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "end".to_string(),
+                cmd: "trim".to_string(),
+            })?;
+
+        let start = voice.frame_at(name, raw_start, "trim")?.round() as usize;
+        let end = voice.frame_at(name, raw_end, "trim")?.round() as usize;
+
+        // an end past the Track's is its end
+        let end = end.min(voice.frames.saturating_sub(1));
+        if start >= end {
+            return Err(CmdErr::Formatting {
+                err: format!("Trim start ({start}) has to come before its end ({end}) in {} frames", voice.frames),
+            });
+        }
+
+        voice.trim = Some((start, end));
+        Ok(Command::Trim(TrimArgs { idx, region: Some((start, end)) }))
+    }
+
+    fn try_seek(&mut self, args: String) -> CmdResult<Command> {
+        // seek <voice> <position> [-q]
+        //
+        // jumps the playhead to position (frames, <n>ms or <n>b,
+        // as trim), within any trim; -q puts it on the nearest
+        // beat of the Voice's tempo, counted from the Track's start
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "seek".to_string(),
+            })?;
+
+        let idx = self.voice_idx(name.to_string())?;
+
+        let raw = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "position".to_string(),
+                cmd: "seek".to_string(),
+            })?;
+
+        let flags = SeekFlags::parse_flags(self, &mut args, "seek")?;
+        let voice = self.find_voice(name.to_string())?;
+        let mut position = voice.frame_at(name, raw, "seek")?;
+        if flags.quantize {
+            let beat = voice.beat_frames().ok_or(CmdErr::Formatting {
+                err: format!("{name} has no tempo to quantize to"),
+            })?;
+            position = (position / beat).round() * beat;
+        }
+
+        let (start, end) = voice.trim.unwrap_or((0, voice.frames.saturating_sub(1)));
+        let position = (position.round() as usize).clamp(start, end);
+        Ok(Command::Seek(SeekArgs { idx, position }))
+    }
+
+    // lfo <voice> <gain|velocity|pan> [-w <wave>] [-t <tempo>] [-d <depth>]
+    //
+    // one cycle per unit of tempo: s|m|b:<interval> for its own,
+    // c:<tempocontext> or g:<group> to lock to one, or v (default)
+    // for its Voice's; waves are sine (default), tri, square
+    // and random; depth defaults to 0.5
+    fn try_lfo(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let name = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "name".to_string(),
+                cmd: "lfo".to_string(),
+            })?
+            .to_string();
+
+        let idx = self.voice_idx(name.clone())?;
+
+        let target = match args.next() {
+            Some("gain") => LfoTarget::Gain,
+            Some("velocity") => LfoTarget::Velocity,
+            Some("pan") => LfoTarget::Pan,
+            Some(other) => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: "lfo".to_string(),
+            }),
+            None => return Err(CmdErr::MissingArg {
+                arg: "gain/velocity/pan".to_string(),
+                cmd: "lfo".to_string(),
+            }),
+        };
+
+        let LfoFlags { wave, tempo, depth, name: proc_name } = LfoFlags::parse_flags(self, &mut args, "lfo")?;
+
+        let tempo = match tempo.as_deref() {
+            None | Some("v") => None,
+            Some(raw) => {
+                let idx = self.find_voice(name.clone())?.proc_tempi.len();
+                Some(self.flag_tempo_repr(raw, TempoMode::Process, idx, true, "lfo")?)
+            }
+        };
+
+        let voice = self.find_voice(name)?;
+        let repr_tempo = tempo.clone().unwrap_or_else(|| TempoRepr::clone_owner(&voice.tempo));
+        let repr = ProcRepr::new(voice.processes.len(), ProcKind::Lfo, idx, Some(repr_tempo), None);
+        voice.add_process(proc_name, repr)?;
+        if let Some(t) = &tempo && t.mode == TempoMode::Process {
+            voice.proc_tempi.insert(voice.proc_tempi.len(), TempoRepr::clone(t));
+        }
+
+        Ok(Command::Lfo(LfoArgs {
+            idx,
+            tempo_state: tempo.as_ref().and_then(TempoRepr::made),
+            tempo,
+            wave,
+            target,
+            depth,
+            rng: X128P::new(fast_seed()),
+        }))
+    }
+
+    // follow <voice> <tempocontext> [--bpm <min>-<max>]
+    //
+    // steers the TempoContext to the onsets in the Voice;
+    // beats are folded into the bpm range (default 60-180)
+    fn try_follow(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let (name, tc_name) = match (args.next(), args.next()) {
+            (Some(name), Some(tc_name)) => (name.to_string(), tc_name.to_string()),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "voice and tempocontext".to_string(),
+                cmd: "follow".to_string(),
+            }),
+        };
+
+        let FollowFlags { name: proc_name, bpm: (min_bpm, max_bpm) } = FollowFlags::parse_flags(self, &mut args, "follow")?;
+
+        let tc = TempoRepr::clone_owner(self.find_tc(tc_name)?);
+        let tempo = tc.idx;
+        let idx = self.voice_idx(name.clone())?;
+
+        let voice = self.find_voice(name)?;
+        let repr = ProcRepr::new(voice.processes.len(), ProcKind::Follow, idx, Some(tc), None);
+        voice.add_process(proc_name, repr)?;
+
+        Ok(Command::Follow(FollowArgs { idx, tempo, min_bpm, max_bpm }))
+    }
+
+    // proc rm <voice>.<proc>
+    // proc set <voice>.<proc> <param> <value>
+    //
+    // (proc ls is shown on the command thread, like status)
+    fn try_proc(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let action = args.next().ok_or(CmdErr::MissingArg {
+            arg: "rm|set".to_string(),
+            cmd: "proc".to_string(),
+        })?;
+        let cmd = format!("proc {action}");
+        let target = args.next().ok_or(CmdErr::MissingArg {
+            arg: "voice.proc".to_string(),
+            cmd: cmd.clone(),
+        })?;
+
+        // the Voice may itself be group.voice
+        let (v_name, p_name) = target.rsplit_once('.').ok_or(CmdErr::Formatting {
+            err: format!("Name the Process as <voice>.<proc>, not {target}"),
+        })?;
+        let idx = self.voice_idx(v_name.to_string())?;
+        let voice = self.find_voice(v_name.to_string())?;
+        let repr = voice.processes.get(p_name).ok_or(CmdErr::NoItem {
+            ty: "Process".to_string(),
+            name: target.to_string(),
+        })?;
+        let (proc, kind) = (repr.idx, repr.kind);
+        let steps = repr.pattern.as_ref().map_or(0, |p| p.steps.len());
+
+        let action = match action {
+            "rm" => {
+                // the engine's Vec closes up behind it
+                voice.processes.remove(p_name);
+                for p in voice.processes.values_mut() {
+                    if p.idx > proc {
+                        p.idx -= 1;
+                    }
+                }
+                ProcAction::Remove
+            }
+            "set" => {
+                let (param, raw) = match (args.next(), args.next()) {
+                    (Some(param), Some(raw)) => (param, raw),
+                    _ => return Err(CmdErr::MissingArg {
+                        arg: "param and value".to_string(),
+                        cmd,
+                    }),
+                };
+                let cmd = format!("proc set {param}");
+                let invalid = || CmdErr::InvalidArg {
+                    arg: raw.to_owned(),
+                    cmd: cmd.clone(),
+                };
+
+                let param = match (kind, param) {
+                    (ProcKind::Seq, "period") => ProcParam::Period(
+                        raw.parse::<usize>().ok().filter(|p| *p > 0).ok_or_else(invalid)?
+                    ),
+                    (ProcKind::Seq, "steps") => {
+                        let steps = raw.split(',')
+                            .map(|step| step.parse::<f32>().ok().filter(|s| *s >= 0.0))
+                            .collect::<Option<Vec<f32>>>()
+                            .ok_or_else(invalid)?;
+                        // what the steps had, cut or padded to the new count,
+                        // so the engine only swaps them in
+                        let voice = self.find_voice(v_name.to_string())?;
+                        let pattern = voice.processes.get(p_name).and_then(|p| p.pattern.as_ref());
+                        let resized = |of: Option<&Vec<f32>>, fill: f32| {
+                            let mut v = of.cloned().unwrap_or_default();
+                            v.resize(steps.len(), fill);
+                            v
+                        };
+                        ProcParam::Steps {
+                            chance: resized(pattern.map(|p| &p.chance), 100.0),
+                            jit: resized(pattern.map(|p| &p.jit), 100.0),
+                            levels: resized(pattern.map(|p| &p.levels), 1.0),
+                            steps,
+                        }
+                    }
+                    (ProcKind::Seq, "levels") => ProcParam::Levels(seq_levels(raw, steps, &cmd)?),
+                    (ProcKind::Seq, "swing") => ProcParam::Swing(self.flag_swing(raw, &cmd)?),
+                    (ProcKind::Seq, "seed") => ProcParam::Seed(raw.parse::<u64>().map_err(|_| invalid())?),
+                    (ProcKind::Lfo, "depth") => ProcParam::Depth(self.flag_depth(raw, &cmd)?),
+                    (ProcKind::Lfo, "wave") => ProcParam::Wave(self.flag_wave(raw, &cmd)?),
+                    _ => return Err(CmdErr::Formatting {
+                        err: format!("A {} has no {param} to set", kind.name()),
+                    }),
+                };
+
+                // keep the pattern status draws up to date
+                let voice = self.find_voice(v_name.to_string())?;
+                if let Some(pattern) = voice.processes.get_mut(p_name).and_then(|p| p.pattern.as_mut()) {
+                    match &param {
+                        ProcParam::Period(period) => pattern.period = *period,
+                        ProcParam::Steps { steps, chance, jit, levels } => {
+                            pattern.steps = steps.clone();
+                            pattern.chance = chance.clone();
+                            pattern.jit = jit.clone();
+                            pattern.levels = levels.clone();
+                        }
+                        ProcParam::Levels(levels) => pattern.levels = levels.clone(),
+                        ProcParam::Swing(swing) => pattern.swing = *swing,
+                        ProcParam::Seed(seed) => pattern.seed = Some(*seed),
+                        _ => (),
+                    }
+                }
+                ProcAction::Set(param)
+            }
+            _ => return Err(CmdErr::InvalidArg {
+                arg: action.to_owned(),
+                cmd: "proc".to_string(),
+            }),
+        };
+
+        Ok(Command::Proc(ProcArgs { idx, proc, action }))
+    }
+
+    // rename -v|-g|-t <old> <new>
+    //
+    // a Voice in a Group (group.voice) keeps its Group; nothing
+    // the engine holds changes, since it only knows indices
+    fn try_rename(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let (ty, old, new) = match (args.next(), args.next(), args.next()) {
+            (Some(ty), Some(old), Some(new)) => (ty, old.to_string(), new.to_string()),
+            _ => return Err(CmdErr::MissingArg {
+                arg: "type, old and new names".to_string(),
+                cmd: "rename".to_string(),
+            }),
+        };
+        if new.contains('.') {
+            return Err(CmdErr::InvalidArg { arg: new, cmd: "rename".to_string() });
+        }
+
+        let already = |ty: &str, name: String| CmdErr::AlreadyIs { ty: ty.to_string(), name };
+        match ty {
+            "-v" | "--voice" => {
+                self.find_voice(old.clone())?;
+                let voices = match old.split_once('.') {
+                    Some((g_name, _)) => &mut self.find_group(g_name.to_string())?.voices,
+                    None => &mut self.engine_state.voices,
+                };
+                let v_name = old.rsplit('.').next().unwrap();
+                if voices.contains_key(&new) {
+                    return Err(already("Voice", new));
+                }
+                let voice = voices.remove(v_name).unwrap();
+                voices.insert(new, voice);
+            }
+            "-g" | "--group" => {
+                let groups = &mut self.engine_state.groups;
+                if groups.contains_key(&new) {
+                    return Err(already("Group", new));
+                }
+                let group = groups.remove(&old).ok_or(CmdErr::NoItem {
+                    ty: "Group".to_string(),
+                    name: old,
+                })?;
+                groups.insert(new, group);
+            }
+            "-t" | "--tempocontext" => {
+                let tcs = &mut self.engine_state.tempo_cons;
+                if tcs.contains_key(&new) {
+                    return Err(already("TempoContext", new));
+                }
+                let tc = tcs.remove(&old).ok_or(CmdErr::NoItem {
+                    ty: "TempoContext".to_string(),
+                    name: old,
+                })?;
+                tcs.insert(new, tc);
+            }
+            _ => return Err(CmdErr::InvalidArg {
+                arg: ty.to_owned(),
+                cmd: "rename".to_string(),
+            }),
+        }
+
+        Ok(Command::Rename(RenameArgs {}))
+    }
+
+    // audio restart
+    // session load <script>
+    // session crossfade next <n>b|<n>ms [-t <tempo>]
+    // session drop
+    //
+    // a script is one command per line (# for comments), run
+    // into a second session that plays nothing until it's
+    // crossfaded in; from then on commands go to it
+    fn try_session(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let action = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "load/crossfade/drop".to_string(),
+                cmd: "session".to_string(),
+            })?;
+
+        let action = match action {
+            "load" => {
+                let path = args
+                    .next()
+                    .ok_or(CmdErr::MissingArg {
+                        arg: "script".to_string(),
+                        cmd: "session load".to_string(),
+                    })?;
+                let (state, commands) = self.run_script(path)?;
+                let session = state.session(commands);
+                self.engine_state.next = Some(Box::new(state));
+                SessionAction::Load(session)
+            }
+            "crossfade" | "xfade" => {
+                if args.next() != Some("next") {
+                    return Err(CmdErr::MissingArg {
+                        arg: "next".to_string(),
+                        cmd: "session crossfade".to_string(),
+                    });
+                }
+                let raw = args
+                    .next()
+                    .ok_or(CmdErr::MissingArg {
+                        arg: "length".to_string(),
+                        cmd: "session crossfade".to_string(),
+                    })?;
+
+                let beat = CrossfadeFlags::parse_flags(self, &mut args, "session crossfade")?.tempo;
+
+                let invalid = || CmdErr::InvalidArg {
+                    arg: raw.to_owned(),
+                    cmd: "session crossfade".to_string(),
+                };
+                let (count, per) = if let Some(ms) = raw.strip_suffix("ms") {
+                    (ms, convert_interval(&TempoUnit::Millis, 1.0))
+                } else if let Some(beats) = raw.strip_suffix('b') {
+                    (beats, beat)
+                } else {
+                    return Err(invalid());
+                };
+                let count = count
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|n| *n >= 0.0)
+                    .ok_or_else(invalid)?;
+
+                let next = self.engine_state.next.take().ok_or(CmdErr::Formatting {
+                    err: "No session loaded; use 'session load <script>'".to_string(),
+                })?;
+                // its script's lines are numbered on from here
+                let mut rev = self.engine_state.rev;
+                self.engine_state = *next;
+                for event in &mut self.engine_state.history {
+                    rev += 1;
+                    event.rev = rev;
+                }
+                self.engine_state.rev = rev;
+                SessionAction::Crossfade((count * per).round() as usize)
+            }
+            "drop" => {
+                self.engine_state.next = None;
+                SessionAction::Drop
+            }
+            other => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: "session".to_string(),
+            }),
+        };
+
+        Ok(Command::Session(SessionArgs { action }))
+    }
+
+    // the state a script (one command per line, # for comments)
+    // leads to from nothing loaded, each line a new revision,
+    // and the Commands that take an engine there
+    fn run_script(&self, path: &str) -> CmdResult<(EngineState, Vec<Command>)> {
+        let script = std::fs::read_to_string(path).map_err(|e| CmdErr::FileIo {
+            path: path.to_string(),
+            err: e.to_string(),
+        })?;
+
+        let mut staging = Self::new(self.engine_state.fresh());
+        let mut commands = Vec::<Command>::new();
+        for (n, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let plan = staging.plan(line.to_string()).map_err(|e| CmdErr::Formatting {
+                err: format!("{path}, line {}: {e}", n + 1),
+            })?;
+            if matches!(plan.command, Command::Session(_) | Command::Audio(_) | Command::Quit(_)) {
+                return Err(CmdErr::Formatting {
+                    err: format!("{path}, line {}: '{line}' can't be used in a script", n + 1),
+                });
+            }
+            staging.engine_state = plan.state;
+            staging.engine_state.record(line, plan.undo);
+            commands.push(plan.command);
+        }
+
+        Ok((staging.engine_state, commands))
+    }
+
+    // run <path>
+    //
+    // queues a script's lines to go through prepare and commit
+    // in order, as if typed, by whoever feeds this processor
+    // (see next_scripted); a script run from a script goes
+    // ahead of the rest of the lines that ran it
+    fn try_run(&mut self, args: String) -> CmdResult<()> {
+        let path = args.split_whitespace().next().ok_or(CmdErr::MissingArg {
+            arg: "path".to_string(),
+            cmd: "run".to_string(),
+        })?;
+        let script = std::fs::read_to_string(path).map_err(|e| CmdErr::FileIo {
+            path: path.to_string(),
+            err: e.to_string(),
+        })?;
+
+        let lines: Vec<(String, String)> = script
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (format!("{path}, line {}", n + 1), line.split('#').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(at, line)| (at, line.to_string()))
+            .collect();
+
+        // scripts that run each other in a circle
+        if self.script.len() + lines.len() > MAX_SCRIPTED {
+            self.script.clear();
+            return Err(CmdErr::Formatting {
+                err: format!("More than {MAX_SCRIPTED} lines queued; does {path} end up running itself?"),
+            });
+        }
+        for line in lines.into_iter().rev() {
+            self.script.push_front(line);
+        }
+
+        Ok(())
+    }
+
+    // the next line queued by run, and where it's from
+    pub fn next_scripted(&mut self) -> Option<(String, String)> {
+        self.script.pop_front()
+    }
+
+    // alias [<name> [= <line>[; <line>...]]]
+    // alias rm <name>
+    //
+    // names a sequence of commands; $1..$9 in them are the
+    // words the alias is given, $@ all of them; without a
+    // body, shows one alias (or all of them)
+    fn try_alias(&mut self, args: String) -> CmdResult<Option<String>> {
+        let (head, body) = match args.split_once('=') {
+            Some((head, body)) => (head.trim(), Some(body.trim())),
+            None => (args.trim(), None),
+        };
+        let mut words = head.split_whitespace();
+
+        match (words.next(), words.next(), body) {
+            (None, _, None) => match self.aliases.is_empty() {
+                true => Ok(Some("No aliases".to_string())),
+                false => Ok(Some(self.aliases
+                    .iter()
+                    .map(|(name, body)| format!("{name} = {body}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"))),
+            },
+            (Some("rm"), Some(name), None) => match self.aliases.remove(name) {
+                Some(_) => Ok(None),
+                None => Err(CmdErr::NoItem { ty: "alias".to_string(), name: name.to_string() }),
+            },
+            (Some(name), None, None) => match self.aliases.get(name) {
+                Some(body) => Ok(Some(format!("{name} = {body}"))),
+                None => Err(CmdErr::NoItem { ty: "alias".to_string(), name: name.to_string() }),
+            },
+            (Some(name), None, Some(body)) => {
+                self.add_alias(name, body)?;
+                Ok(None)
+            }
+            _ => Err(CmdErr::Formatting { err: "Usage: alias <name> = <line>[; <line>...]".to_string() }),
+        }
+    }
+
+    // also how aliases come in from blast.conf
+    pub fn add_alias(&mut self, name: &str, body: &str) -> CmdResult<()> {
+        if name == "rm" || name.contains('$') {
+            return Err(CmdErr::InvalidArg { arg: name.to_string(), cmd: "alias".to_string() });
+        }
+        if schema::find(name).is_some() {
+            return Err(CmdErr::AlreadyIs { ty: "command".to_string(), name: name.to_string() });
+        }
+        if body.split(';').all(|line| line.trim().is_empty()) {
+            return Err(CmdErr::MissingArg { arg: "<line>".to_string(), cmd: "alias".to_string() });
+        }
+
+        // an alias that ends up running itself would never finish
+        let mut aliases = self.aliases.clone();
+        aliases.insert(name.to_string(), body.to_string());
+        let mut reached = vec![name.to_string()];
+        let mut next = 0;
+        while next < reached.len() {
+            for line in aliases[&reached[next]].clone().split(';') {
+                let Some(cmd) = line.split_whitespace().next() else { continue };
+                if cmd == name {
+                    return Err(CmdErr::Formatting { err: format!("{name} would end up running itself") });
+                }
+                if aliases.contains_key(cmd) && !reached.iter().any(|r| r == cmd) {
+                    reached.push(cmd.to_string());
+                }
+            }
+            next += 1;
+        }
+
+        self.aliases = aliases;
+        Ok(())
+    }
+
+    // queues an alias's lines, with its words filled in, ahead
+    // of anything else queued (so an alias can be used in a script)
+    fn expand_alias(&mut self, name: String, body: String, args: String) -> CmdResult<()> {
+        let words: Vec<&str> = args.split_whitespace().collect();
+
+        let mut lines = Vec::<(String, String)>::new();
+        for line in body.split(';').map(str::trim).filter(|l| !l.is_empty()) {
+            let mut expanded = String::new();
+            let mut rest = line;
+            while let Some(at) = rest.find('$') {
+                expanded.push_str(&rest[..at]);
+                let after = &rest[at + 1..];
+                match after.chars().next() {
+                    Some('@') => expanded.push_str(&words.join(" ")),
+                    Some(n @ '1'..='9') => {
+                        let word = words.get(n as usize - '1' as usize).ok_or(CmdErr::MissingArg {
+                            arg: format!("${n}"),
+                            cmd: name.clone(),
+                        })?;
+                        expanded.push_str(word);
+                    }
+                    // anything else is left as it is
+                    _ => {
+                        expanded.push('$');
+                        rest = after;
+                        continue;
+                    }
+                }
+                rest = &after[1..];
+            }
+            expanded.push_str(rest);
+            lines.push((format!("alias {name}"), expanded));
+        }
+
+        if self.script.len() + lines.len() > MAX_SCRIPTED {
+            self.script.clear();
+            return Err(CmdErr::Formatting {
+                err: format!("More than {MAX_SCRIPTED} lines queued"),
+            });
+        }
+        for line in lines.into_iter().rev() {
+            self.script.push_front(line);
+        }
+
+        Ok(())
+    }
+
+    // save <file>
+    //
+    // writes the history as a script that open (or session load)
+    // rebuilds the state from; transport isn't kept, so a set
+    // opens with nothing playing
+    fn try_save(&self, args: String) -> CmdResult<()> {
+        let path = args.split_whitespace().next().ok_or(CmdErr::MissingArg {
+            arg: "file".to_string(),
+            cmd: "save".to_string(),
+        })?;
+
+        let mut script = format!("# BLAST set, saved at revision {}\n", self.engine_state.rev);
+        for event in &self.engine_state.history {
+            if !moves_playhead(&event.line) {
+                script.push_str(&event.line);
+                script.push('\n');
+            }
+        }
+
+        std::fs::write(path, script).map_err(|e| CmdErr::FileIo {
+            path: path.to_string(),
+            err: e.to_string(),
+        })
+    }
+
+    // open <file>
+    //
+    // replaces the state with the one a saved set (or any
+    // script) builds, and has the engine fade over to it
+    fn try_open(&mut self, args: String) -> CmdResult<Command> {
+        let path = args.split_whitespace().next().ok_or(CmdErr::MissingArg {
+            arg: "file".to_string(),
+            cmd: "open".to_string(),
+        })?;
+
+        let (mut state, commands) = self.run_script(path)?;
+        let session = state.session(commands);
+        state.next = self.engine_state.next.take();
+        self.engine_state = state;
+
+        Ok(Command::Session(SessionArgs { action: SessionAction::Replace(session) }))
+    }
+
+    // undo [<count>]
+    //
+    // takes back the last count lines (default 1) that change
+    // what the session is; lines that only move things (see
+    // moves_playhead) aren't undone, but the ones after the first
+    // line undone go with it. where every line has an inverse,
+    // those are all the engine is sent; otherwise the state is
+    // rebuilt from the history before them and the engine takes
+    // over from where it is, as for a revert. the lines can be
+    // redone in order until something new is entered
+    fn try_undo(&mut self, args: String) -> CmdResult<Command> {
+        let count = match args.split_whitespace().next() {
+            Some(raw) => raw.parse::<usize>().ok().filter(|n| *n > 0).ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: "undo".to_string(),
+            })?,
+            None => 1,
+        };
+
+        let history = &self.engine_state.history;
+        let undoable: Vec<usize> = (0..history.len()).filter(|&i| !moves_playhead(&history[i].line)).collect();
+        if undoable.len() < count {
+            return Err(CmdErr::Formatting {
+                err: format!("Only {} lines to undo", undoable.len()),
+            });
+        }
+
+        let cut = undoable[undoable.len() - count];
+        let (kept, taken) = history.split_at(cut);
+        let undone: Vec<HistoryEntry> = taken.iter().filter(|e| !moves_playhead(&e.line)).cloned().collect();
+
+        let (mut state, command) = match self.plan_inverses(&undone) {
+            Some((state, commands)) => (state, SessionAction::Undo(commands)),
+            None => {
+                let (mut state, mut commands) = self.replay(kept)?;
+                let carry = self.engine_state.carry(&mut state);
+                commands.push(state.restart(&carry));
+                let session = state.session(commands);
+                (state, SessionAction::Restore(session, carry))
+            }
+        };
+        state.history = kept.to_vec();
+        state.rev = self.engine_state.rev;
+        state.next = self.engine_state.next.take();
+        state.undone = std::mem::take(&mut self.engine_state.undone);
+        state.undone.extend(undone.into_iter().rev());
+        self.engine_state = state;
+
+        Ok(Command::Session(SessionArgs { action: command }))
+    }
+
+    // the state left by planning each line's inverse, last line
+    // first, and their Commands; None if any line has none (or
+    // its inverse doesn't plan, which a rebuild will show up)
+    fn plan_inverses(&self, entries: &[HistoryEntry]) -> Option<(EngineState, Vec<Command>)> {
+        let mut staging = Self::new(self.engine_state.clone());
+        let mut commands = Vec::<Command>::new();
+        for entry in entries.iter().rev() {
+            let plan = staging.plan(entry.undo.clone()?).ok()?;
+            staging.engine_state = plan.state;
+            commands.push(plan.command);
+        }
+
+        Some((staging.engine_state, commands))
+    }
+
+    // revert <rev>
+    //
+    // rebuilds the state from the history up to and including
+    // rev (0 is before anything), and has the engine take over
+    // from where it is with a session built the same way: what's
+    // still there carries on from where it is, what's gone fades
+    // out, and what's back (and was playing) starts over
+    fn try_revert(&mut self, args: String) -> CmdResult<Command> {
+        let raw = args
+            .split_whitespace()
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "revision".to_string(),
+                cmd: "revert".to_string(),
+            })?;
+        let rev = raw
+            .parse::<u64>()
+            .ok()
+            .filter(|r| *r == 0 || self.engine_state.history.iter().any(|e| e.rev == *r))
+            .ok_or(CmdErr::NoItem {
+                ty: "revision".to_string(),
+                name: raw.to_owned(),
+            })?;
+
+        let kept: Vec<HistoryEntry> = self.engine_state.history
+            .iter()
+            .take_while(|e| e.rev <= rev)
+            .cloned()
+            .collect();
+        let (mut state, mut commands) = self.replay(&kept)?;
+        let carry = self.engine_state.carry(&mut state);
+        commands.push(state.restart(&carry));
+        let session = state.session(commands);
+        state.rev = self.engine_state.rev;
+        state.next = self.engine_state.next.take();
+        self.engine_state = state;
+
+        Ok(Command::Session(SessionArgs { action: SessionAction::Restore(session, carry) }))
+    }
+
+    // the state a history leads to from nothing loaded, and the
+    // Commands that take an engine there; the same history always
+    // plans out the same way. lines that only move things (see
+    // moves_playhead) count toward the state's transport, but
+    // aren't sent, so the session built is at rest (see restart)
+    pub fn replay(&self, entries: &[HistoryEntry]) -> CmdResult<(EngineState, Vec<Command>)> {
+        let mut staging = Self::new(self.engine_state.fresh());
+        let mut commands = Vec::<Command>::new();
+        for entry in entries {
+            let plan = staging.plan(entry.line.clone()).map_err(|e| CmdErr::Formatting {
+                err: format!("revision {}: {e}", entry.rev),
+            })?;
+            staging.engine_state = plan.state;
+            staging.engine_state.history.push(HistoryEntry { undo: plan.undo, ..entry.clone() });
+            if !moves_playhead(&entry.line) {
+                commands.push(plan.command);
+            }
+        }
+
+        Ok((staging.engine_state, commands))
+    }
+
+    // a Track's waveform as wide as the terminal, marking where
+    // a Voice of it is (one that's playing, if any is)
+    fn wave(&mut self, args: String) -> CmdResult<String> {
+        let mut args = args.split_whitespace();
+        let name = args.next().ok_or(CmdErr::MissingArg {
+            arg: "track".to_string(),
+            cmd: "wave".to_string(),
+        })?;
+        let WaveFlags { rows } = WaveFlags::parse_flags(self, &mut args, "wave")?;
+
+        let state = &self.engine_state;
+        let track = state.tracks.get(name).ok_or(CmdErr::NoItem {
+            ty: "track".to_string(),
+            name: name.to_string(),
+        })?;
+        let mut voices: Vec<&VoiceRepr> = state.voices
+            .values()
+            .chain(state.groups.values().flat_map(|g| g.voices.values()))
+            .filter(|v| v.track == track.idx)
+            .collect();
+        voices.sort_by_key(|v| (!matches!(v.transport, Transport::Playing), v.idx));
+        let playhead = voices.first().map(|v| v.meter.playhead().0);
+
+        let width = tui::size().0.saturating_sub(1);
+        Ok(format!("{name}\n{}", track.envelope.draw(width, rows, playhead)))
+    }
+
+    fn info(&self, args: String) -> CmdResult<String> {
+        let name = args.split_whitespace().next().ok_or(CmdErr::MissingArg {
+            arg: "track".to_string(),
+            cmd: "info".to_string(),
+        })?;
+        let track = self.engine_state.tracks.get(name).ok_or(CmdErr::NoItem {
+            ty: "track".to_string(),
+            name: name.to_string(),
+        })?;
+        Ok(track.info.to_string())
+    }
+
+    // history [<count>]: the last count revisions (default 20)
+    fn show_history(&self, args: String) -> CmdResult<String> {
+        let count = match args.split_whitespace().next() {
+            Some(raw) => raw.parse::<usize>().map_err(|_| CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: "history".to_string(),
+            })?,
+            None => 20,
+        };
+
+        let history = &self.engine_state.history;
+        let shown = &history[history.len().saturating_sub(count)..];
+        if shown.is_empty() {
+            return Ok("No history yet".to_string());
+        }
+
+        let width = shown.last().map_or(1, |e| e.rev.to_string().len());
+        Ok(shown
+            .iter()
+            .map(|e| format!("{:>width$}  {}", e.rev, e.line))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn try_audio(&mut self, args: String) -> CmdResult<Command> {
+        let action = match args.split_whitespace().next() {
+            Some("restart") => AudioAction::Restart,
+            Some(other) => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: "audio".to_string(),
+            }),
+            None => return Err(CmdErr::MissingArg {
+                arg: "restart".to_string(),
+                cmd: "audio".to_string(),
+            }),
+        };
+
+        Ok(Command::Audio(AudioArgs { action }))
+    }
+
+    // latency <period> [<buffer>]
+    //
+    // reopens the device asking for period frames a cycle and a
+    // buffer of buffer frames (four periods if it's left off);
+    // the device says what it settled on
+    fn try_latency(&mut self, args: String) -> CmdResult<Command> {
+        let frames = |raw: &str| raw.parse::<usize>().map_err(|_| CmdErr::InvalidArg {
+            arg: raw.to_owned(),
+            cmd: "latency".to_string(),
+        });
+        let mut args = args.split_whitespace();
+        let period = frames(args.next().unwrap_or_default())?;
+        let buffer = args.next().map(frames).transpose()?;
+        let latency = Latency::new(period, buffer).map_err(|err| CmdErr::Formatting { err })?;
+
+        Ok(Command::Audio(AudioArgs { action: AudioAction::Latency(latency) }))
+    }
+
+    // rec start <file> | rec stop
+    //
+    // planned like anything else, then the file is created (and
+    // its writer started) here, so it's only once the line is
+    // known to be good; rec stop finishes the file and writes
+    // the cue sheet marked while it played
+    fn try_rec(&mut self, line: String) -> CmdResult<Plan> {
+        let plan = self.plan(line.clone())?;
+        let Command::Audio(AudioArgs { action: AudioAction::Record(tap) }) = &plan.command else {
+            return Ok(plan);
+        };
+        match tap {
+            Some(tap) => {
+                if let Some(recording) = &self.recording {
+                    return Err(CmdErr::Formatting {
+                        err: format!("Already recording to {}; rec stop first", recording.path),
+                    });
+                }
+                let path = line.split_whitespace().nth(2).unwrap_or_default();
+                let recording = Recording::start(path, Arc::clone(tap), sample_rate::get(), self.engine_state.out_channels)
+                    .map_err(|err| CmdErr::FileIo { path: path.to_string(), err })?;
+                self.recording = Some(recording);
+            }
+            None => {
+                let recording = self.recording.take().ok_or(CmdErr::Formatting {
+                    err: "Not recording".to_string(),
+                })?;
+                tui::ok(recording.stop().map_err(|err| CmdErr::Formatting { err })?);
+            }
+        }
+
+        Ok(plan)
+    }
+
+    fn plan_rec(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        let tap = match args.next() {
+            Some("start") => {
+                args.next().ok_or(CmdErr::MissingArg {
+                    arg: "file".to_string(),
+                    cmd: "rec start".to_string(),
+                })?;
+                Some(Arc::new(Tap::new(sample_rate::get(), self.engine_state.out_channels)))
+            }
+            Some("stop") => None,
+            Some(other) => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: "rec".to_string(),
+            }),
+            None => return Err(CmdErr::MissingArg {
+                arg: "start/stop".to_string(),
+                cmd: "rec".to_string(),
+            }),
+        };
+
+        Ok(Command::Audio(AudioArgs { action: AudioAction::Record(tap) }))
+    }
+
+    // midi clock <device> -t <tempocontext> | off
+    //
+    // planned like anything else, then the device is opened here,
+    // so a listener only runs once the line is known to be good
+    fn try_midi(&mut self, line: String) -> CmdResult<Plan> {
+        let plan = self.plan(line)?;
+        let Command::Midi(MidiArgs { action }) = &plan.command else {
+            return Ok(plan);
+        };
+        let sync = match action {
+            MidiAction::Clock { device, sync, .. } => {
+                midi::listen(device, Arc::clone(sync)).map_err(|err| CmdErr::Formatting { err })?;
+                Some(Arc::clone(sync))
+            }
+            MidiAction::ClockOff => None,
+        };
+        // one clock at a time
+        if let Some(old) = std::mem::replace(&mut self.midi_clock, sync) {
+            old.close();
+        }
+
+        Ok(plan)
+    }
 
-        let tempo: Vec<_> = tempo.split(':').collect();
+    fn plan_midi(&mut self, args: String) -> CmdResult<Command> {
+        let mut args = args.split_whitespace();
+        match args.next() {
+            Some("clock") => (),
+            Some(other) => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: "midi".to_string(),
+            }),
+            None => return Err(CmdErr::MissingArg {
+                arg: "clock".to_string(),
+                cmd: "midi".to_string(),
+            }),
+        }
 
-        if tempo.len() != 2 {
-            return Err(CmdErr::TempoFormatting{});
+        let device = args.next().ok_or(CmdErr::MissingArg {
+            arg: "device (e.g. hw:1,0,0) or off".to_string(),
+            cmd: "midi clock".to_string(),
+        })?;
+        if device == "off" {
+            return Ok(Command::Midi(MidiArgs { action: MidiAction::ClockOff }));
         }
 
-        let u = tempo.get(0).unwrap();
-        let unit = match *u {
-            "b" => TempoUnit::Bpm,
-            "m" => TempoUnit::Millis,
-            "s" => TempoUnit::Samples,
-            _ => return Err(CmdErr::InvalidArg {
-                               arg: u.to_string(),
-                               cmd: "-t/--tempo".to_string(),
-                            }),
+        let tc = match args.next() {
+            Some("-t" | "--tempocontext") => {
+                let name = args.next().ok_or(CmdErr::MissingArg {
+                    arg: "tempocontext".to_string(),
+                    cmd: "midi clock -t".to_string(),
+                })?;
+                self.find_tc(name.to_string())?.idx
+            }
+            Some(other) => return Err(CmdErr::InvalidArg {
+                arg: other.to_owned(),
+                cmd: "midi clock".to_string(),
+            }),
+            None => return Err(CmdErr::MissingArg {
+                arg: "-t <tempocontext>".to_string(),
+                cmd: "midi clock".to_string(),
+            }),
         };
 
-        let int_str = tempo.get(1).unwrap();
-        let interval = int_str
-                       .parse::<f32>()
-                       .map_err(|_| CmdErr::InvalidArg {
-                                    arg: tempo[1].to_owned(),
-                                    cmd: "-t/--tempo".to_string(),
-                       })?;
+        Ok(Command::Midi(MidiArgs {
+            action: MidiAction::Clock {
+                device: device.to_string(),
+                tc,
+                sync: Arc::new(ClockSync::new()),
+            },
+        }))
+    }
 
-        let mut tempo_state = TempoRepr::new(self.engine_state.tempo_cons.len());
-        tempo_state.init(TempoMode::Context, unit, interval);
-        let ts_clone = TempoRepr::clone(&tempo_state);
-        self.engine_state.tempo_cons.insert(name.to_string(), tempo_state);
+    // midi notes <device> | midi notes off
+    // midi map [<note> trig|start|stop <voice> | <note> off]
+    // midi keys <voice> | midi keys off
+    //
+    // nothing for the engine: a mapped note runs its line through
+    // the REPL, like anything typed
+    fn try_midi_notes(&mut self, args: String) -> CmdResult<Option<String>> {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        let bad = |err: String| CmdErr::Formatting { err };
+        match words[..] {
+            ["notes", "off"] => self.notes.close(),
+            ["notes", device] => {
+                self.notes.listen(device).map_err(bad)?;
+                tui::ok(format!("Playing notes from {device}"));
+            }
+            ["notes"] => return Err(CmdErr::MissingArg {
+                arg: "device (e.g. hw:1,0,0) or off".to_string(),
+                cmd: "midi notes".to_string(),
+            }),
+            ["map"] => return Ok(Some(self.notes.listing())),
+            ["keys", "off"] => self.notes.keys(None),
+            ["keys", voice] => {
+                let keymap = self.find_voice(voice.to_string())?.keymap.ok_or(CmdErr::Formatting {
+                    err: format!("{voice} has no keymap; use 'keymap {voice} root <note>'"),
+                })?;
+                self.notes.keys(Some((voice, keymap)));
+            }
+            ["map", note, action] => self.notes.map(note, action, None).map_err(bad)?,
+            // the Voice needn't be loaded yet; playing it says if it isn't
+            ["map", note, action, voice] => self.notes.map(note, action, Some(voice)).map_err(bad)?,
+            _ => return Err(CmdErr::InvalidArg {
+                arg: args.trim().to_string(),
+                cmd: "midi".to_string(),
+            }),
+        }
+        Ok(None)
+    }
+
+    // script run <file> [<name>] runs a Rhai script on a thread
+    // of its own (named for the file, unless given one), script
+    // stop <name>|all stops it, and script lists what's running
+    fn try_script(&mut self, args: String) -> CmdResult<()> {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        let bad = |err: String| CmdErr::Formatting { err };
+        match words[..] {
+            [] | ["ls"] => tui::log(self.scripts.listing()),
+            ["run", path] => {
+                let name = std::path::Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or(path);
+                self.scripts.run(path, name).map_err(bad)?;
+                tui::ok(format!("Running {path} as {name}"));
+            }
+            ["run", path, name] => {
+                self.scripts.run(path, name).map_err(bad)?;
+                tui::ok(format!("Running {path} as {name}"));
+            }
+            ["run"] => return Err(CmdErr::MissingArg {
+                arg: "path".to_string(),
+                cmd: "script run".to_string(),
+            }),
+            ["stop", name] => self.scripts.stop(name).map_err(bad)?,
+            ["stop"] => return Err(CmdErr::MissingArg {
+                arg: "name (or all)".to_string(),
+                cmd: "script stop".to_string(),
+            }),
+            _ => return Err(CmdErr::InvalidArg {
+                arg: args.trim().to_string(),
+                cmd: "script".to_string(),
+            }),
+        }
+        Ok(())
+    }
+
+    // cue [label] adds a marker by hand to the cue sheet of
+    // what's being recorded
+    fn try_cue(&mut self, args: String) -> CmdResult<()> {
+        let label = match args.trim() {
+            "" => "mark",
+            label => label,
+        };
+        match &mut self.recording {
+            Some(recording) => recording.cues.mark(clock::current(), label),
+            None => return Err(CmdErr::Formatting {
+                err: "Not recording; cue sheets are written by 'rec start <file>'".to_string(),
+            }),
+        }
 
-        Ok(Command::Tc(TcArgs { tempo: ts_clone }))
+        Ok(())
     }
 
-    // TODO: make able to apply to Group
-    // TODO: implement naming Processes
-    //       and replace insert("seq".to_string(), ...) with
-    //       insert(name, ...)
-    fn try_seq(&mut self, args: String) -> CmdResult<Command> {
+    fn try_bundle(&mut self, args: String) -> CmdResult<()> {
+        // bundle export <file> [--hashes] [-f <file>...]
+        //
+        // packs the audio behind every loaded Track (or only its
+        // hash with --hashes) plus any extra files, e.g. presets
+        // and scripts, into one .blastpack
         let mut args = args.split_whitespace();
-        let name = args
+        let action = args
             .next()
-            .ok_or(CmdErr::MissingArg { 
-                arg: "name".to_string(), 
-                cmd: "seq".to_string() 
+            .ok_or(CmdErr::MissingArg {
+                arg: "export/import".to_string(),
+                cmd: "bundle".to_string(),
             })?;
-        let name = name.to_string();
-
-        // default assign to Process
-        let mut tempo: TempoRepr = {
-            // TODO: find object? needs to be more generic
-            let voice = self.find_voice(name.clone())?;
-            TempoRepr::new(voice.proc_tempi.len())
-        };
-        let mut period: usize = 4;
-        let mut steps: Vec<f32> = Vec::new();
-        let mut chance: Vec<f32> = Vec::new();
-        let mut jit: Vec<f32> = Vec::new();
-        // implement user-defined seed l8r
-        let mut rng = X128P::new(fast_seed());
 
-        while let Some(arg) = args.next() {
-            match arg {
-                "-t" | "--tempo" => {
-                    let t_arg = args
-                        .next()
-                        .ok_or(CmdErr::MissingArg {
-                            arg: "unit:interval".to_string(),
-                            cmd: "seq -t".to_string(),
-                        })?;
+        match action {
+            "export" => (),
+            "import" => return Err(CmdErr::Formatting {
+                err: "Tracks are loaded at startup; run 'blast bundle import <file>' from the shell".to_string(),
+            }),
+            _ => return Err(CmdErr::InvalidArg {
+                arg: action.to_owned(),
+                cmd: "bundle".to_string(),
+            }),
+        }
 
-                    let t_args: Vec<_> = t_arg.split(':').collect();
-
-                    if t_args.len() != 2 {
-                        if t_args[0] != "v" {
-                            return Err(CmdErr::TempoFormatting{});
-                        } else {
-                            // refer to Voice's TempoState
-                            tempo = {
-                                let voice = self.find_voice(name.clone())?;
-                                TempoRepr::clone_owner(&voice.tempo)
-                            };
-                            continue;
-                        }
-                    }
+        let pack = args
+            .next()
+            .ok_or(CmdErr::MissingArg {
+                arg: "file".to_string(),
+                cmd: "bundle export".to_string(),
+            })?;
 
-                    let u = t_args.get(0).unwrap();
+        let BundleFlags { hashes: hashes_only, files } = BundleFlags::parse_flags(self, &mut args, "bundle export")?;
 
-                    if *u == "c" {
-                        // find TempoContext
-                        let tc_name = t_args.get(1).unwrap();
-                        let tc_name = tc_name.to_string();
-                        let tc = self.find_tc(tc_name)?;
-                        tempo = TempoRepr::clone_owner(&tc);
-                        continue;
-                    }
+        // sorted so the same session always packs the same way
+        let mut tracks: Vec<String> = self.engine_state.tracks
+            .values()
+            .map(|t| t.path.clone())
+            .collect();
+        tracks.sort();
 
-                    if *u == "g" {
-                        // find Group
-                        let g_name = t_args.get(1).unwrap();
-                        let g_name = g_name.to_string();
-                        let g = self.find_group(g_name)?;
-                        tempo = TempoRepr::clone_owner(&g.tempo);
-                        continue;
-                    }
+        bundle::export(pack, &tracks, &files, hashes_only).map_err(|e| CmdErr::FileIo {
+            path: pack.to_string(),
+            err: format!("{:?}", e),
+        })?;
 
-                    // if not referring, then init new TempoState
-                    //
-                    let unit = match *u {
-                        "s" => TempoUnit::Samples,
-                        "m" => TempoUnit::Millis,
-                        "b" => TempoUnit::Bpm,
-                        _ => return Err(CmdErr::InvalidArg {
-                            arg: u.to_string(), 
-                            cmd: "seq -t".to_string()
-                        }),
-                    };
+        Ok(())
+    }
 
-                    let int_str = t_args.get(1).unwrap();
-                    let interval = int_str
-                                .parse::<f32>()
-                                .map_err(|_| CmdErr::InvalidArg { 
-                                    arg: int_str.to_string(), 
-                                    cmd: "seq -t".to_string() 
-                                })?;
+    // status [<name>]
+    //
+    // a Voice (with its Processes, Seq patterns drawn as steps),
+    // Group or TempoContext: its index, tempo and transport;
+    // with no name, where the global clock is in bars
+    pub fn status(&mut self, args: String) -> CmdResult<String> {
+        let Some(name) = args.split_whitespace().next() else {
+            let (beat_len, beats_per_bar) = clock::meter();
+            let bpm = 60.0 * sample_rate::get() as f32 / beat_len;
+            return Ok(format!("clock  {}  ({beats_per_bar} beats at {bpm:.1} BPM)", clock::position()));
+        };
 
-                    tempo.init(TempoMode::Process, unit, interval);
+        if let Ok(voice) = self.find_voice(name.to_string()) {
+            let voice = voice.clone();
+            let tempo = self.engine_state.tempo_name(&voice.tempo);
+            let mut procs: Vec<(&String, &ProcRepr)> = voice.processes.iter().collect();
+            procs.sort_by_key(|(_, p)| p.idx);
+
+            let mut out = format!("{name}  voice {}  {}  tempo {tempo}", voice.idx, voice.transport.name());
+            for (p_name, p) in procs {
+                match &p.pattern {
+                    Some(pattern) => out.push_str(&format!("\n  {p_name}  {}", pattern.diagram())),
+                    None => out.push_str(&format!("\n  {p_name}")),
                 }
-                "-p" | "--period" => {
-                    period = args
-                        .next()
-                        .ok_or(CmdErr::MissingArg { 
-                            arg: "value".to_string(), 
-                            cmd: "seq -p".to_string() 
-                        })
-                        .and_then(|raw| 
-                            raw.parse::<usize>()
-                               .map_err(|_| CmdErr::InvalidArg { 
-                                   arg: raw.to_owned(), 
-                                   cmd: "seq -p".to_string() 
-                               })
-                        )?;
-                }
-                "-s" | "--steps" => {
-                    let s_arg = args
-                        .next()
-                        .ok_or(CmdErr::MissingArg {
-                            arg: "value".to_string(),
-                            cmd: "seq -s".to_string(),
-                        })?;
-
-                    let step_strs: Vec<&str> = s_arg.split(',').collect();
+            }
+            return Ok(out);
+        }
 
-                    for step in step_strs {
-                        match step.parse::<f32>() {
-                            Ok(val) => steps.push(val),
-                            Err(_) => return Err(CmdErr::InvalidArg {
-                                    arg: step.to_owned(),
-                                    cmd: "seq -s".to_string(),
-                                }),
-                        }
-                    }
+        let state = &self.engine_state;
+        if let Some(group) = state.groups.get(name) {
+            let mut voices: Vec<(&String, &VoiceRepr)> = group.voices.iter().collect();
+            voices.sort_by_key(|(_, v)| v.idx);
 
-                    // set chance and jit Vecs to same len as steps
-                    // to avoid panics
-                    chance.resize(steps.len(), 100f32);
-                    jit.resize(steps.len(), 100f32);
-                }
-                "-c" | "--chance" => {
-                    // a value specifies chance for the step
-                    //// at the same index as the value
-                    // _ is shorthand for 100
-                    // n:val specifies chance=val for step=n
-                    // a:val sets the same chance=val for all steps
-                    // n1-n2:val specifies a chance=val for
-                    //// n1-n2 contiguous steps
-
-                    if steps.len() < 1 {
-                        return Err(CmdErr::Formatting {
-                            err: "Must provide arguments to -s/--steps before -c/--chance or -j/--jitter".to_string()
-                        });
-                    }
+            let tempo = state.tempo_name(&group.tempo);
+            let mut out = format!("{name}  group {}  {}  tempo {tempo}", group.idx, group.transport.name());
+            for (v_name, v) in voices {
+                out.push_str(&format!("\n  {v_name}  {}", v.transport.name()));
+            }
+            return Ok(out);
+        }
 
-                    let c_arg = args.next().ok_or(CmdErr::MissingArg {
-                        arg: "value".to_string(),
-                        cmd: "seq -c".to_string(),
-                    })?;
+        if let Some(tc) = state.tempo_cons.get(name) {
+            return Ok(format!(
+                "{name}  tempocontext {}  {}  {}  {} beats a bar",
+                tc.idx, tc.transport.name(), state.tempo_name(tc), tc.beats_per_bar,
+            ));
+        }
 
-                    let c_strs: Vec<&str> = c_arg.split(',').collect();
+        Err(CmdErr::NoItem {
+            ty: "Voice, Group or TempoContext".to_string(),
+            name: name.to_string(),
+        })
+    }
 
-                    let mut spec_char = |s: &str| -> Option<char> {
-                        for c in s.chars() {
-                            match c {
-                                '_' => return Some('_'),
-                                ':' => return Some(':'),
-                                '-' => return Some('-'),
-                                _ => continue,
-                            }
-                        }
-                        None
-                    };
-                    
-                    // use chance.len() if too many arguments were provided
-                    let len = {
-                        if c_strs.len() > chance.len() {
-                            chance.len()
-                        } else {
-                            c_strs.len()
+    // what could finish the word being typed (the last in line,
+    // which is everything up to the cursor), and where that word
+    // starts: command names, then the command's flags, or names
+    // from the state for whatever argument the word is
+    pub fn complete(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let words: Vec<&str> = line[..start].split_whitespace().collect();
+
+        let mut found: Vec<String> = match words.first() {
+            None => schema::COMMANDS
+                .iter()
+                .flat_map(|spec| std::iter::once(spec.name).chain(spec.aliases.iter().copied()))
+                .map(str::to_string)
+                .chain(self.aliases.keys().cloned())
+                .collect(),
+            Some(cmd) => {
+                let Some(spec) = schema::find(cmd) else {
+                    return (start, Vec::new());
+                };
+                if word.starts_with('-') {
+                    spec.all_args().flat_map(|arg| arg.flags.iter().map(|f| f.to_string())).collect()
+                } else {
+                    // a flag's value, or else the next positional argument
+                    let ty = match words[1..].last().filter(|w| w.starts_with('-')) {
+                        Some(flag) => spec.all_args().find(|a| a.flags.contains(flag)).map(|a| a.ty),
+                        None => {
+                            let given = words[1..]
+                                .iter()
+                                .enumerate()
+                                .filter(|(i, w)| !w.starts_with('-') && (*i == 0 || !words[*i].starts_with('-')))
+                                .count();
+                            spec.args.iter().filter(|a| a.flags.is_empty()).nth(given).map(|a| a.ty)
                         }
                     };
-
-                    for i in {0..len} {
-                        let string = c_strs.get(i).unwrap();
-                        match spec_char(string) {
-                            Some(c) => {
-                                match c {
-                                    '_' => chance[i] = 100.0,
-                                    ':' => {
-                                        let at_index: Vec<&str> = string.split(':').collect();
-                                        if at_index.len() != 2 {
-                                            return Err(
-                                                CmdErr::Formatting {
-                                                    err: "Indexed chance arguments must be formatted beat:chance".to_string(),
-                                                }
-                                            );
-                                        }
-
-                                        // get chance first in case index = 'a'
-                                        let chance_str = at_index.get(1).unwrap();
-                                        let chance_val = chance_str
-                                            .parse::<f32>()
-                                            .map_err(|_| CmdErr::InvalidArg {
-                                                arg: chance_str.to_string(),
-                                                cmd: "seq -c".to_string(),
-                                            })?;
-
-                                        let index_str = at_index.get(0).unwrap();
-
-                                        // if index = 'a', set all chance vals to chance_val and continue
-                                        if *index_str == "a" {
-                                            for i in {0..chance.len()} {
-                                                chance[i] = chance_val;
-                                            }
-                                            continue;
-                                        }
-
-                                        let index = index_str
-                                                    .parse::<f32>()
-                                                    .map_err(|_| CmdErr::InvalidArg {
-                                                        arg: index_str.to_string(),
-                                                        cmd: "seq -c".to_string(),
-                                                    })?;
-                                        
-                                        for i in {0..steps.len()} {
-                                            let step = *steps.get(i).unwrap();
-                                            if index == step {
-                                                chance[i] = chance_val;
-                                                break;
-                                            }
-                                            // only reaches here if index isn't found
-                                            return Err(CmdErr::Formatting {
-                                                err: "Invalid index for seq -c".to_string()
-                                            });
-                                        }
-                                    }
-                                    '-' => {
-                                        let at_indices: Vec<&str> = string.split(':').collect();
-                                        if at_indices.len() != 2 {
-                                            return Err(
-                                                CmdErr::Formatting {
-                                                    err: "Ranged chance arguments must be formatted range:chance".to_string(),
-                                                }
-                                            );
-                                        }
-                                        
-                                        let chance_str = at_indices.get(1).unwrap();
-                                        let chance_val = chance_str
-                                                         .parse::<f32>()
-                                                         .map_err(|_| CmdErr::InvalidArg {
-                                                            arg: chance_str.to_string(),
-                                                            cmd: "seq -c".to_string(),
-                                                         })?;
-
-                                        let indices = at_indices.get(0).unwrap();
-                                        let indices: Vec<&str> = indices.split('-').collect();
-                                        if indices.len() != 2 {
-                                            return Err(
-                                                CmdErr::Formatting {
-                                                    err: "Ranges must be formatted lower-upper".to_string(),
-                                                }
-                                            );
-                                        }
-
-                                        let i1_str = indices.get(0).unwrap();
-                                        let idx1 = i1_str
-                                                   .parse::<f32>()
-                                                   .map_err(|_| CmdErr::InvalidArg {
-                                                        arg: i1_str.to_string(),
-                                                        cmd: "seq -c".to_string(),
-                                                   })?;
-                                        let i2_str = indices.get(0).unwrap();
-                                        let idx2 = i2_str
-                                                   .parse::<f32>()
-                                                   .map_err(|_| CmdErr::InvalidArg {
-                                                        arg: i2_str.to_string(),
-                                                        cmd: "seq -c".to_string(),
-                                                   })?;
-
-                                        let mut lower = idx1;
-                                        let mut upper = idx2;
-
-                                        if lower > upper {
-                                            lower = idx2;
-                                            upper = idx1;
-                                        }
-                        
-                                        // only check against lower because who cares if upper is too high
-                                        if lower > *steps.get(steps.len() - 1).unwrap() {
-                                            return Err(CmdErr::Formatting {
-                                                err: "seq -c range applies to nothing".to_string()
-                                            });
-                                        }
-
-                                        for idx in {0..steps.len()} {
-                                            let step = *steps.get(idx).unwrap();
-                                            if step >= lower && step <= upper {
-                                                chance[idx] = chance_val;
-                                            }
-                                        }
-                                    }
-                                    _ => (),
-                                }
-                            }
-                            // no special chars; just assign value at current index
-                            None => {
-                                let chance_val = string
-                                                 .parse::<f32>()
-                                                 .map_err(|_| CmdErr::InvalidArg { 
-                                                     arg: string.to_string(), 
-                                                     cmd: "seq -c".to_string() 
-                                                 })?;
-                                chance[i] = chance_val;
-                            }
-                        }
-                    }                   
+                    ty.map(|ty| self.names_for(ty, word)).unwrap_or_default()
                 }
-                "-j" | "--jitter" => {
-                    // a value specifies jitter for the step
-                    //// at the same index as the value
-                    // _ means no jitter
-                    // e|l indicates jitter before=e and after=l the beat
-                    //// (of ranges e-0.0 and 0.0-l)
-                    // e1-e2|l1-l2 indicate jitter ranges
-                    // n:e|l specifies jitter=e|l for step=n
-                    // a:e|l specifies jitter=e|l for all steps
-                    // n1-n2,e1-2|l1-l2 specifies jitter ranges for
-                    //// n1-n2 contiguous steps
-                }
-                _ => return Err(CmdErr::InvalidArg { arg: arg.to_owned(), cmd: "seq".to_string() }),
             }
+        };
+
+        found.retain(|f| f.starts_with(word));
+        found.sort();
+        found.dedup();
+        (start, found)
+    }
+
+    fn names_for(&self, ty: schema::ArgType, word: &str) -> Vec<String> {
+        use schema::ArgType;
+
+        let state = &self.engine_state;
+        match ty {
+            ArgType::Track => state.tracks.keys().cloned().collect(),
+            ArgType::Voice => state.voices
+                .keys()
+                .cloned()
+                .chain(state.groups.iter().flat_map(|(g_name, g)| {
+                    g.voices.keys().map(move |name| format!("{g_name}.{name}"))
+                }))
+                .collect(),
+            ArgType::Group => state.groups.keys().cloned().collect(),
+            ArgType::TempoContext => state.tempo_cons.keys().cloned().collect(),
+            ArgType::Bus => state.buses.keys().cloned().collect(),
+            ArgType::Tempo => state.tempo_cons
+                .keys()
+                .map(|name| format!("c:{name}"))
+                .chain(state.groups.keys().map(|name| format!("g:{name}")))
+                .collect(),
+            ArgType::Choice(choices) => choices.iter().map(|c| c.to_string()).collect(),
+            // only the item after the last comma is being typed
+            ArgType::List(inner) => {
+                let done = word.rfind(',').map_or("", |i| &word[..=i]);
+                self.names_for(*inner, &word[done.len()..])
+                    .into_iter()
+                    .map(|name| format!("{done}{name}"))
+                    .collect()
+            }
+            _ => Vec::new(),
         }
+    }
 
-        // TODO: allow for Idx::Group
-        let voice = self.find_voice(name.clone())?;
-        let repr = ProcRepr::new(
-            voice.processes.len(), 
-            Idx::Voice(voice.idx), 
-            Some(TempoRepr::clone(&tempo))
-        );
-        voice.processes.insert("seq".to_string(), repr);
-        // push tempo to proc_tempi only if owned by the Process
-        if tempo.mode == TempoMode::Process {
-            voice.proc_tempi.insert(
-                voice.proc_tempi.len(), 
-                TempoRepr::clone(&tempo)
-            );
+    // ls [tracks|voices|groups|tc|buses]
+    //
+    // what exists, in index order; everything with no argument
+    pub fn list(&self, args: String) -> CmdResult<String> {
+        let all = ["tracks", "voices", "groups", "tc", "buses"];
+        let kinds: Vec<&str> = match args.split_whitespace().next() {
+            None => all.to_vec(),
+            Some("tempocon") => vec!["tc"],
+            Some(kind) if all.contains(&kind) => vec![kind],
+            Some(kind) => return Err(CmdErr::InvalidArg {
+                arg: kind.to_string(),
+                cmd: "ls".to_string(),
+            }),
+        };
+
+        let state = &self.engine_state;
+        let mut out = Vec::<String>::new();
+        for kind in kinds {
+            // (index, line), sorted by index
+            let mut rows: Vec<((usize, usize), String)> = match kind {
+                "tracks" => state.tracks
+                    .iter()
+                    .map(|(name, t)| ((t.idx, 0), format!("{}  {name}  {}ch  {}", t.idx, t.channels, t.path)))
+                    .collect(),
+                "voices" => state.voices
+                    .iter()
+                    .map(|(name, v)| ((0, v.idx), (v.idx.to_string(), name.clone(), v)))
+                    .chain(state.groups.iter().flat_map(|(g_name, g)| {
+                        g.voices.iter().map(move |(name, v)| {
+                            ((g.idx + 1, v.idx), (format!("{}.{}", g.idx, v.idx), format!("{g_name}.{name}"), v))
+                        })
+                    }))
+                    .map(|(key, (idx, name, v))| {
+                        let tempo = state.tempo_name(&v.tempo);
+                        (key, format!("{idx}  {name}  {}  tempo {tempo}", v.transport.name()))
+                    })
+                    .collect(),
+                "groups" => state.groups
+                    .iter()
+                    .map(|(name, g)| {
+                        let tempo = state.tempo_name(&g.tempo);
+                        let line = format!("{}  {name}  {}  tempo {tempo}  {} voices", g.idx, g.transport.name(), g.voices.len());
+                        ((g.idx, 0), line)
+                    })
+                    .collect(),
+                "buses" => state.buses
+                    .iter()
+                    .map(|(name, b)| ((*b, 0), format!("{b}  {name}")))
+                    .collect(),
+                _ => state.tempo_cons
+                    .iter()
+                    .map(|(name, t)| {
+                        let line = format!("{}  {name}  {}  {}", t.idx, t.transport.name(), state.tempo_name(t));
+                        ((t.idx, 0), line)
+                    })
+                    .collect(),
+            };
+            rows.sort_by_key(|(key, _)| *key);
+
+            out.push(kind.to_string());
+            match rows.is_empty() {
+                true => out.push("  none".to_string()),
+                false => out.extend(rows.into_iter().map(|(_, line)| format!("  {line}"))),
+            }
         }
 
-        let args = SeqArgs {
-            idx: Idx::Voice(voice.idx),
-            tempo,
-            period,
-            steps,
-            chance,
-            jit,
-            rng,
+        Ok(out.join("\n"))
+    }
+
+    // proc ls [<voice>]
+    //
+    // every Voice's Processes (or one Voice's) by name and kind
+    pub fn list_procs(&mut self, args: String) -> CmdResult<String> {
+        let mut voices: Vec<(String, &VoiceRepr)> = match args.split_whitespace().nth(1) {
+            Some(name) => {
+                let voice = self.find_voice(name.to_string())?;
+                vec![(name.to_string(), &*voice)]
+            }
+            None => {
+                let state = &self.engine_state;
+                state.voices
+                    .iter()
+                    .map(|(name, v)| (name.clone(), v))
+                    .chain(state.groups.iter().flat_map(|(g_name, g)| {
+                        g.voices.iter().map(move |(name, v)| (format!("{g_name}.{name}"), v))
+                    }))
+                    .collect()
+            }
         };
+        voices.sort_by(|a, b| a.0.cmp(&b.0));
 
-        Ok(Command::Seq(args))
+        let mut lines = Vec::<String>::new();
+        for (name, voice) in voices {
+            let mut procs: Vec<(&String, &ProcRepr)> = voice.processes.iter().collect();
+            if procs.is_empty() {
+                continue;
+            }
+            procs.sort_by_key(|(_, p)| p.idx);
+
+            lines.push(name);
+            for (p_name, p) in procs {
+                let kind = p.kind.name();
+                match &p.pattern {
+                    Some(pattern) => lines.push(format!("  {p_name}  {kind}  {}", pattern.diagram())),
+                    None => lines.push(format!("  {p_name}  {kind}")),
+                }
+            }
+        }
+
+        match lines.is_empty() {
+            true => Ok("No Processes".to_string()),
+            false => Ok(lines.join("\n")),
+        }
     }
 
     // StateResults (returned to a CmdResult fn)
     //
-    fn parse_type_and_name(&self, args: String, cmd: String) -> StateResult<(String, String)> {
-        let mut args = args.split_whitespace();
-        let first = args
-            .next()
-            .ok_or(StateErr::MissingArg { 
-                arg: "type and name".to_string(), 
-                cmd: cmd.clone() 
-            })?;
-        let second = args
-            .next()
-            .ok_or(StateErr::MissingArg { 
-                arg: "type or name".to_string(), 
-                cmd: cmd
-            })?;
+    // a Group's start and stop carry to its Voices; pausing
+    // something that isn't playing leaves it as it was
+    fn set_transport(&mut self, idxs: &[Idx], to: Transport, cascade: bool) {
+        let next = |now: Transport| match (now, to) {
+            (Transport::Stopped, Transport::Paused) => Transport::Stopped,
+            _ => to,
+        };
+
+        let state = &mut self.engine_state;
+        for idx in idxs {
+            match *idx {
+                Idx::Voice(i) => {
+                    for voice in state.voices.values_mut().filter(|v| v.idx == i) {
+                        voice.transport = next(voice.transport);
+                    }
+                }
+                Idx::Group(i) => {
+                    for group in state.groups.values_mut().filter(|g| g.idx == i) {
+                        group.transport = next(group.transport);
+                        for voice in group.voices.values_mut().filter(|_| cascade) {
+                            voice.transport = to;
+                        }
+                    }
+                }
+                Idx::Tempo(i) => {
+                    for tc in state.tempo_cons.values_mut().filter(|t| t.idx == i) {
+                        tc.transport = next(tc.transport);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // any number of -v|-g|-t <name> pairs, or all: TempoContexts
+    // first, so Voices and Groups on them don't start without them
+    fn get_targets(&mut self, args: String, cmd: String) -> StateResult<Vec<Idx>> {
+        if args.trim() == "all" {
+            let state = &self.engine_state;
+            let mut tcs: Vec<usize> = state.tempo_cons.values().map(|t| t.idx).collect();
+            let mut groups: Vec<usize> = state.groups.values().map(|g| g.idx).collect();
+            let mut voices: Vec<usize> = state.voices.values().map(|v| v.idx).collect();
+            tcs.sort();
+            groups.sort();
+            voices.sort();
+
+            return Ok(tcs.into_iter().map(Idx::Tempo)
+                .chain(groups.into_iter().map(Idx::Group))
+                .chain(voices.into_iter().map(Idx::Voice))
+                .collect());
+        }
+
+        let words: Vec<&str> = args.split_whitespace().collect();
+        if words.is_empty() || !words.len().is_multiple_of(2) {
+            return Err(StateErr::MissingArg {
+                arg: "type and name, or all".to_string(),
+                cmd,
+            });
+        }
 
-        Ok((first.to_string(), second.to_string()))
+        let mut idxs = Vec::<Idx>::new();
+        for pair in words.chunks(2) {
+            idxs.push(self.get_idx(pair[0].to_string(), pair[1].to_string())?);
+        }
+        Ok(idxs)
     }
 
     fn get_idx(&mut self, ty: String, name: String) -> StateResult<Idx> {
@@ -1201,24 +5389,37 @@ impl CmdProcessor {
                 let t = self.find_tc(name)?;
                 Ok(Idx::Tempo(t.idx))
             }
-            _ => return Err(StateErr::MissingArg { 
+            _ => Err(StateErr::MissingArg { 
                 arg: "type".to_string(), 
                 cmd: "-v/-g/-t".to_string() 
             }),
         }
     }
 
+    // like get_idx for -v, but Voices inside Groups (group.voice)
+    // live in the Group's Vec, so they need both indices
+    fn voice_idx(&mut self, name: String) -> StateResult<Idx> {
+        match name.split_once('.') {
+            Some((g_name, _)) => {
+                let g_idx = self.find_group(g_name.to_string())?.idx;
+                let v_idx = self.find_voice(name)?.idx;
+                Ok(Idx::GroupVoice(g_idx, v_idx))
+            }
+            None => Ok(Idx::Voice(self.find_voice(name)?.idx)),
+        }
+    }
+
     fn find_track(&mut self, name: String) -> StateResult<&mut TrackRepr> {
         self.engine_state.tracks
             .get_mut(&name)
             .ok_or(StateErr::NoItem { 
                 ty: "track".to_string(), 
-                name: name
+                name
             })
     }
 
     fn find_voice(&mut self, args: String) -> StateResult<&mut VoiceRepr> {      
-        let mut args: Vec<&str> = args.split('.').collect();
+        let args: Vec<&str> = args.split('.').collect();
         if args.len() > 2 {
             return Err(StateErr::Formatting { 
                 err: "Too many delimiters for format group.voice".to_string() 
@@ -1227,7 +5428,7 @@ impl CmdProcessor {
 
         // args will never be 0
         if args.len() == 1 {
-            let v_name = args.get(0).unwrap();
+            let v_name = args.first().unwrap();
             let v_name = v_name.to_string();
             self.engine_state.voices
                 .get_mut(&v_name)
@@ -1236,7 +5437,7 @@ impl CmdProcessor {
                     group: None 
                 })
         } else {
-            let group = args.get(0).unwrap();
+            let group = args.first().unwrap();
             let group = group.to_string();
             let voice = args.get(1).unwrap();
             let voice = voice.to_string();
@@ -1251,10 +5452,10 @@ impl CmdProcessor {
                         })
                 }
                 None => {
-                    return Err(StateErr::NoItem { 
+                    Err(StateErr::NoItem { 
                         ty: "Group".to_string(), 
                         name: group 
-                    });
+                    })
                 }
             }
         }
@@ -1264,7 +5465,16 @@ impl CmdProcessor {
         self.engine_state.groups.get_mut(&name)
             .ok_or(StateErr::NoItem { 
                 ty: "Group".to_string(), 
-                name: name
+                name
+            })
+    }
+
+    fn find_bus(&self, name: String) -> StateResult<usize> {
+        self.engine_state.buses.get(&name)
+            .copied()
+            .ok_or(StateErr::NoItem {
+                ty: "bus".to_string(),
+                name
             })
     }
 
@@ -1272,7 +5482,7 @@ impl CmdProcessor {
         self.engine_state.tempo_cons.get_mut(&name)
             .ok_or(StateErr::NoItem { 
                 ty: "TempoContext".to_string(), 
-                name: name 
+                name 
             })
     }
 }
@@ -1296,6 +5506,7 @@ type StateResult<T> = Result<T, StateErr>;
 macro_rules! cmd_errors {
     ( $( $var:ident { $( $arg:ident : $type:ty ),* } ),* $(,)? ) => {
         #[derive(Debug)]
+        #[non_exhaustive]
         pub enum CmdErr {
             $(
                 $var { $( $arg: $type, )* },
@@ -1303,6 +5514,7 @@ macro_rules! cmd_errors {
         }
 
         #[derive(Debug)]
+        #[allow(dead_code)] // not every variant is raised internally
         enum StateErr {
             $(
                 $var { $( $arg: $type, )* },
@@ -1329,15 +5541,223 @@ cmd_errors! {
     MissingArg { arg: String, cmd: String },
     InvalidArg { arg: String, cmd: String },
     AlreadyIs { ty: String, name: String },
-    NoCmd { cmd: String },
+    NoCmd { cmd: String, suggestion: Option<String> },
     NoItem { ty: String, name: String },
     NoVoice { name: String, group: Option<String> },
+    FileIo { path: String, err: String },
+    FlagTwice { flag: String, cmd: String },
 }
 
 // display different messages based on error
 //
 use std::fmt;
 
+// seq -c: a value per step in order (_ for 100), n:val for step n,
+// a:val for all of them, or n1-n2:val for every step in between
+fn seq_chance(raw: &str, steps: &[f32]) -> CmdResult<Vec<f32>> {
+    let mut chance = vec![100f32; steps.len()];
+    let c_strs: Vec<&str> = raw.split(',').collect();
+
+    let spec_char = |s: &str| -> Option<char> {
+        for c in s.chars() {
+            match c {
+                '_' => return Some('_'),
+                ':' => return Some(':'),
+                '-' => return Some('-'),
+                _ => continue,
+            }
+        }
+        None
+    };
+    
+    // use chance.len() if too many arguments were provided
+    let len = {
+        if c_strs.len() > chance.len() {
+            chance.len()
+        } else {
+            c_strs.len()
+        }
+    };
+
+    for i in 0..len {
+        let string = c_strs.get(i).unwrap();
+        match spec_char(string) {
+            Some(c) => {
+                match c {
+                    '_' => chance[i] = 100.0,
+                    ':' => {
+                        let at_index: Vec<&str> = string.split(':').collect();
+                        if at_index.len() != 2 {
+                            return Err(
+                                CmdErr::Formatting {
+                                    err: "Indexed chance arguments must be formatted beat:chance".to_string(),
+                                }
+                            );
+                        }
+
+                        // get chance first in case index = 'a'
+                        let chance_str = at_index.get(1).unwrap();
+                        let chance_val = chance_str
+                            .parse::<f32>()
+                            .map_err(|_| CmdErr::InvalidArg {
+                                arg: chance_str.to_string(),
+                                cmd: "seq -c".to_string(),
+                            })?;
+
+                        let index_str = at_index.first().unwrap();
+
+                        // if index = 'a', set all chance vals to chance_val and continue
+                        if *index_str == "a" {
+                            chance.fill(chance_val);
+                            continue;
+                        }
+
+                        let index = index_str
+                                    .parse::<f32>()
+                                    .map_err(|_| CmdErr::InvalidArg {
+                                        arg: index_str.to_string(),
+                                        cmd: "seq -c".to_string(),
+                                    })?;
+                        
+                        match steps.iter().position(|step| *step == index) {
+                            Some(i) => chance[i] = chance_val,
+                            // index isn't one of the steps
+                            None => return Err(CmdErr::Formatting {
+                                err: "Invalid index for seq -c".to_string()
+                            }),
+                        }
+                    }
+                    '-' => {
+                        let at_indices: Vec<&str> = string.split(':').collect();
+                        if at_indices.len() != 2 {
+                            return Err(
+                                CmdErr::Formatting {
+                                    err: "Ranged chance arguments must be formatted range:chance".to_string(),
+                                }
+                            );
+                        }
+                        
+                        let chance_str = at_indices.get(1).unwrap();
+                        let chance_val = chance_str
+                                         .parse::<f32>()
+                                         .map_err(|_| CmdErr::InvalidArg {
+                                            arg: chance_str.to_string(),
+                                            cmd: "seq -c".to_string(),
+                                         })?;
+
+                        let indices = at_indices.first().unwrap();
+                        let indices: Vec<&str> = indices.split('-').collect();
+                        if indices.len() != 2 {
+                            return Err(
+                                CmdErr::Formatting {
+                                    err: "Ranges must be formatted lower-upper".to_string(),
+                                }
+                            );
+                        }
+
+                        let i1_str = indices.first().unwrap();
+                        let idx1 = i1_str
+                                   .parse::<f32>()
+                                   .map_err(|_| CmdErr::InvalidArg {
+                                        arg: i1_str.to_string(),
+                                        cmd: "seq -c".to_string(),
+                                   })?;
+                        let i2_str = indices.get(1).unwrap();
+                        let idx2 = i2_str
+                                   .parse::<f32>()
+                                   .map_err(|_| CmdErr::InvalidArg {
+                                        arg: i2_str.to_string(),
+                                        cmd: "seq -c".to_string(),
+                                   })?;
+
+                        let mut lower = idx1;
+                        let mut upper = idx2;
+
+                        if lower > upper {
+                            lower = idx2;
+                            upper = idx1;
+                        }
+        
+                        // only check against lower because who cares if upper is too high
+                        if lower > *steps.last().unwrap() {
+                            return Err(CmdErr::Formatting {
+                                err: "seq -c range applies to nothing".to_string()
+                            });
+                        }
+
+                        for (step, c) in steps.iter().zip(chance.iter_mut()) {
+                            if *step >= lower && *step <= upper {
+                                *c = chance_val;
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            // no special chars; just assign value at current index
+            None => {
+                let chance_val = string
+                                 .parse::<f32>()
+                                 .map_err(|_| CmdErr::InvalidArg { 
+                                     arg: string.to_string(), 
+                                     cmd: "seq -c".to_string() 
+                                 })?;
+                chance[i] = chance_val;
+            }
+        }
+    }
+
+    Ok(chance)
+}
+
+// how hard each of a Seq's steps hits, in step order: linear or
+// dB (e.g. 1,0.5,-6dB); _ is full level, and steps left off hit
+// at full level
+fn seq_levels(raw: &str, steps: usize, cmd: &str) -> CmdResult<Vec<f32>> {
+    let strs: Vec<&str> = raw.split(',').collect();
+    if strs.len() > steps {
+        return Err(CmdErr::Formatting {
+            err: format!("{} levels for {} steps", strs.len(), steps),
+        });
+    }
+
+    let mut levels = vec![1f32; steps];
+    for (level, raw) in levels.iter_mut().zip(strs) {
+        *level = match raw {
+            "_" => 1.0,
+            _ => gain_val(raw).ok_or(CmdErr::InvalidArg {
+                arg: raw.to_owned(),
+                cmd: cmd.to_string(),
+            })?,
+        };
+    }
+
+    Ok(levels)
+}
+
+// linear, or in decibels with a dB suffix (e.g. -6dB)
+fn gain_val(raw: &str) -> Option<f32> {
+    match raw.strip_suffix("dB").or(raw.strip_suffix("db")) {
+        Some(db) => db.parse::<f32>().map(|db| 10f32.powf(db / 20.0)),
+        None => raw.parse::<f32>(),
+    }
+    .ok()
+    .filter(|v| *v >= 0.0)
+}
+
+impl CmdErr {
+    // the part of the line the error is about, if it names one
+    pub fn blame(&self) -> Option<&str> {
+        match self {
+            CmdErr::InvalidArg { arg, .. } => Some(arg),
+            CmdErr::AlreadyIs { name, .. } | CmdErr::NoItem { name, .. } | CmdErr::NoVoice { name, .. } => Some(name),
+            CmdErr::NoCmd { cmd, .. } => Some(cmd),
+            CmdErr::FileIo { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for CmdErr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -1357,8 +5777,12 @@ impl fmt::Display for CmdErr {
             CmdErr::AlreadyIs { ty, name } => {
                 write!(f, "Already a {} called '{}'", ty, name)
             }
-            CmdErr::NoCmd { cmd } => {
-                write!(f, "Invalid command '{}'", cmd)
+            CmdErr::NoCmd { cmd, suggestion } => {
+                write!(f, "Invalid command '{}'", cmd)?;
+                match suggestion {
+                    Some(s) => write!(f, "; did you mean '{}'?", s),
+                    None => write!(f, " (help lists them)"),
+                }
             }
             CmdErr::NoItem { ty, name } => {
                 write!(f, "Couldn't find {} '{}'", ty, name)
@@ -1369,6 +5793,12 @@ impl fmt::Display for CmdErr {
                     None => write!(f, "Couldn't find Voice '{}'", name),
                 }
             }
+            CmdErr::FileIo { path, err } => {
+                write!(f, "Couldn't access '{}': {}", path, err)
+            }
+            CmdErr::FlagTwice { flag, cmd } => {
+                write!(f, "'{}' given twice for '{}'", flag, cmd)
+            }
         }
     }
 }
@@ -0,0 +1,83 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::audio_processing::blast_time::sample_rate;
+
+// cue sheet
+//
+// rec start <file> opens one next to the recording (<stem>.cue):
+// markers are stamped with the global clock as commands are
+// accepted, and counted from the recording's first sample when
+// the sheet is written, so they line up with the WAV it names
+//
+// the sheet is written as a CUE file: the recording's start and
+// every marker become a TRACK whose INDEX is mm:ss:ff (75 frames
+// per second)
+//
+pub struct CueSheet {
+    path: String,
+    audio: String, // the recording, as the sheet refers to it
+    markers: Vec<Marker>,
+}
+
+struct Marker {
+    at: u64, // the global clock when it was marked
+    label: String,
+}
+
+impl CueSheet {
+    // the sheet for the recording at audio
+    pub fn new(audio: &str) -> Self {
+        let path = match Path::new(audio).with_extension("cue").to_string_lossy() {
+            // never over the recording itself
+            path if path == audio => format!("{audio}.cue"),
+            path => path.into_owned(),
+        };
+        let name = Path::new(audio).file_name().map(|name| name.to_string_lossy().into_owned());
+
+        Self {
+            path,
+            audio: name.unwrap_or_else(|| audio.to_string()),
+            markers: Vec::<Marker>::new(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn mark(&mut self, at: u64, label: &str) {
+        self.markers.push(Marker { at, label: label.to_string() });
+    }
+
+    // start: the global clock at the recording's first sample;
+    // anything marked before it is at 00:00:00
+    pub fn write(&self, start: u64) -> io::Result<()> {
+        let mut f = File::create(&self.path)?;
+        writeln!(f, "TITLE \"blast session\"")?;
+        writeln!(f, "FILE \"{}\" WAVE", self.audio.replace('"', "'"))?;
+
+        // CUE requires the first track to start at 00:00:00
+        let first = Marker { at: start, label: "rec start".to_string() };
+        for (i, marker) in std::iter::once(&first).chain(&self.markers).enumerate() {
+            writeln!(f, "  TRACK {:02} AUDIO", i + 1)?;
+            writeln!(f, "    TITLE \"{}\"", marker.label.replace('"', "'"))?;
+            writeln!(f, "    INDEX 01 {}", cue_time(marker.at.saturating_sub(start)))?;
+        }
+
+        Ok(())
+    }
+}
+
+// samples -> mm:ss:ff
+fn cue_time(samples: u64) -> String {
+    let sr = sample_rate::get().max(1) as u64;
+    let frames = samples * 75 / sr;
+    let (mins, rest) = (frames / (75 * 60), frames % (75 * 60));
+    let (secs, frames) = (rest / 75, rest % 75);
+
+    format!("{:02}:{:02}:{:02}", mins, secs, frames)
+}
@@ -0,0 +1,119 @@
+use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::{
+    ffi::{CStr, c_char, c_void},
+    ptr,
+};
+
+#[cfg(target_os = "linux")]
+use alsa_sys::*;
+
+// devices
+//
+// the PCMs ALSA knows of, as snd_device_name_hint lists them:
+// hw: and plughw: for every card (USB interfaces included), and
+// whatever asoundrc defines (default, dmix, pulse, ...)
+//
+// any of the names can be given to --device, or as device in
+// blast.conf, instead of the default hw:0,0
+//
+// elsewhere they're cpal's output devices, if it's built in
+//
+
+#[cfg(target_os = "linux")]
+pub const DEFAULT_DEVICE: &str = "hw:0,0";
+
+// the one opened, once it is
+static PLAYING: Mutex<String> = Mutex::new(String::new());
+
+pub fn set_playing(name: &str) {
+    *PLAYING.lock().unwrap() = name.to_string();
+}
+
+pub fn playing() -> String {
+    PLAYING.lock().unwrap().clone()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub name: String,
+    pub desc: Option<String>, // one line, card and device
+}
+
+// the PCMs that can play (capture-only ones are left out)
+#[cfg(target_os = "linux")]
+pub fn playback() -> Result<Vec<Device>, String> {
+    let mut devices = Vec::new();
+    unsafe {
+        let mut hints: *mut *mut c_void = ptr::null_mut();
+        let err = snd_device_name_hint(-1, c"pcm".as_ptr(), &mut hints);
+        if err < 0 {
+            let why = CStr::from_ptr(snd_strerror(err)).to_string_lossy();
+            return Err(format!("Couldn't list audio devices: {why}"));
+        }
+
+        let mut hint = hints;
+        while !(*hint).is_null() {
+            let name = take_hint(*hint, c"NAME");
+            let desc = take_hint(*hint, c"DESC");
+            let ioid = take_hint(*hint, c"IOID");
+            // no IOID means both ways
+            if let Some(name) = name
+                && ioid.as_deref() != Some("Input") {
+                let desc = desc.map(|d| d.lines().map(str::trim).collect::<Vec<_>>().join(", "));
+                devices.push(Device { name, desc });
+            }
+            hint = hint.add(1);
+        }
+        snd_device_name_free_hint(hints);
+    }
+    Ok(devices)
+}
+
+#[cfg(all(not(target_os = "linux"), feature = "cpal"))]
+pub fn playback() -> Result<Vec<Device>, String> {
+    use cpal::traits::{DeviceTrait, HostTrait};
+
+    let outputs = cpal::default_host().output_devices().map_err(|e| e.to_string())?;
+    Ok(outputs
+        .filter_map(|d| d.name().ok())
+        .map(|name| Device { name, desc: None })
+        .collect())
+}
+
+#[cfg(all(not(target_os = "linux"), not(feature = "cpal")))]
+pub fn playback() -> Result<Vec<Device>, String> {
+    Err("No audio backend built in to list devices with".to_string())
+}
+
+// a hint's field, which ALSA allocates for the caller to free
+#[cfg(target_os = "linux")]
+unsafe fn take_hint(hint: *mut c_void, id: &CStr) -> Option<String> {
+    unsafe {
+        let field: *mut c_char = snd_device_name_get_hint(hint, id.as_ptr());
+        if field.is_null() {
+            return None;
+        }
+        let text = CStr::from_ptr(field).to_string_lossy().into_owned();
+        libc::free(field.cast());
+        Some(text)
+    }
+}
+
+// for the devices command: one per line, the one playing marked
+pub fn listing(devices: &[Device], playing: &str) -> String {
+    if devices.is_empty() {
+        return "No playback devices found".to_string();
+    }
+    let width = devices.iter().map(|d| d.name.len()).max().unwrap_or(0);
+    devices
+        .iter()
+        .map(|d| {
+            let mark = if d.name == playing { '*' } else { ' ' };
+            format!("{mark} {:<width$}  {}", d.name, d.desc.as_deref().unwrap_or(""))
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,222 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::audio_processing::{
+    blast_time::{sample_rate, blast_time::TempoState},
+    reaper::{ReapQueue, Remains},
+};
+
+// Effects
+//
+// unlike Processes, which steer a Voice's parameters, Effects
+// work on audio: a frame at a time, on a Voice's or Group's
+// output (after routing, so one sample per output channel)
+//
+macro_rules! effects {
+    ( $( $variant:ident ),* $(,)? ) => {
+        #[non_exhaustive]
+        pub enum Effect {
+            $(
+                $variant($variant),
+            )*
+        }
+
+        impl Effect {
+            pub fn process(&mut self, frame: &mut [f32]) {
+                match self {
+                    $(
+                        Effect::$variant(inner) => inner.process(frame),
+                    )*
+                }
+            }
+
+            pub fn reset(&mut self) {
+                match self {
+                    $(
+                        Effect::$variant(inner) => inner.reset(),
+                    )*
+                }
+            }
+        }
+    };
+}
+
+effects! {
+    Delay,
+    Verb,
+}
+
+impl Effect {
+    // its lines, handed to reaper rather than freed on the audio
+    // thread; what's left allocates nothing
+    pub fn reap(&mut self, reaper: &ReapQueue) {
+        match self {
+            Effect::Delay(delay) => reaper.push(Remains::Buf(std::mem::take(&mut delay.state.buf))),
+            Effect::Verb(verb) => reaper.push(Remains::Verb(verb.take())),
+        }
+    }
+}
+
+// the longest a delay can be, which is what its buffer holds
+pub const MAX_DELAY_SECS: f32 = 4.0;
+
+// a delay buffer with room for MAX_DELAY_SECS
+pub fn delay_buffer(channels: usize) -> Vec<f32> {
+    vec![0.0; (MAX_DELAY_SECS * sample_rate::get() as f32) as usize * channels]
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DelayTime {
+    Samples(f32),
+    Beats(f32), // of the owner's TempoState, so it follows tempo changes
+}
+
+pub struct Delay {
+    pub state: DelayState,
+}
+
+pub struct DelayState {
+    pub time: DelayTime,
+    pub tempo: Rc<RefCell<TempoState>>, // the owner's
+    pub feedback: f32, // 0..1, how much of each repeat comes back
+    pub mix: f32, // 0 is dry, 1 only the repeats
+    pub channels: usize,
+    pub buf: Vec<f32>, // circular, interleaved; preallocated by the command thread
+    pub write: usize, // frame written next
+}
+
+impl Delay {
+    fn process(&mut self, frame: &mut [f32]) {
+        let state = &mut self.state;
+        let frames = state.buf.len() / state.channels;
+        if frames < 2 { return; }
+
+        let len = match state.time {
+            DelayTime::Samples(n) => n,
+            DelayTime::Beats(b) => b * state.tempo.borrow().interval,
+        };
+        let len = (len.round() as usize).clamp(1, frames - 1);
+
+        let read = (state.write + frames - len) % frames;
+        for (ch, sample) in frame.iter_mut().enumerate().take(state.channels) {
+            let delayed = state.buf[read * state.channels + ch];
+            state.buf[state.write * state.channels + ch] = *sample + delayed * state.feedback;
+            *sample = *sample * (1.0 - state.mix) + delayed * state.mix;
+        }
+        state.write = (state.write + 1) % frames;
+    }
+
+    fn reset(&mut self) {
+        self.state.buf.fill(0.0);
+        self.state.write = 0;
+    }
+}
+
+// reverb
+//
+// Freeverb: per output channel, eight lowpass-feedback combs in
+// parallel into four allpasses in series, fed the frame's mono sum;
+// each channel's delays are a little longer than the last's,
+// which is what spreads it
+//
+// the buffers are allocated by Verb::new, on the command thread
+//
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const TUNING_RATE: f32 = 44100.0; // what the tunings are in samples of
+const SPREAD: usize = 23;
+const INPUT_GAIN: f32 = 0.015;
+const WET_GAIN: f32 = 3.0;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+
+pub struct Verb {
+    banks: Vec<VerbBank>, // one per output channel
+    feedback: f32, // from the room size
+    damp: f32,
+    mix: f32, // 0 is dry, 1 only the reverb
+}
+
+struct VerbBank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+struct Comb {
+    buf: Vec<f32>,
+    idx: usize,
+    store: f32, // the lowpass
+}
+
+struct Allpass {
+    buf: Vec<f32>,
+    idx: usize,
+}
+
+impl Verb {
+    // room and damping are 0..1
+    pub fn new(channels: usize, room: f32, damping: f32, mix: f32) -> Self {
+        let scale = sample_rate::get() as f32 / TUNING_RATE;
+        let len = |tuning: usize, ch: usize| (((tuning + ch * SPREAD) as f32 * scale) as usize).max(1);
+
+        let banks = (0..channels)
+            .map(|ch| VerbBank {
+                combs: COMB_TUNING
+                    .iter()
+                    .map(|t| Comb { buf: vec![0.0; len(*t, ch)], idx: 0, store: 0.0 })
+                    .collect(),
+                allpasses: ALLPASS_TUNING
+                    .iter()
+                    .map(|t| Allpass { buf: vec![0.0; len(*t, ch)], idx: 0 })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            banks,
+            feedback: room * 0.28 + 0.7,
+            damp: damping * 0.4,
+            mix,
+        }
+    }
+
+    // its lines, leaving a Verb with none (which allocates nothing)
+    pub fn take(&mut self) -> Verb {
+        std::mem::replace(self, Verb { banks: Vec::new(), feedback: 0.0, damp: 0.0, mix: 0.0 })
+    }
+
+    fn process(&mut self, frame: &mut [f32]) {
+        let input = frame.iter().sum::<f32>() * INPUT_GAIN;
+
+        for (sample, bank) in frame.iter_mut().zip(&mut self.banks) {
+            let mut out = 0.0;
+            for comb in &mut bank.combs {
+                let delayed = comb.buf[comb.idx];
+                comb.store = delayed * (1.0 - self.damp) + comb.store * self.damp;
+                comb.buf[comb.idx] = input + comb.store * self.feedback;
+                comb.idx = (comb.idx + 1) % comb.buf.len();
+                out += delayed;
+            }
+
+            for allpass in &mut bank.allpasses {
+                let delayed = allpass.buf[allpass.idx];
+                allpass.buf[allpass.idx] = out + delayed * ALLPASS_FEEDBACK;
+                allpass.idx = (allpass.idx + 1) % allpass.buf.len();
+                out = delayed - out;
+            }
+
+            *sample = *sample * (1.0 - self.mix) + out * WET_GAIN * self.mix;
+        }
+    }
+
+    fn reset(&mut self) {
+        for bank in &mut self.banks {
+            for comb in &mut bank.combs {
+                comb.buf.fill(0.0);
+                comb.store = 0.0;
+            }
+            for allpass in &mut bank.allpasses {
+                allpass.buf.fill(0.0);
+            }
+        }
+    }
+}
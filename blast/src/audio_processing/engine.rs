@@ -1,27 +1,56 @@
 use std::{
     rc::Rc, cell::RefCell,
-    collections::{HashMap, hash_map::Entry},
+    collections::HashMap,
+    sync::Arc,
 };
 
-use alsa_sys::*;
 
-use crate::file_parsing::decode_helpers::{
-    DecodeResult, DecodeError, AudioFile,
-};
+use crate::file_parsing::decode_helpers::{AudioFile, Samples};
 use crate::audio_processing::{
     commands::*, // too many to list
     processes::*, // this will be ditto
-    blast_rand::{
-        X128P, fast_seed
-    },
+    effects::{Effect, Delay, DelayState},
+    routing::Routing,
+    notes::{KeyMap, KeyMode},
+    midi::ClockFollow,
+    events::{Event, EventQueue},
+    responses::{Response, ResponseQueue},
+    reaper::{ReapQueue, Remains},
+    meters::{Level, Meter},
+    simd,
     blast_time::{
         sample_rate,
-        blast_time::{
-            clock, TempoMode, TempoUnit, TempoState
-        }
+        blast_time::{clock, convert_interval, Position, TempoMode, TempoState},
     },
 };
 
+// Voice slots allocated up front, unless blast.conf says
+// otherwise; loading and unloading reuses them, so the audio
+// thread never grows the list
+pub const VOICE_POOL: usize = 64;
+
+// frames mixed at a time; a longer period is rendered as
+// several blocks. what each Voice does (its Processes, tempo,
+// fades) still happens frame by frame, in step with every other
+// Voice, but it renders onto a bus a block long, which is only
+// then put through its Effects, metered and mixed in
+pub const BLOCK: usize = 256;
+
+// aux buses a session can have, and so how many sends each
+// Voice keeps (a fixed array, so setting one never allocates)
+pub const MAX_BUSES: usize = 8;
+
+// room each Voice and Group is given up front (see Slots), so
+// adding to them never allocates: Processes, Effects (a delay
+// and a reverb), and TempoContexts in a session
+pub const MAX_PROCESSES: usize = 16;
+pub const MAX_EFFECTS: usize = 2;
+pub const MAX_TEMPO_CONTEXTS: usize = 64;
+
+// Tracks a session can have, bounces and imports included;
+// room for them is made up front, as for the above
+pub const MAX_TRACKS: usize = 256;
+
 // audio engine
 //
 pub struct Conductor {
@@ -30,196 +59,901 @@ pub struct Conductor {
     tempo_cons: Vec<Rc<RefCell<TempoState>>>,
     out_channels: usize,
     tracks: Vec<AudioFile>,
+    gain: f32, // master
+    mix: Vec<f32>, // a BLOCK of frames, one sample per output channel
+    direct: Vec<f32>, // as mix, for Voices sent past the master (out)
+    fade_ms: f32,
+    retiring: Vec<Voice>, // unloaded, but still fading out
+    effects: Vec<Effect>, // on the master bus
+    buses: Vec<AuxBus>, // sent to by Voices, returned to the master
+    next: Option<Box<Conductor>>, // loaded in the background, silent
+    outgoing: Option<Box<Conductor>>, // crossfading out
+    xfade: Fade, // this session's side of the crossfade
+    handover: bool, // outgoing is fading its own Voices out instead (see Restore)
+    midi_clock: Option<ClockFollow>, // drives a TempoContext
+    meter: Meter, // the master bus
+    events: Option<Arc<EventQueue>>, // once something's listening
+    responses: Option<Arc<ResponseQueue>>, // to the REPL, if there is one
+    reaper: Option<Arc<ReapQueue>>, // frees what Voices leave behind
+    last_beat: u64, // the clock's, for Event::Beat
 }
 
 impl Conductor {
     pub fn prepare(out_channels: usize, tracks: HashMap<String, AudioFile>) -> Self {
+        Self::with_tracks(out_channels, tracks.into_values().collect())
+    }
+
+    // tracks in the order of their TrackReprs' idxs
+    pub fn with_tracks(out_channels: usize, mut tracks: Vec<AudioFile>) -> Self {
+        tracks.reserve_exact(MAX_TRACKS.saturating_sub(tracks.len()));
         Self { 
-            voices: Vec::<Voice>::new(), 
-            groups: Vec::<Group>::new(),
-            tempo_cons: Vec::<Rc<RefCell<TempoState>>>::new(),
+            voices: Vec::<Voice>::with_capacity(VOICE_POOL), 
+            groups: Vec::<Group>::with_capacity(VOICE_POOL),
+            tempo_cons: Vec::<Rc<RefCell<TempoState>>>::with_capacity(MAX_TEMPO_CONTEXTS),
             out_channels, 
-            tracks: tracks.into_values().collect(),
+            tracks,
+            gain: 1.0,
+            mix: vec![0.0; BLOCK * out_channels],
+            direct: vec![0.0; BLOCK * out_channels],
+            fade_ms: 5.0,
+            retiring: Vec::<Voice>::with_capacity(16),
+            effects: Vec::<Effect>::with_capacity(MAX_EFFECTS),
+            buses: Vec::<AuxBus>::with_capacity(MAX_BUSES),
+            next: None,
+            outgoing: None,
+            xfade: Fade::new(),
+            handover: false,
+            midi_clock: None,
+            meter: Meter::default(),
+            events: None,
+            responses: None,
+            reaper: None,
+            last_beat: 0,
         }
     }
 
-    pub fn coordinate(&mut self, areas_ptr: *const snd_pcm_channel_area_t, offset: snd_pcm_uframes_t, frames: snd_pcm_uframes_t) {
-        unsafe {
-            let areas = std::slice::from_raw_parts(areas_ptr, self.out_channels);
+    // room for voices Voices at once; the CmdProcessor's
+    // EngineState is given the same number (set_pool), so
+    // the list is never outgrown (nor the Groups, which
+    // hold at least one each)
+    pub fn reserve(&mut self, voices: usize) {
+        self.voices.reserve_exact(voices.saturating_sub(self.voices.len()));
+        self.groups.reserve_exact(voices.saturating_sub(self.groups.len()));
+    }
 
-            for f in 0..frames {
-                for ch in 0..self.out_channels {
-                    let a = &areas[ch];
-                    let base = a.addr as *mut u8;
+    // from here on, beats, Seq steps and Voices running out are
+    // pushed onto queue (see events)
+    pub fn publish(&mut self, queue: Arc<EventQueue>) {
+        self.events = Some(queue);
+    }
 
-                    // ALSA channel area addressing
-                    let bit_offset = a.first as isize + (offset + f) as isize * a.step as isize;
-                    let byte_offset = bit_offset / 8;
+    // from here on, what apply() has to say about a Command
+    // is pushed onto queue (see responses)
+    pub fn respond_to(&mut self, queue: Arc<ResponseQueue>) {
+        self.responses = Some(queue);
+    }
+
+    // from here on, Voices (those it has, and any loaded) hand
+    // their buffers to queue when they're dropped, rather than
+    // freeing them (see reaper)
+    pub fn reap_with(&mut self, queue: Arc<ReapQueue>) {
+        self.hook(Some(queue));
+    }
+
+    // every Voice in the session (and the ones it's fading to
+    // or from) onto reaper, or off of any
+    fn hook(&mut self, reaper: Option<Arc<ReapQueue>>) {
+        let groups = self.groups.iter_mut().flat_map(|g| &mut g.voices);
+        for voice in self.voices.iter_mut().chain(&mut self.retiring).chain(groups) {
+            voice.reaper = reaper.clone();
+        }
+        for session in self.next.iter_mut().chain(&mut self.outgoing) {
+            session.hook(reaper.clone());
+        }
+        self.reaper = reaper;
+    }
+
+    fn respond(&self, response: Response) {
+        if let Some(responses) = &self.responses {
+            responses.push(response);
+        }
+    }
+
+    // what a Command replaced, freed off the audio thread if
+    // there's a reaper (offline, it's freed here)
+    fn reap(&self, remains: Remains) {
+        if let Some(reaper) = &self.reaper {
+            reaper.push(remains);
+        }
+    }
+
+    // try_bounce and try_import_stems keep a session within
+    // MAX_TRACKS, so Tracks go into the room made for them (see
+    // with_tracks); should they outgrow it all the same, the old
+    // list is reaped rather than freed here
+    fn add_tracks(&mut self, tracks: impl ExactSizeIterator<Item = AudioFile>) {
+        let len = self.tracks.len() + tracks.len();
+        if len > self.tracks.capacity() {
+            let mut grown = Vec::<AudioFile>::with_capacity(len.max(MAX_TRACKS));
+            grown.append(&mut self.tracks);
+            let old = std::mem::replace(&mut self.tracks, grown);
+            self.reap(Remains::Tracks(old));
+        }
+        self.tracks.extend(tracks);
+    }
+
+    // a session the audio thread is done with, whole; its Voices
+    // are taken off the reaper first, so they drop their buffers
+    // where they are, which is then the reaping thread (rather
+    // than pushing them back onto the queue it's emptying)
+    fn reap_session(&self, mut session: Box<Conductor>) {
+        session.hook(None);
+        self.reap(Remains::Session(Unshared::session(session)));
+    }
+
+    fn reap_effect(&self, mut effect: Effect) {
+        if let Some(reaper) = &self.reaper {
+            effect.reap(reaper);
+        }
+    }
+
+    // what the master bus meter publishes to
+    pub fn master_level(&self) -> Arc<Level> {
+        self.meter.level()
+    }
+
+    // samples in each frame render writes
+    pub fn out_channels(&self) -> usize {
+        self.out_channels
+    }
+
+    // renders interleaved frames into out: a backend's buffer,
+    // or a file's when there's no device (offline processing)
+    pub fn render(&mut self, out: &mut [i16]) {
+        let channels = self.out_channels.max(1);
+        for block in out.chunks_mut(BLOCK * channels) {
+            let frames = block.len() / channels;
+            self.mix_block(frames);
+            // saturates as a float -> int cast does, so this clips
+            simd::to_i16(&mut block[..frames * channels], &self.mix);
+        }
+        self.publish_meters();
+    }
 
-                    let sample_ptr = base.offset(byte_offset) as *mut i16;
-            
-                    unsafe {
-                        *sample_ptr = 0;
+    fn publish_meters(&mut self) {
+        self.meter.publish();
+        let rate = sample_rate::get().max(1) as f32;
+        for voice in self.voices.iter_mut().chain(self.groups.iter_mut().flat_map(|g| &mut g.voices)) {
+            voice.meter.publish();
+            let secs = |frames: f32| frames / (voice.rate * rate);
+            voice.meter.locate(secs(voice.state.position), secs(voice.state.end as f32));
+        }
+    }
+
+    // renders Group g alone into out, as render does: through its
+    // own Effects and gain, but not the aux buses or the master, and
+    // without moving the clock (see CmdProcessor::try_bounce)
+    pub fn render_group(&mut self, g: usize, out: &mut [i16]) {
+        let channels = self.out_channels.max(1);
+        for block in out.chunks_mut(BLOCK * channels) {
+            let frames = block.len() / channels;
+            let group = &mut self.groups[g];
+            for _ in 0..frames {
+                group.process();
+                for tc in &self.tempo_cons {
+                    let mut ts = tc.borrow_mut();
+                    if ts.active {
+                        ts.update(1.0);
                     }
+                }
+            }
 
-                    for voice in &mut self.voices {
-                        if voice.state.active {
-                            voice.process(sample_ptr, f, ch);
-                        }
+            let mix = &mut self.mix[..frames * channels];
+            mix.fill(0.0);
+            group.mix_into(mix, &mut []);
+            simd::to_i16(&mut block[..frames * channels], mix);
+        }
+    }
+
+    pub fn group_is_playing(&self, g: usize) -> bool {
+        let group = &self.groups[g];
+        group.state.active && group.voices.iter().any(Voice::is_playing)
+    }
+
+    // whether anything would still make sound
+    pub fn is_playing(&self) -> bool {
+        self.voices.iter().any(Voice::is_playing)
+            || self.retiring.iter().any(Voice::is_playing)
+            || self.groups
+                .iter()
+                .any(|g| g.state.active && g.voices.iter().any(Voice::is_playing))
+            || self.outgoing.as_ref().is_some_and(|old| old.is_playing())
+    }
+
+    // frames (up to a BLOCK) into mix
+    fn mix_block(&mut self, frames: usize) {
+        let channels = self.out_channels.max(1);
+        let len = frames * self.out_channels;
+        let start = clock::current();
+        self.mix_session(frames, start);
+
+        // a crossfade is equal power, so it doesn't dip in the
+        // middle; a session taken over (see Restore) is added as it
+        // is, fading out what it's left with on its own
+        if let Some(old) = &mut self.outgoing {
+            old.mix_session(frames, start);
+            let (new, old) = (&mut self.mix[..len], &old.mix[..len]);
+            if self.handover {
+                simd::add(new, old);
+            } else {
+                for (new, old) in new.chunks_exact_mut(channels).zip(old.chunks_exact(channels)) {
+                    self.xfade.advance();
+                    let angle = self.xfade.level * std::f32::consts::FRAC_PI_2;
+                    let (fade_in, fade_out) = (angle.sin(), angle.cos());
+                    for (new, old) in new.iter_mut().zip(old) {
+                        *new = *new * fade_in + *old * fade_out;
                     }
+                }
+            }
+        }
+        let done = match &self.outgoing {
+            Some(old) if self.handover => !old.is_playing(),
+            Some(_) => self.xfade.remaining == 0,
+            None => false,
+        };
+        if done && let Some(old) = self.outgoing.take() {
+            self.reap_session(old);
+            if !std::mem::take(&mut self.handover) {
+                self.respond(Response::Ok("Crossfade done"));
+            }
+        }
 
-                    for group in &mut self.groups {
-                        if group.state.active {
-                            group.process(sample_ptr, f, ch);
-                        }
+        self.meter.measure(&self.mix[..len]);
+        clock::advance(frames as u64);
+
+        if let Some(events) = &self.events {
+            let (beat_len, beats_per_bar) = clock::meter();
+            for now in start + 1..=start + frames as u64 {
+                let beat = (now as f64 / beat_len as f64) as u64;
+                if beat != self.last_beat {
+                    self.last_beat = beat;
+                    events.push(now, Event::Beat(Position::at(beat as f64, beats_per_bar)));
+                }
+            }
+        }
+    }
+
+    // what a Voice's last frame (at now) did, if anything's
+    // listening; cleared either way
+    fn report(events: &Option<Arc<EventQueue>>, now: u64, idx: Idx, state: &mut VoiceState) {
+        let fired = state.fired.take();
+        let ended = std::mem::take(&mut state.ended);
+        let Some(events) = events else { return };
+        if let Some(step) = fired {
+            events.push(now, Event::Step { voice: idx, step });
+        }
+        if ended {
+            events.push(now, Event::End(idx));
+        }
+    }
+
+    // frames of this session alone, through its master gain,
+    // starting at the clock's sample start
+    fn mix_session(&mut self, frames: usize, start: u64) {
+        for f in 0..frames {
+            let now = start + f as u64;
+            for (v, voice) in self.voices.iter_mut().enumerate() {
+                // an idle Voice may have a trig waiting on its tempo
+                if voice.state.active || voice.state.trig_at.is_some() {
+                    voice.process();
+                    Self::report(&self.events, now, Idx::Voice(v), &mut voice.state);
+                }
+            }
+
+            for (g, group) in self.groups.iter_mut().enumerate() {
+                if group.state.active {
+                    group.process();
+                    for (v, voice) in group.voices.iter_mut().enumerate() {
+                        Self::report(&self.events, now, Idx::GroupVoice(g, v), &mut voice.state);
                     }
                 }
+            }
+
+            for tc in &self.tempo_cons {
+                let mut ts = tc.borrow_mut();
+                if ts.active {
+                    ts.update(1.0);
+                }
+            }
+            if let Some(follow) = &mut self.midi_clock {
+                follow.drive(&mut self.tempo_cons[follow.tc].borrow_mut());
+            }
 
-                clock::advance(1);
+            for voice in self.retiring.iter_mut().filter(|v| v.state.active) {
+                voice.process();
             }
         }
+
+        // mix in f32 so Voices can't overflow each other;
+        // only the master output is brought back to i16
+        let channels = self.out_channels.max(1);
+        let mix = &mut self.mix[..frames * self.out_channels];
+        let direct = &mut self.direct[..frames * self.out_channels];
+        mix.fill(0.0);
+        let buses = &mut self.buses;
+        for voice in self.voices.iter_mut().chain(&mut self.retiring) {
+            let out = if voice.direct { &mut *direct } else { &mut *mix };
+            voice.mix_into(out, buses, None);
+        }
+        for group in &mut self.groups {
+            group.mix_into(mix, buses);
+        }
+        self.retiring.retain(|v| v.state.active);
+
+        // every block, sent to or not, so tails ring out
+        for bus in buses {
+            bus.mix_into(mix, channels);
+        }
+
+        for e in &mut self.effects {
+            for frame in mix.chunks_exact_mut(channels) {
+                e.process(frame);
+            }
+        }
+
+        simd::scale(mix, self.gain);
+        simd::add(mix, direct);
+        direct.fill(0.0);
+    }
+
+    // fade length in samples
+    fn fade_len(&self) -> usize {
+        (self.fade_ms * sample_rate::get() as f32 / 1000.0) as usize
     }
 
     pub fn apply(&mut self, cmd: Command) {
         match cmd {
             Command::Load(args) => self.load(args),
+            Command::Bus(args) => {
+                let bus = AuxBus::new(args.buf, args.effects.into_inner(), args.tempo_state.into_inner());
+                self.buses.push(bus);
+            }
+            Command::Send(args) => {
+                let Some(voice) = self.voice_mut(args.idx) else { return };
+                voice.sends[args.bus] = args.amount;
+            }
+            Command::Clone(args) => self.clone_voice(args),
             Command::Start(args) => self.start(args),
+            Command::Trig(args) => {
+                let Some(voice) = self.voice_mut(args.idx) else { return };
+                voice.trig(args.on_beat);
+            }
             Command::Pause(args) => self.pause(args),
             Command::Resume(args) => self.resume(args),
             Command::Stop(args) => self.stop(args),
             Command::Unload(args) => self.unload(args),
             Command::Velocity(args) => self.velocity(args),
+            Command::Stretch(args) => self.stretch(args),
+            Command::Pitch(args) => self.pitch(args),
+            Command::Keymap(args) => {
+                let Some(voice) = self.voice_mut(args.idx) else { return };
+                voice.set_keymap(args.keymap);
+            }
+            Command::Note(args) => {
+                let Some(voice) = self.voice_mut(args.idx) else { return };
+                voice.note(args.note);
+            }
+            Command::Gain(args) => self.gain(args),
+            Command::Route(args) => self.route(args),
+            Command::Out(args) => {
+                let Some(voice) = self.voice_mut(args.idx) else { return };
+                let old = std::mem::replace(&mut voice.routing, args.routing);
+                voice.direct = args.direct;
+                self.reap(Remains::Routing(old));
+            }
+            Command::Loop(args) => self.set_loop(args),
+            Command::Poly(args) => self.set_poly(args),
+            Command::Mute(args) => self.mute(args),
+            Command::Solo(args) => self.solo(args),
+            Command::Fade(args) => self.fade_ms = args.ms,
             Command::Group(args) => self.group(args),
+            Command::ImportStems(mut args) => {
+                self.add_tracks(args.tracks.drain(..));
+                for cmd in args.commands.drain(..) {
+                    self.apply(cmd);
+                }
+                // the lists they came in, emptied
+                self.reap(Remains::Tracks(args.tracks));
+                self.reap(Remains::Commands(args.commands));
+            }
+            Command::Bounce(args) => self.add_tracks(args.track.into_iter()),
             Command::Tc(args) => self.tempo_context(args),
+            Command::Tempo(args) => self.tempo(args),
+            Command::Meter(args) => self.meter(args),
             Command::Seq(args) => self.seq(args),
-            Command::Quit(_) => {
-                unsafe {
-                    libc::raise(libc::SIGTERM);
+            Command::Follow(args) => self.follow(args),
+            Command::Lfo(args) => self.lfo(args),
+            Command::Proc(args) => self.proc(args),
+            Command::Delay(args) => self.delay(args),
+            Command::Verb(args) => self.verb(args),
+            Command::Pan(args) => self.pan(args),
+            Command::Offset(args) => self.offset(args),
+            Command::Trim(args) => self.trim(args),
+            Command::Seek(args) => self.seek(args),
+            Command::Rename(_) => (), // names aren't kept in here
+            Command::Session(args) => self.session(args),
+            Command::Audio(_) => (), // there's no device in here
+            Command::Midi(args) => self.midi(args),
+            Command::Quit(_) => (), // whoever plays the Conductor stops
+        }
+    }
+
+    fn session(&mut self, args: SessionArgs) {
+        match args.action {
+            SessionAction::Load(session) => {
+                self.load_next(session);
+                self.respond(Response::Ok("Session loaded in the background"));
+            }
+            SessionAction::Crossfade(len) => {
+                let Some(mut old) = self.next.take() else {
+                    self.respond(Response::Warn("No session loaded to crossfade to"));
+                    return;
+                };
+                // self becomes the incoming session, so Commands
+                // sent from here on reach it; a crossfade that
+                // was still running is cut short
+                std::mem::swap(self, &mut old);
+                self.events = old.events.take();
+                self.last_beat = old.last_beat;
+                if let Some(cut) = old.outgoing.take() {
+                    self.reap_session(cut);
+                }
+                self.xfade.level = 0.0;
+                self.xfade.ramp(1.0, len);
+                self.outgoing = Some(old);
+            }
+            SessionAction::Drop => {
+                if let Some(next) = self.next.take() {
+                    self.reap_session(next);
                 }
             }
+            SessionAction::Replace(session) => {
+                // whatever was loaded in the background stays there
+                // (and open says what it opened; see CmdProcessor::commit)
+                let pending = self.next.take();
+                self.load_next(session);
+                let fade_len = self.fade_len();
+                self.session(SessionArgs { action: SessionAction::Crossfade(fade_len) });
+                self.next = pending;
+            }
+            SessionAction::Restore(session, carry) => {
+                let mut next = session.into_inner();
+                next.responses = self.responses.clone();
+                next.hook(self.reaper.clone());
+                for &(from, to) in &carry {
+                    next.take_over(self, from, to);
+                }
+
+                // self becomes the rebuilt session; this one keeps
+                // playing what wasn't taken over until it's faded
+                // out (a crossfade still running is cut short), and
+                // whatever was loaded in the background stays there
+                std::mem::swap(self, &mut next);
+                let mut old = next;
+                self.events = old.events.take();
+                self.last_beat = old.last_beat;
+                self.midi_clock = old.midi_clock.take();
+                self.next = old.next.take();
+                if let Some(cut) = old.outgoing.take() {
+                    self.reap_session(cut);
+                }
+                old.retire();
+                self.handover = true;
+                self.outgoing = Some(old);
+                self.respond(Response::Ok("Session rebuilt, taking over where it was"));
+            }
+            SessionAction::Undo(mut commands) => {
+                for cmd in commands.drain(..) {
+                    self.apply(cmd);
+                }
+                self.reap(Remains::Commands(commands));
+            }
         }
     }
 
-    fn load(&mut self, args: LoadArgs) {
-        let track = self.tracks.get(args.track_idx).unwrap();
-        let tempo_state = self.tempo_from_repr(args.tempo_repr);
-        self.voices.push(Voice::new(track, tempo_state));
+    // the session a crossfade goes over to, replacing any loaded before
+    fn load_next(&mut self, session: Unshared<Box<Conductor>>) {
+        let mut next = session.into_inner();
+        next.responses = self.responses.clone();
+        next.hook(self.reaper.clone());
+        if let Some(old) = self.next.replace(next) {
+            self.reap_session(old);
+        }
     }
 
-    
-    fn start(&mut self, args: StartArgs) {
-        match args.idx {
-            Idx::Voice(idx) => {
-                let voice: &mut Voice = self.voices.get_mut(idx).unwrap();
-                voice.start();
+    // picks up where from is in old, for to, rebuilt in its place
+    // (see SessionAction::Restore); from is left silent
+    fn take_over(&mut self, old: &mut Conductor, from: Idx, to: Idx) {
+        match (from, to) {
+            (Idx::Tempo(o), Idx::Tempo(n)) => {
+                if let (Some(o), Some(n)) = (old.tempo_cons.get(o), self.tempo_cons.get(n)) {
+                    n.borrow_mut().carry_on(&o.borrow());
+                }
             }
-            Idx::Group(idx) => {
-                let group: &mut Group = self.groups.get_mut(idx).unwrap();
-                group.start();
+            (Idx::Group(o), Idx::Group(n)) => {
+                if let (Some(o), Some(n)) = (old.groups.get_mut(o), self.groups.get_mut(n)) {
+                    n.take_over(o);
+                }
             }
-            Idx::Tempo(idx) => {
-                let mut tc = self.tempo_cons.get(idx).unwrap().borrow_mut();
-                tc.start();
+            (from, to) => {
+                if let (Some(o), Some(n)) = (old.voice_mut(from), self.voice_mut(to)) {
+                    n.take_over(o);
+                }
             }
-            _ => (),
         }
     }
 
+    // fades out whatever's still playing: Voices as unload
+    // does, Groups as stop does
+    fn retire(&mut self) {
+        let fade_len = self.fade_len();
+        for voice in &mut self.voices {
+            voice.state.trig_at = None;
+            voice.fade_out(fade_len, AfterFade::Unload);
+        }
+        for group in &mut self.groups {
+            group.fade_out(fade_len, AfterFade::Stop);
+        }
+    }
+
+    // the Voice goes into a slot reserved for it (see reserve);
+    // one more than that is the CmdProcessor out of step
+    fn load(&mut self, mut args: LoadArgs) {
+        let Some(tempo_state) = self.tempo_from_repr(&args.tempo_repr, args.tempo_state.take()) else { return };
+        let Some(track) = self.tracks.get(args.track_idx) else {
+            self.respond(Response::Err("No such Track in the engine"));
+            return;
+        };
+        if self.voices.len() == self.voices.capacity() {
+            self.respond(Response::Err("No Voice slot left in the engine"));
+            return;
+        }
+        let mut voice = Voice::new(track, tempo_state, self.out_channels, args);
+        voice.reaper = self.reaper.clone();
+        self.voices.push(voice);
+        self.update_audible(0);
+    }
+
+    fn clone_voice(&mut self, args: CloneArgs) {
+        let Some(tempo_state) = self.tempo_from_repr(&args.tempo_repr, args.tempo_state) else { return };
+        if self.voices.len() == self.voices.capacity() {
+            self.respond(Response::Err("No Voice slot left in the engine"));
+            return;
+        }
+        let Some(src) = self.voice_mut(args.src) else { return };
+        let mut voice = src.duplicate(tempo_state, args.bus, args.routing, args.meter, args.slots.into_inner());
+        voice.reaper = self.reaper.clone();
+        self.voices.push(voice);
+        self.update_audible(0);
+    }
+
+    fn start(&mut self, args: StartArgs) {
+        self.transport(&args.idxs, Voice::start, Group::start, TempoState::start);
+    }
+
     fn pause(&mut self, args: PauseArgs) {
-        match args.idx {
-            Idx::Voice(idx) => {
-                let voice: &mut Voice = self.voices.get_mut(idx).unwrap();
-                voice.pause();
-            }
-            Idx::Group(idx) => {
-                let group: &mut Group = self.groups.get_mut(idx).unwrap();
-                group.pause();
+        self.transport(&args.idxs, Voice::pause, Group::pause, TempoState::pause);
+    }
+
+    fn resume(&mut self, args: ResumeArgs) {
+        self.transport(&args.idxs, Voice::resume, Group::resume, TempoState::resume);
+    }
+
+    fn stop(&mut self, args: StopArgs) {
+        self.transport(&args.idxs, Voice::stop, Group::stop, TempoState::stop);
+    }
+
+    // start, pause, resume or stop, on each of idxs
+    fn transport(&mut self, idxs: &[Idx], voice: fn(&mut Voice, usize), group: fn(&mut Group, usize), tempo: fn(&mut TempoState)) {
+        let fade_len = self.fade_len();
+        for &idx in idxs {
+            match idx {
+                Idx::Voice(_) => if let Some(v) = self.voice_mut(idx) { voice(v, fade_len) },
+                Idx::Group(g) => if let Some(g) = self.group_mut(g) { group(g, fade_len) },
+                Idx::Tempo(t) => if let Some(tc) = self.tempo_con(t) { tempo(&mut tc.borrow_mut()) },
+                _ => (),
             }
-            Idx::Tempo(idx) => {
-                let mut tc = self.tempo_cons.get(idx).unwrap().borrow_mut();
-                tc.pause();
+        }
+    }
+
+    fn unload(&mut self, args: UnloadArgs) {
+        // indices shift right away (the CmdProcessor already
+        // expects that), but the Voice keeps fading out on the side
+        // (as many as there's room for; any more are cut off)
+        if args.idx >= self.voices.len() {
+            self.respond(Response::Err("No such Voice in the engine"));
+            return;
+        }
+        let mut voice = self.voices.remove(args.idx);
+        voice.fade_out(self.fade_len(), AfterFade::Unload);
+        if voice.state.active {
+            match self.retiring.len() < self.retiring.capacity() {
+                true => self.retiring.push(voice),
+                false => self.respond(Response::Warn("Too many Voices fading out; cut this one off")),
             }
-            _ => (),
         }
+        self.update_audible(self.fade_len());
     }
 
-    fn resume(&mut self, args: ResumeArgs) {
+    fn velocity(&mut self, args: VelocityArgs) {
+        let Some(voice) = self.voice_mut(Idx::Voice(args.idx)) else { return };
+        voice.state.velocity = args.val;
+    }
+
+    fn stretch(&mut self, args: StretchArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        let pitch = voice.granular.pitch;
+        voice.granular.set(args.factor, pitch, voice.state.position);
+    }
+
+    fn pitch(&mut self, args: PitchArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        let stretch = voice.granular.stretch;
+        voice.granular.set(stretch, 2f32.powf(args.semitones / 12.0), voice.state.position);
+    }
+
+    // a Voice on its own or in a Group; one the engine doesn't
+    // have is the CmdProcessor out of step with it, and said so
+    fn voice_mut(&mut self, idx: Idx) -> Option<&mut Voice> {
+        let voice = match idx {
+            Idx::Voice(v) => self.voices.get_mut(v),
+            Idx::GroupVoice(g, v) => self.groups.get_mut(g).and_then(|g| g.voices.get_mut(v)),
+            _ => None,
+        };
+        if voice.is_none() && let Some(responses) = &self.responses {
+            responses.push(Response::Err("No such Voice in the engine"));
+        }
+        voice
+    }
+
+    // as voice_mut, for a Group
+    fn group_mut(&mut self, g: usize) -> Option<&mut Group> {
+        let group = self.groups.get_mut(g);
+        if group.is_none() && let Some(responses) = &self.responses {
+            responses.push(Response::Err("No such Group in the engine"));
+        }
+        group
+    }
+
+    // as voice_mut, for a TempoContext
+    fn tempo_con(&self, t: usize) -> Option<&Rc<RefCell<TempoState>>> {
+        let tc = self.tempo_cons.get(t);
+        if tc.is_none() {
+            self.respond(Response::Err("No such TempoContext in the engine"));
+        }
+        tc
+    }
+
+    // as voice_mut, for an aux bus
+    fn bus_mut(&mut self, b: usize) -> Option<&mut AuxBus> {
+        let bus = self.buses.get_mut(b);
+        if bus.is_none() && let Some(responses) = &self.responses {
+            responses.push(Response::Err("No such bus in the engine"));
+        }
+        bus
+    }
+
+    fn gain(&mut self, args: GainArgs) {
         match args.idx {
-            Idx::Voice(idx) => {
-                let voice: &mut Voice = self.voices.get_mut(idx).unwrap();
-                voice.resume();
-            }
-            Idx::Group(idx) => {
-                let group: &mut Group = self.groups.get_mut(idx).unwrap();
-                group.resume();
-            }
-            Idx::Tempo(idx) => {
-                let mut tc = self.tempo_cons.get(idx).unwrap().borrow_mut();
-                tc.resume();
-            }
-            _ => (),
+            Some(Idx::Group(g)) => if let Some(group) = self.group_mut(g) { group.state.gain = args.val },
+            Some(Idx::Bus(b)) => if let Some(bus) = self.bus_mut(b) { bus.gain = args.val },
+            Some(idx) => if let Some(voice) = self.voice_mut(idx) { voice.state.gain = args.val },
+            None => self.gain = args.val,
         }
     }
 
-    fn stop(&mut self, args: StopArgs) {
+    fn offset(&mut self, args: OffsetArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        voice.state.offset = args.samples;
+    }
+
+    fn trim(&mut self, args: TrimArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        voice.trim(args.region);
+    }
+
+    fn seek(&mut self, args: SeekArgs) {
+        let fade_len = self.fade_len();
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        voice.seek_to(args.position, fade_len);
+    }
+
+    fn pan(&mut self, args: PanArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        voice.state.pan = args.val;
+    }
+
+    fn mute(&mut self, args: MuteArgs) {
         match args.idx {
-            Idx::Voice(idx) => {
-                let voice: &mut Voice = self.voices.get_mut(idx).unwrap();
-                voice.stop();
-            }
-            Idx::Group(idx) => {
-                let group: &mut Group = self.groups.get_mut(idx).unwrap();
-                group.stop();
-            }
-            Idx::Tempo(idx) => {
-                let mut tc = self.tempo_cons.get(idx).unwrap().borrow_mut();
-                tc.stop();
+            Idx::Group(g) => if let Some(group) = self.group_mut(g) { group.state.muted = args.on },
+            idx => if let Some(voice) = self.voice_mut(idx) { voice.state.muted = args.on },
+        }
+        self.update_audible(self.fade_len());
+    }
+
+    fn solo(&mut self, args: SoloArgs) {
+        match args.idx {
+            Idx::Group(g) => if let Some(group) = self.group_mut(g) { group.state.soloed = args.on },
+            idx => if let Some(voice) = self.voice_mut(idx) { voice.state.soloed = args.on },
+        }
+        self.update_audible(self.fade_len());
+    }
+
+    // mute and solo
+    //
+    // while anything is soloed, only soloed Voices and Groups
+    // (and Groups holding a soloed Voice) are heard; muting
+    // always wins. Either way the change ramps over fade_len
+    //
+    fn update_audible(&mut self, fade_len: usize) {
+        let any_solo = self.voices.iter().any(|v| v.state.soloed)
+            || self.groups.iter().any(|g| {
+                g.state.soloed || g.voices.iter().any(|v| v.state.soloed)
+            });
+
+        for voice in &mut self.voices {
+            let state = &voice.state;
+            let on = !state.muted && (!any_solo || state.soloed);
+            voice.set_audible(on, fade_len);
+        }
+
+        for group in &mut self.groups {
+            let state = &group.state;
+            let holds_solo = group.voices.iter().any(|v| v.state.soloed);
+            let on = !state.muted && (!any_solo || state.soloed || holds_solo);
+            let len = if group.state.active { fade_len } else { 0 };
+            group.audible.ramp(if on { 1.0 } else { 0.0 }, len);
+
+            // inside a Group, a soloed Group lets all of its Voices through
+            let soloed = group.state.soloed;
+            for voice in &mut group.voices {
+                let state = &voice.state;
+                let on = !state.muted && (!any_solo || state.soloed || soloed);
+                voice.set_audible(on, fade_len);
             }
-            _ => (),
         }
     }
 
-    fn unload(&mut self, args: UnloadArgs) {
-        self.voices.remove(args.idx);
+    // only Voices are routed; the routing replaced is reaped
+    fn route(&mut self, args: RouteArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        let old = std::mem::replace(&mut voice.routing, args.routing);
+        voice.direct = false;
+        self.reap(Remains::Routing(old));
     }
 
-    fn velocity(&mut self, args: VelocityArgs) {
-        let voice: &mut Voice = self.voices.get_mut(args.idx).unwrap();
-        voice.state.velocity = args.val;
+    fn set_loop(&mut self, args: LoopArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        voice.state.looping = args.looping;
+        voice.state.rewind_loops();
     }
 
-    fn group(&mut self, args: GroupArgs) {
-       let tempo = self.tempo_from_repr(args.tempo);
-       let mut voices: Vec<Voice> = Vec::new();
-       for (idx, update_tempo, p_ids) in args.vs_fs_ps {
+    fn set_poly(&mut self, args: PolyArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        let old = voice.set_poly(args.max, args.steal, args.pool);
+        if old.capacity() > 0 {
+            self.reap(Remains::Tails(old));
+        }
+    }
+
+    fn group(&mut self, mut args: GroupArgs) {
+       let Some(tempo) = self.tempo_from_repr(&args.tempo, args.tempo_state.take()) else { return };
+       // made with room for every one of them
+       let mut voices = args.voices.into_inner();
+       for (idx, update_tempo, p_ids) in &args.vs_fs_ps {
            // move Voices out of conductor.voices into group.voices
-           let mut voice = self.voices.remove(idx);
-           if update_tempo {
+           if *idx >= self.voices.len() {
+               self.respond(Response::Err("No such Voice in the engine"));
+               continue;
+           }
+           let mut voice = self.voices.remove(*idx);
+           if *update_tempo {
                // refer to Group TempoState
                voice.state.tempo = Rc::clone(&tempo);
-               for p in p_ids {
+               for &p in p_ids {
                    // these Processes also refer to the 
                    // Group TempoState
-                   let mut process = &mut voice.processes[p];
-                   process.update_tempo(Rc::clone(&tempo));
+                   if let Some(process) = voice.processes.get_mut(p) {
+                       process.update_tempo(Rc::clone(&tempo));
+                   }
                }
            }
            voices.push(voice);
        }
 
-       let group = Group::new(voices, tempo);
+       // there's room for a Group per Voice slot (see reserve)
+       let group = Group::new(voices, tempo, self.out_channels, args.bus, args.levels, args.slots.into_inner());
        self.groups.push(group);
+       self.update_audible(self.fade_len());
     }
 
+    // try_tc keeps them within MAX_TEMPO_CONTEXTS
     fn tempo_context(&mut self, args: TcArgs) {
-        let tempo_state = self.tempo_from_repr(args.tempo);
+        let Some(tempo_state) = self.tempo_from_repr(&args.tempo, args.tempo_state) else { return };
         self.tempo_cons.push(tempo_state);
     }
 
+    fn tempo(&mut self, args: TempoArgs) {
+        let tempo = match args.idx {
+            Idx::Tempo(t) => match self.tempo_con(t) {
+                Some(tc) => Rc::clone(tc),
+                None => return,
+            },
+            Idx::Group(g) => match self.group_mut(g) {
+                Some(group) => Rc::clone(&group.state.tempo),
+                None => return,
+            },
+            idx => match self.voice_mut(idx) {
+                Some(voice) => Rc::clone(&voice.state.tempo),
+                None => return,
+            },
+        };
+        let mut ts = tempo.borrow_mut();
+        match args.action {
+            TempoAction::Ramp { to, len } => {
+                let len = match len {
+                    // the average beat over a linear glide
+                    RampLen::Beats(n) => n * (ts.interval + to) / 2.0,
+                    RampLen::Samples(n) => n,
+                };
+                ts.ramp_to(to, len);
+            }
+            TempoAction::Set { unit, interval } => {
+                let to = convert_interval(&unit, interval);
+                ts.unit = unit;
+                if ts.mode == TempoMode::TBD {
+                    ts.mode = TempoMode::Voice;
+                }
+                match ts.interval > 0.0 {
+                    true => ts.ramp_to(to, 0.0),
+                    false => ts.interval = to,
+                }
+            }
+        }
+    }
+
+    fn meter(&mut self, args: MeterArgs) {
+        let tempo = match args.idx {
+            Some(Idx::Tempo(t)) => match self.tempo_con(t) {
+                Some(tc) => tc,
+                None => return,
+            },
+            Some(Idx::Group(g)) => match self.group_mut(g) {
+                Some(group) => &group.state.tempo,
+                None => return,
+            },
+            _ => {
+                let beat_len = args.beat_len.unwrap_or(clock::meter().0);
+                clock::set_meter(beat_len, args.beats_per_bar);
+                return;
+            }
+        };
+        tempo.borrow_mut().beats_per_bar = args.beats_per_bar;
+    }
+
+    fn midi(&mut self, args: MidiArgs) {
+        self.midi_clock = match args.action {
+            MidiAction::Clock { tc, sync, .. } => Some(ClockFollow::new(tc, sync)),
+            MidiAction::ClockOff => None,
+        };
+    }
+
     // Processes
     //
+    // a Voice's Processes (and its own tempos for them) were
+    // made with room for as many as add_process allows
     fn seq(&mut self, args: SeqArgs) {
-        let tempo = self.tempo_from_repr(TempoRepr::clone(&args.tempo));
+        let Some(tempo) = self.tempo_from_repr(&args.tempo, args.tempo_state) else { return };
         let state = SeqState {
             active: true,
             tempo: Rc::clone(&tempo),
@@ -227,109 +961,627 @@ impl Conductor {
             steps: args.steps,
             chance: args.chance,
             jit: args.jit,
+            levels: args.levels,
+            swing: args.swing,
             rng: args.rng,
+            seed: args.seed,
             idx: 0,
+            phase: args.phase,
+            primed: false,
+            last: 0.0,
         };
         
         match args.idx {
-            Idx::Voice(v) => {
-                let voice: &mut Voice = self.voices.get_mut(v).unwrap();
+            Idx::Group(g) => {
+                let Some(group) = self.group_mut(g) else { return };
+                group.processes.push(Process::Seq(Seq { state }));
+            }
+            idx => {
+                let Some(voice) = self.voice_mut(idx) else { return };
                 voice.processes.push(Process::Seq(Seq { state }));
                 if args.tempo.mode == TempoMode::Process {
                     voice.proc_tempi.push(tempo);
                 }
             }
-            Idx::Group(g) => {
-                let group: &mut Group = self.groups.get_mut(g).unwrap();
-                group.processes.push(Process::Seq(Seq { state }));
+        }
+    }
+
+    // one delay per Voice or Group; a new one replaces it, and
+    // the line it had is reaped
+    fn delay(&mut self, args: DelayArgs) {
+        let channels = self.out_channels;
+        let (effects, tempo) = match args.idx {
+            Idx::Group(g) => match self.group_mut(g) {
+                Some(group) => (&mut group.effects, Rc::clone(&group.state.tempo)),
+                None => return,
+            },
+            // a bus has no tempo, so its delays are timed in
+            // samples (see try_delay), and its own is never read
+            Idx::Bus(b) => match self.bus_mut(b) {
+                Some(bus) => (&mut bus.effects, Rc::clone(&bus.tempo)),
+                None => return,
+            },
+            idx => match self.voice_mut(idx) {
+                Some(voice) => (&mut voice.effects, Rc::clone(&voice.state.tempo)),
+                None => return,
+            },
+        };
+
+        let delay = args.time.map(|time| {
+            let state = DelayState {
+                time,
+                tempo,
+                feedback: args.feedback,
+                mix: args.mix,
+                channels,
+                buf: args.buf,
+                write: 0,
+            };
+            Effect::Delay(Delay { state })
+        });
+        if let Some(old) = swap_effect(effects, |e| matches!(e, Effect::Delay(_)), delay) {
+            self.reap_effect(old);
+        }
+    }
+
+    // one reverb per Voice, Group, bus or the master; a new one
+    // replaces it, as a delay does
+    fn verb(&mut self, args: VerbArgs) {
+        let effects = match args.idx {
+            None => &mut self.effects,
+            Some(Idx::Group(g)) => match self.group_mut(g) {
+                Some(group) => &mut group.effects,
+                None => return,
+            },
+            Some(Idx::Bus(b)) => match self.bus_mut(b) {
+                Some(bus) => &mut bus.effects,
+                None => return,
+            },
+            Some(idx) => match self.voice_mut(idx) {
+                Some(voice) => &mut voice.effects,
+                None => return,
+            },
+        };
+
+        if let Some(old) = swap_effect(effects, |e| matches!(e, Effect::Verb(_)), args.verb.map(Effect::Verb)) {
+            self.reap_effect(old);
+        }
+    }
+
+    fn lfo(&mut self, args: LfoArgs) {
+        let owned = args.tempo.as_ref().is_some_and(|t| t.mode == TempoMode::Process);
+        let tempo = match &args.tempo {
+            Some(tr) => match self.tempo_from_repr(tr, args.tempo_state) {
+                Some(tempo) => Some(tempo),
+                None => return,
+            },
+            None => None,
+        };
+
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+
+        // no tempo of its own means its Voice's
+        let tempo = tempo.unwrap_or_else(|| Rc::clone(&voice.state.tempo));
+        let state = LfoState {
+            tempo: Rc::clone(&tempo),
+            wave: args.wave,
+            target: args.target,
+            depth: args.depth,
+            rng: args.rng,
+            held: 0.0,
+            cycle: u32::MAX,
+        };
+        voice.processes.push(Process::Lfo(Lfo { state }));
+        if owned {
+            voice.proc_tempi.push(tempo);
+        }
+    }
+
+    fn follow(&mut self, args: FollowArgs) {
+        let Some(tc) = self.tempo_con(args.tempo) else { return };
+        let follow = Follow::new(Rc::clone(tc), args.min_bpm, args.max_bpm);
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        voice.processes.push(Process::Follow(follow));
+    }
+
+    fn proc(&mut self, args: ProcArgs) {
+        let Some(voice) = self.voice_mut(args.idx) else { return };
+        if args.proc >= voice.processes.len() {
+            self.respond(Response::Err("No such Process in the engine"));
+            return;
+        }
+
+        let param = match args.action {
+            ProcAction::Remove => {
+                let mut process = voice.processes.remove(args.proc);
+                if let Some(reaper) = &self.reaper {
+                    process.reap(reaper);
+                }
+                return;
             }
-            _ => (), // will only be Voice or Group
+            ProcAction::Set(param) => param,
+        };
+
+        // a pattern comes whole, its lengths already matched
+        // (see try_proc), and the one it replaces is reaped
+        let mut spent: [Vec<f32>; 4] = Default::default();
+        let process = &mut voice.processes[args.proc];
+        match (&mut *process, param) {
+            (Process::Seq(seq), param) => {
+                let state = &mut seq.state;
+                match param {
+                    ProcParam::Period(period) => state.period = period,
+                    ProcParam::Steps { steps, chance, jit, levels } => spent = [
+                        std::mem::replace(&mut state.steps, steps),
+                        std::mem::replace(&mut state.chance, chance),
+                        std::mem::replace(&mut state.jit, jit),
+                        std::mem::replace(&mut state.levels, levels),
+                    ],
+                    ProcParam::Levels(levels) => spent[0] = std::mem::replace(&mut state.levels, levels),
+                    ProcParam::Swing(swing) => state.swing = swing,
+                    ProcParam::Seed(seed) => state.seed = Some(seed),
+                    _ => return,
+                }
+                // picks the pattern up from where the tempo is
+                process.reset();
+            }
+            (Process::Lfo(lfo), ProcParam::Depth(depth)) => lfo.state.depth = depth,
+            (Process::Lfo(lfo), ProcParam::Wave(wave)) => lfo.state.wave = wave,
+            _ => (),
+        }
+        for buf in spent.into_iter().filter(|buf| buf.capacity() > 0) {
+            self.reap(Remains::Buf(buf));
         }
     }
 
     // helpers
     //
-    fn tempo_from_repr(&self, tr: TempoRepr) -> Rc<RefCell<TempoState>> {
-        // either create (and init) a new TempoState,
-        // or find the referenced one within Groups or Contexts
-        let mut tempo = Rc::new(RefCell::new(TempoState::new(None)));
-        if tr.owned {
-            tempo.borrow_mut().init(tr.mode, tr.unit, tr.interval);
-        } else {
-            match tr.mode {
-                TempoMode::Voice => {
-                    tempo = Rc::clone(&self.voices[tr.idx].state.tempo);
-                }
-                TempoMode::Group => {
-                    tempo = Rc::clone(&self.groups[tr.idx].state.tempo);
-                }
-                TempoMode::Context => {
-                    tempo = Rc::clone(&self.tempo_cons[tr.idx]);
-                }
-                // Process will never borrow from another Process
-                TempoMode::Process | TempoMode::TBD => (),
+    // the TempoState made for tr on the command thread (see
+    // TempoRepr::made), or the one it refers to within Voices,
+    // Groups or Contexts; only a Command put together by hand
+    // comes without the one it should, so it's made here
+    fn tempo_from_repr(&self, tr: &TempoRepr, made: MadeTempo) -> Option<Rc<RefCell<TempoState>>> {
+        if let Some(made) = made {
+            return Some(made.into_inner());
+        }
+
+        let tempo = match tr.mode {
+            _ if tr.owned => {
+                let mut ts = TempoState::new(None);
+                ts.init(tr.mode, tr.unit, tr.interval);
+                ts.beats_per_bar = tr.beats_per_bar;
+                return Some(Rc::new(RefCell::new(ts)));
             }
+            TempoMode::Voice => self.voices.get(tr.idx).map(|v| &v.state.tempo),
+            TempoMode::Group => self.groups.get(tr.idx).map(|g| &g.state.tempo),
+            TempoMode::Context => self.tempo_cons.get(tr.idx),
+            // Process will never borrow from another Process
+            TempoMode::Process | TempoMode::TBD => return Some(Rc::new(RefCell::new(TempoState::new(None)))),
+        };
+        if tempo.is_none() {
+            self.respond(Response::Err("No such tempo in the engine"));
         }
+        tempo.map(Rc::clone)
+    }
+
+}
 
-        tempo
+// a tempo rebuilt in from's place picks up where it is, unless
+// it's a TempoContext's, which is carried over on its own
+fn carry_tempo(to: &Rc<RefCell<TempoState>>, from: &Rc<RefCell<TempoState>>) {
+    let (mut to, from) = (to.borrow_mut(), from.borrow());
+    if to.mode == from.mode && to.mode != TempoMode::Context {
+        to.carry_on(&from);
     }
+}
 
+// puts effect in the place of the one is picks out (after the
+// rest, if there's none), or takes that one out for None; what
+// it replaced is returned, to be reaped
+fn swap_effect(effects: &mut Vec<Effect>, is: fn(&Effect) -> bool, effect: Option<Effect>) -> Option<Effect> {
+    let at = effects.iter().position(is);
+    match (at, effect) {
+        (Some(at), Some(effect)) => Some(std::mem::replace(&mut effects[at], effect)),
+        (Some(at), None) => Some(effects.remove(at)),
+        (None, Some(effect)) => {
+            effects.push(effect);
+            None
+        }
+        (None, None) => None,
+    }
+}
+
+// what a Voice or Group is given to fill, so adding Processes,
+// Effects and their tempos never allocates (see Unshared::slots)
+pub struct Slots {
+    pub processes: Vec<Process>,
+    pub effects: Vec<Effect>,
+    pub tempi: Vec<Rc<RefCell<TempoState>>>,
 }
 
 pub struct VoiceState {
     pub active: bool,
     pub position: f32,
+    pub start: usize, // the frames it plays, start..end (see trim)
     pub end: usize,
     pub velocity: f32,
     pub gain: f32,
+    pub pan: f32, // -1..1, between the first two outputs
+    pub mods: Mods,
+    pub muted: bool,
+    pub soloed: bool,
+    pub looping: Looping,
+    pub loops_left: Option<u32>, // None loops forever
+    pub trigger: bool, // set by Processes to restart playback
+    pub trigger_level: f32, // and how hard the restart hits
+    pub fired: Option<usize>, // the Seq step that set trigger, for events
+    pub ended: bool, // played out to its end this frame, for events
+    pub hit: f32, // how hard what's playing was hit
+    pub trig_at: Option<u32>, // a trig waiting for its tempo to count this far
+    pub offset: f32, // in samples, against its tempo; negative plays early
+    pub level: f32, // peak of the last frame rendered, 0..1
     pub tempo: Rc<RefCell<TempoState>>,
 }
 
+// modulation from Processes, on top of the parameters
+// they modulate; starts over every frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mods {
+    pub gain: f32,
+    pub velocity: f32,
+    pub pan: f32,
+}
+
+impl Mods {
+    pub const NONE: Self = Self { gain: 1.0, velocity: 1.0, pan: 0.0 };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Looping {
+    Off,
+    On,
+    Times(u32), // wrap this many times, then play out
+}
+
+impl VoiceState {
+    // position is within the playable frames
+    // (reversed Voices run out at start)
+    fn in_range(&self) -> bool {
+        self.position >= self.start as f32 && (self.position as usize) < self.end
+    }
+
+    fn rewind(&mut self) {
+        self.position = match self.velocity >= 0.0 {
+            true => self.start as f32,
+            false => self.end as f32,
+        };
+    }
+
+    fn rewind_loops(&mut self) {
+        self.loops_left = match self.looping {
+            Looping::Times(n) => Some(n),
+            _ => None,
+        };
+    }
+}
+
 pub struct Voice {
-    samples: Vec<i16>,
-    sample_rate: u32,
+    samples: Samples, // the Track's
+    reader: usize, // its read-ahead cursor, if they're mapped (see Samples::reader)
+    first: usize, // where its part of them starts
+    rate: f32, // Track's sample rate / device's, i.e. frames per output frame
     channels: usize,
+    routing: Routing,
+    direct: bool, // past the master, straight to its outputs (out)
+    loop_start: f32, // embedded loop points, or the whole Track,
+    loop_end: f32, // kept within the trim
+    track_loop: (f32, f32), // the loop points untrimmed
+    last: usize, // the end untrimmed
+    fade: Fade,
+    audible: Fade, // ramps for mute/solo
+    poly: usize, // most instances sounding at once; 1 is monophonic
+    steal: Steal,
+    tails: Vec<Tail>, // earlier instances, oldest first
     pub state: VoiceState,  
     processes: Vec<Process>,
+    effects: Vec<Effect>,
+    sends: [f32; MAX_BUSES], // how much goes to each aux bus, after its Effects
+    bus: Vec<f32>, // a BLOCK of frames, for the Effects to work on
+    outputs: usize, // samples in each of its frames
+    rendered: usize, // frames on bus, since the last block was mixed
+    granular: Granular, // stretch and pitch
+    keymap: Option<KeyMap>,
     proc_tempi: Vec<Rc<RefCell<TempoState>>>, // TempoMode::Process
+    meter: Meter, // what it adds to the mix, before any Group
+    reaper: Option<Arc<ReapQueue>>, // where its buffers go when it's dropped
+}
+
+// however it's dropped (unloaded, done fading out, or with its
+// Group or session), its buffers (delay and reverb lines, Seq
+// steps, its bus, routing and tails) are freed off the audio
+// thread if there's a reaper to take them; so is its hold on the
+// Track's samples, in case it was the last one (a Track dropped
+// with its session while the Voice was still fading out)
+impl Drop for Voice {
+    fn drop(&mut self) {
+        self.samples.release(self.reader);
+        let Some(reaper) = &self.reaper else { return };
+        reaper.push(Remains::Samples(self.samples.clone()));
+        for effect in &mut self.effects {
+            effect.reap(reaper);
+        }
+        for process in &mut self.processes {
+            process.reap(reaper);
+        }
+        reaper.push(Remains::Buf(std::mem::take(&mut self.bus)));
+        reaper.push(Remains::Routing(std::mem::replace(&mut self.routing, Routing::empty(0, 0))));
+        if self.tails.capacity() > 0 {
+            reaper.push(Remains::Tails(std::mem::take(&mut self.tails)));
+        }
+    }
 }
 
 impl Voice {
-    fn new(af: &AudioFile, tempo_state: Rc<RefCell<TempoState>>) -> Self {
+    // an AutoLoop trims the Track down to where it sounds
+    // and replaces its loop points; the Track's samples are
+    // shared, not copied, so trimming only moves where it starts
+    fn new(af: &AudioFile, tempo_state: Rc<RefCell<TempoState>>, outputs: usize, args: LoadArgs) -> Self {
+        let slots = args.slots.into_inner();
+        let channels = af.num_channels as usize;
+        let autoloop = args.autoloop;
+        let (first, frames) = match autoloop {
+            Some(al) => (al.start * channels, al.end - al.start),
+            None => (0, af.samples.len() / channels),
+        };
+        let end = frames - 1;
         let voice_state = VoiceState {
             active: false,
             position: 0.0,
-            end: af.samples.len() / af.num_channels as usize - 1,
+            start: 0,
+            end,
             velocity: 1.0,
-            gain: 1.0,
+            gain: args.gain,
+            pan: 0.0,
+            mods: Mods::NONE,
+            muted: false,
+            soloed: false,
+            looping: Looping::Off,
+            loops_left: None,
+            trigger: false,
+            trigger_level: 1.0,
+            fired: None,
+            ended: false,
+            hit: 1.0,
+            trig_at: None,
+            offset: 0.0,
+            level: 0.0,
             tempo: tempo_state
         };
 
+        let loop_points = match autoloop {
+            Some(al) => Some((al.loop_start - al.start, al.loop_end - al.start)),
+            None => af.loop_points,
+        };
+        let (loop_start, loop_end) = match loop_points {
+            Some((s, e)) if s < e.min(end) => (s, e.min(end)),
+            _ => (0, end),
+        };
+
         Self {
             samples: af.samples.clone(),
-            sample_rate: af.sample_rate, 
-            channels: af.num_channels as usize, 
+            reader: af.samples.reader(),
+            first,
+            rate: af.sample_rate as f32 / sample_rate::get().max(1) as f32,
+            channels,
+            routing: args.routing,
+            direct: false,
+            loop_start: loop_start as f32,
+            loop_end: loop_end as f32,
+            track_loop: (loop_start as f32, loop_end as f32),
+            last: end,
+            fade: Fade::new(),
+            audible: Fade::new(),
+            poly: 1,
+            steal: Steal::Oldest,
+            tails: Vec::new(),
+            state: voice_state,
+            processes: slots.processes,
+            effects: slots.effects,
+            sends: [0.0; MAX_BUSES],
+            bus: args.bus,
+            outputs,
+            rendered: 0,
+            granular: Granular::new(),
+            keymap: None,
+            proc_tempi: slots.tempi,
+            meter: Meter::new(args.meter),
+            reaper: None,
+        }
+    }
+
+    // a Voice on the same samples with this one's settings, that
+    // plays on its own: stopped, unmuted, monophonic, and with no
+    // Processes or Effects; routing is copied into the one given
+    fn duplicate(&self, tempo_state: Rc<RefCell<TempoState>>, bus: Vec<f32>, mut routing: Routing, meter: Arc<Level>, slots: Slots) -> Self {
+        let state = &self.state;
+        let voice_state = VoiceState {
+            active: false,
+            position: state.start as f32,
+            start: state.start,
+            end: state.end,
+            velocity: state.velocity,
+            gain: state.gain,
+            pan: state.pan,
+            mods: Mods::NONE,
+            muted: false,
+            soloed: false,
+            looping: state.looping,
+            loops_left: None,
+            trigger: false,
+            trigger_level: 1.0,
+            fired: None,
+            ended: false,
+            hit: 1.0,
+            trig_at: None,
+            offset: state.offset,
+            level: 0.0,
+            tempo: tempo_state,
+        };
+
+        // same shape, so this reuses routing's buffers
+        routing.clone_from(&self.routing);
+        let mut granular = Granular::new();
+        granular.set(self.granular.stretch, self.granular.pitch, voice_state.position);
+
+        Self {
+            samples: self.samples.clone(),
+            reader: self.samples.reader(),
+            first: self.first,
+            rate: self.rate,
+            channels: self.channels,
+            routing,
+            direct: self.direct,
+            loop_start: self.loop_start,
+            loop_end: self.loop_end,
+            track_loop: self.track_loop,
+            last: self.last,
+            fade: Fade::new(),
+            audible: Fade::new(),
+            poly: 1,
+            steal: Steal::Oldest,
+            tails: Vec::new(),
             state: voice_state,
-            processes: Vec::<Process>::new(),
-            proc_tempi: Vec::<Rc<RefCell<TempoState>>>::new(),
+            processes: slots.processes,
+            effects: slots.effects,
+            sends: self.sends,
+            bus,
+            outputs: self.outputs,
+            rendered: 0,
+            granular,
+            keymap: self.keymap,
+            proc_tempi: slots.tempi,
+            meter: Meter::new(meter),
+            reaper: None,
+        }
+    }
+
+    // plays frames start..end alone (or the whole Track, for None):
+    // start and retrigger rewind to the region, the loop is kept
+    // within it, and playback that's under way carries on inside it
+    // (or runs out, if it's already past the end)
+    fn trim(&mut self, region: Option<(usize, usize)>) {
+        let (start, end) = match region {
+            Some((start, end)) => (start.min(self.last), end.min(self.last)),
+            None => (0, self.last),
+        };
+        let state = &mut self.state;
+        let was_in = state.in_range();
+        state.start = start;
+        state.end = end.max(start);
+        if was_in {
+            state.position = state.position.clamp(state.start as f32, state.end as f32);
         }
+
+        let (from, to) = (state.start as f32, state.end as f32);
+        let (loop_start, loop_end) = (self.track_loop.0.max(from), self.track_loop.1.min(to));
+        (self.loop_start, self.loop_end) = match loop_start < loop_end {
+            true => (loop_start, loop_end),
+            false => (from, to),
+        };
+    }
+
+    // moves the playhead to position (kept within the trim); a
+    // playing Voice ramps up from silence there, as a start does,
+    // and a paused one resumes from it (start still rewinds)
+    fn seek_to(&mut self, position: usize, fade_len: usize) {
+        let state = &mut self.state;
+        state.position = position.clamp(state.start, state.end) as f32;
+        self.granular.reset(state.position);
+        if state.active && self.fade.pending.is_none() {
+            self.fade.level = 0.0;
+            self.fade.fade_in(fade_len);
+        }
+    }
+
+    fn is_playing(&self) -> bool {
+        let state = &self.state;
+        let wraps = state.looping != Looping::Off && state.loops_left != Some(0);
+        state.active && (wraps || state.in_range() || !self.tails.is_empty())
+    }
+
+    fn set_audible(&mut self, on: bool, fade_len: usize) {
+        // an inactive Voice isn't processed, so it can't ramp
+        let fade_len = if self.state.active { fade_len } else { 0 };
+        self.audible.ramp(if on { 1.0 } else { 0.0 }, fade_len);
+    }
+
+    // picks up where old (the Voice this one was rebuilt from)
+    // is: playing or not, where, and how far along its fades,
+    // tempo and polyphony; old is left silent
+    fn take_over(&mut self, old: &mut Voice) {
+        let (to, from) = (&mut self.state, &mut old.state);
+        to.active = from.active;
+        to.position = from.position.clamp(to.start as f32, to.end as f32);
+        to.loops_left = from.loops_left;
+        to.hit = from.hit;
+        to.trig_at = from.trig_at.take();
+        from.active = false;
+        carry_tempo(&to.tempo, &from.tempo);
+        for (to, from) in self.proc_tempi.iter().zip(&old.proc_tempi) {
+            carry_tempo(to, from);
+        }
+
+        std::mem::swap(&mut self.fade, &mut old.fade);
+        match old.granular.is_on() {
+            true => std::mem::swap(&mut self.granular.grains, &mut old.granular.grains),
+            false => self.granular.reset(self.state.position),
+        }
+        if self.tails.capacity() == old.tails.capacity() {
+            std::mem::swap(&mut self.tails, &mut old.tails);
+        }
+        old.tails.clear();
+        // a Seq picks its pattern up from where the tempo is
+        for process in &mut self.processes {
+            process.reset();
+        }
+    }
+
+    // the pool is allocated by whoever sends the Command,
+    // with room for the instances being stolen as well; the
+    // one it replaces is returned, to be reaped
+    fn set_poly(&mut self, max: usize, steal: Steal, pool: Vec<Tail>) -> Vec<Tail> {
+        self.poly = max.max(1);
+        self.steal = steal;
+        std::mem::replace(&mut self.tails, pool)
     }
 
-    fn start(&mut self) {
+    fn start(&mut self, fade_len: usize) {
+        // a polyphonic Voice that's already playing
+        // just gets another instance
+        if self.poly > 1 && self.state.active && self.fade.pending.is_none() {
+            self.retrigger();
+            self.state.hit = 1.0;
+            return;
+        }
+
+        self.tails.clear();
+
         let state = &mut self.state;
         state.active = true;
+        state.hit = 1.0;
+        state.rewind_loops();
+        state.rewind();
+        self.granular.reset(state.position);
 
         for p in &mut self.processes {
             p.reset();
         }
 
+        // one borrowed that isn't running is pointed out by the
+        // CmdProcessor; nothing here prints
         let mut ts = state.tempo.borrow_mut();
         if ts.mode == TempoMode::Voice || ts.mode == TempoMode::TBD {
             ts.start();
-        } else {
-            if ts.active == false {
-                println!("\nWarn: Tempo not active for Voice");
-            }
         }
                 
         for tempo_state in &mut self.proc_tempi {
@@ -337,30 +1589,45 @@ impl Voice {
             ts.start();
         }
 
-        state.position = match state.velocity >= 0.0 {
-            true => 0.0,
-            false => state.end as f32,
-        };
+        // playback jumps, so ramp up from silence
+        self.fade.level = 0.0;
+        self.fade.fade_in(fade_len);
     }
 
-    fn pause(&mut self) {
-        self.state.active = false;
+    fn pause(&mut self, fade_len: usize) {
+        self.fade_out(fade_len, AfterFade::Pause);
     }
 
-    fn resume(&mut self) {
+    fn resume(&mut self, fade_len: usize) {
         self.state.active = true;
+        self.fade.fade_in(fade_len);
+    }
 
-        let ts = self.state.tempo.borrow();
-        if ts.mode != TempoMode::Voice {
-            if ts.active == false {
-                println!("\nWarn: Tempo not active for Voice");
-            }
+    fn stop(&mut self, fade_len: usize) {
+        self.fade_out(fade_len, AfterFade::Stop);
+    }
+
+    // an inactive Voice isn't processed, so it can't fade
+    fn fade_out(&mut self, fade_len: usize, then: AfterFade) {
+        let fade_len = if self.state.active { fade_len } else { 0 };
+        if let Some(after) = self.fade.fade_out(fade_len, then) {
+            self.finish(after);
         }
     }
 
-    fn stop(&mut self) {
+    fn finish(&mut self, after: AfterFade) {
+        match after {
+            AfterFade::Pause | AfterFade::Unload => self.state.active = false,
+            AfterFade::Stop => self.halt(),
+        }
+    }
+
+    fn halt(&mut self) {
         let state = &mut self.state;
         state.active = false;
+        state.trig_at = None;
+        state.rewind_loops();
+        state.rewind();
 
         for p in &mut self.processes {
             p.reset();
@@ -377,26 +1644,166 @@ impl Voice {
             ts.reset();
         }
 
-        state.position = match state.velocity >= 0.0 {
-            true => 0.0,
-            false => state.end as f32,
-        };
+        self.tails.clear();
+        for e in &mut self.effects {
+            e.reset();
+        }
+    }
+
+    // restarts playback; when polyphonic, whatever was playing
+    // carries on as a tail instead of being cut off
+    fn retrigger(&mut self) {
+        if self.poly > 1 && self.state.in_range() {
+            let sounding = self.tails.iter().filter(|t| !t.stolen()).count();
+            if sounding + 1 >= self.poly {
+                match self.steal {
+                    Steal::Never => return,
+                    Steal::Oldest => {
+                        if let Some(oldest) = self.tails.iter_mut().find(|t| !t.stolen()) {
+                            oldest.fade.fade_out(STEAL_FADE, AfterFade::Unload);
+                        }
+                    }
+                }
+            }
+
+            // only if several steals land within one STEAL_FADE
+            if self.tails.len() == self.tails.capacity() {
+                self.tails.remove(0);
+            }
+            self.tails.push(Tail { position: self.state.position, fade: Fade::new(), hit: self.state.hit });
+        }
+
+        self.state.rewind();
+        self.granular.reset(self.state.position);
+    }
+
+    // a one-shot from the top: a playing Voice is retriggered
+    // (so the active flag is left alone), an idle one started
+    // without a fade-in, which would blunt the attack
+    fn trig(&mut self, on_beat: bool) {
+        let ts = self.state.tempo.borrow();
+        // a tempo that isn't running has no next beat
+        if on_beat && ts.active {
+            let at = (ts.current as f32 / ts.interval).ceil() * ts.interval;
+            drop(ts);
+            self.state.trig_at = Some(at as u32);
+            return;
+        }
+        drop(ts);
+        self.fire();
+    }
+
+    fn fire(&mut self) {
+        self.state.trig_at = None;
+        match self.state.active {
+            true => self.retrigger(),
+            false => self.start(0),
+        }
+        self.state.hit = 1.0;
+    }
+
+    // taking a keymap off leaves the sample as it is
+    fn set_keymap(&mut self, keymap: Option<KeyMap>) {
+        if keymap.is_none() && let Some(old) = self.keymap {
+            match old.mode {
+                KeyMode::Varispeed => self.state.velocity = 1.0,
+                KeyMode::Pitch => {
+                    let stretch = self.granular.stretch;
+                    self.granular.set(stretch, 1.0, self.state.position);
+                }
+            }
+        }
+        self.keymap = keymap;
+    }
+
+    // repitches for a note (ignored outside the keymap) and plays from the top
+    fn note(&mut self, note: u8) {
+        let Some(keymap) = self.keymap else { return };
+        let Some(ratio) = keymap.ratio(note) else { return };
+
+        match keymap.mode {
+            KeyMode::Varispeed => self.state.velocity = ratio,
+            KeyMode::Pitch => {
+                let stretch = self.granular.stretch;
+                self.granular.set(stretch, ratio, self.state.position);
+            }
+        }
+        self.trig(false);
+    }
+
+    // renders its next frame onto its bus (see mix_into)
+    fn process(&mut self) {
+        // checked before rendering, which an idle Voice skips
+        if let Some(at) = self.state.trig_at
+            && self.state.tempo.borrow().current >= at
+        {
+            self.fire();
+        }
+
+        // rendered on its own bus, so it can be metered
+        let at = self.rendered * self.outputs;
+        let mut bus = std::mem::take(&mut self.bus);
+        self.render(&mut bus[at..at + self.outputs]);
+        self.bus = bus;
+        self.rendered += 1;
+
+        if let Some(after) = self.fade.advance() {
+            self.finish(after);
+        }
+    }
+
+    // what it rendered since the last block, through its
+    // Effects and onto out, and into the aux buses it sends to
+    // (at a Group's levels, if it's in one, so they fade and mute
+    // with it); the bus is left silent for the next
+    fn mix_into(&mut self, out: &mut [f32], buses: &mut [AuxBus], levels: Option<&[f32]>) {
+        let len = std::mem::take(&mut self.rendered) * self.outputs;
+        if len == 0 {
+            return;
+        }
+        // for a mapped Track, where to have paged in
+        self.samples.want(self.reader, self.first + self.state.position.max(0.0) as usize * self.channels);
+
+        let bus = &mut self.bus[..len];
+        for e in &mut self.effects {
+            for frame in bus.chunks_exact_mut(self.outputs) {
+                e.process(frame);
+            }
+        }
+        simd::add(out, bus);
+        for (aux, amount) in buses.iter_mut().zip(self.sends) {
+            if amount > 0.0 {
+                aux.send(bus, amount, levels);
+            }
+        }
+        self.meter.measure(bus);
+        bus.fill(0.0);
     }
 
-    fn process(&mut self, acc: *mut i16, frame: u64, mut ch: usize) {
+    fn render(&mut self, out: &mut [f32]) {
         if !self.state.active { return; }
 
         let state = &mut self.state;
 
         // processing
+        state.mods = Mods::NONE;
         for p in &mut self.processes {
             p.process(state);
         }
 
-        let mut own_tempo = state.tempo.borrow_mut();
-        if own_tempo.mode == TempoMode::Voice || own_tempo.mode == TempoMode::TBD {
-            // only update own TempoState if it belongs to this Voice
-            own_tempo.update(1.0);
+        if state.trigger {
+            state.trigger = false;
+            self.retrigger();
+            self.state.hit = self.state.trigger_level;
+        }
+        let state = &mut self.state;
+
+        {
+            let mut own_tempo = state.tempo.borrow_mut();
+            if own_tempo.mode == TempoMode::Voice || own_tempo.mode == TempoMode::TBD {
+                // only update own TempoState if it belongs to this Voice
+                own_tempo.update(1.0);
+            }
         }
 
         for tempo_state in &mut self.proc_tempi {
@@ -404,53 +1811,259 @@ impl Voice {
             ts.update(1.0);
         }
 
-        let idx = state.position as usize;
-        if idx >= state.end || idx < 0 {
-            return;
+        // a looping Voice wraps around instead of running out
+        if state.looping != Looping::Off && state.loops_left != Some(0) {
+            let len = self.loop_end - self.loop_start;
+            let wrapped = if state.velocity >= 0.0 && state.position >= self.loop_end {
+                state.position -= len;
+                true
+            } else if state.velocity < 0.0 && state.position < self.loop_start {
+                state.position += len;
+                true
+            } else {
+                false
+            };
+
+            if wrapped && let Some(n) = &mut state.loops_left {
+                *n -= 1;
+            }
         }
 
-        // if there are more output channels than the track has
-        // recorded into, then skip putting info into the extra
-        // channels, unless the track is mono and there are two 
-        // output channels, in which case, output the same samples 
-        // through both channels
-        //
-        // this is a hack; def need a better routing system later
-        if self.channels == 1 {
-            if ch < 2 {
-                ch = 0;
-            } else {
-                return;
+        // a Track recorded at a different rate than the device's
+        // steps through its frames faster or slower, so that it
+        // keeps its pitch; velocity scales on top of that
+        let mods = state.mods;
+        let step = state.velocity * mods.velocity * self.rate;
+        let pan = (state.pan + mods.pan).clamp(-1.0, 1.0);
+        self.audible.advance();
+        let level = state.gain * mods.gain * self.fade.level * self.audible.level;
+
+        // tails play out once, without looping
+        for t in 0..self.tails.len() {
+            let tail = &mut self.tails[t];
+            if tail.fade.advance().is_some() {
+                continue; // stolen, and now silent
             }
-        } else if ch >= self.channels {
+            let (position, tail_level) = (tail.position, level * tail.hit * tail.fade.level);
+            tail.position += step;
+            self.play(position, step, tail_level, pan, out);
+        }
+        let (start, end) = (self.state.start as f32, self.state.end);
+        self.tails.retain(|t| {
+            t.fade.level > 0.0 && t.position >= start && (t.position as usize) < end
+        });
+
+        if !self.state.in_range() {
+            self.state.level = 0.0;
             return;
         }
+        let position = self.state.position;
 
-        // linear interpolation
-        let mut sample = 0f32;
-        let s0 = self.samples[(idx * self.channels) + (ch % self.channels)] as f32;
-        if state.velocity != 1.0 {
-            let frac = state.position.fract();
-            let s1 = self.samples[((idx + 1) * self.channels) + (ch % self.channels)] as f32;
-            sample = s0 * (1.0 - frac) + s1 * frac;
+        // advance
+        if self.granular.is_on() {
+            self.state.position += step / self.granular.stretch;
+            self.state.level = self.play_grains(position, step * self.granular.pitch, level * self.state.hit, pan, out);
         } else {
-            sample = s0;
+            self.state.position += step;
+            self.state.level = self.play(position, step, level * self.state.hit, pan, out);
         }
 
-        unsafe {
-            *acc += (sample * state.gain) as i16;
+        // off the end (or the start, backwards) with no loop to
+        // bring it back
+        let state = &self.state;
+        if !state.in_range() && (state.looping == Looping::Off || state.loops_left == Some(0)) {
+            self.state.ended = true;
         }
+    }
 
-        // advance
-        if ch == self.channels - 1 {
-            state.position += state.velocity;
+    // plays both grains, restarting whichever has run its
+    // course near position (the timeline); returns the peak
+    fn play_grains(&mut self, position: f32, step: f32, level: f32, pan: f32, out: &mut [f32]) -> f32 {
+        let len = self.granular.len;
+        let mut grains = self.granular.grains;
+        let mut peak = 0f32;
+
+        for g in 0..grains.len() {
+            if grains[g].age >= len {
+                let other = grains[1 - g].position;
+                grains[g] = Grain { position: self.seek(position, other), age: 0 };
+            }
+
+            // Hann windows half a grain apart sum to 1
+            let grain = &mut grains[g];
+            let phase = grain.age as f32 / len as f32;
+            let window = 0.5 - 0.5 * (std::f32::consts::TAU * phase).cos();
+            let at = grain.position;
+            grain.position += step;
+            grain.age += 1;
+
+            if at >= self.state.start as f32 && (at as usize) < self.state.end {
+                peak = peak.max(self.play(at, step, level * window, pan, out) * window);
+            }
+        }
+
+        self.granular.grains = grains;
+        peak
+    }
+
+    // WSOLA: of the frames within SEEK_MS of position, the one
+    // where the Track sounds most like what's playing at other,
+    // so the new grain starts in step with the old one
+    fn seek(&self, position: f32, other: f32) -> f32 {
+        let width = self.granular.len / 4;
+        let last = self.state.end.saturating_sub(width);
+        if position < 0.0 || other < 0.0 || other as usize > last {
+            return position;
+        }
+
+        let stride = (width / 32).max(1);
+        let mono = |frame: usize| -> f32 {
+            let at = self.first + frame * self.channels;
+            let frame = &self.samples[at..at + self.channels];
+            frame.iter().map(|s| *s as f32).sum()
+        };
+
+        let target = other as usize;
+        let from = (position as usize).saturating_sub(self.granular.seek);
+        let to = (position as usize + self.granular.seek).min(last);
+        let mut best = (position, f32::MIN);
+        for candidate in from..=to {
+            let (mut ab, mut bb) = (0f32, 0f32);
+            for i in (0..width).step_by(stride) {
+                let (a, b) = (mono(target + i), mono(candidate + i));
+                ab += a * b;
+                bb += b * b;
+            }
+            let score = if bb > 0.0 { ab / bb.sqrt() } else { 0.0 };
+            if score > best.1 {
+                best = (candidate as f32 + other.fract(), score);
+            }
+        }
+        best.0
+    }
+
+    // mixes the frame at position into out,
+    // returning its peak (before any gain)
+    fn play(&self, position: f32, step: f32, level: f32, pan: f32, out: &mut [f32]) -> f32 {
+        let idx = position as usize;
+        let frac = position.fract();
+        let mut peak = 0f32;
+        for src in 0..self.channels {
+            // linear interpolation
+            let at = self.first + idx * self.channels + src;
+            let s0 = self.samples[at] as f32;
+            let sample = if step != 1.0 {
+                let s1 = self.samples[at + self.channels] as f32;
+                s0 * (1.0 - frac) + s1 * frac
+            } else {
+                s0
+            };
+
+            match pan {
+                0.0 => self.routing.mix(src, sample * level, out),
+                _ => self.routing.mix_panned(src, sample * level, pan, out),
+            }
+            peak = peak.max(sample.abs());
+        }
+        peak / 32768.0
+    }
+}
+
+// stretch and pitch
+//
+// velocity changes speed and pitch together; to change one without
+// the other, the Voice plays its Track as two overlapping grains
+// that read at the pitch's speed, while the position they start
+// from moves at the stretched one (WSOLA: each new grain starts
+// where the Track best matches what the old grain is playing)
+//
+const GRAIN_MS: f32 = 40.0;
+const SEEK_MS: f32 = 10.0;
+
+#[derive(Clone, Copy)]
+struct Grain {
+    position: f32,
+    age: usize, // frames played
+}
+
+struct Granular {
+    stretch: f32, // 2 is twice as long
+    pitch: f32, // ratio, 2 is an octave up
+    len: usize, // of a grain, in frames
+    seek: usize, // how far a grain's start can move
+    grains: [Grain; 2],
+}
+
+impl Granular {
+    fn new() -> Self {
+        let frames = |ms: f32| (ms * sample_rate::get() as f32 / 1000.0) as usize;
+        let len = frames(GRAIN_MS).max(2);
+        let mut granular = Self {
+            stretch: 1.0,
+            pitch: 1.0,
+            len,
+            seek: frames(SEEK_MS),
+            grains: [Grain { position: 0.0, age: 0 }; 2],
+        };
+        granular.reset(0.0);
+        granular
+    }
+
+    fn is_on(&self) -> bool {
+        self.stretch != 1.0 || self.pitch != 1.0
+    }
+
+    // the second grain picks up at full volume, so the
+    // attack isn't faded in; the first starts next frame
+    fn reset(&mut self, position: f32) {
+        self.grains = [
+            Grain { position, age: self.len },
+            Grain { position, age: self.len / 2 },
+        ];
+    }
+
+    fn set(&mut self, stretch: f32, pitch: f32, position: f32) {
+        let was_on = self.is_on();
+        self.stretch = stretch;
+        self.pitch = pitch;
+        if !was_on {
+            self.reset(position);
         }
     }
 }
 
+// polyphony
+//
+// a polyphonic Voice keeps earlier instances sounding (as tails)
+// when it's restarted, up to its maximum; past that, the Steal
+// policy decides between cutting the oldest and ignoring the restart
+//
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Steal {
+    Oldest,
+    Never,
+}
+
+// a stolen tail ramps out over this many frames
+const STEAL_FADE: usize = 64;
+
+pub struct Tail {
+    position: f32,
+    fade: Fade,
+    hit: f32, // as hard as it was hit, whatever hits after it
+}
+
+impl Tail {
+    fn stolen(&self) -> bool {
+        self.fade.pending.is_some() || self.fade.level <= 0.0
+    }
+}
+
 pub struct GroupState {
     pub active: bool,
     pub gain: f32,
+    pub muted: bool,
+    pub soloed: bool,
     pub tempo: Rc<RefCell<TempoState>>,
 }
 
@@ -458,24 +2071,50 @@ pub struct Group {
     pub state: GroupState, 
     pub voices: Vec<Voice>,
     pub processes: Vec<Process>,
+    effects: Vec<Effect>,
+    bus: Vec<f32>, // the Group's own mix, a BLOCK of it, before its gain
+    levels: Vec<f32>, // its gain and fades, each sample of the block
+    outputs: usize,
+    rendered: usize, // frames since the last block was mixed
+    fade: Fade,
+    audible: Fade, // ramps for mute/solo
 }
 
 impl Group {
-    fn new(voices: Vec<Voice>, tempo: Rc<RefCell<TempoState>>) -> Self {
+    fn new(voices: Vec<Voice>, tempo: Rc<RefCell<TempoState>>, outputs: usize, bus: Vec<f32>, levels: Vec<f32>, slots: Slots) -> Self {
         let state = GroupState {
             active: false,
             gain: 1.0,
+            muted: false,
+            soloed: false,
             tempo,
         };
 
         Self {
             state,
             voices,
-            processes: Vec::<Process>::new(),
+            processes: slots.processes,
+            effects: slots.effects,
+            bus,
+            levels,
+            outputs,
+            rendered: 0,
+            fade: Fade::new(),
+            audible: Fade::new(),
+        }
+    }
+
+    // as Voice::take_over; its Voices are taken over one by one
+    fn take_over(&mut self, old: &mut Group) {
+        self.state.active = std::mem::take(&mut old.state.active);
+        carry_tempo(&self.state.tempo, &old.state.tempo);
+        std::mem::swap(&mut self.fade, &mut old.fade);
+        for process in &mut self.processes {
+            process.reset();
         }
     }
 
-    fn start(&mut self) {
+    fn start(&mut self, fade_len: usize) {
         let state = &mut self.state;
         state.active = true;
 
@@ -484,40 +2123,56 @@ impl Group {
             if ts.mode == TempoMode::Group {
                 ts.active = true;
                 ts.reset();
-            } else {
-                if ts.active == false {
-                    println!("\nWarn: Tempo not active for Group");
-                }
             }
         }
 
+        // the Group's own fade covers its Voices
         for voice in &mut self.voices {
-            voice.start();
+            voice.start(0);
         }
+
+        self.fade.level = 0.0;
+        self.fade.fade_in(fade_len);
     }
 
-    fn pause(&mut self) {
-        self.state.active = false; // Voices still active, but won't
-                                   // be doing anything since their
-                                   // process() isn't being called
+    fn pause(&mut self, fade_len: usize) {
+        // Voices stay active, but won't be doing anything
+        // once the Group stops calling their process()
+        self.fade_out(fade_len, AfterFade::Pause);
     }
 
-    fn resume(&mut self) {
+    fn resume(&mut self, fade_len: usize) {
         self.state.active = true;
-                
-        let ts = self.state.tempo.borrow();
-        if ts.mode == TempoMode::Context {
-            if ts.active == false {
-                println!("\nWarn: Tempo not active for Group");
-            }
+        self.fade.fade_in(fade_len);
+    }
+
+    fn stop(&mut self, fade_len: usize) {
+        self.fade_out(fade_len, AfterFade::Stop);
+    }
+
+    fn fade_out(&mut self, fade_len: usize, then: AfterFade) {
+        let fade_len = if self.state.active { fade_len } else { 0 };
+        if let Some(after) = self.fade.fade_out(fade_len, then) {
+            self.finish(after);
         }
     }
 
-    fn stop(&mut self) {
+    fn finish(&mut self, after: AfterFade) {
+        match after {
+            AfterFade::Pause | AfterFade::Unload => self.state.active = false,
+            AfterFade::Stop => self.halt(),
+        }
+    }
+
+    fn halt(&mut self) {
         self.state.active = false;
 
-        for mut voice in &mut self.voices {
+        for voice in &mut self.voices {
             voice.state.active = false;
+            voice.tails.clear();
+        }
+        for e in &mut self.effects {
+            e.reset();
         }
                 
         let mut ts = self.state.tempo.borrow_mut();
@@ -527,17 +2182,181 @@ impl Group {
         }
     }
 
-    fn process(&mut self, acc: *mut i16, frame: u64, mut ch: usize) {
+    // its Voices' next frames, and the level it's mixed in at
+    fn process(&mut self) {
         if !self.state.active { return; }
 
-        // processing
         for v in &mut self.voices {
-            v.process(acc, frame, ch);
+            v.process();
         }
 
-        let mut ts = self.state.tempo.borrow_mut();
-        if ts.mode == TempoMode::Group {
-            ts.update(1.0);
+        self.audible.advance();
+        let level = self.state.gain * self.fade.level * self.audible.level;
+        let at = self.rendered * self.outputs;
+        self.levels[at..at + self.outputs].fill(level);
+        self.rendered += 1;
+
+        {
+            let mut ts = self.state.tempo.borrow_mut();
+            if ts.mode == TempoMode::Group {
+                ts.update(1.0);
+            }
+        }
+
+        if let Some(after) = self.fade.advance() {
+            self.finish(after);
+        }
+    }
+
+    // its Voices' blocks, through its Effects and onto out
+    // at each frame's level
+    fn mix_into(&mut self, out: &mut [f32], buses: &mut [AuxBus]) {
+        let frames = std::mem::take(&mut self.rendered);
+        let len = frames * self.outputs;
+        if len == 0 {
+            return;
+        }
+
+        let bus = &mut self.bus[..len];
+        let levels = &self.levels[..len];
+        for v in &mut self.voices {
+            v.mix_into(bus, buses, Some(levels));
+        }
+        for e in &mut self.effects {
+            for frame in bus.chunks_exact_mut(self.outputs) {
+                e.process(frame);
+            }
+        }
+
+        simd::add_product(out, bus, levels);
+        bus.fill(0.0);
+    }
+}
+
+// aux buses
+//
+// one set of Effects shared by any number of Voices: each sends
+// some of its output (after its own Effects and gain) onto the
+// bus, which runs through the bus's Effects once a block and is
+// returned to the master mix at the bus's gain
+//
+pub struct AuxBus {
+    pub gain: f32, // its return
+    effects: Vec<Effect>,
+    buf: Vec<f32>, // a BLOCK of output frames; allocated by the command thread
+    tempo: Rc<RefCell<TempoState>>, // what its delays hold, but never read
+}
+
+impl AuxBus {
+    fn new(buf: Vec<f32>, effects: Vec<Effect>, tempo: Rc<RefCell<TempoState>>) -> Self {
+        Self { gain: 1.0, effects, buf, tempo }
+    }
+
+    // src (a Voice's block) at amount, and at a Group's levels
+    fn send(&mut self, src: &[f32], amount: f32, levels: Option<&[f32]>) {
+        let buf = &mut self.buf[..src.len()];
+        match levels {
+            Some(levels) => {
+                for ((out, s), level) in buf.iter_mut().zip(src).zip(levels) {
+                    *out += s * level * amount;
+                }
+            }
+            None => {
+                for (out, s) in buf.iter_mut().zip(src) {
+                    *out += s * amount;
+                }
+            }
+        }
+    }
+
+    // what was sent this block, through the Effects and onto
+    // out; left silent for the next
+    fn mix_into(&mut self, out: &mut [f32], channels: usize) {
+        let buf = &mut self.buf[..out.len()];
+        for e in &mut self.effects {
+            for frame in buf.chunks_exact_mut(channels) {
+                e.process(frame);
+            }
+        }
+        simd::scale(buf, self.gain);
+        simd::add(out, buf);
+        buf.fill(0.0);
+    }
+}
+
+// fades
+//
+// Voices and Groups ramp their level in and out instead of
+// jumping (which is what clicks); whatever silences them only
+// happens once the ramp reaches 0
+//
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum AfterFade {
+    Pause,
+    Stop,
+    Unload,
+}
+
+struct Fade {
+    level: f32,
+    target: f32,
+    step: f32,
+    remaining: usize, // frames left in the ramp
+    pending: Option<AfterFade>,
+}
+
+impl Fade {
+    fn new() -> Self {
+        Self { level: 1.0, target: 1.0, step: 0.0, remaining: 0, pending: None }
+    }
+
+    // ramps up from the current level
+    fn fade_in(&mut self, len: usize) {
+        self.pending = None;
+        self.ramp(1.0, len);
+    }
+
+    // returns the action right away if there's nothing to ramp
+    fn fade_out(&mut self, len: usize, then: AfterFade) -> Option<AfterFade> {
+        if len == 0 || self.level <= 0.0 {
+            self.ramp(0.0, 0);
+            self.pending = None;
+            return Some(then);
+        }
+
+        self.ramp(0.0, len);
+        self.pending = Some(then);
+        None
+    }
+
+    fn ramp(&mut self, target: f32, len: usize) {
+        self.target = target;
+        if len == 0 {
+            self.level = target;
+            self.remaining = 0;
+        } else {
+            self.step = (target - self.level) / len as f32;
+            self.remaining = len;
+        }
+    }
+
+    // returns the pending action once the level hits 0
+    fn advance(&mut self) -> Option<AfterFade> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.level += self.step;
+            return None;
+        }
+
+        // land exactly, whatever the float error
+        self.level = self.target;
+        match self.level {
+            0.0 => self.pending.take(),
+            _ => None,
         }
     }
 }
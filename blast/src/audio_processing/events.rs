@@ -0,0 +1,152 @@
+use std::cell::UnsafeCell;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    mpsc::{self, Receiver, Sender},
+};
+use std::{thread, time::Duration};
+
+use crate::audio_processing::{
+    blast_time::blast_time::Position,
+    commands::{EngineState, Idx},
+};
+
+// events
+//
+// what the engine publishes as it plays, for whatever wants to
+// react to musical time: the clock crossing a beat, a Seq's step
+// firing, and a Voice playing out to its end, each stamped with
+// the sample it happened on
+//
+// the audio thread only pushes onto a fixed queue (one that's
+// full drops the event and counts it), and only once something
+// has asked for them; a dispatch thread hands each one to every
+// subscriber, which can be as slow as it likes
+//
+// Voices are the engine's Idx; EngineState::voice_name says which
+// one that is
+//
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    Beat(Position),
+    Step { voice: Idx, step: usize }, // which of its -s steps, from 0
+    End(Idx),
+}
+
+// an event and the sample it happened on
+pub type Stamped = (u64, Event);
+
+impl Event {
+    // e.g. "beat 3.2", "step kick 2" (its second step), "end pad"
+    pub fn describe(&self, engine_state: &EngineState) -> String {
+        let name = |idx: &Idx| engine_state.voice_name(*idx).unwrap_or_else(|| "?".to_string());
+        match self {
+            Event::Beat(at) => format!("beat {at}"),
+            Event::Step { voice, step } => format!("step {} {}", name(voice), step + 1),
+            Event::End(voice) => format!("end {}", name(voice)),
+        }
+    }
+}
+
+// events the queue holds before it drops them
+const QUEUE_LEN: usize = 1024;
+
+// from the audio thread to the dispatch thread: one producer,
+// one consumer, the same as CmdQueue the other way
+pub struct EventQueue {
+    buf: Vec<UnsafeCell<Option<Stamped>>>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+unsafe impl Send for EventQueue {}
+unsafe impl Sync for EventQueue {}
+
+impl EventQueue {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            buf: (0..cap.max(2)).map(|_| UnsafeCell::new(None)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn push(&self, at: u64, event: Event) {
+        let head = self.head.load(Ordering::Relaxed);
+        let next = (head + 1) % self.buf.len();
+        if next == self.tail.load(Ordering::Acquire) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        unsafe {
+            *self.buf[head].get() = Some((at, event));
+        }
+        self.head.store(next, Ordering::Release);
+    }
+
+    pub fn pop(&self) -> Option<Stamped> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+        let stamped = unsafe { (*self.buf[tail].get()).take() };
+        self.tail.store((tail + 1) % self.buf.len(), Ordering::Release);
+        stamped
+    }
+
+    // events lost to a full queue
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+// the control side: hands the Conductor its queue and everyone
+// else a Receiver; the dispatch thread ends with the bus
+pub struct EventBus {
+    queue: Arc<EventQueue>,
+    subscribers: Arc<Mutex<Vec<Sender<Stamped>>>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let queue = Arc::new(EventQueue::new(QUEUE_LEN));
+        let subscribers = Arc::new(Mutex::new(Vec::<Sender<Stamped>>::new()));
+        {
+            let queue = Arc::clone(&queue);
+            let subscribers = Arc::downgrade(&subscribers);
+            thread::spawn(move || {
+                while let Some(subscribers) = subscribers.upgrade() {
+                    while let Some(stamped) = queue.pop() {
+                        // ones that hung up are forgotten
+                        subscribers.lock().unwrap().retain(|tx| tx.send(stamped).is_ok());
+                    }
+                    drop(subscribers);
+                    thread::sleep(Duration::from_millis(1));
+                }
+            });
+        }
+        Self { queue, subscribers }
+    }
+
+    // what the Conductor publishes to (see Conductor::publish)
+    pub fn queue(&self) -> Arc<EventQueue> {
+        Arc::clone(&self.queue)
+    }
+
+    // every event from here on
+    pub fn subscribe(&self) -> Receiver<Stamped> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+}
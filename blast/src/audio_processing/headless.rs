@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::file_parsing::decode_helpers::AudioFile;
+use crate::audio_processing::{
+    engine::Conductor,
+    commands::{CmdErr, CmdProcessor, CmdQueue, CmdResult, Command, EngineState, FadeArgs},
+    blast_time::sample_rate,
+    events::{EventBus, Stamped},
+    meters::{Level, VoiceLevels},
+    reaper::{self, ReapQueue},
+    stats::Snapshot,
+    runtime::{self, RunOptions},
+};
+
+// headless
+//
+// the engine without the terminal, for programs that use blast
+// as a library: an Engine opens the device opts name and renders
+// on an audio thread of its own (pinned and promoted as opts
+// ask), taking lines, checked and planned as the REPL would (the
+// first being opts' script, if any), or Commands built by hand;
+// levels are read off the same meters the REPL draws, and events
+// (beats, Seq steps, Voices ending) come to whoever subscribes
+//
+//   let mut engine = Engine::start(tracks, 48000, 2, &opts)?;
+//   engine.run("load kick")?;
+//   engine.run("start -v kick")?;
+//   let peak = engine.master().peak();
+//   for (at, event) in engine.subscribe() { ... }
+//
+// the REPL (see runtime) is one frontend over the same pieces
+//
+
+pub struct Engine {
+    cmd_processor: CmdProcessor,
+    queue: Arc<CmdQueue>,
+    master: Arc<Level>,
+    stop: Arc<AtomicBool>, // set to end the audio thread
+    events: EventBus,
+    audio: Option<JoinHandle<()>>,
+}
+
+impl Engine {
+    // the Conductor and the device are made on the audio thread
+    // (neither can move between threads); an error is why the
+    // device didn't open
+    pub fn start(tracks: HashMap<String, AudioFile>, rate: u32, channels: usize, opts: &RunOptions) -> Result<Self, String> {
+        sample_rate::set(rate);
+        let mut engine_state = EngineState::new(tracks.clone().into_values().collect(), channels);
+        if let Some(voices) = opts.voices {
+            engine_state.set_pool(voices);
+        }
+        let mut cmd_processor = CmdProcessor::new(engine_state);
+        for (name, body) in &opts.aliases {
+            cmd_processor
+                .add_alias(name, body)
+                .map_err(|error| format!("alias {name}: {error}"))?;
+        }
+
+        let queue = Arc::new(CmdQueue::new(256));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (opened_tx, opened) = mpsc::channel::<Result<Arc<Level>, String>>();
+        let events = EventBus::new();
+        let audio = {
+            let queue = Arc::clone(&queue);
+            let published = events.queue();
+            let stop = Arc::clone(&stop);
+            let stats = Arc::clone(&cmd_processor.stats);
+            let (fade_ms, voices, adaptive) = (opts.fade_ms, opts.voices, opts.adaptive);
+            let opts = RunOptions {
+                realtime: opts.realtime,
+                audio_cpu: opts.audio_cpu,
+                backend: opts.backend.clone(),
+                device: opts.device.clone(),
+                latency: opts.latency,
+                ..RunOptions::default()
+            };
+            thread::spawn(move || {
+                let mut backend = match runtime::open_backend(channels, rate, &opts) {
+                    Ok(backend) => backend,
+                    Err(err) => {
+                        let _ = opened_tx.send(Err(err));
+                        return;
+                    }
+                };
+                let mut conductor = Conductor::prepare(channels, tracks);
+                if let Some(voices) = voices {
+                    conductor.reserve(voices);
+                }
+                if let Some(ms) = fade_ms {
+                    conductor.apply(Command::Fade(FadeArgs { ms }));
+                }
+                conductor.publish(published);
+                let reaped = Arc::new(ReapQueue::new(reaper::QUEUE_LEN));
+                conductor.reap_with(Arc::clone(&reaped));
+                reaper::reap(reaped);
+                let _ = opened_tx.send(Ok(conductor.master_level()));
+                runtime::promote(&opts);
+                runtime::play(&mut conductor, &mut *backend, &queue, adaptive, &stop, &stats);
+            })
+        };
+        let master = opened.recv().map_err(|_| "The audio thread didn't start".to_string())??;
+
+        let mut engine = Self { cmd_processor, queue, master, stop, events, audio: Some(audio) };
+        if let Some(path) = &opts.script {
+            engine.run(&format!("run {path}")).map_err(|error| error.to_string())?;
+        }
+        Ok(engine)
+    }
+
+    // runs line as if it had been typed, along with any lines it
+    // queues (run <file>); the first error stops it
+    pub fn run(&mut self, line: &str) -> CmdResult<()> {
+        self.submit(line.to_string())?;
+        while let Some((at, line)) = self.cmd_processor.next_scripted() {
+            self.submit(line).map_err(|error| CmdErr::Formatting { err: format!("{at}: {error}") })?;
+        }
+        Ok(())
+    }
+
+    fn submit(&mut self, line: String) -> CmdResult<()> {
+        let Some(plan) = self.cmd_processor.prepare(line)? else {
+            return Ok(());
+        };
+        refuse_quit(&plan.command)?;
+        // a long run waits for the engine to take Commands off the
+        // queue rather than overflowing it
+        while self.queue.is_full() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        self.cmd_processor
+            .commit(plan, |cmd| self.queue.try_push(cmd))
+            .map_err(|err| CmdErr::Formatting { err })
+    }
+
+    // straight to the engine: what the EngineState knows (and what
+    // lines are checked against) doesn't change
+    pub fn push(&self, cmd: Command) -> CmdResult<()> {
+        refuse_quit(&cmd)?;
+        self.queue.try_push(cmd).map_err(|err| CmdErr::Formatting { err })
+    }
+
+    // what's loaded and playing, as the REPL sees it
+    pub fn state(&self) -> &EngineState {
+        &self.cmd_processor.engine_state
+    }
+
+    // every Voice's meter, by name (a grouped one as group.voice)
+    pub fn levels(&self) -> VoiceLevels {
+        self.cmd_processor.engine_state.meters()
+    }
+
+    // the master bus
+    pub fn master(&self) -> &Level {
+        &self.master
+    }
+
+    // underruns and render time, as stats shows them
+    pub fn stats(&self) -> Snapshot {
+        self.cmd_processor.stats.snapshot()
+    }
+
+    // every event from here on, stamped with the sample it
+    // happened on; state().voice_name says which Voice one's about
+    pub fn subscribe(&self) -> Receiver<Stamped> {
+        self.events.subscribe()
+    }
+
+    // stops the audio thread and closes the device
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(audio) = self.audio.take() {
+            let _ = audio.join();
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+// quit ends the process (see Conductor::apply), where a library
+// should drop its Engine
+fn refuse_quit(cmd: &Command) -> CmdResult<()> {
+    match cmd {
+        Command::Quit(_) => Err(CmdErr::Formatting { err: "quit ends the process; stop the Engine instead".to_string() }),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,140 @@
+use crate::file_parsing::decode_helpers::{AudioFile, to_f32};
+
+// loudness
+//
+// a Track's sample peak and integrated loudness (ITU-R BS.1770):
+// each channel K-weighted (a high shelf for the head, then a high
+// pass), its mean square taken over 400ms blocks overlapping by
+// 75%, and the blocks gated, first at -70 LUFS and then at 10 LU
+// under the loudness of what's left, before they're averaged
+//
+// every channel is weighted the same (no surround weights, and an
+// LFE counts like the rest), which is exact for mono and stereo
+//
+// run once per Track when the session starts, like autoloop;
+// load --normalize brings a Voice to a target from it
+//
+
+// what load --normalize aims for, when it isn't told
+pub const TARGET_LUFS: f32 = -18.0;
+// how close to full scale a normalized peak may come
+pub const CEILING_DBFS: f32 = -1.0;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+const BLOCK_SECS: f64 = 0.4;
+const STEPS_PER_BLOCK: usize = 4; // 75% overlap
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Loudness {
+    pub peak: f32, // dBFS; -inf for silence
+    pub lufs: f32, // integrated; -inf for silence
+}
+
+impl Loudness {
+    // the gain that brings the Track to target LUFS,
+    // held back so its peak stays under CEILING_DBFS;
+    // 1.0 for a silent Track
+    pub fn gain_to(&self, target: f32) -> f32 {
+        if !self.lufs.is_finite() || !self.peak.is_finite() {
+            return 1.0;
+        }
+        let db = (target - self.lufs).min(CEILING_DBFS - self.peak);
+        10f32.powf(db / 20.0)
+    }
+}
+
+pub fn measure(af: &AudioFile) -> Loudness {
+    let channels = af.num_channels.max(1) as usize;
+    let rate = af.sample_rate.max(1) as f64;
+
+    let peak = af.samples.iter().map(|&s| to_f32(s).abs()).fold(0.0, f32::max);
+    let peak = 20.0 * peak.log10();
+
+    // the K-weighted energy of each 100ms step, summed across channels
+    let step = ((rate * BLOCK_SECS) as usize / STEPS_PER_BLOCK).max(1);
+    let frames = af.frames();
+    let mut steps = vec![0f64; frames.div_ceil(step)];
+    for ch in 0..channels {
+        let mut filter = KWeighting::new(rate);
+        for (i, x) in af.channel_f32(ch).enumerate() {
+            let y = filter.process(x as f64);
+            steps[i / step] += y * y;
+        }
+    }
+
+    // a Track shorter than a block is one block, however long
+    let blocks: Vec<f64> = match steps.len() < STEPS_PER_BLOCK {
+        true => vec![steps.iter().sum::<f64>() / frames.max(1) as f64],
+        false => steps
+            .windows(STEPS_PER_BLOCK)
+            .map(|w| w.iter().sum::<f64>() / (step * STEPS_PER_BLOCK) as f64)
+            .collect(),
+    };
+
+    let loudness = |z: f64| -0.691 + 10.0 * z.log10();
+    let mean_over = |gate: f64| {
+        let kept: Vec<f64> = blocks.iter().copied().filter(|z| loudness(*z) > gate).collect();
+        (!kept.is_empty()).then(|| kept.iter().sum::<f64>() / kept.len() as f64)
+    };
+    let lufs = mean_over(ABSOLUTE_GATE)
+        .and_then(|z| mean_over(loudness(z) + RELATIVE_GATE))
+        .map_or(f32::NEG_INFINITY, |z| loudness(z) as f32);
+
+    Loudness { peak, lufs }
+}
+
+// the two stages of BS.1770's pre-filter, designed for any rate
+// (the standard only tabulates 48 kHz)
+struct KWeighting {
+    stages: [Biquad; 2],
+}
+
+impl KWeighting {
+    fn new(rate: f64) -> Self {
+        use std::f64::consts::PI;
+
+        // a high shelf of about +4 dB above 1.5 kHz
+        let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        // and a high pass at about 38 Hz
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+
+        Self { stages: [shelf, high_pass] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.stages.iter_mut().fold(x, |x, stage| stage.process(x))
+    }
+}
+
+// direct form II transposed; a is normalized, so a0 is left out
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
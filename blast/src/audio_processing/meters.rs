@@ -0,0 +1,114 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+};
+
+// meters
+//
+// peak and RMS levels of each Voice and the master bus; the
+// audio thread measures every frame it mixes and publishes a
+// period's worth at a time into atomics, which the UI thread
+// reads without ever holding up the audio
+//
+// levels are linear, 1.0 being full scale (i16::MAX)
+//
+// a Voice's Level also carries where it is in its Track,
+// published along with the levels, for the prompt to show
+//
+
+// each Voice's Level, by name, as the UI gets them
+pub type VoiceLevels = Vec<(String, Arc<Level>)>;
+
+#[derive(Default, Debug)]
+pub struct Level {
+    peak: AtomicU32, // f32 bits
+    rms: AtomicU32,
+    playhead: AtomicU32, // seconds into the Track
+    length: AtomicU32,   // seconds
+}
+
+impl Level {
+    pub fn peak(&self) -> f32 {
+        f32::from_bits(self.peak.load(Ordering::Relaxed))
+    }
+
+    pub fn rms(&self) -> f32 {
+        f32::from_bits(self.rms.load(Ordering::Relaxed))
+    }
+
+    // (seconds in, seconds long)
+    pub fn playhead(&self) -> (f32, f32) {
+        let load = |secs: &AtomicU32| f32::from_bits(secs.load(Ordering::Relaxed));
+        (load(&self.playhead), load(&self.length))
+    }
+}
+
+// the audio thread's side: sums up frames until published
+#[derive(Default, Debug)]
+pub struct Meter {
+    level: Arc<Level>,
+    peak: f32,
+    squares: f64,
+    samples: usize,
+}
+
+impl Meter {
+    pub fn new(level: Arc<Level>) -> Self {
+        Self { level, ..Self::default() }
+    }
+
+    pub fn level(&self) -> Arc<Level> {
+        Arc::clone(&self.level)
+    }
+
+    pub fn measure(&mut self, frame: &[f32]) {
+        for sample in frame {
+            let s = sample.abs() / i16::MAX as f32;
+            self.peak = self.peak.max(s);
+            self.squares += (s * s) as f64;
+        }
+        self.samples += frame.len();
+    }
+
+    // once a period; a Meter that measured nothing reads silent
+    pub fn publish(&mut self) {
+        let rms = match self.samples {
+            0 => 0.0,
+            n => (self.squares / n as f64).sqrt() as f32,
+        };
+        self.level.peak.store(self.peak.to_bits(), Ordering::Relaxed);
+        self.level.rms.store(rms.to_bits(), Ordering::Relaxed);
+        self.peak = 0.0;
+        self.squares = 0.0;
+        self.samples = 0;
+    }
+
+    pub fn locate(&self, playhead: f32, length: f32) {
+        self.level.playhead.store(playhead.to_bits(), Ordering::Relaxed);
+        self.level.length.store(length.to_bits(), Ordering::Relaxed);
+    }
+}
+
+pub const FLOOR_DB: f32 = -60.0;
+
+pub fn to_db(level: f32) -> f32 {
+    match level > 0.0 {
+        true => (20.0 * level.log10()).max(FLOOR_DB),
+        false => FLOOR_DB,
+    }
+}
+
+// a bar width columns wide: filled to the RMS, with a tick
+// at the peak, on a dB scale from FLOOR_DB to 0
+pub fn bar(level: &Level, width: usize) -> String {
+    let cols = |l: f32| (((to_db(l) - FLOOR_DB) / -FLOOR_DB) * width as f32).round() as usize;
+    let (rms, peak) = (cols(level.rms()).min(width), cols(level.peak()).min(width));
+
+    (0..width)
+        .map(|c| match c {
+            c if peak > 0 && c == peak - 1 => '|',
+            c if c < rms => '#',
+            _ => '.',
+        })
+        .collect()
+}
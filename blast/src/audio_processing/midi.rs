@@ -0,0 +1,462 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        mpsc::Sender,
+    },
+};
+
+#[cfg(target_os = "linux")]
+use std::{
+    ffi::{CStr, CString},
+    ptr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+#[cfg(target_os = "linux")]
+use alsa_sys::*;
+
+use crate::audio_processing::{
+    blast_time::blast_time::TempoState,
+    notes::{self, KeyMap},
+    runtime::Input,
+};
+#[cfg(target_os = "linux")]
+use crate::audio_processing::blast_time::blast_time::clock;
+
+// midi
+//
+// following an external MIDI clock: a listener thread reads a raw
+// MIDI input, picks the clock (24 ticks per beat), start, continue,
+// stop and song position messages out from whatever else is on the
+// wire, and publishes them through a ClockSync
+//
+// the Conductor reads the ClockSync every frame and drives one
+// TempoContext with it: started, paused and resumed with the
+// transport, its beat the length of the last 24 ticks, and its
+// count held to the ticks so it can't drift from the sequencer
+//
+// notes from a pad controller (or any keyboard) on another input
+// play Voices instead: each note on that's mapped runs its line,
+// trig kick, start pad or stop pad, as if it had been typed, and
+// the rest can play a keymapped Voice as an instrument (note pad
+// 62), repitched from its root
+//
+
+pub const TICKS_PER_BEAT: u64 = 24;
+
+// the clock's messages, only read off a device on Linux
+#[cfg(any(target_os = "linux", test))]
+const CLOCK: u8 = 0xF8;
+#[cfg(any(target_os = "linux", test))]
+const START: u8 = 0xFA;
+#[cfg(any(target_os = "linux", test))]
+const CONTINUE: u8 = 0xFB;
+#[cfg(any(target_os = "linux", test))]
+const STOP: u8 = 0xFC;
+#[cfg(any(target_os = "linux", test))]
+const SONG_POSITION: u8 = 0xF2;
+
+#[cfg(target_os = "linux")]
+const SND_RAWMIDI_NONBLOCK: i32 = 0x0002;
+
+// shared between the listener and the audio thread;
+// the audio thread only reads
+#[derive(Default, Debug)]
+pub struct ClockSync {
+    ticks: AtomicU64, // since the last start (or song position)
+    starts: AtomicU32, // start messages so far
+    running: AtomicBool,
+    beat_len: AtomicU32, // f32 bits, in samples; 0 until there are two ticks
+    closed: AtomicBool, // tells the listener to let the device go
+    // the listener's own: when the last 24 ticks came in
+    #[cfg(any(target_os = "linux", test))]
+    stamps: Mutex<Vec<u64>>,
+}
+
+impl ClockSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks.load(Ordering::Relaxed)
+    }
+
+    pub fn starts(&self) -> u32 {
+        self.starts.load(Ordering::Relaxed)
+    }
+
+    pub fn running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    // None until the clock has ticked twice
+    pub fn beat_len(&self) -> Option<f32> {
+        match f32::from_bits(self.beat_len.load(Ordering::Relaxed)) {
+            len if len > 0.0 => Some(len),
+            _ => None,
+        }
+    }
+
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+// the listener's side, fed by a ClockReader
+#[cfg(any(target_os = "linux", test))]
+impl ClockSync {
+    #[cfg(target_os = "linux")]
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    // a tick at sample at (of the global clock); the beat is
+    // measured over as many of the last 24 ticks as there are
+    pub fn tick(&self, at: u64) {
+        let mut stamps = self.stamps.lock().unwrap();
+        if stamps.len() > TICKS_PER_BEAT as usize {
+            stamps.remove(0);
+        }
+        stamps.push(at);
+        if let (Some(first), Some(last)) = (stamps.first(), stamps.last())
+            && stamps.len() > 1 && last > first {
+            let per_tick = (last - first) as f32 / (stamps.len() - 1) as f32;
+            let beat_len = per_tick * TICKS_PER_BEAT as f32;
+            self.beat_len.store(beat_len.to_bits(), Ordering::Relaxed);
+        }
+        drop(stamps);
+
+        if self.running() {
+            self.ticks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn start(&self) {
+        self.ticks.store(0, Ordering::Relaxed);
+        self.starts.fetch_add(1, Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    // in sixteenths from the top of the song
+    pub fn song_position(&self, sixteenths: u64) {
+        self.ticks.store(sixteenths * TICKS_PER_BEAT / 4, Ordering::Relaxed);
+    }
+}
+
+// picks the clock's messages out of a raw MIDI stream
+//
+// clock, start, continue and stop are one byte and can turn up in
+// the middle of any other message; song position has two data bytes
+#[cfg(any(target_os = "linux", test))]
+#[derive(Default)]
+pub struct ClockReader {
+    position: Option<Vec<u8>>, // song position data, while it comes in
+}
+
+#[cfg(any(target_os = "linux", test))]
+impl ClockReader {
+    pub fn feed(&mut self, byte: u8, at: u64, sync: &ClockSync) {
+        match byte {
+            CLOCK => sync.tick(at),
+            START => sync.start(),
+            CONTINUE => sync.resume(),
+            STOP => sync.stop(),
+            SONG_POSITION => self.position = Some(Vec::with_capacity(2)),
+            // the other real-time bytes don't interrupt a message
+            0xF9 | 0xFD..=0xFF => (),
+            // any other status byte ends one
+            0x80..=0xF1 | 0xF3..=0xF7 => self.position = None,
+            data => {
+                if let Some(bytes) = &mut self.position {
+                    bytes.push(data);
+                    if let [lsb, msb] = bytes[..] {
+                        sync.song_position(((msb as u64) << 7) | lsb as u64);
+                        self.position = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the audio thread's side: holds a TempoContext to the ticks
+pub struct ClockFollow {
+    pub tc: usize,
+    sync: Arc<ClockSync>,
+    starts: u32,
+    ticks: u64,
+}
+
+impl ClockFollow {
+    pub fn new(tc: usize, sync: Arc<ClockSync>) -> Self {
+        // a start that came before this doesn't count
+        let starts = sync.starts();
+        Self { tc, sync, starts, ticks: 0 }
+    }
+
+    // once a frame, after the TempoState has counted it;
+    // the count is moved up to the last tick and kept short of
+    // the next, so it can't run ahead of the sequencer
+    pub fn drive(&mut self, ts: &mut TempoState) {
+        let starts = self.sync.starts();
+        if starts != self.starts {
+            self.starts = starts;
+            self.ticks = 0;
+            ts.start();
+        }
+        ts.active = self.sync.running();
+
+        let ticks = self.sync.ticks();
+        let tick_len = |ts: &TempoState| ts.interval as f64 / TICKS_PER_BEAT as f64;
+        let count = |ts: &TempoState| ts.current as f64 + ts.frac;
+        let set = |ts: &mut TempoState, samples: f64| {
+            ts.current = samples as u32;
+            ts.frac = samples - ts.current as f64;
+        };
+
+        if ticks != self.ticks {
+            self.ticks = ticks;
+            if let Some(beat_len) = self.sync.beat_len() {
+                ts.ramp_to(beat_len, 0.0);
+            }
+            // the first tick after a start is the downbeat
+            let last_tick = ticks.saturating_sub(1) as f64 * tick_len(ts);
+            if count(ts) < last_tick {
+                set(ts, last_tick);
+            }
+        }
+
+        let next_tick = self.ticks as f64 * tick_len(ts);
+        if count(ts) > next_tick {
+            set(ts, next_tick);
+        }
+    }
+}
+
+
+// picks note ons out of a raw MIDI stream, on any channel
+//
+// a channel message can leave its status byte off when it's the
+// same as the last one's (running status), and real-time bytes
+// can turn up in the middle of one
+#[derive(Default)]
+pub struct NoteReader {
+    status: u8,
+    data: Vec<u8>,
+}
+
+impl NoteReader {
+    // the note and velocity, once a note on is complete
+    pub fn feed(&mut self, byte: u8) -> Option<(u8, u8)> {
+        match byte {
+            0xF8..=0xFF => None,
+            0x80..=0xF7 => {
+                self.status = byte;
+                self.data.clear();
+                None
+            }
+            data => {
+                let len = match self.status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    0x80..=0xE0 => 2,
+                    _ => return None, // system common, or nothing yet
+                };
+                self.data.push(data);
+                if self.data.len() < len {
+                    return None;
+                }
+                let message = (self.status & 0xF0, self.data[0], self.data.get(1).copied());
+                self.data.clear();
+                match message {
+                    // a note on at velocity 0 is a note off
+                    (0x90, note, Some(velocity)) if velocity > 0 => Some((note, velocity)),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+// what notes play, shared with the listener
+#[derive(Default)]
+struct Routes {
+    map: BTreeMap<u8, String>,       // note -> line
+    keys: Option<(String, KeyMap)>, // the Voice every other note plays, within its span
+}
+
+impl Routes {
+    fn line(&self, note: u8) -> Option<String> {
+        if let Some(line) = self.map.get(&note) {
+            return Some(line.clone());
+        }
+        let (voice, keymap) = self.keys.as_ref()?;
+        keymap.ratio(note).map(|_| format!("note {voice} {note}"))
+    }
+}
+
+// the REPL's side of note input: what each note runs, and the
+// listener, while there is one
+#[derive(Default)]
+pub struct NoteInput {
+    routes: Arc<Mutex<Routes>>,
+    listening: Option<Arc<AtomicBool>>, // set to let the device go
+    pub inbox: Option<Sender<Input>>,   // where lines go, when there's a REPL
+}
+
+impl NoteInput {
+    // has note (a name or number) trig, start or stop voice;
+    // off unmaps it
+    pub fn map(&self, note: &str, action: &str, voice: Option<&str>) -> Result<(), String> {
+        let n = notes::parse(note).ok_or_else(|| format!("'{note}' isn't a note"))?;
+        let map = &mut self.routes.lock().unwrap().map;
+        match (action, voice) {
+            ("off", None) => {
+                map.remove(&n).ok_or_else(|| format!("{} isn't mapped", notes::name(n)))?;
+            }
+            ("trig" | "start" | "stop", Some(voice)) => {
+                map.insert(n, format!("{action} {voice}"));
+            }
+            ("trig" | "start" | "stop", None) => return Err(format!("{action} which Voice?")),
+            _ => return Err(format!("A note can trig, start or stop a Voice, not '{action}'")),
+        }
+        Ok(())
+    }
+
+    // has the notes that aren't mapped play voice by its keymap
+    // (as it is now), or nothing
+    pub fn keys(&self, keys: Option<(&str, KeyMap)>) {
+        self.routes.lock().unwrap().keys = keys.map(|(voice, keymap)| (voice.to_string(), keymap));
+    }
+
+    // the line note runs, if any
+    #[cfg(test)]
+    pub fn line(&self, note: u8) -> Option<String> {
+        self.routes.lock().unwrap().line(note)
+    }
+
+    pub fn listing(&self) -> String {
+        let routes = self.routes.lock().unwrap();
+        let mut lines: Vec<String> = routes.map
+            .iter()
+            .map(|(note, line)| format!("{:>4} ({note}) = {line}", notes::name(*note)))
+            .collect();
+        if let Some((voice, keymap)) = &routes.keys {
+            let low = keymap.root.saturating_sub(keymap.span);
+            let high = keymap.root.saturating_add(keymap.span).min(127);
+            lines.push(format!("the rest of {}-{} play {voice}", notes::name(low), notes::name(high)));
+        }
+        if lines.is_empty() {
+            return "No notes mapped".to_string();
+        }
+        lines.join("\n")
+    }
+
+    // listens on device (one at a time), sending the line each
+    // mapped note runs to the REPL
+    pub fn listen(&mut self, device: &str) -> Result<(), String> {
+        let inbox = self.inbox.clone().ok_or("No REPL to play notes into")?;
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&closed);
+        let routes = Arc::clone(&self.routes);
+        let mut reader = NoteReader::default();
+        read(device, move || stop.load(Ordering::Relaxed), move |bytes| {
+            for byte in bytes {
+                if let Some((note, _)) = reader.feed(*byte)
+                    && let Some(line) = routes.lock().unwrap().line(note) {
+                    let _ = inbox.send(Input::Line { from: "midi".to_string(), line, reply: None });
+                }
+            }
+        })?;
+
+        self.close();
+        self.listening = Some(closed);
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        if let Some(closed) = self.listening.take() {
+            closed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// opens a raw MIDI input (e.g. hw:1,0,0) and follows its clock
+// on a thread of its own, until the ClockSync is closed
+#[cfg(target_os = "linux")]
+pub fn listen(device: &str, sync: Arc<ClockSync>) -> Result<(), String> {
+    let mut reader = ClockReader::default();
+    let closed = Arc::clone(&sync);
+    read(device, move || closed.is_closed(), move |bytes| {
+        let at = clock::current();
+        for byte in bytes {
+            reader.feed(*byte, at, &sync);
+        }
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listen(device: &str, _sync: Arc<ClockSync>) -> Result<(), String> {
+    read(device, || true, |_| ())
+}
+
+// reads a raw MIDI input on a thread of its own, handing feed
+// whatever comes in, until closed says to stop
+#[cfg(target_os = "linux")]
+fn read(
+    device: &str,
+    closed: impl Fn() -> bool + Send + 'static,
+    mut feed: impl FnMut(&[u8]) + Send + 'static,
+) -> Result<(), String> {
+    let name = CString::new(device).map_err(|_| format!("Invalid MIDI device name: {device}"))?;
+    let device = device.to_string();
+    let (opened, result) = mpsc::channel::<Result<(), String>>();
+
+    // the handle never leaves the thread that opened it
+    thread::spawn(move || unsafe {
+        let mut input: *mut snd_rawmidi_t = ptr::null_mut();
+        let err = snd_rawmidi_open(&mut input, ptr::null_mut(), name.as_ptr(), SND_RAWMIDI_NONBLOCK);
+        if err < 0 {
+            let why = CStr::from_ptr(snd_strerror(err)).to_string_lossy();
+            let _ = opened.send(Err(format!("Couldn't open MIDI input {device}: {why}")));
+            return;
+        }
+        let _ = opened.send(Ok(()));
+
+        let mut buf = [0u8; 64];
+        while !closed() {
+            let n = snd_rawmidi_read(input, buf.as_mut_ptr().cast(), buf.len());
+            if n <= 0 {
+                // nothing waiting (or the device is gone for now)
+                thread::sleep(Duration::from_micros(500));
+                continue;
+            }
+            feed(&buf[..n as usize]);
+        }
+        snd_rawmidi_close(input);
+    });
+
+    result.recv().map_err(|_| "MIDI listener stopped before opening".to_string())?
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read(
+    device: &str,
+    _closed: impl Fn() -> bool + Send + 'static,
+    _feed: impl FnMut(&[u8]) + Send + 'static,
+) -> Result<(), String> {
+    Err(format!("Couldn't open MIDI input {device}: raw MIDI is only read through ALSA (Linux)"))
+}
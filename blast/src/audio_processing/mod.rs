@@ -1,6 +1,35 @@
+pub mod autoloop;
+pub mod backend;
+pub mod bindings;
+pub mod capture;
 pub mod commands;
+pub mod cue;
+pub mod devices;
+pub mod effects;
 pub mod engine;
+pub mod events;
+pub mod headless;
+pub mod loudness;
+pub mod meters;
+pub mod midi;
+pub mod notes;
+pub mod osc;
 pub mod blast_time;
+pub mod offline;
 pub mod processes;
+pub mod realtime;
+pub mod reaper;
+pub mod record;
+pub mod responses;
+pub mod ring;
+pub mod routing;
 pub mod runtime;
+pub mod schema;
+pub mod scripting;
+pub mod simd;
+pub mod socket;
+pub mod stats;
+pub mod tui;
+pub mod wave;
+pub mod ws;
 pub mod blast_rand;
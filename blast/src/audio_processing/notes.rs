@@ -0,0 +1,58 @@
+// notes
+//
+// MIDI note numbers, from names like C2, F#3 or Bb-1
+// (middle C is C4, 60), and keymaps, which turn a Voice
+// into an instrument: a note repitches it relative to the
+// root and plays it from the top
+//
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KeyMode {
+    Varispeed, // faster and shorter going up, like a tape
+    Pitch, // the same length, through the stretch/pitch grains
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KeyMap {
+    pub root: u8, // the note that plays the sample as it is
+    pub span: u8, // semitones either side of the root that play
+    pub mode: KeyMode,
+}
+
+impl KeyMap {
+    // the playback ratio for a note, if it's in the span
+    pub fn ratio(&self, note: u8) -> Option<f32> {
+        let semitones = note as i32 - self.root as i32;
+        match semitones.unsigned_abs() <= self.span as u32 {
+            true => Some(2f32.powf(semitones as f32 / 12.0)),
+            false => None,
+        }
+    }
+}
+
+const NAMES: [(&str, i32); 7] = [("C", 0), ("D", 2), ("E", 4), ("F", 5), ("G", 7), ("A", 9), ("B", 11)];
+
+// a note name (C4, f#3, Bb-1) or number (0..=127)
+pub fn parse(raw: &str) -> Option<u8> {
+    if let Ok(n) = raw.parse::<u8>() {
+        return (n <= 127).then_some(n);
+    }
+
+    let letter = raw.get(..1)?.to_ascii_uppercase();
+    let base = NAMES.iter().find(|(name, _)| *name == letter)?.1;
+    let rest = &raw[1..];
+    let (shift, octave) = match rest.chars().next() {
+        Some('#') => (1, &rest[1..]),
+        Some('b') => (-1, &rest[1..]),
+        _ => (0, rest),
+    };
+    let octave = octave.parse::<i32>().ok()?;
+
+    let n = (octave + 1) * 12 + base + shift;
+    (0..=127).contains(&n).then_some(n as u8)
+}
+
+pub fn name(note: u8) -> String {
+    const SHARPS: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    format!("{}{}", SHARPS[note as usize % 12], note as i32 / 12 - 1)
+}
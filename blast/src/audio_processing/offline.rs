@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use crate::file_parsing::decode_helpers::AudioFile;
+use crate::audio_processing::{
+    engine::Conductor,
+    commands::{CmdProcessor, Command, EngineState, FadeArgs},
+    blast_time::sample_rate,
+    runtime::RunOptions,
+};
+
+// offline processing
+//
+// runs a Track through the same CmdProcessor and Conductor as
+// a live session, but renders into memory instead of a device
+//
+// a preset is a list of command lines applied to the Track's Voice,
+// with $voice standing in for its name, e.g.
+//   velocity $voice 0.5
+//   gain -v $voice -6dB
+//   loop $voice 2
+// `load $voice` and `start -v $voice` are added if the preset
+// doesn't have them
+//
+// a whole session renders the same way, for as long as it's
+// asked to: every Track, a script run as `run` would, and the
+// engine driven a block at a time as fast as it goes
+//
+// so does a bounce, of one Group out of a session rebuilt from
+// its history (see CmdProcessor::try_bounce)
+//
+
+// the longest a bounce runs with no length given
+pub const MAX_BOUNCE_SECS: f32 = 60.0;
+
+// renders until every Voice has played out, or max_secs;
+// returns interleaved samples at the Track's rate and channel count
+pub fn process(preset: &[String], af: AudioFile, max_secs: f32) -> Result<Vec<i16>, String> {
+    let name = af.file_name.clone();
+    let channels = af.num_channels.max(1) as usize;
+
+    sample_rate::set(af.sample_rate);
+    let max_frames = (max_secs * af.sample_rate as f32) as usize;
+
+    let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], channels));
+    let mut conductor = Conductor::prepare(channels, HashMap::from([(name.clone(), af)]));
+
+    let mut lines: Vec<String> = preset
+        .iter()
+        .map(|line| line.replace("$voice", &name))
+        .collect();
+
+    let has = |lines: &[String], cmd: &str| lines.iter().any(|l| l.split_whitespace().next() == Some(cmd));
+    if !has(&lines, "load") {
+        lines.insert(0, format!("load {name}"));
+    }
+    if !has(&lines, "start") {
+        lines.push(format!("start -v {name}"));
+    }
+
+    for line in lines {
+        match cmd_processor.parse(line.clone()) {
+            // quitting raises SIGTERM, which would end the whole batch
+            Ok(Some(Command::Quit(_))) => return Err(format!("'{line}' can't be used in a preset")),
+            Ok(Some(cmd)) => conductor.apply(cmd),
+            Ok(None) => (),
+            Err(error) => return Err(format!("'{line}': {error}")),
+        }
+    }
+
+    // frame by frame, so the output ends exactly where playback does
+    let mut out = Vec::<i16>::new();
+    let mut frame = vec![0i16; channels];
+    while conductor.is_playing() && out.len() < max_frames * channels {
+        conductor.render(&mut frame);
+        out.extend_from_slice(&frame);
+    }
+
+    Ok(out)
+}
+
+// frames rendered at a time for a session
+const BLOCK: usize = 1024;
+
+// runs opts' script (if any) against every Track, as a live
+// session would, and renders secs of what it plays; returns
+// interleaved samples at rate
+pub fn render(tracks: HashMap<String, AudioFile>, rate: u32, channels: usize, opts: &RunOptions, secs: f32) -> Result<Vec<i16>, String> {
+    sample_rate::set(rate);
+    let frames = (secs * rate as f32) as usize;
+
+    let mut engine_state = EngineState::new(tracks.clone().into_values().collect(), channels);
+    let mut conductor = Conductor::prepare(channels, tracks);
+    if let Some(voices) = opts.voices {
+        engine_state.set_pool(voices);
+        conductor.reserve(voices);
+    }
+    let mut cmd_processor = CmdProcessor::new(engine_state);
+    if let Some(ms) = opts.fade_ms {
+        conductor.apply(Command::Fade(FadeArgs { ms }));
+    }
+    for (name, body) in &opts.aliases {
+        cmd_processor
+            .add_alias(name, body)
+            .map_err(|error| format!("alias {name} from blast.conf: {error}"))?;
+    }
+
+    if let Some(path) = &opts.script {
+        cmd_processor.parse(format!("run {path}")).map_err(|error| error.to_string())?;
+    }
+    while let Some((at, line)) = cmd_processor.next_scripted() {
+        match cmd_processor.parse(line.clone()) {
+            Ok(Some(Command::Quit(_))) => return Err(format!("{at}: '{line}' can't be used in a render")),
+            Ok(Some(cmd)) => conductor.apply(cmd),
+            Ok(None) => (),
+            Err(error) => return Err(format!("{at}: {error}")),
+        }
+    }
+
+    let mut out = vec![0i16; frames * channels];
+    for block in out.chunks_mut(BLOCK * channels) {
+        conductor.render(block);
+    }
+
+    Ok(out)
+}
+
+// applies commands to a session of tracks (in TrackRepr order),
+// then renders Group g for frames, or until it's played out (up
+// to MAX_BOUNCE_SECS); returns interleaved samples at the device's
+// rate and channel count
+pub fn bounce(tracks: Vec<AudioFile>, channels: usize, commands: Vec<Command>, g: usize, frames: Option<usize>) -> Vec<i16> {
+    let mut conductor = Conductor::with_tracks(channels, tracks);
+    for cmd in commands {
+        conductor.apply(cmd);
+    }
+
+    if let Some(frames) = frames {
+        let mut out = vec![0i16; frames * channels];
+        conductor.render_group(g, &mut out);
+        return out;
+    }
+
+    let max_frames = (MAX_BOUNCE_SECS * sample_rate::get() as f32) as usize;
+    let mut out = Vec::<i16>::new();
+    let mut frame = vec![0i16; channels];
+    while conductor.group_is_playing(g) && out.len() < max_frames * channels {
+        conductor.render_group(g, &mut frame);
+        out.extend_from_slice(&frame);
+    }
+    out
+}
@@ -0,0 +1,124 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::mpsc::Sender,
+    thread,
+};
+
+use crate::audio_processing::{runtime::Input, tui};
+
+// osc
+//
+// an OSC server on a UDP port (--osc 9000, or osc in blast.conf)
+// for TouchOSC, SuperCollider, a DAW...: each message under /blast
+// is turned into the line it stands for and run as if it had been
+// typed, so it's checked, echoed and undone like any other
+//
+// the address after /blast is the command and its first words,
+// and the arguments follow in order:
+//   /blast/start "kick"          start kick
+//   /blast/velocity/kick 0.8     velocity kick 0.8
+//   /blast/tempo "beat" "b:140"  tempo beat b:140
+//
+// bundles are unpacked and run in order, as they come (their time
+// tags are ignored)
+//
+
+// opens addr (e.g. 127.0.0.1:9000) and sends every line a message
+// stands for to inbox, from a thread of its own; returns where
+// it's listening
+pub fn listen(addr: &str, inbox: Sender<Input>) -> Result<SocketAddr, String> {
+    let socket = UdpSocket::bind(addr).map_err(|e| format!("Couldn't listen for OSC on {addr}: {e}"))?;
+    let bound = socket.local_addr().map_err(|e| e.to_string())?;
+
+    thread::spawn(move || {
+        let mut buf = vec![0u8; 65536];
+        while let Ok((n, from)) = socket.recv_from(&mut buf) {
+            match lines(&buf[..n]) {
+                Ok(lines) => {
+                    for line in lines {
+                        if inbox.send(Input::Line { from: "osc".to_string(), line, reply: None }).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(err) => tui::warn(format!("osc from {from}: {err}")),
+            }
+        }
+    });
+
+    Ok(bound)
+}
+
+// the lines a packet (a message, or a bundle of them) stands for
+pub fn lines(packet: &[u8]) -> Result<Vec<String>, String> {
+    let mut lines = Vec::new();
+    unpack(packet, &mut lines)?;
+    Ok(lines)
+}
+
+fn unpack(packet: &[u8], lines: &mut Vec<String>) -> Result<(), String> {
+    let mut at = 0;
+
+    if packet.starts_with(b"#bundle\0") {
+        at = 16; // past the time tag
+        while at < packet.len() {
+            // a size that's negative, or runs past the end, is the
+            // same as one cut short
+            let len = usize::try_from(int(packet, &mut at)?).map_err(|_| "Truncated bundle")?;
+            let end = at.checked_add(len).ok_or("Truncated bundle")?;
+            let element = packet.get(at..end).ok_or("Truncated bundle")?;
+            unpack(element, lines)?;
+            at = end;
+        }
+        return Ok(());
+    }
+
+    let address = string(packet, &mut at)?;
+    let path = address
+        .strip_prefix("/blast/")
+        .ok_or_else(|| format!("{address} isn't under /blast"))?;
+    let mut words: Vec<String> = path.split('/').filter(|w| !w.is_empty()).map(str::to_string).collect();
+    if words.is_empty() {
+        return Err(format!("{address} names no command"));
+    }
+
+    // very old senders leave the type tags off when there are no
+    // arguments
+    let tags = if at < packet.len() { string(packet, &mut at)? } else { "," };
+    let tags = tags.strip_prefix(',').ok_or_else(|| format!("Bad type tags for {address}"))?;
+    for tag in tags.chars() {
+        let word = match tag {
+            'i' => int(packet, &mut at)?.to_string(),
+            'f' => f32::from_bits(int(packet, &mut at)? as u32).to_string(),
+            'h' => long(packet, &mut at)?.to_string(),
+            'd' => f64::from_bits(long(packet, &mut at)? as u64).to_string(),
+            's' | 'S' => string(packet, &mut at)?.to_string(),
+            other => return Err(format!("Unsupported argument type '{other}' for {address}")),
+        };
+        words.push(word);
+    }
+
+    lines.push(words.join(" "));
+    Ok(())
+}
+
+// a NUL-terminated string, padded to 4 bytes
+fn string<'a>(packet: &'a [u8], at: &mut usize) -> Result<&'a str, String> {
+    let rest = packet.get(*at..).unwrap_or_default();
+    let len = rest.iter().position(|b| *b == 0).ok_or("Unterminated string")?;
+    let s = str::from_utf8(&rest[..len]).map_err(|_| "A string isn't UTF-8".to_string())?;
+    *at += (len + 4) & !3;
+    Ok(s)
+}
+
+fn int(packet: &[u8], at: &mut usize) -> Result<i32, String> {
+    let bytes = packet.get(*at..*at + 4).ok_or("Truncated argument")?;
+    *at += 4;
+    Ok(i32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn long(packet: &[u8], at: &mut usize) -> Result<i64, String> {
+    let bytes = packet.get(*at..*at + 8).ok_or("Truncated argument")?;
+    *at += 8;
+    Ok(i64::from_be_bytes(bytes.try_into().unwrap()))
+}
@@ -1,16 +1,19 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
 
 use crate::audio_processing::{
     blast_rand::X128P,
-    engine::VoiceState,
-    blast_time::blast_time::{TempoState, TempoMode},
+    engine::{VoiceState, Mods},
+    blast_time::{sample_rate, blast_time::TempoState},
+    reaper::{ReapQueue, Remains},
 };
 
 // Processes
 //
 macro_rules! processes {
     ( $( $variant:ident ),* $(,)? ) => {
+        #[non_exhaustive]
         pub enum Process {
             $(
                 $variant($variant),
@@ -47,6 +50,21 @@ macro_rules! processes {
 
 processes! {
     Seq,
+    Follow,
+    Lfo,
+}
+
+impl Process {
+    // a Seq's steps, handed to reaper rather than freed on the
+    // audio thread; the others hold no buffers
+    pub fn reap(&mut self, reaper: &ReapQueue) {
+        if let Process::Seq(seq) = self {
+            let state = &mut seq.state;
+            for buf in [&mut state.steps, &mut state.chance, &mut state.jit, &mut state.levels] {
+                reaper.push(Remains::Buf(std::mem::take(buf)));
+            }
+        }
+    }
 }
 
 pub struct Seq {
@@ -60,8 +78,47 @@ pub struct SeqState {
     pub steps: Vec<f32>,
     pub chance: Vec<f32>,
     pub jit: Vec<f32>,
-    pub rng: X128P, // TODO: impl user-defined seed
+    pub levels: Vec<f32>, // how hard each step hits
+    pub swing: Swing,
+    pub rng: X128P,
+    pub seed: Option<u64>, // a seeded Seq makes the same choices every time it starts
     pub idx: usize,
+    pub phase: Arc<AtomicUsize>, // last step played, read by `status`
+    pub primed: bool, // whether steps already behind have been skipped
+    pub last: f64, // where the tempo was last frame, in beats
+}
+
+// phase before any step has played
+pub const NO_STEP: usize = usize::MAX;
+
+// swing
+//
+// pushes every other subdivision late by amount (a fraction of
+// a subdivision); each pair of subdivisions is stretched up to
+// the late one and squeezed after it, so steps between the
+// grid lines move along and keep their order
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Swing {
+    pub amount: f32, // 0..1; 0 is straight
+    pub division: f32, // subdivisions per unit of tempo
+}
+
+impl Swing {
+    pub const STRAIGHT: Self = Self { amount: 0.0, division: 2.0 };
+
+    // where a step (in units of tempo) lands
+    pub fn apply(&self, step: f32) -> f32 {
+        if self.amount == 0.0 { return step; }
+
+        let sub = 1.0 / self.division;
+        let pair = (step / (2.0 * sub)).floor() * 2.0 * sub;
+        let t = step - pair;
+        let late = sub * (1.0 + self.amount);
+        match t < sub {
+            true => pair + t * (1.0 + self.amount),
+            false => pair + late + (t - sub) * (1.0 - self.amount),
+        }
+    }
 }
 
 impl Seq {
@@ -74,23 +131,266 @@ impl Seq {
 
         if !tempo.active { return; }
 
-        let current = tempo.current() % state.period as f32;
+        // a Voice's offset moves its steps earlier or later
+        // against the tempo (and the tempo's count is in samples)
+        let shifted = tempo.current as f64 + tempo.frac - voice.offset as f64;
+        if shifted < 0.0 { return; }
+        // a step fires on the first whole sample at or after it
+        // (swung steps rarely land on one); compared in beats, which
+        // a tempo ramp never moves backwards (the samples can shrink)
+        let beats = shifted / tempo.interval as f64;
+        let period = state.period as f64;
+        let current = (beats % period) as f32;
+        let swing = state.swing;
+        let at = |step: f32| swing.apply(step);
+
+        // a tempo started over (or moved back) starts the pattern over
+        if beats < state.last {
+            state.idx = 0;
+            state.primed = false;
+        }
+
+        // an early Voice starts partway into the pattern;
+        // its start plays whatever it skips
+        if !state.primed {
+            state.primed = true;
+            while state.idx < state.steps.len() && at(state.steps[state.idx]) < current {
+                state.idx += 1;
+            }
+            state.idx %= state.steps.len();
+            state.last = beats - 1.0 / tempo.interval as f64;
+        }
 
-        if current == state.steps[state.idx] {
+        // every step passed since the last frame (usually none or
+        // one, but a tempo ramp moves the count by more than a sample)
+        let last = state.last.rem_euclid(period) as f32;
+        let passed = |due: f32| match current >= last {
+            true => due > last && due <= current,
+            false => due > last || due <= current, // wrapped around
+        };
+        for _ in 0..state.steps.len() {
+            if !passed(at(state.steps[state.idx])) {
+                break;
+            }
             let rand = state.rng.next_i64_range(0, 100);
             if rand < state.chance[state.idx] as i64 {
-                voice.position = match voice.velocity >= 0.0 {
-                    true => 0.0,
-                    false => voice.end as f32,
-                };
+                voice.trigger = true;
+                voice.trigger_level = state.levels[state.idx];
+                voice.fired = Some(state.idx);
             }
+            state.phase.store(state.idx, Ordering::Relaxed);
             state.idx += 1;
             state.idx %= state.steps.len();
         }
+        state.last = beats;
     }
 
     fn reset(&mut self) {
+        if let Some(seed) = self.state.seed {
+            self.state.rng = X128P::new(seed);
+        }
         self.state.idx = 0;
+        self.state.primed = false;
+        self.state.phase.store(NO_STEP, Ordering::Relaxed);
+    }
+
+    fn update_tempo(&mut self, ts: Rc<RefCell<TempoState>>) {
+        self.state.tempo = ts;
+    }
+}
+
+// tempo follower
+//
+// listens to its Voice for onsets (the fast envelope of its
+// level jumping above the slow one) and steers a TempoContext
+// toward them: the beat interval moves toward the time between
+// onsets (doubled or halved into range), and the beat's phase
+// is pulled toward each onset
+//
+pub struct Follow {
+    pub state: FollowState,
+}
+
+pub struct FollowState {
+    pub tempo: Rc<RefCell<TempoState>>, // the TempoContext being steered
+    min: f32, // beat interval bounds, in samples
+    max: f32,
+    fast: f32, // envelopes of the Voice's level
+    slow: f32,
+    attack: f32, // per-sample coefficients
+    release: f32,
+    settle: f32,
+    since: u64, // samples since the last onset
+    heard: bool, // whether there's been an onset to measure from
+}
+
+// how far the fast envelope has to rise over the slow one,
+// and the floor under which nothing counts as an onset
+const ONSET_RATIO: f32 = 1.5;
+const ONSET_FLOOR: f32 = 0.02;
+// how much of the error each onset corrects
+const INTERVAL_PULL: f32 = 0.25;
+const PHASE_PULL: f32 = 0.5;
+
+impl Follow {
+    // min_bpm..max_bpm is the range the beat is folded into
+    pub fn new(tempo: Rc<RefCell<TempoState>>, min_bpm: f32, max_bpm: f32) -> Self {
+        let sr = sample_rate::get().max(1) as f32;
+        let coef = |secs: f32| 1.0 - (-1.0 / (secs * sr)).exp();
+
+        Self {
+            state: FollowState {
+                tempo,
+                min: sr * 60.0 / max_bpm,
+                max: sr * 60.0 / min_bpm,
+                fast: 0.0,
+                slow: 0.0,
+                attack: coef(0.001),
+                release: coef(0.01),
+                settle: coef(0.1),
+                since: 0,
+                heard: false,
+            },
+        }
+    }
+
+    fn process(&mut self, voice: &mut VoiceState) {
+        let state = &mut self.state;
+        let x = voice.level;
+
+        let fast_coef = if x > state.fast { state.attack } else { state.release };
+        state.fast += (x - state.fast) * fast_coef;
+        state.slow += (x - state.slow) * state.settle;
+        state.since += 1;
+
+        // at most one onset per half of the shortest beat
+        let onset = state.fast > state.slow * ONSET_RATIO + ONSET_FLOOR
+            && state.since as f32 > state.min / 2.0;
+
+        if onset {
+            self.onset();
+        }
+    }
+
+    fn onset(&mut self) {
+        let state = &mut self.state;
+        let mut ts = state.tempo.borrow_mut();
+
+        if state.heard {
+            let mut ioi = state.since as f32;
+            while ioi > state.max {
+                ioi /= 2.0;
+            }
+            while ioi < state.min {
+                ioi *= 2.0;
+            }
+
+            // whole samples, since Seqs fire on exact steps;
+            // at least one at a time, so rounding can't stall it
+            if ioi <= state.max {
+                let err = ioi - ts.interval;
+                let step = match (err * INTERVAL_PULL).abs() < 1.0 {
+                    true => err.clamp(-1.0, 1.0),
+                    false => err * INTERVAL_PULL,
+                };
+                ts.interval = (ts.interval + step).round().max(1.0);
+            }
+        }
+
+        // pull the nearest beat toward now
+        let interval = ts.interval;
+        let pos = ts.current as f32 % interval;
+        let err = if pos > interval / 2.0 { pos - interval } else { pos };
+        ts.current = (ts.current as f32 - err * PHASE_PULL).round().max(0.0) as u32;
+
+        state.heard = true;
+        state.since = 0;
+    }
+
+    fn reset(&mut self) {
+        let state = &mut self.state;
+        state.fast = 0.0;
+        state.slow = 0.0;
+        state.since = 0;
+        state.heard = false;
+    }
+
+    fn update_tempo(&mut self, ts: Rc<RefCell<TempoState>>) {
+        self.state.tempo = ts;
+    }
+}
+
+// LFO
+//
+// modulates one of its Voice's parameters once per cycle of its
+// TempoState (so it can lock to a Group or TempoContext);
+// the modulation is applied on top of the parameter, which keeps
+// whatever value it was set to
+//
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wave {
+    Sine,
+    Triangle,
+    Square,
+    Random, // a new value every cycle
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LfoTarget {
+    Gain,     // dips to 1 - depth
+    Velocity, // swings by +/- depth
+    Pan,      // swings by +/- depth
+}
+
+pub struct Lfo {
+    pub state: LfoState,
+}
+
+pub struct LfoState {
+    pub tempo: Rc<RefCell<TempoState>>,
+    pub wave: Wave,
+    pub target: LfoTarget,
+    pub depth: f32,
+    pub rng: X128P,
+    pub held: f32, // Random's value for this cycle
+    pub cycle: u32,
+}
+
+impl Lfo {
+    fn process(&mut self, voice: &mut VoiceState) {
+        let state = &mut self.state;
+        let tempo = state.tempo.borrow();
+
+        if !tempo.active { return; }
+
+        let pos = tempo.current();
+        let phase = pos.fract();
+
+        // -1..1
+        let value = match state.wave {
+            Wave::Sine => (phase * std::f32::consts::TAU).sin(),
+            Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Wave::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Wave::Random => {
+                let cycle = pos as u32;
+                if cycle != state.cycle {
+                    state.cycle = cycle;
+                    state.held = state.rng.next_i64_range(-1000, 1001) as f32 / 1000.0;
+                }
+                state.held
+            }
+        };
+
+        let mods: &mut Mods = &mut voice.mods;
+        match state.target {
+            LfoTarget::Gain => mods.gain *= 1.0 - state.depth * (1.0 - value) / 2.0,
+            LfoTarget::Velocity => mods.velocity *= 1.0 + state.depth * value,
+            LfoTarget::Pan => mods.pan += state.depth * value,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state.cycle = u32::MAX;
     }
 
     fn update_tempo(&mut self, ts: Rc<RefCell<TempoState>>) {
@@ -0,0 +1,151 @@
+use std::io;
+
+// low-latency mode
+//
+// both calls are best-effort: on failure they return a diagnostic
+// explaining which limit got in the way, and the engine carries on
+// with normal scheduling/paging
+//
+// (rtkit isn't asked, since that means talking D-Bus; the limits
+// below are what rtkit itself is configured around anyway)
+//
+
+// puts the calling thread on SCHED_FIFO at (up to) priority,
+// returning the priority it got
+#[cfg(target_os = "linux")]
+pub fn promote_current_thread(priority: i32) -> Result<i32, String> {
+    unsafe {
+        let max = libc::sched_get_priority_max(libc::SCHED_FIFO);
+        let mut prio = priority.clamp(1, max.max(1));
+
+        let mut err = set_fifo(prio);
+        if err.is_none() {
+            return Ok(prio);
+        }
+
+        // unprivileged users can still go as high as RLIMIT_RTPRIO,
+        // but only after raising the soft limit themselves
+        let mut lim: libc::rlimit = std::mem::zeroed();
+        if libc::getrlimit(libc::RLIMIT_RTPRIO, &mut lim) == 0 && lim.rlim_max > 0 {
+            let allowed = lim.rlim_max.min(prio as libc::rlim_t);
+            lim.rlim_cur = allowed;
+            if libc::setrlimit(libc::RLIMIT_RTPRIO, &lim) == 0 {
+                prio = allowed as i32;
+                err = set_fifo(prio);
+                if err.is_none() {
+                    return Ok(prio);
+                }
+            }
+        }
+
+        Err(format!(
+            "couldn't get SCHED_FIFO priority {} ({}); RLIMIT_RTPRIO allows {}. \
+             Add e.g. '@audio - rtprio 95' under /etc/security/limits.d/ and join \
+             the audio group, or grant CAP_SYS_NICE. Using normal scheduling",
+            priority,
+            err.unwrap(),
+            lim.rlim_max,
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn promote_current_thread(_priority: i32) -> Result<i32, String> {
+    Err("SCHED_FIFO is only asked for on Linux; using normal scheduling".to_string())
+}
+
+// locks the process's pages in RAM so the audio thread never
+// waits on a page fault; future allocations are only locked
+// when RLIMIT_MEMLOCK is unlimited, otherwise they could start
+// failing once the limit is reached
+pub fn lock_memory() -> Result<&'static str, String> {
+    unsafe {
+        let mut lim: libc::rlimit = std::mem::zeroed();
+        libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut lim);
+
+        let unlimited = lim.rlim_cur == libc::RLIM_INFINITY;
+        let flags = match unlimited {
+            true => libc::MCL_CURRENT | libc::MCL_FUTURE,
+            false => libc::MCL_CURRENT,
+        };
+
+        if libc::mlockall(flags) == 0 {
+            return Ok(match unlimited {
+                true => "locked current and future memory",
+                false => "locked current memory (RLIMIT_MEMLOCK is finite, so later allocations aren't locked)",
+            });
+        }
+
+        Err(format!(
+            "couldn't lock memory ({}); RLIMIT_MEMLOCK is {} bytes. \
+             Add e.g. '@audio - memlock unlimited' under /etc/security/limits.d/, \
+             or grant CAP_IPC_LOCK. Pages may fault on the audio thread",
+            io::Error::last_os_error(),
+            lim.rlim_cur,
+        ))
+    }
+}
+
+// CPU affinity
+//
+// restricts the calling thread (and any threads it spawns
+// afterward) to the given cores
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpus: &[usize]) -> Result<(), String> {
+    if cpus.is_empty() {
+        return Err("no CPUs left to pin to".to_string());
+    }
+
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(format!("no such CPU {cpu}"));
+            }
+            libc::CPU_SET(cpu, &mut set);
+        }
+
+        match libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set) {
+            0 => Ok(()),
+            _ => Err(format!(
+                "couldn't pin to CPUs {:?} ({})",
+                cpus,
+                io::Error::last_os_error()
+            )),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpus: &[usize]) -> Result<(), String> {
+    Err("threads can only be pinned on Linux".to_string())
+}
+
+// cores this process may run on
+#[cfg(target_os = "linux")]
+pub fn available_cpus() -> Vec<usize> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Vec::new();
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn available_cpus() -> Vec<usize> {
+    let n = std::thread::available_parallelism().map_or(1, |n| n.get());
+    (0..n).collect()
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn set_fifo(prio: i32) -> Option<io::Error> {
+    let param = libc::sched_param { sched_priority: prio };
+    match unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) } {
+        0 => None,
+        code => Some(io::Error::from_raw_os_error(code)),
+    }
+}
@@ -0,0 +1,115 @@
+use std::cell::UnsafeCell;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use std::{thread, time::Duration};
+
+use crate::file_parsing::decode_helpers::{AudioFile, Samples};
+use crate::audio_processing::{
+    commands::{Command, Unshared},
+    effects::Verb,
+    engine::{Conductor, Tail},
+    routing::Routing,
+};
+
+// reaper
+//
+// what the audio thread is done with, freed somewhere else: a
+// Track's samples can run to megabytes, and a delay line to a few
+// seconds' worth, and handing that back to the allocator in the
+// middle of a block is an xrun
+//
+// the same goes for what a Command replaces: a delay line, a
+// reverb, a Seq's pattern, a routing, a polyphony pool
+//
+// a Voice itself isn't Send (its tempo is shared through an Rc),
+// so what goes over is its buffers, taken out of it (or, for the
+// samples it shares, another hold on them), and whatever of it
+// shares no tempo whole; what's left is the Vecs that hold its
+// Processes and Effects (and tempo Rcs) themselves, a few words
+// per entry, which are still freed where they are
+//
+// the audio thread pushes them onto a fixed queue, and a reaping
+// thread drops them; if the queue's full, they're dropped in
+// place after all, and counted
+//
+
+pub enum Remains {
+    Samples(Samples), // a Track's, if nothing else holds them (a mapping's unmapped)
+    Buf(Vec<f32>),
+    Verb(Verb), // its comb and allpass lines
+    Routing(Routing),
+    Tails(Vec<Tail>),
+    Session(Unshared<Box<Conductor>>), // crossfaded out, dropped or replaced
+    Commands(Vec<Command>), // an undo's, once applied
+    Tracks(Vec<AudioFile>), // the engine's, when outgrown, or an import's, emptied
+}
+
+// buffers the queue holds before the audio thread frees its own
+pub const QUEUE_LEN: usize = 1024;
+
+// from the audio thread to the reaping thread: one producer,
+// one consumer, as ResponseQueue
+pub struct ReapQueue {
+    buf: Vec<UnsafeCell<Option<Remains>>>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    inline: AtomicU64,
+}
+
+unsafe impl Send for ReapQueue {}
+unsafe impl Sync for ReapQueue {}
+
+impl ReapQueue {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            buf: (0..cap.max(2)).map(|_| UnsafeCell::new(None)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            inline: AtomicU64::new(0),
+        }
+    }
+
+    pub fn push(&self, remains: Remains) {
+        let head = self.head.load(Ordering::Relaxed);
+        let next = (head + 1) % self.buf.len();
+        if next == self.tail.load(Ordering::Acquire) {
+            self.inline.fetch_add(1, Ordering::Relaxed);
+            return; // and remains drops here
+        }
+        unsafe {
+            *self.buf[head].get() = Some(remains);
+        }
+        self.head.store(next, Ordering::Release);
+    }
+
+    pub fn pop(&self) -> Option<Remains> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+        let remains = unsafe { (*self.buf[tail].get()).take() };
+        self.tail.store((tail + 1) % self.buf.len(), Ordering::Release);
+        remains
+    }
+
+    // buffers there wasn't room for, freed on the audio thread
+    pub fn inline(&self) -> u64 {
+        self.inline.load(Ordering::Relaxed)
+    }
+}
+
+// drops whatever's pushed onto the queue, for as long
+// as the queue's still pushed to
+pub fn reap(queue: Arc<ReapQueue>) {
+    thread::spawn(move || loop {
+        while let Some(remains) = queue.pop() {
+            drop(remains);
+        }
+        if Arc::strong_count(&queue) == 1 {
+            return;
+        }
+        thread::sleep(Duration::from_millis(5));
+    });
+}
@@ -0,0 +1,129 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::file_parsing::wav;
+use crate::audio_processing::{
+    blast_time::blast_time::clock,
+    cue::CueSheet,
+    ring::Ring,
+};
+
+// record
+//
+// rec start <file> tees the master, as the audio loop hands it
+// to the device, into a WAV: the audio thread only copies each
+// period into a ring, and a writer thread of its own drains it
+// to disk, so playback never waits on the file
+//
+// the ring holds a couple of seconds; if the disk falls behind
+// by more than that, what doesn't fit is dropped (and counted),
+// never waited for
+//
+// it keeps a cue sheet as well, of what's marked while it plays
+// (see cue.rs), and writes it next to the WAV when it stops
+//
+
+const RING_SECS: usize = 2;
+
+// the audio thread's end
+pub struct Tap {
+    ring: Ring,
+    channels: usize,
+    started: AtomicU64, // the global clock at the first sample pushed
+    dropped: AtomicU64, // samples
+    stopped: AtomicBool,
+}
+
+impl Tap {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            ring: Ring::new(sample_rate as usize * channels * RING_SECS),
+            channels: channels.max(1),
+            started: AtomicU64::new(u64::MAX),
+            dropped: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    // a period, once it's rendered (and the clock has moved past it)
+    pub fn push(&self, samples: &[i16]) {
+        let frames = (samples.len() / self.channels) as u64;
+        let at = clock::current().saturating_sub(frames);
+        let _ = self.started.compare_exchange(u64::MAX, at, Ordering::Relaxed, Ordering::Relaxed);
+
+        let n = self.ring.push(samples);
+        if n < samples.len() {
+            self.dropped.fetch_add((samples.len() - n) as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+// the command thread's end: the file and the thread writing it
+pub struct Recording {
+    pub path: String,
+    pub cues: CueSheet,
+    tap: Arc<Tap>,
+    channels: usize,
+    sample_rate: u32,
+    writer: JoinHandle<Result<u64, String>>, // samples written
+}
+
+impl Recording {
+    // creates path and starts writing whatever tap is given
+    pub fn start(path: &str, tap: Arc<Tap>, sample_rate: u32, channels: usize) -> Result<Self, String> {
+        let mut file = wav::Writer::create(path, sample_rate, channels as u32).map_err(|e| format!("{e}"))?;
+
+        let ring = Arc::clone(&tap);
+        let writer = thread::spawn(move || {
+            let mut buf = vec![0i16; 4096 * channels];
+            let mut written = 0;
+            loop {
+                // read stopped first, so nothing pushed before it is missed
+                let stopped = ring.stopped.load(Ordering::Acquire);
+                let n = ring.ring.pop_with(&mut buf, |s| s);
+                if n > 0 {
+                    file.write(&buf[..n]).map_err(|e| format!("{e}"))?;
+                    written += n as u64;
+                } else if stopped {
+                    break;
+                } else {
+                    thread::sleep(Duration::from_millis(20));
+                }
+            }
+            file.finish().map_err(|e| format!("{e}"))?;
+            Ok(written)
+        });
+
+        let cues = CueSheet::new(path);
+        Ok(Self { path: path.to_string(), cues, tap, channels, sample_rate, writer })
+    }
+
+    // drains what's left and closes the file, then writes the cue
+    // sheet; says how long it is (and what was dropped, if anything)
+    pub fn stop(self) -> Result<String, String> {
+        self.tap.stopped.store(true, Ordering::Release);
+        let written = self.writer.join().map_err(|_| "The recording's writer panicked".to_string())??;
+
+        let frames = self.channels.max(1) as f32 * self.sample_rate.max(1) as f32;
+        let mut done = format!("Recorded {:.1}s to {}", written as f32 / frames, self.path);
+        let dropped = self.tap.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            done.push_str(&format!(" ({:.2}s dropped; the disk fell behind)", dropped as f32 / frames));
+        }
+
+        // nothing pushed means nothing played; the markers are all at the start
+        let started = match self.tap.started.load(Ordering::Relaxed) {
+            u64::MAX => clock::current(),
+            at => at,
+        };
+        self.cues.write(started).map_err(|e| format!("{}: {e}", self.cues.path()))?;
+        done.push_str(&format!(", cue sheet {}", self.cues.path()));
+        Ok(done)
+    }
+}
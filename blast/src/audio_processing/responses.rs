@@ -0,0 +1,107 @@
+use std::cell::UnsafeCell;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use std::{thread, time::Duration};
+
+use crate::audio_processing::tui;
+
+// responses
+//
+// what the engine has to say about the Commands it applies:
+// something done that took a while (a session loaded in the
+// background, a crossfade finished), or a Command it couldn't
+// carry out or only partly did
+//
+// the audio thread can't print, so it pushes them onto a fixed
+// queue (dropping any that don't fit, and counting them), and a
+// forwarding thread hands them to the REPL in the order they
+// were pushed. each is a fixed message, so pushing one never
+// allocates
+//
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Response {
+    Ok(&'static str),
+    Warn(&'static str),
+    Err(&'static str),
+}
+
+impl Response {
+    // through the log, as the REPL's own messages are
+    pub fn show(&self) {
+        match self {
+            Response::Ok(text) => tui::ok(*text),
+            Response::Warn(text) => tui::warn(text),
+            Response::Err(text) => tui::err(text),
+        }
+    }
+}
+
+// responses the queue holds before it drops them
+pub const QUEUE_LEN: usize = 256;
+
+// from the audio thread to the forwarding thread: one producer,
+// one consumer, as EventQueue
+pub struct ResponseQueue {
+    buf: Vec<UnsafeCell<Option<Response>>>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+unsafe impl Send for ResponseQueue {}
+unsafe impl Sync for ResponseQueue {}
+
+impl ResponseQueue {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            buf: (0..cap.max(2)).map(|_| UnsafeCell::new(None)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn push(&self, response: Response) {
+        let head = self.head.load(Ordering::Relaxed);
+        let next = (head + 1) % self.buf.len();
+        if next == self.tail.load(Ordering::Acquire) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        unsafe {
+            *self.buf[head].get() = Some(response);
+        }
+        self.head.store(next, Ordering::Release);
+    }
+
+    pub fn pop(&self) -> Option<Response> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+        let response = unsafe { (*self.buf[tail].get()).take() };
+        self.tail.store((tail + 1) % self.buf.len(), Ordering::Release);
+        response
+    }
+
+    // responses lost to a full queue
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+// hands each response to send, in order, until send says
+// whoever it went to is gone
+pub fn forward(queue: Arc<ResponseQueue>, mut send: impl FnMut(Response) -> bool + Send + 'static) {
+    thread::spawn(move || loop {
+        while let Some(response) = queue.pop() {
+            if !send(response) {
+                return;
+            }
+        }
+        thread::sleep(Duration::from_millis(1));
+    });
+}
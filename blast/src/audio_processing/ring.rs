@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicI16, AtomicUsize, Ordering};
+
+// ring
+//
+// samples passed from one thread to another without either
+// waiting on the other: one producer, one consumer, and read
+// and write only ever count up
+//
+// the audio thread is on one end or the other (the cpal and JACK
+// callbacks read what it renders ahead; a recording's writer
+// reads what it plays), so neither end allocates or locks
+//
+
+pub struct Ring {
+    buf: Vec<AtomicI16>,
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+impl Ring {
+    pub fn new(len: usize) -> Self {
+        Self {
+            buf: (0..len.max(1)).map(|_| AtomicI16::new(0)).collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+
+    // samples there's room for
+    pub fn free(&self) -> usize {
+        let used = self.write.load(Ordering::Relaxed).wrapping_sub(self.read.load(Ordering::Acquire));
+        self.buf.len() - used
+    }
+
+    // whether anything's been pushed yet
+    #[cfg(any(feature = "cpal", target_os = "linux"))]
+    pub fn started(&self) -> bool {
+        self.write.load(Ordering::Acquire) > 0
+    }
+
+    // as many of samples as there's room for; returns how many
+    pub fn push(&self, samples: &[i16]) -> usize {
+        let n = samples.len().min(self.free());
+        let write = self.write.load(Ordering::Relaxed);
+        for (i, s) in samples[..n].iter().enumerate() {
+            self.buf[(write + i) % self.buf.len()].store(*s, Ordering::Relaxed);
+        }
+        self.write.store(write + n, Ordering::Release);
+        n
+    }
+
+    // as much of out as there's samples for, each as convert
+    // makes it; returns how many
+    pub fn pop_with<T>(&self, out: &mut [T], convert: impl Fn(i16) -> T) -> usize {
+        let read = self.read.load(Ordering::Relaxed);
+        let write = self.write.load(Ordering::Acquire);
+        let n = write.wrapping_sub(read).min(out.len());
+        for (i, s) in out[..n].iter_mut().enumerate() {
+            *s = convert(self.buf[(read + i) % self.buf.len()].load(Ordering::Relaxed));
+        }
+        self.read.store(read + n, Ordering::Release);
+        n
+    }
+}
@@ -0,0 +1,125 @@
+use crate::file_parsing::decode_helpers::ChannelMap;
+
+// channel routing
+//
+// every Voice owns a matrix of gains from each of its Track's
+// channels (sources) to each of the device's channels (outputs);
+// a source can feed any number of outputs, and an output can
+// be fed by any number of sources
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct Routing {
+    gains: Vec<Vec<f32>>, // [source][output]
+}
+
+impl Routing {
+    // a silent matrix, to be filled with connect()
+    pub fn empty(sources: usize, outputs: usize) -> Self {
+        Self { gains: vec![vec![0.0; outputs]; sources] }
+    }
+
+    // what a Track gets when it's loaded:
+    // - mono is sent to the first two outputs (centered)
+    // - otherwise source n goes to output n, wrapping around
+    //   (and scaled down) when there are more sources than outputs
+    pub fn default_for(sources: usize, outputs: usize) -> Self {
+        let mut routing = Self::empty(sources, outputs);
+        if outputs == 0 {
+            return routing;
+        }
+
+        if sources == 1 {
+            for out in 0..outputs.min(2) {
+                routing.connect(0, out, 1.0);
+            }
+        } else {
+            let folds = sources.div_ceil(outputs);
+            for src in 0..sources {
+                routing.connect(src, src % outputs, 1.0 / folds as f32);
+            }
+        }
+
+        routing
+    }
+
+    // default_for, unless the Track's channels say which speakers
+    // they're for (a WAV channel mask): then its ChannelMap down to
+    // the outputs, which folds surrounds down by what they are
+    pub fn for_layout(mask: Option<u32>, sources: usize, outputs: usize) -> Self {
+        match mask {
+            Some(mask) => Self::from_map(&ChannelMap::for_layout(Some(mask), sources, outputs)),
+            None => Self::default_for(sources, outputs),
+        }
+    }
+
+    // just the outputs given (out): mono to each of them, or
+    // source n to the nth, wrapping around (and scaled down)
+    // when there are more sources than outputs
+    pub fn to_outputs(sources: usize, outputs: usize, chans: &[usize]) -> Self {
+        let mut routing = Self::empty(sources, outputs);
+        if chans.is_empty() {
+            return routing;
+        }
+
+        if sources == 1 {
+            for out in chans {
+                routing.connect(0, *out, 1.0);
+            }
+        } else {
+            let folds = sources.div_ceil(chans.len());
+            for src in 0..sources {
+                routing.connect(src, chans[src % chans.len()], 1.0 / folds as f32);
+            }
+        }
+
+        routing
+    }
+
+    pub fn from_map(map: &ChannelMap) -> Self {
+        let mut routing = Self::empty(map.sources(), map.outputs());
+        for src in 0..map.sources() {
+            for out in 0..map.outputs() {
+                routing.connect(src, out, map.gain(src, out));
+            }
+        }
+        routing
+    }
+
+    pub fn sources(&self) -> usize {
+        self.gains.len()
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.gains.first().map_or(0, Vec::len)
+    }
+
+    // returns false if either channel is out of range
+    pub fn connect(&mut self, src: usize, out: usize, gain: f32) -> bool {
+        match self.gains.get_mut(src).and_then(|row| row.get_mut(out)) {
+            Some(g) => {
+                *g = gain;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // adds one source sample into every output it's routed to
+    #[inline]
+    pub fn mix(&self, src: usize, sample: f32, out: &mut [f32]) {
+        for (acc, gain) in out.iter_mut().zip(&self.gains[src]) {
+            *acc += sample * gain;
+        }
+    }
+
+    // like mix, but balanced between the first two outputs
+    // (pan -1 is only the first, 1 only the second)
+    #[inline]
+    pub fn mix_panned(&self, src: usize, sample: f32, pan: f32, out: &mut [f32]) {
+        let balance = [(1.0 - pan).min(1.0), (1.0 + pan).min(1.0)];
+        for (o, (acc, gain)) in out.iter_mut().zip(&self.gains[src]).enumerate() {
+            let b = balance.get(o).copied().unwrap_or(1.0);
+            *acc += sample * gain * b;
+        }
+    }
+}
@@ -1,199 +1,391 @@
-use alsa_sys::*;
-use std::os::unix::io::AsRawFd;
 use libc::{
-    self, 
-    c_int, EAGAIN, EPIPE,
-    ioctl, winsize, STDOUT_FILENO, TIOCGWINSZ,
+    self,
     termios, tcgetattr, tcsetattr, cfmakeraw, TCSANOW,
 };
 use std::{
-    mem,
-    ptr,
     thread,
-    ffi::CString,
-    time::Duration,
-    io::{self, Read, Write},
-    collections::{HashMap, hash_map::Entry},
-    sync::{Arc, Mutex, 
-        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering}
+    time::{Duration, Instant},
+    io::{BufRead, Read},
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, mpsc,
+        atomic::{AtomicBool, Ordering}
     },
 };
 
 use crate::file_parsing::decode_helpers::AudioFile;
 use crate::audio_processing::{
-    engine::{Conductor, Voice},
+    engine::Conductor,
+    tui::{self, Ui},
+    bindings::{self, Bindings, Key, Action},
     commands::{
-        CmdQueue, CmdProcessor, Command, EngineState,
+        CmdQueue, CmdProcessor, EngineState,
+        Command, FadeArgs, AudioArgs, AudioAction,
     },
-    blast_time::{blast_time::clock, sample_rate},
+    blast_time::sample_rate,
+    backend::{self, AudioBackend, Cycle, Latency},
+    devices,
+    events::{EventBus, Stamped},
+    responses::{self, Response, ResponseQueue},
+    reaper::{self, ReapQueue},
+    osc,
+    realtime,
+    socket::{self, Addr},
+    ws,
+    record::Tap,
+    stats::Stats,
 };
 
-pub fn run_blast(tracks: HashMap<String, AudioFile>, sample_rate: u32, num_channels: u32) {
-    // initialize audio engine and engine state
-    let tracks_for_state = tracks.clone().into_values().collect();
-    let mut engine_state = EngineState::new(tracks_for_state, num_channels as usize);
-    let mut conductor = Conductor::prepare(num_channels as usize, tracks);
-
-    sample_rate::set(sample_rate);
+// options from the command line
+#[derive(Default)]
+pub struct RunOptions {
+    // SCHED_FIFO priority for the audio thread, plus mlockall
+    pub realtime: Option<i32>,
+    // core the audio thread is pinned to
+    pub audio_cpu: Option<usize>,
+    // transport ramp length (the engine defaults to 5ms)
+    pub fade_ms: Option<f32>,
+    // Voice slots allocated up front (default VOICE_POOL)
+    pub voices: Option<usize>,
+    // lengthen the period when xruns keep coming
+    pub adaptive: bool,
+    // run before the first prompt, as if by `run <path>`
+    pub script: Option<String>,
+    // from blast.conf's [aliases]
+    pub aliases: Vec<(String, String)>,
+    // from blast.conf's [keys]
+    pub keys: Vec<(String, String)>,
+    // from blast.conf's [notes]
+    pub notes: Vec<(String, String)>,
+    // the PCM to play on (default hw:0,0)
+    pub device: Option<String>,
+    // alsa or cpal (default: the first built in)
+    pub backend: Option<String>,
+    // period and buffer to ask the device for
+    pub latency: Option<Latency>,
+    // UDP port to take OSC messages on
+    pub osc: Option<u16>,
+    // the interface OSC is bound to (default 127.0.0.1, as
+    // anyone who can reach it can run any line)
+    pub osc_host: Option<IpAddr>,
+    // Unix socket or TCP address to take lines on
+    pub listen: Option<Addr>,
+    // TCP address to serve the WebSocket API on
+    pub ws: Option<String>,
+    // the rate to play at, and to resample Tracks to (default 48000)
+    pub rate: Option<u32>,
+    // leave Tracks at their own rates, for Voices to step through
+    // (a faster start, at a cost in quality)
+    pub no_resample: bool,
+    // log beats, Seq steps and Voices ending as they happen
+    pub verbose_events: bool,
+}
 
-    // take over STDIN
-    let marker = Arc::new(Mutex::new(0usize));
-    let buffer = Arc::new(Mutex::new(String::new()));
-    let cursor = Arc::new(Mutex::new(0usize));
-    let repl_chars = ['^', 'X', 'v', '>', 'X', '<', 'Z'];
+// what the REPL thread waits on: each byte typed, and lines
+// from elsewhere (an OSC message, a MIDI note) to run as if
+// they'd been entered
+pub enum Input {
+    Key(u8),
+    // reply, if given, gets what running it logged
+    Line { from: String, line: String, reply: Option<mpsc::Sender<Vec<tui::Line>>> },
+    // from the engine, with --verbose-events
+    Event(Stamped),
+    // the state as JSON, for the WebSocket API
+    State(mpsc::Sender<String>),
+    // what the engine said about a Command it applied
+    Response(Response),
+}
 
-    {
-        let marker = marker.clone();
-        let marker_for_mt = marker.clone();
-        let buffer = buffer.clone();
-        let cursor = cursor.clone();
-        let sr = sample_rate.clone();
+// adaptive period
+//
+// XRUN_LIMIT underruns within XRUN_WINDOW mean the period is too
+// short for the load; with --adaptive the device is reopened with
+// one twice as long (up to Latency::MAX_PERIOD), instead of
+// stuttering on
+//
+pub const XRUN_LIMIT: usize = 3;
+pub const XRUN_WINDOW: Duration = Duration::from_secs(5);
 
-        let mut width = 80usize;
-        let mut height = 40usize;
-        let mut divider = width / 3;
+#[derive(Default)]
+pub struct XrunWatch {
+    since: Option<Instant>, // the first xrun of the window
+    count: usize,
+}
 
-        thread::spawn(move || {
-            loop {
-                let mut m = marker_for_mt.lock().unwrap();
-                *m = (*m + 1) % repl_chars.len();
-                drop(m);
-                thread::sleep(Duration::from_millis(100));
+impl XrunWatch {
+    // true once there have been too many; starts counting over
+    pub fn xrun(&mut self, now: Instant) -> bool {
+        match self.since {
+            Some(since) if now.duration_since(since) <= XRUN_WINDOW => self.count += 1,
+            _ => {
+                self.since = Some(now);
+                self.count = 1;
             }
-        });
-        thread::spawn(move || {
-            let mut last_len = 0;
-            loop {
-                {
-                    // redraw marker + input_text
-                    let m = *marker.lock().unwrap();
-                    let buf = buffer.lock().unwrap();
-                    let curr_len = buf.len();
-                    print!("\r{} {}", repl_chars[m], *buf);
-
-                    if last_len > curr_len {
-                        let diff = last_len - curr_len;
-                        for _ in 0..diff {
-                            print!(" ");
-                        }
+        }
 
-                        print!("\x1b[{}D", diff);
-                    }
-                    last_len = curr_len;
-
-/*
-                    unsafe {
-                        let mut ws: winsize = mem::zeroed();
-                        if ioctl(STDOUT_FILENO, TIOCGWINSZ.into(), &mut ws) == 0 {
-                            width = ws.ws_col as usize;
-                            height = ws.ws_row as usize;
-                            divider = width / 3;
-                        };
-                    }
+        if self.count >= XRUN_LIMIT {
+            *self = Self::default();
+            return true;
+        }
+        false
+    }
+}
 
-                    let mut term = String::with_capacity(width * height);
+// device recovery
+//
+// a device that goes away (a USB interface unplugged, a suspend
+// it can't resume from, the sound server restarting) leaves the
+// Conductor as it was: commands are still applied, nothing is
+// rendered, and the device is reopened every so often (from
+// RETRY_FIRST, doubling up to RETRY_MAX) until it's back, when
+// every Voice carries on from where it stopped
+//
+pub const RETRY_FIRST: Duration = Duration::from_millis(250);
+pub const RETRY_MAX: Duration = Duration::from_secs(4);
 
-                    for j in {0..height} {
-                        for i in {0..width} {
-                            if i == divider {
-                                term.push('|');
-                            } else {
-                                term.push(' ');
-                            }
-                        }
-                    }
-                    print!("\x1b[H{}", term);
-                    
-                    print!("\x1b[H");
-  */
-                    let cur = *cursor.lock().unwrap();
-                    let diff = curr_len - cur;
-                    print!(" \x1b[{}D", diff);
-
-                    std::io::stdout().flush().unwrap();
-                }
-                thread::sleep(Duration::from_millis(15));
-            }
-        });
+pub struct Recovery {
+    pub since: Instant, // when it went
+    next: Instant,      // the next try
+    wait: Duration,     // before the one after that
+}
+
+impl Recovery {
+    pub fn new(now: Instant) -> Self {
+        Self { since: now, next: now + RETRY_FIRST, wait: RETRY_FIRST }
+    }
+
+    // reopens the device asking for latency, if a try is due;
+    // true once it's back
+    pub fn retry(&mut self, backend: &mut dyn AudioBackend, latency: Latency, now: Instant) -> bool {
+        if now < self.next {
+            return false;
+        }
+        if backend.reopen(latency).is_ok() {
+            return true;
+        }
+        self.wait = (self.wait * 2).min(RETRY_MAX);
+        self.next = now + self.wait;
+        false
+    }
+}
+
+pub fn run_blast(tracks: HashMap<String, AudioFile>, sample_rate: u32, num_channels: u32, opts: &RunOptions) {
+    // lock pages before anything else is allocated
+    if opts.realtime.is_some() {
+        match realtime::lock_memory() {
+            Ok(msg) => tui::log(format!("RT: {msg}")),
+            Err(diag) => tui::warn(diag),
+        }
+    }
+
+    // initialize audio engine and engine state
+    let tracks_for_state = tracks.clone().into_values().collect();
+    let mut engine_state = EngineState::new(tracks_for_state, num_channels as usize);
+    let mut conductor = Conductor::prepare(num_channels as usize, tracks);
+    if let Some(voices) = opts.voices {
+        engine_state.set_pool(voices);
+        conductor.reserve(voices);
+    }
+    if let Some(ms) = opts.fade_ms {
+        conductor.apply(Command::Fade(FadeArgs { ms }));
+    }
+
+    sample_rate::set(sample_rate);
+
+    // take over STDIN; everything from here on is printed
+    // through the TUI's log. piped in (cat setlist.txt | blast, or
+    // from a shell script) it's read a line at a time instead, and
+    // the log goes straight to stdout
+    let ui = Ui { master: conductor.master_level(), ..Ui::default() };
+    let interactive = stdin_is_tty();
+
+    if interactive {
+        raw_mode("on");
+        tui::open(ui.clone());
     }
- 
-    raw_mode("on");
 
     // create command queue between command and audio threads
     // and intialize the command processor with engine state
     // (just tracks for now)
     let queue = Arc::new(CmdQueue::new(256));
     let mut cmd_processor = CmdProcessor::new(engine_state);
+    cmd_processor.stats = Arc::clone(&ui.stats);
+    for (name, body) in &opts.aliases {
+        if let Err(error) = cmd_processor.add_alias(name, body) {
+            tui::warn(format!("alias {name} from blast.conf: {error}"));
+        }
+    }
+    for (note, action) in &opts.notes {
+        let mut words = action.split_whitespace();
+        let mapped = match (words.next(), words.next(), words.next()) {
+            (Some(action), voice, None) => cmd_processor.notes.map(note, action, voice),
+            _ => Err("expected trig|start|stop <voice>".to_string()),
+        };
+        if let Err(error) = mapped {
+            tui::warn(format!("note {note} from blast.conf: {error}"));
+        }
+    }
+    let mut bindings = Bindings::default();
+    for (key, action) in &opts.keys {
+        if let Err(error) = bindings.bind(key, action) {
+            tui::warn(format!("key {key} from blast.conf: {error}"));
+        }
+    }
+    // every key typed, and every line sent in, goes through the
+    // inbox, in the order they came
+    let (inbox_tx, inbox) = mpsc::channel::<Input>();
+    if interactive {
+        let keys = inbox_tx.clone();
+        thread::spawn(move || while keys.send(Input::Key(read_char())).is_ok() {});
+    } else {
+        // no keys ever come, so the REPL below only runs lines,
+        // and the end of them is a quit
+        let lines = inbox_tx.clone();
+        thread::spawn(move || read_lines(std::io::stdin().lock(), &lines));
+    }
+    if let Some(port) = opts.osc {
+        let host = opts.osc_host.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        match osc::listen(&SocketAddr::new(host, port).to_string(), inbox_tx.clone()) {
+            Ok(addr) => tui::log(format!("OSC: listening on {addr}")),
+            Err(err) => tui::warn(err),
+        }
+    }
+    if let Some(addr) = &opts.listen {
+        match socket::listen(addr, inbox_tx.clone()) {
+            Ok(addr) => tui::log(format!("Listening for lines on {addr}")),
+            Err(err) => tui::warn(err),
+        }
+    }
+    if let Some(addr) = &opts.ws {
+        match ws::serve(addr, inbox_tx.clone()) {
+            Ok(addr) => tui::log(format!("WebSocket: serving on ws://{addr}")),
+            Err(err) => tui::warn(err),
+        }
+    }
+    // kept until the engine stops, along with its dispatch thread
+    let bus = opts.verbose_events.then(EventBus::new);
+    if let Some(bus) = &bus {
+        conductor.publish(bus.queue());
+        let events = bus.subscribe();
+        let inbox = inbox_tx.clone();
+        thread::spawn(move || while let Ok(stamped) = events.recv() && inbox.send(Input::Event(stamped)).is_ok() {});
+    }
+    let responded = Arc::new(ResponseQueue::new(responses::QUEUE_LEN));
+    conductor.respond_to(Arc::clone(&responded));
+    {
+        let inbox = inbox_tx.clone();
+        responses::forward(responded, move |response| inbox.send(Input::Response(response)).is_ok());
+    }
+    let reaped = Arc::new(ReapQueue::new(reaper::QUEUE_LEN));
+    conductor.reap_with(Arc::clone(&reaped));
+    reaper::reap(reaped);
+    cmd_processor.scripts.inbox = Some(inbox_tx.clone());
+    cmd_processor.notes.inbox = Some(inbox_tx);
+
     // REPL
-    println!("");
     {
-        let buffer = buffer.clone();
-        let cursor = cursor.clone();
+        let Ui { buffer, cursor, status, meters, .. } = ui.clone();
+        let ui = ui.clone();
         let queue = queue.clone();
 
         let mut cmd_history = Vec::<String>::new();
         let mut cmd_idx = cmd_history.len();
+        let script = opts.script.clone();
 
         thread::spawn(move || {
+            if let Some(path) = script {
+                submit(&mut cmd_processor, &queue, format!("run {path}"), None);
+                run_scripted(&mut cmd_processor, &queue);
+            }
+            *status.lock().unwrap() = cmd_processor.engine_state.summary();
+            *meters.lock().unwrap() = cmd_processor.engine_state.meters();
+
             loop {
-                let c = read_char();
-               
+                let c = next_key(&inbox, &mut cmd_processor, &queue, &ui);
+
+                if ui.keys.load(Ordering::Relaxed) && !matches!(c, 3 | 11) {
+                    let key = match c {
+                        b' ' => Some(Key::Space),
+                        27 if next_key(&inbox, &mut cmd_processor, &queue, &ui) == b'[' => match next_key(&inbox, &mut cmd_processor, &queue, &ui) {
+                            b'A' => Some(Key::Up),
+                            b'B' => Some(Key::Down),
+                            b'C' => Some(Key::Right),
+                            b'D' => Some(Key::Left),
+                            _ => None,
+                        },
+                        c if c.is_ascii_graphic() => Some(Key::Char(c as char)),
+                        _ => None,
+                    };
+                    if let Some(action) = key.and_then(|key| bindings.get(key)) {
+                        press(&mut cmd_processor, &queue, &ui, action);
+                    }
+                    continue;
+                }
+
                 match c {
                     b'\n' | b'\r' => {
                         // enter
-                        print!("\n");
-                        let mut buf = buffer.lock().unwrap();
-
-                        let mut cur = cursor.lock().unwrap();
-                        *cur = 0;
-
-                        let mut cmd = buf.clone();
+                        let cmd = std::mem::take(&mut *buffer.lock().unwrap());
+                        *cursor.lock().unwrap() = 0;
                         cmd_history.push(cmd.clone());
                         cmd_idx = cmd_history.len();
 
-                        match cmd_processor.parse(cmd) {
-                            Ok(valid) => {
-                                match queue.try_push(valid) {
-                                    Ok(()) => (),
-                                    Err(error) => {
-                                        buf.clear();
-                                        println!("\nErr: {error}");
-                                    }
-                                }
-                            }
-                            Err(error) => {
-                                buf.clear();
-                                println!("\nErr: {error}");
-                            }
-                        }
-
-                        buf.clear();
+                        execute(&mut cmd_processor, &queue, &ui, cmd, None);
+                    }
+                    11 => {
+                        // CTL + K: keys mode, or back to typing
+                        let keys = !ui.keys.load(Ordering::Relaxed);
+                        ui.keys.store(keys, Ordering::Relaxed);
                     }
                     127 => {
                         // backspace
                         let mut buf = buffer.lock().unwrap();
                         let mut cur = cursor.lock().unwrap();
 
-                        if !buf.is_empty() {
-                            if *cur > 0 {
-                                buf.remove(*cur - 1);
-                                *cur -= 1;
+                        if !buf.is_empty() && *cur > 0 {
+                            buf.remove(*cur - 1);
+                            *cur -= 1;
+                        }
+                    }
+                    9 => {
+                        // tab: finish the word at the cursor, as far
+                        // as it can go; list what it could be otherwise
+                        let mut buf = buffer.lock().unwrap();
+                        let mut cur = cursor.lock().unwrap();
+
+                        let (start, found) = cmd_processor.complete(&buf[..*cur]);
+                        let completion = match found.as_slice() {
+                            [] => None,
+                            [only] => Some(format!("{only} ")),
+                            many => {
+                                let prefix = common_prefix(many);
+                                if prefix.len() > *cur - start {
+                                    Some(prefix.to_string())
+                                } else {
+                                    tui::log(many.join("  "));
+                                    None
+                                }
                             }
+                        };
+                        if let Some(completion) = completion {
+                            buf.replace_range(start..*cur, &completion);
+                            *cur = start + completion.len();
                         }
                     }
                     3 => {
                         // CTL + C
                         raw_mode("off");
+                        tui::close();
                         let mut buf = buffer.lock().unwrap();
                         buf.clear();
-                        println!("\nInterrupted.");
+                        println!("Interrupted.");
                         std::process::exit(130);
                     }
                     27 => {
                         // ESC
-                        let c2 = read_char();
+                        let c2 = next_key(&inbox, &mut cmd_processor, &queue, &ui);
                         if c2 == b'[' {
-                            let c3 = read_char();
+                            let c3 = next_key(&inbox, &mut cmd_processor, &queue, &ui);
                             match c3 {
                                 b'D' => { // left arrow
                                     let mut cur = cursor.lock().unwrap();
@@ -204,28 +396,25 @@ pub fn run_blast(tracks: HashMap<String, AudioFile>, sample_rate: u32, num_chann
                                     let mut cur = cursor.lock().unwrap();
                                     if *cur < buf.len() { *cur += 1; }
                                 }
-                                b'A' => { // up arrow
-                                    if cmd_idx > 0 {
-                                        cmd_idx -= 1;
-                                        let mut buf = buffer.lock().unwrap();
-                                        buf.clear();
-                                        if let Some(prev) = cmd_history.get(cmd_idx) {
-                                            *buf = prev.clone();
-                                        }
+                                b'A' if cmd_idx > 0 => { // up arrow
+                                    cmd_idx -= 1;
+                                    let mut buf = buffer.lock().unwrap();
+                                    buf.clear();
+                                    if let Some(prev) = cmd_history.get(cmd_idx) {
+                                        *buf = prev.clone();
                                     }
                                 }
-                                b'B' => { // down arrow
-                                    if cmd_idx < cmd_history.len() {
-                                        cmd_idx += 1;
-                                        let mut buf = buffer.lock().unwrap();
-                                        buf.clear();
-                                        if cmd_idx < cmd_history.len() {
-                                            if let Some(prev) = cmd_history.get(cmd_idx) {
-                                                *buf = prev.clone();
-                                            }
-                                        }
+                                b'B' if cmd_idx < cmd_history.len() => { // down arrow
+                                    cmd_idx += 1;
+                                    let mut buf = buffer.lock().unwrap();
+                                    buf.clear();
+                                    if let Some(prev) = cmd_history.get(cmd_idx) {
+                                        *buf = prev.clone();
                                     }
                                 }
+                                b'5' | b'6' if next_key(&inbox, &mut cmd_processor, &queue, &ui) == b'~' => { // page up, page down
+                                    ui.scroll_by(if c3 == b'5' { 1 } else { -1 });
+                                }
                                 _ => (),
                             }
                             continue;
@@ -248,148 +437,276 @@ pub fn run_blast(tracks: HashMap<String, AudioFile>, sample_rate: u32, num_chann
     install_panic_hook();
 
     // audio setup and main loop
-    unsafe {
-        // open pcm
-        let mut handle: *mut snd_pcm_t = ptr::null_mut();
-        let dev = CString::new("hw:0,0").unwrap();
-
-        check_code(
-            snd_pcm_open(
-                &mut handle,
-                dev.as_ptr(),
-                SND_PCM_STREAM_PLAYBACK,
-                0,
-            ),
-            "snd_pcm_open",
-        );
-
-        // config hardware
-        let mut hw: *mut snd_pcm_hw_params_t = ptr::null_mut();
-        snd_pcm_hw_params_malloc(&mut hw);
-        snd_pcm_hw_params_any(handle, hw);
-
-        check_code(
-            snd_pcm_hw_params_set_access(handle, hw, SND_PCM_ACCESS_MMAP_INTERLEAVED),
-            "set_access",
-        );
-        check_code(
-            snd_pcm_hw_params_set_format(handle, hw, SND_PCM_FORMAT_S16_LE),
-            "set_format",
-        );
-        check_code(snd_pcm_hw_params_set_channels(handle, hw, num_channels), "set_ channels");
-        check_code(snd_pcm_hw_params_set_rate(handle, hw, sample_rate, 0), "set_rate");
-
-        let mut period_size: snd_pcm_uframes_t = 128;
-        check_code(
-            snd_pcm_hw_params_set_period_size_near(handle, hw, &mut period_size, 0 as *mut i32),
-            "set_period_size",
-        );
-
-        let mut buffer_size: snd_pcm_uframes_t = period_size * 4;
-        check_code(
-            snd_pcm_hw_params_set_buffer_size_near(handle, hw, &mut buffer_size),
-            "set_buffer_size",
-        );
-
-        check_code(snd_pcm_hw_params(handle, hw), "snd_pcm_hw_params");
-        snd_pcm_hw_params_free(hw);
-
-        // config software params
-        let mut sw: *mut snd_pcm_sw_params_t = ptr::null_mut();
-        snd_pcm_sw_params_malloc(&mut sw);
-        snd_pcm_sw_params_current(handle, sw);
-
-        let mut boundary: snd_pcm_uframes_t = 0;
-        snd_pcm_sw_params_get_boundary(sw, &mut boundary);
-        snd_pcm_sw_params_set_stop_threshold(handle, sw, boundary);
-        // start immediately upon write
-        check_code(snd_pcm_sw_params_set_start_threshold(handle, sw, period_size), "set_start_threshold");
-
-        // wake when period is available
-        check_code(
-            snd_pcm_sw_params_set_avail_min(handle, sw, period_size),
-            "set_avail_min",
-        );
-
-        check_code(snd_pcm_sw_params(handle, sw), "snd_pcm_sw_params");
-        snd_pcm_sw_params_free(sw);
-
-        // prepare device
-        check_code(snd_pcm_prepare(handle), "snd_pcm_prepare");
-       
-        loop {
-            if TERM_RECEIVED.load(Ordering::Relaxed) {
-                break;
-            }
+    let channels = num_channels as usize;
+    let mut backend = open_backend(channels, sample_rate, opts).unwrap_or_else(|err| panic!("{err} (devices lists the others)"));
+
+    // only this (the audio) thread is pinned and promoted;
+    // the REPL and redraw threads were spawned before it
+    promote(opts);
+    play(&mut conductor, &mut *backend, &queue, opts.adaptive, &TERM_RECEIVED, &ui.stats);
+
+    ui.buffer.lock().unwrap().clear();
+    tui::close();
+    raw_mode("off");
+}
+
+// opens the device opts name (or the first default that will
+// open), saying what it's playing on
+pub(crate) fn open_backend(channels: usize, sample_rate: u32, opts: &RunOptions) -> Result<Box<dyn AudioBackend>, String> {
+    let latency = opts.latency.unwrap_or(Latency::DEFAULT);
+    let backend = match (opts.backend.as_deref(), opts.device.as_deref()) {
+        (None, None) => backend::open_default(channels, sample_rate, latency)?,
+        (named, device) => {
+            let named = named.or(backend::default_backend()).unwrap_or("none");
+            backend::open(named, device, channels, sample_rate, latency)?
+        }
+    };
+    devices::set_playing(&backend.device());
+    backend::settle(backend.latency());
+    tui::log(format!("Audio: {} on {} ({})", backend.latency().describe(sample_rate), backend.device(), backend.name()));
+    Ok(backend)
+}
 
-            // apply commands from queue
-            while let Some(cmd) = queue.try_pop() {
-                conductor.apply(cmd);
+// pins and promotes the calling (audio) thread, as opts ask
+pub(crate) fn promote(opts: &RunOptions) {
+    if let Some(cpu) = opts.audio_cpu {
+        match realtime::pin_current_thread(&[cpu]) {
+            Ok(()) => tui::log(format!("RT: audio thread pinned to CPU {cpu}")),
+            Err(diag) => tui::warn(diag),
+        }
+    }
+    if let Some(priority) = opts.realtime {
+        match realtime::promote_current_thread(priority) {
+            Ok(prio) => tui::log(format!("RT: audio thread on SCHED_FIFO priority {prio}")),
+            Err(diag) => tui::warn(diag),
+        }
+    }
+}
+
+// the audio loop: applies what's been queued and renders into
+// backend, riding out underruns and a lost device, until stop
+// is set; how it's keeping up goes into stats
+pub(crate) fn play(conductor: &mut Conductor, backend: &mut dyn AudioBackend, queue: &CmdQueue, adaptive: bool, stop: &AtomicBool, stats: &Stats) {
+    let sample_rate = sample_rate::get();
+    let channels = conductor.out_channels().max(1);
+    let mut xruns = XrunWatch::default();
+    // where rec tees the master, while it's recording
+    let mut tap: Option<Arc<Tap>> = None;
+    // while the device is gone, and the latency to reopen it with
+    let mut lost: Option<(Recovery, Latency)> = None;
+
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // apply commands from queue
+        let mut restart = false;
+        let mut relatch = None;
+        while let Some(cmd) = queue.try_pop() {
+            match cmd {
+                Command::Audio(AudioArgs { action: AudioAction::Restart }) => restart = true,
+                Command::Audio(AudioArgs { action: AudioAction::Latency(asked) }) => relatch = Some(asked),
+                Command::Audio(AudioArgs { action: AudioAction::Record(next) }) => tap = next,
+                // ends the same way SIGTERM does, from here rather
+                // than by raising it from the Conductor
+                Command::Quit(_) => return,
+                cmd => conductor.apply(cmd),
             }
+        }
 
-            let mut avail = snd_pcm_avail_update(handle) as i32;
-            if avail == -EPIPE {
-                // underrun
-                snd_pcm_recover(handle, avail, 1);
+        if let Some((recovery, latency)) = &mut lost {
+            // restarting is what's being tried; another latency
+            // is tried from the next time
+            if let Some(asked) = relatch.take() {
+                *latency = asked;
+            }
+            if !recovery.retry(backend, *latency, Instant::now()) {
+                thread::sleep(Duration::from_millis(20));
                 continue;
             }
-            if avail < 0 {
-                snd_pcm_recover(handle, avail, 1);
+            backend::settle(backend.latency());
+            tui::ok(format!("Audio: {} back after {:.1}s", backend.device(), recovery.since.elapsed().as_secs_f32()));
+            stats.recovered();
+            lost = None;
+            restart = false;
+        }
+
+        // the Conductor isn't touched, so everything picks up
+        // where it was once the device is back
+        if restart {
+            let latency = backend.latency();
+            if !reopen(backend, latency) {
+                lost = Some((Recovery::new(Instant::now()), latency));
                 continue;
             }
-            if avail < period_size as i32 {
-                let r = snd_pcm_wait(handle, -1);
-                if r < 0 {
-                    snd_pcm_recover(handle, r, 1);
+            tui::ok("Audio restarted");
+        }
+        // the same, asking for another latency; one the device
+        // won't take leaves it as it was
+        if let Some(asked) = relatch {
+            let before = backend.latency();
+            match backend.reopen(asked) {
+                Ok(()) => {
+                    backend::settle(backend.latency());
+                    tui::ok(format!("Latency: {}", backend.latency().describe(sample_rate)));
+                }
+                Err(err) => {
+                    tui::err(format!("{err}; keeping {}", before.describe(sample_rate)));
+                    if !reopen(backend, before) {
+                        lost = Some((Recovery::new(Instant::now()), before));
+                        continue;
+                    }
                 }
-                continue;
             }
+        }
 
-            // get remaining frames to write
-            let mut remaining = avail as snd_pcm_uframes_t;
+        let cycle = backend.cycle(&mut |out| {
+            let started = Instant::now();
+            conductor.render(out);
+            stats.rendered(started.elapsed(), out.len() / channels, sample_rate);
+            if let Some(tap) = &tap {
+                tap.push(out);
+            }
+        });
+        if cycle == Cycle::Lost {
+            tui::err(format!("Audio: lost {}; playback resumes when it's back", backend.device()));
+            lost = Some((Recovery::new(Instant::now()), backend.latency()));
+            continue;
+        }
+        if cycle == Cycle::Underrun {
+            stats.underrun();
+        }
+        if cycle == Cycle::Underrun
+            && adaptive
+            && backend.period() < Latency::MAX_PERIOD
+            && xruns.xrun(Instant::now()) {
+            let doubled = backend.latency().doubled();
+            if !reopen(backend, doubled) {
+                lost = Some((Recovery::new(Instant::now()), doubled));
+                continue;
+            }
+            tui::warn(format!(
+                "{XRUN_LIMIT} underruns in {}s; latency raised to {}",
+                XRUN_WINDOW.as_secs(),
+                backend.latency().describe(sample_rate),
+            ));
+        }
+    }
+}
 
-            while remaining > 0 {
-                let mut areas_ptr: *const snd_pcm_channel_area_t = ptr::null();
-                let mut offset: snd_pcm_uframes_t = 0;
-                let mut frames: snd_pcm_uframes_t = remaining;
+// the EngineState only changes once the engine has the Command;
+// false if it failed, saying why (and where in the line, or at
+// which line of a script)
+fn submit(cmd_processor: &mut CmdProcessor, queue: &CmdQueue, line: String, at: Option<&str>) -> bool {
+    let located = |error: &dyn std::fmt::Display| match at {
+        Some(at) => format!("{at}: {error}"),
+        None => error.to_string(),
+    };
+    match cmd_processor.prepare(line.clone()) {
+        Ok(None) => true,
+        Ok(Some(plan)) => match cmd_processor.commit(plan, |valid| queue.try_push(valid)) {
+            Ok(()) => true,
+            Err(error) => {
+                tui::err(located(&error));
+                false
+            }
+        },
+        Err(error) => {
+            tui::error(&line, located(&error), error.blame());
+            false
+        }
+    }
+}
 
-                // mmap begin
-                let r = snd_pcm_mmap_begin(handle, &mut areas_ptr, &mut offset, &mut frames);
-                if r == -EAGAIN {
-                    break; // hardware not ready
-                }
-                if r < 0 {
-                    snd_pcm_recover(handle, r, 1);
-                    break;
-                }
+// what every one of words starts with
+fn common_prefix(words: &[String]) -> &str {
+    let first = &words[0];
+    let len = words[1..].iter().fold(first.len(), |len, word| {
+        first.bytes().zip(word.bytes()).take(len).take_while(|(a, b)| a == b).count()
+    });
+    &first[..len]
+}
 
-                // write to DMA buffer
-                conductor.coordinate(areas_ptr, offset, frames);
+// a line as entered (or sent from elsewhere): echoed,
+// acknowledged if it changed the state, and followed by what it
+// queued; the Voice it names is selected
+fn execute(cmd_processor: &mut CmdProcessor, queue: &CmdQueue, ui: &Ui, line: String, from: Option<&str>) {
+    tui::log(format!("{}> {line}", from.unwrap_or_default()));
+    run_line(cmd_processor, queue, ui, line, from);
+}
 
-                let committed = snd_pcm_mmap_commit(handle, offset, frames) as i32;
-                if committed < 0 {
-                    snd_pcm_recover(handle, committed, 1);
-                    break;
-                }
+// execute, without the echo
+fn run_line(cmd_processor: &mut CmdProcessor, queue: &CmdQueue, ui: &Ui, line: String, from: Option<&str>) {
+    ui.scroll.store(0, Ordering::Relaxed);
 
-                remaining -= committed as snd_pcm_uframes_t;
-            }
-            if snd_pcm_state(handle) != SND_PCM_STATE_RUNNING {
-                snd_pcm_start(handle);
-            }
+    // a capture whose bars are up goes first
+    if let Some(result) = cmd_processor.poll_capture() {
+        let sent = result.map_err(|e| e.to_string()).and_then(|plan| {
+            cmd_processor.commit(plan, |seq| queue.try_push(seq))
+        });
+        if let Err(error) = sent {
+            tui::err(error);
         }
     }
 
-    buffer.lock().unwrap().clear();
-    raw_mode("off");
+    // an opened set can be ahead of the one it replaced, but
+    // says which revision it's at itself
+    let rev = cmd_processor.engine_state.rev();
+    let opened = line.split_whitespace().next() == Some("open");
+    if submit(cmd_processor, queue, line.clone(), from) && cmd_processor.engine_state.rev() > rev && !opened {
+        tui::ok(format!("ok (rev {})", cmd_processor.engine_state.rev()));
+    }
+    run_scripted(cmd_processor, queue);
+    let voices = cmd_processor.engine_state.meters();
+    let mut selected = ui.selected.lock().unwrap();
+    *selected = tui::select(&voices, &line, selected.take());
+    *ui.status.lock().unwrap() = cmd_processor.engine_state.summary();
+    *ui.meters.lock().unwrap() = voices;
 }
 
-// check error codes for alsa
-//
-unsafe fn check_code(code: c_int, ctx: &str) {
-    if code < 0 {
-        let msg = std::ffi::CStr::from_ptr(snd_strerror(code));
-        panic!("{ctx}: {}", msg.to_string_lossy());
+// a bound key in keys mode
+fn press(cmd_processor: &mut CmdProcessor, queue: &CmdQueue, ui: &Ui, action: &Action) {
+    let current = ui.selected.lock().unwrap().as_ref().map(|(name, _)| name.clone());
+    if let Action::Select(by) = action {
+        let voices = cmd_processor.engine_state.meters();
+        let names: Vec<&str> = voices.iter().map(|(name, _)| name.as_str()).collect();
+        let next = bindings::step(&names, current.as_deref(), *by);
+        *ui.selected.lock().unwrap() = voices.iter().find(|(name, _)| Some(name.as_str()) == next).cloned();
+        return;
+    }
+    match action.line(current.as_deref(), &cmd_processor.engine_state) {
+        Ok(Some(line)) => execute(cmd_processor, queue, ui, line, None),
+        Ok(None) => (),
+        Err(error) => tui::err(error),
+    }
+}
+
+// the lines run has queued, each error with its line; a long
+// script waits for the engine to take Commands off the queue
+// rather than overflowing it
+fn run_scripted(cmd_processor: &mut CmdProcessor, queue: &CmdQueue) {
+    while let Some((at, line)) = cmd_processor.next_scripted() {
+        while queue.is_full() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        submit(cmd_processor, queue, line, Some(&at));
+    }
+}
+
+// closes the device and opens it again (asking for latency);
+// false if it didn't come back, saying why, for the loop to
+// keep trying (see Recovery)
+fn reopen(backend: &mut dyn AudioBackend, latency: Latency) -> bool {
+    match backend.reopen(latency) {
+        Ok(()) => {
+            backend::settle(backend.latency());
+            true
+        }
+        Err(err) => {
+            tui::err(format!("{err}; retrying until it's back"));
+            false
+        }
     }
 }
 
@@ -399,13 +716,14 @@ static TERM_RECEIVED: AtomicBool = AtomicBool::new(false);
 
 extern "C" fn handle_sigterm(_sig: libc::c_int) {
     TERM_RECEIVED.store(true, Ordering::Relaxed);
+    tui::close();
     raw_mode("off");
 }
 
 fn install_sigterm_handler() {
     unsafe {
         let mut sa: libc::sigaction = std::mem::zeroed();
-        sa.sa_sigaction = handle_sigterm as usize;
+        sa.sa_sigaction = handle_sigterm as *const () as usize;
         sa.sa_flags = 0;
 
         // non-blocking
@@ -418,6 +736,7 @@ fn install_sigterm_handler() {
 
 fn install_panic_hook() {
     std::panic::set_hook(Box::new(|info| {
+        tui::close();
         raw_mode("off");
         eprintln!("\nPanic: {info}");
         std::process::exit(130);
@@ -451,8 +770,53 @@ fn raw_mode(switch: &str) {
     }
 }
 
+// sends each line of reader to inbox until it ends, as if typed;
+// comments (from #) and blank lines are skipped, as in a script.
+// its end is sent as a quit, run after the lines before it, so a
+// piped script (or </dev/null) doesn't leave blast running
+pub(crate) fn read_lines(reader: impl BufRead, inbox: &mpsc::Sender<Input>) {
+    for line in reader.lines() {
+        let Ok(line) = line else { break };
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let sent = Input::Line { from: "stdin".to_string(), line: line.to_string(), reply: None };
+        if inbox.send(sent).is_err() {
+            return;
+        }
+    }
+    let _ = inbox.send(Input::Line { from: "stdin".to_string(), line: "quit".to_string(), reply: None });
+}
+
+fn stdin_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
 fn read_char() -> u8 {
     let mut buf = [0u8; 1];
     std::io::stdin().read_exact(&mut buf).unwrap();
     buf[0]
 }
+
+// the next key typed, running any lines sent in before it
+fn next_key(inbox: &mpsc::Receiver<Input>, cmd_processor: &mut CmdProcessor, queue: &CmdQueue, ui: &Ui) -> u8 {
+    loop {
+        match inbox.recv() {
+            Ok(Input::Key(c)) => return c,
+            Ok(Input::Line { from, line, reply: None }) => execute(cmd_processor, queue, ui, line, Some(&from)),
+            Ok(Input::Event((_, event))) => tui::log(event.describe(&cmd_processor.engine_state)),
+            Ok(Input::Response(response)) => response.show(),
+            Ok(Input::State(reply)) => {
+                let _ = reply.send(ws::state_json(&cmd_processor.engine_state, &ui.master));
+            }
+            Ok(Input::Line { from, line, reply: Some(reply) }) => {
+                tui::log(format!("{from}> {line}"));
+                let ((), logged) = tui::tee(|| run_line(cmd_processor, queue, ui, line, Some(&from)));
+                let _ = reply.send(logged);
+            }
+            // only if the keyboard's thread is gone
+            Err(_) => panic!("Lost the keyboard"),
+        }
+    }
+}
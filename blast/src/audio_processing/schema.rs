@@ -0,0 +1,984 @@
+// command schema
+//
+// a declarative description of every REPL command: its names,
+// arguments (positional or behind flags), their types and help;
+// `blast --dump-commands json` prints it, so editors and other
+// frontends don't have to scrape the parser
+//
+// keep in step with CmdProcessor::prepare/plan (test_schema_matches_parser
+// fails on a command they dispatch that has no spec here); flags parsed
+// by a #[derive(CommandArgs)] struct are described by it instead
+//
+
+use super::commands::{
+    BundleFlags, CaptureFlags, CrossfadeFlags, DelayFlags, FollowFlags, GroupFlags, LfoFlags, LoadFlags,
+    SeekFlags, SeqExportFlags, SeqFlags, StemFlags, TrigFlags, VerbFlags, WaveFlags,
+};
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub usage: &'static str, // {} where each of flags' usage goes, in order
+    pub help: &'static str,
+    pub args: &'static [ArgSpec],
+    pub flags: &'static [(&'static str, &'static [ArgSpec])], // a derived USAGE and HELP each
+}
+
+impl CommandSpec {
+    // usage, with the derived flags filled in
+    pub fn full_usage(&self) -> String {
+        self.flags
+            .iter()
+            .fold(self.usage.to_string(), |usage, (flags, _)| usage.replacen("{}", flags, 1))
+    }
+
+    // args, then every derived flag
+    pub fn all_args(&self) -> impl Iterator<Item = &'static ArgSpec> {
+        self.args.iter().chain(self.flags.iter().flat_map(|(_, args)| args.iter()))
+    }
+}
+
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub flags: &'static [&'static str], // empty if positional
+    pub ty: ArgType,
+    pub required: bool,
+    pub repeats: bool,
+    pub help: &'static str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgType {
+    Track,
+    Voice, // group.voice for Voices inside Groups
+    Group,
+    TempoContext,
+    Bus,
+    Int,
+    Float,
+    Gain,  // linear, or decibels with a dB suffix
+    Tempo, // unit:interval, or c:<name>/g:<name>
+    Path,
+    Text,
+    List(&'static ArgType), // comma-separated
+    Choice(&'static [&'static str]),
+}
+
+impl ArgType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArgType::Track => "track",
+            ArgType::Voice => "voice",
+            ArgType::Group => "group",
+            ArgType::TempoContext => "tempocontext",
+            ArgType::Bus => "bus",
+            ArgType::Int => "int",
+            ArgType::Float => "float",
+            ArgType::Gain => "gain",
+            ArgType::Tempo => "tempo",
+            ArgType::Path => "path",
+            ArgType::Text => "text",
+            ArgType::List(_) => "list",
+            ArgType::Choice(_) => "choice",
+        }
+    }
+}
+
+// shorthands for the table below
+const fn pos(name: &'static str, ty: ArgType, help: &'static str) -> ArgSpec {
+    ArgSpec { name, flags: &[], ty, required: true, repeats: false, help }
+}
+
+const fn opt(name: &'static str, ty: ArgType, help: &'static str) -> ArgSpec {
+    ArgSpec { name, flags: &[], ty, required: false, repeats: false, help }
+}
+
+const fn flag(name: &'static str, flags: &'static [&'static str], ty: ArgType, help: &'static str) -> ArgSpec {
+    ArgSpec { name, flags, ty, required: false, repeats: false, help }
+}
+
+const VOICE: ArgSpec = flag("voice", &["-v", "--voice"], ArgType::Voice, "a Voice");
+const GROUP: ArgSpec = flag("group", &["-g", "--group"], ArgType::Group, "a Group");
+const BUS: ArgSpec = flag("bus", &["-b", "--bus"], ArgType::Bus, "an aux bus");
+const TC: ArgSpec = flag("tempocontext", &["-t", "--tempocontext"], ArgType::TempoContext, "a TempoContext");
+const TARGETS: &[ArgSpec] = &[
+    opt("all", ArgType::Choice(&["all"]), "every TempoContext, Group and ungrouped Voice"),
+    ArgSpec { repeats: true, ..VOICE },
+    ArgSpec { repeats: true, ..GROUP },
+    ArgSpec { repeats: true, ..TC },
+];
+const SWITCH: &[ArgSpec] = &[
+    VOICE,
+    GROUP,
+    opt("state", ArgType::Choice(&["on", "off"]), "on (default) or off"),
+];
+
+pub static COMMANDS: &[CommandSpec] = &[
+    // Voices
+    CommandSpec {
+        name: "load",
+        aliases: &[],
+        usage: "load <track> {}",
+        help: "makes a Voice from a Track",
+        args: &[pos("track", ArgType::Track, "the Track (and new Voice's) name")],
+        flags: &[(LoadFlags::USAGE, LoadFlags::HELP)],
+    },
+    CommandSpec {
+        name: "clone",
+        aliases: &[],
+        usage: "clone <voice> <newname>",
+        help: "makes a Voice on the same Track with the same settings, but no Processes or Effects",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice to copy"),
+            pos("newname", ArgType::Text, "the new Voice's name"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "start",
+        aliases: &[],
+        usage: "start -v|-g|-t <name>... | start all",
+        help: "plays from the beginning (or adds an instance, if polyphonic)",
+        args: TARGETS,
+        flags: &[],
+    },
+    CommandSpec {
+        name: "trig",
+        aliases: &[],
+        usage: "trig <voice> {}",
+        help: "fires a Voice from the top, now or on the next beat, without stopping it first",
+        args: &[pos("voice", ArgType::Voice, "the Voice")],
+        flags: &[(TrigFlags::USAGE, TrigFlags::HELP)],
+    },
+    CommandSpec {
+        name: "pause",
+        aliases: &[],
+        usage: "pause -v|-g|-t <name>... | pause all",
+        help: "holds playback where it is",
+        args: TARGETS,
+        flags: &[],
+    },
+    CommandSpec {
+        name: "resume",
+        aliases: &[],
+        usage: "resume -v|-g|-t <name>... | resume all",
+        help: "continues paused playback",
+        args: TARGETS,
+        flags: &[],
+    },
+    CommandSpec {
+        name: "stop",
+        aliases: &[],
+        usage: "stop -v|-g|-t <name>... | stop all",
+        help: "stops playback and rewinds",
+        args: TARGETS,
+        flags: &[],
+    },
+    CommandSpec {
+        name: "unload",
+        aliases: &[],
+        usage: "unload <voice>",
+        help: "removes a Voice",
+        args: &[pos("voice", ArgType::Voice, "the Voice")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "velocity",
+        aliases: &[],
+        usage: "velocity <voice> <value>",
+        help: "playback speed; negative plays in reverse",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("value", ArgType::Float, "1 is normal speed"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "stretch",
+        aliases: &[],
+        usage: "stretch <voice> <factor>",
+        help: "changes a Voice's length without its pitch (2 is twice as long, 1 is off)",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("factor", ArgType::Float, "how much longer it plays"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "pitch",
+        aliases: &[],
+        usage: "pitch <voice> <semitones>",
+        help: "changes a Voice's pitch without its length (0 is off)",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("semitones", ArgType::Float, "up or down, within four octaves"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "keymap",
+        aliases: &[],
+        usage: "keymap <voice> root [<note>] [span <semitones>] [-m varispeed|pitch] | keymap <voice> off",
+        help: "makes a Voice playable by note, repitched relative to a root",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("root", ArgType::Text, "root <note>: the note that plays it as it is (C4 or 60; left off, the file's own), or off"),
+            flag("span", &["span"], ArgType::Int, "semitones either side of the root that play (default 24, at most 48)"),
+            flag("mode", &["-m", "--mode"], ArgType::Choice(&["varispeed", "pitch"]), "varispeed (default) changes the length too; pitch doesn't"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "note",
+        aliases: &[],
+        usage: "note <voice> <note>",
+        help: "plays a keymapped Voice from the top at a note",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("note", ArgType::Text, "a name (C4, F#3, Bb2) or MIDI number"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "gain",
+        aliases: &[],
+        usage: "gain master|-v <voice>|-g <group>|-b <bus> <value>",
+        help: "sets the level of the master bus, a Voice, a Group or an aux bus's return",
+        args: &[
+            flag("master", &["master"], ArgType::Text, "the master bus"),
+            VOICE,
+            GROUP,
+            BUS,
+            pos("value", ArgType::Gain, "linear (>= 0), or decibels, e.g. -6dB"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "pan",
+        aliases: &[],
+        usage: "pan <voice> <value>",
+        help: "balances a Voice between the first two outputs",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("value", ArgType::Float, "-1 (first) to 1 (second)"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "offset",
+        aliases: &[],
+        usage: "offset <voice> <samples>|<ms>ms",
+        help: "moves a Voice's Seq steps earlier (negative) or later against its tempo",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("offset", ArgType::Text, "samples, or milliseconds with ms (e.g. -5ms)"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "trim",
+        aliases: &[],
+        usage: "trim <voice> <start> <end> | off",
+        help: "plays only part of a Voice's Track; start, loop and retrigger stay inside it",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("start", ArgType::Text, "frames, <n>ms, or <n>b (beats of the Voice's tempo); off plays the whole Track"),
+            opt("end", ArgType::Text, "as start; past the Track's end is its end"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "seek",
+        aliases: &[],
+        usage: "seek <voice> <position> {}",
+        help: "jumps a Voice's playhead, within its trim",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("position", ArgType::Text, "frames, <n>ms, or <n>b (beats of the Voice's tempo)"),
+        ],
+        flags: &[(SeekFlags::USAGE, SeekFlags::HELP)],
+    },
+    CommandSpec {
+        name: "route",
+        aliases: &[],
+        usage: "route -v <voice> <src>:<out>[:<gain>]... | default",
+        help: "replaces a Voice's channel routing; channels count from 0",
+        args: &[
+            ArgSpec { required: true, ..VOICE },
+            ArgSpec {
+                name: "connection",
+                flags: &[],
+                ty: ArgType::Text,
+                required: true,
+                repeats: true,
+                help: "<src>:<out>[:<gain>], or 'default'",
+            },
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "out",
+        aliases: &[],
+        usage: "out <voice> <ch>[,<ch>...]|master",
+        help: "plays a Voice on just those outputs, past the master's Effects and gain (master puts it back)",
+        args: &[
+            pos("voice", ArgType::Voice, "a Voice not in a Group"),
+            pos("channels", ArgType::Text, "outputs counted from 0, comma-separated, or master"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "loop",
+        aliases: &[],
+        usage: "loop <voice> [on|off|<count>]",
+        help: "wraps playback between the Track's loop points",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            opt("mode", ArgType::Text, "on (default), off, or how many times to wrap"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "poly",
+        aliases: &[],
+        usage: "poly <voice> <max|off> [oldest|never]",
+        help: "lets restarts layer instead of cutting off",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("max", ArgType::Int, "instances sounding at once, or off"),
+            opt("steal", ArgType::Choice(&["oldest", "never"]), "what a restart does when all are sounding"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "mute",
+        aliases: &[],
+        usage: "mute -v <voice>|-g <group> [on|off]",
+        help: "silences a Voice or Group without stopping it",
+        args: SWITCH,
+        flags: &[],
+    },
+    CommandSpec {
+        name: "solo",
+        aliases: &[],
+        usage: "solo -v <voice>|-g <group> [on|off]",
+        help: "while anything is soloed, only soloed parts are heard",
+        args: SWITCH,
+        flags: &[],
+    },
+    CommandSpec {
+        name: "fade",
+        aliases: &[],
+        usage: "fade <ms>",
+        help: "ramp length for start/stop/pause/resume/unload",
+        args: &[pos("ms", ArgType::Float, "milliseconds")],
+        flags: &[],
+    },
+    // Groups
+    CommandSpec {
+        name: "group",
+        aliases: &[],
+        usage: "group <name> {}",
+        help: "moves Voices into a Group that plays them together",
+        args: &[pos("name", ArgType::Text, "the new Group's name")],
+        flags: &[(GroupFlags::USAGE, GroupFlags::HELP)],
+    },
+    CommandSpec {
+        name: "import-stems",
+        aliases: &[],
+        usage: "import-stems <dir> as <group> {}",
+        help: "loads every file in a directory as a Voice of one new Group, so multitrack stems play in lockstep",
+        args: &[
+            pos("dir", ArgType::Path, "the stems (.wav and .aif)"),
+            pos("as", ArgType::Choice(&["as"]), "the word as"),
+            pos("group", ArgType::Text, "the new Group's name"),
+        ],
+        flags: &[(StemFlags::USAGE, StemFlags::HELP)],
+    },
+    CommandSpec {
+        name: "bounce",
+        aliases: &[],
+        usage: "bounce <group> <name> [<length>]",
+        help: "renders a Group through its Processes and Effects into a new Track, from a copy of the session",
+        args: &[
+            pos("group", ArgType::Group, "the Group"),
+            pos("name", ArgType::Text, "the new Track's name"),
+            opt("length", ArgType::Text, "frames, <n>ms or <n>b (beats of the Group's tempo); until it's played out (up to 60s) if not given"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "tc",
+        aliases: &["tempocon"],
+        usage: "tc <name> <unit>:<interval>",
+        help: "makes a TempoContext that Voices, Groups and Processes can share",
+        args: &[
+            pos("name", ArgType::Text, "the new TempoContext's name"),
+            pos("tempo", ArgType::Tempo, "s|m|b:<interval>"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "tempo",
+        aliases: &[],
+        usage: "tempo ramp -t <tempocontext> | -g <group> <tempo> <n>b|<n>s|<n>ms | tempo -v|-g|-t <name> <unit>:<interval>",
+        help: "sets a Voice's, Group's or TempoContext's beat, or glides a TempoContext's or Group's to a new length over beats or seconds",
+        args: &[
+            opt("action", ArgType::Choice(&["ramp"]), "ramp, or leave out to set the tempo straight away"),
+            flag("voice", &["-v", "--voice"], ArgType::Voice, "the Voice to set (one with its own tempo)"),
+            flag("tempocontext", &["-t", "--tempocontext"], ArgType::TempoContext, "the TempoContext"),
+            flag("group", &["-g", "--group"], ArgType::Group, "the Group (one with its own tempo)"),
+            pos("tempo", ArgType::Tempo, "s|m|b:<interval> (or c:<tempocontext> to ramp to)"),
+            opt("duration", ArgType::Text, "for ramp: <n>b (beats), <n>s or <n>ms"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "meter",
+        aliases: &[],
+        usage: "meter <beats>[/<note>] [<tempo>] | meter <beats>[/<note>] -t <tempocontext> | -g <group>",
+        help: "sets how many beats make a bar, for the global clock (with its beat) or a TempoContext or Group",
+        args: &[
+            pos("beats", ArgType::Text, "beats per bar, optionally over a note (7/8)"),
+            opt("tempo", ArgType::Tempo, "the clock's beat, s|m|b:<interval> or c:<tempocontext> (default b:120)"),
+            flag("tempocontext", &["-t", "--tempocontext"], ArgType::TempoContext, "the TempoContext to set"),
+            flag("group", &["-g", "--group"], ArgType::Group, "the Group to set (one with its own tempo)"),
+        ],
+        flags: &[],
+    },
+    // Processes
+    CommandSpec {
+        name: "seq",
+        aliases: &[],
+        usage: "seq <voice> {} | seq export <voice> <file.mid> {} | seq import <voice> <file.mid> [-k <note>] [<seq flags>]",
+        help: "retriggers a Voice on a pattern of steps; export writes one as a MIDI file, import makes one from a MIDI clip",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice, or export/import"),
+            flag("key", &["-k", "--key"], ArgType::Text, "import: the only note read"),
+        ],
+        flags: &[(SeqFlags::USAGE, SeqFlags::HELP), (SeqExportFlags::USAGE, SeqExportFlags::HELP)],
+    },
+    CommandSpec {
+        name: "capture",
+        aliases: &[],
+        usage: "capture <voice> <bars> {} | capture end|cancel",
+        help: "records starts of a Voice for some bars, then plays them back as a quantized Seq",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice, or end/cancel"),
+            opt("bars", ArgType::Int, "how long to record"),
+        ],
+        flags: &[(CaptureFlags::USAGE, CaptureFlags::HELP)],
+    },
+    CommandSpec {
+        name: "lfo",
+        aliases: &[],
+        usage: "lfo <voice> gain|velocity|pan {}",
+        help: "modulates a Voice's gain, velocity or pan, one cycle per unit of tempo",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("target", ArgType::Choice(&["gain", "velocity", "pan"]), "what to modulate"),
+        ],
+        flags: &[(LfoFlags::USAGE, LfoFlags::HELP)],
+    },
+    CommandSpec {
+        name: "delay",
+        aliases: &[],
+        usage: "delay -v|-g|-b <name> <time>|off {}",
+        help: "echoes a Voice, Group or aux bus, in time with its tempo if given as a note division",
+        args: &[
+            VOICE,
+            GROUP,
+            BUS,
+            pos("time", ArgType::Text, "s:<samples>, m:<ms>, a division of the owner's tempo (1/8), or off"),
+        ],
+        flags: &[(DelayFlags::USAGE, DelayFlags::HELP)],
+    },
+    CommandSpec {
+        name: "verb",
+        aliases: &["reverb"],
+        usage: "verb master|-g <group>|-v <voice>|-b <bus> [off] {}",
+        help: "puts a reverb on the master bus, a Group, a Voice or an aux bus",
+        args: &[
+            opt("master", ArgType::Choice(&["master"]), "the master bus, instead of -g/-v/-b"),
+            GROUP,
+            VOICE,
+            BUS,
+            opt("off", ArgType::Choice(&["off"]), "takes it off"),
+        ],
+        flags: &[(VerbFlags::USAGE, VerbFlags::HELP)],
+    },
+    CommandSpec {
+        name: "follow",
+        aliases: &[],
+        usage: "follow <voice> <tempocontext> {}",
+        help: "steers a TempoContext to the onsets heard in a Voice",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice listened to"),
+            pos("tempocontext", ArgType::TempoContext, "the TempoContext steered"),
+        ],
+        flags: &[(FollowFlags::USAGE, FollowFlags::HELP)],
+    },
+    CommandSpec {
+        name: "proc",
+        aliases: &[],
+        usage: "proc ls [<voice>] | proc rm <voice>.<proc> | proc set <voice>.<proc> <param> <value>",
+        help: "lists, removes or adjusts Processes by name; a Seq sets period, steps, levels, swing or seed, an LFO depth or wave",
+        args: &[
+            pos("action", ArgType::Choice(&["ls", "rm", "set"]), "what to do"),
+            opt("process", ArgType::Text, "<voice>.<proc>, or a Voice for ls"),
+            opt("param", ArgType::Choice(&["period", "steps", "levels", "swing", "seed", "depth", "wave"]), "what to set"),
+            opt("value", ArgType::Text, "as the Process's own flag takes it"),
+        ],
+        flags: &[],
+    },
+    // Program
+    CommandSpec {
+        name: "cue",
+        aliases: &[],
+        usage: "cue [label]",
+        help: "marks the cue sheet of the recording by hand",
+        args: &[
+            opt("label", ArgType::Text, "what the marker is called"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "bundle",
+        aliases: &[],
+        usage: "bundle export <file> {}",
+        help: "packs the session's Tracks (and any other files) into a tar",
+        args: &[
+            pos("action", ArgType::Choice(&["export"]), "import is 'blast bundle import' from the shell"),
+            pos("file", ArgType::Path, "the archive"),
+        ],
+        flags: &[(BundleFlags::USAGE, BundleFlags::HELP)],
+    },
+    CommandSpec {
+        name: "status",
+        aliases: &[],
+        usage: "status [<name>]",
+        help: "shows a Voice (with its Processes, Seq patterns as steps: x..x..x.), Group or TempoContext, or with no name the clock's bar and beat",
+        args: &[opt("name", ArgType::Text, "a Voice, Group or TempoContext")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "bus",
+        aliases: &[],
+        usage: "bus <name>",
+        help: "makes an aux bus: Voices send to it, its Effects run once for all of them, and it returns to the master",
+        args: &[pos("name", ArgType::Text, "the new bus's name")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "send",
+        aliases: &[],
+        usage: "send <voice> <bus> <amount>|off",
+        help: "sends some of a Voice, after its Effects and gain, to an aux bus",
+        args: &[
+            pos("voice", ArgType::Voice, "the Voice"),
+            pos("bus", ArgType::Bus, "the aux bus"),
+            pos("amount", ArgType::Gain, "linear (>= 0), decibels (e.g. -12dB), or off"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "ls",
+        aliases: &[],
+        usage: "ls [tracks|voices|groups|tc|buses]",
+        help: "lists what exists, by index, with tempi and whether each is playing, paused or stopped",
+        args: &[opt("kind", ArgType::Choice(&["tracks", "voices", "groups", "tc", "buses"]), "just one kind (default all)")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "stats",
+        aliases: &[],
+        usage: "stats [reset]",
+        help: "shows underruns, device recoveries and how much of each period rendering takes, or starts counting over",
+        args: &[opt("action", ArgType::Choice(&["reset"]), "start counting over")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "devices",
+        aliases: &[],
+        usage: "devices",
+        help: "lists the audio devices that can play, for --device or device in blast.conf (* is the one playing)",
+        args: &[],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "wave",
+        aliases: &[],
+        usage: "wave <track> {}",
+        help: "draws a Track's peaks across the terminal, with its length and ^ where a Voice of it is playing",
+        args: &[pos("track", ArgType::Track, "the Track")],
+        flags: &[(WaveFlags::USAGE, WaveFlags::HELP)],
+    },
+    CommandSpec {
+        name: "info",
+        aliases: &[],
+        usage: "info <track>",
+        help: "shows what a Track's file is (format, encoding, bit depth, rate, length, loop, root and tags) and what it decoded to",
+        args: &[pos("track", ArgType::Track, "the Track")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "history",
+        aliases: &[],
+        usage: "history [<count>]",
+        help: "lists the last accepted lines (default 20), each with its revision",
+        args: &[opt("count", ArgType::Int, "how many")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "undo",
+        aliases: &[],
+        usage: "undo [<count>]",
+        help: "takes back the last lines (default 1) that change the session, by their inverses where they have them, otherwise by rebuilding without them and taking over from where the engine is",
+        args: &[opt("count", ArgType::Int, "how many")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "redo",
+        aliases: &[],
+        usage: "redo",
+        help: "runs the last line undone again, until something new is entered",
+        args: &[],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "revert",
+        aliases: &[],
+        usage: "revert <rev>",
+        help: "rebuilds everything as it was at a revision (0 for nothing) and fades over to it",
+        args: &[pos("rev", ArgType::Int, "a revision from history")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "rename",
+        aliases: &[],
+        usage: "rename -v|-g|-t <old> <new>",
+        help: "renames a Voice (group.voice stays in its Group), Group or TempoContext",
+        args: &[
+            pos("type", ArgType::Choice(&["-v", "-g", "-t"]), "what's renamed"),
+            pos("old", ArgType::Text, "its name now"),
+            pos("new", ArgType::Text, "its new name"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "run",
+        aliases: &[],
+        usage: "run <path>",
+        help: "runs a file of commands, one per line (# for comments), in order as if typed; each line that fails is reported with its number and the rest still run",
+        args: &[pos("path", ArgType::Path, "the script")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "alias",
+        aliases: &[],
+        usage: "alias [<name> [= <line>[; <line>...]]] | alias rm <name>",
+        help: "names a sequence of commands, run as one with $1..$9 (and $@) filled in from its words; shows them without a body",
+        args: &[
+            opt("name", ArgType::Text, "the alias, or rm to remove one"),
+            opt("lines", ArgType::Text, "= then commands separated by ;"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "save",
+        aliases: &[],
+        usage: "save <file>",
+        help: "writes the session's history as a script that rebuilds it, without transport, so it opens with nothing playing",
+        args: &[pos("file", ArgType::Path, "the set to write")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "open",
+        aliases: &[],
+        usage: "open <file>",
+        help: "replaces the session with the one a saved set (or any script) builds, fading over to it",
+        args: &[pos("file", ArgType::Path, "the set to open")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "session",
+        aliases: &[],
+        usage: "session load <script> | session crossfade next <n>b|<n>ms {} | session drop",
+        help: "prepares a second session from a script and crossfades the master over to it",
+        args: &[
+            pos("action", ArgType::Choice(&["load", "crossfade", "drop"]), "what to do with the next session"),
+            opt("script", ArgType::Path, "commands to build it from (load), or 'next' (crossfade)"),
+            opt("length", ArgType::Text, "the crossfade, in beats (8b) or milliseconds (500ms)"),
+        ],
+        flags: &[(CrossfadeFlags::USAGE, CrossfadeFlags::HELP)],
+    },
+    CommandSpec {
+        name: "audio",
+        aliases: &[],
+        usage: "audio restart",
+        help: "closes and reopens the audio device; everything carries on where it was",
+        args: &[pos("action", ArgType::Choice(&["restart"]), "what to do with the device")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "latency",
+        aliases: &[],
+        usage: "latency [<period> [<buffer>]]",
+        help: "shows the device's period and buffer, or reopens it asking for others",
+        args: &[
+            opt("period", ArgType::Int, "frames rendered a cycle (16-4096)"),
+            opt("buffer", ArgType::Int, "frames buffered, at least two periods (default four)"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "script",
+        aliases: &[],
+        usage: "script [ls] | script run <file> [<name>] | script stop <name>|all",
+        help: "runs Rhai scripts for generative control, each on a thread of its own, issuing lines as if typed",
+        args: &[
+            pos("action", ArgType::Choice(&["ls", "run", "stop"]), "what to do (default ls)"),
+            opt("file", ArgType::Path, "the .rhai script to run (run)"),
+            opt("name", ArgType::Text, "what to call it (run; default the file's name), or the one to stop, or all (stop)"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "rec",
+        aliases: &[],
+        usage: "rec start <file> | rec stop",
+        help: "records the master as it plays into a WAV, without holding playback up, and a CUE sheet of its transport, pattern and session changes",
+        args: &[
+            pos("action", ArgType::Choice(&["start", "stop"]), "what to do with the recording"),
+            opt("file", ArgType::Path, "the WAV to write (start)"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "midi",
+        aliases: &[],
+        usage: "midi clock <device> -t <tempocontext> | midi clock off | midi notes <device>|off | midi map [<note> trig|start|stop <voice> | <note> off] | midi keys <voice>|off",
+        help: "follows a MIDI clock with a TempoContext, or plays Voices from the notes on a raw MIDI input",
+        args: &[
+            pos("action", ArgType::Choice(&["clock", "notes", "map", "keys"]), "clock, notes, map to list or change what notes play, or keys to play a keymapped Voice with the rest"),
+            opt("device", ArgType::Text, "a raw MIDI input like hw:1,0,0, or off (clock, notes)"),
+            flag("tempocontext", &["-t", "--tempocontext"], ArgType::TempoContext, "the TempoContext the clock drives"),
+            opt("note", ArgType::Text, "a name (C2) or number (36) to map (map)"),
+            opt("play", ArgType::Choice(&["trig", "start", "stop", "off"]), "what the note does to the Voice, or off to unmap it (map)"),
+            opt("voice", ArgType::Voice, "the Voice it plays (map), or the keymapped Voice, or off (keys)"),
+        ],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "help",
+        aliases: &[],
+        usage: "help [<command>]",
+        help: "lists every command, or shows one's usage, arguments and examples",
+        args: &[opt("command", ArgType::Text, "the command to explain")],
+        flags: &[],
+    },
+    CommandSpec {
+        name: "quit",
+        aliases: &["q"],
+        usage: "quit",
+        help: "exits (finishing any recording and its cue sheet)",
+        args: &[],
+        flags: &[],
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name == name || spec.aliases.contains(&name))
+}
+
+// lines worth copying, for help <command>
+static EXAMPLES: &[(&str, &[&str])] = &[
+    ("load", &["load kick", "load pad -t b:90 --autoloop", "load hat -t c:beat", "load vox --normalize -23"]),
+    ("clone", &["clone kick kick2"]),
+    ("start", &["start -v kick", "start -t beat -g drums", "start all"]),
+    ("trig", &["trig snare", "trig snare -b"]),
+    ("keymap", &["keymap pad root C3 span 12 -m pitch"]),
+    ("note", &["note pad G3"]),
+    ("gain", &["gain master 0.8", "gain -v kick -6dB", "gain -g drums 0.5", "gain -b room -3dB"]),
+    ("trim", &["trim kick 4 12", "trim pad 0ms 1ms", "trim kick off"]),
+    ("seek", &["seek pad 1ms", "seek kick 8"]),
+    ("route", &["route -v pad 0:0 0:1:0.5"]),
+    ("out", &["out hat 0", "out hat master"]),
+    ("poly", &["poly snare 4 oldest"]),
+    ("group", &["group drums -v kick,snare,hat", "group drums -v kick,snare -t c:beat"]),
+    ("import-stems", &["import-stems stems/song as song -t b:120 --pad"]),
+    ("bounce", &["bounce drums drumloop 8b", "bounce drums fill 2000ms"]),
+    ("tc", &["tc beat b:120", "tc slow m:750"]),
+    ("tempo", &["tempo -v kick b:90", "tempo ramp -t beat b:140 8b", "tempo ramp -g drums s:12000 2s"]),
+    ("meter", &["meter 7/8 b:140", "meter 3 -t beat"]),
+    ("seq", &[
+        "seq kick -t c:beat -p 16 -s 0,4,8,12",
+        "seq hat -t c:beat -p 8 -s 0,1,2,3,4,5,6,7 -c a:60 --seed 7",
+        "seq snare -n fill -p 8 -s 2,6 -v 1,0.5 -w 30",
+    ]),
+    ("capture", &["capture kick 2 -t c:beat -q 4", "capture end"]),
+    ("lfo", &["lfo pad gain -w sine -t b:30 -d 0.5", "lfo pad pan -w tri -t c:beat"]),
+    ("delay", &["delay -v snare 1/8 -f 0.4 -m 0.3", "delay -g drums 250ms", "delay -v snare off"]),
+    ("verb", &["verb master -r 0.8 -d 0.4 -m 0.2", "verb -g drums off", "verb -b room -r 0.9 -m 1"]),
+    ("bus", &["bus room"]),
+    ("send", &["send kick room -12dB", "send snare room off"]),
+    ("follow", &["follow kick beat --bpm 80-160"]),
+    ("proc", &["proc ls kick", "proc rm kick.fill", "proc set kick.seq steps 0,3,6"]),
+    ("cue", &["cue chorus", "cue"]),
+    ("wave", &["wave kick", "wave pad -r 8"]),
+    ("info", &["info kick"]),
+    ("rename", &["rename -v kick low", "rename -g drums kit"]),
+    ("alias", &["alias drop = stop -g $1; start -g $1", "alias rm drop"]),
+    ("session", &["session load next.txt", "session crossfade next 8b -t b:120"]),
+    ("latency", &["latency", "latency 256", "latency 64 128"]),
+    ("stats", &["stats", "stats reset"]),
+    ("rec", &["rec start take1.wav", "rec stop"]),
+    ("script", &["script run hats.rhai", "script run gen.rhai drums", "script stop drums", "script stop all", "script"]),
+    ("midi", &["midi clock hw:1,0,0 -t beat", "midi clock off", "midi map C2 trig kick", "midi keys pad", "midi notes hw:2,0,0", "midi map"]),
+];
+
+// help: every command and what it does
+// help <command>: its usage, arguments and examples
+pub fn help(name: Option<&str>) -> Option<String> {
+    let Some(name) = name else {
+        let mut lines: Vec<String> = COMMANDS.iter().map(|spec| format!("{:<14}{}", spec.name, spec.help)).collect();
+        lines.push("help <command> shows its usage and examples".to_string());
+        return Some(lines.join("\n"));
+    };
+    let spec = find(name)?;
+
+    let mut lines = vec![format!("usage: {}", spec.full_usage()), spec.help.to_string()];
+    if !spec.aliases.is_empty() {
+        lines.push(format!("also: {}", spec.aliases.join(", ")));
+    }
+    if spec.all_args().next().is_some() {
+        lines.push(String::new());
+        for arg in spec.all_args() {
+            let names = match arg.flags.is_empty() {
+                true => format!("<{}>", arg.name),
+                false => arg.flags.join(", "),
+            };
+            let mut notes = vec![arg.ty.name()];
+            if arg.required {
+                notes.push("required");
+            }
+            if arg.repeats {
+                notes.push("repeats");
+            }
+            lines.push(format!("  {names:<22}{}  ({})", arg.help, notes.join(", ")));
+        }
+    }
+    let examples = examples(spec.name);
+    if !examples.is_empty() {
+        lines.push(String::new());
+        lines.extend(examples.iter().map(|e| format!("  {e}")));
+    }
+    Some(lines.join("\n"))
+}
+
+pub fn examples(name: &str) -> &'static [&'static str] {
+    EXAMPLES
+        .iter()
+        .find(|(cmd, _)| *cmd == name)
+        .map_or(&[], |(_, examples)| examples)
+}
+
+// the command (or alias) closest to a mistyped one,
+// if any is close enough to be what was meant
+pub fn suggest<'a>(typed: &str, names: impl Iterator<Item = &'a str>) -> Option<String> {
+    let most = (typed.chars().count() / 3).clamp(1, 2);
+    COMMANDS
+        .iter()
+        .flat_map(|spec| std::iter::once(spec.name).chain(spec.aliases.iter().copied()))
+        .chain(names)
+        .map(|name| (edit_distance(typed, name), name))
+        .filter(|(d, _)| *d <= most)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, name)| name.to_string())
+}
+
+// Levenshtein, over chars, with a swap of two
+// neighbours as one edit (the usual typo)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            d[i][j] = (d[i - 1][j - 1] + cost).min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// JSON, by hand
+//
+pub fn to_json() -> String {
+    let commands: Vec<String> = COMMANDS.iter().map(command_json).collect();
+    format!("{{\"commands\":[{}]}}", commands.join(","))
+}
+
+fn command_json(spec: &CommandSpec) -> String {
+    let args: Vec<String> = spec.all_args().map(arg_json).collect();
+    format!(
+        "{{\"name\":{},\"aliases\":{},\"usage\":{},\"help\":{},\"args\":[{}],\"examples\":{}}}",
+        string(spec.name),
+        strings(spec.aliases),
+        string(&spec.full_usage()),
+        string(spec.help),
+        args.join(","),
+        strings(examples(spec.name)),
+    )
+}
+
+fn arg_json(arg: &ArgSpec) -> String {
+    format!(
+        "{{\"name\":{},\"flags\":{},\"type\":{},\"required\":{},\"repeats\":{},\"help\":{}}}",
+        string(arg.name),
+        strings(arg.flags),
+        type_json(&arg.ty),
+        arg.required,
+        arg.repeats,
+        string(arg.help),
+    )
+}
+
+fn type_json(ty: &ArgType) -> String {
+    match ty {
+        ArgType::List(of) => format!("{{\"name\":\"list\",\"of\":{}}}", type_json(of)),
+        ArgType::Choice(choices) => format!("{{\"name\":\"choice\",\"choices\":{}}}", strings(choices)),
+        _ => format!("{{\"name\":{}}}", string(ty.name())),
+    }
+}
+
+fn strings(items: &[&str]) -> String {
+    let items: Vec<String> = items.iter().map(|s| string(s)).collect();
+    format!("[{}]", items.join(","))
+}
+
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    thread::JoinHandle,
+};
+
+use crate::audio_processing::runtime::Input;
+
+// scripting
+//
+// generative control in Rhai (built with --features rhai): a
+// script runs on a thread of its own, and every line it issues
+// goes through the REPL's inbox, to be checked, echoed and undone
+// like anything typed; it reads the clock through the same
+// atomics the prompt does
+//
+//   script run gen.rhai          runs it, as gen
+//   script run gen.rhai hats     as hats
+//   script stop hats             (or all)
+//   script                       what's running
+//
+// what a script can call:
+//   blast("trig kick")           runs a line; false if it was refused
+//   bar(), beat(), phase()       where the clock is (bar and beat from 0)
+//   bpm(), beats_per_bar()       the meter it's read in
+//   samples(), rate()            the clock in samples, and the sample rate
+//   wait(beats)                  sleeps until that many beats from now
+//   next_bar()                   sleeps until the next downbeat
+//   sleep(ms)
+//   rand(), rand(lo, hi)         a float in 0..1, or an int in lo..hi
+//   print(...)                   to the log
+//
+// e.g. a hat that skips a beat at random:
+//   loop {
+//       if rand() > 0.3 { blast("trig hat -b"); }
+//       wait(1);
+//   }
+//
+
+#[derive(Default)]
+pub struct Scripts {
+    running: BTreeMap<String, (Arc<AtomicBool>, JoinHandle<()>)>, // set to stop it
+    pub inbox: Option<Sender<Input>>, // where lines go, when there's a REPL
+}
+
+impl Scripts {
+    // compiles path (saying what's wrong with it, if anything) and
+    // runs it as name
+    pub fn run(&mut self, path: &str, name: &str) -> Result<(), String> {
+        self.prune();
+        if self.running.contains_key(name) {
+            return Err(format!("A script is already running as {name}; stop it first"));
+        }
+        let inbox = self.inbox.clone().ok_or("Scripts need the REPL to run their lines")?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = engine::spawn(path, name, inbox, Arc::clone(&stop))?;
+        self.running.insert(name.to_string(), (stop, thread));
+        Ok(())
+    }
+
+    // stops name, or every script at "all"
+    pub fn stop(&mut self, name: &str) -> Result<(), String> {
+        self.prune();
+        if name == "all" {
+            for (stop, _) in self.running.values() {
+                stop.store(true, Ordering::Relaxed);
+            }
+            self.running.clear();
+            return Ok(());
+        }
+        let (stop, _) = self.running.remove(name).ok_or_else(|| format!("No script is running as {name}"))?;
+        stop.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn listing(&mut self) -> String {
+        self.prune();
+        if self.running.is_empty() {
+            return "No scripts running".to_string();
+        }
+        self.running.keys().cloned().collect::<Vec<_>>().join("\n")
+    }
+
+    // forgets the ones that have finished by themselves
+    fn prune(&mut self) {
+        self.running.retain(|_, (_, thread)| !thread.is_finished());
+    }
+}
+
+impl Drop for Scripts {
+    fn drop(&mut self) {
+        for (stop, _) in self.running.values() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(feature = "rhai")]
+mod engine {
+    use std::{
+        path::PathBuf,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+            mpsc::{self, Sender},
+        },
+        thread::{self, JoinHandle},
+        time::Duration,
+    };
+
+    use rhai::{Dynamic, Engine, EvalAltResult};
+
+    use crate::audio_processing::{
+        blast_rand::{X128P, fast_seed},
+        blast_time::{blast_time::clock, sample_rate},
+        runtime::Input,
+        tui::{self, Kind},
+    };
+
+    pub fn spawn(path: &str, name: &str, inbox: Sender<Input>, stop: Arc<AtomicBool>) -> Result<JoinHandle<()>, String> {
+        let engine = engine(name, inbox, Arc::clone(&stop));
+        let ast = engine.compile_file(PathBuf::from(path)).map_err(|e| format!("{path}: {e}"))?;
+        let name = name.to_string();
+
+        Ok(thread::spawn(move || match engine.run_ast(&ast) {
+            Ok(()) => tui::log(format!("script {name} done")),
+            Err(err) if matches!(*err, EvalAltResult::ErrorTerminated(..)) => tui::log(format!("script {name} stopped")),
+            Err(err) => tui::err(format!("script {name}: {err}")),
+        }))
+    }
+
+    fn engine(name: &str, inbox: Sender<Input>, stop: Arc<AtomicBool>) -> Engine {
+        let mut engine = Engine::new();
+
+        let stopped = Arc::clone(&stop);
+        engine.on_progress(move |_| stopped.load(Ordering::Relaxed).then_some(Dynamic::UNIT));
+        engine.on_print(|text| tui::log(text));
+        engine.on_debug(|text, _, _| tui::log(text));
+
+        let from = name.to_string();
+        let inbox = Mutex::new(inbox);
+        engine.register_fn("blast", move |line: &str| -> bool {
+            let (reply, answer) = mpsc::channel();
+            let sent = Input::Line { from: from.clone(), line: line.to_string(), reply: Some(reply) };
+            if inbox.lock().unwrap().send(sent).is_err() {
+                return false;
+            }
+            answer.recv().is_ok_and(|logged| logged.iter().all(|l| l.kind != Kind::Err))
+        });
+
+        engine.register_fn("bar", || clock::position().bar as i64);
+        engine.register_fn("beat", || clock::position().beat as i64);
+        engine.register_fn("phase", || clock::position().phase as f64);
+        engine.register_fn("bpm", || {
+            let (beat_len, _) = clock::meter();
+            60.0 * sample_rate::get() as f64 / beat_len as f64
+        });
+        engine.register_fn("beats_per_bar", || clock::meter().1 as i64);
+        engine.register_fn("samples", || clock::current() as i64);
+        engine.register_fn("rate", || sample_rate::get() as i64);
+
+        let stopped = Arc::clone(&stop);
+        engine.register_fn("wait", move |beats: f64| {
+            let (beat_len, _) = clock::meter();
+            until(clock::current() + (beats.max(0.0) * beat_len as f64) as u64, &stopped);
+        });
+        let stopped = Arc::clone(&stop);
+        engine.register_fn("wait", move |beats: i64| {
+            let (beat_len, _) = clock::meter();
+            until(clock::current() + (beats.max(0) as f64 * beat_len as f64) as u64, &stopped);
+        });
+        let stopped = Arc::clone(&stop);
+        engine.register_fn("next_bar", move || {
+            let (beat_len, beats_per_bar) = clock::meter();
+            let bar = clock::position().bar + 1;
+            until((bar as f64 * beats_per_bar as f64 * beat_len as f64) as u64, &stopped);
+        });
+        engine.register_fn("sleep", move |ms: i64| {
+            let until = std::time::Instant::now() + Duration::from_millis(ms.max(0) as u64);
+            while std::time::Instant::now() < until && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        let rng = Arc::new(Mutex::new(X128P::new(fast_seed())));
+        let rand = Arc::clone(&rng);
+        engine.register_fn("rand", move || rand.lock().unwrap().next_f64());
+        engine.register_fn("rand", move |lo: i64, hi: i64| {
+            if hi <= lo {
+                return lo;
+            }
+            rng.lock().unwrap().next_i64_range(lo, hi)
+        });
+
+        engine
+    }
+
+    // sleeps until the clock reaches sample, or the script's stopped
+    fn until(sample: u64, stop: &AtomicBool) {
+        while clock::current() < sample && !stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+#[cfg(not(feature = "rhai"))]
+mod engine {
+    use std::{
+        sync::{Arc, atomic::AtomicBool, mpsc::Sender},
+        thread::JoinHandle,
+    };
+
+    use crate::audio_processing::runtime::Input;
+
+    pub fn spawn(_: &str, _: &str, _: Sender<Input>, _: Arc<AtomicBool>) -> Result<JoinHandle<()>, String> {
+        Err("Scripting isn't built in (build with --features rhai)".to_string())
+    }
+}
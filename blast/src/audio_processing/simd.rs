@@ -0,0 +1,172 @@
+// simd
+//
+// the loops a block spends its time in, four samples at a time:
+// SSE2 on x86_64 and NEON on aarch64 (both always there on those
+// targets, so nothing is detected at runtime), and plain loops
+// anywhere else and for the last few samples of a block
+//
+// every lane does what the plain loop does (no fused multiply-
+// adds), so the result is the same to the bit either way
+//
+
+const LANES: usize = 4;
+
+// out += src
+pub fn add(out: &mut [f32], src: &[f32]) {
+    let len = out.len().min(src.len());
+    let (out, src) = (&mut out[..len], &src[..len]);
+    let split = len - len % LANES;
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::*;
+        for i in (0..split).step_by(LANES) {
+            let sum = _mm_add_ps(_mm_loadu_ps(out.as_ptr().add(i)), _mm_loadu_ps(src.as_ptr().add(i)));
+            _mm_storeu_ps(out.as_mut_ptr().add(i), sum);
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        use std::arch::aarch64::*;
+        for i in (0..split).step_by(LANES) {
+            let sum = vaddq_f32(vld1q_f32(out.as_ptr().add(i)), vld1q_f32(src.as_ptr().add(i)));
+            vst1q_f32(out.as_mut_ptr().add(i), sum);
+        }
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let split = 0;
+
+    scalar::add(&mut out[split..], &src[split..]);
+}
+
+// out += src * gains, sample by sample
+pub fn add_product(out: &mut [f32], src: &[f32], gains: &[f32]) {
+    let len = out.len().min(src.len()).min(gains.len());
+    let (out, src, gains) = (&mut out[..len], &src[..len], &gains[..len]);
+    let split = len - len % LANES;
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::*;
+        for i in (0..split).step_by(LANES) {
+            let product = _mm_mul_ps(_mm_loadu_ps(src.as_ptr().add(i)), _mm_loadu_ps(gains.as_ptr().add(i)));
+            let sum = _mm_add_ps(_mm_loadu_ps(out.as_ptr().add(i)), product);
+            _mm_storeu_ps(out.as_mut_ptr().add(i), sum);
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        use std::arch::aarch64::*;
+        for i in (0..split).step_by(LANES) {
+            let product = vmulq_f32(vld1q_f32(src.as_ptr().add(i)), vld1q_f32(gains.as_ptr().add(i)));
+            let sum = vaddq_f32(vld1q_f32(out.as_ptr().add(i)), product);
+            vst1q_f32(out.as_mut_ptr().add(i), sum);
+        }
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let split = 0;
+
+    scalar::add_product(&mut out[split..], &src[split..], &gains[split..]);
+}
+
+// buf *= gain
+pub fn scale(buf: &mut [f32], gain: f32) {
+    let split = buf.len() - buf.len() % LANES;
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::*;
+        let gain = _mm_set1_ps(gain);
+        for i in (0..split).step_by(LANES) {
+            let scaled = _mm_mul_ps(_mm_loadu_ps(buf.as_ptr().add(i)), gain);
+            _mm_storeu_ps(buf.as_mut_ptr().add(i), scaled);
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        use std::arch::aarch64::*;
+        let gain = vdupq_n_f32(gain);
+        for i in (0..split).step_by(LANES) {
+            let scaled = vmulq_f32(vld1q_f32(buf.as_ptr().add(i)), gain);
+            vst1q_f32(buf.as_mut_ptr().add(i), scaled);
+        }
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let split = 0;
+
+    scalar::scale(&mut buf[split..], gain);
+}
+
+// out = src as i16: truncated, clipped to i16's range,
+// and NaN as silence, as the cast does
+pub fn to_i16(out: &mut [i16], src: &[f32]) {
+    let len = out.len().min(src.len());
+    let (out, src) = (&mut out[..len], &src[..len]);
+    let split = len - len % LANES;
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::*;
+        let (low, high) = (_mm_set1_ps(i16::MIN as f32), _mm_set1_ps(i16::MAX as f32));
+        for i in (0..split).step_by(LANES) {
+            let x = _mm_loadu_ps(src.as_ptr().add(i));
+            // clipped first, since out of i32's range converts
+            // to i32::MIN; NaN is masked to 0
+            let x = _mm_and_ps(x, _mm_cmpord_ps(x, x));
+            let x = _mm_min_ps(_mm_max_ps(x, low), high);
+            let packed = _mm_packs_epi32(_mm_cvttps_epi32(x), _mm_setzero_si128());
+            _mm_storel_epi64(out.as_mut_ptr().add(i) as *mut __m128i, packed);
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        use std::arch::aarch64::*;
+        for i in (0..split).step_by(LANES) {
+            // converting saturates (NaN to 0), and so does narrowing
+            let x = vcvtq_s32_f32(vld1q_f32(src.as_ptr().add(i)));
+            vst1_s16(out.as_mut_ptr().add(i), vqmovn_s32(x));
+        }
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let split = 0;
+
+    scalar::to_i16(&mut out[split..], &src[split..]);
+}
+
+// out = channel ch of interleaved src, as floats in -1..1;
+// the stride keeps the gather plain, but the scaling isn't
+#[cfg(any(target_os = "linux", test))]
+pub fn deinterleave(out: &mut [f32], src: &[i16], channels: usize, ch: usize) {
+    for (sample, frame) in out.iter_mut().zip(src.chunks_exact(channels.max(1))) {
+        *sample = frame[ch] as f32;
+    }
+    // a power of two, so the same as dividing by it
+    scale(out, 1.0 / 32768.0);
+}
+
+// the plain loops, which the vector ones have to match
+pub(crate) mod scalar {
+    pub fn add(out: &mut [f32], src: &[f32]) {
+        for (acc, sample) in out.iter_mut().zip(src) {
+            *acc += sample;
+        }
+    }
+
+    pub fn add_product(out: &mut [f32], src: &[f32], gains: &[f32]) {
+        for ((acc, sample), gain) in out.iter_mut().zip(src).zip(gains) {
+            *acc += sample * gain;
+        }
+    }
+
+    pub fn scale(buf: &mut [f32], gain: f32) {
+        for sample in buf {
+            *sample *= gain;
+        }
+    }
+
+    pub fn to_i16(out: &mut [i16], src: &[f32]) {
+        for (sample, mixed) in out.iter_mut().zip(src) {
+            *sample = *mixed as i16;
+        }
+    }
+}
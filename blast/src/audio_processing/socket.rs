@@ -0,0 +1,122 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::TcpListener,
+    os::unix::{fs::FileTypeExt, net::{UnixListener, UnixStream}},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use crate::audio_processing::{runtime::Input, tui};
+
+// socket
+//
+// the REPL's grammar over a local socket (--listen, or listen in
+// blast.conf), for scripts and editors (a vim or emacs
+// send-to-repl, socat, nc): each line a client writes is run as
+// if it had been typed, and what running it logged (the ok, an
+// error, what status printed) is written back, one line each,
+// then an empty line to say it's done
+//
+// a path is a Unix socket; a port, or host:port, is TCP (a bare
+// port only on 127.0.0.1, as anyone who can reach it can run
+// anything the REPL can)
+//
+// one thread accepts, and one more reads each client; lines from
+// every client go through the inbox, in the order they came
+//
+
+// where --listen takes lines from
+#[derive(Clone, PartialEq, Debug)]
+pub enum Addr {
+    Unix(String),
+    Tcp(String),
+}
+
+impl Addr {
+    pub fn parse(addr: &str) -> Result<Self, String> {
+        if addr.is_empty() {
+            return Err("Empty address".to_string());
+        }
+        if addr.contains('/') {
+            return Ok(Addr::Unix(addr.to_string()));
+        }
+        if addr.parse::<u16>().is_ok() {
+            return Ok(Addr::Tcp(format!("127.0.0.1:{addr}")));
+        }
+        match addr.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => Ok(Addr::Tcp(addr.to_string())),
+            _ => Err(format!("'{addr}' is neither a socket path, a port nor host:port")),
+        }
+    }
+}
+
+// opens addr and sends every line a client writes to inbox,
+// answering each with what it logged; returns where it's
+// listening
+pub fn listen(addr: &Addr, inbox: Sender<Input>) -> Result<String, String> {
+    match addr {
+        Addr::Unix(path) => {
+            // one left by a blast that didn't get to clean up refuses
+            // connections, and is replaced; one that's answered is
+            // another blast's, and left to it
+            if fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                match UnixStream::connect(path) {
+                    Ok(_) => return Err(format!("Couldn't listen on {path}: another blast is listening there")),
+                    Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                        let _ = fs::remove_file(path);
+                    }
+                    Err(_) => (),
+                }
+            }
+            let listener = UnixListener::bind(path).map_err(|e| format!("Couldn't listen on {path}: {e}"))?;
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let Ok(reader) = stream.try_clone() else { continue };
+                    serve(BufReader::new(reader), stream, inbox.clone());
+                }
+            });
+            Ok(path.clone())
+        }
+        Addr::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).map_err(|e| format!("Couldn't listen on {addr}: {e}"))?;
+            let bound = listener.local_addr().map_err(|e| e.to_string())?;
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let Ok(reader) = stream.try_clone() else { continue };
+                    serve(BufReader::new(reader), stream, inbox.clone());
+                }
+            });
+            Ok(bound.to_string())
+        }
+    }
+}
+
+// reads a client's lines on a thread of its own, until it hangs up
+fn serve(reader: impl BufRead + Send + 'static, mut writer: impl Write + Send + 'static, inbox: Sender<Input>) {
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else { return };
+            let line = line.trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            let (reply, answer) = mpsc::channel();
+            let sent = Input::Line { from: "socket".to_string(), line, reply: Some(reply) };
+            if inbox.send(sent).is_err() {
+                return;
+            }
+            // nothing, if the REPL's gone before it ran
+            let logged = answer.recv().unwrap_or_default();
+            let mut out = String::new();
+            for tui::Line { text, .. } in logged {
+                out.push_str(&text);
+                out.push('\n');
+            }
+            out.push('\n');
+            if writer.write_all(out.as_bytes()).and_then(|_| writer.flush()).is_err() {
+                return;
+            }
+        }
+    });
+}
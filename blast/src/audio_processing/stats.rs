@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// stats
+//
+// how the audio loop is keeping up, so a glitch can be put down
+// to the patch (render time creeping up to the period) or to the
+// device (underruns while rendering takes a fraction of it):
+// underruns, times the device was recovered after going away,
+// and how long each period took to render against how long it
+// plays for
+//
+// the audio thread only ever adds to and stores into atomics
+// (play() has them, shared with the CmdProcessor); stats and the
+// status bar read them
+//
+
+pub struct Stats {
+    periods: AtomicU64,
+    underruns: AtomicU64,
+    recoveries: AtomicU64,
+    last_ns: AtomicU64, // the last period's render
+    worst_ns: AtomicU64,
+    total_ns: AtomicU64,
+    budget_ns: AtomicU64, // what the last period plays for
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            periods: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+            recoveries: AtomicU64::new(0),
+            last_ns: AtomicU64::new(0),
+            worst_ns: AtomicU64::new(0),
+            total_ns: AtomicU64::new(0),
+            budget_ns: AtomicU64::new(0),
+        }
+    }
+
+    // a period of frames at sample_rate, which took took to render
+    pub fn rendered(&self, took: Duration, frames: usize, sample_rate: u32) {
+        let ns = took.as_nanos() as u64;
+        let budget = frames as u64 * 1_000_000_000 / sample_rate.max(1) as u64;
+        self.periods.fetch_add(1, Ordering::Relaxed);
+        self.last_ns.store(ns, Ordering::Relaxed);
+        self.worst_ns.fetch_max(ns, Ordering::Relaxed);
+        self.total_ns.fetch_add(ns, Ordering::Relaxed);
+        self.budget_ns.store(budget, Ordering::Relaxed);
+    }
+
+    pub fn underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn recovered(&self) {
+        self.recoveries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // starts counting over (stats reset)
+    pub fn reset(&self) {
+        for count in [&self.periods, &self.underruns, &self.recoveries, &self.last_ns, &self.worst_ns, &self.total_ns] {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let ns = |count: &AtomicU64| Duration::from_nanos(count.load(Ordering::Relaxed));
+        let periods = self.periods.load(Ordering::Relaxed);
+        Snapshot {
+            periods,
+            underruns: self.underruns.load(Ordering::Relaxed),
+            recoveries: self.recoveries.load(Ordering::Relaxed),
+            last: ns(&self.last_ns),
+            worst: ns(&self.worst_ns),
+            mean: Duration::from_nanos(self.total_ns.load(Ordering::Relaxed) / periods.max(1)),
+            budget: ns(&self.budget_ns),
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snapshot {
+    pub periods: u64,
+    pub underruns: u64,
+    pub recoveries: u64,
+    pub last: Duration,
+    pub worst: Duration,
+    pub mean: Duration,
+    pub budget: Duration,
+}
+
+impl Snapshot {
+    // how much of a period rendering it took, 1.0 being all of it
+    pub fn load(&self, took: Duration) -> f32 {
+        match self.budget.is_zero() {
+            true => 0.0,
+            false => took.as_secs_f32() / self.budget.as_secs_f32(),
+        }
+    }
+
+    // for stats
+    pub fn describe(&self) -> String {
+        let ms = |d: Duration| d.as_secs_f32() * 1000.0;
+        let pct = |d: Duration| self.load(d) * 100.0;
+        format!(
+            "{} periods of {:.2}ms: render {:.3}ms last ({:.0}%), {:.3}ms mean ({:.0}%), {:.3}ms worst ({:.0}%); {} underruns, {} recoveries",
+            self.periods, ms(self.budget),
+            ms(self.last), pct(self.last),
+            ms(self.mean), pct(self.mean),
+            ms(self.worst), pct(self.worst),
+            self.underruns, self.recoveries,
+        )
+    }
+
+    // for the status bar; nothing until the engine's played
+    pub fn brief(&self) -> Option<String> {
+        if self.periods == 0 {
+            return None;
+        }
+        Some(format!(
+            "dsp {:.0}% (worst {:.0}%)  xruns {}",
+            self.load(self.mean) * 100.0,
+            self.load(self.worst) * 100.0,
+            self.underruns,
+        ))
+    }
+}
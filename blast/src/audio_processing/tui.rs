@@ -0,0 +1,341 @@
+use crate::audio_processing::{
+    blast_time::blast_time::{Position, clock},
+    meters::{self, Level, VoiceLevels},
+    stats::Stats,
+};
+
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    io::Write,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+// tui
+//
+// the terminal, split into a log that scrolls, a status bar
+// and the input line at the bottom
+//
+// nothing prints on its own: every thread sends its lines
+// through log(), and one thread draws the whole screen, so
+// output can't land in the middle of the prompt; before the
+// screen is up (and offline, in tests) log() just prints
+//
+// once there are Voices, a meter for each (and the master)
+// sits between the log and the status bar
+//
+// the prompt counts the global clock in bar.beat of its meter,
+// and where the Voice last named in a line is in its Track
+//
+// lines are logged as what they are, and colored by it:
+// errors red, warnings yellow, confirmations green; an error
+// in a line shows the line with the part to blame marked
+//
+
+pub const SCROLLBACK: usize = 1000;
+
+const FRAME: Duration = Duration::from_millis(15);
+
+static LOG: OnceLock<Sender<Line>> = OnceLock::new();
+
+thread_local! {
+    // what this thread has logged, while it's being kept (see tee)
+    static TEE: RefCell<Option<Vec<Line>>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+    Info,
+    Ok,
+    Warn,
+    Err,
+}
+
+impl Kind {
+    fn color(self) -> &'static str {
+        match self {
+            Kind::Info => "",
+            Kind::Ok => "\x1b[32m",
+            Kind::Warn => "\x1b[33m",
+            Kind::Err => "\x1b[31m",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Line {
+    pub kind: Kind,
+    pub text: String,
+}
+
+impl Line {
+    pub fn new(kind: Kind, text: impl Into<String>) -> Self {
+        Self { kind, text: text.into() }
+    }
+}
+
+fn send(kind: Kind, text: String) {
+    for text in text.lines() {
+        let line = Line::new(kind, text);
+        TEE.with_borrow_mut(|tee| {
+            if let Some(kept) = tee {
+                kept.push(line.clone());
+            }
+        });
+        match LOG.get() {
+            Some(tx) => {
+                let _ = tx.send(line);
+            }
+            None => println!("{}", line.text),
+        }
+    }
+}
+
+pub fn log(text: impl Into<String>) {
+    send(Kind::Info, text.into());
+}
+
+pub fn ok(text: impl Into<String>) {
+    send(Kind::Ok, text.into());
+}
+
+pub fn warn(text: impl Display) {
+    send(Kind::Warn, format!("Warn: {text}"));
+}
+
+pub fn err(text: impl Display) {
+    send(Kind::Err, format!("Err: {text}"));
+}
+
+// runs f, keeping a copy of everything it logs on this thread
+// (as well as logging it), e.g. to answer whoever sent a line
+pub fn tee<T>(f: impl FnOnce() -> T) -> (T, Vec<Line>) {
+    let outer = TEE.replace(Some(Vec::new()));
+    let out = f();
+    let kept = TEE.replace(outer).unwrap_or_default();
+    TEE.with_borrow_mut(|tee| {
+        if let Some(outer) = tee {
+            outer.extend(kept.iter().cloned());
+        }
+    });
+    (out, kept)
+}
+
+// an error in line; if the part to blame can be found in
+// it, the line is shown again with that part marked
+pub fn error(line: &str, text: impl Display, blame: Option<&str>) {
+    err(text);
+    if let Some((start, len)) = blame.and_then(|b| span(line, b)) {
+        let mark = format!("  {line}\n  {}{}", " ".repeat(start), "^".repeat(len.max(1)));
+        send(Kind::Err, mark);
+    }
+}
+
+// where word is in line (in chars), preferring it standing
+// on its own to it inside another word
+pub fn span(line: &str, word: &str) -> Option<(usize, usize)> {
+    if word.is_empty() {
+        return None;
+    }
+    let alone = |at: usize| {
+        let before = line[..at].chars().next_back();
+        let after = line[at + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    };
+    let at = line
+        .match_indices(word)
+        .map(|(at, _)| at)
+        .find(|at| alone(*at))
+        .or_else(|| line.find(word))?;
+    Some((line[..at].chars().count(), word.chars().count()))
+}
+
+// a Voice the prompt follows, by name
+pub type Selected = (String, Arc<Level>);
+
+// what the REPL thread shares with the drawing thread
+#[derive(Clone, Default)]
+pub struct Ui {
+    pub buffer: Arc<Mutex<String>>,
+    pub cursor: Arc<Mutex<usize>>,
+    pub status: Arc<Mutex<String>>,
+    pub scroll: Arc<AtomicUsize>, // lines back from the end of the log
+    pub meters: Arc<Mutex<VoiceLevels>>,
+    pub master: Arc<Level>,
+    pub selected: Arc<Mutex<Option<Selected>>>,
+    pub keys: Arc<AtomicBool>, // keys mode, where keys act on their own
+    pub stats: Arc<Stats>, // the audio loop's, for the status bar
+}
+
+impl Ui {
+    // PgUp/PgDn, a screen at a time
+    pub fn scroll_by(&self, lines: isize) {
+        let rows = size().1.saturating_sub(2) as isize;
+        let by = lines * rows.max(1);
+        let to = (self.scroll.load(Ordering::Relaxed) as isize + by).clamp(0, SCROLLBACK as isize);
+        self.scroll.store(to as usize, Ordering::Relaxed);
+    }
+}
+
+// takes over the screen (the alternate one, so the shell's is
+// left as it was) and starts drawing it
+pub fn open(ui: Ui) {
+    let (tx, rx) = mpsc::channel::<Line>();
+    if LOG.set(tx).is_err() {
+        return;
+    }
+    print!("\x1b[?1049h\x1b[2J");
+    let _ = std::io::stdout().flush();
+
+    thread::spawn(move || draw(ui, rx));
+}
+
+// gives the shell its screen back; only writes,
+// so it's safe in a signal handler
+pub fn close() {
+    if LOG.get().is_some() {
+        let leave = b"\x1b[?1049l";
+        unsafe {
+            libc::write(libc::STDOUT_FILENO, leave.as_ptr().cast(), leave.len());
+        }
+    }
+}
+
+fn draw(ui: Ui, rx: Receiver<Line>) {
+    let mut lines = Vec::<Line>::new();
+    let mut last = String::new();
+
+    loop {
+        lines.extend(rx.try_iter());
+        if lines.len() > SCROLLBACK {
+            lines.drain(..lines.len() - SCROLLBACK);
+        }
+
+        let selected = ui.selected.lock().unwrap().clone();
+        let selected = selected.as_ref().map(|(name, level)| (name.as_str(), &**level));
+        let prompt = prompt(clock::position(), selected, ui.keys.load(Ordering::Relaxed));
+        let frame = frame(&lines, &ui, &prompt, size());
+        if frame != last {
+            print!("{frame}");
+            let _ = std::io::stdout().flush();
+            last = frame;
+        }
+
+        thread::sleep(FRAME);
+    }
+}
+
+// the whole screen, top to bottom, each row cleared to its
+// end, finishing with the cursor where it is in the input
+pub fn frame(lines: &[Line], ui: &Ui, prompt: &str, (width, height): (usize, usize)) -> String {
+    let voices = ui.meters.lock().unwrap().clone();
+    let meter_rows = match voices.is_empty() {
+        true => 0,
+        false => (voices.len() + 1).min(height.saturating_sub(2) / 2),
+    };
+    let rows = height.saturating_sub(2 + meter_rows);
+    let scroll = ui.scroll.load(Ordering::Relaxed).min(lines.len().saturating_sub(rows));
+    let end = lines.len() - scroll;
+    let shown = &lines[end.saturating_sub(rows)..end];
+
+    let clip = |text: &str| text.chars().take(width).collect::<String>();
+    let mut out = String::with_capacity(width * height);
+    for row in 0..rows {
+        // the log sits on the status bar, like a terminal's would
+        let line = (row + shown.len()).checked_sub(rows).and_then(|i| shown.get(i));
+        out.push_str(&format!("\x1b[{};1H", row + 1));
+        match line {
+            Some(Line { kind: Kind::Info, text }) => out.push_str(&clip(text)),
+            Some(Line { kind, text }) => out.push_str(&format!("{}{}\x1b[0m", kind.color(), clip(text))),
+            None => (),
+        }
+        out.push_str("\x1b[K");
+    }
+
+    let master = ("master".to_string(), Arc::clone(&ui.master));
+    for (row, (name, level)) in std::iter::once(master).chain(voices).take(meter_rows).enumerate() {
+        out.push_str(&format!("\x1b[{};1H{}\x1b[K", rows + row + 1, clip(&meter(&name, &level, width))));
+    }
+
+    let mut status = ui.status.lock().unwrap().clone();
+    if let Some(stats) = ui.stats.snapshot().brief() {
+        status.push_str(&format!("  [{stats}]"));
+    }
+    if scroll > 0 {
+        status.push_str(&format!("  [{scroll} lines back]"));
+    }
+    out.push_str(&format!("\x1b[{};1H\x1b[7m{:<width$}\x1b[0m", rows + meter_rows + 1, clip(&status)));
+
+    let buffer = ui.buffer.lock().unwrap();
+    let cursor = *ui.cursor.lock().unwrap();
+    out.push_str(&format!("\x1b[{height};1H{}\x1b[K", clip(&format!("{prompt} {buffer}"))));
+    let col = cursor + prompt.chars().count() + 2;
+    out.push_str(&format!("\x1b[{height};{}H", col.min(width.max(1))));
+    out
+}
+
+// e.g. 3.2 kick 1.5/4.0s >, or in keys mode 3.2 kick 1.5/4.0s [keys]
+pub fn prompt(at: Position, selected: Option<(&str, &Level)>, keys: bool) -> String {
+    let mut prompt = at.to_string();
+    if let Some((name, level)) = selected {
+        let (playhead, length) = level.playhead();
+        prompt.push_str(&format!(" {name} {playhead:.1}/{length:.1}s"));
+    }
+    prompt.push_str(if keys { " [keys]" } else { " >" });
+    prompt
+}
+
+// name, bar and peak in dB, e.g.
+// kick         ##########.....|.........  -12.0
+pub fn meter(name: &str, level: &Level, width: usize) -> String {
+    let name: String = name.chars().take(12).collect();
+    let bar = meters::bar(level, width.saturating_sub(21));
+    format!("{name:<12} {bar} {:>6.1}", meters::to_db(level.peak()))
+}
+
+// how far loading has got, e.g.
+// loading ##########..........  50%   12.0/24.0MB
+pub fn loading(done: usize, total: usize, width: usize) -> String {
+    let part = match total {
+        0 => 1.0,
+        total => (done as f64 / total as f64).min(1.0),
+    };
+    let cols = width.saturating_sub(31);
+    let filled = (part * cols as f64).round() as usize;
+    let mb = |bytes: usize| bytes as f64 / (1 << 20) as f64;
+    format!(
+        "loading {}{} {:>3.0}% {:>7.1}/{:.1}MB",
+        "#".repeat(filled),
+        ".".repeat(cols - filled),
+        part * 100.0,
+        mb(done),
+        mb(total),
+    )
+}
+
+// the Voice a line names, or the one already selected while
+// it's still there (looked up again, as a revert replaces it)
+pub fn select(voices: &[Selected], line: &str, selected: Option<Selected>) -> Option<Selected> {
+    let named = |name: &str| voices.iter().find(|(voice, _)| voice == name).cloned();
+    line.split_whitespace()
+        .skip(1)
+        .find_map(named)
+        .or_else(|| selected.and_then(|(name, _)| named(&name)))
+}
+
+// columns and rows, 80x24 if the terminal won't say
+pub fn size() -> (usize, usize) {
+    unsafe {
+        let mut ws: libc::winsize = std::mem::zeroed();
+        match libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) {
+            0 if ws.ws_col > 0 && ws.ws_row > 2 => (ws.ws_col as usize, ws.ws_row as usize),
+            _ => (80, 24),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::file_parsing::decode_helpers::AudioFile;
+
+// wave
+//
+// an overview of a Track for the terminal: its peaks, taken once
+// when it's loaded into a fixed number of bins, and drawn at
+// whatever width there is as columns of eighth blocks, with a
+// ruler under them marking seconds and where a Voice is playing
+//
+// the bins are kept with the Track, so drawing it again (at
+// another width, or to follow the playhead) never touches the
+// samples
+//
+
+pub const BINS: usize = 1024;
+
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Clone, Debug, Default)]
+pub struct Envelope {
+    peaks: Vec<f32>, // 0..1 of full scale, at most BINS of them
+    seconds: f32,
+}
+
+impl Envelope {
+    pub fn of(af: &AudioFile) -> Self {
+        let channels = af.num_channels.max(1) as usize;
+        let frames = af.samples.len() / channels;
+        let per_bin = frames.div_ceil(BINS).max(1);
+        let peaks = af.samples
+            .chunks(per_bin * channels)
+            .map(|bin| bin.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0) as f32 / i16::MAX as f32)
+            .map(|peak| peak.min(1.0))
+            .collect();
+        let seconds = frames as f32 / af.sample_rate.max(1) as f32;
+        Self { peaks, seconds }
+    }
+
+    #[cfg(test)]
+    pub fn seconds(&self) -> f32 {
+        self.seconds
+    }
+
+    // the loudest bin under each of width columns
+    pub fn columns(&self, width: usize) -> Vec<f32> {
+        let bins = self.peaks.len();
+        (0..width)
+            .map(|c| {
+                let (from, to) = (c * bins / width, ((c + 1) * bins / width).max(c * bins / width + 1));
+                self.peaks[from.min(bins)..to.min(bins)].iter().fold(0.0, |a: f32, p| a.max(*p))
+            })
+            .collect()
+    }
+
+    // rows of blocks, the loudest reaching the top, then ^ at
+    // the playhead (in seconds) over a ruler with the length at
+    // its end, e.g. for one row
+    // ▂▅█▇▅▄▃▂▁▁▁  ▂▆█▆▄▃▂▁
+    //           ^
+    // 0s                  1.2s
+    pub fn draw(&self, width: usize, rows: usize, playhead: Option<f32>) -> String {
+        let width = width.max(1);
+        let rows = rows.max(1);
+        let columns = self.columns(width);
+
+        let mut out = String::new();
+        for row in (0..rows).rev() {
+            let line: String = columns
+                .iter()
+                .map(|peak| {
+                    // eighths of the whole height, less what's below this row
+                    let eighths = (peak * (rows * 8) as f32).round() as usize;
+                    BLOCKS[eighths.saturating_sub(row * 8).min(8)]
+                })
+                .collect();
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+
+        if let Some(at) = playhead.filter(|_| self.seconds > 0.0) {
+            let col = ((at / self.seconds) * width as f32) as usize;
+            out.push_str(&format!("{}^\n", " ".repeat(col.min(width - 1))));
+        }
+        let end = format!("{:.1}s", self.seconds);
+        out.push_str(&format!("0s{end:>w$}", w = width.saturating_sub(2)));
+        out
+    }
+}
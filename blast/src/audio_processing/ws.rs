@@ -0,0 +1,430 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::audio_processing::{
+    blast_time::{sample_rate, blast_time::clock},
+    commands::EngineState,
+    meters::Level,
+    runtime::Input,
+    schema,
+    tui::{self, Kind},
+};
+
+// ws
+//
+// a WebSocket server (--ws, or ws in blast.conf) for a browser UI
+// on top of the engine: every client is sent the state as JSON
+// BROADCAST_EVERY, and each text message it sends is a line, run
+// as if it had been typed and answered with what it logged
+//
+//   {"type":"state",
+//    "clock":{"samples":96000,"bar":0,"beat":2,"phase":0.5,"bpm":120,"beats_per_bar":4},
+//    "master":{"peak":0.5,"rms":0.2},
+//    "voices":[{"name":"kick","playing":true,"muted":false,"soloed":false,
+//               "peak":0.5,"rms":0.2,"position":0.25,"length":1}]}
+//   {"type":"reply","line":"start -v kick","log":[{"kind":"ok","text":"ok (rev 3)"}]}
+//
+// positions and lengths are in seconds; the state is made on the
+// REPL's thread (asked for through the inbox, like any line), so
+// it's never halfway through a command
+//
+// a bare port listens only on 127.0.0.1, as anyone who can reach
+// it can run anything the REPL can; for the same reason a browser
+// is only let in from a page served from this machine (its Origin
+// is localhost, 127.0.0.1 or [::1]), so no other site the user has
+// open can connect. a client that isn't a browser sends no Origin
+//
+
+pub const BROADCAST_EVERY: Duration = Duration::from_millis(100);
+
+// how long a client has to take what it's sent; one that stalls
+// for longer (a tab that's stopped reading) is hung up on
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+// what a client can send in one message
+const MAX_MESSAGE: usize = 64 * 1024;
+
+// the handshake's answer is the client's key and this, hashed
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+type Client = Arc<Mutex<TcpStream>>;
+
+// a port, or host:port
+pub fn addr(raw: &str) -> Result<String, String> {
+    if raw.parse::<u16>().is_ok() {
+        return Ok(format!("127.0.0.1:{raw}"));
+    }
+    match raw.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => Ok(raw.to_string()),
+        _ => Err(format!("'{raw}' is neither a port nor host:port")),
+    }
+}
+
+// opens addr, sends the state to every client that connects and
+// every line they send to inbox; returns where it's listening
+pub fn serve(addr: &str, inbox: Sender<Input>) -> Result<String, String> {
+    let listener = TcpListener::bind(addr).map_err(|e| format!("Couldn't listen on {addr}: {e}"))?;
+    let bound = listener.local_addr().map_err(|e| e.to_string())?;
+    let clients = Arc::new(Mutex::new(Vec::<Client>::new()));
+
+    {
+        let clients = Arc::clone(&clients);
+        let inbox = inbox.clone();
+        thread::spawn(move || loop {
+            thread::sleep(BROADCAST_EVERY);
+            if clients.lock().unwrap().is_empty() {
+                continue;
+            }
+            let (reply, answer) = mpsc::channel();
+            if inbox.send(Input::State(reply)).is_err() {
+                return;
+            }
+            let Ok(state) = answer.recv() else { return };
+            let frame = frame(OP_TEXT, state.as_bytes());
+
+            // sent without holding the list, so a slow client holds
+            // up neither new ones nor (for long) the rest; ones that
+            // hung up or stalled are forgotten
+            let sending = clients.lock().unwrap().clone();
+            let gone: Vec<Client> = sending
+                .into_iter()
+                .filter(|client| {
+                    let mut stream = client.lock().unwrap();
+                    let failed = stream.write_all(&frame).is_err();
+                    if failed {
+                        // half a frame may be out; its reader stops too
+                        let _ = stream.shutdown(Shutdown::Both);
+                    }
+                    failed
+                })
+                .collect();
+            if !gone.is_empty() {
+                clients.lock().unwrap().retain(|client| !gone.iter().any(|g| Arc::ptr_eq(client, g)));
+            }
+        });
+    }
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let clients = Arc::clone(&clients);
+            let inbox = inbox.clone();
+            thread::spawn(move || {
+                if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+                    return;
+                }
+                let Ok(mut writer) = stream.try_clone() else { return };
+                let mut reader = BufReader::new(stream);
+                if let Err(err) = handshake(&mut reader, &mut writer) {
+                    tui::warn(format!("WebSocket: {err}"));
+                    return;
+                }
+                let client = Arc::new(Mutex::new(writer));
+                clients.lock().unwrap().push(Arc::clone(&client));
+                serve_client(reader, client, inbox);
+            });
+        }
+    });
+    Ok(bound.to_string())
+}
+
+// the state a client is sent, as the REPL sees it
+pub fn state_json(engine_state: &EngineState, master: &Level) -> String {
+    let position = clock::position();
+    let (beat_len, beats_per_bar) = clock::meter();
+    let bpm = 60.0 * sample_rate::get() as f32 / beat_len;
+
+    let voices: Vec<String> = engine_state
+        .meters()
+        .iter()
+        .filter_map(|(name, level)| {
+            let voice = engine_state.voice(name)?;
+            let (position, length) = level.playhead();
+            Some(format!(
+                "{{\"name\":{},\"playing\":{},\"muted\":{},\"soloed\":{},\"peak\":{},\"rms\":{},\"position\":{},\"length\":{}}}",
+                schema::string(name),
+                voice.is_playing(),
+                voice.is_muted(),
+                voice.is_soloed(),
+                number(level.peak()),
+                number(level.rms()),
+                number(position),
+                number(length),
+            ))
+        })
+        .collect();
+
+    format!(
+        "{{\"type\":\"state\",\"clock\":{{\"samples\":{},\"bar\":{},\"beat\":{},\"phase\":{},\"bpm\":{},\"beats_per_bar\":{}}},\"master\":{{\"peak\":{},\"rms\":{}}},\"voices\":[{}]}}",
+        clock::current(),
+        position.bar,
+        position.beat,
+        number(position.phase),
+        number(bpm),
+        beats_per_bar,
+        number(master.peak()),
+        number(master.rms()),
+        voices.join(","),
+    )
+}
+
+// what running line logged
+fn reply_json(line: &str, logged: &[tui::Line]) -> String {
+    let log: Vec<String> = logged
+        .iter()
+        .map(|tui::Line { kind, text }| {
+            let kind = match kind {
+                Kind::Info => "info",
+                Kind::Ok => "ok",
+                Kind::Warn => "warn",
+                Kind::Err => "err",
+            };
+            format!("{{\"kind\":\"{kind}\",\"text\":{}}}", schema::string(text))
+        })
+        .collect();
+    format!("{{\"type\":\"reply\",\"line\":{},\"log\":[{}]}}", schema::string(line), log.join(","))
+}
+
+// JSON has no NaN or infinity
+fn number(v: f32) -> String {
+    match v.is_finite() {
+        true => v.to_string(),
+        false => "0".to_string(),
+    }
+}
+
+// reads the client's messages until it closes or hangs up
+fn serve_client(mut reader: BufReader<TcpStream>, client: Client, inbox: Sender<Input>) {
+    let mut message = Vec::<u8>::new();
+    while let Ok((fin, op, payload)) = read_frame(&mut reader) {
+        match op {
+            OP_CLOSE => {
+                let _ = client.lock().unwrap().write_all(&frame(OP_CLOSE, &payload));
+                return;
+            }
+            OP_PING => {
+                if client.lock().unwrap().write_all(&frame(OP_PONG, &payload)).is_err() {
+                    return;
+                }
+                continue;
+            }
+            OP_PONG => continue,
+            OP_TEXT | OP_CONTINUATION => message.extend_from_slice(&payload),
+            // binary means nothing here
+            _ => {
+                message.clear();
+                continue;
+            }
+        }
+        if message.len() > MAX_MESSAGE {
+            return;
+        }
+        if !fin {
+            continue;
+        }
+
+        let line = String::from_utf8_lossy(&std::mem::take(&mut message)).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        let (reply, answer) = mpsc::channel();
+        let sent = Input::Line { from: "ws".to_string(), line: line.clone(), reply: Some(reply) };
+        if inbox.send(sent).is_err() {
+            return;
+        }
+        // nothing, if the REPL's gone before it ran
+        let logged = answer.recv().unwrap_or_default();
+        let reply = frame(OP_TEXT, reply_json(&line, &logged).as_bytes());
+        if client.lock().unwrap().write_all(&reply).is_err() {
+            return;
+        }
+    }
+}
+
+// reads the HTTP upgrade request and accepts it
+fn handshake(reader: &mut impl BufRead, writer: &mut impl Write) -> Result<(), String> {
+    let mut key = None;
+    let mut origin = None;
+    let mut first = true;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err("hung up during the handshake".to_string());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if first && !line.starts_with("GET ") {
+            return Err(format!("not a WebSocket request: {line}"));
+        }
+        first = false;
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    if let Some(origin) = origin
+        && !local_origin(&origin)
+    {
+        let _ = writer.write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n");
+        return Err(format!("turned away a page from {origin}"));
+    }
+
+    let Some(key) = key else {
+        let _ = writer.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n");
+        return Err("no Sec-WebSocket-Key in the request".to_string());
+    };
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept(&key),
+    );
+    writer.write_all(response.as_bytes()).map_err(|e| e.to_string())
+}
+
+// scheme://host[:port], with host this machine
+fn local_origin(origin: &str) -> bool {
+    let Some((_, rest)) = origin.split_once("://") else { return false };
+    let host = match rest.strip_prefix('[') {
+        Some(v6) => v6.split(']').next(),
+        None => rest.split(':').next(),
+    };
+    matches!(host, Some("localhost" | "127.0.0.1" | "::1"))
+}
+
+// the Sec-WebSocket-Accept for a client's key
+pub fn accept(key: &str) -> String {
+    base64(&sha1(format!("{key}{GUID}").as_bytes()))
+}
+
+// (fin, opcode, payload), unmasked
+fn read_frame(reader: &mut impl Read) -> std::io::Result<(bool, u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head)?;
+    let fin = head[0] & 0x80 != 0;
+    let op = head[0] & 0x0f;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as usize
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len) as usize
+        }
+        len => len as usize,
+    };
+    if len > MAX_MESSAGE {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "message too long"));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    Ok((fin, op, payload))
+}
+
+// a whole message, from the server (so unmasked)
+pub fn frame(op: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x80 | op];
+    match payload.len() {
+        len @ 0..=125 => out.push(len as u8),
+        len @ 126..=0xffff => {
+            out.push(126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(payload);
+    out
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (w, word) in w.iter_mut().zip(block.chunks(4)) {
+            *w = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0u8; 20];
+    for (out, h) in out.chunks_mut(4).zip(h) {
+        out.copy_from_slice(&h.to_be_bytes());
+    }
+    out
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
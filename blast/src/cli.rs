@@ -0,0 +1,15 @@
+// cli
+//
+// what the blast binary needs past the prelude: the REPL it
+// hands over to, parsing its options, and what's behind its
+// subcommands (bundling assets, checking files). it follows
+// the binary, and other crates shouldn't lean on it
+//
+pub use crate::file_parsing::{bundle, validate};
+pub use crate::audio_processing::{
+    runtime::run_blast,
+    socket::Addr,
+    ws::addr as ws_addr,
+    realtime::{available_cpus, pin_current_thread},
+    tui::{loading, size as term_size},
+};
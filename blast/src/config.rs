@@ -0,0 +1,178 @@
+use std::{env, fs};
+
+use crate::audio_processing::{backend::Latency, socket::Addr, ws};
+
+// blast.conf
+//
+// one `key = value` per line, # starts a comment;
+// read from ./blast.conf, or else $XDG_CONFIG_HOME/blast/blast.conf
+// (~/.config/blast/blast.conf)
+//
+// keys:
+//   audio_cpu = 3          pin the audio thread to this core
+//   worker_cpus = 0,1,2    cores for decoding and the REPL
+//                          (default: every core but audio_cpu)
+//   fade_ms = 5            ramp length for click-free transport
+//   voices = 64            Voices that can be loaded at once,
+//                          allocated at startup (default 64)
+//   device = plughw:1,0    the PCM to play on (default hw:0,0;
+//                          the REPL's devices lists them)
+//   backend = pulse        alsa, pulse, jack, cpal or null
+//                          (default: alsa on Linux)
+//   period = 256           frames rendered a cycle (default 128)
+//   buffer = 1024          frames the device buffers (default
+//                          four periods)
+//   osc = 9000             UDP port to take OSC messages on
+//   listen = /tmp/blast    Unix socket (or port, or host:port)
+//                          to take REPL lines on
+//   ws = 8080              port (or host:port) to serve the
+//                          WebSocket API on
+//
+// after a line reading [aliases], each line is an alias
+// for the REPL instead, as `alias` would take it:
+//   drop = stop -g $1; start -g $1
+//
+// and after [keys], each binds a key for the REPL's keys mode
+// (see audio_processing::bindings):
+//   space = startstop
+//   t = trig $v -b
+//
+// and after [notes], each maps a MIDI note (a name or number) to
+// a Voice it plays, as midi map would:
+//   C2 = trig kick
+//   37 = start pad
+//
+#[derive(Default, Debug)]
+pub struct Config {
+    pub audio_cpu: Option<usize>,
+    pub worker_cpus: Option<Vec<usize>>,
+    pub fade_ms: Option<f32>,
+    pub voices: Option<usize>,
+    pub device: Option<String>,
+    pub backend: Option<String>,
+    pub latency: Option<Latency>, // if period or buffer is given
+    pub osc: Option<u16>,
+    pub listen: Option<Addr>,
+    pub ws: Option<String>,
+    pub rate: Option<u32>,
+    pub aliases: Vec<(String, String)>, // in the order they're given
+    pub keys: Vec<(String, String)>,
+    pub notes: Vec<(String, String)>,
+}
+
+impl Config {
+    // a missing file is an empty Config
+    pub fn load() -> Result<Self, String> {
+        match Self::find() {
+            Some(path) => {
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| format!("{path}: {e}"))?;
+                Self::parse(&text).map_err(|e| format!("{path}: {e}"))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn find() -> Option<String> {
+        let xdg = env::var("XDG_CONFIG_HOME")
+            .ok()
+            .or_else(|| env::var("HOME").ok().map(|home| format!("{home}/.config")));
+
+        let mut candidates = vec!["blast.conf".to_string()];
+        if let Some(dir) = xdg {
+            candidates.push(format!("{dir}/blast/blast.conf"));
+        }
+
+        candidates.into_iter().find(|p| fs::metadata(p).is_ok())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut section = "";
+        let mut period = None;
+        let mut buffer = None;
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = match name {
+                    "aliases" => "aliases",
+                    "keys" => "keys",
+                    "notes" => "notes",
+                    _ => return Err(format!("line {}: unknown section [{}]", n + 1, name)),
+                };
+                continue;
+            }
+
+            let (key, val) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or(format!("line {}: expected key = value", n + 1))?;
+
+            match section {
+                "aliases" => {
+                    config.aliases.push((key.to_string(), val.to_string()));
+                    continue;
+                }
+                "keys" => {
+                    config.keys.push((key.to_string(), val.to_string()));
+                    continue;
+                }
+                "notes" => {
+                    config.notes.push((key.to_string(), val.to_string()));
+                    continue;
+                }
+                _ => (),
+            }
+
+            let bad = || format!("line {}: invalid value '{}' for {}", n + 1, val, key);
+
+            match key {
+                "audio_cpu" => config.audio_cpu = Some(val.parse().map_err(|_| bad())?),
+                "worker_cpus" => {
+                    let cpus = val
+                        .split(',')
+                        .map(|c| c.trim().parse::<usize>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| bad())?;
+                    config.worker_cpus = Some(cpus);
+                }
+                "fade_ms" => {
+                    let ms = val.parse::<f32>().map_err(|_| bad())?;
+                    if ms < 0.0 {
+                        return Err(bad());
+                    }
+                    config.fade_ms = Some(ms);
+                }
+                "voices" => match val.parse::<usize>() {
+                    Ok(n) if n > 0 => config.voices = Some(n),
+                    _ => return Err(bad()),
+                },
+                "device" if !val.is_empty() => config.device = Some(val.to_string()),
+                "device" => return Err(bad()),
+                "backend" if matches!(val, "alsa" | "pulse" | "jack" | "cpal" | "null") => config.backend = Some(val.to_string()),
+                "backend" => return Err(bad()),
+                "period" => period = Some(val.parse::<usize>().map_err(|_| bad())?),
+                "osc" => config.osc = Some(val.parse().map_err(|_| bad())?),
+                "listen" => config.listen = Some(Addr::parse(val).map_err(|_| bad())?),
+                "ws" => config.ws = Some(ws::addr(val).map_err(|_| bad())?),
+                "buffer" => buffer = Some(val.parse::<usize>().map_err(|_| bad())?),
+                "rate" => match val.parse::<u32>() {
+                    Ok(hz) if hz > 0 => config.rate = Some(hz),
+                    _ => return Err(bad()),
+                },
+                _ => return Err(format!("line {}: unknown key '{}'", n + 1, key)),
+            }
+        }
+
+        if period.is_some() || buffer.is_some() {
+            let period = period.unwrap_or(Latency::DEFAULT.period);
+            config.latency = Some(Latency::new(period, buffer)?);
+        }
+
+        Ok(config)
+    }
+}
@@ -1,15 +1,12 @@
-use std::fs::File;
-use std::io::{self, Read, SeekFrom};
-use std::ops::{Shl, BitOr, AddAssign};
-use super::decode_helpers::{AudioFile, DecodeResult, DecodeError};
+use super::decode_helpers::{AudioFile, DecodeResult, DecodeError, Metadata, Progress, chunk_at, read_file};
 
-fn print_id(vec: &mut Vec<u8>, start: &mut usize, end: &mut usize) -> DecodeResult<()> {
+fn print_id(vec: &[u8], start: &mut usize, end: &mut usize) -> DecodeResult<()> {
     *end += 4;
 
     for i in *start..*end {
         let c = match vec.get(i) {
             Some(val) => val,
-            None => return Err(DecodeError::UnexpectedEof),
+            None => return Err(DecodeError::UnexpectedEof.at_offset(i)),
         };
 
         print!("{}", char::from(*c));
@@ -17,12 +14,12 @@ fn print_id(vec: &mut Vec<u8>, start: &mut usize, end: &mut usize) -> DecodeResu
 
     *start = *end;
 
-    println!("");
+    println!();
 
     Ok(())
 }
 
-fn parse_bytes(bytes: &mut Vec<u8>, start: &mut usize, end: &mut usize, inc: usize) -> DecodeResult<u32> {
+fn parse_bytes(bytes: &[u8], start: &mut usize, end: &mut usize, inc: usize) -> DecodeResult<u32> {
     let mut value: u32 = 0;
 
     *end += inc;
@@ -32,7 +29,7 @@ fn parse_bytes(bytes: &mut Vec<u8>, start: &mut usize, end: &mut usize, inc: usi
     for i in *start..*end {
         let b: u8 = match bytes.get(i) {
             Some(val) => *val,
-            None => return Err(DecodeError::UnexpectedEof),
+            None => return Err(DecodeError::UnexpectedEof.at_offset(i)),
         };
 
         value += (b as u32) << shift;
@@ -48,18 +45,13 @@ fn parse_bytes(bytes: &mut Vec<u8>, start: &mut usize, end: &mut usize, inc: usi
 }
 //
 // special function to parse IEEE 80-bit extended floating-point
-fn parse_ieee_extended(reader: &mut Vec<u8>, start: &mut usize, end: &mut usize) -> DecodeResult<f64> {
+pub fn parse_ieee_extended(reader: &[u8], start: &mut usize, end: &mut usize) -> DecodeResult<f64> {
     let mut bytes = [0u8; 10];
     *end += 10;
-    let mut i = 0;
-    for byte in *start..*end {
-        let b = match reader.get(byte) {
-            Some(val) => val,
-            None => return Err(DecodeError::UnexpectedEof),
-        };
-        bytes[i] = *b;
-        i += 1;
-    }
+    match reader.get(*start..*end) {
+        Some(val) => bytes.copy_from_slice(val),
+        None => return Err(DecodeError::UnexpectedEof.at_offset(reader.len())),
+    };
     *start = *end;
 
     let sign = (bytes[0] & 0x80) != 0;
@@ -97,60 +89,92 @@ fn parse_ieee_extended(reader: &mut Vec<u8>, start: &mut usize, end: &mut usize)
 // so adjust this to search only for those and
 // extract the relevant information
 pub fn parse(path: &str) -> DecodeResult<AudioFile> {
-    let mut f = File::open(path)?;
-    let mut reader = Vec::new();
-    f.read_to_end(&mut reader)?;
+    parse_with(path, None)
+}
+
+// as parse, telling progress as the file's read
+pub fn parse_with(path: &str, progress: Progress) -> DecodeResult<AudioFile> {
+    let reader = read_file(path, progress)?;
+
+    let file_name: &str = match path.rsplit_once('.') {
+        Some((before, after)) if !before.is_empty() && !after.is_empty() => {
+            match before.rsplit_once('/') {
+                Some((_assets, name)) => name,
+                None => return Err(DecodeError::InvalidData("File is not nested".to_string()).in_file(path)),
+            }
+        }
+        _ => return Err(DecodeError::InvalidData("File has no name".to_string()).in_file(path)),
+    };
+
+    // the chunk being read, for an error to name
+    let mut chunk = chunk_at(&reader, 0);
+    read_chunks(path, file_name, &reader, &mut chunk).map_err(|e| e.within(path, &chunk.0, chunk.1))
+}
 
+fn read_chunks(path: &str, file_name: &str, reader: &[u8], chunk: &mut (String, usize)) -> DecodeResult<AudioFile> {
     let mut start = 0;
     let mut end = 0;
 
     // FORM
-    print_id(&mut reader, &mut start, &mut end)?;
+    print_id(reader, &mut start, &mut end)?;
 
-    let form_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let form_size: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
     println!("Form size: {form_size}");
 
     // AIFF
-    print_id(&mut reader, &mut start, &mut end)?;
+    print_id(reader, &mut start, &mut end)?;
     
-    println!("");
+    println!();
 
     // COMM
-    print_id(&mut reader, &mut start, &mut end)?;
+    *chunk = chunk_at(reader, start);
+    print_id(reader, &mut start, &mut end)?;
 
-    let comm_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let comm_size: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
     if comm_size == 18 {
         println!("Comm size: {comm_size}");
     } else {
         return Err(DecodeError::InvalidData("Comm size should be 18".to_string()));
     }
 
-    let num_channels: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+    let num_channels: u32 = parse_bytes(reader, &mut start, &mut end, 2)?;
     println!("Num channels: {num_channels}");
 
-    let num_frames: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let num_frames: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
     println!("Num sample frames: {num_frames}");
 
-    let sample_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+    let sample_size: u32 = parse_bytes(reader, &mut start, &mut end, 2)?;
     println!("Sample size: {sample_size}");
 
 
-    let sample_rate: f64 = parse_ieee_extended(&mut reader, &mut start, &mut end)?;
+    let sample_rate: f64 = parse_ieee_extended(reader, &mut start, &mut end)?;
     println!("Sample rate: {sample_rate}");
     
-    println!("");
+    println!();
+
+    // anything between COMM and SSND (INST, MARK, NAME...)
+    // is read separately, if at all
+    while reader.get(start..start + 4).ok_or(DecodeError::UnexpectedEof.at_offset(start))? != b"SSND" {
+        *chunk = chunk_at(reader, start);
+        start += 4;
+        end = start;
+        let size = parse_bytes(reader, &mut start, &mut end, 4)? as usize;
+        start += size + (size & 1);
+        end = start;
+    }
 
     // SSND
-    print_id(&mut reader, &mut start, &mut end)?;
+    *chunk = chunk_at(reader, start);
+    print_id(reader, &mut start, &mut end)?;
 
-    let ssnd_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)? - 8;
+    let ssnd_size: u32 = parse_bytes(reader, &mut start, &mut end, 4)? - 8;
     println!("Data size: {ssnd_size}");
 
     // typically 0
-    let offset: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let offset: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
     println!("Offset: {offset}");
     // also typically 0
-    let block_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let block_size: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
     println!("Block size: {block_size}");
 
     let mut samples: Vec<i16> = Vec::new();
@@ -159,25 +183,73 @@ pub fn parse(path: &str) -> DecodeResult<AudioFile> {
     for i in (start..end).step_by(2) {
         let s1 = match reader.get(i) {
             Some(val) => *val,
-            None => return Err(DecodeError::UnexpectedEof),
+            None => return Err(DecodeError::UnexpectedEof.at_offset(reader.len())),
         };
         let s2 = match reader.get(i + 1) {
             Some(val) => *val,
-            None => return Err(DecodeError::UnexpectedEof),
+            None => return Err(DecodeError::UnexpectedEof.at_offset(reader.len())),
         };
 
         samples.push(i16::from_be_bytes([s1, s2]));
     }
 
-    let file_name: &str = match path.rsplit_once(|b: char| b == '.') {
-        Some((before, after)) if !before.is_empty() && !after.is_empty() => {
-            match before.rsplit_once(|b: char| b == '/') {
-                Some((assets, name)) => name,
-                None => return Err(DecodeError::InvalidData("File is not nested".to_string())),
+    let mut af = AudioFile::new(path, file_name, "aiff", sample_rate as u32, num_channels, sample_size, samples);
+    // INST and the text chunks are looked for anywhere
+    *chunk = (String::new(), 12);
+    af.root_note = base_note(reader)?;
+    af.meta = Metadata { encoding: "PCM".to_string(), data_bytes: ssnd_size as usize, tags: text_chunks(reader)? };
+    Ok(af)
+}
+
+// NAME, AUTH, (c) and ANNO hold plain text, wherever they are
+fn text_chunks(reader: &[u8]) -> DecodeResult<Vec<(String, String)>> {
+    let mut tags = Vec::new();
+    let mut start = 12;
+    while start + 8 <= reader.len() {
+        let id = &reader[start..start + 4];
+        start += 4;
+        let mut end = start;
+        let size = parse_bytes(reader, &mut start, &mut end, 4)? as usize;
+
+        let name = match id {
+            b"NAME" => Some("title"),
+            b"AUTH" => Some("author"),
+            b"(c) " => Some("copyright"),
+            b"ANNO" => Some("comment"),
+            _ => None,
+        };
+        if let Some(name) = name
+            && let Some(text) = reader.get(start..start + size)
+        {
+            let text = String::from_utf8_lossy(text).trim_end_matches('\0').trim().to_string();
+            if !text.is_empty() {
+                tags.push((name.to_string(), text));
             }
         }
-        _ => return Err(DecodeError::InvalidData("File has no name".to_string())),
-    };
 
-    Ok(AudioFile::new(file_name, "aiff", sample_rate as u32, num_channels, sample_size, samples))
+        start += size + (size & 1);
+    }
+    Ok(tags)
+}
+
+// "INST" holds sampler settings, the first of them the note
+// that plays the sample as it is; it can come before or after
+// SSND
+fn base_note(reader: &[u8]) -> DecodeResult<Option<u8>> {
+    // past FORM, its size and AIFF
+    let mut start = 12;
+    while start + 8 <= reader.len() {
+        let id = &reader[start..start + 4];
+        start += 4;
+        let mut end = start;
+        let size = parse_bytes(reader, &mut start, &mut end, 4)? as usize;
+
+        if id == b"INST" {
+            let note = *reader.get(start).ok_or(DecodeError::UnexpectedEof.at_offset(start))?;
+            return Ok((note <= 127).then_some(note));
+        }
+
+        start += size + (size & 1);
+    }
+    Ok(None)
 }
@@ -0,0 +1,311 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::collections::HashMap;
+use super::decode_helpers::{DecodeResult, DecodeError};
+
+// .blastpack bundles
+//
+// a bundle is a plain ustar archive (so `tar xf` opens it too)
+// whose first member is a `manifest`, followed by the files it lists
+//
+// every manifest line after the header is tab-separated:
+//   kind    path    size    hash    packed
+// - kind is "track" (referenced audio) or "file" (presets, scripts, etc)
+// - path is relative to the directory blast runs from
+// - hash is FNV-1a 64 of the contents, in hex
+// - packed is 1 if the contents follow in the archive, 0 if only
+//   the hash was kept (the asset has to be found on import)
+//
+const MANIFEST: &str = "manifest";
+const HEADER: &str = "blastpack 1";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntryKind {
+    Track,
+    File,
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub kind: EntryKind,
+    pub path: String,
+    pub size: u64,
+    pub hash: u64,
+    pub packed: bool,
+}
+
+// export
+//
+// tracks are packed unless hashes_only is set;
+// other files are always packed, they're small
+pub fn export(pack: &str, tracks: &[String], files: &[String], hashes_only: bool) -> DecodeResult<Vec<Entry>> {
+    let mut entries = Vec::<Entry>::new();
+    let mut contents = Vec::<Vec<u8>>::new();
+
+    let listed = tracks
+        .iter()
+        .map(|p| (EntryKind::Track, p, !hashes_only))
+        .chain(files.iter().map(|p| (EntryKind::File, p, true)));
+
+    for (kind, path, packed) in listed {
+        let bytes = fs::read(path)?;
+        entries.push(Entry {
+            kind,
+            path: member_name(path)?,
+            size: bytes.len() as u64,
+            hash: hash(&bytes),
+            packed,
+        });
+        contents.push(if packed { bytes } else { Vec::new() });
+    }
+
+    let mut out = File::create(pack)?;
+    let manifest = write_manifest(&entries);
+    write_member(&mut out, MANIFEST, manifest.as_bytes())?;
+
+    for (entry, bytes) in entries.iter().zip(contents.iter()) {
+        if entry.packed {
+            write_member(&mut out, &entry.path, bytes)?;
+        }
+    }
+
+    // end of archive is two empty blocks
+    out.write_all(&[0u8; 1024])?;
+
+    Ok(entries)
+}
+
+// import
+//
+// restores every entry under dest; packed entries are written from
+// the archive, unpacked ones are kept if dest already has a file with
+// the same hash, otherwise resolve is asked for a path to copy from
+// (returning None leaves the entry missing)
+//
+// returns the entries that are still missing
+pub fn import<F>(pack: &str, dest: &str, mut resolve: F) -> DecodeResult<Vec<Entry>>
+where
+    F: FnMut(&Entry) -> Option<String>,
+{
+    let mut data = Vec::new();
+    File::open(pack)?.read_to_end(&mut data)?;
+
+    let members = read_members(&data)?;
+    let manifest = members
+        .get(MANIFEST)
+        .ok_or(DecodeError::InvalidData("Bundle has no manifest".to_string()))?;
+    let manifest = std::str::from_utf8(manifest)
+        .map_err(|_| DecodeError::InvalidData("Manifest is not utf-8".to_string()))?;
+
+    let mut missing = Vec::<Entry>::new();
+
+    for entry in read_manifest(manifest)? {
+        let target = Path::new(dest).join(&entry.path);
+
+        if entry.packed {
+            let bytes = members
+                .get(entry.path.as_str())
+                .ok_or(DecodeError::InvalidData(format!("'{}' is listed but not packed", entry.path)))?;
+            if hash(bytes) != entry.hash {
+                return Err(DecodeError::InvalidData(format!("'{}' is corrupted", entry.path)));
+            }
+            write_file(&target, bytes)?;
+            continue;
+        }
+
+        // hash-only entry: already there?
+        if fs::read(&target).is_ok_and(|bytes| hash(&bytes) == entry.hash) {
+            continue;
+        }
+
+        match resolve(&entry) {
+            Some(found) => write_file(&target, &fs::read(found)?)?,
+            None => missing.push(entry),
+        }
+    }
+
+    Ok(missing)
+}
+
+// FNV-1a 64
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+// manifest
+//
+fn write_manifest(entries: &[Entry]) -> String {
+    let mut out = format!("{HEADER}\n");
+    for e in entries {
+        let kind = match e.kind {
+            EntryKind::Track => "track",
+            EntryKind::File => "file",
+        };
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{:016x}\t{}\n",
+            kind, e.path, e.size, e.hash, e.packed as u8
+        ));
+    }
+    out
+}
+
+fn read_manifest(text: &str) -> DecodeResult<Vec<Entry>> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(DecodeError::UnsupportedFormat("Unknown bundle version".to_string()));
+    }
+
+    let bad = |line: &str| DecodeError::InvalidData(format!("Bad manifest line '{line}'"));
+
+    let mut entries = Vec::<Entry>::new();
+    for line in lines.filter(|l| !l.is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [kind, path, size, hash, packed] = fields[..] else {
+            return Err(bad(line));
+        };
+
+        let kind = match kind {
+            "track" => EntryKind::Track,
+            "file" => EntryKind::File,
+            _ => return Err(bad(line)),
+        };
+
+        entries.push(Entry {
+            kind,
+            path: member_name(path)?,
+            size: size.parse().map_err(|_| bad(line))?,
+            hash: u64::from_str_radix(hash, 16).map_err(|_| bad(line))?,
+            packed: packed == "1",
+        });
+    }
+
+    Ok(entries)
+}
+
+// paths are stored relative, and never allowed to climb out of dest
+fn member_name(path: &str) -> DecodeResult<String> {
+    let name = path.trim_start_matches('/');
+    if name.is_empty() || name == MANIFEST || name.split('/').any(|c| c == "..") {
+        return Err(DecodeError::InvalidData(format!("Can't bundle '{path}'")));
+    }
+    Ok(name.to_string())
+}
+
+fn write_file(target: &Path, bytes: &[u8]) -> DecodeResult<()> {
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(target, bytes)?;
+    Ok(())
+}
+
+// ustar
+//
+fn write_member(out: &mut File, name: &str, bytes: &[u8]) -> DecodeResult<()> {
+    out.write_all(&tar_header(name, bytes.len() as u64)?)?;
+    out.write_all(bytes)?;
+
+    let pad = bytes.len().next_multiple_of(512) - bytes.len();
+    out.write_all(&vec![0u8; pad])?;
+
+    Ok(())
+}
+
+fn tar_header(name: &str, size: u64) -> DecodeResult<[u8; 512]> {
+    if name.len() > 100 {
+        return Err(DecodeError::InvalidData(format!("Path too long to bundle: '{name}'")));
+    }
+    if size > 0o77777777777 {
+        return Err(DecodeError::InvalidData(format!("File too large to bundle: '{name}'")));
+    }
+
+    let mut h = [0u8; 512];
+    h[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut h[100..108], 0o644); // mode
+    write_octal(&mut h[108..116], 0); // uid
+    write_octal(&mut h[116..124], 0); // gid
+    write_octal(&mut h[124..136], size);
+    write_octal(&mut h[136..148], 0); // mtime
+    h[156] = b'0'; // regular file
+    h[257..263].copy_from_slice(b"ustar\0");
+    h[263..265].copy_from_slice(b"00");
+
+    // checksum is summed with its own field as spaces,
+    // then stored as six digits, NUL, space
+    h[148..156].fill(b' ');
+    let sum: u64 = h.iter().map(|&b| b as u64).sum();
+    write_octal(&mut h[148..155], sum);
+
+    Ok(h)
+}
+
+// zero-padded, NUL-terminated
+fn write_octal(field: &mut [u8], val: u64) {
+    let digits = field.len() - 1;
+    let s = format!("{:0digits$o}", val);
+    field[..digits].copy_from_slice(s.as_bytes());
+    field[digits] = 0;
+}
+
+fn read_octal(field: &[u8]) -> DecodeResult<u64> {
+    let s: String = field
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| b as char)
+        .collect();
+    u64::from_str_radix(s.trim(), 8)
+        .map_err(|_| DecodeError::InvalidData("Bad number in bundle header".to_string()))
+}
+
+fn read_members(data: &[u8]) -> DecodeResult<HashMap<String, &[u8]>> {
+    let mut members = HashMap::<String, &[u8]>::new();
+    let mut pos = 0;
+
+    while let Some(h) = data.get(pos..pos + 512) {
+        if h.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let field = |range: std::ops::Range<usize>| -> String {
+            h[range]
+                .iter()
+                .take_while(|&&b| b != 0)
+                .map(|&b| b as char)
+                .collect()
+        };
+
+        let stored = read_octal(&h[148..156])?;
+        let sum: u64 = h
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+            .sum();
+        if stored != sum {
+            return Err(DecodeError::InvalidData("Bad checksum in bundle".to_string()));
+        }
+
+        let (name, prefix) = (field(0..100), field(345..500));
+        let name = if prefix.is_empty() { name } else { format!("{prefix}/{name}") };
+        let size = read_octal(&h[124..136])? as usize;
+
+        pos += 512;
+        let body = data
+            .get(pos..pos + size)
+            .ok_or(DecodeError::UnexpectedEof)?;
+
+        // regular files only
+        if h[156] == b'0' || h[156] == 0 {
+            members.insert(name, body);
+        }
+
+        pos += size.next_multiple_of(512);
+    }
+
+    Ok(members)
+}
@@ -1,38 +1,604 @@
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DecodeError {
     Io(std::io::Error),
     UnsupportedFormat(String),
     UnexpectedEof,
     InvalidData(String),
+    // one of the above, and where in which file it happened
+    At(Location, Box<DecodeError>),
 }
 
 pub type DecodeResult<T> = Result<T, DecodeError>;
 
+// as much as is known of where a file went wrong: the parsers note
+// the byte they were reading, and whoever's reading the file the
+// chunk it was in and its path
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Location {
+    pub path: Option<String>,
+    pub chunk: Option<String>, // its ID, e.g. "fmt " or "COMM"
+    pub offset: Option<usize>, // bytes into the file
+}
+
+impl DecodeError {
+    // err happened reading byte offset
+    pub fn at_offset(self, offset: usize) -> Self {
+        self.noted(Location { offset: Some(offset), ..Location::default() })
+    }
+
+    // err happened within chunk (starting at chunk_start) of path,
+    // or no chunk in particular if it's ""; a byte already noted is
+    // kept over the chunk's start
+    pub fn within(self, path: &str, chunk: &str, chunk_start: usize) -> Self {
+        self.noted(Location {
+            path: Some(path.to_string()),
+            chunk: (!chunk.is_empty()).then(|| chunk.to_string()),
+            offset: Some(chunk_start),
+        })
+    }
+
+    // err happened in path, wherever in it
+    pub fn in_file(self, path: &str) -> Self {
+        self.noted(Location { path: Some(path.to_string()), ..Location::default() })
+    }
+
+    fn noted(self, outer: Location) -> Self {
+        match self {
+            DecodeError::At(inner, err) => DecodeError::At(
+                Location {
+                    path: inner.path.or(outer.path),
+                    chunk: inner.chunk.or(outer.chunk),
+                    offset: inner.offset.or(outer.offset),
+                },
+                err,
+            ),
+            err => DecodeError::At(outer, Box::new(err)),
+        }
+    }
+
+    // what went wrong, without where
+    pub fn kind(&self) -> &DecodeError {
+        match self {
+            DecodeError::At(_, err) => err.kind(),
+            err => err,
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            DecodeError::At(location, _) => Some(location),
+            _ => None,
+        }
+    }
+}
+
+// e.g. assets/kick.aif: Comm size should be 18 (COMM chunk, byte 12)
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "{err}"),
+            DecodeError::UnsupportedFormat(what) => write!(f, "unsupported format: {what}"),
+            DecodeError::UnexpectedEof => write!(f, "file ends early"),
+            DecodeError::InvalidData(what) => write!(f, "{what}"),
+            DecodeError::At(location, err) => {
+                if let Some(path) = &location.path {
+                    write!(f, "{path}: ")?;
+                }
+                write!(f, "{err}")?;
+                match (&location.chunk, location.offset) {
+                    (Some(chunk), Some(offset)) => write!(f, " ({} chunk, byte {offset})", chunk.trim_end()),
+                    (Some(chunk), None) => write!(f, " ({} chunk)", chunk.trim_end()),
+                    (None, Some(offset)) => write!(f, " (byte {offset})"),
+                    (None, None) => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// told how far a decode has got, as bytes read and bytes in all,
+// after each PROGRESS_STEP (so a UI can draw a loading bar)
+pub type Progress<'a> = Option<&'a (dyn Fn(usize, usize) + Sync)>;
+
+pub const PROGRESS_STEP: usize = 1 << 20;
+
+// all of path, PROGRESS_STEP at a time
+pub fn read_file(path: &str, progress: Progress) -> DecodeResult<Vec<u8>> {
+    let io = |e: std::io::Error| DecodeError::from(e).in_file(path);
+    let mut f = File::open(path).map_err(io)?;
+    let total = f.metadata().map_err(io)?.len() as usize;
+
+    let mut bytes = Vec::with_capacity(total);
+    loop {
+        let read = (&mut f).take(PROGRESS_STEP as u64).read_to_end(&mut bytes).map_err(io)?;
+        if read == 0 {
+            break;
+        }
+        if let Some(progress) = progress {
+            progress(bytes.len(), total.max(bytes.len()));
+        }
+    }
+    Ok(bytes)
+}
+
+// the ID of the chunk whose header starts at start, and start,
+// for a parser to say which chunk it's in
+pub fn chunk_at(bytes: &[u8], start: usize) -> (String, usize) {
+    let id = bytes.get(start..start + 4).unwrap_or_default();
+    (String::from_utf8_lossy(id).into_owned(), start)
+}
+
 impl From<std::io::Error> for DecodeError {
     fn from(err: std::io::Error) -> Self {
         DecodeError::Io(err)
     }
 }
 
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::mapped::Mapping;
+
 #[derive(Clone, Debug)]
 pub struct AudioFile {
+    pub path: String,
     pub file_name: String,
     pub format: String,
     pub sample_rate: u32,
     pub num_channels: u32,
     pub bits_per_sample: u32,
-    pub samples: Vec<i16>,
+    pub(crate) samples: Samples, // shared by every Voice that plays it
+    pub loop_points: Option<(usize, usize)>, // embedded (start, end) frames, end exclusive
+    pub root_note: Option<u8>, // embedded MIDI note it plays at as it is (AIFF INST, WAV smpl)
+    pub channel_mask: Option<u32>, // which speaker each channel is for (WAV EXTENSIBLE; see SPEAKERS)
+    pub(crate) meta: Metadata,
+}
+
+// a Track's samples, interleaved: decoded into memory, or read in
+// place from a mapped file (see mapped.rs); either way a slice of
+// i16s, and cloned by sharing
+#[derive(Clone)]
+pub enum Samples {
+    Decoded(Arc<[i16]>),
+    Mapped(Arc<Mapping>),
+}
+
+impl Samples {
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, Samples::Mapped(_))
+    }
+
+    // whether a and b are the same samples, not just equal ones
+    pub fn ptr_eq(a: &Samples, b: &Samples) -> bool {
+        match (a, b) {
+            (Samples::Decoded(a), Samples::Decoded(b)) => Arc::ptr_eq(a, b),
+            (Samples::Mapped(a), Samples::Mapped(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    // a read-ahead cursor for a Voice, to post where it's about
+    // to read to, so a mapping can have it paged in by then (see
+    // Mapping::reader); nothing for decoded samples
+    pub fn reader(&self) -> usize {
+        match self {
+            Samples::Mapped(mapping) => mapping.reader(),
+            Samples::Decoded(_) => 0,
+        }
+    }
+
+    pub fn release(&self, reader: usize) {
+        if let Samples::Mapped(mapping) = self {
+            mapping.release(reader);
+        }
+    }
+
+    #[inline]
+    pub fn want(&self, reader: usize, sample: usize) {
+        if let Samples::Mapped(mapping) = self {
+            mapping.want(reader, sample);
+        }
+    }
+}
+
+impl Deref for Samples {
+    type Target = [i16];
+
+    #[inline]
+    fn deref(&self) -> &[i16] {
+        match self {
+            Samples::Decoded(samples) => samples,
+            Samples::Mapped(mapping) => mapping.samples(),
+        }
+    }
+}
+
+impl From<Vec<i16>> for Samples {
+    fn from(samples: Vec<i16>) -> Self {
+        Samples::Decoded(samples.into())
+    }
+}
+
+impl PartialEq for Samples {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+// just how many, and where from: an hour of them is a lot to print
+impl std::fmt::Debug for Samples {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Samples::Decoded(samples) => write!(f, "Decoded({} samples)", samples.len()),
+            Samples::Mapped(mapping) => write!(f, "Mapped({} samples)", mapping.samples().len()),
+        }
+    }
+}
+
+// what a file says about itself besides its samples
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub encoding: String, // as the file names it: PCM, IEEE float, EXTENSIBLE...
+    pub data_bytes: usize, // the samples as stored, before decoding
+    pub tags: Vec<(String, String)>, // title, artist...: WAV's LIST INFO, AIFF's NAME, AUTH, (c) and ANNO
 }
 
 impl AudioFile {
-    pub fn new(file_name: &str, format: &str, sample_rate: u32, num_channels: u32, bits_per_sample: u32, samples: Vec<i16>) -> Self {
+    pub fn new(path: &str, file_name: &str, format: &str, sample_rate: u32, num_channels: u32, bits_per_sample: u32, samples: Vec<i16>) -> Self {
         Self {
+            path: path.to_string(),
             file_name: file_name.to_string(),
             format: format.to_string(),
             sample_rate,
             num_channels,
             bits_per_sample,
-            samples
+            samples: samples.into(),
+            loop_points: None,
+            root_note: None,
+            channel_mask: None,
+            meta: Metadata::default(),
         }
     }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.num_channels.max(1) as usize
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate.max(1) as f64)
+    }
+
+    // read in place from a mapped file rather than decoded
+    pub fn is_mapped(&self) -> bool {
+        self.samples.is_mapped()
+    }
+
+    // the samples as floats in -1..1, still interleaved
+    pub fn samples_f32(&self) -> Vec<f32> {
+        self.samples.iter().map(|&s| to_f32(s)).collect()
+    }
+
+    // channel ch of the samples, frame by frame, without copying
+    // them out; empty for a channel the Track hasn't got
+    pub fn channel(&self, ch: usize) -> impl Iterator<Item = i16> + '_ {
+        let channels = self.num_channels.max(1) as usize;
+        let skip = match ch < channels {
+            true => ch,
+            false => self.samples.len(),
+        };
+        self.samples.iter().skip(skip).step_by(channels).copied()
+    }
+
+    pub fn channel_f32(&self, ch: usize) -> impl Iterator<Item = f32> + '_ {
+        self.channel(ch).map(to_f32)
+    }
+
+    // every channel as its own Vec of floats in -1..1
+    pub fn deinterleaved_f32(&self) -> Vec<Vec<f32>> {
+        (0..self.num_channels as usize).map(|ch| self.channel_f32(ch).collect()).collect()
+    }
+
+    // converts the samples (and loop points) to rate, so Voices
+    // play them as they are rather than stepping through them at
+    // another rate
+    //
+    // band-limited interpolation: each output sample is the input
+    // around it through a Kaiser-windowed sinc, cut off just under
+    // the lower of the two Nyquists, so going down doesn't alias
+    // and going up doesn't image; the kernel is tabulated once and
+    // interpolated between its phases
+    pub fn resample_to(&mut self, rate: u32) {
+        if self.sample_rate == rate || self.sample_rate == 0 || rate == 0 {
+            return;
+        }
+
+        let channels = self.num_channels.max(1) as usize;
+        let frames = self.frames();
+        let ratio = rate as f64 / self.sample_rate as f64;
+        let out_frames = (frames as f64 * ratio).round() as usize;
+
+        // in input samples: the cutoff (1 is the input's Nyquist)
+        // and how far either side of a sample its kernel reaches
+        let cutoff = ratio.min(1.0) * ROLLOFF;
+        let reach = SINC_ZEROS as f64 / cutoff;
+        let table = sinc_table();
+        let kernel = |x: f64| {
+            let pos = (x * cutoff).abs() * SINC_PHASES as f64;
+            let i = pos as usize;
+            match table.get(i + 1) {
+                Some(next) => {
+                    let frac = pos - i as f64;
+                    cutoff * (table[i] + (next - table[i]) * frac)
+                }
+                None => 0.0,
+            }
+        };
+
+        let mut samples = Vec::<i16>::with_capacity(out_frames * channels);
+        let mut acc = vec![0f64; channels];
+        for n in 0..out_frames {
+            let t = n as f64 / ratio;
+            let first = (t - reach).ceil().max(0.0) as usize;
+            let last = ((t + reach).floor() as usize).min(frames.saturating_sub(1));
+
+            acc.fill(0.0);
+            for k in first..=last {
+                let w = kernel(t - k as f64);
+                let frame = &self.samples[k * channels..(k + 1) * channels];
+                for (sum, &x) in acc.iter_mut().zip(frame) {
+                    *sum += w * x as f64;
+                }
+            }
+            samples.extend(acc.iter().map(|sum| sum.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16));
+        }
+
+        self.loop_points = self.loop_points.map(|(s, e)| {
+            ((s as f64 * ratio).round() as usize, (e as f64 * ratio).round() as usize)
+        });
+        self.samples = samples.into();
+        self.sample_rate = rate;
+    }
+
+    // the Track folded down to one channel
+    pub fn to_mono(&self) -> AudioFile {
+        self.to_channels(1)
+    }
+
+    // the Track as a left and right pair: mono on both, anything
+    // wider folded down
+    pub fn to_stereo(&self) -> AudioFile {
+        self.to_channels(2)
+    }
+
+    // by its channel mask, if it has one
+    pub fn to_channels(&self, channels: usize) -> AudioFile {
+        self.remap(&ChannelMap::for_layout(self.channel_mask, self.num_channels as usize, channels))
+    }
+
+    // each output channel the sum of the Track's through map;
+    // what's past i16 is clipped, which a map from between()
+    // can't reach
+    pub fn remap(&self, map: &ChannelMap) -> AudioFile {
+        let sources = self.num_channels.max(1) as usize;
+        let mut samples = Vec::<i16>::with_capacity(self.frames() * map.outputs());
+        for frame in self.samples.chunks_exact(sources) {
+            for row in &map.gains {
+                let sum: f32 = row.iter().zip(frame).map(|(g, &x)| g * x as f32).sum();
+                samples.push(sum.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+            }
+        }
+
+        AudioFile {
+            num_channels: map.outputs() as u32,
+            samples: samples.into(),
+            channel_mask: None,
+            ..self.clone()
+        }
+    }
+}
+
+// a sample as a float, full scale (i16::MIN) being -1; dividing by
+// a power of two, so the conversion is exact and 0 stays 0
+#[inline]
+pub fn to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+// gains from each of one layout's channels (sources) to each of
+// another's (outputs), for converting a Track's channels; the
+// shape of a Routing, but between files rather than to a device
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelMap {
+    gains: Vec<Vec<f32>>, // [output][source]
+}
+
+// WAVE_FORMAT_EXTENSIBLE's speaker positions, in the order of the
+// channel mask's bits; a file's channels take the set bits in turn
+pub const SPEAKERS: [&str; 18] = [
+    "FL", "FR", "FC", "LFE", "BL", "BR", "FLC", "FRC", "BC",
+    "SL", "SR", "TC", "TFL", "TFC", "TFR", "TBL", "TBC", "TBR",
+];
+
+// the speakers a channel mask names, one per channel
+pub fn speakers(mask: u32) -> Vec<&'static str> {
+    SPEAKERS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| mask & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+// what a channel is for, as far as folding it down goes
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    Left,
+    Right,
+    Center, // front or back
+    Lfe,
+    LeftSurround, // side, back or height
+    RightSurround,
+}
+
+impl ChannelMap {
+    pub fn new(gains: Vec<Vec<f32>>) -> Self {
+        Self { gains }
+    }
+
+    // the usual conversion between channel counts:
+    // - mono is copied to the first two outputs (as Routing does)
+    // - 3 to 8 channels, in WAV's order (L R C LFE, then the
+    //   surrounds), fold down to stereo as in ITU-R BS.775: the
+    //   center and surrounds 3 dB down into their sides, the LFE
+    //   left out; mono is the two sides of that summed
+    // - any other pairing keeps the channels both have, folding
+    //   extra sources onto the outputs round-robin
+    //
+    // a downmix is then scaled so no output's gains add up to more
+    // than 1: a full scale source never clips, at the cost of
+    // quieter (e.g. -6 dB for one side of a hard-panned stereo
+    // pair) downmixes
+    pub fn between(sources: usize, outputs: usize) -> Self {
+        Self::for_layout(None, sources, outputs)
+    }
+
+    // as between, but the sources are the speakers mask names (if
+    // it names as many as there are), rather than WAV's order for
+    // so many channels
+    pub fn for_layout(mask: Option<u32>, sources: usize, outputs: usize) -> Self {
+        let mut map = Self { gains: vec![vec![0.0; sources]; outputs] };
+        if sources == 0 || outputs == 0 {
+            return map;
+        }
+
+        let layout = match mask.and_then(|mask| mask_roles(mask, sources)) {
+            Some(layout) => Some(layout),
+            None => roles(sources).map(<[Role]>::to_vec),
+        };
+        match (sources, outputs, layout) {
+            (1, _, _) => {
+                for out in 0..outputs.min(2) {
+                    map.gains[out][0] = 1.0;
+                }
+            }
+            (_, 1 | 2, Some(layout)) => {
+                let half = std::f32::consts::FRAC_1_SQRT_2;
+                for (src, role) in layout.iter().enumerate() {
+                    let (l, r) = match role {
+                        Role::Left => (1.0, 0.0),
+                        Role::Right => (0.0, 1.0),
+                        Role::Center => (half, half),
+                        Role::Lfe => (0.0, 0.0),
+                        Role::LeftSurround => (half, 0.0),
+                        Role::RightSurround => (0.0, half),
+                    };
+                    match outputs {
+                        1 => map.gains[0][src] = half * (l + r),
+                        _ => (map.gains[0][src], map.gains[1][src]) = (l, r),
+                    }
+                }
+            }
+            (2, 1, _) => map.gains[0] = vec![0.5; 2],
+            _ => {
+                let folds = sources.div_ceil(outputs);
+                for src in 0..sources {
+                    map.gains[src % outputs][src] = 1.0 / folds as f32;
+                }
+            }
+        }
+
+        for row in &mut map.gains {
+            let total: f32 = row.iter().sum();
+            if total > 1.0 {
+                row.iter_mut().for_each(|g| *g /= total);
+            }
+        }
+        map
+    }
+
+    pub fn sources(&self) -> usize {
+        self.gains.first().map_or(0, Vec::len)
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.gains.len()
+    }
+
+    pub fn gain(&self, src: usize, out: usize) -> f32 {
+        self.gains.get(out).and_then(|row| row.get(src)).copied().unwrap_or(0.0)
+    }
+}
+
+// WAV's default speaker order for each channel count it's used for
+fn roles(channels: usize) -> Option<&'static [Role]> {
+    use Role::*;
+    Some(match channels {
+        3 => &[Left, Right, Center],
+        4 => &[Left, Right, LeftSurround, RightSurround],
+        5 => &[Left, Right, Center, LeftSurround, RightSurround],
+        6 => &[Left, Right, Center, Lfe, LeftSurround, RightSurround],
+        7 => &[Left, Right, Center, Lfe, Center, LeftSurround, RightSurround],
+        8 => &[Left, Right, Center, Lfe, LeftSurround, RightSurround, LeftSurround, RightSurround],
+        _ => return None,
+    })
+}
+
+// the roles of the speakers in mask, if it names one per channel
+fn mask_roles(mask: u32, channels: usize) -> Option<Vec<Role>> {
+    use Role::*;
+    let layout: Vec<Role> = speakers(mask)
+        .iter()
+        .map(|speaker| match *speaker {
+            "FL" | "FLC" => Left,
+            "FR" | "FRC" => Right,
+            "FC" | "BC" | "TC" | "TFC" | "TBC" => Center,
+            "LFE" => Lfe,
+            "BL" | "SL" | "TFL" | "TBL" => LeftSurround,
+            _ => RightSurround,
+        })
+        .collect();
+    (layout.len() == channels).then_some(layout)
+}
+
+// the resampler's kernel: how many zero crossings it spans either
+// side, how finely each is tabulated, and how far under Nyquist it
+// cuts off (leaving the window room to roll off)
+const SINC_ZEROS: usize = 32;
+const SINC_PHASES: usize = 512;
+const ROLLOFF: f64 = 0.96;
+const KAISER_BETA: f64 = 9.0; // about 90 dB down in the stopband
+
+// sinc(x) * kaiser(x / SINC_ZEROS) at every 1 / SINC_PHASES of x
+// from 0 to SINC_ZEROS; the kernel's symmetric, so only one side
+fn sinc_table() -> Vec<f64> {
+    use std::f64::consts::PI;
+
+    let i0 = |x: f64| {
+        // the modified Bessel function, by its series
+        let (mut sum, mut term, mut k) = (1.0, 1.0, 1.0);
+        while term > sum * 1e-12 {
+            term *= (x / (2.0 * k)).powi(2);
+            sum += term;
+            k += 1.0;
+        }
+        sum
+    };
+    let len = SINC_ZEROS * SINC_PHASES;
+    (0..=len)
+        .map(|j| {
+            let x = j as f64 / SINC_PHASES as f64;
+            let sinc = match j {
+                0 => 1.0,
+                _ => (PI * x).sin() / (PI * x),
+            };
+            let r = j as f64 / len as f64;
+            sinc * i0(KAISER_BETA * (1.0 - r * r).sqrt()) / i0(KAISER_BETA)
+        })
+        .collect()
 }
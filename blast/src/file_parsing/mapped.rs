@@ -0,0 +1,228 @@
+use std::fs::File;
+use std::ops::Range;
+use std::sync::{
+    Arc, Mutex, OnceLock, Weak,
+    atomic::{AtomicUsize, Ordering},
+};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+use super::decode_helpers::{DecodeError, DecodeResult};
+
+// mapped
+//
+// an hour of stereo at 48 kHz is over 600MB of samples; rather
+// than decode all of it into memory, a big 16-bit PCM WAV is
+// mapped, and its data chunk (which on a little-endian machine
+// already is the samples) read where it lies: the kernel pages it
+// in as it's played, and can drop it again when memory's short
+//
+// a page that isn't in yet would be read from disk on the audio
+// thread, so a read-ahead thread keeps the pages around each
+// playing Voice in: a Voice holds a cursor of its own (a reader)
+// and posts where it's reading to it (a store to an atomic, once
+// a block), and the thread faults in READ_AHEAD either side of
+// every cursor. it sleeps until a Voice has moved far enough from
+// where it was last read ahead for, and is woken (unparked) then,
+// so posting is still just a store most blocks. past READERS
+// Voices on one mapping, the rest share the last cursor
+//
+// wav::open maps files of MAP_BYTES or more; smaller ones, and
+// any in another format, are decoded as before
+//
+
+// files this big or bigger are mapped (wav::open)
+pub const MAP_BYTES: usize = 64 << 20;
+// kept paged in around a Voice's position
+pub const READ_AHEAD: Duration = Duration::from_secs(2);
+// Voices on one mapping with a cursor each
+const READERS: usize = 32;
+// a cursor no Voice holds
+const FREE: usize = usize::MAX;
+// a cursor held, with nothing posted to it yet
+const IDLE: usize = usize::MAX - 1;
+
+pub struct Mapping {
+    ptr: *const u8,
+    len: usize,
+    data: Range<usize>, // the samples, in bytes from the start of the file
+    ahead: usize, // READ_AHEAD, in bytes
+    cursors: [AtomicUsize; READERS], // the sample each reader last read from
+    read: [AtomicUsize; READERS], // where each was when it was last read ahead for
+}
+
+// the mapping is read only, and only unmapped when dropped
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    // maps all of path, read only
+    #[cfg(unix)]
+    pub fn open(path: &str) -> DecodeResult<Self> {
+        use std::os::fd::AsRawFd;
+
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // the file can close; the mapping holds on
+        Ok(Self {
+            ptr: ptr as *const u8,
+            len,
+            data: 0..0,
+            ahead: 0,
+            // the last is shared, so it's never free
+            cursors: std::array::from_fn(|i| AtomicUsize::new(if i == READERS - 1 { IDLE } else { FREE })),
+            read: std::array::from_fn(|_| AtomicUsize::new(IDLE)),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn open(path: &str) -> DecodeResult<Self> {
+        let _ = File::open(path)?;
+        Err(DecodeError::UnsupportedFormat("Mapping files isn't supported here".to_string()))
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    // takes data (the data chunk's bytes) as the samples, and
+    // bytes_per_sec of them as a second, then starts reading ahead;
+    // the chunk has to start on a sample, and samples have to be
+    // little-endian i16s, to be read in place
+    pub fn into_samples(mut self, data: Range<usize>, bytes_per_sec: usize) -> DecodeResult<Arc<Self>> {
+        if cfg!(target_endian = "big") {
+            return Err(DecodeError::UnsupportedFormat("Mapped samples have to be little-endian".to_string()));
+        }
+        if data.end > self.len {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        if !data.start.is_multiple_of(2) {
+            return Err(DecodeError::InvalidData("Data chunk doesn't start on a sample".to_string()));
+        }
+        self.data = data.start..data.end - (data.len() & 1);
+        self.ahead = bytes_per_sec * READ_AHEAD.as_secs() as usize;
+
+        let mapping = Arc::new(self);
+        mapping.touch(0);
+        read_ahead_for(&mapping);
+        Ok(mapping)
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        // aligned, as the page is and data.start is even
+        unsafe { std::slice::from_raw_parts(self.ptr.add(self.data.start) as *const i16, self.data.len() / 2) }
+    }
+
+    // a cursor for a Voice to post to, until it's released; a
+    // free one if there's one left, else the shared last one.
+    // doesn't lock or allocate, so a Voice can be made anywhere
+    pub fn reader(&self) -> usize {
+        (0..READERS - 1)
+            .find(|&i| self.cursors[i].compare_exchange(FREE, IDLE, Ordering::AcqRel, Ordering::Relaxed).is_ok())
+            .unwrap_or(READERS - 1)
+    }
+
+    pub fn release(&self, reader: usize) {
+        if reader < READERS - 1 {
+            self.read[reader].store(IDLE, Ordering::Relaxed);
+            self.cursors[reader].store(FREE, Ordering::Release);
+        }
+    }
+
+    // where a reader is, as an index into samples(); a store,
+    // and a wake of the read-ahead thread once it's gone a
+    // quarter of READ_AHEAD from where that last read for it
+    #[inline]
+    pub fn want(&self, reader: usize, sample: usize) {
+        self.cursors[reader].store(sample, Ordering::Relaxed);
+        let read = self.read[reader].load(Ordering::Relaxed);
+        if (read == IDLE || sample.abs_diff(read) > self.ahead / 8)
+            && let Some(thread) = READ_AHEAD_THREAD.get()
+        {
+            thread.unpark();
+        }
+    }
+
+    // reads ahead for each reader that's moved on since it last did
+    fn read_ahead(&self) {
+        for (cursor, read) in self.cursors.iter().zip(&self.read) {
+            let at = cursor.load(Ordering::Relaxed);
+            if at < IDLE && read.swap(at, Ordering::Relaxed) != at {
+                self.touch(at);
+            }
+        }
+    }
+
+    // touches each page within READ_AHEAD of sample,
+    // so it's there when it's played
+    fn touch(&self, sample: usize) {
+        let page = page_size();
+        let at = self.data.start + sample * 2;
+        let from = at.saturating_sub(self.ahead).max(self.data.start) / page * page;
+        let to = (at + self.ahead).min(self.data.end);
+        #[cfg(unix)]
+        if to > from {
+            unsafe {
+                libc::madvise(self.ptr.add(from) as *mut libc::c_void, to - from, libc::MADV_WILLNEED);
+            }
+        }
+        for offset in (from..to).step_by(page) {
+            unsafe { std::ptr::read_volatile(self.ptr.add(offset)) };
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    (unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).max(1) as usize
+}
+
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+// every mapping still held somewhere; the read-ahead thread
+// drops the ones that aren't
+static MAPPINGS: Mutex<Vec<Weak<Mapping>>> = Mutex::new(Vec::new());
+// parked until a reader needs it (see Mapping::want)
+static READ_AHEAD_THREAD: OnceLock<Thread> = OnceLock::new();
+
+fn read_ahead_for(mapping: &Arc<Mapping>) {
+    MAPPINGS.lock().unwrap().push(Arc::downgrade(mapping));
+    READ_AHEAD_THREAD.get_or_init(|| {
+        thread::spawn(|| loop {
+            let live: Vec<Arc<Mapping>> = {
+                let mut mappings = MAPPINGS.lock().unwrap();
+                mappings.retain(|m| m.strong_count() > 0);
+                mappings.iter().filter_map(Weak::upgrade).collect()
+            };
+            for mapping in &live {
+                mapping.read_ahead();
+            }
+            // the last hold on a mapping may be this one
+            drop(live);
+            thread::park();
+        })
+        .thread()
+        .clone()
+    });
+}
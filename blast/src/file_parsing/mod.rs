@@ -1,4 +1,11 @@
 pub mod aiff;
+pub mod bundle;
 pub mod decode_helpers;
+pub mod mapped;
+// frames are parsed but not yet decoded, so nothing calls in
+#[allow(dead_code)]
 pub mod mpeg;
+pub mod smf;
+pub mod stems;
+pub mod validate;
 pub mod wav;
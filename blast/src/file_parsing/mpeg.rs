@@ -1,7 +1,10 @@
+// variable names mirror the header bit layout (AAAB_BCCD etc.)
+#![allow(non_snake_case)]
+
 use std::fs::File;
-use std::io::{self, Read, SeekFrom};
+use std::io::{self, Read};
 use std::collections::HashMap;
-use super::decode_helpers::{AudioFile, DecodeResult, DecodeError};
+use super::decode_helpers::{DecodeResult, DecodeError};
 
 // iterate through frames by frame size
 pub fn parse(path: &str) -> DecodeResult<Vec<u8>> {
@@ -15,38 +18,27 @@ pub fn parse(path: &str) -> DecodeResult<Vec<u8>> {
 
     // find any two bytes that look like frame sync
     while cur < file_len {
-        if let b = reader[cur] {
-            if b == 0xFF {
-                if reader[cur + 1] & 0xE0 == 0xE0 {
-                    let fp = cur;
-                    let mut supb: usize = 0;
-                    supb = ((reader[cur] as usize) << 24);
-                    cur += 1;
-                    if cur >= file_len {
-                        break;
-                    }
-                    supb |= ((reader[cur] as usize) << 16);
-                    cur += 1;
-                    if cur >= file_len {
-                        break;
-                    }
-                    supb |= ((reader[cur] as usize) << 8);
-                    cur += 1;
-                    if cur >= file_len {
-                        break;
-                    }
-                    supb |= reader[cur] as usize;
-                    possibles.entry(supb).or_insert(vec![fp]).push(fp);
-                    cur += 1;
-                } else {
-                    cur += 1;
-                }
-            } else {
-                cur += 1;
+        if reader[cur] == 0xFF && reader[cur + 1] & 0xE0 == 0xE0 {
+            let fp = cur;
+            let mut supb: usize = (reader[cur] as usize) << 24;
+            cur += 1;
+            if cur >= file_len {
+                break;
             }
-        } else {
-            break;
+            supb |= (reader[cur] as usize) << 16;
+            cur += 1;
+            if cur >= file_len {
+                break;
+            }
+            supb |= (reader[cur] as usize) << 8;
+            cur += 1;
+            if cur >= file_len {
+                break;
+            }
+            supb |= reader[cur] as usize;
+            possibles.entry(supb).or_insert(vec![fp]).push(fp);
         }
+        cur += 1;
     }
    
     // sort possible headers by frequency (most to least frequent)
@@ -58,19 +50,17 @@ pub fn parse(path: &str) -> DecodeResult<Vec<u8>> {
     });
    
     // get a reference header to validate less common headers
-    let mut refheader: Header = Header::new(); 
     let mut i = 0;
-    loop {
-        let (pos_ref, indices) = vecs[i];
+    let refheader: Header = loop {
+        let (pos_ref, _indices) = vecs[i];
         match parse_header(pos_ref) {
             Ok((v, l, p, br, sr, pd, cm)) => {
-                refheader = Header::format(v, l, p, br, sr, pd, cm);
-                break;
+                break Header::format(v, l, p, br, sr, pd, cm);
             },
             Err(error) => eprintln!("{:?}", error),
         };
         i += 1;
-    }
+    };
 
     // if a header is valid, compare it to the reference;
     // if matches the reference, get frame length and collect data
@@ -89,12 +79,9 @@ pub fn parse(path: &str) -> DecodeResult<Vec<u8>> {
                             };
                         
                             for index in indices {
-                                let mut frame_data: Vec<u8> = Vec::with_capacity(frame_len);
                                 let start = index + skip;
                                 let end = start + frame_len;
-                                for i in start..end {
-                                    frame_data.push(reader[i]);
-                                }
+                                let frame_data: Vec<u8> = reader[start..end].to_vec();
                                 frames.push(Frame::new(*index, frame_data));
                             }
 
@@ -139,18 +126,6 @@ struct Header {
 }
 
 impl Header {
-    fn new() -> Self {
-        Self { 
-            version: 0f32, 
-            layer: 0, 
-            protected: false, 
-            bitrate: 0, 
-            sr: 0f64, 
-            padded: false, 
-            channel_mode: 0 
-        }
-    }
-
     fn format(version: u8, layer: u8, not_protected: u8, bitrate: u32, sr: f64, padded: u8, channel_mode: u8) -> Self {
         let version: f32 = match version {
             0x0 => 2.5f32,
@@ -166,15 +141,9 @@ impl Header {
             _   => 0, // check if greater than 0
         };
 
-        let protected: bool = match not_protected {
-            0 => true,
-            _ => false,
-        };
+        let protected: bool = not_protected == 0;
 
-        let padded: bool = match padded {
-            1 => true,
-            _ => false,
-        };
+        let padded: bool = padded == 1;
 
         Self {
             version,
@@ -192,15 +161,10 @@ impl Header {
     }
 
     fn match_ref(&self, other: &Header) -> bool {
-        if self.version == other.version
+        self.version == other.version
         && self.layer == other.layer
         && self.sr == other.sr
-        && self.channel_mode == other.channel_mode
-        && self.protected == other.protected {
-            return true;
-        } else {
-            return false;
-        }
+        && self.channel_mode == other.channel_mode && self.protected == other.protected
     }
 
     // returns frame length in bytes
@@ -209,9 +173,9 @@ impl Header {
    
         let br: f64 = br as f64 * 1000f64;
         let frame_len: f64 = match layer {
-            3 => 144f64 * br as f64 / sr,
-            2 => 144f64 * br as f64 / sr,
-            1 => (12f64 * br as f64 / sr) * 4f64,
+            3 => 144f64 * br / sr,
+            2 => 144f64 * br / sr,
+            1 => (12f64 * br / sr) * 4f64,
             _ => 20f64, // dummy number (this will never trigger)
         };
 
@@ -302,14 +266,15 @@ fn match_sr(FFGH: &u8, v_id: &u8) -> f64 {
     sr
 }
 
-fn skiparound(reader: &mut Vec<u8>, cur: &mut usize) {
+#[allow(dead_code)] // manual frame-stepping aid for debugging
+fn skiparound(reader: &[u8], cur: &mut usize) {
     loop {
         let mut input = String::new();
         io::stdin().read_line(&mut input).expect("Failure");
         let input = input.trim();
         let isok = input.parse::<i32>().is_ok();
         if isok {
-            let sign = input.chars().nth(0).unwrap();
+            let sign = input.chars().next().unwrap();
             if sign == '-' {
                 let parsed = &input[1..].parse::<usize>().unwrap();
                 *cur -= parsed;
@@ -365,9 +330,7 @@ fn skiparound(reader: &mut Vec<u8>, cur: &mut usize) {
 
 // cur is set at the fourth byte in the header
 fn parse_header(bytes: &usize) -> DecodeResult<(u8, u8, u8, u32, f64, u8, u8)> {
-    let unex_eof = DecodeError::UnexpectedEof;
-    
-    let AAAB_BCCD = (bytes >> 16) as u8 else { return Err(unex_eof) };
+    let AAAB_BCCD = (bytes >> 16) as u8;
     // AAA
     // (23-21) = guaranteed set at this point
     //
@@ -384,12 +347,12 @@ fn parse_header(bytes: &usize) -> DecodeResult<(u8, u8, u8, u32, f64, u8, u8)> {
 
     print!("MPEG Version ");
     match version {
-        0x0 => print!("2.5\n"),
+        0x0 => println!("2.5"),
         0x1 => {
             return Err(DecodeError::UnsupportedFormat(String::from("Unsupported audio version")));
         },
-        0x2 => print!("2\n"),
-        0x3 => print!("1\n"),
+        0x2 => println!("2"),
+        0x3 => println!("1"),
         _   => {
             return Err(DecodeError::InvalidData(String::from("Invalid audio version id")));
         },
@@ -407,9 +370,9 @@ fn parse_header(bytes: &usize) -> DecodeResult<(u8, u8, u8, u32, f64, u8, u8)> {
         0x0 => {
             return Err(DecodeError::UnsupportedFormat(String::from("Cannot parse reserved layer")))
         },
-        0x1 => print!("III\n"),
-        0x2 => print!("II\n"),
-        0x3 => print!("I\n"),
+        0x1 => println!("III"),
+        0x2 => println!("II"),
+        0x3 => println!("I"),
         _   => {
             return Err(DecodeError::InvalidData(String::from("Invalid layer description")))
         },
@@ -425,14 +388,14 @@ fn parse_header(bytes: &usize) -> DecodeResult<(u8, u8, u8, u32, f64, u8, u8)> {
         println!("Protected");
     }
     
-    let EEEE_FFGH = (bytes >> 8) as u8 else { return Err(unex_eof) };
+    let EEEE_FFGH = (bytes >> 8) as u8;
     // EEEE
     // (15,12) = bitrate index
     // this depends on combinations of version (V) and layer (L)
     // apply V2 to V2.5
     // 0000 and 1111 are not allowed
     let EEEE = EEEE_FFGH >> 4;
-    let mut bitrate: u32;
+    let bitrate: u32;
     if EEEE == 0 || EEEE == 0xF {
         return Err(DecodeError::UnsupportedFormat(String::from("This application does not support 'free' or 'bad' bitrates")));
     } else {
@@ -463,7 +426,7 @@ fn parse_header(bytes: &usize) -> DecodeResult<(u8, u8, u8, u32, f64, u8, u8)> {
     // (8) = private bit
     // ignore
     //
-    let IIJJ_KLMM = *bytes as u8 else { return Err(unex_eof) };
+    let IIJJ_KLMM = *bytes as u8;
     // I
     // (7,6) = channel mode
     let IIJJ = IIJJ_KLMM >> 4;
@@ -483,7 +446,7 @@ fn parse_header(bytes: &usize) -> DecodeResult<(u8, u8, u8, u32, f64, u8, u8)> {
 
     // bits 3-0 are not pertinent
    
-    println!("");
+    println!();
     Ok((
         version,
         layer,
@@ -0,0 +1,190 @@
+use std::fs;
+
+use super::decode_helpers::{DecodeError, DecodeResult};
+
+// smf
+//
+// Standard MIDI Files, as far as a Seq needs them: a clip of
+// notes by tick. written as format 0 (one track, each note a
+// sixteenth long); read from format 0 or 1, every track's notes
+// merged, anything that isn't a note skipped
+//
+
+// ticks per quarter note written (a Seq's step unit is a quarter)
+pub const PPQ: u16 = 480;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+    pub ppq: u16, // ticks per quarter note
+    pub notes: Vec<Note>, // in order of tick
+    pub len: u32, // in ticks, where the clip ends (its end of track)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub tick: u32,
+    pub key: u8,
+    pub velocity: u8, // 1..=127
+}
+
+pub fn write(path: &str, clip: &Clip) -> DecodeResult<()> {
+    fs::write(path, encode(clip)?)?;
+    Ok(())
+}
+
+pub fn read(path: &str) -> DecodeResult<Clip> {
+    decode(&fs::read(path)?)
+}
+
+// a note too close to the last tick to end is an error
+pub fn encode(clip: &Clip) -> DecodeResult<Vec<u8>> {
+    let gate = (clip.ppq as u32 / 4).max(1);
+
+    // (tick, status, key, velocity); at the same tick a note
+    // ends before the next starts
+    let mut events = Vec::<(u32, u8, u8, u8)>::new();
+    for note in &clip.notes {
+        let off = note.tick.checked_add(gate).ok_or_else(|| {
+            DecodeError::InvalidData(format!("note at tick {} ends past the last tick", note.tick))
+        })?;
+        events.push((note.tick, 0x90, note.key & 0x7f, note.velocity.clamp(1, 127)));
+        events.push((off, 0x80, note.key & 0x7f, 0));
+    }
+    events.sort_by_key(|&(tick, status, _, _)| (tick, status));
+
+    let mut track = Vec::<u8>::new();
+    let mut now = 0;
+    for (tick, status, key, velocity) in events {
+        push_vlq(&mut track, tick - now);
+        track.extend_from_slice(&[status, key, velocity]);
+        now = tick;
+    }
+    push_vlq(&mut track, clip.len.saturating_sub(now));
+    track.extend_from_slice(&[0xff, 0x2f, 0x00]);
+
+    let mut bytes = Vec::<u8>::new();
+    bytes.extend_from_slice(b"MThd");
+    bytes.extend_from_slice(&6u32.to_be_bytes());
+    bytes.extend_from_slice(&0u16.to_be_bytes()); // format 0
+    bytes.extend_from_slice(&1u16.to_be_bytes()); // one track
+    bytes.extend_from_slice(&clip.ppq.to_be_bytes());
+    bytes.extend_from_slice(b"MTrk");
+    bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&track);
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> DecodeResult<Clip> {
+    if bytes.get(0..4) != Some(b"MThd".as_slice()) {
+        return Err(DecodeError::UnsupportedFormat("not a MIDI file".to_string()));
+    }
+    let header_len = be32(bytes, 4)? as usize;
+    let format = be16(bytes, 8)?;
+    let division = be16(bytes, 12)?;
+    if format > 1 {
+        return Err(DecodeError::UnsupportedFormat(format!("MIDI file format {format}")));
+    }
+    if division & 0x8000 != 0 || division == 0 {
+        return Err(DecodeError::UnsupportedFormat("SMPTE time division".to_string()));
+    }
+
+    let mut notes = Vec::<Note>::new();
+    let mut len = 0;
+    let mut at = 8 + header_len;
+    while at + 8 <= bytes.len() {
+        let chunk_len = be32(bytes, at + 4)? as usize;
+        let body = bytes.get(at + 8..at + 8 + chunk_len).ok_or(DecodeError::UnexpectedEof)?;
+        // other chunks are for someone else
+        if &bytes[at..at + 4] == b"MTrk" {
+            len = len.max(read_track(body, &mut notes)?);
+        }
+        at += 8 + chunk_len;
+    }
+
+    notes.sort_by_key(|note| note.tick);
+    Ok(Clip { ppq: division, notes, len })
+}
+
+// adds the track's note ons to notes; the tick it ends on
+fn read_track(track: &[u8], notes: &mut Vec<Note>) -> DecodeResult<u32> {
+    let mut at = 0;
+    let mut tick = 0u32;
+    let mut running: Option<u8> = None;
+    while at < track.len() {
+        tick = tick
+            .checked_add(read_vlq(track, &mut at)?)
+            .ok_or_else(|| DecodeError::InvalidData("track runs past the last tick".to_string()))?;
+        let first = *track.get(at).ok_or(DecodeError::UnexpectedEof)?;
+        let status = match first {
+            0x80.. => {
+                at += 1;
+                first
+            }
+            // running status: the last one's, and this is data
+            _ => running.ok_or_else(|| DecodeError::InvalidData("data byte without a status".to_string()))?,
+        };
+        match status {
+            0xff => {
+                let kind = *track.get(at).ok_or(DecodeError::UnexpectedEof)?;
+                at += 1;
+                let len = read_vlq(track, &mut at)? as usize;
+                at += len;
+                if kind == 0x2f {
+                    break;
+                }
+            }
+            0xf0 | 0xf7 => {
+                let len = read_vlq(track, &mut at)? as usize;
+                at += len;
+                running = None;
+            }
+            0xf1..=0xfe => return Err(DecodeError::InvalidData(format!("system message {status:#x} in a track"))),
+            _ => {
+                running = Some(status);
+                let data = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+                let msg = track.get(at..at + data).ok_or(DecodeError::UnexpectedEof)?;
+                if status & 0xf0 == 0x90 && msg[1] > 0 {
+                    notes.push(Note { tick, key: msg[0], velocity: msg[1] });
+                }
+                at += data;
+            }
+        }
+    }
+    Ok(tick)
+}
+
+fn push_vlq(out: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+fn read_vlq(bytes: &[u8], at: &mut usize) -> DecodeResult<u32> {
+    let mut value = 0u32;
+    for _ in 0..4 {
+        let b = *bytes.get(*at).ok_or(DecodeError::UnexpectedEof)?;
+        *at += 1;
+        value = (value << 7) | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::InvalidData("variable-length number over 4 bytes".to_string()))
+}
+
+fn be16(bytes: &[u8], at: usize) -> DecodeResult<u16> {
+    let b = bytes.get(at..at + 2).ok_or(DecodeError::UnexpectedEof)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn be32(bytes: &[u8], at: usize) -> DecodeResult<u32> {
+    let b = bytes.get(at..at + 4).ok_or(DecodeError::UnexpectedEof)?;
+    Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
@@ -0,0 +1,88 @@
+use std::fs;
+
+use super::{aiff, wav, decode_helpers::{AudioFile, DecodeError, DecodeResult}};
+
+// stems
+//
+// a directory of multitrack stems, decoded and checked to line
+// up: the same sample rate and the same length, so Voices made
+// from them stay in lockstep
+//
+// stems that don't line up are refused, unless asked to be
+// resampled (to the rate most of them share) or padded (with
+// silence, to the longest)
+//
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Fixes {
+    pub resample: bool,
+    pub pad: bool,
+}
+
+// sorted by name, so a directory always imports the same way
+pub fn read(dir: &str, fixes: Fixes) -> DecodeResult<Vec<AudioFile>> {
+    let mut paths = Vec::<String>::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let supported = matches!(path.extension().and_then(|e| e.to_str()), Some("wav" | "aif"));
+        match path.to_str() {
+            Some(valid) if supported => paths.push(valid.to_string()),
+            _ => continue,
+        }
+    }
+    paths.sort();
+
+    if paths.is_empty() {
+        return Err(DecodeError::InvalidData(format!("No .wav or .aif files in {dir}")));
+    }
+
+    let mut stems = Vec::<AudioFile>::new();
+    for path in &paths {
+        let af = match path.ends_with(".wav") {
+            true => wav::parse(path)?,
+            false => aiff::parse(path)?,
+        };
+        stems.push(af);
+    }
+
+    let rate = common_rate(&stems);
+    if let Some(off) = stems.iter().find(|af| af.sample_rate != rate) {
+        if !fixes.resample {
+            return Err(DecodeError::InvalidData(format!(
+                "{} is at {}Hz, the other stems at {rate}Hz; add --resample to convert it",
+                off.file_name, off.sample_rate,
+            )));
+        }
+        for af in &mut stems {
+            af.resample_to(rate);
+        }
+    }
+
+    let frames = AudioFile::frames;
+    let longest = stems.iter().map(frames).max().unwrap_or(0);
+    if let Some(short) = stems.iter().find(|af| frames(af) != longest) {
+        if !fixes.pad {
+            return Err(DecodeError::InvalidData(format!(
+                "{} is {} frames, the longest stem {longest}; add --pad to fill it out with silence",
+                short.file_name, frames(short),
+            )));
+        }
+        for af in &mut stems {
+            let mut samples = af.samples.to_vec();
+            samples.resize(longest * af.num_channels.max(1) as usize, 0);
+            af.samples = samples.into();
+        }
+    }
+
+    Ok(stems)
+}
+
+// the rate most stems are at (the highest, on a tie)
+fn common_rate(stems: &[AudioFile]) -> u32 {
+    let count = |rate: u32| stems.iter().filter(|af| af.sample_rate == rate).count();
+    stems
+        .iter()
+        .map(|af| af.sample_rate)
+        .max_by_key(|rate| (count(*rate), *rate))
+        .unwrap_or(0)
+}
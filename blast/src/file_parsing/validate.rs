@@ -0,0 +1,472 @@
+use std::fmt;
+use std::fs;
+
+use super::aiff;
+use super::decode_helpers::{DecodeError, DecodeResult, read_file};
+use super::wav::{FormatCode, SUBFORMAT_GUID};
+
+// validate
+//
+// vets a file's structure without decoding it: where parse stops
+// at the first thing it can't read, this walks the whole file and
+// notes every anomaly it finds, so a sample library can be checked
+// in one pass and each file's problems seen at once
+//
+// WAV and AIFF are walked chunk by chunk: sizes that run past the
+// end or don't add up to the file's, format and data chunks that
+// are missing, repeated or out of place, format fields that
+// disagree with each other, and data that isn't whole frames
+//
+// MP3 is walked frame by frame, each header giving the length to
+// the next: lost sync, reserved fields, headers that change the
+// format partway through, and a last frame that's cut off
+//
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anomaly {
+    pub offset: usize, // bytes into the file
+    pub chunk: Option<String>, // its ID, for WAV and AIFF
+    pub what: String,
+}
+
+impl Anomaly {
+    fn new(offset: usize, chunk: Option<&[u8]>, what: impl Into<String>) -> Self {
+        let chunk = chunk.map(|id| String::from_utf8_lossy(id).trim_end().to_string());
+        Self { offset, chunk, what: what.into() }
+    }
+}
+
+// e.g. byte 12 (COMM chunk): 20 bytes, where blast reads 18
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.chunk {
+            Some(chunk) => write!(f, "byte {} ({chunk} chunk): {}", self.offset, self.what),
+            None => write!(f, "byte {}: {}", self.offset, self.what),
+        }
+    }
+}
+
+// the extensions validate knows
+pub const EXTENSIONS: [&str; 4] = ["wav", "aif", "aiff", "mp3"];
+
+// every anomaly in path, in the order they're found (none for a
+// sound file); Err if it can't be read, or its extension isn't
+// one of EXTENSIONS
+pub fn file(path: &str) -> DecodeResult<Vec<Anomaly>> {
+    let ext = path.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    if !EXTENSIONS.contains(&ext.as_str()) {
+        return Err(DecodeError::UnsupportedFormat(format!("can't validate .{ext} files")).in_file(path));
+    }
+    let bytes = read_file(path, None)?;
+    Ok(match ext.as_str() {
+        "wav" => wav(&bytes),
+        "mp3" => mpeg(&bytes),
+        _ => aiff(&bytes),
+    })
+}
+
+// path if it's a file, or every file with one of EXTENSIONS
+// under it if it's a directory, in name order
+pub fn files(path: &str) -> DecodeResult<Vec<String>> {
+    if !fs::metadata(path).map_err(|e| DecodeError::from(e).in_file(path))?.is_dir() {
+        return Ok(vec![path.to_string()]);
+    }
+
+    let mut found = Vec::new();
+    for entry in fs::read_dir(path).map_err(|e| DecodeError::from(e).in_file(path))? {
+        let entry = entry?.path();
+        let Some(sub) = entry.to_str() else { continue };
+        if entry.is_dir() {
+            found.extend(files(sub)?);
+        } else if entry
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        {
+            found.push(sub.to_string());
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+struct Chunk<'a> {
+    id: &'a [u8],
+    start: usize, // of its header
+    size: usize, // as its header gives it
+    body: &'a [u8], // as much of it as the file holds
+}
+
+// the chunks in bytes[start..], each header's size read little-
+// or big-endian; a chunk that runs past the end is kept, cut short,
+// and ends the walk, as does an ID that isn't text (after which
+// nothing can be trusted to be a chunk)
+fn chunks<'a>(bytes: &'a [u8], mut start: usize, big_endian: bool, found: &mut Vec<Anomaly>) -> Vec<Chunk<'a>> {
+    let mut chunks = Vec::new();
+    while start < bytes.len() {
+        if start + 8 > bytes.len() {
+            found.push(Anomaly::new(start, None, format!("{} stray bytes, too few for a chunk header", bytes.len() - start)));
+            break;
+        }
+        let id = &bytes[start..start + 4];
+        if !id.iter().all(|b| (0x20..=0x7E).contains(b)) {
+            found.push(Anomaly::new(start, None, "chunk ID isn't text; the chunks after it can't be found"));
+            break;
+        }
+        let size = [bytes[start + 4], bytes[start + 5], bytes[start + 6], bytes[start + 7]];
+        let size = match big_endian {
+            true => u32::from_be_bytes(size),
+            false => u32::from_le_bytes(size),
+        } as usize;
+
+        let body_start = start + 8;
+        let body_end = body_start.saturating_add(size);
+        if body_end > bytes.len() {
+            found.push(Anomaly::new(
+                start,
+                Some(id),
+                format!("{size} bytes, but the file ends {} bytes in", bytes.len() - body_start),
+            ));
+            chunks.push(Chunk { id, start, size, body: &bytes[body_start..] });
+            break;
+        }
+        if size & 1 == 1 && body_end == bytes.len() {
+            found.push(Anomaly::new(start, Some(id), "odd size, with no pad byte after it"));
+        }
+        chunks.push(Chunk { id, start, size, body: &bytes[body_start..body_end] });
+        start = body_end + (size & 1);
+    }
+    chunks
+}
+
+// whether the file starts with the container's header, e.g. RIFF
+// <size> WAVE, its size checked against the file's (the chunks are
+// walked to the end of the file either way, as the parsers read them)
+fn container(bytes: &[u8], ids: (&[u8], &[u8]), big_endian: bool, found: &mut Vec<Anomaly>) -> bool {
+    let (outer, form) = ids;
+    let name = |id: &[u8]| String::from_utf8_lossy(id).to_string();
+    if bytes.len() < 12 {
+        found.push(Anomaly::new(0, None, format!("{} bytes, too few for a {} header", bytes.len(), name(outer))));
+        return false;
+    }
+    if &bytes[0..4] != outer || &bytes[8..12] != form {
+        found.push(Anomaly::new(0, None, format!("not a {} {} file", name(outer), name(form))));
+        return false;
+    }
+
+    let size = [bytes[4], bytes[5], bytes[6], bytes[7]];
+    let size = match big_endian {
+        true => u32::from_be_bytes(size),
+        false => u32::from_le_bytes(size),
+    } as usize;
+    let declared = size.saturating_add(8);
+    if declared > bytes.len() {
+        found.push(Anomaly::new(4, None, format!("{} gives {declared} bytes, but the file has {}", name(outer), bytes.len())));
+    } else if declared < bytes.len() {
+        found.push(Anomaly::new(declared, None, format!("{} bytes after the {} ends", bytes.len() - declared, name(outer))));
+    }
+    true
+}
+
+fn le16(b: &[u8], at: usize) -> u32 {
+    u16::from_le_bytes([b[at], b[at + 1]]) as u32
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+fn be16(b: &[u8], at: usize) -> u32 {
+    u16::from_be_bytes([b[at], b[at + 1]]) as u32
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+// the one chunk called id, noting when there's none or more than one
+fn only<'a, 'b>(chunks: &'b [Chunk<'a>], id: &[u8], found: &mut Vec<Anomaly>) -> Option<&'b Chunk<'a>> {
+    let mut matching = chunks.iter().filter(|c| c.id == id);
+    let first = matching.next();
+    for repeat in matching {
+        found.push(Anomaly::new(repeat.start, Some(id), "a second one; only the first is read"));
+    }
+    if first.is_none() {
+        found.push(Anomaly::new(0, None, format!("no {} chunk", String::from_utf8_lossy(id).trim_end())));
+    }
+    first
+}
+
+pub fn wav(bytes: &[u8]) -> Vec<Anomaly> {
+    let mut found = Vec::new();
+    if !container(bytes, (b"RIFF", b"WAVE"), false, &mut found) {
+        return found;
+    }
+    let chunks = chunks(bytes, 12, false, &mut found);
+
+    let fmt = only(&chunks, b"fmt ", &mut found);
+    let data = only(&chunks, b"data", &mut found);
+    if let Some(fmt) = fmt
+        && fmt.start != 12
+    {
+        found.push(Anomaly::new(fmt.start, Some(fmt.id), "not the first chunk, where blast reads it"));
+    }
+    if let (Some(fmt), Some(data)) = (fmt, data)
+        && data.start < fmt.start
+    {
+        found.push(Anomaly::new(data.start, Some(data.id), "comes before the fmt chunk"));
+    }
+
+    let Some(fmt) = fmt else { return found };
+    let at = |offset: usize| fmt.start + 8 + offset;
+    let note = |found: &mut Vec<Anomaly>, offset: usize, what: String| found.push(Anomaly::new(at(offset), Some(fmt.id), what));
+    let b = fmt.body;
+    if b.len() < 16 {
+        note(&mut found, 0, format!("{} bytes, too few for the format's 16", fmt.size));
+        return found;
+    }
+
+    let tag = le16(b, 0);
+    let (channels, rate, byte_rate, block_align, bits) = (le16(b, 2), le32(b, 4), le32(b, 8), le16(b, 12), le16(b, 14));
+    let code = FormatCode::from_u16(tag as u16);
+    if code.is_none() {
+        note(&mut found, 0, format!("format tag 0x{tag:04X}, which blast doesn't decode"));
+    }
+    if channels == 0 {
+        note(&mut found, 2, "no channels".to_string());
+    }
+    if rate == 0 {
+        note(&mut found, 4, "a sample rate of 0".to_string());
+    }
+    if bits == 0 {
+        note(&mut found, 14, "0 bits per sample".to_string());
+    }
+    let frame = channels * bits.div_ceil(8);
+    if block_align != frame {
+        note(&mut found, 12, format!("block align of {block_align}, but {channels} channels of {bits} bits take {frame}"));
+    }
+    if byte_rate != rate * block_align {
+        note(&mut found, 8, format!("byte rate of {byte_rate}, not the rate times block align ({})", rate * block_align));
+    }
+
+    if code == Some(FormatCode::WaveFormatExtensible) {
+        if b.len() < 40 || le16(b, 16) < 22 {
+            note(&mut found, 16, "EXTENSIBLE, without the 22 bytes of its extension".to_string());
+        } else {
+            let (valid_bits, mask) = (le16(b, 18), le32(b, 20));
+            if valid_bits > bits {
+                note(&mut found, 18, format!("{valid_bits} valid bits in a {bits}-bit container"));
+            }
+            if mask != 0 && mask.count_ones() != channels {
+                note(&mut found, 20, format!("a channel mask of {} speakers for {channels} channels", mask.count_ones()));
+            }
+            let sub = le16(b, 24) as u16;
+            if b[26..40] != SUBFORMAT_GUID || FormatCode::from_u16(sub).is_none_or(|c| c == FormatCode::WaveFormatExtensible) {
+                note(&mut found, 24, "a sub-format blast doesn't decode".to_string());
+            }
+        }
+    }
+
+    if let Some(data) = data
+        && block_align > 0
+        && !data.size.is_multiple_of(block_align as usize)
+    {
+        found.push(Anomaly::new(
+            data.start,
+            Some(data.id),
+            format!("{} bytes, not a whole number of {block_align}-byte frames", data.size),
+        ));
+    }
+    found
+}
+
+pub fn aiff(bytes: &[u8]) -> Vec<Anomaly> {
+    let mut found = Vec::new();
+    if bytes.get(8..12) == Some(b"AIFC") {
+        found.push(Anomaly::new(8, None, "AIFF-C, which blast doesn't decode"));
+        return found;
+    }
+    if !container(bytes, (b"FORM", b"AIFF"), true, &mut found) {
+        return found;
+    }
+    let chunks = chunks(bytes, 12, true, &mut found);
+
+    let comm = only(&chunks, b"COMM", &mut found);
+    let ssnd = only(&chunks, b"SSND", &mut found);
+    let Some(comm) = comm else { return found };
+    if comm.start != 12 {
+        found.push(Anomaly::new(comm.start, Some(comm.id), "not the first chunk, where blast reads it"));
+    }
+    if comm.size != 18 {
+        found.push(Anomaly::new(comm.start, Some(comm.id), format!("{} bytes, where blast reads 18", comm.size)));
+    }
+    let b = comm.body;
+    if b.len() < 18 {
+        return found;
+    }
+
+    let at = comm.start + 8;
+    let (channels, frames, bits) = (be16(b, 0), be32(b, 2), be16(b, 6));
+    let rate = aiff::parse_ieee_extended(b, &mut 8, &mut 8).unwrap_or(0.0);
+    if channels == 0 {
+        found.push(Anomaly::new(at, Some(comm.id), "no channels"));
+    }
+    if bits != 16 {
+        found.push(Anomaly::new(at + 6, Some(comm.id), format!("{bits}-bit samples, where blast reads 16")));
+    }
+    if !rate.is_finite() || rate < 1.0 {
+        found.push(Anomaly::new(at + 8, Some(comm.id), format!("a sample rate of {rate}")));
+    }
+
+    if let Some(ssnd) = ssnd {
+        if ssnd.size < 8 {
+            found.push(Anomaly::new(ssnd.start, Some(ssnd.id), format!("{} bytes, too few for its offset and block size", ssnd.size)));
+            return found;
+        }
+        let offset = be32(ssnd.body, 0) as usize;
+        let samples = (ssnd.size - 8).saturating_sub(offset);
+        let expected = frames as usize * channels as usize * bits.div_ceil(8) as usize;
+        if samples != expected {
+            found.push(Anomaly::new(
+                ssnd.start,
+                Some(ssnd.id),
+                format!("{samples} bytes of samples, where COMM's {frames} frames take {expected}"),
+            ));
+        }
+    }
+    found
+}
+
+// bitrates in kbps by index, for MPEG-1 layers I to III and then
+// MPEG-2 (and 2.5) layer I and layers II and III
+const BITRATES: [[u32; 15]; 5] = [
+    [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448],
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384],
+    [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320],
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+#[derive(Clone, Copy, PartialEq)]
+struct FrameHeader {
+    version: u8, // 1, 2 or 25 (for 2.5)
+    layer: u8,
+    rate: u32,
+    mono: bool,
+    len: usize, // of the whole frame, header included
+}
+
+// the header in h (the four bytes at a frame's start), or what's
+// wrong with it
+fn frame_header(h: u32) -> Result<FrameHeader, &'static str> {
+    if h >> 21 != 0x7FF {
+        return Err("no frame sync");
+    }
+    let version = match (h >> 19) & 3 {
+        0 => 25,
+        2 => 2,
+        3 => 1,
+        _ => return Err("a reserved MPEG version"),
+    };
+    let layer = match (h >> 17) & 3 {
+        1 => 3,
+        2 => 2,
+        3 => 1,
+        _ => return Err("a reserved layer"),
+    };
+    let bitrate = match (h >> 12) & 0xF {
+        0 => return Err("a free-format bitrate, which can't be stepped through"),
+        0xF => return Err("a bad bitrate index"),
+        i => {
+            let row = match (version, layer) {
+                (1, l) => l as usize - 1,
+                (_, 1) => 3,
+                _ => 4,
+            };
+            BITRATES[row][i as usize] * 1000
+        }
+    };
+    let rate = match ((h >> 10) & 3, version) {
+        (3, _) => return Err("a reserved sample rate"),
+        (i, 1) => [44100, 48000, 32000][i as usize],
+        (i, 2) => [22050, 24000, 16000][i as usize],
+        (i, _) => [11025, 12000, 8000][i as usize],
+    };
+    let padding = (h >> 9) & 1;
+    let len = match layer {
+        1 => (12 * bitrate / rate + padding) * 4,
+        3 if version != 1 => 72 * bitrate / rate + padding,
+        _ => 144 * bitrate / rate + padding,
+    } as usize;
+    Ok(FrameHeader { version, layer, rate, mono: (h >> 6) & 3 == 3, len })
+}
+
+pub fn mpeg(bytes: &[u8]) -> Vec<Anomaly> {
+    let mut found = Vec::new();
+
+    // an ID3v2 tag first, its size in 7-bit bytes, and an ID3v1
+    // tag in the last 128
+    let mut at = match bytes.get(..10) {
+        Some(id3) if &id3[..3] == b"ID3" => {
+            let size = id3[6..10].iter().fold(0usize, |size, &b| (size << 7) | (b & 0x7F) as usize);
+            10 + size + if id3[5] & 0x10 != 0 { 10 } else { 0 }
+        }
+        _ => 0,
+    };
+    let end = match bytes.len() >= 128 && &bytes[bytes.len() - 128..bytes.len() - 125] == b"TAG" {
+        true => bytes.len() - 128,
+        false => bytes.len(),
+    };
+
+    let header_at = |at: usize| bytes.get(at..at + 4).map(|h| frame_header(be32(h, 0)));
+    let mut previous: Option<FrameHeader> = None;
+    let mut frames = 0;
+    while at + 4 <= end {
+        let header = match header_at(at).unwrap() {
+            Ok(header) => header,
+            Err(what) => {
+                // find the next frame whose header reads, and whose
+                // length leads to another (or past the end)
+                let next = (at + 1..end.saturating_sub(3)).find(|&n| match header_at(n) {
+                    Some(Ok(h)) => n + h.len >= end || matches!(header_at(n + h.len), Some(Ok(_))),
+                    _ => false,
+                });
+                let what = match next {
+                    Some(next) => format!("{what}; {} bytes skipped to the next frame", next - at),
+                    None => format!("{what}; no frames after it"),
+                };
+                found.push(Anomaly::new(at, None, what));
+                match next {
+                    Some(next) => {
+                        at = next;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+        };
+
+        if let Some(previous) = previous {
+            let changes = [
+                (previous.version != header.version, "MPEG version"),
+                (previous.layer != header.layer, "layer"),
+                (previous.rate != header.rate, "sample rate"),
+                (previous.mono != header.mono, "channel count"),
+            ];
+            for (_, field) in changes.iter().filter(|(changed, _)| *changed) {
+                found.push(Anomaly::new(at, None, format!("{field} changes from the frame before")));
+            }
+        }
+
+        if at + header.len > end {
+            found.push(Anomaly::new(at, None, format!("last frame cut off, {} of its {} bytes there", end - at, header.len)));
+        }
+        frames += 1;
+        previous = Some(header);
+        at += header.len;
+    }
+    if frames == 0 && found.is_empty() {
+        found.push(Anomaly::new(at, None, "no MPEG frames"));
+    }
+    found
+}
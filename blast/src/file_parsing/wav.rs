@@ -1,7 +1,8 @@
 use std::fs::File;
-use std::io::{self, Read, SeekFrom};
-use std::ops::{Shl, BitOr, AddAssign};
-use super::decode_helpers::{AudioFile, DecodeError, DecodeResult};
+use std::io::{Write, BufWriter, Seek, SeekFrom};
+use std::ops::Range;
+use super::decode_helpers::{AudioFile, DecodeError, DecodeResult, Metadata, Progress, Samples, chunk_at, read_file};
+use super::mapped::{Mapping, MAP_BYTES};
 
 // format codes
 #[repr(u16)]
@@ -25,15 +26,88 @@ impl FormatCode {
             _ => None,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::WaveFormatPcm => "PCM",
+            Self::WaveFormatIeeeFloat => "IEEE float",
+            Self::WaveFormatAlaw => "A-law",
+            Self::WaveFormatMulaw => "mu-law",
+            Self::WaveFormatExtensible => "EXTENSIBLE",
+        }
+    }
+}
+
+// the rest of an EXTENSIBLE sub-format's GUID, after the format
+// code and two zero bytes: {xxxxxxxx-0000-0010-8000-00AA00389B71}
+pub const SUBFORMAT_GUID: [u8; 14] = [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+
+// how the data chunk's samples are stored, once an EXTENSIBLE
+// file's sub-format is looked through: the format, and the bytes
+// each sample takes
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Encoding {
+    code: FormatCode,
+    width: usize,
+}
+
+impl Encoding {
+    fn new(code: FormatCode, width: usize) -> DecodeResult<Self> {
+        use FormatCode::*;
+        match (code, width) {
+            (WaveFormatPcm, 1..=4) | (WaveFormatIeeeFloat, 4 | 8) | (WaveFormatAlaw | WaveFormatMulaw, 1) => Ok(Self { code, width }),
+            _ => Err(DecodeError::UnsupportedFormat(format!("{}-byte {} samples", width, code.name()))),
+        }
+    }
+
+    // one sample, as the engine's i16: wider integers keep their
+    // top 16 bits (where a sample with fewer valid bits than its
+    // container has them), 8-bit is unsigned, floats are clipped
+    // to full scale, and the G.711 laws are expanded
+    #[inline]
+    fn sample(&self, b: &[u8]) -> i16 {
+        use FormatCode::*;
+        match (self.code, self.width) {
+            (WaveFormatPcm, 1) => (b[0] as i16 - 128) << 8,
+            (WaveFormatPcm, n) => i16::from_le_bytes([b[n - 2], b[n - 1]]),
+            (WaveFormatIeeeFloat, 4) => float_to_i16(f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            (WaveFormatIeeeFloat, _) => float_to_i16(f64::from_le_bytes(b[..8].try_into().unwrap())),
+            (WaveFormatAlaw, _) => alaw(b[0]),
+            (WaveFormatMulaw, _) => mulaw(b[0]),
+            (WaveFormatExtensible, _) => 0, // always looked through
+        }
+    }
 }
 
-pub fn print_id(vec: &mut Vec<u8>, start: &mut usize, end: &mut usize) -> DecodeResult<()> {
+fn float_to_i16(x: f64) -> i16 {
+    (x * 32768.0).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+fn alaw(byte: u8) -> i16 {
+    let a = byte ^ 0x55;
+    let (exponent, mantissa) = ((a >> 4) & 7, (a & 0x0F) as i16);
+    let magnitude = match exponent {
+        0 => (mantissa << 4) + 8,
+        e => ((mantissa << 4) + 0x108) << (e - 1),
+    };
+    // the sign bit set is positive
+    if a & 0x80 != 0 { magnitude } else { -magnitude }
+}
+
+fn mulaw(byte: u8) -> i16 {
+    let u = !byte;
+    let (exponent, mantissa) = ((u >> 4) & 7, (u & 0x0F) as i16);
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+    if u & 0x80 != 0 { -magnitude } else { magnitude }
+}
+
+pub fn print_id(vec: &[u8], start: &mut usize, end: &mut usize) -> DecodeResult<()> {
     *end += 4;
 
     for i in *start..*end {
-        let c = match vec.get(i) {
+        let _c = match vec.get(i) {
             Some(val)   => val,
-            None    => return Err(DecodeError::UnexpectedEof),
+            None    => return Err(DecodeError::UnexpectedEof.at_offset(i)),
         };
     }
 
@@ -43,7 +117,7 @@ pub fn print_id(vec: &mut Vec<u8>, start: &mut usize, end: &mut usize) -> Decode
     Ok(())
 }
 
-fn parse_bytes(bytes: &mut Vec<u8>, start: &mut usize, end: &mut usize, inc: usize) -> DecodeResult<u32> {
+fn parse_bytes(bytes: &[u8], start: &mut usize, end: &mut usize, inc: usize) -> DecodeResult<u32> {
     let mut value: u32 = 0;
 
     *end += inc;
@@ -53,7 +127,7 @@ fn parse_bytes(bytes: &mut Vec<u8>, start: &mut usize, end: &mut usize, inc: usi
     for i in *start..*end {
         let b: u8 = match bytes.get(i) {
             Some(val) => *val,
-            None => return Err(DecodeError::UnexpectedEof),
+            None => return Err(DecodeError::UnexpectedEof.at_offset(i)),
         };
     
         value += (b as u32) << shift;
@@ -67,101 +141,357 @@ fn parse_bytes(bytes: &mut Vec<u8>, start: &mut usize, end: &mut usize, inc: usi
 }
 
 pub fn parse(path: &str) -> DecodeResult<AudioFile> {
-    let mut f = File::open(path)?;
-    let mut reader = Vec::new();
-    f.read_to_end(&mut reader)?;
+    parse_with(path, None)
+}
+
+// as parse, telling progress as the file's read
+pub fn parse_with(path: &str, progress: Progress) -> DecodeResult<AudioFile> {
+    let reader = read_file(path, progress)?;
+
+    let (mut af, data, encoding) = read(path, &reader)?;
+    let data_start = data.start - 8;
+    af.samples = decode(&reader, data, encoding).map_err(|e| e.within(path, "data", data_start))?.into();
+    Ok(af)
+}
 
+// as parse, but a file of MAP_BYTES or more is mapped instead,
+// if it can be
+pub fn open(path: &str) -> DecodeResult<AudioFile> {
+    open_with(path, None)
+}
+
+// as open, telling progress; a mapped file is done as soon as
+// it's mapped, its pages read as it's played
+pub fn open_with(path: &str, progress: Progress) -> DecodeResult<AudioFile> {
+    let metadata = std::fs::metadata(path).map_err(|e| DecodeError::from(e).in_file(path))?;
+    let len = metadata.len() as usize;
+    if len < MAP_BYTES {
+        return parse_with(path, progress);
+    }
+    match map(path) {
+        Ok(af) => {
+            if let Some(progress) = progress {
+                progress(len, len);
+            }
+            Ok(af)
+        }
+        Err(_) => parse_with(path, progress),
+    }
+}
+
+// maps path and plays its samples from where they lie (see
+// mapped.rs) rather than decoding them into memory; only 16-bit
+// PCM is stored as it's played
+pub fn map(path: &str) -> DecodeResult<AudioFile> {
+    let mapping = Mapping::open(path).map_err(|e| e.in_file(path))?;
+    let (mut af, data, encoding) = read(path, mapping.bytes())?;
+
+    if encoding != (Encoding { code: FormatCode::WaveFormatPcm, width: 2 }) {
+        return Err(DecodeError::UnsupportedFormat(format!(
+            "Only 16-bit PCM can be mapped, not {}-bit {}", af.bits_per_sample, af.meta.encoding,
+        )).in_file(path));
+    }
+    let bytes_per_sec = af.sample_rate as usize * af.num_channels as usize * 2;
+    let data_start = data.start - 8;
+    af.samples = Samples::Mapped(
+        mapping.into_samples(data, bytes_per_sec).map_err(|e| e.within(path, "data", data_start))?,
+    );
+    Ok(af)
+}
+
+// the samples in the bytes of data, as i16s
+fn decode(reader: &[u8], data: Range<usize>, encoding: Encoding) -> DecodeResult<Vec<i16>> {
+    let bytes = reader.get(data).ok_or(DecodeError::UnexpectedEof.at_offset(reader.len()))?;
+    Ok(bytes.chunks_exact(encoding.width).map(|b| encoding.sample(b)).collect())
+}
+
+// everything but the samples, which are left empty, and where
+// they are (the data chunk's bytes) and how they're stored
+fn read(path: &str, reader: &[u8]) -> DecodeResult<(AudioFile, Range<usize>, Encoding)> {
+    let file_name: &str = match path.rsplit_once('.') {
+        Some((before, after)) if !before.is_empty() && !after.is_empty() => {
+            match before.rsplit_once('/') {
+                Some((_assets, name)) => name,
+                None => return Err(DecodeError::InvalidData("File is not nested".to_string()).in_file(path)),
+            }
+        }
+        _ => return Err(DecodeError::InvalidData("File has no name".to_string()).in_file(path)),
+    };
+
+    // the chunk being read, for an error to name
+    let mut chunk = chunk_at(reader, 0);
+    read_chunks(path, file_name, reader, &mut chunk).map_err(|e| e.within(path, &chunk.0, chunk.1))
+}
+
+fn read_chunks(
+    path: &str,
+    file_name: &str,
+    reader: &[u8],
+    chunk: &mut (String, usize),
+) -> DecodeResult<(AudioFile, Range<usize>, Encoding)> {
     let mut start: usize= 0;
     let mut end: usize = 0;
 
     // RIFF
     // (print_id always increments end by four before printing
     //  and sets start to end afterward)
-    print_id(&mut reader, &mut start, &mut end)?;
+    print_id(reader, &mut start, &mut end)?;
 
     // (parse_bytes increments end by the integer argument
     //  before decoding the reader from start to end
     //  and sets start to end afterward))
-    let riff_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let _riff_size: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
 
     // WAVE
-    print_id(&mut reader, &mut start, &mut end)?;
+    print_id(reader, &mut start, &mut end)?;
 
     // "fmt "
-    print_id(&mut reader, &mut start,&mut end)?;        
+    *chunk = chunk_at(reader, start);
+    print_id(reader, &mut start,&mut end)?;        
 
-    let fmt_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let fmt_size: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
+    let fmt_end = start + fmt_size as usize + (fmt_size as usize & 1);
 
-    let Some(fmt_tag) = FormatCode::from_u16(parse_bytes(&mut reader, &mut start, &mut end, 2)?.try_into().unwrap())
+    let Some(fmt_tag) = FormatCode::from_u16(parse_bytes(reader, &mut start, &mut end, 2)?.try_into().unwrap())
     else {
         return Err(DecodeError::UnsupportedFormat(String::from("Unrecognized format tag")));
     };
     
-    let num_channels: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+    let num_channels: u32 = parse_bytes(reader, &mut start, &mut end, 2)?;
     
-    let sample_rate: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
-
-    let data_rate: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+    let sample_rate: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
 
-    let data_blk_sz: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+    let _data_rate: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
 
-    let bits_per_sample: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+    let block_align: u32 = parse_bytes(reader, &mut start, &mut end, 2)?;
 
-    // if !WaveFormatPcm (i.e. is extensible)
-    if fmt_size >= 18 {
-        // extension is either 0 or 22
-        let cb_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+    let mut bits_per_sample: u32 = parse_bytes(reader, &mut start, &mut end, 2)?;
 
-        if cb_size > 0 {
-            let valid_bits: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+    // EXTENSIBLE: the format is the sub-format's, the first two
+    // bytes of its GUID; bits_per_sample is the container, and the
+    // extension says how many of those bits are the sample and
+    // which speaker each channel is for
+    let mut code = fmt_tag;
+    let mut channel_mask = None;
+    if fmt_tag == FormatCode::WaveFormatExtensible {
+        let cb_size: u32 = parse_bytes(reader, &mut start, &mut end, 2)?;
+        if cb_size < 22 {
+            return Err(DecodeError::InvalidData(format!("EXTENSIBLE fmt extension is {cb_size} bytes, not 22")));
+        }
 
-            let dw_channel_mask: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
+        let valid_bits: u32 = parse_bytes(reader, &mut start, &mut end, 2)?;
+        if valid_bits > 0 && valid_bits <= bits_per_sample {
+            bits_per_sample = valid_bits;
+        }
 
-            let old_fmt: u32 = parse_bytes(&mut reader, &mut start, &mut end, 2)?;
+        // 0 leaves the speakers unassigned
+        let mask: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
+        channel_mask = (mask != 0).then_some(mask);
 
-            // skip over Microsoft stuff
-            // TODO: compare against audio media subtype
-            for i in 0..14 {
-                end += i;
-            }
-            start = end;
-        }
+        let guid = reader.get(start..start + 16).ok_or(DecodeError::UnexpectedEof.at_offset(reader.len()))?;
+        code = match FormatCode::from_u16(u16::from_le_bytes([guid[0], guid[1]])) {
+            Some(sub) if guid[2..] == SUBFORMAT_GUID && sub != FormatCode::WaveFormatExtensible => sub,
+            _ => return Err(DecodeError::UnsupportedFormat(String::from("Unrecognized EXTENSIBLE sub-format"))),
+        };
     }
 
+    // the container, which for a 24-bit sample could be 3 or 4 bytes
+    let width = match block_align / num_channels.max(1) {
+        0 => bits_per_sample.div_ceil(8),
+        width => width,
+    };
+    let encoding = Encoding::new(code, width as usize)?;
 
     //
     // TODO: parse "fact" chunk if non-PCM (and if exists)
     //
 
+    // anything between fmt and data (LIST, fact...) is skipped,
+    // but for the tags in LIST INFO
+    let mut tags = Vec::new();
+    start = fmt_end;
+    while reader.get(start..start + 4).ok_or(DecodeError::UnexpectedEof.at_offset(start))? != b"data" {
+        *chunk = chunk_at(reader, start);
+        let id = &reader[start..start + 4];
+        start += 4;
+        end = start;
+        let size = parse_bytes(reader, &mut start, &mut end, 4)? as usize;
+        if id == b"LIST" {
+            tags.extend(parse_info(reader, start, size));
+        }
+        start += size + (size & 1);
+    }
+    end = start;
+
     // "data"
-    print_id(&mut reader, &mut start, &mut end)?;
-    let data_size: u32 = parse_bytes(&mut reader, &mut start, &mut end, 4)?;
-   
-    let mut samples: Vec<i16> = Vec::new();
-    
+    *chunk = chunk_at(reader, start);
+    print_id(reader, &mut start, &mut end)?;
+    let data_size: u32 = parse_bytes(reader, &mut start, &mut end, 4)?;
+
     end += data_size as usize;
-    for i in (start..end).step_by(2) {
-        let s1 = match reader.get(i) {
-            Some(val) => *val,
-            None => return Err(DecodeError::UnexpectedEof),
-        };
-        let s2 = match reader.get(i + 1) {
-            Some(val) => *val,
-            None => return Err(DecodeError::UnexpectedEof),
-        };
- 
-        samples.push(i16::from_le_bytes([s1, s2]));
+    let data = start..end;
+
+    // chunks after "data" (padded to even sizes)
+    start = end + (data_size as usize & 1);
+    let mut loop_points: Option<(usize, usize)> = None;
+    let mut root_note: Option<u8> = None;
+    while start + 8 <= reader.len() {
+        *chunk = chunk_at(reader, start);
+        let id = &reader[start..start + 4];
+        start += 4;
+        end = start;
+        let size = parse_bytes(reader, &mut start, &mut end, 4)? as usize;
+
+        if id == b"smpl" {
+            (root_note, loop_points) = parse_smpl(reader, start, size)?;
+        }
+        if id == b"LIST" {
+            tags.extend(parse_info(reader, start, size));
+        }
+
+        start += size + (size & 1);
     }
 
-    let file_name: &str = match path.rsplit_once(|b: char| b == '.') {
-        Some((before, after)) if !before.is_empty() && !after.is_empty() => {
-            match before.rsplit_once(|b: char| b == '/') {
-                Some((assets, name)) => name,
-                None => return Err(DecodeError::InvalidData("File is not nested".to_string())),
-            }
+    let name = match fmt_tag {
+        FormatCode::WaveFormatExtensible => format!("{} ({})", fmt_tag.name(), code.name()),
+        _ => fmt_tag.name().to_string(),
+    };
+    let mut af = AudioFile::new(path, file_name, "wav", sample_rate, num_channels, bits_per_sample, Vec::new());
+    af.loop_points = loop_points;
+    af.root_note = root_note;
+    af.channel_mask = channel_mask;
+    af.meta = Metadata { encoding: name, data_bytes: data_size as usize, tags };
+    Ok((af, data, encoding))
+}
+
+// "LIST" of type "INFO" is a run of short text chunks (INAM is
+// the title, IART the artist...); other LISTs (adtl) are skipped
+fn parse_info(reader: &[u8], chunk_start: usize, size: usize) -> Vec<(String, String)> {
+    let Some(list) = reader.get(chunk_start..chunk_start + size) else {
+        return Vec::new();
+    };
+    if list.get(..4) != Some(b"INFO") {
+        return Vec::new();
+    }
+
+    let mut tags = Vec::new();
+    let mut at = 4;
+    while let Some(header) = list.get(at..at + 8) {
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let Some(text) = list.get(at + 8..at + 8 + len) else { break };
+        let text = String::from_utf8_lossy(text).trim_end_matches('\0').trim().to_string();
+        if !text.is_empty() {
+            tags.push((info_name(&header[..4]), text));
         }
-        _ => return Err(DecodeError::InvalidData("File has no name".to_string())),
+        at += 8 + len + (len & 1);
+    }
+    tags
+}
+
+fn info_name(id: &[u8]) -> String {
+    let name = match id {
+        b"INAM" => "title",
+        b"IART" => "artist",
+        b"IPRD" => "album",
+        b"ICMT" => "comment",
+        b"ICOP" => "copyright",
+        b"ICRD" => "date",
+        b"IGNR" => "genre",
+        b"ISFT" => "software",
+        b"IENG" => "engineer",
+        _ => return String::from_utf8_lossy(id).to_string(),
     };
+    name.to_string()
+}
+
+type Smpl = (Option<u8>, Option<(usize, usize)>); // unity note, loop
+
+// "smpl" holds sampler settings, then any number of loops;
+// the unity note (the one that plays the sample as it is) and
+// the first loop are used
+fn parse_smpl(reader: &[u8], chunk_start: usize, size: usize) -> DecodeResult<Smpl> {
+    // manufacturer, product and sample period come before the
+    // unity note
+    let mut start = chunk_start + 12;
+    let mut end = start;
+    let unity = parse_bytes(reader, &mut start, &mut end, 4)?;
+    let root_note = (unity <= 127).then_some(unity as u8);
+
+    // then pitch fraction, SMPTE format and offset, before the
+    // loop count
+    start = chunk_start + 28;
+    end = start;
+    let num_loops = parse_bytes(reader, &mut start, &mut end, 4)?;
+    let _sampler_data = parse_bytes(reader, &mut start, &mut end, 4)?;
+
+    if num_loops == 0 || size < 36 + 24 {
+        return Ok((root_note, None));
+    }
+
+    // cue point id and type come before the loop's frames
+    start += 8;
+    end = start;
+    let loop_start = parse_bytes(reader, &mut start, &mut end, 4)? as usize;
+    let loop_end = parse_bytes(reader, &mut start, &mut end, 4)? as usize; // inclusive
+
+    if loop_end < loop_start {
+        return Ok((root_note, None));
+    }
+
+    Ok((root_note, Some((loop_start, loop_end + 1))))
+}
+
+// encodes 16-bit PCM: RIFF header, fmt chunk, data chunk
+pub fn write(path: &str, sample_rate: u32, num_channels: u32, samples: &[i16]) -> DecodeResult<()> {
+    let mut w = Writer::create(path, sample_rate, num_channels)?;
+    w.write(samples)?;
+    w.finish()
+}
 
-    Ok(AudioFile::new(file_name, "wav", sample_rate, num_channels, bits_per_sample, samples))
+// the same, a block at a time (e.g. a recording as it plays);
+// the sizes in the header are filled in by finish
+pub struct Writer {
+    f: BufWriter<File>,
+    data_size: u32,
+}
+
+impl Writer {
+    pub fn create(path: &str, sample_rate: u32, num_channels: u32) -> DecodeResult<Self> {
+        let block_align = num_channels * 2;
+        let mut f = BufWriter::new(File::create(path)?);
+
+        f.write_all(b"RIFF")?;
+        f.write_all(&36u32.to_le_bytes())?;
+        f.write_all(b"WAVE")?;
+
+        f.write_all(b"fmt ")?;
+        f.write_all(&16u32.to_le_bytes())?;
+        f.write_all(&(FormatCode::WaveFormatPcm as u16).to_le_bytes())?;
+        f.write_all(&(num_channels as u16).to_le_bytes())?;
+        f.write_all(&sample_rate.to_le_bytes())?;
+        f.write_all(&(sample_rate * block_align).to_le_bytes())?;
+        f.write_all(&(block_align as u16).to_le_bytes())?;
+        f.write_all(&16u16.to_le_bytes())?;
+
+        f.write_all(b"data")?;
+        f.write_all(&0u32.to_le_bytes())?;
+        Ok(Self { f, data_size: 0 })
+    }
+
+    pub fn write(&mut self, samples: &[i16]) -> DecodeResult<()> {
+        for s in samples {
+            self.f.write_all(&s.to_le_bytes())?;
+        }
+        self.data_size += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> DecodeResult<()> {
+        self.f.seek(SeekFrom::Start(4))?;
+        self.f.write_all(&(36 + self.data_size).to_le_bytes())?;
+        self.f.seek(SeekFrom::Start(40))?;
+        self.f.write_all(&self.data_size.to_le_bytes())?;
+        self.f.flush()?;
+        Ok(())
+    }
 }
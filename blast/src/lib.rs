@@ -1,27 +1,64 @@
-pub mod audio_processing;
-pub mod file_parsing;
+pub mod prelude;
+pub mod cli;
 
-use audio_processing::runtime::run_blast;
-use file_parsing::decode_helpers::{DecodeResult, DecodeError, AudioFile};
+// internals: only what the prelude (or cli) re-exports
+// reaches other crates
+mod audio_processing;
+pub mod config;
+mod file_parsing;
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use std::collections::HashMap;
+    use crate::{
+        audio_processing::{
+            runtime::{play, run_blast, read_lines, RunOptions, XrunWatch, XRUN_WINDOW, Recovery, RETRY_FIRST, Input},
+            commands::{CmdProcessor, CmdErr, EngineState, LoadFlags, BundleFlags, DelayFlags},
+            capture::Capture,
+            cue::CueSheet,
+            autoloop,
+            loudness,
+            engine::{Conductor, MAX_TRACKS},
+            blast_time::{sample_rate, blast_time::{clock, Position, TempoMode, TempoState, TempoUnit}},
+            routing::Routing,
+            offline,
+            schema,
+            notes,
+            midi::{ClockReader, ClockSync, NoteReader},
+            osc,
+            tui::{self, Ui},
+            wave::Envelope,
+            devices::{self, Device},
+            backend::{self, AudioBackend, Cycle, Latency, null::Null},
+            bindings::{self, Action, Bindings, Key},
+            commands::{Command, CmdQueue, FadeArgs, MidiArgs, MidiAction, AudioArgs, AudioAction, QuitArgs, Idx, SessionArgs, SessionAction},
+            ring::Ring,
+            responses::{Response, ResponseQueue},
+            simd,
+            reaper::{ReapQueue, Remains},
+            stats::Stats,
+        },
+        file_parsing::{aiff, wav, bundle, smf, decode_helpers::{self, AudioFile, ChannelMap, DecodeError, Samples}},
+        config::Config,
+    };
 
     #[test]
+    #[ignore = "needs assets/ and an ALSA output device"]
     fn test_wav() {
         println!("parsing a wav file");
         let path = "assets/fairies.wav";
 
         let af = match wav::parse(path) {
             Ok(file) => file,
-            Err(error) => panic!("Error with file"),
+            Err(_error) => panic!("Error with file"),
         };
 
-        run_blast(vec![af], af.sample_rate, af.num_channels);
+        let (sample_rate, num_channels) = (af.sample_rate, af.num_channels);
+        run_blast(HashMap::from([(af.file_name.clone(), af)]), sample_rate, num_channels, &RunOptions::default());
     }
 
     #[test]
+    #[ignore = "needs assets/ and an ALSA output device"]
     fn test_aiff() {
         let path = "assets/winterly.aif";
 
@@ -30,6 +67,3833 @@ mod tests {
             Err(error) => panic!("{:?}", error),
         };
 
-        run_blast(vec![af], af.sample_rate, af.num_channels);
+        let (sample_rate, num_channels) = (af.sample_rate, af.num_channels);
+        run_blast(HashMap::from([(af.file_name.clone(), af)]), sample_rate, num_channels, &RunOptions::default());
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("blast_bundle_{}", std::process::id()));
+        let src = dir.join("src");
+        std::fs::create_dir_all(src.join("assets")).unwrap();
+
+        let track = src.join("assets/kick.wav");
+        let script = src.join("set.txt");
+        std::fs::write(&track, [1u8, 2, 3, 4, 5]).unwrap();
+        std::fs::write(&script, "load kick\nstart kick\n").unwrap();
+
+        let track = track.to_str().unwrap().to_string();
+        let script = script.to_str().unwrap().to_string();
+        let pack = dir.join("set.blastpack");
+        let pack = pack.to_str().unwrap();
+
+        // packed: everything comes back byte for byte
+        bundle::export(pack, std::slice::from_ref(&track), std::slice::from_ref(&script), false).unwrap();
+        let out = dir.join("packed");
+        let missing = bundle::import(pack, out.to_str().unwrap(), |_| None).unwrap();
+        assert!(missing.is_empty());
+        assert_eq!(std::fs::read(out.join(track.trim_start_matches('/'))).unwrap(), [1, 2, 3, 4, 5]);
+        assert_eq!(
+            std::fs::read_to_string(out.join(script.trim_start_matches('/'))).unwrap(),
+            "load kick\nstart kick\n"
+        );
+
+        // hashes only: the track has to be resolved
+        bundle::export(pack, std::slice::from_ref(&track), &[], true).unwrap();
+        let out = dir.join("hashed");
+        let missing = bundle::import(pack, out.to_str().unwrap(), |_| None).unwrap();
+        assert_eq!(missing.len(), 1);
+
+        let missing = bundle::import(pack, out.to_str().unwrap(), |_| Some(track.clone())).unwrap();
+        assert!(missing.is_empty());
+        assert_eq!(std::fs::read(out.join(track.trim_start_matches('/'))).unwrap(), [1, 2, 3, 4, 5]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_routing() {
+        let mut centered = Routing::empty(1, 4);
+        centered.connect(0, 0, 1.0);
+        centered.connect(0, 1, 1.0);
+        assert_eq!(Routing::default_for(1, 4), centered);
+
+        let mut downmix = Routing::empty(2, 1);
+        downmix.connect(0, 0, 0.5);
+        downmix.connect(1, 0, 0.5);
+        assert_eq!(Routing::default_for(2, 1), downmix);
+
+        let mut out = [0.0; 2];
+        let stereo = Routing::default_for(2, 2);
+        stereo.mix(0, 1.0, &mut out);
+        stereo.mix(1, -1.0, &mut out);
+        assert_eq!(out, [1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_wav_smpl_loop() {
+        // 16-bit mono, 8 frames, looping frames 2..=5
+        let mut smpl = vec![0u8; 36];
+        smpl[12..16].copy_from_slice(&57u32.to_le_bytes()); // unity note
+        smpl[28..32].copy_from_slice(&1u32.to_le_bytes());
+        for field in [0u32, 0, 2, 5, 0, 0] {
+            smpl.extend_from_slice(&field.to_le_bytes());
+        }
+
+        let mut fmt = Vec::new();
+        for (field, size) in [(1u32, 2), (1, 2), (44100, 4), (88200, 4), (2, 2), (16, 2)] {
+            fmt.extend_from_slice(&field.to_le_bytes()[..size]);
+        }
+
+        let mut body = b"WAVEfmt ".to_vec();
+        body.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+        body.extend_from_slice(&fmt);
+        body.extend_from_slice(b"data");
+        body.extend_from_slice(&16u32.to_le_bytes());
+        body.extend_from_slice(&[0u8; 16]);
+        body.extend_from_slice(b"smpl");
+        body.extend_from_slice(&(smpl.len() as u32).to_le_bytes());
+        body.extend_from_slice(&smpl);
+
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+
+        let path = std::env::temp_dir().join(format!("blast_smpl_{}.wav", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let af = wav::parse(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(af.samples.len(), 8);
+        assert_eq!(af.loop_points, Some((2, 6)));
+        assert_eq!(af.root_note, Some(57));
+    }
+
+    #[test]
+    fn test_info() {
+        // 16-bit stereo at 8000 Hz, 4000 frames, with LIST INFO
+        // (a title, and an odd-length artist) before data
+        let mut info = b"INFO".to_vec();
+        for (id, text) in [(b"INAM", &b"Kick\0"[..]), (b"IART", &b"Me\0"[..])] {
+            info.extend_from_slice(id);
+            info.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            info.extend_from_slice(text);
+            if text.len() % 2 == 1 {
+                info.push(0);
+            }
+        }
+
+        let mut fmt = Vec::new();
+        for (field, size) in [(1u32, 2), (2, 2), (8000, 4), (32000, 4), (4, 2), (16, 2)] {
+            fmt.extend_from_slice(&field.to_le_bytes()[..size]);
+        }
+
+        let mut body = b"WAVE".to_vec();
+        for (id, chunk) in [(b"fmt ", fmt), (b"LIST", info), (b"data", vec![0u8; 16000])] {
+            body.extend_from_slice(id);
+            body.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            body.extend_from_slice(&chunk);
+        }
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+
+        let dir = std::env::temp_dir().join(format!("blast_info_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kick.wav");
+        std::fs::write(&path, bytes).unwrap();
+        let af = wav::parse(path.to_str().unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((af.frames(), af.duration().as_millis()), (4000, 500));
+        assert_eq!((af.meta.encoding.as_str(), af.meta.data_bytes), ("PCM", 16000));
+        assert_eq!(af.meta.tags, [("title", "Kick"), ("artist", "Me")].map(|(k, v)| (k.to_string(), v.to_string())));
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 2));
+        assert!(matches!(cmd_processor.parse("info kick".to_string()), Ok(None)));
+        assert!(matches!(cmd_processor.parse("info snare".to_string()), Err(CmdErr::NoItem { .. })));
+    }
+
+    #[test]
+    fn test_wav_map() {
+        let tone: Vec<i16> = (0..8000).map(|i| ((i * 37) % 2000) as i16 - 1000).collect();
+        let dir = std::env::temp_dir().join(format!("blast_map_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("long.wav");
+        let path = path.to_str().unwrap();
+        wav::write(path, 8000, 2, &tone).unwrap();
+
+        // read in place, the same samples as decoding gives
+        let mapped = wav::map(path).unwrap();
+        let decoded = wav::parse(path).unwrap();
+        assert!(mapped.samples.is_mapped() && !decoded.samples.is_mapped());
+        assert_eq!(mapped.samples, decoded.samples);
+        assert_eq!((mapped.frames(), mapped.meta.data_bytes), (4000, 16000));
+        // small enough to decode
+        assert!(!wav::open(path).unwrap().samples.is_mapped());
+
+        // each Voice on it is read ahead for on its own, and
+        // gives its cursor back when it's done
+        let (a, b) = (mapped.samples.reader(), mapped.samples.reader());
+        assert_ne!(a, b);
+        mapped.samples.want(a, 0);
+        mapped.samples.want(b, 6000);
+        mapped.samples.release(a);
+        assert_eq!(mapped.samples.reader(), a);
+        let readers: Vec<usize> = (0..40).map(|_| mapped.samples.reader()).collect();
+        assert_eq!(readers.last(), readers.get(readers.len() - 2));
+
+        // and played the same
+        let preset = ["fade 0".to_string()];
+        assert_eq!(offline::process(&preset, mapped, 1.0).unwrap(), offline::process(&preset, decoded, 1.0).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_decode_progress() {
+        use std::sync::Mutex;
+        use crate::audio_processing::tui;
+
+        // 2.5MB of samples, read a MB at a time
+        let tone: Vec<i16> = (0..1_310_720).map(|i| (i % 2000) as i16 - 1000).collect();
+        let dir = std::env::temp_dir().join(format!("blast_progress_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("long.wav");
+        let path = path.to_str().unwrap();
+        wav::write(path, 48000, 2, &tone).unwrap();
+        let len = std::fs::metadata(path).unwrap().len() as usize;
+
+        let told = Mutex::new(Vec::new());
+        let progress = |done: usize, total: usize| told.lock().unwrap().push((done, total));
+        let af = wav::parse_with(path, Some(&progress)).unwrap();
+        assert_eq!(af.samples, wav::parse(path).unwrap().samples);
+
+        let told = told.into_inner().unwrap();
+        assert_eq!(told.len(), 3);
+        assert!(told.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(told.iter().all(|&(_, total)| total == len));
+        assert_eq!(told.last(), Some(&(len, len)));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(tui::loading(1 << 20, 2 << 20, 51), "loading ##########..........  50%     1.0/2.0MB");
+        assert_eq!(tui::loading(0, 0, 41), "loading ########## 100%     0.0/0.0MB");
+    }
+
+    #[test]
+    fn test_validate() {
+        use crate::file_parsing::validate;
+
+        let dir = std::env::temp_dir().join(format!("blast_validate_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("drums")).unwrap();
+        let path = dir.join("drums/kick.wav");
+        let path = path.to_str().unwrap();
+        wav::write(path, 48000, 2, &[0i16; 200]).unwrap();
+        assert_eq!(validate::file(path).unwrap(), []);
+        let clean = std::fs::read(path).unwrap();
+
+        // cut short, and with a block align that doesn't fit its format
+        let mut bad = clean[..clean.len() - 10].to_vec();
+        bad[32] = 3;
+        let found = validate::wav(&bad);
+        let whats: Vec<String> = found.iter().map(|a| a.to_string()).collect();
+        assert_eq!(whats, [
+            format!("byte 4: RIFF gives {} bytes, but the file has {}", clean.len(), bad.len()),
+            "byte 36 (data chunk): 400 bytes, but the file ends 390 bytes in".to_string(),
+            "byte 32 (fmt chunk): block align of 3, but 2 channels of 16 bits take 4".to_string(),
+            "byte 28 (fmt chunk): byte rate of 192000, not the rate times block align (144000)".to_string(),
+            "byte 36 (data chunk): 400 bytes, not a whole number of 3-byte frames".to_string(),
+        ]);
+
+        // the AIFF parse refuses, all of what's wrong with it
+        let mut aif = b"FORM".to_vec();
+        aif.extend_from_slice(&32u32.to_be_bytes());
+        aif.extend_from_slice(b"AIFFCOMM");
+        aif.extend_from_slice(&20u32.to_be_bytes());
+        aif.extend_from_slice(&[0, 1, 0, 0, 0, 4, 0, 24, 0x40, 0x0B, 0xFA, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let whats: Vec<String> = validate::aiff(&aif).iter().map(|a| a.to_string()).collect();
+        assert_eq!(whats, [
+            "byte 0: no SSND chunk",
+            "byte 12 (COMM chunk): 20 bytes, where blast reads 18",
+            "byte 26 (COMM chunk): 24-bit samples, where blast reads 16",
+        ]);
+
+        // MP3: 128 kbps frames at 44.1 kHz (417 bytes), a few bytes of
+        // junk, then one at 48 kHz (384 bytes), cut off
+        let frame = |header: [u8; 4], len: usize| {
+            let mut frame = header.to_vec();
+            frame.resize(len, 0);
+            frame
+        };
+        let mut mp3 = [frame([0xFF, 0xFB, 0x90, 0x00], 417), frame([0xFF, 0xFB, 0x90, 0x00], 417)].concat();
+        assert_eq!(validate::mpeg(&mp3), []);
+        mp3.extend_from_slice(&[0; 5]);
+        mp3.extend(frame([0xFF, 0xFB, 0x94, 0x00], 100));
+        let whats: Vec<String> = validate::mpeg(&mp3).iter().map(|a| a.to_string()).collect();
+        assert_eq!(whats, [
+            "byte 834: no frame sync; 5 bytes skipped to the next frame",
+            "byte 839: sample rate changes from the frame before",
+            "byte 839: last frame cut off, 100 of its 384 bytes there",
+        ]);
+
+        // a directory is searched for what can be checked
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::write(dir.join("bad.aif"), &aif).unwrap();
+        let paths = validate::files(dir.to_str().unwrap()).unwrap();
+        assert_eq!(paths.iter().map(|p| p.rsplit('/').next().unwrap()).collect::<Vec<_>>(), ["bad.aif", "kick.wav"]);
+        assert!(validate::file(dir.join("notes.txt").to_str().unwrap()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_wav_extensible() {
+        // 5.1, 24 bits in 3-byte containers, one frame
+        let extensible = |bits: u16, width: u16, mask: u32, sub: u16, data: Vec<u8>| {
+            let mut fmt = Vec::new();
+            fmt.extend_from_slice(&0xFFFEu16.to_le_bytes());
+            fmt.extend_from_slice(&6u16.to_le_bytes());
+            fmt.extend_from_slice(&48000u32.to_le_bytes());
+            fmt.extend_from_slice(&(48000 * 6 * width as u32).to_le_bytes());
+            fmt.extend_from_slice(&(6 * width).to_le_bytes());
+            fmt.extend_from_slice(&(width * 8).to_le_bytes());
+            fmt.extend_from_slice(&22u16.to_le_bytes());
+            fmt.extend_from_slice(&bits.to_le_bytes());
+            fmt.extend_from_slice(&mask.to_le_bytes());
+            fmt.extend_from_slice(&sub.to_le_bytes());
+            fmt.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71]);
+
+            let mut body = b"WAVE".to_vec();
+            for (id, chunk) in [(b"fmt ", fmt), (b"data", data)] {
+                body.extend_from_slice(id);
+                body.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+                body.extend_from_slice(&chunk);
+            }
+            let mut bytes = b"RIFF".to_vec();
+            bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&body);
+
+            let dir = std::env::temp_dir().join(format!("blast_ext_{}_{bits}_{sub}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("surround.wav");
+            std::fs::write(&path, bytes).unwrap();
+            let af = wav::parse(path.to_str().unwrap());
+            std::fs::remove_dir_all(&dir).unwrap();
+            af
+        };
+
+        let pcm: Vec<u8> = [0x7FFFFFi32, -0x800000, 0x123456, 0, 0x000100, -0x000100]
+            .iter()
+            .flat_map(|s| s.to_le_bytes()[..3].to_vec())
+            .collect();
+        let af = extensible(24, 3, 0x3F, 1, pcm).unwrap();
+        assert_eq!(&*af.samples, &[0x7FFF, -0x8000, 0x1234, 0, 1, -1]);
+        assert_eq!((af.bits_per_sample, af.meta.encoding.as_str(), af.channel_mask), (24, "EXTENSIBLE (PCM)", Some(0x3F)));
+        assert_eq!(decode_helpers::speakers(0x3F), ["FL", "FR", "FC", "LFE", "BL", "BR"]);
+
+        // the mask is what the default routing folds down by
+        let routing = Routing::for_layout(af.channel_mask, 6, 2);
+        assert_eq!(routing, Routing::from_map(&ChannelMap::between(6, 2)));
+        assert_ne!(routing, Routing::default_for(6, 2));
+        // a quad layout given as its speakers, not as 4-channel WAV order
+        let sides = ChannelMap::for_layout(Some(0x603), 4, 2);
+        assert!(sides.gain(2, 0) > 0.0 && sides.gain(3, 1) > 0.0 && sides.gain(2, 1) == 0.0);
+
+        // floats, with 20 valid bits of a 32-bit container read as such
+        let float: Vec<u8> = [0.5f32, -1.0, 2.0, 0.0, 0.25, -0.25].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let af = extensible(32, 4, 0, 3, float).unwrap();
+        assert_eq!(&*af.samples, &[16384, -32768, 32767, 0, 8192, -8192]);
+        assert_eq!((af.meta.encoding.as_str(), af.channel_mask), ("EXTENSIBLE (IEEE float)", None));
+        let af = extensible(20, 4, 0x3F, 1, [0, 0, 0x10, 0x00].repeat(6)).unwrap();
+        assert_eq!((af.bits_per_sample, af.samples[0]), (20, 16));
+
+        // a sub-format that isn't audio
+        assert!(matches!(extensible(24, 3, 0x3F, 0x55, vec![0; 18]).unwrap_err().kind(), DecodeError::UnsupportedFormat(_)));
+    }
+
+    #[test]
+    fn test_decode_error_context() {
+        let dir = std::env::temp_dir().join(format!("blast_err_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // COMM of 20 bytes, not 18
+        let mut aif = b"FORM".to_vec();
+        aif.extend_from_slice(&38u32.to_be_bytes());
+        aif.extend_from_slice(b"AIFFCOMM");
+        aif.extend_from_slice(&20u32.to_be_bytes());
+        aif.extend_from_slice(&[0; 20]);
+        let path = dir.join("bad.aif");
+        std::fs::write(&path, aif).unwrap();
+        let path = path.to_str().unwrap();
+        let err = aiff::parse(path).unwrap_err();
+        assert!(matches!(err.kind(), DecodeError::InvalidData(_)));
+        let at = err.location().unwrap();
+        assert_eq!((at.path.as_deref(), at.chunk.as_deref(), at.offset), (Some(path), Some("COMM"), Some(12)));
+        assert_eq!(err.to_string(), format!("{path}: Comm size should be 18 (COMM chunk, byte 12)"));
+
+        // a fmt chunk cut off after its format tag
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&14u32.to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        let path = dir.join("cut.wav");
+        std::fs::write(&path, wav).unwrap();
+        let path = path.to_str().unwrap();
+        let err = wav::parse(path).unwrap_err();
+        assert!(matches!(err.kind(), DecodeError::UnexpectedEof));
+        assert_eq!(err.to_string(), format!("{path}: file ends early (fmt chunk, byte 22)"));
+
+        // no file at all still says which
+        let missing = dir.join("missing.wav");
+        let err = wav::open(missing.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err.kind(), DecodeError::Io(_)));
+        assert_eq!(err.location().unwrap().path.as_deref(), missing.to_str());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_aiff_inst() {
+        // 16-bit mono at 8000 Hz, 4 frames, with INST (base note
+        // 57) between COMM and SSND
+        let mut comm = Vec::new();
+        comm.extend_from_slice(&1u16.to_be_bytes());
+        comm.extend_from_slice(&4u32.to_be_bytes());
+        comm.extend_from_slice(&16u16.to_be_bytes());
+        comm.extend_from_slice(&[0x40, 0x0B]);
+        comm.extend_from_slice(&(8000u64 << 51).to_be_bytes());
+        let mut inst = vec![0u8; 20];
+        inst[0] = 57;
+        let mut ssnd = vec![0u8; 8];
+        for s in [1i16, 2, 3, 4] {
+            ssnd.extend_from_slice(&s.to_be_bytes());
+        }
+
+        let mut body = b"AIFF".to_vec();
+        for (id, chunk) in [(b"COMM", &comm), (b"INST", &inst), (b"SSND", &ssnd)] {
+            body.extend_from_slice(id);
+            body.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            body.extend_from_slice(chunk);
+        }
+        let mut bytes = b"FORM".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&body);
+
+        let name = format!("blast_inst_{}", std::process::id());
+        let path = std::env::temp_dir().join(format!("{name}.aiff"));
+        std::fs::write(&path, bytes).unwrap();
+        let af = aiff::parse(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((af.sample_rate, af.samples.to_vec()), (8000, vec![1, 2, 3, 4]));
+        assert_eq!(af.root_note, Some(57));
+
+        // a keymap's root defaults to it...
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        cmd_processor.parse(format!("load {name}")).unwrap();
+        let Some(Command::Keymap(args)) = cmd_processor.parse(format!("keymap {name} root span 12")).unwrap() else { panic!() };
+        assert_eq!(args.keymap.map(|k| (k.root, k.span)), Some((57, 12)));
+
+        // ...and MIDI notes that aren't mapped play it by its span
+        assert!(cmd_processor.prepare("midi keys kick".to_string()).is_err());
+        cmd_processor.prepare(format!("midi keys {name}")).unwrap();
+        cmd_processor.prepare("midi map A3 trig other".to_string()).unwrap();
+        assert_eq!(cmd_processor.notes.line(60), Some(format!("note {name} 60")));
+        assert_eq!(cmd_processor.notes.line(57).as_deref(), Some("trig other"));
+        assert_eq!(cmd_processor.notes.line(70), None);
+        cmd_processor.prepare("midi keys off".to_string()).unwrap();
+        assert_eq!(cmd_processor.notes.line(60), None);
+    }
+
+    #[test]
+    fn test_config_parse() {
+        let config = Config::parse("# pin audio\naudio_cpu = 3\n\nworker_cpus = 0, 1,2 # rest\n").unwrap();
+        assert_eq!(config.audio_cpu, Some(3));
+        assert_eq!(config.worker_cpus, Some(vec![0, 1, 2]));
+
+        assert!(Config::parse("audio_cpu = three").is_err());
+        assert!(Config::parse("audio_core = 3").is_err());
+        assert!(Config::parse("audio_cpu").is_err());
+        let config = Config::parse("device = plughw:1,0\n").unwrap();
+        assert_eq!(config.device.as_deref(), Some("plughw:1,0"));
+        assert!(Config::parse("device =").is_err());
+    }
+
+    #[test]
+    fn test_offline_process() {
+        let path = std::env::temp_dir().join(format!("blast_offline_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let tone: Vec<i16> = (0..100).map(|i| (i * 100) as i16).collect();
+        wav::write(path, 8000, 1, &tone).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(*af.samples, *tone);
+
+        // no ramps, so every sample can be compared
+        let preset = vec!["fade 0".to_string(), "gain -v $voice 0.5".to_string()];
+        let out = offline::process(&preset, af, 10.0).unwrap();
+
+        // the last frame is only ever interpolated toward
+        assert_eq!(out.len(), tone.len() - 1);
+        assert!(out.iter().zip(&tone).all(|(o, t)| *o == t / 2));
+    }
+
+    #[test]
+    fn test_trim() {
+        let path = std::env::temp_dir().join(format!("blast_trim_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let tone: Vec<i16> = (0..100).map(|i| (i * 100) as i16).collect();
+        wav::write(path, 8000, 1, &tone).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let preset = |lines: &[&str]| {
+            let mut preset = vec!["fade 0".to_string()];
+            preset.extend(lines.iter().map(|l| l.to_string()));
+            offline::process(&preset, af.clone(), 10.0)
+        };
+
+        // frames, ms (at 8kHz, 1.25ms is 10 frames) and beats
+        // (of 20 frames, at 24000bpm) all land on the same region
+        for trim in ["trim $voice 10 30", "trim $voice 1.25ms 3.75ms", "trim $voice 0.5b 1.5b"] {
+            let out = preset(&["load $voice -t b:24000", trim]).unwrap();
+            assert_eq!(out, tone[10..30], "{trim}");
+        }
+
+        // the loop stays inside it
+        let out = preset(&["trim $voice 10 20", "loop $voice 1"]).unwrap();
+        assert_eq!(out, [&tone[10..20], &tone[10..20]].concat());
+
+        // trimming a playing Voice moves it into the region
+        let out = preset(&["start -v $voice", "trim $voice 50 60"]).unwrap();
+        assert_eq!(out, tone[50..60]);
+
+        // an end past the Track's is its end; off plays it all again
+        assert_eq!(preset(&["trim $voice 90 1000"]).unwrap(), tone[90..99]);
+        assert_eq!(preset(&["trim $voice 10 20", "trim $voice off"]).unwrap().len(), 99);
+
+        assert!(preset(&["trim $voice 30 10"]).is_err());
+        assert!(preset(&["trim $voice 0 1b"]).is_err()); // no tempo
+    }
+
+    #[test]
+    fn test_seek() {
+        let path = std::env::temp_dir().join(format!("blast_seek_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let tone: Vec<i16> = (0..100).map(|i| (i * 100) as i16).collect();
+        wav::write(path, 8000, 1, &tone).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let preset = |lines: &[&str]| {
+            let mut preset = vec!["fade 0".to_string(), "load $voice -t b:24000".to_string(), "start -v $voice".to_string()];
+            preset.extend(lines.iter().map(|l| l.to_string()));
+            offline::process(&preset, af.clone(), 10.0)
+        };
+
+        // the playhead jumps, in frames, ms or beats (of 20 frames)
+        assert_eq!(preset(&["seek $voice 70"]).unwrap(), tone[70..99]);
+        assert_eq!(preset(&["seek $voice 5ms"]).unwrap(), tone[40..99]);
+        assert_eq!(preset(&["seek $voice 2.5b"]).unwrap(), tone[50..99]);
+        // quantized to the nearest beat
+        assert_eq!(preset(&["seek $voice 67 -q"]).unwrap(), tone[60..99]);
+        // and kept inside a trim
+        assert!(preset(&["trim $voice 10 30", "seek $voice 80"]).unwrap().is_empty());
+        assert_eq!(preset(&["trim $voice 10 30", "seek $voice 0"]).unwrap(), tone[10..30]);
+    }
+
+    #[test]
+    fn test_clone() {
+        let path = std::env::temp_dir().join(format!("blast_clone_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        wav::write(path, 8000, 1, &[1000i16; 100]).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let preset = |lines: &[&str]| {
+            let mut preset = vec![
+                "fade 0".to_string(),
+                "load $voice".to_string(),
+                "gain -v $voice 0.5".to_string(),
+                "trim $voice 10 30".to_string(),
+                "clone $voice twin".to_string(),
+            ];
+            preset.extend(lines.iter().map(|l| l.to_string()));
+            offline::process(&preset, af.clone(), 10.0)
+        };
+
+        // the copy keeps the trim and gain
+        let out = preset(&["start -v twin"]).unwrap();
+        assert_eq!(out, [500; 20]);
+        // and plays on its own from there
+        let out = preset(&["gain -v twin 0.25", "mute -v $voice", "start -v $voice", "start -v twin"]).unwrap();
+        assert_eq!(out, [250; 20]);
+        let out = preset(&["gain -v twin 0.25", "start -v $voice", "start -v twin"]).unwrap();
+        assert_eq!(out, [750; 20]);
+        // names are still unique
+        assert!(preset(&["clone $voice twin"]).is_err());
+    }
+
+    #[test]
+    fn test_start_fades_in() {
+        let path = std::env::temp_dir().join(format!("blast_fade_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        wav::write(path, 8000, 1, &[10000i16; 200]).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // 5ms at 8kHz is a 40 frame ramp
+        let out = offline::process(&["fade 5".to_string()], af, 10.0).unwrap();
+        assert_eq!(out[0], 0);
+        assert!((4900..=5100).contains(&out[20]));
+        assert!(out[40..].iter().all(|s| *s == 10000));
+    }
+
+    #[test]
+    fn test_poly_instances() {
+        let path = std::env::temp_dir().join(format!("blast_poly_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        wav::write(path, 8000, 1, &[1000i16; 200]).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let preset = |lines: &[&str]| {
+            let mut preset = vec!["fade 0".to_string()];
+            preset.extend(lines.iter().map(|l| l.to_string()));
+            offline::process(&preset, af.clone(), 10.0).unwrap()
+        };
+
+        // a monophonic restart cuts itself off
+        let out = preset(&["start -v $voice", "start -v $voice"]);
+        assert_eq!(out[0], 1000);
+
+        // a polyphonic one stacks
+        let out = preset(&["poly $voice 4", "start -v $voice", "start -v $voice", "start -v $voice"]);
+        assert_eq!(out[0], 3000);
+        assert_eq!(out.len(), 199);
+
+        // once full, `never` ignores restarts
+        let out = preset(&["poly $voice 2 never", "start -v $voice", "start -v $voice", "start -v $voice"]);
+        assert_eq!(out[0], 2000);
+    }
+
+    #[test]
+    fn test_plan_commit() {
+        let tracks = ["kick", "snare"]
+            .iter()
+            .map(|name| AudioFile::new(name, name, "wav", 8000, 1, 16, vec![0; 16]))
+            .collect();
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks, 2));
+
+        // planning doesn't change anything
+        assert!(cmd_processor.plan("load kick".to_string()).is_ok());
+        assert!(cmd_processor.plan("load kick".to_string()).is_ok());
+
+        // nor does a commit the engine never got
+        let plan = cmd_processor.prepare("load kick".to_string()).unwrap().unwrap();
+        let sent = cmd_processor.commit(plan, |_| Err("Command queue full"));
+        assert!(sent.is_err());
+        assert!(cmd_processor.parse("load kick".to_string()).is_ok());
+        assert!(matches!(
+            cmd_processor.parse("load kick".to_string()),
+            Err(CmdErr::AlreadyIs { .. })
+        ));
+
+        // a Group that fails halfway keeps its Voices out of it
+        cmd_processor.parse("load snare".to_string()).unwrap();
+        assert!(cmd_processor.parse("group g -v kick,hat".to_string()).is_err());
+        assert!(cmd_processor.parse("start -v kick".to_string()).is_ok());
+        assert!(cmd_processor.parse("group g -v kick,snare".to_string()).is_ok());
+        assert!(cmd_processor.parse("start -v kick".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_schema_matches_parser() {
+        let tracks = vec![AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![0; 16])];
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks, 2));
+
+        // every name in the schema reaches a parser (which then
+        // complains about its arguments, not the command)
+        for spec in schema::COMMANDS {
+            for name in std::iter::once(&spec.name).chain(spec.aliases) {
+                if *name == "quit" || *name == "q" {
+                    continue; // would be sent, not rejected
+                }
+                let result = cmd_processor.parse(name.to_string());
+                assert!(!matches!(result, Err(CmdErr::NoCmd { .. })), "{name}");
+            }
+        }
+
+        // and every command prepare or plan takes has a spec: the
+        // names their top-level matches dispatch on, read from the source
+        let source = include_str!("audio_processing/commands.rs");
+        let from = source.find("    pub fn prepare(").unwrap();
+        let to = source.find("    fn plan_redo(").unwrap();
+        let dispatched: Vec<&str> = source[from..to]
+            .lines()
+            .filter(|line| line.len() - line.trim_start().len() == 12)
+            .filter_map(|line| line.trim().split_once(" =>"))
+            .map(|(pattern, _)| pattern.split(" if ").next().unwrap())
+            .filter(|pattern| pattern.starts_with('"'))
+            .flat_map(|pattern| pattern.split(" | ").map(|name| name.trim_matches('"')))
+            .collect();
+        assert!(dispatched.contains(&"load") && dispatched.contains(&"tempocon") && dispatched.contains(&"help"));
+        for name in dispatched {
+            assert!(schema::find(name).is_some(), "{name} has no CommandSpec");
+        }
+
+        assert!(schema::find("tempocon").is_some_and(|spec| spec.name == "tc"));
+        let json = schema::to_json();
+        assert!(json.starts_with("{\"commands\":[{\"name\":\"load\""));
+    }
+
+    #[test]
+    fn test_help() {
+        let text = schema::help(Some("seq")).unwrap();
+        assert!(text.starts_with("usage: seq <voice>"), "{text}");
+        assert!(text.contains("-s, --steps") && text.contains("seq kick -t c:beat -p 16 -s 0,4,8,12"), "{text}");
+        assert!(schema::help(None).unwrap().lines().any(|l| l.starts_with("tempo ")));
+        assert!(schema::help(Some("nothing")).is_none());
+
+        let tracks = ["kick", "snare", "hat", "pad"].map(|name| AudioFile::new(name, name, "wav", 8000, 1, 16, vec![0; 16]));
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.to_vec(), 2));
+        match cmd_processor.parse("strat -v kick".to_string()) {
+            Err(error @ CmdErr::NoCmd { .. }) => assert_eq!(error.to_string(), "Invalid command 'strat'; did you mean 'start'?"),
+            _ => panic!("strat isn't a command"),
+        }
+        assert!(matches!(
+            cmd_processor.parse("help sqe".to_string()),
+            Err(CmdErr::NoCmd { suggestion: Some(s), .. }) if s == "seq",
+        ));
+        assert!(matches!(cmd_processor.parse("xyzzy".to_string()), Err(CmdErr::NoCmd { suggestion: None, .. })));
+
+        // the examples are real lines, each command's run in order:
+        // anything wrong with them is down to what's loaded, never
+        // their syntax
+        for spec in schema::COMMANDS {
+            if matches!(spec.name, "cue" | "midi" | "rec" | "script" | "session" | "import-stems" | "capture") {
+                continue; // these reach for files, devices or the live clock
+            }
+            let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.to_vec(), 2));
+            for line in ["tc beat b:120", "load kick", "load snare", "load hat", "load pad", "keymap pad root C3"] {
+                cmd_processor.parse(line.to_string()).unwrap();
+            }
+            for example in schema::examples(spec.name) {
+                let result = cmd_processor.parse(example.to_string());
+                assert!(
+                    matches!(result, Ok(_) | Err(CmdErr::AlreadyIs { .. } | CmdErr::NoItem { .. } | CmdErr::NoVoice { .. })),
+                    "{example}: {:?}", result.err(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_derived_flags() {
+        sample_rate::set(8000);
+        let tracks = ["kick", "hat"].map(|name| AudioFile::new(name, name, "wav", 8000, 1, 16, vec![0; 16]));
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.to_vec(), 2));
+        cmd_processor.parse("load kick".to_string()).unwrap();
+
+        // help shows the derived usage, and every derived flag
+        for spec in schema::COMMANDS {
+            assert_eq!(spec.usage.matches("{}").count(), spec.flags.len(), "{}", spec.name);
+            let text = schema::help(Some(spec.name)).unwrap();
+            for (usage, args) in spec.flags {
+                assert!(text.lines().next().unwrap().contains(usage), "{}: {usage}", spec.name);
+                for arg in *args {
+                    assert!(text.contains(&arg.flags.join(", ")) && text.contains(arg.help), "{}: {}", spec.name, arg.name);
+                }
+            }
+        }
+        assert_eq!(LoadFlags::USAGE, "[-t <tempo>] [--autoloop] [--normalize [<lufs>]]");
+        assert_eq!(BundleFlags::USAGE, "[--hashes] [-f <file>]...");
+        assert_eq!(DelayFlags::HELP[0].flags, ["-f", "--feedback"]);
+
+        let err = |line: &str| cmd_processor.plan(line.to_string()).err();
+        assert!(matches!(
+            err("delay -v kick 1/8 -f"),
+            Some(CmdErr::MissingArg { arg, cmd }) if arg == "feedback" && cmd == "delay -f",
+        ));
+        assert!(matches!(
+            err("delay -v kick 1/8 --mix 2"),
+            Some(CmdErr::InvalidArg { arg, cmd }) if arg == "2" && cmd == "delay -m",
+        ));
+        assert!(matches!(
+            err("verb master -x 1"),
+            Some(CmdErr::InvalidArg { arg, .. }) if arg == "-x",
+        ));
+        assert!(matches!(
+            err("lfo kick gain -w saw"),
+            Some(CmdErr::InvalidArg { arg, cmd }) if arg == "saw" && cmd == "lfo -w",
+        ));
+        assert!(err("lfo kick pan --wave tri -t m:250 -d 0.2").is_none());
+
+        // a value that can be left off isn't taken from the next flag
+        assert!(err("load hat --normalize -t b:90").is_none());
+        assert!(err("load hat --normalize -20 --autoloop").is_none());
+        assert!(matches!(err("load hat --normalize loud"), Some(CmdErr::InvalidArg { arg, .. }) if arg == "loud"));
+        // flags come in any order; -c and -v still need steps
+        assert!(err("seq kick -c a:50 -v _,0.5 -s 0,2 -p 8").is_none());
+        assert!(matches!(err("seq kick -c a:50"), Some(CmdErr::Formatting { .. })));
+        // a flag given twice is turned down, unless it repeats
+        assert!(matches!(
+            err("group g2 -v nope -v kick"),
+            Some(CmdErr::FlagTwice { flag, cmd }) if flag == "-v" && cmd == "group",
+        ));
+        assert!(matches!(err("load hat --autoloop --autoloop"), Some(CmdErr::FlagTwice { .. })));
+        assert!(matches!(err("delay -v kick 1/8 -f 0.2 --feedback 0.3"), Some(CmdErr::FlagTwice { flag, .. }) if flag == "--feedback"));
+        assert!(matches!(
+            err("group drums -v kick,hat"),
+            Some(CmdErr::NoVoice { name, .. }) if name == "hat",
+        ));
+        assert!(matches!(
+            cmd_processor.parse("wave kick -r 20".to_string()),
+            Err(CmdErr::InvalidArg { arg, cmd }) if arg == "20" && cmd == "wave -r",
+        ));
+        assert!(matches!(
+            cmd_processor.parse("bundle export out.blastpack --hashes -f".to_string()),
+            Err(CmdErr::MissingArg { arg, cmd }) if arg == "file" && cmd == "bundle export -f",
+        ));
+        assert!(cmd_processor.parse("capture kick 2 -b 3 -q 2".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_mute_solo() {
+        sample_rate::set(8000);
+        let tracks: HashMap<String, AudioFile> = [("kick", 1000), ("snare", 100)]
+            .iter()
+            .map(|&(name, level)| {
+                let af = AudioFile::new(name, name, "wav", 8000, 1, 16, vec![level; 800]);
+                (name.to_string(), af)
+            })
+            .collect();
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("fade 0");
+        run("load kick");
+        run("load snare");
+        run("start -v kick");
+        assert_eq!(run("start -v snare"), 1100);
+        assert_eq!(run("solo -v kick"), 1000);
+        assert_eq!(run("mute -v kick"), 0); // muting wins
+        assert_eq!(run("solo -v kick off"), 100);
+        assert_eq!(run("mute -v kick off"), 1100);
+    }
+
+    #[test]
+    fn test_stalled_tempo() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![1000; 800]);
+        let tracks = HashMap::from([("kick".to_string(), af)]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut run = |line: &str| {
+            let (cmd, kept) = tui::tee(|| cmd_processor.parse(line.to_string()).unwrap());
+            if let Some(cmd) = cmd {
+                conductor.apply(cmd);
+            }
+            kept.into_iter().map(|l| l.text).collect::<Vec<_>>()
+        };
+
+        // the warning comes from the CmdProcessor, not the engine
+        run("fade 0");
+        run("tc beat b:120");
+        run("load kick -t c:beat");
+        assert_eq!(run("start -v kick"), ["Warn: Tempo not active for Voice kick"]);
+        run("start -t beat");
+        assert!(run("start -v kick").is_empty());
+        assert!(run("start all").is_empty());
+
+        let mut frame = [0i16; 1];
+        conductor.render(&mut frame);
+        assert_eq!(frame[0], 1000);
+    }
+
+    #[test]
+    fn test_voice_pool() {
+        sample_rate::set(8000);
+        let tracks: HashMap<String, AudioFile> = ["a", "b", "c"]
+            .iter()
+            .map(|&name| (name.to_string(), AudioFile::new(name, name, "wav", 8000, 1, 16, vec![100; 80])))
+            .collect();
+
+        let mut engine_state = EngineState::new(tracks.clone().into_values().collect(), 1);
+        let mut conductor = Conductor::prepare(1, tracks);
+        engine_state.set_pool(2);
+        conductor.reserve(2);
+        let mut cmd_processor = CmdProcessor::new(engine_state);
+        let mut run = |line: &str| {
+            let cmd = cmd_processor.parse(line.to_string())?;
+            if let Some(cmd) = cmd {
+                conductor.apply(cmd);
+            }
+            Ok::<(), CmdErr>(())
+        };
+
+        run("fade 0").unwrap();
+
+        // Voices in Groups take slots too
+        run("load a").unwrap();
+        run("load b").unwrap();
+        run("group ab -v b").unwrap();
+        assert!(matches!(run("load c"), Err(CmdErr::Formatting { .. })));
+
+        // an unloaded Voice's slot goes to the next
+        run("unload a").unwrap();
+        run("load c").unwrap();
+        run("start -v c").unwrap();
+        let mut frame = [0i16; 1];
+        conductor.render(&mut frame);
+        assert_eq!(frame[0], 100);
+
+        let config = Config::parse("voices = 16\n").unwrap();
+        assert_eq!(config.voices, Some(16));
+        assert!(Config::parse("voices = 0\n").is_err());
+    }
+
+    #[test]
+    fn test_responses() {
+        use std::sync::Arc;
+
+        sample_rate::set(8000);
+        let af = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![100; 80]);
+        let tracks = HashMap::from([("kick".to_string(), af)]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let queue = Arc::new(ResponseQueue::new(4));
+        conductor.respond_to(Arc::clone(&queue));
+
+        // a Command for a Voice the engine doesn't have
+        cmd_processor.parse("load kick".to_string()).unwrap();
+        conductor.apply(cmd_processor.parse("trig kick".to_string()).unwrap().unwrap());
+        let session = |action| Command::Session(SessionArgs { action });
+        conductor.apply(session(SessionAction::Crossfade(4)));
+        conductor.apply(session(SessionAction::Load(cmd_processor.state().session(Vec::new()))));
+        conductor.apply(session(SessionAction::Crossfade(4)));
+        conductor.render(&mut [0i16; 8]);
+
+        // in order, and one too many for the queue is dropped
+        let responses: Vec<Response> = std::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(responses, [
+            Response::Err("No such Voice in the engine"),
+            Response::Warn("No session loaded to crossfade to"),
+            Response::Ok("Session loaded in the background"),
+        ]);
+        assert_eq!(queue.dropped(), 1);
+
+        conductor.apply(session(SessionAction::Load(cmd_processor.state().session(Vec::new()))));
+        assert_eq!(queue.pop(), Some(Response::Ok("Session loaded in the background")));
+        conductor.apply(session(SessionAction::Crossfade(4)));
+        conductor.render(&mut [0i16; 8]);
+        assert_eq!(queue.pop(), Some(Response::Ok("Crossfade done")));
+    }
+
+    #[test]
+    fn test_block_render() {
+        sample_rate::set(8000);
+        let tracks: HashMap<String, AudioFile> = ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(n, &name)| {
+                let samples = (0..3000).map(|i| ((i * (n + 3) * 37) % 2000) as i16 - 1000).collect();
+                (name.to_string(), AudioFile::new(name, name, "wav", 8000, 1, 16, samples))
+            })
+            .collect();
+        let lines = [
+            "load a", "load b", "load c",
+            "seq a -t s:37 -p 4 -s 0,2",
+            "lfo b gain -w sine -t s:100 -d 0.5",
+            "group g -v b,c",
+            "delay -g g m:10 -f 0.4 -m 0.3",
+            "verb master -r 0.5 -d 0.4 -m 0.2",
+            "start all",
+        ];
+
+        // however many frames are rendered at a time (a period
+        // over a BLOCK, or one by one), the output is the same
+        let render = |at_once: usize| {
+            let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 2));
+            let mut conductor = Conductor::prepare(2, tracks.clone());
+            let mut out = Vec::new();
+            let mut play = |conductor: &mut Conductor, frames: usize| {
+                let mut period = vec![0i16; at_once * 2];
+                for _ in 0..frames / at_once {
+                    conductor.render(&mut period);
+                    out.extend_from_slice(&period);
+                }
+            };
+            for line in lines {
+                conductor.apply(cmd_processor.parse(line.to_string()).unwrap().unwrap());
+            }
+            play(&mut conductor, 700);
+            conductor.apply(cmd_processor.parse("pause -g g".to_string()).unwrap().unwrap());
+            play(&mut conductor, 700);
+            out
+        };
+
+        let whole = render(700);
+        assert!(whole.iter().any(|&s| s != 0));
+        assert_eq!(whole, render(1));
+    }
+
+    #[test]
+    fn test_simd() {
+        // odd lengths leave a tail for the plain loop
+        for len in [0, 3, 4, 37] {
+            let src: Vec<f32> = (0..len).map(|i| (i as f32 - 17.3) * 1234.567).collect();
+            let gains: Vec<f32> = (0..len).map(|i| 1.0 / (i as f32 + 1.5)).collect();
+            let start: Vec<f32> = (0..len).map(|i| i as f32 * 0.5).collect();
+
+            let (mut fast, mut plain) = (start.clone(), start.clone());
+            simd::add(&mut fast, &src);
+            simd::scalar::add(&mut plain, &src);
+            assert_eq!(fast, plain);
+
+            let (mut fast, mut plain) = (start.clone(), start.clone());
+            simd::add_product(&mut fast, &src, &gains);
+            simd::scalar::add_product(&mut plain, &src, &gains);
+            assert_eq!(fast, plain);
+
+            let (mut fast, mut plain) = (src.clone(), src.clone());
+            simd::scale(&mut fast, 0.7);
+            simd::scalar::scale(&mut plain, 0.7);
+            assert_eq!(fast, plain);
+        }
+
+        // clipped, truncated and NaN as silence, as `as` does
+        let src = [1e9, -1e9, 32767.9, -32768.9, -0.7, 0.7, f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 100.5, -3.0];
+        let (mut fast, mut plain) = ([1i16; 11], [1i16; 11]);
+        simd::to_i16(&mut fast, &src);
+        simd::scalar::to_i16(&mut plain, &src);
+        assert_eq!(fast, plain);
+        assert_eq!(fast[..4], [i16::MAX, i16::MIN, i16::MAX, i16::MIN]);
+        assert_eq!(fast[6], 0);
+
+        let mut left = [0f32; 3];
+        simd::deinterleave(&mut left, &[16384, 1, -32768, 2, 0, 3], 2, 0);
+        assert_eq!(left, [0.5, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_reaper() {
+        use std::sync::Arc;
+        sample_rate::set(8000);
+        let af = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![1000; 800]);
+        let track = af.samples.clone();
+        let tracks = HashMap::from([("kick".to_string(), af.clone())]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let reaped = Arc::new(ReapQueue::new(16));
+        conductor.reap_with(Arc::clone(&reaped));
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+        };
+
+        run("fade 0");
+        run("load kick");
+        run("delay -v kick m:10");
+        run("verb -v kick");
+        run("seq kick -p 4 -s 0,2");
+        run("start -v kick");
+        assert!(reaped.pop().is_none());
+
+        // its hold on the Track's samples (shared, not copied), its
+        // delay and reverb lines, its Seq's two steps (with their
+        // chances, jitter and levels), and its bus and routing, not
+        // freed in place
+        run("unload kick");
+        assert!(matches!(reaped.pop(), Some(Remains::Samples(samples)) if Samples::ptr_eq(&samples, &track)));
+        assert!(matches!(reaped.pop(), Some(Remains::Buf(buf)) if !buf.is_empty()));
+        assert!(matches!(reaped.pop(), Some(Remains::Verb(_))));
+        for _ in 0..4 {
+            assert!(matches!(reaped.pop(), Some(Remains::Buf(buf)) if buf.len() == 2));
+        }
+        assert!(matches!(reaped.pop(), Some(Remains::Buf(buf)) if !buf.is_empty()));
+        assert!(matches!(reaped.pop(), Some(Remains::Routing(_))));
+        assert!(reaped.pop().is_none());
+
+        // a full queue leaves them to the audio thread (a bare
+        // Voice leaves three: samples, bus and routing)
+        for _ in 0..5 {
+            run("load kick");
+            run("unload kick");
+        }
+        assert_eq!(reaped.inline(), 0);
+        run("load kick");
+        run("unload kick");
+        assert_eq!(reaped.inline(), 3);
+    }
+
+    #[test]
+    fn test_reap_session() {
+        use std::sync::Arc;
+        use crate::audio_processing::commands::{HistoryEntry, UnloadArgs};
+        sample_rate::set(8000);
+        let af = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![1000; 800]);
+        let tracks = HashMap::from([("kick".to_string(), af.clone())]);
+        let cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let reaped = Arc::new(ReapQueue::new(16));
+        conductor.reap_with(Arc::clone(&reaped));
+        let session = |action| Command::Session(SessionArgs { action });
+
+        // built on this side, Voice and all
+        let built = || {
+            let history = [HistoryEntry { rev: 1, line: "load kick".to_string(), undo: None }];
+            let (state, commands) = cmd_processor.replay(&history).unwrap();
+            state.session(commands)
+        };
+        conductor.apply(session(SessionAction::Load(built())));
+        conductor.apply(session(SessionAction::Crossfade(4)));
+        assert!(reaped.pop().is_none());
+
+        // the one faded out goes whole, once the crossfade's done
+        conductor.render(&mut [0i16; 8]);
+        assert!(matches!(reaped.pop(), Some(Remains::Session(_))));
+        assert!(reaped.pop().is_none());
+
+        // and the Voice that came with the new one hands its buffers over too
+        conductor.apply(Command::Fade(FadeArgs { ms: 0.0 }));
+        conductor.apply(Command::Unload(UnloadArgs { idx: 0 }));
+        assert!(matches!(reaped.pop(), Some(Remains::Samples(_))));
+        while reaped.pop().is_some() {}
+
+        // as does one loaded and dropped, or loaded over
+        conductor.apply(session(SessionAction::Load(built())));
+        conductor.apply(session(SessionAction::Load(built())));
+        assert!(matches!(reaped.pop(), Some(Remains::Session(_))));
+        conductor.apply(session(SessionAction::Drop));
+        assert!(matches!(reaped.pop(), Some(Remains::Session(_))));
+        assert!(reaped.pop().is_none());
+    }
+
+    #[test]
+    fn test_reap_replaced() {
+        use std::sync::Arc;
+        use crate::audio_processing::commands::StartArgs;
+        sample_rate::set(8000);
+        let af = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![1000; 800]);
+        let tracks = HashMap::from([("kick".to_string(), af.clone())]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 2));
+        let mut conductor = Conductor::prepare(2, tracks);
+        let reaped = Arc::new(ReapQueue::new(16));
+        let responses = Arc::new(ResponseQueue::new(4));
+        conductor.reap_with(Arc::clone(&reaped));
+        conductor.respond_to(Arc::clone(&responses));
+        let mut run = |line: &str| {
+            conductor.apply(cmd_processor.parse(line.to_string()).unwrap().unwrap());
+        };
+
+        run("load kick");
+        run("delay -v kick m:10");
+        run("verb -v kick");
+        run("seq kick -p 4 -s 0,2");
+        run("poly kick 4");
+        assert!(reaped.pop().is_none());
+
+        // whatever a Command replaces is handed over, not freed in place
+        run("delay -v kick m:20");
+        assert!(matches!(reaped.pop(), Some(Remains::Buf(buf)) if !buf.is_empty()));
+        run("verb -v kick");
+        assert!(matches!(reaped.pop(), Some(Remains::Verb(_))));
+        run("proc set kick.seq steps 0,1,3");
+        for _ in 0..4 {
+            assert!(matches!(reaped.pop(), Some(Remains::Buf(buf)) if buf.len() == 2));
+        }
+        run("out kick 1");
+        assert!(matches!(reaped.pop(), Some(Remains::Routing(_))));
+        run("poly kick 2");
+        assert!(matches!(reaped.pop(), Some(Remains::Tails(_))));
+        run("proc rm kick.seq");
+        assert_eq!(std::iter::from_fn(|| reaped.pop()).count(), 4);
+        run("delay -v kick off");
+        assert!(matches!(reaped.pop(), Some(Remains::Buf(buf)) if !buf.is_empty()));
+        assert!(reaped.pop().is_none());
+
+        // and what it names that the engine doesn't have is said so, not a panic
+        let idxs = vec![Idx::Voice(3), Idx::Group(0), Idx::Tempo(0)];
+        conductor.apply(Command::Start(StartArgs { idxs }));
+        assert_eq!(std::iter::from_fn(|| responses.pop()).collect::<Vec<_>>(), [
+            Response::Err("No such Voice in the engine"),
+            Response::Err("No such Group in the engine"),
+            Response::Err("No such TempoContext in the engine"),
+        ]);
+    }
+
+    #[test]
+    fn test_status_pattern() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![0; 800]);
+        let tracks = HashMap::from([("kick".to_string(), af)]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        for line in ["load kick", "seq kick -t s:1 -p 8 -s 0,3,6", "start -v kick"] {
+            conductor.apply(cmd_processor.parse(line.to_string()).unwrap().unwrap());
+        }
+
+        // nothing has played yet
+        assert_eq!(cmd_processor.status("kick".to_string()).unwrap(), "kick  voice 0  playing  tempo none\n  seq  x..x..x.");
+
+        // one sample per step, so steps 0 and 3 have played
+        let mut frame = [0i16; 1];
+        for _ in 0..5 {
+            conductor.render(&mut frame);
+        }
+        assert_eq!(
+            cmd_processor.status("kick".to_string()).unwrap(),
+            "kick  voice 0  playing  tempo none\n  seq  x..\x1b[7mx\x1b[0m..x."
+        );
+    }
+
+    #[test]
+    fn test_events() {
+        use crate::prelude::{Event, EventBus};
+        use crate::audio_processing::events::EventQueue;
+        use std::sync::Arc;
+
+        sample_rate::set(8000);
+        let kick = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![0; 800]);
+        let blip = AudioFile::new("blip", "blip", "wav", 8000, 1, 16, vec![100; 4]);
+        let tracks = HashMap::from([("kick".to_string(), kick), ("blip".to_string(), blip)]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let queue = Arc::new(EventQueue::new(1024));
+        conductor.publish(Arc::clone(&queue));
+        for line in ["fade 0", "load kick", "load blip", "seq kick -t s:1 -p 8 -s 0,3,6", "start -v kick", "start -v blip"] {
+            conductor.apply(cmd_processor.parse(line.to_string()).unwrap().unwrap());
+        }
+
+        // steps as they fire, and a Voice that plays out, named
+        // as the REPL names them
+        let mut frame = [0i16; 1];
+        for _ in 0..5 {
+            conductor.render(&mut frame);
+        }
+        let happened = |queue: &EventQueue| {
+            std::iter::from_fn(|| queue.pop())
+                .filter(|(_, event)| !matches!(event, Event::Beat(_)))
+                .map(|(_, event)| event.describe(&cmd_processor.engine_state))
+                .collect::<Vec<_>>()
+        };
+        let mut seen = happened(&queue);
+        seen.sort();
+        assert_eq!(seen, ["end blip", "step kick 1", "step kick 2"]);
+
+        // and every beat the clock crosses
+        let mut beat = None;
+        for _ in 0..100_000 {
+            conductor.render(&mut frame);
+            if let Some((_, Event::Beat(at))) = std::iter::from_fn(|| queue.pop()).find(|(_, e)| matches!(e, Event::Beat(_))) {
+                beat = Some(at);
+                break;
+            }
+        }
+        assert!(beat.is_some_and(|at| at.phase == 0.0));
+
+        // a full queue drops what doesn't fit, and counts it
+        let small = EventQueue::new(4);
+        for step in 0..5 {
+            small.push(step, Event::End(Idx::Voice(0)));
+        }
+        assert_eq!(small.dropped(), 2);
+        assert_eq!(std::iter::from_fn(|| small.pop()).count(), 3);
+
+        // subscribers each get every event
+        let bus = EventBus::new();
+        let (first, second) = (bus.subscribe(), bus.subscribe());
+        bus.queue().push(7, Event::End(Idx::Voice(1)));
+        for rx in [first, second] {
+            assert_eq!(rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap(), (7, Event::End(Idx::Voice(1))));
+        }
+    }
+
+    #[test]
+    fn test_capture_quantize() {
+        // 1 bar of 4 beats, 4000 samples a beat, in 16ths
+        let mut capture = Capture::new("kick".to_string(), 500, 1, 4, 4000.0, 4);
+        for t in [400, 500, 1480, 2540, 8510, 16300, 16500] {
+            capture.record(t);
+        }
+
+        // before the start and after the end are dropped;
+        // 16300 rounds up to step 16, which wraps to 0
+        assert_eq!(capture.hits(), 5);
+        assert!(!capture.done(16499));
+        assert!(capture.done(16500));
+        assert_eq!(capture.seq_line(), "seq kick -t s:1000 -p 16 -s 0,1,2,8");
+    }
+
+    #[test]
+    fn test_follow_tempo() {
+        use std::{rc::Rc, cell::RefCell};
+        use crate::audio_processing::{
+            engine::{VoiceState, Looping, Mods},
+            processes::{Process, Follow},
+            blast_time::blast_time::{TempoState, TempoMode, TempoUnit},
+        };
+
+        sample_rate::set(8000);
+        let tc = Rc::new(RefCell::new(TempoState::new(Some(TempoMode::Context))));
+        tc.borrow_mut().init(TempoMode::Context, TempoUnit::Bpm, 100.0);
+
+        let mut voice = VoiceState {
+            active: true,
+            position: 0.0,
+            start: 0,
+            end: 0,
+            velocity: 1.0,
+            gain: 1.0,
+            pan: 0.0,
+            mods: Mods::NONE,
+            muted: false,
+            soloed: false,
+            looping: Looping::Off,
+            loops_left: None,
+            trigger: false,
+            trigger_level: 1.0,
+            fired: None,
+            ended: false,
+            hit: 1.0,
+            trig_at: None,
+            offset: 0.0,
+            level: 0.0,
+            tempo: Rc::clone(&tc),
+        };
+        let mut follow = Process::Follow(Follow::new(Rc::clone(&tc), 60.0, 180.0));
+
+        // a click every 4000 samples is 120bpm at 8kHz
+        for n in 0..160_000 {
+            tc.borrow_mut().update(1.0);
+            voice.level = if n % 4000 < 40 { 0.6 } else { 0.0 };
+            follow.process(&mut voice);
+        }
+
+        let interval = tc.borrow().interval;
+        assert_eq!(interval, 4000.0);
+    }
+
+    #[test]
+    fn test_lfo_square_gain() {
+        let path = std::env::temp_dir().join(format!("blast_lfo_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        wav::write(path, 8000, 1, &[1000i16; 400]).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // a 100 sample cycle, fully on for the first half
+        let preset = ["fade 0", "lfo $voice gain -w square -t s:100 -d 1"].map(String::from);
+        let out = offline::process(&preset, af, 10.0).unwrap();
+        assert_eq!((out[0], out[49], out[50], out[99], out[100]), (1000, 1000, 0, 0, 1000));
+    }
+
+    #[test]
+    fn test_session_crossfade() {
+        sample_rate::set(8000);
+        let tracks: HashMap<String, AudioFile> = [("old", 1000), ("new", 2000)]
+            .iter()
+            .map(|&(name, level)| {
+                let af = AudioFile::new(name, name, "wav", 8000, 1, 16, vec![level; 800]);
+                (name.to_string(), af)
+            })
+            .collect();
+
+        let script = std::env::temp_dir().join(format!("blast_session_{}.txt", std::process::id()));
+        std::fs::write(&script, "# handover\nfade 0\nload new\nstart -v new\n").unwrap();
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("fade 0");
+        run("load old");
+        assert_eq!(run("start -v old"), 1000);
+        // the next session is silent until it's crossfaded in
+        assert_eq!(run(&format!("session load {}", script.display())), 1000);
+        std::fs::remove_file(&script).unwrap();
+
+        // 10ms is 80 frames; halfway, both are at cos(45°)
+        run("session crossfade next 10ms");
+        let mut frame = [0i16; 1];
+        for _ in 0..39 {
+            conductor.render(&mut frame);
+        }
+        assert_eq!(frame[0], ((1000.0 + 2000.0) * std::f32::consts::FRAC_1_SQRT_2) as i16);
+        for _ in 0..40 {
+            conductor.render(&mut frame);
+        }
+        assert_eq!(frame[0], 2000);
+
+        // and commands now go to the new session
+        assert!(cmd_processor.parse("stop -v old".to_string()).is_err());
+        assert!(cmd_processor.parse("stop -v new".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_autoloop_detect() {
+        // 100 frames of silence either side of 20 cycles of a 50 frame cosine
+        let mut samples = vec![0i16; 100];
+        samples.extend((0..1000).map(|i| ((i as f32 / 50.0 * std::f32::consts::TAU).cos() * 8000.0) as i16));
+        samples.extend([0i16; 100]);
+        let af = AudioFile::new("phrase", "phrase", "wav", 8000, 1, 16, samples);
+
+        let al = autoloop::detect(&af).unwrap();
+        assert_eq!((al.start, al.end, al.loop_end), (100, 1100, 1100));
+        assert!(al.loop_start > al.start && al.loop_start < al.loop_end);
+        assert_eq!((al.loop_end - al.loop_start) % 50, 0);
+
+        let silent = AudioFile::new("silent", "silent", "wav", 8000, 1, 16, vec![0; 800]);
+        assert_eq!(autoloop::detect(&silent), None);
+    }
+
+    #[test]
+    fn test_loudness() {
+        // a second of a half scale 997 Hz sine reads 6 dB under
+        // the -3 LUFS of full scale
+        let samples: Vec<i16> = (0..48000)
+            .map(|i| ((i as f32 * 997.0 / 48000.0 * std::f32::consts::TAU).sin() * 16384.0) as i16)
+            .collect();
+        let af = AudioFile::new("tone", "tone", "wav", 48000, 1, 16, samples);
+        let measured = loudness::measure(&af);
+        assert!((measured.lufs - -9.03).abs() < 0.1, "{}", measured.lufs);
+        assert!((measured.peak - -6.02).abs() < 0.01, "{}", measured.peak);
+
+        let silent = AudioFile::new("silent", "silent", "wav", 48000, 1, 16, vec![0; 4800]);
+        assert_eq!(loudness::measure(&silent).gain_to(loudness::TARGET_LUFS), 1.0);
+
+        // brought down to -18, and up to 0 only as far as the ceiling
+        let peak = |preset: &[&str]| {
+            let preset: Vec<String> = preset.iter().map(|l| l.to_string()).collect();
+            let out = offline::process(&preset, af.clone(), 2.0).unwrap();
+            out.iter().map(|s| s.unsigned_abs()).max().unwrap() as f32 / 16384.0
+        };
+        let db = |gain: f32| 20.0 * gain.log10();
+        assert!((db(peak(&["fade 0", "load $voice --normalize"])) - -8.97).abs() < 0.1);
+        assert!((db(peak(&["fade 0", "load $voice --normalize -23"])) - -13.97).abs() < 0.1);
+        assert!((db(peak(&["fade 0", "load $voice --normalize 0"])) - 5.02).abs() < 0.1);
+        assert!((peak(&["fade 0", "load $voice"]) - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_resample_to() {
+        // a 1 kHz sine at 44.1k comes out as the same sine at 48k
+        let sine = |rate: f64, frames: usize| -> Vec<i16> {
+            (0..frames).map(|i| ((i as f64 * 1000.0 / rate * std::f64::consts::TAU).sin() * 16000.0).round() as i16).collect()
+        };
+        let mut af = AudioFile::new("tone", "tone", "wav", 44100, 1, 16, sine(44100.0, 4410));
+        af.loop_points = Some((441, 4410));
+        af.resample_to(48000);
+        assert_eq!((af.sample_rate, af.frames(), af.loop_points), (48000, 4800, Some((480, 4800))));
+        let want = sine(48000.0, 4800);
+        // away from the ends, where the kernel runs off the input
+        let err = (200..4600).map(|i| (af.samples[i] as i32 - want[i] as i32).abs()).max().unwrap();
+        assert!(err <= 4, "{err}");
+
+        // and a tone over the new Nyquist is filtered out rather
+        // than folded back down
+        let mut high = AudioFile::new("high", "high", "wav", 48000, 2, 16, sine(48000.0 / 15.0, 9600).iter().flat_map(|&s| [s, s]).collect());
+        high.resample_to(22050);
+        assert_eq!(high.num_channels, 2);
+        let loudest = high.samples[400..high.samples.len() - 400].iter().map(|s| s.unsigned_abs()).max().unwrap();
+        assert!(loudest < 50, "{loudest}");
+    }
+
+    #[test]
+    fn test_shared_samples() {
+        use std::sync::Arc;
+        sample_rate::set(8000);
+        let af = AudioFile::new("ramp", "ramp", "wav", 8000, 1, 16, (0..800).collect());
+        let Samples::Decoded(track) = af.samples.clone() else { panic!() };
+        let tracks = HashMap::from([("ramp".to_string(), af.clone())]);
+        let mut conductor = Conductor::prepare(1, tracks);
+        // planning copies the EngineState, never the samples
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        let held = Arc::strong_count(&track);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        // each Voice holds the Track's samples rather than a copy
+        run("fade 0");
+        run("load ramp");
+        assert_eq!(Arc::strong_count(&track), held + 1);
+        run("clone ramp twin");
+        run("trim twin 100 400");
+        assert_eq!(Arc::strong_count(&track), held + 2);
+
+        // and reads its own part of them
+        run("start -v ramp");
+        assert_eq!(run("start -v twin"), 1 + 100);
+        assert_eq!(run("fade 0"), 2 + 101);
+
+        run("unload ramp");
+        run("unload twin");
+        assert_eq!(Arc::strong_count(&track), held);
+    }
+
+    #[test]
+    fn test_voice_rate() {
+        // a Track at another rate than the device's steps through its
+        // frames at the ratio, interpolating between them
+        sample_rate::set(8000);
+        let render = |rate: u32, velocity: &str| {
+            let ramp = (0..400).map(|i| i * 40).collect();
+            let tracks = HashMap::from([("ramp".to_string(), AudioFile::new("ramp", "ramp", "wav", rate, 1, 16, ramp))]);
+            let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+            let mut conductor = Conductor::prepare(1, tracks);
+            for line in ["fade 0", "load ramp", &format!("velocity ramp {velocity}"), "start -v ramp"] {
+                conductor.apply(cmd_processor.parse(line.to_string()).unwrap().unwrap());
+            }
+            let mut out = [0i16; 16];
+            conductor.render(&mut out);
+            out
+        };
+
+        // 6 kHz is 3/4 of a frame per frame, 12 kHz 3/2
+        let slow = render(6000, "1");
+        assert!(slow.iter().enumerate().all(|(i, s)| *s == (i * 30) as i16), "{slow:?}");
+        let fast = render(12000, "1");
+        assert!(fast.iter().enumerate().all(|(i, s)| *s == (i * 60) as i16), "{fast:?}");
+        // velocity on top of it
+        let both = render(4000, "1.5");
+        assert!(both.iter().enumerate().all(|(i, s)| *s == (i * 30) as i16), "{both:?}");
+        // and at the device's rate it's the samples themselves
+        let same = render(8000, "1");
+        assert!(same.iter().enumerate().all(|(i, s)| *s == (i * 40) as i16), "{same:?}");
+    }
+
+    #[test]
+    fn test_channel_maps() {
+        // a hard-left stereo pair folds to half of it in mono, and
+        // a mono Track comes out the same on both sides
+        let stereo = AudioFile::new("pair", "pair", "wav", 8000, 2, 16, vec![20000, 0, -20000, 0, 10000, 10000]);
+        let mono = stereo.to_mono();
+        assert_eq!((mono.num_channels, &*mono.samples), (1, &[10000, -10000, 10000][..]));
+        let back = mono.to_stereo();
+        assert_eq!(&*back.samples, &[10000, 10000, -10000, -10000, 10000, 10000]);
+        assert_eq!(stereo.to_stereo().samples, stereo.samples);
+
+        // 5.1 to stereo: center and surrounds 3 dB down into their
+        // sides, no LFE, and nothing past full scale
+        let map = ChannelMap::between(6, 2);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let left: Vec<f32> = (0..6).map(|src| map.gain(src, 0)).collect();
+        let total = 1.0 + 2.0 * half;
+        assert_eq!(left, [1.0 / total, 0.0, half / total, 0.0, half / total, 0.0]);
+        assert_eq!(map.gain(3, 1), 0.0);
+        let loud = AudioFile::new("loud", "loud", "wav", 8000, 6, 16, vec![i16::MAX; 6]).to_stereo();
+        assert_eq!(&*loud.samples, &[i16::MAX, i16::MAX]);
+
+        // an unknown layout keeps the channels both have
+        let quad = AudioFile::new("quad", "quad", "wav", 8000, 2, 16, vec![100, 200]).to_channels(4);
+        assert_eq!(&*quad.samples, &[100, 200, 0, 0]);
+    }
+
+    #[test]
+    fn test_sample_views() {
+        let af = AudioFile::new("pair", "pair", "wav", 8000, 2, 16, vec![i16::MIN, 16384, 0, -8192, 32767, 1]);
+        assert_eq!(af.samples_f32()[..4], [-1.0, 0.5, 0.0, -0.25]);
+        assert_eq!(af.channel(1).collect::<Vec<_>>(), [16384, -8192, 1]);
+        assert_eq!(af.channel_f32(0).take(2).collect::<Vec<_>>(), [-1.0, 0.0]);
+        assert_eq!(af.channel(2).count(), 0);
+
+        let planes = af.deinterleaved_f32();
+        assert_eq!((planes.len(), planes[1][..2].to_vec()), (2, vec![0.5, -0.25]));
+        assert!(planes[0][2] < 1.0);
+    }
+
+    #[test]
+    fn test_delay_repeats() {
+        let mut samples = vec![0i16; 400];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // half dry, half wet, each repeat half the last
+        let preset = ["fade 0", "delay -v $voice s:100 -f 0.5 -m 0.5"].map(String::from);
+        let out = offline::process(&preset, af, 10.0).unwrap();
+        assert_eq!((out[0], out[50], out[100], out[200], out[300]), (500, 0, 500, 250, 125));
+    }
+
+    #[test]
+    fn test_aux_bus() {
+        let mut samples = vec![0i16; 400];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // a bus that only echoes, once, 100 frames on
+        let preset = |lines: &[&str]| {
+            let mut preset = vec!["fade 0", "load $voice", "bus fx", "delay -b fx s:100 -f 0 -m 1"];
+            preset.extend(lines);
+            let preset: Vec<String> = preset.into_iter().map(String::from).collect();
+            offline::process(&preset, af.clone(), 10.0)
+        };
+
+        // the dry Voice, and what it sent coming back
+        let out = preset(&["send $voice fx 0.5"]).unwrap();
+        assert_eq!((out[0], out[50], out[100]), (1000, 0, 500));
+        let out = preset(&["send $voice fx 0.5", "gain -b fx 0.5"]).unwrap();
+        assert_eq!((out[0], out[100]), (1000, 250));
+        // a muted Voice sends nothing
+        let out = preset(&["send $voice fx 0.5", "mute -v $voice", "start -v $voice"]).unwrap();
+        assert!(out.iter().all(|s| *s == 0));
+        // one delay serves both
+        let out = preset(&["send $voice fx 0.5", "clone $voice twin", "start -v $voice", "start -v twin"]).unwrap();
+        assert_eq!((out[0], out[100]), (2000, 1000));
+        // a bus has no tempo to divide
+        assert!(preset(&["delay -b fx 1/8"]).is_err());
+    }
+
+    #[test]
+    fn test_offset_shifts_steps() {
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // steps at 0 and 200 of every 400, with the first
+        // step 0 played by the start; 800 frames of it
+        let hits = |offset: &str| {
+            let preset = ["fade 0", "seq $voice -t s:100 -p 4 -s 0,2", offset].map(String::from);
+            let out = offline::process(&preset, af.clone(), 0.1).unwrap();
+            out.iter().enumerate().filter(|(_, s)| **s != 0).map(|(i, _)| i).collect::<Vec<_>>()
+        };
+        assert_eq!(hits("offset $voice 0"), vec![0, 200, 400, 600]);
+        assert_eq!(hits("offset $voice -10"), vec![0, 190, 390, 590, 790]);
+        // a late step 0 still restarts what the start began
+        assert_eq!(hits("offset $voice 10"), vec![0, 10, 210, 410, 610]);
+    }
+
+    #[test]
+    fn test_out() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, vec![1000i16; 100]);
+        let tracks = HashMap::from([("click".to_string(), af.clone())]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 4));
+        let mut conductor = Conductor::prepare(4, tracks);
+        let mut frame = [0i16; 4];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame
+        };
+
+        run("fade 0");
+        run("load click");
+        run("gain master 0.5");
+        assert_eq!(run("start -v click"), [500, 500, 0, 0]);
+        // just the outputs given, past the master's gain
+        assert_eq!(run("out click 2,3"), [0, 0, 1000, 1000]);
+        assert_eq!(run("out click 1"), [0, 1000, 0, 0]);
+        // and back
+        assert_eq!(run("out click master"), [500, 500, 0, 0]);
+
+        assert!(cmd_processor.parse("out click 4".to_string()).is_err());
+        assert!(cmd_processor.parse("out click".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_gain() {
+        sample_rate::set(8000);
+        let tracks = HashMap::from([
+            ("a".to_string(), AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![1000i16; 400])),
+            ("b".to_string(), AudioFile::new("b", "b", "wav", 8000, 1, 16, vec![2000i16; 400])),
+        ]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        for line in ["fade 0", "load a", "load b", "group drums -v b"] {
+            run(line);
+        }
+        assert_eq!(run("start all"), 3000);
+        // a Voice's, its Group's and the master's multiply
+        assert_eq!(run("gain -v a 0.5"), 2500);
+        assert_eq!(run("gain -g drums 0.25"), 1000);
+        assert_eq!(run("gain -v drums.b 2"), 1500);
+        assert_eq!(run("gain master -6dB"), 751);
+        assert_eq!(run("gain master 0dB"), 1500);
+        assert_eq!(run("gain -g drums 1"), 4500);
+
+        for bad in ["gain", "gain master", "gain master -1", "gain master loud", "gain -x a 1", "gain -v c 1", "gain -g kit 1", "gain -v a"] {
+            assert!(cmd_processor.parse(bad.to_string()).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_bounce() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("hit", "hit", "wav", 8000, 1, 16, vec![1000i16; 100]);
+        let tracks = HashMap::from([("hit".to_string(), af.clone())]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("fade 0");
+        run("load hit");
+        run("group drums -v hit");
+        run("gain -g drums 0.5");
+        // rendered off to the side; nothing here starts playing
+        assert_eq!(run("bounce drums hits"), 0);
+        assert_eq!(run("bounce drums half 50"), 0);
+
+        // the Group's gain is in it, and it ends where the Group did
+        run("load hits");
+        assert_eq!(run("start -v hits"), 500);
+        let rest = (0..120).map(|_| run("fade 0")).collect::<Vec<_>>();
+        assert!(rest[..97].iter().all(|s| *s == 500));
+        assert!(rest[97..].iter().all(|s| *s == 0));
+
+        run("load half");
+        assert_eq!(run("start -v half"), 500);
+        let rest = (0..60).map(|_| run("fade 0")).collect::<Vec<_>>();
+        assert!(rest[..48].iter().all(|s| *s == 500));
+        assert!(rest[48..].iter().all(|s| *s == 0));
+
+        // a revert replays the bounces, which find their Tracks
+        run("revert 6");
+        run("load half");
+        assert!(cmd_processor.parse("bounce drums hit".to_string()).is_err());
+        assert!(cmd_processor.parse("bounce kit more".to_string()).is_err());
+
+        // the engine only has room for so many Tracks
+        let full = (0..MAX_TRACKS)
+            .map(|i| AudioFile::new(&format!("t{i}"), &format!("t{i}"), "wav", 8000, 1, 16, vec![0; 2]))
+            .collect();
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(full, 1));
+        cmd_processor.parse("load t0".to_string()).unwrap();
+        cmd_processor.parse("group drums -v t0".to_string()).unwrap();
+        match cmd_processor.parse("bounce drums more".to_string()) {
+            Err(CmdErr::Formatting { err }) => assert!(err.contains("at most"), "{err}"),
+            _ => panic!("bounced past MAX_TRACKS"),
+        }
+    }
+
+    #[test]
+    fn test_trig() {
+        sample_rate::set(8000);
+        // each sample says where it is
+        let samples = (1..=800).map(|n| n * 10).collect::<Vec<i16>>();
+        let af = AudioFile::new("hit", "hit", "wav", 8000, 1, 16, samples);
+        let tracks = HashMap::from([("hit".to_string(), af.clone())]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("load hit -t s:100");
+        // an idle Voice starts from the top, without a fade-in
+        assert_eq!(run("trig hit"), 10);
+        for _ in 0..30 {
+            run("fade 5");
+        }
+        // a playing one jumps back
+        assert_eq!(run("trig hit"), 10);
+        assert_eq!(run("fade 5"), 20);
+
+        // on the beat waits for its tempo (started by the first
+        // trig, 33 frames ago) to reach 100 samples
+        let fired = (0..100)
+            .map(|n| run(if n == 0 { "trig hit -b" } else { "fade 5" }))
+            .position(|s| s == 10);
+        assert_eq!(fired, Some(67));
+
+        assert!(cmd_processor.parse("trig hit -x".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_history_revert() {
+        sample_rate::set(8000);
+        let tracks: HashMap<String, AudioFile> = [("kick", 1000), ("snare", 2000)]
+            .iter()
+            .map(|&(name, level)| {
+                let af = AudioFile::new(name, name, "wav", 8000, 1, 16, vec![level; 800]);
+                (name.to_string(), af)
+            })
+            .collect();
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("fade 0");
+        run("load kick");
+        assert_eq!(run("start -v kick"), 1000);
+        run("load snare");
+        assert_eq!(run("start -v snare"), 3000);
+        // neither rejected lines nor control-only ones are kept
+        assert!(cmd_processor.parse("start -v hat".to_string()).is_err());
+        cmd_processor.parse("history".to_string()).unwrap();
+
+        let lines = |cmd_processor: &CmdProcessor| {
+            let history = cmd_processor.engine_state.history();
+            history.iter().map(|e| (e.rev, e.line.clone())).collect::<Vec<_>>()
+        };
+        assert_eq!(lines(&cmd_processor).len(), 5);
+        assert_eq!(lines(&cmd_processor)[2], (3, "start -v kick".to_string()));
+
+        // the history replays to the state it recorded
+        let history = cmd_processor.engine_state.history().to_vec();
+        let (replayed, commands) = cmd_processor.replay(&history).unwrap();
+        assert_eq!(replayed.history(), history.as_slice());
+        // the starts count toward it, but aren't sent
+        assert_eq!(commands.len(), 3);
+        assert!(replayed.voice("kick").unwrap().is_playing());
+
+        // back to before the snare; fade 0 means the engine is there at once
+        let revert = cmd_processor.parse("revert 3".to_string()).unwrap().unwrap();
+        conductor.apply(revert);
+        conductor.render(&mut frame);
+        conductor.render(&mut frame);
+        assert_eq!(frame[0], 1000);
+        assert_eq!(lines(&cmd_processor).last(), Some(&(3, "start -v kick".to_string())));
+        assert!(cmd_processor.parse("start -v snare".to_string()).is_err());
+
+        // and revisions carry on from where they were
+        cmd_processor.parse("load snare".to_string()).unwrap();
+        assert_eq!(lines(&cmd_processor).last(), Some(&(6, "load snare".to_string())));
+        assert!(matches!(cmd_processor.parse("revert 4".to_string()), Err(CmdErr::NoItem { .. })));
+        assert!(cmd_processor.parse("revert 0".to_string()).is_ok());
+        assert!(cmd_processor.engine_state.history().is_empty());
+    }
+
+    #[test]
+    fn test_revert_keeps_playing() {
+        use std::sync::Arc;
+        sample_rate::set(8000);
+        let ramp = (0..800).map(|i| i * 10).collect();
+        let tracks = HashMap::from([
+            ("ramp".to_string(), AudioFile::new("ramp", "ramp", "wav", 8000, 1, 16, ramp)),
+            ("snare".to_string(), AudioFile::new("snare", "snare", "wav", 8000, 1, 16, vec![2000; 800])),
+        ]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let queue = Arc::new(ResponseQueue::new(4));
+        conductor.respond_to(Arc::clone(&queue));
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("fade 0");
+        run("load ramp");
+        assert_eq!(run("start -v ramp"), 0);
+        run("load snare");
+        assert_eq!(run("start -v snare"), 20 + 2000);
+
+        // back to before the snare: the ramp carries on from where
+        // it was, and the snare (fade 0) is gone at once
+        assert_eq!(run("revert 3"), 30);
+        assert_eq!(queue.pop(), Some(Response::Ok("Session rebuilt, taking over where it was")));
+        assert_eq!(run("history"), 40);
+        assert!(cmd_processor.engine_state.voice("ramp").unwrap().is_playing());
+        assert!(cmd_processor.engine_state.voice("snare").is_none());
+    }
+
+    #[test]
+    fn test_import_stems() {
+        use std::sync::Arc;
+        sample_rate::set(8000);
+        let dir = std::env::temp_dir().join(format!("blast_stems_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stem = |name: &str, level: i16, frames: usize| {
+            let path = dir.join(format!("{name}.wav"));
+            wav::write(path.to_str().unwrap(), 8000, 1, &vec![level; frames]).unwrap();
+        };
+        stem("bass", 1000, 800);
+        stem("drums", 2000, 600);
+
+        let kick = AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![0; 16]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![kick.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("kick".to_string(), kick)]));
+
+        // the lengths differ, and it says how to get past that
+        let line = format!("import-stems {} as band", dir.display());
+        match cmd_processor.parse(line.clone()) {
+            Err(CmdErr::FileIo { err, .. }) => assert!(err.contains("--pad"), "{err}"),
+            _ => panic!("stems of different lengths were imported"),
+        }
+
+        // the Tracks go into room made for them, and the lists
+        // they came in are freed off the audio thread
+        let reaped = Arc::new(ReapQueue::new(16));
+        conductor.reap_with(Arc::clone(&reaped));
+        let mut frame = [0i16; 1];
+        for line in ["fade 0".to_string(), format!("{line} --pad"), "start -g band".to_string()] {
+            conductor.apply(cmd_processor.parse(line).unwrap().unwrap());
+        }
+        assert!(matches!(reaped.pop(), Some(Remains::Tracks(tracks)) if tracks.is_empty()));
+        assert!(matches!(reaped.pop(), Some(Remains::Commands(commands)) if commands.is_empty()));
+        assert!(reaped.pop().is_none());
+
+        // and there's only so much room
+        let full = (0..MAX_TRACKS - 1)
+            .map(|i| AudioFile::new(&format!("t{i}"), &format!("t{i}"), "wav", 8000, 1, 16, vec![0; 2]))
+            .collect();
+        let mut full = CmdProcessor::new(EngineState::new(full, 1));
+        match full.parse(format!("import-stems {} as band --pad", dir.display())) {
+            Err(CmdErr::Formatting { err }) => assert!(err.contains("at most"), "{err}"),
+            _ => panic!("imported past MAX_TRACKS"),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut out = Vec::new();
+        for _ in 0..800 {
+            conductor.render(&mut frame);
+            out.push(frame[0]);
+        }
+        // together, then the bass alone where the drums were padded
+        assert_eq!(out[0], 3000);
+        assert_eq!(out[599], 3000);
+        assert_eq!(out[600], 1000);
+        assert!(cmd_processor.parse("stop -v band.drums".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_seq_swing() {
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // steps on every half unit of 100 samples
+        let hits = |swing: &str| {
+            let seq = format!("seq $voice -t s:100 -p 2 -s 0,0.5,1,1.5 {swing}");
+            let preset = ["fade 0".to_string(), seq];
+            let out = offline::process(&preset, af.clone(), 0.05).unwrap();
+            out.iter().enumerate().filter(|(_, s)| **s != 0).map(|(i, _)| i).collect::<Vec<_>>()
+        };
+        assert_eq!(hits(""), vec![0, 50, 100, 150, 200, 250, 300, 350]);
+        // the off halves land 40% of a half late, every time
+        assert_eq!(hits("-w 40"), vec![0, 70, 100, 170, 200, 270, 300, 370]);
+        // in quarters, the halves are on the beat and stay put
+        assert_eq!(hits("-w 40:4"), hits(""));
+    }
+
+    #[test]
+    fn test_tempo_ramp() {
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // a step every beat, the beat gliding from 100 to 50 samples
+        let preset = [
+            "fade 0".to_string(),
+            "tc glide s:100".to_string(),
+            "seq $voice -t c:glide -p 1 -s 0".to_string(),
+            "tempo ramp -t glide s:50 2b".to_string(),
+            "start -t glide".to_string(),
+            "start -v $voice".to_string(),
+        ];
+        let out = offline::process(&preset, af.clone(), 0.05).unwrap();
+        let hits = out.iter().enumerate().filter(|(_, s)| **s != 0).map(|(i, _)| i).collect::<Vec<_>>();
+        // the beat shortens as it goes, then holds at 50
+        assert_eq!(hits, vec![0, 86, 147, 197, 247, 297, 347, 397]);
+
+        sample_rate::set(8000);
+        let hat = AudioFile::new("hat", "hat", "wav", 8000, 1, 16, vec![0i16; 8]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af, hat], 1));
+        cmd_processor.parse("load click".to_string()).unwrap();
+        cmd_processor.parse("load hat".to_string()).unwrap();
+        cmd_processor.parse("tc slow b:60".to_string()).unwrap();
+        cmd_processor.parse("group drums -v click".to_string()).unwrap();
+        cmd_processor.parse("group synced -t c:slow -v hat".to_string()).unwrap();
+        assert!(cmd_processor.parse("tempo ramp -g drums b:90 4b".to_string()).is_ok());
+        // a Group on a TempoContext can't pull it along
+        assert!(matches!(
+            cmd_processor.parse("tempo ramp -g synced b:90 4b".to_string()),
+            Err(CmdErr::Formatting { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("tempo ramp -g drums b:90 4 bars".to_string()),
+            Err(CmdErr::InvalidArg { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("tempo ramp -t nowhere b:90 1s".to_string()),
+            Err(CmdErr::NoItem { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_tempo_set() {
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // made at one tempo, set to another before it plays
+        let preset = [
+            "fade 0".to_string(),
+            "tc beat s:100".to_string(),
+            "seq $voice -t c:beat -p 1 -s 0".to_string(),
+            "tempo -t beat s:50".to_string(),
+            "start -t beat".to_string(),
+            "start -v $voice".to_string(),
+        ];
+        let out = offline::process(&preset, af.clone(), 0.03).unwrap();
+        let hits = out.iter().enumerate().filter(|(_, s)| **s != 0).map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(hits, vec![0, 50, 100, 150, 200]);
+
+        sample_rate::set(8000);
+        let hat = AudioFile::new("hat", "hat", "wav", 8000, 1, 16, vec![0i16; 8]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af, hat], 1));
+        cmd_processor.parse("load click".to_string()).unwrap();
+        cmd_processor.parse("load hat".to_string()).unwrap();
+        cmd_processor.parse("tc slow b:60".to_string()).unwrap();
+        cmd_processor.parse("group synced -t c:slow -v hat".to_string()).unwrap();
+        assert!(cmd_processor.parse("tempo -v click m:250".to_string()).is_ok());
+        let status = cmd_processor.status("click".to_string()).unwrap();
+        assert!(status.contains("tempo m:250"), "{status}");
+        // a Voice on a Group's tempo is set through the Group
+        assert!(matches!(
+            cmd_processor.parse("tempo -v synced.hat b:90".to_string()),
+            Err(CmdErr::Formatting { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("tempo -t slow x:90".to_string()),
+            Err(CmdErr::InvalidArg { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("tempo -t slow b:0".to_string()),
+            Err(CmdErr::InvalidArg { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_bars() {
+        sample_rate::set(8000);
+        let at = Position::at(7.5, 3);
+        assert_eq!((at.bar, at.beat, at.phase), (2, 1, 0.5));
+        assert_eq!(at.to_string(), "3.2");
+        assert!(Position::at(6.0, 3).is_downbeat());
+
+        // a TempoState counts in its own bars
+        let mut ts = TempoState::new(Some(TempoMode::Context));
+        ts.init(TempoMode::Context, TempoUnit::Samples, 100.0);
+        ts.beats_per_bar = 3;
+        ts.start();
+        for _ in 0..750 {
+            ts.update(1.0);
+        }
+        assert_eq!((ts.bar(), ts.beat(), ts.phase()), (2, 1, 0.5));
+
+        // the clock reads 120 BPM in 4 until its meter is set
+        assert_eq!(clock::position_at(5 * 4000).to_string(), "2.2");
+
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, vec![0i16; 8]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        cmd_processor.parse("tc waltz b:90".to_string()).unwrap();
+        assert!(cmd_processor.parse("meter 3/4 -t waltz".to_string()).is_ok());
+        assert!(cmd_processor.parse("meter 7/8 b:140".to_string()).is_ok());
+        for bad in ["meter 0", "meter 7/6", "meter 3 -t nowhere", "meter 4 b:120 extra"] {
+            assert!(cmd_processor.parse(bad.to_string()).is_err(), "{bad}");
+        }
+        assert!(cmd_processor.status(String::new()).unwrap().starts_with("clock  "));
+    }
+
+    #[test]
+    fn test_midi_clock() {
+        // real-time bytes are picked out of the middle of a note-on
+        let sync = ClockSync::new();
+        let mut reader = ClockReader::default();
+        for (at, byte) in [0x90u8, 0xF8, 60, 0xFA, 100, 0xF8, 0xF8].into_iter().enumerate() {
+            reader.feed(byte, at as u64 * 10, &sync);
+        }
+        assert_eq!((sync.starts(), sync.ticks(), sync.running()), (1, 2, true));
+        // three ticks (one before the start), 25 samples apart on average
+        assert_eq!(sync.beat_len(), Some(24.0 * 25.0));
+        // song position is in sixteenths (six ticks)
+        for byte in [0xFC, 0xF2, 3, 1, 0xFB] {
+            reader.feed(byte, 70, &sync);
+        }
+        assert_eq!((sync.ticks(), sync.running()), ((128 + 3) * 6, true));
+
+        sample_rate::set(8000);
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("click".to_string(), af)]));
+        for line in ["fade 0", "load click", "tc ext b:60", "seq click -t c:ext -p 1 -s 0,0.5", "start -v click"] {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+        }
+
+        // planned without a device behind it; the test is the listener
+        let plan = cmd_processor.plan("midi clock hw:9,0,0 -t ext".to_string()).unwrap();
+        let Command::Midi(MidiArgs { action: MidiAction::Clock { sync, .. } }) = &plan.command else {
+            panic!("not a MIDI clock");
+        };
+        let sync = std::sync::Arc::clone(sync);
+        conductor.apply(plan.command);
+
+        // a tick every 10 samples is a beat of 240
+        // (heard a frame after it comes in)
+        let mut frame = [0i16; 1];
+        let mut hits = Vec::<usize>::new();
+        sync.start();
+        for n in 0..480 {
+            if n % 10 == 0 {
+                sync.tick(n as u64);
+            }
+            conductor.render(&mut frame);
+            if frame[0] != 0 {
+                hits.push(n);
+            }
+        }
+        // (0 is the start -v)
+        assert_eq!(hits, vec![0, 1, 121, 241, 361]);
+
+        // stopped, it holds; a start plays the top again
+        sync.stop();
+        for n in 0..100 {
+            sync.tick(480 + n);
+            conductor.render(&mut frame);
+            assert_eq!(frame[0], 0);
+        }
+        sync.start();
+        sync.tick(600);
+        conductor.render(&mut frame);
+        conductor.render(&mut frame);
+        assert_ne!(frame[0], 0);
+
+        assert!(matches!(
+            cmd_processor.plan("midi clock hw:9,0,0 -t nowhere".to_string()),
+            Err(CmdErr::NoItem { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_seq_levels() {
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        let hits = |levels: &str| {
+            let seq = format!("seq $voice -t s:100 -p 4 -s 0,1,2,3 {levels}");
+            let preset = ["fade 0".to_string(), seq];
+            let out = offline::process(&preset, af.clone(), 0.05).unwrap();
+            out.iter().filter(|s| **s != 0).copied().collect::<Vec<_>>()
+        };
+        // -6dB is about half; steps left off hit at full level
+        assert_eq!(hits("-v 1,0.25,-6dB"), vec![1000, 250, 501, 1000]);
+        assert_eq!(hits("-v _,0"), vec![1000, 1000, 1000]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        cmd_processor.parse("load click".to_string()).unwrap();
+        assert!(matches!(
+            cmd_processor.parse("seq click -v 1,0.5".to_string()),
+            Err(CmdErr::Formatting { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("seq click -s 0,1 -v 1,loud".to_string()),
+            Err(CmdErr::InvalidArg { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_seq_seed() {
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        let steps = (0..16).map(|n| n.to_string()).collect::<Vec<_>>().join(",");
+        let hits = |seed: &str| {
+            let seq = format!("seq $voice -t s:100 -p 16 -s {steps} -c a:50 {seed}");
+            let preset = ["fade 0".to_string(), seq];
+            let out = offline::process(&preset, af.clone(), 0.2).unwrap();
+            out.iter().enumerate().filter(|(_, s)| **s != 0).map(|(i, _)| i).collect::<Vec<_>>()
+        };
+        let seven = hits("--seed 7");
+        assert_eq!(seven, hits("--seed 7"));
+        assert_ne!(seven, hits("--seed 8"));
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        cmd_processor.parse("load click".to_string()).unwrap();
+        assert!(matches!(
+            cmd_processor.parse("seq click -s 0 --seed -1".to_string()),
+            Err(CmdErr::InvalidArg { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_seq_midi() {
+        // format 1: a tempo track, then notes in running status,
+        // a note on at velocity 0 ending the first
+        let mut bytes = b"MThd\0\0\0\x06\0\x01\0\x02\0\x60".to_vec();
+        let tempo = [0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20, 0x00, 0xff, 0x2f, 0x00];
+        let notes = [
+            0x00, 0x99, 36, 100, 0x30, 36, 0, 0x00, 38, 127,
+            0x81, 0x00, 0x89, 38, 0, 0x00, 0xff, 0x2f, 0x00,
+        ];
+        for track in [&tempo[..], &notes[..]] {
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(track);
+        }
+        let clip = smf::decode(&bytes).unwrap();
+        assert_eq!(clip.ppq, 96);
+        assert_eq!(clip.len, 48 + 128);
+        assert_eq!(
+            clip.notes.iter().map(|n| (n.tick, n.key, n.velocity)).collect::<Vec<_>>(),
+            [(0, 36, 100), (48, 38, 127)],
+        );
+        assert_eq!(smf::decode(&smf::encode(&clip).unwrap()).unwrap(), clip);
+        assert!(smf::decode(b"RIFF").is_err());
+
+        // deltas that add up past a u32 of ticks, and a note
+        // with no room after it to end, are turned down
+        let mut track = vec![0x00, 0x90, 36, 100];
+        for _ in 0..17 {
+            track.extend_from_slice(&[0xff, 0xff, 0xff, 0x7f, 36, 100]);
+        }
+        let mut long = b"MThd\0\0\0\x06\0\0\0\x01\0\x60MTrk".to_vec();
+        long.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        long.extend_from_slice(&track);
+        assert!(matches!(smf::decode(&long), Err(DecodeError::InvalidData(_))));
+        let last = smf::Clip { ppq: 96, notes: vec![smf::Note { tick: u32::MAX, key: 36, velocity: 100 }], len: 0 };
+        assert!(matches!(smf::encode(&last), Err(DecodeError::InvalidData(_))));
+
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, vec![1000; 16]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        let dir = std::env::temp_dir();
+        let (out, back) = (dir.join("blast_seq_out.mid"), dir.join("blast_seq_back.mid"));
+        let (out, back) = (out.to_str().unwrap(), back.to_str().unwrap());
+        for line in [
+            "load click".to_string(),
+            "seq click -t s:100 -p 4 -s 0,1.5,3 -v 1,0.5,1".to_string(),
+            format!("seq export click {out} -r 2"),
+            format!("seq import click {out} -t s:100 -n back"),
+            format!("seq export click {back} -n back"),
+        ] {
+            cmd_processor.parse(line.clone()).unwrap_or_else(|e| panic!("{line}: {e}"));
+        }
+
+        // a unit of tempo is a quarter note; levels are velocities
+        let written = smf::read(out).unwrap();
+        assert_eq!(written.len, 8 * smf::PPQ as u32);
+        assert_eq!(
+            written.notes.iter().map(|n| (n.tick, n.velocity)).collect::<Vec<_>>(),
+            [(0, 127), (720, 64), (1440, 127), (1920, 127), (2640, 64), (3360, 127)],
+        );
+        // two cycles came back as one Seq of both
+        assert_eq!(smf::read(back).unwrap(), written);
+
+        // with more than one, the Seq's named
+        assert!(matches!(
+            cmd_processor.parse(format!("seq export click {out}")),
+            Err(CmdErr::Formatting { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse(format!("seq export click {out} -n nothing")),
+            Err(CmdErr::NoItem { .. }),
+        ));
+        std::fs::remove_file(out).unwrap();
+        std::fs::remove_file(back).unwrap();
+        assert!(matches!(
+            cmd_processor.parse(format!("seq import click {out}")),
+            Err(CmdErr::FileIo { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_named_procs() {
+        let mut samples = vec![0i16; 800];
+        samples[0] = 1000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // the start plays at 0; removing the first Seq leaves
+        // the second first in line
+        let preset = [
+            "fade 0",
+            "seq $voice -t s:100 -p 1 -s 0",
+            "seq $voice -n late -t s:100 -p 1 -s 0.5",
+            "proc rm $voice.seq",
+            "proc set $voice.late period 2",
+        ].map(String::from);
+        let out = offline::process(&preset, af.clone(), 0.1).unwrap();
+        let hits: Vec<usize> = out.iter().enumerate().filter(|(_, s)| **s != 0).map(|(i, _)| i).collect();
+        assert_eq!(hits, [0, 50, 250, 450, 650]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        for line in ["load click", "seq click -s 0", "seq click -s 1", "lfo click gain -n wobble"] {
+            cmd_processor.parse(line.to_string()).unwrap();
+        }
+        assert_eq!(
+            cmd_processor.list_procs("ls".to_string()).unwrap(),
+            "click\n  seq  seq  x...\n  seq2  seq  .x..\n  wobble  lfo",
+        );
+        assert!(matches!(
+            cmd_processor.parse("seq click -s 0 -n wobble".to_string()),
+            Err(CmdErr::AlreadyIs { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("proc rm click.nothing".to_string()),
+            Err(CmdErr::NoItem { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("proc set click.wobble period 2".to_string()),
+            Err(CmdErr::Formatting { .. }),
+        ));
+
+        cmd_processor.parse("proc set click.seq2 steps 0,2".to_string()).unwrap();
+        cmd_processor.parse("proc rm click.seq".to_string()).unwrap();
+        assert_eq!(
+            cmd_processor.list_procs("ls click".to_string()).unwrap(),
+            "click\n  seq2  seq  x.x.\n  wobble  lfo",
+        );
+    }
+
+    #[test]
+    fn test_transport_all() {
+        sample_rate::set(8000);
+        let tracks = HashMap::from([
+            ("a".to_string(), AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![100; 800])),
+            ("b".to_string(), AudioFile::new("b", "b", "wav", 8000, 1, 16, vec![1000; 800])),
+        ]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("fade 0");
+        run("load a");
+        run("load b");
+        run("tc glide s:100");
+        assert_eq!(run("start all"), 1100);
+        assert_eq!(run("pause all"), 0);
+        assert_eq!(run("resume -v a -v b"), 1100);
+        assert_eq!(run("stop -v b"), 100);
+        assert_eq!(run("stop all"), 0);
+
+        assert!(matches!(
+            cmd_processor.parse("start -v a -v".to_string()),
+            Err(CmdErr::MissingArg { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_ls() {
+        let tracks = vec![
+            AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![0; 16]),
+            AudioFile::new("b", "b", "wav", 8000, 2, 16, vec![0; 16]),
+            AudioFile::new("c", "c", "wav", 8000, 1, 16, vec![0; 16]),
+        ];
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks, 2));
+        for line in [
+            "tc glide b:120",
+            "load a -t c:glide",
+            "load b",
+            "load c -t m:250",
+            "group pair -v b,c -t b:90",
+            "start -t glide -g pair",
+            "pause -g pair",
+        ] {
+            cmd_processor.parse(line.to_string()).unwrap();
+        }
+
+        let voices = cmd_processor.list("voices".to_string()).unwrap();
+        assert!(voices.starts_with("voices\n  0  a  stopped  tempo c:glide\n"), "{voices}");
+        assert!(voices.contains("  pair.b  playing"), "{voices}");
+        assert_eq!(
+            cmd_processor.list("tc".to_string()).unwrap(),
+            "tc\n  0  glide  playing  b:120",
+        );
+        assert!(cmd_processor.list(String::new()).unwrap().contains("groups\n  0  pair  paused  tempo b:90  2 voices"));
+        assert!(matches!(cmd_processor.list("cues".to_string()), Err(CmdErr::InvalidArg { .. })));
+
+        assert_eq!(
+            cmd_processor.status("glide".to_string()).unwrap(),
+            "glide  tempocontext 0  playing  b:120  4 beats a bar",
+        );
+        assert!(cmd_processor.status("pair".to_string()).unwrap().starts_with("pair  group 0  paused  tempo b:90"));
+        assert!(matches!(cmd_processor.status("nobody".to_string()), Err(CmdErr::NoItem { .. })));
+    }
+
+    #[test]
+    fn test_rename() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![100; 800]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("a".to_string(), af)]));
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        // renaming a Voice frees its Track's name for another
+        run("fade 0");
+        run("load a");
+        run("rename -v a first");
+        run("load a");
+        run("tc clock s:100");
+        run("rename -t clock beat");
+        run("group both -v first,a -t c:beat");
+        run("rename -v both.a second");
+        run("rename -g both pair");
+        assert_eq!(run("start -t beat -g pair"), 200);
+        assert_eq!(run("stop -g pair"), 0);
+
+        let voices = cmd_processor.list("voices".to_string()).unwrap();
+        assert!(voices.contains("pair.first") && voices.contains("pair.second"), "{voices}");
+        assert!(matches!(
+            cmd_processor.parse("rename -v pair.first second".to_string()),
+            Err(CmdErr::AlreadyIs { .. }),
+        ));
+        assert!(matches!(
+            cmd_processor.parse("rename -t clock other".to_string()),
+            Err(CmdErr::NoItem { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_save_open() {
+        use std::sync::Arc;
+        sample_rate::set(8000);
+        let af = AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![100; 800]);
+        let tracks = HashMap::from([("a".to_string(), af)]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        for line in [
+            "tc beat b:120",
+            "load a -t c:beat",
+            "seq a -n fill -p 8 -s 0,3,6 -v 1,0.5,0.25",
+            "start -t beat -v a",
+            "proc set a.fill swing 20",
+            "rename -v a lead",
+        ] {
+            cmd_processor.parse(line.to_string()).unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("blast_set_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        cmd_processor.parse(format!("save {path}")).unwrap();
+        let script = std::fs::read_to_string(&path).unwrap();
+        assert!(!script.contains("start"), "{script}");
+
+        // a fresh processor and engine end up where this one was
+        let mut opened = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let queue = Arc::new(ResponseQueue::new(4));
+        conductor.respond_to(Arc::clone(&queue));
+        conductor.apply(opened.parse(format!("open {path}")).unwrap().unwrap());
+        let _ = std::fs::remove_file(&path);
+
+        // it isn't left in the background, so the engine doesn't say it is
+        assert_eq!(queue.pop(), None);
+
+        assert_eq!(opened.engine_state.history().len(), 5);
+        assert_eq!(
+            opened.list("voices".to_string()).unwrap(),
+            "voices\n  0  lead  stopped  tempo c:beat",
+        );
+        assert_eq!(
+            opened.list_procs("ls lead".to_string()).unwrap(),
+            cmd_processor.list_procs("ls lead".to_string()).unwrap(),
+        );
+        let mut frame = [0i16; 1];
+        conductor.render(&mut frame);
+        assert_eq!(frame[0], 0);
+
+        assert!(matches!(
+            opened.parse("open /nonexistent/set.txt".to_string()),
+            Err(CmdErr::FileIo { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_run_script() {
+        let tracks = vec![AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![0; 16])];
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks, 1));
+
+        let dir = std::env::temp_dir();
+        let inner = dir.join(format!("blast_inner_{}.txt", std::process::id()));
+        let outer = dir.join(format!("blast_outer_{}.txt", std::process::id()));
+        std::fs::write(&inner, "tc beat b:120\n").unwrap();
+        std::fs::write(&outer, format!(
+            "# setup\nload a\n\nload nothing\nrun {}\nseq a -t c:beat -s 0  # after the inner script\n",
+            inner.display(),
+        )).unwrap();
+
+        cmd_processor.parse(format!("run {}", outer.display())).unwrap();
+        let mut errors = Vec::<String>::new();
+        while let Some((at, line)) = cmd_processor.next_scripted() {
+            if let Err(error) = cmd_processor.parse(line) {
+                errors.push(format!("{at}: {error}"));
+            }
+        }
+        let _ = std::fs::remove_file(&inner);
+        let _ = std::fs::remove_file(&outer);
+
+        // the bad line is reported and the rest still run
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("line 4"), "{}", errors[0]);
+        let lines: Vec<String> = cmd_processor.engine_state.history().iter().map(|e| e.line.clone()).collect();
+        assert_eq!(lines, ["load a", "tc beat b:120", "seq a -t c:beat -s 0"]);
+
+        assert!(matches!(
+            cmd_processor.parse("run /nonexistent/script.txt".to_string()),
+            Err(CmdErr::FileIo { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        sample_rate::set(8000);
+        let ramp = (0..800).map(|i| i * 10).collect();
+        let tracks = HashMap::from([
+            ("ramp".to_string(), AudioFile::new("ramp", "ramp", "wav", 8000, 1, 16, ramp)),
+            ("snare".to_string(), AudioFile::new("snare", "snare", "wav", 8000, 1, 16, vec![2000; 800])),
+        ]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.clone().into_values().collect(), 1));
+        let mut conductor = Conductor::prepare(1, tracks);
+        let mut frame = [0i16; 1];
+        // None for a line that's refused
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).ok()? {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            Some(frame[0])
+        };
+
+        run("fade 0");
+        run("load ramp");
+        assert_eq!(run("start -v ramp"), Some(0));
+        run("load snare");
+        assert_eq!(run("start -v snare"), Some(20 + 2000));
+
+        // the load is taken back by its inverse (and the start
+        // after it goes with it); the ramp isn't touched
+        assert_eq!(run("undo"), Some(30));
+        assert_eq!(run("redo"), Some(40));
+        assert_eq!(run("mute -v ramp"), Some(0));
+        assert_eq!(run("undo"), Some(60));
+
+        // a gain has no inverse, so the undo rebuilds, and the
+        // ramp carries on from where it was
+        assert_eq!(run("gain -v ramp 0.5"), Some(35));
+        assert_eq!(run("undo"), Some(80));
+        assert_eq!(run("start -v snare"), Some(90 + 2000));
+        assert_eq!(run("redo"), None);
+
+        // only lines that change the session count
+        assert_eq!(run("undo 4"), None);
+        let lines: Vec<String> = cmd_processor.engine_state.history().iter().map(|e| e.line.clone()).collect();
+        assert_eq!(lines, ["fade 0", "load ramp", "start -v ramp", "load snare", "start -v snare"]);
+        let undo: Vec<Option<&str>> = cmd_processor.engine_state.history().iter().map(|e| e.undo.as_deref()).collect();
+        assert_eq!(undo, [None, Some("unload ramp"), None, Some("unload snare"), None]);
+    }
+
+    #[test]
+    fn test_alias() {
+        let tracks = vec![AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![0; 16])];
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks, 1));
+        let run = |cmd_processor: &mut CmdProcessor, line: &str| {
+            let mut result = cmd_processor.parse(line.to_string()).map(|_| ());
+            while let Some((at, line)) = cmd_processor.next_scripted() {
+                if let Err(error) = cmd_processor.parse(line) {
+                    result = Err(CmdErr::Formatting { err: format!("{at}: {error}") });
+                }
+            }
+            result
+        };
+
+        run(&mut cmd_processor, "alias setup = tc $1 b:120; load a -t c:$1").unwrap();
+        run(&mut cmd_processor, "alias pattern = setup $1; seq a -t c:$1 -s $2").unwrap();
+        run(&mut cmd_processor, "alias go = start $@").unwrap();
+        run(&mut cmd_processor, "pattern beat 0,2").unwrap();
+        run(&mut cmd_processor, "go -t beat -v a").unwrap();
+        let lines: Vec<String> = cmd_processor.engine_state.history().iter().map(|e| e.line.clone()).collect();
+        assert_eq!(lines, ["tc beat b:120", "load a -t c:beat", "seq a -t c:beat -s 0,2", "start -t beat -v a"]);
+
+        // aliases can't shadow commands or run themselves
+        assert!(matches!(run(&mut cmd_processor, "alias load = start all"), Err(CmdErr::AlreadyIs { .. })));
+        assert!(matches!(
+            run(&mut cmd_processor, "alias setup = pattern x"),
+            Err(CmdErr::Formatting { .. }),
+        ));
+        assert!(matches!(run(&mut cmd_processor, "setup"), Err(CmdErr::MissingArg { .. })));
+        run(&mut cmd_processor, "alias rm pattern").unwrap();
+        assert!(matches!(run(&mut cmd_processor, "pattern"), Err(CmdErr::NoCmd { .. })));
+
+        let config = Config::parse("fade_ms = 2\n[aliases]\ndrop = stop -g $1; start -g $1\n").unwrap();
+        assert_eq!(config.fade_ms, Some(2.0));
+        assert_eq!(config.aliases, [("drop".to_string(), "stop -g $1; start -g $1".to_string())]);
+    }
+
+    #[test]
+    fn test_complete() {
+        let tracks = ["kick", "snare"].map(|name| AudioFile::new(name, name, "wav", 8000, 1, 16, vec![0; 16]));
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks.to_vec(), 1));
+        for line in ["load kick", "load snare", "tc beat b:120", "group drums -v snare", "alias stab = trig $1"] {
+            cmd_processor.parse(line.to_string()).unwrap();
+        }
+        let complete = |line: &str| cmd_processor.complete(line);
+
+        assert_eq!(complete("statu"), (0, vec!["status".to_string()]));
+        assert_eq!(complete("stat"), (0, ["stats", "status"].map(String::from).to_vec()));
+        assert_eq!(complete("st"), (0, ["stab", "start", "stats", "status", "stop", "stretch"].map(String::from).to_vec()));
+        assert_eq!(complete("load k"), (5, vec!["kick".to_string()]));
+        assert_eq!(complete("start -v "), (9, vec!["drums.snare".to_string(), "kick".to_string()]));
+        assert!(complete("start --").1.contains(&"--tempocontext".to_string()));
+        assert_eq!(complete("seq kick -t c:"), (12, vec!["c:beat".to_string()]));
+        assert_eq!(complete("group more -v kick,d"), (14, vec!["kick,drums.snare".to_string()]));
+        assert_eq!(complete("nothing k"), (8, vec![]));
+    }
+
+    #[test]
+    fn test_tui_frame() {
+        let ui = Ui::default();
+        *ui.buffer.lock().unwrap() = "load kick".to_string();
+        *ui.cursor.lock().unwrap() = 4;
+        *ui.status.lock().unwrap() = "rev 3".to_string();
+        let lines: Vec<tui::Line> = (1..=5).map(|n| tui::Line::new(tui::Kind::Info, format!("line {n}"))).collect();
+
+        // 3 rows of log over the status bar and input line,
+        // the last lines sitting on the status bar
+        let frame = tui::frame(&lines, &ui, ">", (12, 5));
+        assert!(frame.contains("\x1b[1;1Hline 3\x1b[K") && frame.contains("\x1b[3;1Hline 5\x1b[K"), "{frame:?}");
+        assert!(frame.contains("\x1b[4;1H\x1b[7mrev 3       \x1b[0m"), "{frame:?}");
+        assert!(frame.contains("\x1b[5;1H> load kick\x1b[K") && frame.ends_with("\x1b[5;7H"), "{frame:?}");
+
+        // scrolled back, shown in the status bar; short logs start at the bottom
+        ui.scroll.store(2, std::sync::atomic::Ordering::Relaxed);
+        let frame = tui::frame(&lines, &ui, ">", (40, 5));
+        assert!(frame.contains("\x1b[1;1Hline 1\x1b[K") && frame.contains("[2 lines back]"), "{frame:?}");
+        let frame = tui::frame(&lines[..1], &ui, ">", (40, 5));
+        assert!(frame.contains("\x1b[1;1H\x1b[K") && frame.contains("\x1b[3;1Hline 1\x1b[K"), "{frame:?}");
+    }
+
+    #[test]
+    fn test_playhead() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![1000; 16000]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("a".to_string(), af)]));
+        for line in ["fade 0", "load a", "start -v a"] {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+        }
+        let mut period = [0i16; 4000];
+        conductor.render(&mut period);
+
+        // the Voice a line names is selected, and stays so
+        let voices = cmd_processor.engine_state.meters();
+        let selected = tui::select(&voices, "gain a 0.5", None);
+        assert_eq!(selected.as_ref().map(|(name, _)| name.as_str()), Some("a"));
+        let selected = tui::select(&voices, "ls", selected);
+        let (name, level) = selected.unwrap();
+        assert!(tui::select(&voices, "a", None).is_none());
+        assert!(tui::select(&[], "ls", Some((name.clone(), level.clone()))).is_none());
+
+        // half a second into two
+        let at = Position::at(9.5, 4);
+        assert_eq!(tui::prompt(at, Some((&name, &level)), false), "3.2 a 0.5/2.0s >");
+        assert_eq!(tui::prompt(at, None, false), "3.2 >");
+        assert_eq!(tui::prompt(at, None, true), "3.2 [keys]");
+
+        // the cursor sits after the prompt, however long
+        let ui = Ui::default();
+        *ui.cursor.lock().unwrap() = 2;
+        let frame = tui::frame(&[], &ui, "3.2 >", (40, 5));
+        assert!(frame.ends_with("\x1b[5;9H"), "{frame:?}");
+    }
+
+    #[test]
+    fn test_stats() {
+        use std::{sync::Arc, time::Duration};
+        let stats = Stats::new();
+        assert_eq!(stats.snapshot().brief(), None);
+
+        // periods of 480 frames at 48 kHz last 10ms
+        stats.rendered(Duration::from_millis(1), 480, 48000);
+        stats.rendered(Duration::from_millis(3), 480, 48000);
+        stats.underrun();
+        stats.recovered();
+        let snapshot = stats.snapshot();
+        assert_eq!((snapshot.periods, snapshot.underruns, snapshot.recoveries), (2, 1, 1));
+        assert_eq!((snapshot.mean, snapshot.worst), (Duration::from_millis(2), Duration::from_millis(3)));
+        assert_eq!(snapshot.brief().unwrap(), "dsp 20% (worst 30%)  xruns 1");
+
+        // the status bar carries them
+        let ui = Ui::default();
+        ui.stats.rendered(Duration::from_millis(5), 480, 48000);
+        let frame = tui::frame(&[], &ui, ">", (60, 5));
+        assert!(frame.contains("[dsp 50% (worst 50%)  xruns 0]"), "{frame:?}");
+
+        // the command shares them, and starts them over
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![], 1));
+        cmd_processor.stats = Arc::clone(&ui.stats);
+        assert!(matches!(cmd_processor.parse("stats reset".to_string()), Ok(None)));
+        assert_eq!(ui.stats.snapshot().periods, 0);
+        assert!(cmd_processor.parse("stats now".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_feedback() {
+        // errors red, warnings yellow, confirmations green
+        let ui = Ui::default();
+        let lines = [
+            tui::Line::new(tui::Kind::Err, "Err: no"),
+            tui::Line::new(tui::Kind::Warn, "Warn: hm"),
+            tui::Line::new(tui::Kind::Ok, "ok (rev 2)"),
+        ];
+        let frame = tui::frame(&lines, &ui, ">", (40, 5));
+        assert!(frame.contains("\x1b[1;1H\x1b[31mErr: no\x1b[0m\x1b[K"), "{frame:?}");
+        assert!(frame.contains("\x1b[2;1H\x1b[33mWarn: hm\x1b[0m\x1b[K"), "{frame:?}");
+        assert!(frame.contains("\x1b[3;1H\x1b[32mok (rev 2)\x1b[0m\x1b[K"), "{frame:?}");
+
+        // the argument an error is about, found in its line
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![], 1));
+        let line = "start -v kik";
+        let Err(error) = cmd_processor.parse(line.to_string()) else { panic!() };
+        assert_eq!(error.blame(), Some("kik"));
+        assert_eq!(tui::span(line, "kik"), Some((9, 3)));
+        let Err(error) = cmd_processor.parse("strat -v kick".to_string()) else { panic!() };
+        assert_eq!(error.blame(), Some("strat"));
+
+        // on its own rather than inside another word, counted in chars
+        assert_eq!(tui::span("gain kick_2 k", "k"), Some((12, 1)));
+        assert_eq!(tui::span("load é kick", "kick"), Some((7, 4)));
+        assert_eq!(tui::span("gain kicks 1", "kick"), Some((5, 4)));
+        assert_eq!(tui::span("gain kick 1", "snare"), None);
+    }
+
+    #[test]
+    fn test_meters() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![16384; 800]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("a".to_string(), af)]));
+        let master = conductor.master_level();
+        for line in ["fade 0", "load a", "gain master 0.5", "start -v a"] {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+        }
+        let mut period = [0i16; 64];
+        conductor.render(&mut period);
+
+        // a Voice is metered before the master gain
+        let meters = cmd_processor.engine_state.meters();
+        assert_eq!(meters.len(), 1);
+        let (name, voice) = &meters[0];
+        assert_eq!(name, "a");
+        assert!((voice.peak() - 0.5).abs() < 0.01 && (voice.rms() - 0.5).abs() < 0.01);
+        assert!((master.peak() - 0.25).abs() < 0.01);
+        let line = tui::meter(name, voice, 41);
+        assert!(line.starts_with("a            #################|..  ") && line.ends_with("  -6.0"), "{line}");
+
+        let stop = cmd_processor.parse("stop -v a".to_string()).unwrap().unwrap();
+        conductor.apply(stop);
+        conductor.render(&mut period);
+        assert_eq!((voice.peak(), master.rms()), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_wave() {
+        // a second at 8kHz: silent, then a ramp up to full scale
+        let mut samples = vec![0i16; 4000];
+        samples.extend((0..4000).map(|i| (i * 8) as i16));
+        let af = AudioFile::new("a", "a", "wav", 8000, 1, 16, samples);
+        let envelope = Envelope::of(&af);
+        assert!((envelope.seconds() - 1.0).abs() < 1e-6);
+        let columns = envelope.columns(4);
+        assert_eq!(columns[0], 0.0);
+        assert!(columns[1] == 0.0 && columns[2] > 0.45 && columns[2] < 0.55 && columns[3] > 0.95, "{columns:?}");
+
+        // eighth blocks, the loudest at the top; the playhead on the ruler
+        let drawn = envelope.draw(8, 1, Some(0.5));
+        assert_eq!(drawn, "    ▂▄▆█\n    ^\n0s  1.0s");
+        let drawn = envelope.draw(8, 2, None);
+        assert_eq!(drawn, "      ▄█\n    ▄███\n0s  1.0s");
+
+        // as a command: the Track must exist, rows must be sensible
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        assert!(cmd_processor.parse("wave a -r 2".to_string()).unwrap().is_none());
+        assert!(matches!(cmd_processor.parse("wave b".to_string()), Err(CmdErr::NoItem { .. })));
+        assert!(matches!(cmd_processor.parse("wave a -r 0".to_string()), Err(CmdErr::InvalidArg { .. })));
+        assert!(matches!(cmd_processor.parse("wave".to_string()), Err(CmdErr::MissingArg { .. })));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let af = AudioFile::new("a", "a", "wav", 8000, 1, 16, vec![0; 800]);
+        let b = AudioFile::new("b", "b", "wav", 8000, 1, 16, vec![0; 800]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af, b], 1));
+        for line in ["load a", "load b"] {
+            cmd_processor.parse(line.to_string()).unwrap();
+        }
+
+        // built-ins follow the Voice's state, so they toggle
+        let mut bindings = Bindings::default();
+        let press = |cmd_processor: &mut CmdProcessor, action: &Action| {
+            let line = action.line(Some("a"), &cmd_processor.engine_state).unwrap().unwrap();
+            cmd_processor.parse(line.clone()).unwrap();
+            line
+        };
+        let space = bindings.get(Key::Space).unwrap().clone();
+        assert_eq!(press(&mut cmd_processor, &space), "start -v a");
+        assert_eq!(press(&mut cmd_processor, &space), "stop -v a");
+        let mute = bindings.get(Key::Char('m')).unwrap().clone();
+        assert_eq!(press(&mut cmd_processor, &mute), "mute -v a on");
+        assert_eq!(press(&mut cmd_processor, &mute), "mute -v a off");
+
+        // lines fill in the selected Voice, and need one if they use it
+        bindings.bind("t", "trig $v -b").unwrap();
+        bindings.bind("space", "stop all").unwrap();
+        let state = &cmd_processor.engine_state;
+        let trig = bindings.get(Key::Char('t')).unwrap();
+        assert_eq!(trig.line(Some("a"), state).unwrap().as_deref(), Some("trig a -b"));
+        assert!(trig.line(None, state).is_err());
+        assert!(bindings.get(Key::Space).unwrap().line(None, state).is_ok());
+        assert!(bindings.get(Key::Up).unwrap().line(None, state).unwrap().is_none());
+        assert!(bindings.bind("ctrl", "mute").is_err() && bindings.bind("x", "select up").is_err());
+
+        // selection wraps around
+        let names = ["a", "b", "c"];
+        assert_eq!(bindings::step(&names, Some("c"), 1), Some("a"));
+        assert_eq!(bindings::step(&names, Some("a"), -1), Some("c"));
+        assert_eq!(bindings::step(&names, None, -1), Some("c"));
+        assert_eq!(bindings::step(&[], None, 1), None);
+
+        let config = Config::parse("[keys]\nspace = startstop\nt = trig $v\n[aliases]\nx = ls\n").unwrap();
+        assert_eq!(config.keys.len(), 2);
+        assert_eq!(config.aliases.len(), 1);
+        assert!(Config::parse("[nope]\n").is_err());
+    }
+
+    #[test]
+    fn test_devices() {
+        let found = [
+            Device { name: "hw:CARD=PCH,DEV=0".to_string(), desc: Some("HDA Intel PCH, ALC3246 Analog".to_string()) },
+            Device { name: "default".to_string(), desc: None },
+        ];
+        assert_eq!(
+            devices::listing(&found, "default"),
+            "  hw:CARD=PCH,DEV=0  HDA Intel PCH, ALC3246 Analog\n* default",
+        );
+        assert_eq!(devices::listing(&[], "hw:0,0"), "No playback devices found");
+
+        // asking ALSA is safe on a machine without sound cards
+        let _ = devices::playback();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_realtime() {
+        use crate::audio_processing::realtime;
+
+        // best-effort: either the thread is on SCHED_FIFO at what it
+        // says it got, or it's left as it was and told why
+        let promoted = std::thread::spawn(|| {
+            let got = realtime::promote_current_thread(99);
+            let (mut policy, mut param) = (0, unsafe { std::mem::zeroed::<libc::sched_param>() });
+            unsafe { libc::pthread_getschedparam(libc::pthread_self(), &mut policy, &mut param) };
+            (got, policy, param.sched_priority)
+        });
+        match promoted.join().unwrap() {
+            (Ok(prio), policy, at) => {
+                assert!((1..=99).contains(&prio));
+                assert_eq!((policy, at), (libc::SCHED_FIFO, prio));
+            }
+            (Err(err), policy, _) => {
+                assert!(err.contains("RLIMIT_RTPRIO") && err.ends_with("Using normal scheduling"), "{err}");
+                assert_eq!(policy, libc::SCHED_OTHER);
+            }
+        }
+
+        // and locking says how much it locked, or what stopped it
+        match realtime::lock_memory() {
+            Ok(locked) => {
+                assert!(locked.starts_with("locked current"));
+                unsafe { libc::munlockall() };
+            }
+            Err(err) => assert!(err.contains("RLIMIT_MEMLOCK"), "{err}"),
+        }
+
+        let cpus = realtime::available_cpus();
+        assert!(!cpus.is_empty());
+        assert!(realtime::pin_current_thread(&[]).is_err());
+    }
+
+    #[test]
+    fn test_backends() {
+        // ALSA first where it's built in
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(backend::default_backend(), Some("alsa"));
+            assert_eq!(backend::BACKENDS[1], "pulse");
+            assert_eq!(backend::BACKENDS[2], "jack");
+        }
+        assert_eq!(backend::default_backend(), backend::BACKENDS.first().copied());
+
+        let Err(err) = backend::open("oss", None, 2, 44100, Latency::DEFAULT) else { panic!() };
+        assert!(err.starts_with("Unknown backend 'oss'") || err.starts_with("No audio backend"), "{err}");
+        let config = Config::parse("backend = cpal\n").unwrap();
+        assert_eq!(config.backend.as_deref(), Some("cpal"));
+        let config = Config::parse("backend = pulse\n").unwrap();
+        assert_eq!(config.backend.as_deref(), Some("pulse"));
+
+        // pulse is the fallback when ALSA's default won't open, and
+        // says why it can't be either (here, or any machine without
+        // a sound server)
+        #[cfg(target_os = "linux")]
+        {
+            let Err(err) = backend::open("pulse", Some("bad\0sink"), 2, 44100, Latency::DEFAULT) else { panic!() };
+            assert_eq!(err, "Invalid sink name 'bad\0sink'");
+            if let Err(err) = backend::open("pulse", None, 2, 44100, Latency::DEFAULT) {
+                assert!(err.starts_with("Couldn't load libpulse-simple") || err.starts_with("Couldn't connect to the sound server"), "{err}");
+            }
+            if let Err(err) = backend::open_default(2, 44100, Latency::DEFAULT) {
+                let (alsa, pulse) = (err.find("alsa: ").unwrap(), err.find("pulse: ").unwrap());
+                assert!(alsa < pulse, "{err}");
+            }
+        }
+        assert!(Config::parse("backend = null\n").is_ok());
+        let config = Config::parse("backend = jack\n").unwrap();
+        assert_eq!(config.backend.as_deref(), Some("jack"));
+        assert!(Config::parse("backend = oss\n").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("blast_render_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+        wav::write(path, 8000, 1, &[1000i16; 200]).unwrap();
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let name = af.file_name.clone();
+        let script = dir.join(format!("blast_render_{}.txt", std::process::id()));
+        std::fs::write(&script, format!("fade 0\nload {name}\nstart -v {name}\n")).unwrap();
+        let opts = RunOptions { script: Some(script.to_str().unwrap().to_string()), ..RunOptions::default() };
+
+        // as long as asked, whatever's playing
+        let out = offline::render(HashMap::from([(name, af)]), 8000, 1, &opts, 0.1).unwrap();
+        std::fs::remove_file(&script).unwrap();
+        assert_eq!(out.len(), 800);
+        assert!(out[..199].iter().all(|s| *s == 1000));
+        assert!(out[200..].iter().all(|s| *s == 0));
+
+        let opts = RunOptions { script: Some("/nonexistent/blast.txt".to_string()), ..RunOptions::default() };
+        assert!(offline::render(HashMap::new(), 8000, 1, &opts, 0.1).is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        // only what fits is pushed, and it wraps around
+        let ring = Ring::new(4);
+        assert_eq!(ring.push(&[1, 2, 3]), 3);
+        assert_eq!(ring.push(&[4, 5]), 1);
+        let mut out = [0i16; 3];
+        assert_eq!(ring.pop_with(&mut out, |s| s), 3);
+        assert_eq!(out, [1, 2, 3]);
+        assert_eq!(ring.push(&[5, 6]), 2);
+        assert_eq!(ring.pop_with(&mut out, |s| s * 10), 3);
+        assert_eq!(out, [40, 50, 60]);
+        assert_eq!(ring.pop_with(&mut out, |s| s), 0);
+
+        sample_rate::set(8000);
+        let path = std::env::temp_dir().join(format!("blast_rec_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(Vec::new(), 2));
+        assert!(cmd_processor.prepare("rec stop".to_string()).is_err());
+
+        let plan = cmd_processor.prepare(format!("rec start {path}")).unwrap().unwrap();
+        let Command::Audio(AudioArgs { action: AudioAction::Record(Some(tap)) }) = &plan.command else { panic!() };
+        assert!(cmd_processor.prepare("rec start other.wav".to_string()).is_err());
+
+        // as the audio loop would, a period at a time
+        for period in 0..4 {
+            tap.push(&[period; 64]);
+        }
+        let plan = cmd_processor.prepare("rec stop".to_string()).unwrap().unwrap();
+        assert!(matches!(plan.command, Command::Audio(AudioArgs { action: AudioAction::Record(None) })));
+
+        let af = wav::parse(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!((af.sample_rate, af.num_channels), (8000, 2));
+        assert_eq!(af.samples.len(), 256);
+        assert!(af.samples[..64].iter().all(|s| *s == 0) && af.samples[192..].iter().all(|s| *s == 3));
+    }
+
+    #[test]
+    fn test_cue_sheet() {
+        sample_rate::set(8000);
+        let dir = std::env::temp_dir();
+
+        // named after the recording, timed from its first sample
+        let mut cues = CueSheet::new(dir.join("blast_cue_take.wav").to_str().unwrap());
+        assert!(cues.path().ends_with("blast_cue_take.cue"));
+        cues.mark(500, "early");
+        cues.mark(1000 + 8000 * 61 + 800, "start -v kick");
+        cues.write(1000).unwrap();
+        let sheet = std::fs::read_to_string(cues.path()).unwrap();
+        std::fs::remove_file(cues.path()).unwrap();
+        assert_eq!(sheet, [
+            "TITLE \"blast session\"",
+            "FILE \"blast_cue_take.wav\" WAVE",
+            "  TRACK 01 AUDIO",
+            "    TITLE \"rec start\"",
+            "    INDEX 01 00:00:00",
+            "  TRACK 02 AUDIO",
+            "    TITLE \"early\"",
+            "    INDEX 01 00:00:00",
+            "  TRACK 03 AUDIO",
+            "    TITLE \"start -v kick\"",
+            "    INDEX 01 01:01:07",
+            "",
+        ].join("\n"));
+        // never over the recording
+        assert!(CueSheet::new("take.cue").path() == "take.cue.cue");
+
+        // rec start keeps one of transport, locates, pattern changes,
+        // session crossfades and marks, and rec stop writes it
+        let path = dir.join(format!("blast_cue_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+        let af = AudioFile::new("tone", "tone", "wav", 8000, 1, 16, vec![1000; 800]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af], 1));
+        assert!(cmd_processor.prepare("cue chorus".to_string()).is_err());
+        cmd_processor.parse("load tone".to_string()).unwrap();
+
+        let plan = cmd_processor.prepare(format!("rec start {path}")).unwrap().unwrap();
+        let Command::Audio(AudioArgs { action: AudioAction::Record(Some(tap)) }) = &plan.command else { panic!() };
+        tap.push(&[0; 64]);
+        let script = dir.join(format!("blast_cue_{}.txt", std::process::id()));
+        std::fs::write(&script, "load tone\n").unwrap();
+        cmd_processor.parse(format!("session load {}", script.display())).unwrap();
+        std::fs::remove_file(&script).unwrap();
+        for line in [
+            "start -v tone", "gain -v tone 0.5", "seek tone 100", "seq tone -t b:120 -p 4 -s 0,2",
+            "proc set tone.seq steps 0,1", "mute -v tone", "session crossfade next 10ms",
+        ] {
+            cmd_processor.parse(line.to_string()).unwrap();
+        }
+        assert!(cmd_processor.prepare("cue chorus".to_string()).unwrap().is_none());
+        cmd_processor.prepare("rec stop".to_string()).unwrap().unwrap();
+
+        let cue_path = path.replace(".wav", ".cue");
+        let sheet = std::fs::read_to_string(&cue_path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(&cue_path).unwrap();
+        assert!(sheet.contains(&format!("FILE \"blast_cue_{}.wav\" WAVE", std::process::id())));
+        let titles: Vec<&str> = sheet.lines().filter_map(|line| line.strip_prefix("    TITLE ")).collect();
+        assert_eq!(titles, [
+            "\"rec start\"",
+            "\"start -v tone\"",
+            "\"seek tone 100\"",
+            "\"seq tone -t b:120 -p 4 -s 0,2\"",
+            "\"proc set tone.seq steps 0,1\"",
+            "\"session crossfade next 10ms\"",
+            "\"chorus\"",
+        ]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_jack_ahead() {
+        use std::sync::atomic::Ordering;
+        use crate::audio_processing::backend::jack::Ahead;
+
+        // nothing pushed yet is just starting: silence, no underrun
+        let ahead = Ahead::new(8 * 2);
+        let mut out = vec![vec![1.0f32; 4]; 2];
+        let play = |ahead: &Ahead, out: &mut Vec<Vec<f32>>, frames: usize| {
+            ahead.pop_into(frames, 2, |at, chunk| {
+                for (ch, port) in out.iter_mut().enumerate() {
+                    simd::deinterleave(&mut port[at..], chunk, 2, ch);
+                }
+            });
+        };
+        play(&ahead, &mut out, 4);
+        assert!(out.iter().flatten().all(|s| *s == 0.0));
+        assert_eq!(ahead.underruns.load(Ordering::Relaxed), 0);
+
+        // a period ahead comes out deinterleaved, a port per channel
+        let period: Vec<i16> = (0..8).map(|i| i * 4096).collect();
+        assert_eq!(ahead.ring.push(&period), 8);
+        play(&ahead, &mut out, 4);
+        assert_eq!(out[0], [0.0, 0.25, 0.5, 0.75]);
+        assert_eq!(out[1], [0.125, 0.375, 0.625, 0.875]);
+        assert_eq!(ahead.underruns.load(Ordering::Relaxed), 0);
+
+        // running dry partway plays silence for the rest, and counts
+        ahead.ring.push(&period[..4]);
+        play(&ahead, &mut out, 4);
+        assert_eq!(out[0], [0.0, 0.25, 0.0, 0.0]);
+        assert_eq!(out[1], [0.125, 0.375, 0.0, 0.0]);
+        assert_eq!(ahead.underruns.load(Ordering::Relaxed), 1);
+
+        // more than a chunk at once, in order
+        let ahead = Ahead::new(4096);
+        let long: Vec<i16> = (0..2048).map(|i| i as i16).collect();
+        ahead.ring.push(&long);
+        let mut out = vec![vec![0.0f32; 1024]; 2];
+        play(&ahead, &mut out, 1024);
+        assert!(out[0].iter().enumerate().all(|(i, s)| *s == (2 * i) as f32 / 32768.0));
+        assert!(out[1].iter().enumerate().all(|(i, s)| *s == (2 * i + 1) as f32 / 32768.0));
+        assert_eq!(ahead.underruns.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_null_backend() {
+        sample_rate::set(8000);
+        let af = AudioFile::new("tone", "tone", "wav", 8000, 1, 16, vec![1000; 200]);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("tone".to_string(), af)]));
+        for line in ["fade 0", "load tone", "start -v tone"] {
+            conductor.apply(cmd_processor.parse(line.to_string()).unwrap().unwrap());
+        }
+
+        // unpaced, as the audio loop drives it
+        let mut null = Null::open(1, 8000, Latency::new(64, None).unwrap()).speed(0.0);
+        let captured = null.capture();
+        for _ in 0..5 {
+            assert_eq!(null.cycle(&mut |out| conductor.render(out)), Cycle::Wrote(64));
+        }
+        let captured = captured.lock().unwrap();
+        assert_eq!(captured.len(), 320);
+        assert!(captured[..199].iter().all(|s| *s == 1000) && captured[200..].iter().all(|s| *s == 0));
+
+        // paced, it only has room for four periods at once...
+        let mut null = Null::open(1, 8000, Latency::new(64, None).unwrap()).speed(0.1);
+        for _ in 0..4 {
+            assert_eq!(null.cycle(&mut |_| ()), Cycle::Wrote(64));
+        }
+        assert_eq!(null.cycle(&mut |_| ()), Cycle::Waited);
+
+        // ...and runs dry if nothing comes
+        let mut null = Null::open(1, 8000, Latency { period: 8, buffer: 32 });
+        assert_eq!(null.cycle(&mut |_| ()), Cycle::Wrote(8));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(null.cycle(&mut |_| ()), Cycle::Underrun);
+        assert_eq!(null.cycle(&mut |_| ()), Cycle::Wrote(8));
+
+        assert_eq!(backend::open("null", None, 2, 8000, Latency::DEFAULT).unwrap().device(), "null");
+    }
+
+    #[test]
+    fn test_device_recovery() {
+        sample_rate::set(8000);
+        let ramp: Vec<i16> = (0..256).collect();
+        let af = AudioFile::new("ramp", "ramp", "wav", 8000, 1, 16, ramp);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("ramp".to_string(), af)]));
+        for line in ["fade 0", "load ramp", "start -v ramp"] {
+            conductor.apply(cmd_processor.parse(line.to_string()).unwrap().unwrap());
+        }
+
+        let latency = Latency::new(64, None).unwrap();
+        let mut null = Null::open(1, 8000, latency).speed(0.0);
+        let captured = null.capture();
+        let plug = null.plug();
+        assert_eq!(null.cycle(&mut |out| conductor.render(out)), Cycle::Wrote(64));
+
+        // unplugged: nothing's rendered, and reopening fails...
+        plug.store(false, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(null.cycle(&mut |out| conductor.render(out)), Cycle::Lost);
+        let start = std::time::Instant::now();
+        let mut recovery = Recovery::new(start);
+        assert!(!recovery.retry(&mut null, latency, start));
+        assert!(!recovery.retry(&mut null, latency, start + RETRY_FIRST));
+        // ...backing off before the next try...
+        plug.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert!(!recovery.retry(&mut null, latency, start + RETRY_FIRST * 2));
+        assert!(recovery.retry(&mut null, latency, start + RETRY_FIRST * 3));
+
+        // ...and once it's back the Voice carries on where it was
+        assert_eq!(null.cycle(&mut |out| conductor.render(out)), Cycle::Wrote(64));
+        let captured = captured.lock().unwrap();
+        assert_eq!(*captured, (0..128).collect::<Vec<i16>>());
+    }
+
+    #[test]
+    fn test_audio_restart() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        sample_rate::set(8000);
+        let ramp: Vec<i16> = (0..8000).collect();
+        let af = AudioFile::new("ramp", "ramp", "wav", 8000, 1, 16, ramp);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let tracks = HashMap::from([("ramp".to_string(), af)]);
+        let queue = CmdQueue::new(8);
+        for line in ["fade 0", "load ramp", "start -v ramp"] {
+            queue.try_push(cmd_processor.parse(line.to_string()).unwrap().unwrap()).unwrap();
+        }
+
+        // the audio loop, a little faster than real time (on a
+        // thread of its own, with the Conductor made there)
+        let mut null = Null::open(1, 8000, Latency::new(64, None).unwrap()).speed(4.0);
+        let captured = null.capture();
+        let (stop, stats) = (AtomicBool::new(false), Stats::new());
+        let wait_for = |len: usize| {
+            while captured.lock().unwrap().len() < len {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        };
+        let kept = std::thread::scope(|scope| {
+            let audio = scope.spawn(|| {
+                let mut conductor = Conductor::prepare(1, tracks);
+                tui::tee(|| play(&mut conductor, &mut null, &queue, false, &stop, &stats)).1
+            });
+            wait_for(640);
+            let restart = cmd_processor.parse("audio restart".to_string()).unwrap().unwrap();
+            assert!(matches!(restart, Command::Audio(AudioArgs { action: AudioAction::Restart })));
+            queue.try_push(restart).unwrap();
+            let restarted = captured.lock().unwrap().len();
+            wait_for(restarted + 640);
+            stop.store(true, Ordering::Relaxed);
+            audio.join().unwrap()
+        });
+
+        // the device was reopened, and the Voice carried on where it
+        // was rather than starting over
+        assert!(kept.iter().any(|line| line.text.contains("Audio restarted")));
+        let captured = captured.lock().unwrap();
+        assert!(captured.len() < 8000);
+        assert!(captured.iter().enumerate().all(|(i, s)| *s == i as i16));
+        assert!(cmd_processor.parse("audio reboot".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_prelude() {
+        // a Track made, planned, played and rendered by hand, and
+        // its history read back, with nothing past the prelude
+        use crate::prelude::{AudioFile, CmdProcessor, Command, Conductor, EngineState, HistoryEntry};
+
+        let tone = AudioFile::new("tone", "tone", "wav", 8000, 1, 16, vec![8000; 800]);
+        assert!(!tone.is_mapped());
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![tone.clone()], 1));
+        let mut conductor = Conductor::with_tracks(1, vec![tone]);
+        for line in ["load tone", "fade 0", "start -v tone"] {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                assert!(!matches!(cmd, Command::Quit(_)));
+                conductor.apply(cmd);
+            }
+        }
+        conductor.apply(Command::fade(0.0));
+        let mut out = vec![0i16; 64];
+        conductor.render(&mut out);
+        assert!(out.iter().all(|&s| s != 0));
+        let history: Vec<&HistoryEntry> = cmd_processor.state().history().iter().collect();
+        assert_eq!(history.iter().map(|e| e.line.as_str()).collect::<Vec<_>>(), ["load tone", "fade 0", "start -v tone"]);
+    }
+
+    #[test]
+    fn test_headless() {
+        use crate::prelude::Engine;
+
+        let tone = AudioFile::new("tone", "tone", "wav", 8000, 1, 16, vec![8000; 8000]);
+        let tracks = HashMap::from([("tone".to_string(), tone)]);
+        let opts = RunOptions { backend: Some("null".to_string()), ..RunOptions::default() };
+        let nowhere = RunOptions { backend: Some("nowhere".to_string()), ..RunOptions::default() };
+        assert!(Engine::start(tracks.clone(), 8000, 1, &nowhere).is_err());
+
+        // lines are checked and planned as the REPL would, and the
+        // engine plays them on a thread of its own
+        let mut engine = Engine::start(tracks, 8000, 1, &opts).unwrap();
+        engine.run("load tone").unwrap();
+        engine.run("start -v tone").unwrap();
+        assert!(matches!(engine.run("start -v nope"), Err(CmdErr::NoVoice { .. })));
+        assert!(engine.run("quit").is_err());
+        assert!(engine.push(Command::quit()).is_err());
+
+        let start = std::time::Instant::now();
+        while engine.master().peak() == 0.0 && start.elapsed().as_secs() < 5 {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert!(engine.master().peak() > 0.0);
+        let levels = engine.levels();
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels[0].0, "tone");
+        engine.stop();
+    }
+
+    #[test]
+    fn test_midi_notes() {
+        // note ons on any channel, with running status and a clock
+        // byte in the middle; velocity 0 and note offs play nothing
+        let mut reader = NoteReader::default();
+        let bytes = [0x90, 36, 100, 38, 0xF8, 90, 0x99, 40, 0, 0x80, 36, 64, 0xB0, 7, 100, 0x95, 42, 1];
+        let played: Vec<(u8, u8)> = bytes.iter().filter_map(|b| reader.feed(*b)).collect();
+        assert_eq!(played, [(36, 100), (38, 90), (42, 1)]);
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(Vec::new(), 2));
+        for line in ["midi map C2 trig kick", "midi map 38 start snare", "midi map 38 stop snare", "midi map 40 trig hat"] {
+            assert!(cmd_processor.prepare(line.to_string()).unwrap().is_none());
+        }
+        assert!(cmd_processor.prepare("midi map 40 off".to_string()).unwrap().is_none());
+        assert_eq!(cmd_processor.notes.line(36).as_deref(), Some("trig kick"));
+        assert_eq!(cmd_processor.notes.line(38).as_deref(), Some("stop snare"));
+        assert_eq!(cmd_processor.notes.line(40), None);
+        assert_eq!(cmd_processor.notes.listing(), "  C2 (36) = trig kick\n  D2 (38) = stop snare");
+
+        for line in ["midi map 40 off", "midi map H2 trig kick", "midi map C2 loop kick", "midi map C2 trig", "midi notes"] {
+            assert!(cmd_processor.prepare(line.to_string()).is_err(), "{line}");
+        }
+        // nowhere to send what's played
+        assert!(cmd_processor.prepare("midi notes hw:9,0,0".to_string()).is_err());
+
+        let config = Config::parse("[notes]\nC2 = trig kick\n37 = start pad\n").unwrap();
+        assert_eq!(config.notes, [("C2".to_string(), "trig kick".to_string()), ("37".to_string(), "start pad".to_string())]);
+    }
+
+    #[test]
+    fn test_osc() {
+        // strings and the address are NUL-terminated and padded to 4
+        let padded = |s: &str| {
+            let mut bytes = s.as_bytes().to_vec();
+            bytes.resize((s.len() + 4) & !3, 0);
+            bytes
+        };
+        let message = |address: &str, tags: &str, args: &[u8]| [padded(address), padded(tags), args.to_vec()].concat();
+
+        let start = message("/blast/start", ",s", &padded("kick"));
+        assert_eq!(osc::lines(&start).unwrap(), ["start kick"]);
+        let velocity = message("/blast/velocity/kick", ",f", &0.5f32.to_be_bytes());
+        assert_eq!(osc::lines(&velocity).unwrap(), ["velocity kick 0.5"]);
+        let offset = message("/blast/offset", ",si", &[padded("pad"), 48000i32.to_be_bytes().to_vec()].concat());
+        assert_eq!(osc::lines(&offset).unwrap(), ["offset pad 48000"]);
+        assert_eq!(osc::lines(&padded("/blast/status")).unwrap(), ["status"]);
+
+        // a bundle's run in order
+        let mut bundle = [padded("#bundle"), vec![0; 8]].concat();
+        for element in [&start, &velocity] {
+            bundle.extend((element.len() as i32).to_be_bytes());
+            bundle.extend(element);
+        }
+        assert_eq!(osc::lines(&bundle).unwrap(), ["start kick", "velocity kick 0.5"]);
+        // a negative or oversized element size is turned down, not wrapped
+        for len in [-1i32, i32::MIN, i32::MAX] {
+            let bad = [padded("#bundle"), vec![0; 8], len.to_be_bytes().to_vec(), start.clone()].concat();
+            assert_eq!(osc::lines(&bad), Err("Truncated bundle".to_string()), "{len}");
+        }
+
+        assert!(osc::lines(&message("/synth/start", ",s", &padded("kick"))).is_err());
+        assert!(osc::lines(&message("/blast/", ",", &[])).is_err());
+        assert!(osc::lines(&message("/blast/start", ",b", &[0; 4])).is_err());
+        assert!(osc::lines(&message("/blast/velocity", ",f", &[0; 2])).is_err());
+        assert!(osc::lines(b"/blast/start").is_err());
+
+        // through a socket, into the inbox
+        let (inbox, lines) = std::sync::mpsc::channel();
+        let addr = osc::listen("127.0.0.1:0", inbox).unwrap();
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(&start, addr).unwrap();
+        let Input::Line { from, line, .. } = lines.recv_timeout(std::time::Duration::from_secs(5)).unwrap() else { panic!() };
+        assert_eq!((from.as_str(), line.as_str()), ("osc", "start kick"));
+
+        assert_eq!(Config::parse("osc = 9000\n").unwrap().osc, Some(9000));
+        assert!(Config::parse("osc = 90000\n").is_err());
+    }
+
+    #[test]
+    fn test_socket() {
+        use std::io::{BufRead, BufReader, Write};
+        use crate::audio_processing::socket::{self, Addr};
+
+        assert_eq!(Addr::parse("7777"), Ok(Addr::Tcp("127.0.0.1:7777".to_string())));
+        assert_eq!(Addr::parse("0.0.0.0:7777"), Ok(Addr::Tcp("0.0.0.0:7777".to_string())));
+        assert_eq!(Addr::parse("/tmp/blast.sock"), Ok(Addr::Unix("/tmp/blast.sock".to_string())));
+        assert!(Addr::parse("blast").is_err());
+        assert!(Addr::parse("").is_err());
+
+        // what's logged while teed is kept, and only that
+        tui::log("before");
+        let (n, kept) = tui::tee(|| {
+            tui::ok("ok (rev 1)");
+            tui::err("No such voice");
+            2
+        });
+        assert_eq!(n, 2);
+        assert_eq!(kept.iter().map(|l| l.text.as_str()).collect::<Vec<_>>(), ["ok (rev 1)", "Err: No such voice"]);
+
+        // a client's line goes through the inbox, and what it
+        // logged comes back, then an empty line
+        let answer = |stream: &mut dyn BufRead| {
+            let mut lines = Vec::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    return lines;
+                }
+                lines.push(line.trim_end().to_string());
+            }
+        };
+        let reply = |inbox: &std::sync::mpsc::Receiver<Input>, expect: &str| {
+            let Input::Line { from, line, reply } = inbox.recv_timeout(std::time::Duration::from_secs(5)).unwrap() else { panic!() };
+            assert_eq!((from.as_str(), line.as_str()), ("socket", expect));
+            reply.unwrap().send(vec![tui::Line::new(tui::Kind::Ok, format!("ran {line}"))]).unwrap();
+        };
+
+        let (inbox_tx, inbox) = std::sync::mpsc::channel();
+        let bound = socket::listen(&Addr::Tcp("127.0.0.1:0".to_string()), inbox_tx.clone()).unwrap();
+        let mut client = std::net::TcpStream::connect(&bound).unwrap();
+        client.write_all(b"start kick\n\nstatus\n").unwrap();
+        let mut from = BufReader::new(client.try_clone().unwrap());
+        reply(&inbox, "start kick");
+        assert_eq!(answer(&mut from), ["ran start kick"]);
+        reply(&inbox, "status");
+        assert_eq!(answer(&mut from), ["ran status"]);
+
+        let path = std::env::temp_dir().join(format!("blast-test-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        // a socket left behind is replaced
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        socket::listen(&Addr::Unix(path.clone()), inbox_tx.clone()).unwrap();
+        // but one that's listened on is left alone
+        assert!(socket::listen(&Addr::Unix(path.clone()), inbox_tx).unwrap_err().contains("another blast"));
+        let mut client = std::os::unix::net::UnixStream::connect(&path).unwrap();
+        client.write_all(b"stop kick\n").unwrap();
+        reply(&inbox, "stop kick");
+        assert_eq!(answer(&mut BufReader::new(client)), ["ran stop kick"]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Config::parse("listen = 7777\n").unwrap().listen, Some(Addr::Tcp("127.0.0.1:7777".to_string())));
+        assert!(Config::parse("listen = nowhere\n").is_err());
+    }
+
+    #[test]
+    fn test_cmd_queue() {
+        use std::sync::Arc;
+
+        // holds one less than its slots, and gives them back in order
+        let queue = CmdQueue::new(4);
+        assert!(queue.try_pop().is_none());
+        for ms in 0..3 {
+            queue.try_push(Command::Fade(FadeArgs { ms: ms as f32 })).unwrap();
+        }
+        assert!(queue.is_full());
+        assert!(queue.try_push(Command::Quit(QuitArgs {})).is_err());
+        assert!(matches!(queue.try_pop(), Some(Command::Fade(FadeArgs { ms })) if ms == 0.0));
+        assert!(!queue.is_full());
+
+        // across threads, every Command arrives whole and in order,
+        // however the two sides interleave
+        const N: usize = 10_000;
+        let queue = Arc::new(CmdQueue::new(8));
+        let producer = {
+            let queue = Arc::clone(&queue);
+            std::thread::spawn(move || {
+                for ms in 0..N {
+                    let mut cmd = Command::Fade(FadeArgs { ms: ms as f32 });
+                    while let Err(full) = queue.try_push(cmd) {
+                        assert_eq!(full, "Command queue full");
+                        cmd = Command::Fade(FadeArgs { ms: ms as f32 });
+                        std::thread::yield_now();
+                    }
+                }
+            })
+        };
+        let mut next = 0;
+        while next < N {
+            match queue.try_pop() {
+                Some(Command::Fade(FadeArgs { ms })) => {
+                    assert_eq!(ms, next as f32);
+                    next += 1;
+                }
+                Some(_) => panic!("only fades were pushed"),
+                None => std::thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert!(queue.try_pop().is_none());
+    }
+
+    #[test]
+    fn test_stdin_lines() {
+        // piped in, each line is run as typed, skipping what a
+        // script would, and the end of it is a quit
+        let (inbox_tx, inbox) = std::sync::mpsc::channel();
+        let piped = "load kick  # the kick\n\n  start -v kick\r\n# done\n";
+        read_lines(std::io::Cursor::new(piped), &inbox_tx);
+        let lines: Vec<String> = inbox
+            .try_iter()
+            .map(|input| match input {
+                Input::Line { from, line, reply: None } if from == "stdin" => line,
+                _ => panic!("only lines, unanswered"),
+            })
+            .collect();
+        assert_eq!(lines, ["load kick", "start -v kick", "quit"]);
+    }
+
+    #[test]
+    fn test_ws() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use crate::audio_processing::ws;
+
+        assert_eq!(ws::addr("8080"), Ok("127.0.0.1:8080".to_string()));
+        assert_eq!(ws::addr("0.0.0.0:8080"), Ok("0.0.0.0:8080".to_string()));
+        assert!(ws::addr("/tmp/blast").is_err());
+        // RFC 6455's example
+        assert_eq!(ws::accept("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(&ws::frame(0x1, &[0; 200])[..4], [0x81, 126, 0, 200]);
+
+        let (inbox_tx, inbox) = std::sync::mpsc::channel();
+        let bound = ws::serve("127.0.0.1:0", inbox_tx).unwrap();
+        let mut client = std::net::TcpStream::connect(&bound).unwrap();
+        client.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: x\r\nOrigin: http://localhost:8000\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+            .unwrap();
+        let mut from = BufReader::new(client.try_clone().unwrap());
+        let mut response = Vec::new();
+        loop {
+            let mut line = String::new();
+            from.read_line(&mut line).unwrap();
+            if line.trim_end().is_empty() {
+                break;
+            }
+            response.push(line.trim_end().to_string());
+        }
+        assert_eq!(response[0], "HTTP/1.1 101 Switching Protocols");
+        assert!(response.contains(&"Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string()));
+
+        // a page from anywhere else is turned away
+        for origin in ["https://example.com", "http://localhost.example.com", "null"] {
+            let mut other = std::net::TcpStream::connect(&bound).unwrap();
+            other.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+            let request = format!("GET / HTTP/1.1\r\nHost: x\r\nOrigin: {origin}\r\nSec-WebSocket-Key: a2V5\r\n\r\n");
+            other.write_all(request.as_bytes()).unwrap();
+            let mut status = String::new();
+            BufReader::new(other).read_line(&mut status).unwrap();
+            assert_eq!(status.trim_end(), "HTTP/1.1 403 Forbidden", "{origin}");
+        }
+
+        // a client's text, masked as it must be, is a line
+        let line = b"start -v kick";
+        let mask = [1u8, 2, 3, 4];
+        let mut frame = vec![0x81, 0x80 | line.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(line.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        client.write_all(&frame).unwrap();
+
+        let message = |from: &mut BufReader<std::net::TcpStream>| {
+            let mut head = [0u8; 4];
+            from.read_exact(&mut head[..2]).unwrap();
+            let len = match head[1] {
+                126 => {
+                    from.read_exact(&mut head[2..]).unwrap();
+                    u16::from_be_bytes([head[2], head[3]]) as usize
+                }
+                len => len as usize,
+            };
+            let mut payload = vec![0u8; len];
+            from.read_exact(&mut payload).unwrap();
+            String::from_utf8(payload).unwrap()
+        };
+
+        // the REPL answers both the line and the state
+        let (mut replied, mut stated) = (false, false);
+        while !(replied && stated) {
+            match inbox.recv_timeout(std::time::Duration::from_secs(5)).unwrap() {
+                Input::Line { from, line, reply } => {
+                    assert_eq!((from.as_str(), line.as_str()), ("ws", "start -v kick"));
+                    reply.unwrap().send(vec![tui::Line::new(tui::Kind::Ok, "ok \"rev 1\"".to_string())]).unwrap();
+                    replied = true;
+                }
+                Input::State(reply) => {
+                    let tracks = vec![AudioFile::new("kick", "kick", "wav", 8000, 1, 16, vec![0; 16])];
+                    let mut cmd_processor = CmdProcessor::new(EngineState::new(tracks, 2));
+                    cmd_processor.parse("load kick".to_string()).unwrap();
+                    let master = crate::audio_processing::meters::Level::default();
+                    reply.send(ws::state_json(&cmd_processor.engine_state, &master)).unwrap();
+                    stated = true;
+                }
+                _ => panic!("only lines and state"),
+            }
+        }
+        let mut messages = [message(&mut from), message(&mut from)];
+        messages.sort();
+        assert_eq!(messages[0], r#"{"type":"reply","line":"start -v kick","log":[{"kind":"ok","text":"ok \"rev 1\""}]}"#);
+        assert!(messages[1].starts_with(r#"{"type":"state","clock":{"samples":"#), "{}", messages[1]);
+        assert!(
+            messages[1].ends_with(r#""voices":[{"name":"kick","playing":false,"muted":false,"soloed":false,"peak":0,"rms":0,"position":0,"length":0}]}"#),
+            "{}", messages[1],
+        );
+
+        assert_eq!(Config::parse("ws = 8080\n").unwrap().ws, Some("127.0.0.1:8080".to_string()));
+        assert!(Config::parse("ws = nowhere\n").is_err());
+    }
+
+    #[test]
+    fn test_scripting() {
+        use crate::audio_processing::scripting::Scripts;
+
+        let mut scripts = Scripts::default();
+        assert!(scripts.run("gen.rhai", "gen").is_err()); // no REPL to run lines
+        assert_eq!(scripts.listing(), "No scripts running");
+        assert!(scripts.stop("gen").is_err());
+
+        let (inbox_tx, inbox) = std::sync::mpsc::channel();
+        scripts.inbox = Some(inbox_tx);
+        let dir = std::env::temp_dir();
+        let script = |name: &str, text: &str| {
+            let path = dir.join(format!("blast-test-{}-{name}.rhai", std::process::id()));
+            std::fs::write(&path, text).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let lines = script("gen", "if blast(\"trig kick\") { blast(\"stop kick\"); }\nif !blast(\"trig nope\") { blast(\"start hat\"); }\n");
+
+        if cfg!(not(feature = "rhai")) {
+            assert!(scripts.run(&lines, "gen").unwrap_err().contains("--features rhai"));
+            std::fs::remove_file(lines).unwrap();
+            return;
+        }
+
+        // each line goes through the inbox, and what it logged
+        // says if it went through
+        scripts.run(&lines, "gen").unwrap();
+        let mut ran = Vec::new();
+        for _ in 0..4 {
+            let Input::Line { from, line, reply } = inbox.recv_timeout(std::time::Duration::from_secs(5)).unwrap() else { panic!() };
+            assert_eq!(from, "gen");
+            let logged = match line.as_str() {
+                "trig nope" => tui::Line::new(tui::Kind::Err, "Err: No voice nope"),
+                _ => tui::Line::new(tui::Kind::Ok, "ok"),
+            };
+            reply.unwrap().send(vec![logged]).unwrap();
+            ran.push(line);
+        }
+        assert_eq!(ran, ["trig kick", "stop kick", "trig nope", "start hat"]);
+
+        // one that never ends runs until it's stopped
+        let forever = script("forever", "loop { sleep(1); }\n");
+        scripts.run(&forever, "hats").unwrap();
+        assert!(scripts.run(&forever, "hats").is_err());
+        assert!(scripts.listing().contains("hats"));
+        scripts.stop("hats").unwrap();
+        assert!(!scripts.listing().contains("hats"));
+
+        let broken = script("broken", "let x = ;\n");
+        assert!(scripts.run(&broken, "broken").is_err());
+        for path in [lines, forever, broken] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_latency() {
+        assert_eq!(Latency::new(256, None), Ok(Latency { period: 256, buffer: 1024 }));
+        assert_eq!(Latency::new(64, Some(128)), Ok(Latency { period: 64, buffer: 128 }));
+        assert!(Latency::new(8, None).is_err());
+        assert!(Latency::new(8192, None).is_err());
+        assert!(Latency::new(256, Some(300)).is_err());
+
+        assert_eq!(Latency::DEFAULT.doubled(), Latency { period: 256, buffer: 1024 });
+        assert_eq!(Latency { period: 4096, buffer: 8192 }.doubled().period, Latency::MAX_PERIOD);
+        assert_eq!(Latency::DEFAULT.describe(48000), "period 128, buffer 512 frames (10.7ms at 48000 Hz)");
+
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(Vec::new(), 2));
+        let plan = cmd_processor.prepare("latency 256".to_string()).unwrap().unwrap();
+        assert!(matches!(
+            plan.command,
+            Command::Audio(AudioArgs { action: AudioAction::Latency(Latency { period: 256, buffer: 1024 }) })
+        ));
+        let plan = cmd_processor.prepare("latency 64 128".to_string()).unwrap().unwrap();
+        assert!(matches!(
+            plan.command,
+            Command::Audio(AudioArgs { action: AudioAction::Latency(Latency { period: 64, buffer: 128 }) })
+        ));
+        assert!(cmd_processor.prepare("latency 4".to_string()).is_err());
+        assert!(cmd_processor.prepare("latency 256 256".to_string()).is_err());
+        assert!(cmd_processor.prepare("latency fast".to_string()).is_err());
+        backend::settle(Latency::DEFAULT);
+        assert!(cmd_processor.prepare("latency".to_string()).unwrap().is_none());
+
+        let config = Config::parse("period = 256\n").unwrap();
+        assert_eq!(config.latency, Some(Latency { period: 256, buffer: 1024 }));
+        let config = Config::parse("buffer = 1024\n").unwrap();
+        assert_eq!(config.latency, Some(Latency { period: 128, buffer: 1024 }));
+        assert!(Config::parse("period = 256\nbuffer = 300\n").is_err());
+        assert!(Config::parse("").unwrap().latency.is_none());
+    }
+
+    #[test]
+    fn test_keymap() {
+        assert_eq!(notes::parse("C4"), Some(60));
+        assert_eq!(notes::parse("f#3"), Some(54));
+        assert_eq!(notes::parse("Bb-1"), Some(10));
+        assert_eq!(notes::parse("127"), Some(127));
+        assert_eq!(notes::parse("128"), None);
+        assert_eq!(notes::parse("H2"), None);
+        assert_eq!(notes::name(69), "A4");
+
+        sample_rate::set(8000);
+        let samples = (0..800).map(|n| n * 10).collect::<Vec<i16>>();
+        let af = AudioFile::new("keys", "keys", "wav", 8000, 1, 16, samples);
+        let mut cmd_processor = CmdProcessor::new(EngineState::new(vec![af.clone()], 1));
+        let mut conductor = Conductor::prepare(1, HashMap::from([("keys".to_string(), af)]));
+        let mut frame = [0i16; 1];
+        let mut run = |line: &str| {
+            if let Some(cmd) = cmd_processor.parse(line.to_string()).unwrap() {
+                conductor.apply(cmd);
+            }
+            conductor.render(&mut frame);
+            frame[0]
+        };
+
+        run("fade 0");
+        run("load keys");
+        run("keymap keys root C3 span 12");
+        // an octave up plays twice as fast, from the top
+        assert_eq!(run("note keys C4"), 0);
+        assert_eq!(run("fade 0"), 20);
+        assert_eq!(run("fade 0"), 40);
+        // the root plays it as it is
+        assert_eq!(run("note keys 48"), 0);
+        assert_eq!(run("fade 0"), 10);
+
+        assert!(matches!(
+            cmd_processor.parse("note keys C5".to_string()),
+            Err(CmdErr::InvalidArg { .. }),
+        ));
+        cmd_processor.parse("keymap keys off".to_string()).unwrap();
+        assert!(matches!(
+            cmd_processor.parse("note keys C3".to_string()),
+            Err(CmdErr::Formatting { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_verb_tail() {
+        sample_rate::set(8000);
+        let mut samples = vec![0i16; 4000];
+        samples[0] = 10000;
+        let af = AudioFile::new("click", "click", "wav", 8000, 1, 16, samples);
+
+        // all wet, so nothing comes out until the shortest comb
+        // (1116 samples at 44.1k, 202 here) comes around
+        let preset = ["fade 0", "verb master -r 0.8 -m 1"].map(String::from);
+        let out = offline::process(&preset, af, 10.0).unwrap();
+        assert!(out[..202].iter().all(|s| *s == 0));
+
+        let energy = |range: std::ops::Range<usize>| out[range].iter().map(|s| (*s as f64).powi(2)).sum::<f64>();
+        assert!(energy(200..1200) > 0.0);
+        assert!(energy(2800..3800) < energy(200..1200));
+    }
+
+    #[test]
+    fn test_xrun_watch() {
+        let mut watch = XrunWatch::default();
+        let start = std::time::Instant::now();
+        let at = |secs: u64| start + std::time::Duration::from_secs(secs);
+
+        // spread out, they never add up
+        assert!(!watch.xrun(at(0)));
+        assert!(!watch.xrun(at(2)));
+        assert!(!watch.xrun(at(XRUN_WINDOW.as_secs() + 1)));
+
+        // close together, the third one trips it, and it starts over
+        assert!(!watch.xrun(at(20)));
+        assert!(!watch.xrun(at(21)));
+        assert!(watch.xrun(at(22)));
+        assert!(!watch.xrun(at(23)));
+    }
+
+    #[test]
+    fn test_stretch_pitch() {
+        sample_rate::set(8000);
+        // one second of a sine 40 frames long
+        let samples: Vec<i16> = (0..8000)
+            .map(|i| ((i as f32 / 40.0 * std::f32::consts::TAU).sin() * 8000.0) as i16)
+            .collect();
+        let af = AudioFile::new("sine", "sine", "wav", 8000, 1, 16, samples);
+
+        let render = |line: &str| {
+            let preset = ["fade 0", line].map(String::from);
+            offline::process(&preset, af.clone(), 10.0).unwrap()
+        };
+        let crossings = |out: &[i16]| out.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count();
+
+        // twice as long, same pitch: 100 cycles in 4000 frames
+        let out = render("stretch $voice 2");
+        assert!((15990..=16010).contains(&out.len()), "{}", out.len());
+        assert!((190..=210).contains(&crossings(&out[2000..6000])));
+
+        // an octave up, same length
+        let out = render("pitch $voice 12");
+        assert!((7990..=8010).contains(&out.len()), "{}", out.len());
+        assert!((380..=420).contains(&crossings(&out[2000..6000])));
     }
 }
@@ -1,21 +1,93 @@
-use std::fs;
+use std::{fs, thread, time::Duration};
+use std::net::IpAddr;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::collections::{HashMap, hash_map::Entry};
 use blast::{
-    file_parsing::{
-        mpeg, aiff, wav,
-        decode_helpers::{
-            DecodeError, DecodeResult, AudioFile
-        },
-    },
-    audio_processing::runtime::run_blast,
+    prelude::*,
+    cli::{self, bundle, validate, run_blast, Addr},
 };
 
+const ASSETS: &str = "blast/assets/";
+// what the engine plays at without --rate (or rate in blast.conf)
+const DEFAULT_RATE: u32 = 48000;
+
 fn main() -> DecodeResult<()> {
-    let mut tracks = HashMap::<String, AudioFile>::new();
-    let mut sample_rates = HashMap::<u32, u32>::new();
-    let mut channel_nums = Vec::<u32>::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bundle") {
+        return run_bundle(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("process") {
+        return run_process(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("render") {
+        return run_render(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("validate") {
+        return run_validate(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("--dump-commands") {
+        return dump_commands(&args[1..]);
+    }
+    let mut opts = parse_options(&args)?;
+
+    let config = Config::load().map_err(DecodeError::InvalidData)?;
+    opts.audio_cpu = config.audio_cpu;
+    opts.fade_ms = config.fade_ms;
+    opts.voices = config.voices;
+    opts.aliases = config.aliases.clone();
+    opts.keys = config.keys.clone();
+    opts.notes = config.notes.clone();
+    // --device wins over blast.conf
+    if opts.device.is_none() {
+        opts.device = config.device.clone();
+    }
+    if opts.backend.is_none() {
+        opts.backend = config.backend.clone();
+    }
+    if opts.latency.is_none() {
+        opts.latency = config.latency;
+    }
+    if opts.osc.is_none() {
+        opts.osc = config.osc;
+    }
+    if opts.listen.is_none() {
+        opts.listen = config.listen.clone();
+    }
+    if opts.ws.is_none() {
+        opts.ws = config.ws.clone();
+    }
+    if opts.rate.is_none() {
+        opts.rate = config.rate;
+    }
+    let workers: Vec<usize> = match &config.worker_cpus {
+        Some(cpus) => cpus.clone(),
+        None => cli::available_cpus()
+            .into_iter()
+            .filter(|&cpu| Some(cpu) != config.audio_cpu)
+            .collect(),
+    };
+
+    // keep decoding and the REPL off the audio core;
+    // every thread spawned from here on inherits this
+    if (config.audio_cpu.is_some() || config.worker_cpus.is_some())
+        && let Err(diag) = cli::pin_current_thread(&workers) {
+        println!("Warn: {diag}");
+    }
+
+    let rate = opts.rate.unwrap_or(DEFAULT_RATE);
+    let (tracks, num_channels) = load_assets(workers.len(), rate, !opts.no_resample)?;
+    run_blast(tracks, rate, num_channels, &opts);
+
+    Ok(())
+}
 
-    for entry in fs::read_dir("blast/assets/")? {
+// decodes everything in ASSETS, resampled to the rate the engine
+// plays at (unless resample is off), and decides the channel count
+// (the most) to play with
+fn load_assets(threads: usize, rate: u32, resample: bool) -> DecodeResult<(HashMap<String, AudioFile>, u32)> {
+    let mut paths = Vec::<String>::new();
+    for entry in fs::read_dir(ASSETS)? {
         let dir = match entry {
             Ok(pathbuf) => pathbuf,
             Err(error) => {
@@ -24,60 +96,21 @@ fn main() -> DecodeResult<()> {
             }
         };
 
-        let pathbuf = dir.path();
-
-        let path = match pathbuf.to_str() {
-            Some(valid) => valid,
-            None => {
-                println!("Error: invalid unicode in '{:?}'", dir.path());
-                continue;
-            }
-        };
-
-
-        let ext: &str = match path.rsplit_once(|b: char| b == '.') {
-            Some((before, after)) if !before.is_empty() && !after.is_empty() => after,
-            _ => "",
-        };
+        match dir.path().to_str() {
+            Some(valid) => paths.push(valid.to_string()),
+            None => println!("Error: invalid unicode in '{:?}'", dir.path()),
+        }
+    }
 
-        let track: AudioFile = match ext {
-            /* TODO: figure out actual mpeg decoding...
-            "mp3" => {
-                match mpeg::parse(path) {
-                    Ok(file) => file,
-                    Err(error) => {
-                        println!("{:?}", error);
-                        continue;
-                    }
-                }
-            }
-            */
-            "wav" => {
-                match wav::parse(path) {
-                    Ok(file) => file,
-                    Err(error) => {
-                        println!("{:?}", error);
-                        continue;
-                    }
-                }
-            }
-            "aif" => {
-                match aiff::parse(path) {
-                    Ok(file) => file,
-                    Err(error) => {
-                        println!("{:?}", error);
-                        continue;
-                    }
-                }
-            }
-            _ => {
-                println!("Error: unsupported format for '{}'", path);
-                continue;
-            }
-        };
+    let mut tracks = HashMap::<String, AudioFile>::new();
+    let mut channel_nums = Vec::<u32>::new();
+    let mut converted = Vec::<(String, u32)>::new();
 
-        *sample_rates.entry(track.sample_rate).or_insert(0) += 1;
+    for (track, from) in decode_all(&paths, threads, resample.then_some(rate)) {
         channel_nums.push(track.num_channels);
+        if from != track.sample_rate {
+            converted.push((track.file_name.clone(), from));
+        }
         
         match tracks.entry(track.file_name.clone()) {
             Entry::Vacant(e) => { e.insert(track);}
@@ -88,25 +121,11 @@ fn main() -> DecodeResult<()> {
         }
     }
 
-    let mutual_rate: u32 = {
-        let mut rates: Vec<(&u32, &u32)> = sample_rates.iter().collect();
-        rates.sort_by(|(_, v1), (_, v2)| v2.cmp(v1));
-        let (key, val) = match rates.get(0) {
-            Some((k, v)) => (**k, **v),
-            None => {
-                println!("Error: problem with deciding sample rate");
-                (44100, 0)
-            }
-        };
-       
-        println!("Mutual sample_rate: {key}");
-
-        key
-    };
+    println!("Sample rate: {rate}");
 
     let num_channels: u32 = {
         channel_nums.sort_by(|v1, v2| v2.cmp(v1));
-        let val = match channel_nums.get(0) {
+        let val = match channel_nums.first() {
             Some(v) => *v,
             None => {
                 println!("Error: problem with deciding num channels");
@@ -120,12 +139,511 @@ fn main() -> DecodeResult<()> {
     };
 
     println!("Loaded tracks [");
-    for (track, _) in &tracks {
-        println!("\t{}", track);
+    for (name, track) in &tracks {
+        match converted.iter().find(|(n, _)| n == name) {
+            Some((_, from)) => println!("\t{} (resampled from {})", name, from),
+            // Voices convert these on the fly
+            None if track.sample_rate != rate => println!("\t{} (at {})", name, track.sample_rate),
+            None => println!("\t{}", name),
+        }
     }
     println!("]");
 
-    run_blast(tracks, mutual_rate, num_channels);
+    Ok((tracks, num_channels))
+}
+
+// decode pool: splits paths between threads, keeping their order,
+// and resamples each Track there (to rate, if given), returning it
+// with the rate it was decoded at; on a terminal, draws a loading
+// bar for the bytes of them all as they're read
+fn decode_all(paths: &[String], threads: usize, rate: Option<u32>) -> Vec<(AudioFile, u32)> {
+    let total: usize = paths
+        .iter()
+        .filter(|p| p.ends_with(".wav") || p.ends_with(".aif"))
+        .filter_map(|p| fs::metadata(p).ok())
+        .map(|m| m.len() as usize)
+        .sum();
+    let loaded = AtomicUsize::new(0);
+    let loaded = &loaded;
+    let decode_counted = move |path: &String| {
+        let read = AtomicUsize::new(0);
+        let progress = |done: usize, _total: usize| {
+            loaded.fetch_add(done - read.swap(done, Ordering::Relaxed), Ordering::Relaxed);
+        };
+        decode(path, Some(&progress))
+    };
+
+    let chunk = paths.len().div_ceil(threads.max(1)).max(1);
+    let convert = move |mut af: AudioFile| {
+        let from = af.sample_rate;
+        // a mapped Track stays as it is, for Voices to step through
+        if let Some(rate) = rate
+            && !af.is_mapped()
+        {
+            af.resample_to(rate);
+        }
+        (af, from)
+    };
+
+    thread::scope(|s| {
+        let handles: Vec<_> = paths
+            .chunks(chunk)
+            .map(|chunk| s.spawn(move || chunk.iter().filter_map(decode_counted).map(convert).collect::<Vec<_>>()))
+            .collect();
+
+        if io::stdout().is_terminal() {
+            let width = cli::term_size().0.min(80);
+            while !handles.iter().all(|h| h.is_finished()) {
+                print!("\r{}", cli::loading(loaded.load(Ordering::Relaxed), total, width));
+                io::stdout().flush().ok();
+                thread::sleep(Duration::from_millis(100));
+            }
+            println!("\r{}", cli::loading(total, total, width));
+        }
+
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+fn decode(path: &str, progress: Progress) -> Option<AudioFile> {
+    let ext: &str = match path.rsplit_once('.') {
+        Some((before, after)) if !before.is_empty() && !after.is_empty() => after,
+        _ => "",
+    };
+
+    match ext {
+        /* TODO: figure out actual mpeg decoding...
+        "mp3" => {
+            match mpeg::parse(path) {
+                Ok(file) => Some(file),
+                Err(error) => {
+                    println!("Error: {error}");
+                    None
+                }
+            }
+        }
+        */
+        "wav" => {
+            match wav::open_with(path, progress) {
+                Ok(file) => Some(file),
+                Err(error) => {
+                    println!("Error: {error}");
+                    None
+                }
+            }
+        }
+        "aif" => {
+            match aiff::parse_with(path, progress) {
+                Ok(file) => Some(file),
+                Err(error) => {
+                    println!("Error: {error}");
+                    None
+                }
+            }
+        }
+        _ => {
+            println!("Error: unsupported format for '{}'", path);
+            None
+        }
+    }
+}
+
+// blast [--rt[=priority]] [--adaptive] [--script <path>] [--device <pcm>] [--backend alsa|pulse|jack|cpal|null]
+//       [--rate <hz>] [--no-resample] [--period <frames>] [--buffer <frames>] [--osc <port>]
+//       [--osc-host <ip>] [--listen <path>|<port>|<host:port>] [--ws <port>|<host:port>] [--verbose-events]
+fn parse_options(args: &[String]) -> DecodeResult<RunOptions> {
+    let mut opts = RunOptions::default();
+    let mut period = None;
+    let mut buffer = None;
+    let frames = |flag: &str, val: Option<&str>| -> DecodeResult<usize> {
+        let val = val.ok_or_else(|| DecodeError::InvalidData(format!("Missing frames for {flag}")))?;
+        val.parse::<usize>()
+            .map_err(|_| DecodeError::InvalidData(format!("Invalid frames '{val}' for {flag}")))
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--script" {
+            let path = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing path for --script".to_string())
+            })?;
+            opts.script = Some(path.clone());
+            continue;
+        }
+        if arg == "--backend" {
+            let backend = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing name for --backend".to_string())
+            })?;
+            opts.backend = Some(backend.clone());
+            continue;
+        }
+        if arg == "--device" {
+            let device = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing name for --device".to_string())
+            })?;
+            opts.device = Some(device.clone());
+            continue;
+        }
+        if arg == "--osc" {
+            let port = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing port for --osc".to_string())
+            })?;
+            opts.osc = Some(osc_port(port)?);
+            continue;
+        }
+        if arg == "--osc-host" {
+            let host = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing address for --osc-host".to_string())
+            })?;
+            opts.osc_host = Some(osc_host(host)?);
+            continue;
+        }
+        if arg == "--listen" {
+            let addr = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing address for --listen".to_string())
+            })?;
+            opts.listen = Some(listen_addr(addr)?);
+            continue;
+        }
+        if arg == "--ws" {
+            let addr = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing address for --ws".to_string())
+            })?;
+            opts.ws = Some(ws_addr(addr)?);
+            continue;
+        }
+        if arg == "--rate" {
+            let hz = args.next().ok_or_else(|| {
+                DecodeError::InvalidData("Missing Hz for --rate".to_string())
+            })?;
+            opts.rate = Some(rate(hz)?);
+            continue;
+        }
+        if arg == "--period" {
+            period = Some(frames(arg, args.next().map(String::as_str))?);
+            continue;
+        }
+        if arg == "--buffer" {
+            buffer = Some(frames(arg, args.next().map(String::as_str))?);
+            continue;
+        }
+
+        let (flag, val) = match arg.split_once('=') {
+            Some((flag, val)) => (flag, Some(val)),
+            None => (arg.as_str(), None),
+        };
+
+        match (flag, val) {
+            ("--rt", None) => opts.realtime = Some(70),
+            ("--rt", Some(prio)) => {
+                let prio = prio.parse::<i32>().map_err(|_| {
+                    DecodeError::InvalidData(format!("Invalid priority '{prio}' for --rt"))
+                })?;
+                opts.realtime = Some(prio);
+            }
+            ("--adaptive", None) => opts.adaptive = true,
+            ("--no-resample", None) => opts.no_resample = true,
+            ("--rate", Some(hz)) => opts.rate = Some(rate(hz)?),
+            ("--verbose-events", None) => opts.verbose_events = true,
+            ("--script", Some(path)) => opts.script = Some(path.to_string()),
+            ("--device", Some(device)) => opts.device = Some(device.to_string()),
+            ("--backend", Some(backend)) => opts.backend = Some(backend.to_string()),
+            ("--osc", Some(port)) => opts.osc = Some(osc_port(port)?),
+            ("--osc-host", Some(host)) => opts.osc_host = Some(osc_host(host)?),
+            ("--listen", Some(addr)) => opts.listen = Some(listen_addr(addr)?),
+            ("--ws", Some(addr)) => opts.ws = Some(ws_addr(addr)?),
+            ("--period", Some(val)) => period = Some(frames(flag, Some(val))?),
+            ("--buffer", Some(val)) => buffer = Some(frames(flag, Some(val))?),
+            _ => return Err(DecodeError::InvalidData(format!("Unknown option '{arg}'"))),
+        }
+    }
+
+    if period.is_some() || buffer.is_some() {
+        let period = period.unwrap_or(Latency::DEFAULT.period);
+        opts.latency = Some(Latency::new(period, buffer).map_err(DecodeError::InvalidData)?);
+    }
+
+    Ok(opts)
+}
+
+fn rate(hz: &str) -> DecodeResult<u32> {
+    match hz.parse::<u32>() {
+        Ok(hz) if hz > 0 => Ok(hz),
+        _ => Err(DecodeError::InvalidData(format!("Invalid rate '{hz}' for --rate"))),
+    }
+}
+
+fn osc_port(port: &str) -> DecodeResult<u16> {
+    port.parse::<u16>()
+        .map_err(|_| DecodeError::InvalidData(format!("Invalid port '{port}' for --osc")))
+}
+
+// an interface to take OSC on from other machines, e.g. 0.0.0.0
+fn osc_host(host: &str) -> DecodeResult<IpAddr> {
+    host.parse::<IpAddr>()
+        .map_err(|_| DecodeError::InvalidData(format!("Invalid address '{host}' for --osc-host")))
+}
+
+fn listen_addr(addr: &str) -> DecodeResult<Addr> {
+    Addr::parse(addr).map_err(|e| DecodeError::InvalidData(format!("{e} for --listen")))
+}
+
+fn ws_addr(addr: &str) -> DecodeResult<String> {
+    cli::ws_addr(addr).map_err(|e| DecodeError::InvalidData(format!("{e} for --ws")))
+}
+
+// blast --dump-commands json
+//
+// prints the REPL's command schema (see audio_processing/schema.rs)
+fn dump_commands(args: &[String]) -> DecodeResult<()> {
+    match args.first().map(String::as_str) {
+        Some("json") | None => {
+            println!("{}", schema::to_json());
+            Ok(())
+        }
+        Some(format) => Err(DecodeError::InvalidData(format!(
+            "Unknown format '{format}' for --dump-commands (only json)"
+        ))),
+    }
+}
+
+// blast process <preset> <file>... [-o <dir>] [--length <secs>]
+//
+// applies a preset (see audio_processing/offline.rs) to every file
+// and writes the results as <dir>/<name>.wav
+fn run_process(args: &[String]) -> DecodeResult<()> {
+    let usage = || DecodeError::InvalidData(
+        "usage: blast process <preset> <file>... [-o <dir>] [--length <secs>]".to_string()
+    );
+
+    let mut args = args.iter();
+    let preset_path = args.next().ok_or_else(usage)?;
+
+    let mut files = Vec::<String>::new();
+    let mut out_dir = "processed".to_string();
+    let mut max_secs = 600f32;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--out" => out_dir = args.next().ok_or_else(usage)?.clone(),
+            "--length" => {
+                max_secs = args
+                    .next()
+                    .and_then(|l| l.parse::<f32>().ok())
+                    .filter(|l| *l > 0.0)
+                    .ok_or_else(usage)?;
+            }
+            _ => files.push(arg.clone()),
+        }
+    }
+    if files.is_empty() {
+        return Err(usage());
+    }
+
+    let preset: Vec<String> = fs::read_to_string(preset_path)?
+        .lines()
+        .map(|l| l.split('#').next().unwrap().trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    fs::create_dir_all(&out_dir)?;
+
+    for path in files {
+        let Some(af) = decode(&path, None) else { continue };
+        let (rate, channels, name) = (af.sample_rate, af.num_channels, af.file_name.clone());
+
+        match offline::process(&preset, af, max_secs) {
+            Ok(samples) => {
+                let out = format!("{}/{}.wav", out_dir.trim_end_matches('/'), name);
+                wav::write(&out, rate, channels, &samples)?;
+                println!("{} -> {}", path, out);
+            }
+            Err(error) => println!("Error: {}: {}", path, error),
+        }
+    }
+
+    Ok(())
+}
+
+// blast render <file> <duration> [--script <path>]
+//
+// plays a session into <file> (a WAV) instead of a device, as
+// fast as it renders, for duration (90, 90s or 500ms); the
+// script is run first, as --script would run it
+fn run_render(args: &[String]) -> DecodeResult<()> {
+    let usage = || DecodeError::InvalidData(
+        "usage: blast render <file> <duration> [--script <path>]".to_string()
+    );
+
+    let (out, raw_len) = match args {
+        [out, len, ..] => (out, len),
+        _ => return Err(usage()),
+    };
+    let secs = parse_duration(raw_len).ok_or_else(|| {
+        DecodeError::InvalidData(format!("Invalid duration '{raw_len}' (e.g. 90, 90s or 500ms)"))
+    })?;
+
+    let mut opts = RunOptions::default();
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.split_once('=') {
+            Some(("--script", path)) => opts.script = Some(path.to_string()),
+            None if arg == "--script" => opts.script = Some(rest.next().ok_or_else(usage)?.clone()),
+            _ => return Err(usage()),
+        }
+    }
+
+    let config = Config::load().map_err(DecodeError::InvalidData)?;
+    opts.fade_ms = config.fade_ms;
+    opts.voices = config.voices;
+    opts.aliases = config.aliases.clone();
+
+    let threads = config.worker_cpus.map_or(cli::available_cpus().len(), |cpus| cpus.len());
+    let rate = config.rate.unwrap_or(DEFAULT_RATE);
+    let (tracks, channels) = load_assets(threads, rate, true)?;
+    let samples = offline::render(tracks, rate, channels as usize, &opts, secs).map_err(DecodeError::InvalidData)?;
+    wav::write(out, rate, channels, &samples)?;
+    println!("Rendered {secs}s -> {out}");
 
     Ok(())
 }
+
+// seconds, bare or with s, or ms
+fn parse_duration(raw: &str) -> Option<f32> {
+    let secs = match raw.strip_suffix("ms") {
+        Some(ms) => ms.parse::<f32>().ok()? / 1000.0,
+        None => raw.strip_suffix('s').unwrap_or(raw).parse::<f32>().ok()?,
+    };
+    Some(secs).filter(|s| s.is_finite() && *s > 0.0)
+}
+
+// blast validate <path>...
+//
+// checks every file given, and every sound file under every
+// directory given, for anomalies in its structure, without
+// decoding any of it; fails if any file has one
+fn run_validate(args: &[String]) -> DecodeResult<()> {
+    if args.is_empty() {
+        return Err(DecodeError::InvalidData("usage: blast validate <path>...".to_string()));
+    }
+
+    let (mut checked, mut failed) = (0, 0);
+    for arg in args {
+        for path in validate::files(arg)? {
+            checked += 1;
+            match validate::file(&path) {
+                Ok(anomalies) if anomalies.is_empty() => println!("{path}: ok"),
+                Ok(anomalies) => {
+                    failed += 1;
+                    println!("{path} [");
+                    for anomaly in anomalies {
+                        println!("\t{anomaly}");
+                    }
+                    println!("]");
+                }
+                Err(error) => {
+                    failed += 1;
+                    println!("Error: {error}");
+                }
+            }
+        }
+    }
+
+    match failed {
+        0 => {
+            println!("Checked {checked} files");
+            Ok(())
+        }
+        _ => Err(DecodeError::InvalidData(format!("{failed} of {checked} files have anomalies"))),
+    }
+}
+
+// blast bundle export <file> [--hashes] [-f <file>...]
+// blast bundle import <file> [dir]
+//
+// runs before a session starts, so import can prompt
+// on a normal terminal for assets the bundle only hashed
+fn run_bundle(args: &[String]) -> DecodeResult<()> {
+    let usage = || DecodeError::InvalidData(
+        "usage: blast bundle export <file> [--hashes] [-f <file>...] | import <file> [dir]".to_string()
+    );
+
+    let (action, pack) = match args {
+        [action, pack, ..] => (action.as_str(), pack.as_str()),
+        _ => return Err(usage()),
+    };
+
+    match action {
+        "export" => {
+            let mut hashes_only = false;
+            let mut files = Vec::<String>::new();
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--hashes" => hashes_only = true,
+                    "-f" | "--file" => files.push(rest.next().ok_or_else(usage)?.clone()),
+                    _ => return Err(usage()),
+                }
+            }
+
+            let mut tracks = Vec::<String>::new();
+            for entry in fs::read_dir(ASSETS)? {
+                let path = entry?.path();
+                if let Some(path) = path.to_str()
+                    && matches!(path.rsplit_once('.'), Some((_, "wav" | "aif"))) {
+                    tracks.push(path.to_string());
+                }
+            }
+            tracks.sort();
+
+            let entries = bundle::export(pack, &tracks, &files, hashes_only)?;
+            println!("Bundled {} entries into '{}'", entries.len(), pack);
+        }
+        "import" => {
+            let dest = args.get(2).map(String::as_str).unwrap_or(".");
+            let missing = bundle::import(pack, dest, prompt_for_asset)?;
+
+            if missing.is_empty() {
+                println!("Restored '{}' into '{}'", pack, dest);
+            } else {
+                println!("Restored '{}' into '{}', still missing [", pack, dest);
+                for entry in missing {
+                    println!("\t{}", entry.path);
+                }
+                println!("]");
+            }
+        }
+        _ => return Err(usage()),
+    }
+
+    Ok(())
+}
+
+// asks for a replacement until one matches the bundled hash,
+// the user accepts a mismatch, or leaves the answer blank
+fn prompt_for_asset(entry: &bundle::Entry) -> Option<String> {
+    let read_line = || {
+        let mut line = String::new();
+        io::stdout().flush().ok()?;
+        io::stdin().read_line(&mut line).ok()?;
+        Some(line.trim().to_string())
+    };
+
+    loop {
+        print!("Missing '{}'; path to a replacement (blank to skip): ", entry.path);
+        let path = read_line().filter(|p| !p.is_empty())?;
+
+        match fs::read(&path) {
+            Ok(bytes) if bundle::hash(&bytes) == entry.hash => return Some(path),
+            Ok(_) => {
+                print!("'{}' differs from the bundled file; use it anyway? [y/N] ", path);
+                if read_line()?.eq_ignore_ascii_case("y") {
+                    return Some(path);
+                }
+            }
+            Err(error) => println!("Error: {error}"),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// prelude
+//
+// the part of the library other crates can build on:
+// decoding Tracks, turning command lines into Commands,
+// the engine that plays them (a Conductor, rendered by hand)
+// and the Engine that plays it on a device of its own
+//
+// the modules under audio_processing and file_parsing are
+// private to the crate: nothing outside this (and the binary's
+// cli) reaches other crates, so they can follow the engine
+// as it changes
+//
+// for the same reason the enums here are #[non_exhaustive], and
+// what a Command carries is the crate's own: other crates get
+// Commands from a CmdProcessor (or Command::fade and ::quit) and
+// hand them on, so a command added, or an argument, breaks none
+// of them
+//
+pub use crate::file_parsing::{
+    aiff, wav,
+    decode_helpers::{AudioFile, ChannelMap, DecodeError, DecodeResult, Progress},
+};
+pub use crate::audio_processing::{
+    engine::Conductor,
+    headless::Engine,
+    runtime::RunOptions,
+    backend::Latency,
+    commands::{Command, CmdProcessor, CmdErr, CmdResult, CmdQueue, EngineState, HistoryEntry, Plan},
+    processes::Process,
+    blast_time::{sample_rate, blast_time::{clock, Position}},
+    effects::Effect,
+    events::{Event, EventBus},
+    offline,
+    schema,
+};
+pub use crate::config::Config;
@@ -1,4 +1,9 @@
 use proc_macro::{TokenStream, TokenTree, Ident, Span};
+use quote::{quote, format_ident};
+use syn::{
+    parse_macro_input, DeriveInput, Data, Fields, Type, Expr, Lit, LitStr,
+    GenericArgument, PathArguments,
+};
 
 #[proc_macro]
 pub fn var_args(var: TokenStream) -> TokenStream {
@@ -7,3 +12,293 @@ pub fn var_args(var: TokenStream) -> TokenStream {
 
     TokenStream::from(TokenTree::Ident(var_args))
 }
+
+// #[derive(CommandArgs)]
+//
+// generates flag parsing for a struct of a command's options,
+// one field per flag:
+//
+//   #[arg(short = "f", long = "feedback", parser = flag_unit, default = 0.35,
+//         kind = ArgType::Float, help = "how much comes back")]
+//   feedback: f32,
+//
+// parser names a CmdProcessor method taking (raw, cmd) and returning
+// a CmdResult of the field's type; an Option field is None unless
+// given, `switch` makes a bool flag that takes no value, and value
+// names what the flag takes in usage and errors (default: long).
+// `optional = <expr>` lets the value be left off (expr is taken
+// then, and whatever follows isn't eaten unless it parses), and
+// `repeats` collects every one given into a Vec field; any other
+// flag given twice is an error, rather than the last one winning.
+// kind is the schema's ArgType for the value (default: Text)
+//
+// the struct gets parse_flags (which reads flags until the words run
+// out), USAGE ("[-f <feedback>] ...") and HELP (each flag's ArgSpec,
+// for the schema; see schema::CommandSpec::flags)
+//
+#[proc_macro_derive(CommandArgs, attributes(arg))]
+pub fn derive_command_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match flags(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Flag {
+    field: syn::Ident,
+    ty: Type,
+    short: Option<String>,
+    long: String,
+    value: Option<String>,
+    parser: Option<syn::Ident>,
+    default: Option<Expr>,
+    help: String,
+    switch: bool,
+    optional: Option<Expr>,
+    repeats: bool,
+    kind: Option<Expr>,
+}
+
+fn flags(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => return Err(syn::Error::new_spanned(name, "CommandArgs needs named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(name, "CommandArgs only derives for structs")),
+    };
+
+    let mut flags = Vec::<Flag>::new();
+    for field in fields {
+        let ident = field.ident.clone().unwrap();
+        let mut flag = Flag {
+            long: ident.to_string(),
+            field: ident,
+            ty: field.ty.clone(),
+            short: None,
+            value: None,
+            parser: None,
+            default: None,
+            help: String::new(),
+            switch: false,
+            optional: None,
+            repeats: false,
+            kind: None,
+        };
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("arg")) {
+            attr.parse_nested_meta(|meta| {
+                let key = meta.path.get_ident().map(|i| i.to_string()).unwrap_or_default();
+                match key.as_str() {
+                    "switch" => flag.switch = true,
+                    "repeats" => flag.repeats = true,
+                    "optional" => flag.optional = Some(meta.value()?.parse::<Expr>()?),
+                    "kind" => flag.kind = Some(meta.value()?.parse::<Expr>()?),
+                    "short" => flag.short = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "long" => flag.long = meta.value()?.parse::<LitStr>()?.value(),
+                    "value" => flag.value = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "help" => flag.help = meta.value()?.parse::<LitStr>()?.value(),
+                    "parser" => flag.parser = Some(meta.value()?.parse::<syn::Ident>()?),
+                    "default" => flag.default = Some(meta.value()?.parse::<Expr>()?),
+                    _ => return Err(meta.error(
+                        "expected short, long, value, parser, default, optional, kind, help, switch or repeats",
+                    )),
+                }
+                Ok(())
+            })?;
+        }
+
+        if !flag.switch && flag.parser.is_none() {
+            return Err(syn::Error::new_spanned(&flag.field, "a flag that takes a value needs a parser"));
+        }
+        if !flag.switch && !flag.repeats && flag.default.is_none() && option_inner(&flag.ty).is_none() {
+            return Err(syn::Error::new_spanned(&flag.field, "needs a default, or to be an Option"));
+        }
+        if flag.optional.is_some() && (flag.default.is_some() || option_inner(&flag.ty).is_none()) {
+            return Err(syn::Error::new_spanned(&flag.field, "an optional value needs an Option, without a default"));
+        }
+        flags.push(flag);
+    }
+
+    let inits = flags.iter().map(|f| {
+        let (field, ty) = (&f.field, &f.ty);
+        let init = match (&f.default, f.switch, f.repeats) {
+            (Some(default), _, _) => quote!(#default),
+            (None, true, _) => quote!(false),
+            (None, false, true) => quote!(Vec::new()),
+            (None, false, false) => quote!(None),
+        };
+        let local = format_ident!("__{}", field);
+        let seen = format_ident!("__{}_seen", field);
+        match f.repeats {
+            true => quote!(let mut #local: #ty = #init;),
+            false => quote!(let mut #local: #ty = #init; let mut #seen = false;),
+        }
+    });
+
+    let arms = flags.iter().map(|f| {
+        let local = format_ident!("__{}", f.field);
+        let long = format!("--{}", f.long);
+        let pattern = match &f.short {
+            Some(short) => {
+                let short = format!("-{short}");
+                quote!(#short | #long)
+            }
+            None => quote!(#long),
+        };
+        let shown = f.short.as_ref().map(|s| format!("-{s}")).unwrap_or(long.clone());
+        let value = f.value.clone().unwrap_or(f.long.clone());
+
+        // so a second one doesn't quietly replace the first
+        let seen = format_ident!("__{}_seen", f.field);
+        let once = match f.repeats {
+            true => quote!(),
+            false => quote! {
+                if #seen {
+                    return Err(crate::audio_processing::commands::CmdErr::FlagTwice {
+                        flag: arg.to_owned(),
+                        cmd: cmd.to_string(),
+                    });
+                }
+                #seen = true;
+            },
+        };
+
+        if f.switch {
+            return quote!(#pattern => {
+                #once
+                #local = true;
+            });
+        }
+
+        let parser = f.parser.as_ref().unwrap();
+        if let Some(bare) = &f.optional {
+            return quote! {
+                #pattern => {
+                    #once
+                    let flag_cmd = format!("{} {}", cmd, #shown);
+                    let given = args.peek().and_then(|raw| processor.#parser(raw, &flag_cmd).ok());
+                    if given.is_some() {
+                        args.next();
+                    }
+                    #local = Some(given.unwrap_or(#bare));
+                }
+            };
+        }
+
+        let parsed = quote!(processor.#parser(raw, &flag_cmd)?);
+        let assign = match option_inner(&f.ty) {
+            _ if f.repeats => quote!(#local.push(#parsed);),
+            Some(_) if f.default.is_none() => quote!(#local = Some(#parsed);),
+            _ => quote!(#local = #parsed;),
+        };
+
+        quote! {
+            #pattern => {
+                #once
+                let flag_cmd = format!("{} {}", cmd, #shown);
+                let raw = args.next().ok_or(crate::audio_processing::commands::CmdErr::MissingArg {
+                    arg: #value.to_string(),
+                    cmd: flag_cmd.clone(),
+                })?;
+                #assign
+            }
+        }
+    });
+
+    let fields_out = flags.iter().map(|f| {
+        let field = &f.field;
+        let local = format_ident!("__{}", field);
+        quote!(#field: #local)
+    });
+
+    let usage = flags
+        .iter()
+        .map(|f| {
+            let shown = f.short.as_ref().map(|s| format!("-{s}")).unwrap_or(format!("--{}", f.long));
+            let value = f.value.as_ref().unwrap_or(&f.long);
+            match (f.switch, &f.optional, f.repeats) {
+                (true, _, _) => format!("[{shown}]"),
+                (false, Some(_), _) => format!("[{shown} [<{value}>]]"),
+                (false, None, true) => format!("[{shown} <{value}>]..."),
+                (false, None, false) => format!("[{shown} <{value}>]"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let help = flags.iter().map(|f| {
+        let names = f.short.iter().map(|s| format!("-{s}")).chain([format!("--{}", f.long)]);
+        let (name, help, repeats) = (&f.long, &f.help, f.repeats);
+        let kind = match &f.kind {
+            Some(kind) => quote!(#kind),
+            None => quote!(crate::audio_processing::schema::ArgType::Text),
+        };
+        quote! {
+            crate::audio_processing::schema::ArgSpec {
+                name: #name,
+                flags: &[#(#names),*],
+                ty: #kind,
+                required: false,
+                repeats: #repeats,
+                help: #help,
+            }
+        }
+    });
+
+    let usage = Lit::Str(LitStr::new(&usage, Span::call_site().into()));
+
+    // switches alone never need the processor
+    let unused = match flags.iter().all(|f| f.switch) {
+        true => quote!(let _ = &processor;),
+        false => quote!(),
+    };
+
+    Ok(quote! {
+        impl #name {
+            pub const USAGE: &'static str = #usage;
+            pub const HELP: &'static [crate::audio_processing::schema::ArgSpec] = &[#(#help),*];
+
+            // reads flags until the words run out
+            pub fn parse_flags<'a>(
+                processor: &mut crate::audio_processing::commands::CmdProcessor,
+                args: &mut impl Iterator<Item = &'a str>,
+                cmd: &str,
+            ) -> crate::audio_processing::commands::CmdResult<Self> {
+                #unused
+                let mut args = args.peekable();
+                #(#inits)*
+
+                while let Some(arg) = args.next() {
+                    match arg {
+                        #(#arms)*
+                        _ => return Err(crate::audio_processing::commands::CmdErr::InvalidArg {
+                            arg: arg.to_owned(),
+                            cmd: cmd.to_string(),
+                        }),
+                    }
+                }
+
+                Ok(Self { #(#fields_out),* })
+            }
+        }
+    })
+}
+
+// T, for Option<T>
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else { return None };
+    let last = path.path.segments.last()?;
+    if last.ident != "Option" {
+        return None;
+    }
+    match &last.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}